@@ -0,0 +1,95 @@
+//! `kivm bench`: timings of the VM dispatch loop (`--suite vm`) or of the
+//! lexer and parser (`--suite parser`) on the kinetix-kivm bench suite.
+
+pub use kinetix_kivm::bench_suite::{compile, Case, VM_SUITE};
+use kinetix_kicomp::ir::CompiledProgram;
+use kinetix_kivm::vm::VM;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Timings of one case over `iterations` runs (after one warm-up run). It
+/// prints as one `key=value` line so two builds can be compared with a
+/// plain diff:
+///
+/// ```text
+/// bench=vm/fib iterations=10 median_us=8123 min_us=8010 max_us=8741
+/// ```
+pub struct Measurement {
+    pub name: String,
+    pub iterations: u32,
+    pub median: Duration,
+    pub min: Duration,
+    pub max: Duration,
+}
+
+impl fmt::Display for Measurement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "bench={} iterations={} median_us={} min_us={} max_us={}",
+            self.name, self.iterations, self.median.as_micros(), self.min.as_micros(), self.max.as_micros()
+        )
+    }
+}
+
+/// Runs `program` on a fresh VM `iterations` times; only `VM::run` is timed.
+pub fn measure(name: &str, program: &CompiledProgram, iterations: u32) -> Result<Measurement, String> {
+    time(name, iterations, || {
+        let mut vm = VM::new(program.clone());
+        let start = Instant::now();
+        vm.run()?;
+        Ok(start.elapsed())
+    })
+}
+
+/// Calls `sample` once to warm up, then `iterations` times; each call
+/// returns the duration of the part it wants timed (so setup isn't counted).
+pub fn time(
+    name: &str,
+    iterations: u32,
+    mut sample: impl FnMut() -> Result<Duration, String>,
+) -> Result<Measurement, String> {
+    let iterations = iterations.max(1);
+    sample().map_err(|e| format!("{}: {}", name, e))?;
+    let mut samples = Vec::with_capacity(iterations as usize);
+    for _ in 0..iterations {
+        samples.push(sample().map_err(|e| format!("{}: {}", name, e))?);
+    }
+    samples.sort();
+    Ok(Measurement {
+        name: name.to_string(),
+        iterations,
+        median: samples[samples.len() / 2],
+        min: samples[0],
+        max: samples[samples.len() - 1],
+    })
+}
+
+/// Compiles and measures every case of `suite`, naming results `prefix/case`.
+pub fn run_suite(
+    prefix: &str,
+    suite: &[Case],
+    iterations: u32,
+    compile: impl Fn(&str) -> Result<CompiledProgram, String>,
+) -> Result<Vec<Measurement>, String> {
+    suite.iter()
+        .map(|case| {
+            let name = format!("{}/{}", prefix, case.name);
+            let program = compile(case.source).map_err(|e| format!("{}: {}", name, e))?;
+            measure(&name, &program, iterations)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_case_compiles_and_runs() {
+        let results = run_suite("vm", VM_SUITE, 1, compile).expect("suite runs");
+        assert_eq!(results.len(), VM_SUITE.len());
+        let line = results[0].to_string();
+        assert!(line.starts_with("bench=vm/fib iterations=1 median_us="), "{}", line);
+    }
+}
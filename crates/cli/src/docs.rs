@@ -0,0 +1,464 @@
+//! Offline documentation in `~/.kinetix/docs`: topic lookup, full-text
+//! search and a local server.
+
+use kinetix_kicomp::output;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+
+const SEARCH_LIMIT: usize = 50;
+
+pub struct DocPage {
+    /// Relative to the docs root, `/`-separated.
+    pub path: String,
+    pub title: String,
+    text: String,
+    /// `text` ASCII-lowercased, so byte offsets line up with `text`.
+    lower: String,
+    anchors: Vec<String>,
+}
+
+pub struct SearchHit<'a> {
+    pub page: &'a DocPage,
+    pub score: usize,
+    pub snippet: String,
+}
+
+/// Every `.html`/`.md`/`.txt` page under the docs root, indexed by its path,
+/// `<title>`, element ids (the anchors builtin and module pages use for each
+/// entry) and plain text.
+pub struct DocIndex {
+    root: PathBuf,
+    pages: Vec<DocPage>,
+}
+
+impl DocIndex {
+    pub fn build(root: &Path) -> Result<Self, String> {
+        let mut files = Vec::new();
+        collect_pages(root, &mut files).map_err(|e| format!("Cannot read documentation at {}: {}", root.display(), e))?;
+        files.sort();
+        let pages = files
+            .iter()
+            .filter_map(|file| {
+                let raw = std::fs::read_to_string(file).ok()?;
+                let rel = file.strip_prefix(root).ok()?;
+                let path = rel.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/");
+                Some(DocPage::parse(path, &raw))
+            })
+            .collect();
+        Ok(Self { root: root.to_path_buf(), pages })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The page (and anchor) documenting `topic`, e.g. `math.normalize`
+    /// -> `modules/math.html#normalize`. Tries, in order: a page named after
+    /// the whole topic, an anchor on the page named after its parent, any
+    /// anchor with the topic's name, and a page named after its last part.
+    pub fn resolve(&self, topic: &str) -> Option<String> {
+        let topic = topic.trim().replace("::", ".").to_ascii_lowercase();
+        let parts: Vec<&str> = topic.split(['.', '/']).filter(|p| !p.is_empty()).collect();
+        let (&name, parents) = parts.split_last()?;
+        let joined = parts.join("/");
+
+        if let Some(page) = self.pages.iter().find(|p| {
+            let stem = p.stem();
+            stem == topic || stem.ends_with(&format!("/{}", joined)) || stem == joined
+        }) {
+            return Some(page.path.clone());
+        }
+        let anchor_candidates = [topic.replace('.', "-"), name.to_string()];
+        if let Some(parent) = parents.last() {
+            for page in self.pages.iter().filter(|p| p.stem().rsplit('/').next() == Some(*parent)) {
+                if let Some(anchor) = anchor_candidates.iter().find(|a| page.has_anchor(a)) {
+                    return Some(format!("{}#{}", page.path, anchor));
+                }
+            }
+        }
+        // Any page carrying the anchor, preferring one whose path mentions the parent.
+        let mut with_anchor: Vec<(&DocPage, &String)> = self
+            .pages
+            .iter()
+            .filter_map(|p| anchor_candidates.iter().find(|a| p.has_anchor(a)).map(|a| (p, a)))
+            .collect();
+        with_anchor.sort_by_key(|(p, _)| !parents.iter().any(|parent| p.path.to_ascii_lowercase().contains(parent)));
+        if let Some((page, anchor)) = with_anchor.first() {
+            return Some(format!("{}#{}", page.path, anchor));
+        }
+        self.pages.iter().find(|p| p.stem().rsplit('/').next() == Some(name)).map(|p| p.path.clone())
+    }
+
+    /// Pages containing every word of `query`, best first. Title and
+    /// file-name matches outweigh body occurrences.
+    pub fn search(&self, query: &str) -> Vec<SearchHit<'_>> {
+        let terms: Vec<String> = query.split_whitespace().map(|t| t.to_ascii_lowercase()).collect();
+        if terms.is_empty() {
+            return Vec::new();
+        }
+        let mut hits: Vec<SearchHit> = self
+            .pages
+            .iter()
+            .filter_map(|page| {
+                let title = page.title.to_ascii_lowercase();
+                let stem = page.stem();
+                let mut score = 0;
+                for term in &terms {
+                    let in_body = page.lower.matches(term.as_str()).count();
+                    let in_title = title.contains(term.as_str());
+                    let in_name = stem.rsplit('/').next() == Some(term.as_str()) || page.has_anchor(term);
+                    if in_body == 0 && !in_title && !in_name {
+                        return None;
+                    }
+                    score += in_body + if in_title { 10 } else { 0 } + if in_name { 20 } else { 0 };
+                }
+                Some(SearchHit { page, score, snippet: page.snippet(&terms[0]) })
+            })
+            .collect();
+        hits.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.page.path.cmp(&b.page.path)));
+        hits.truncate(SEARCH_LIMIT);
+        hits
+    }
+}
+
+impl DocPage {
+    fn parse(path: String, raw: &str) -> Self {
+        let is_html = path.ends_with(".html") || path.ends_with(".htm");
+        let (title, text, anchors) = if is_html {
+            let title = between(raw, "<title>", "</title>").map(|t| decode_entities(t.trim())).unwrap_or_default();
+            (title, html_to_text(raw), html_ids(raw))
+        } else {
+            let title = raw.lines().find(|l| !l.trim().is_empty()).unwrap_or("").trim_start_matches('#').trim().to_string();
+            (title, raw.split_whitespace().collect::<Vec<_>>().join(" "), Vec::new())
+        };
+        let title = if title.is_empty() { path.clone() } else { title };
+        let lower = text.to_ascii_lowercase();
+        Self { path, title, text, lower, anchors }
+    }
+
+    /// Lowercased path without its extension.
+    fn stem(&self) -> String {
+        let lower = self.path.to_ascii_lowercase();
+        match lower.rfind('.') {
+            Some(dot) if dot > lower.rfind('/').map_or(0, |s| s + 1) => lower[..dot].to_string(),
+            _ => lower,
+        }
+    }
+
+    fn has_anchor(&self, name: &str) -> bool {
+        self.anchors.iter().any(|a| a.eq_ignore_ascii_case(name))
+    }
+
+    fn snippet(&self, term: &str) -> String {
+        let at = self.lower.find(term).unwrap_or(0);
+        let mut start = at.saturating_sub(60);
+        while !self.text.is_char_boundary(start) {
+            start -= 1;
+        }
+        let mut end = (at + term.len() + 100).min(self.text.len());
+        while !self.text.is_char_boundary(end) {
+            end += 1;
+        }
+        let mut snippet = self.text[start..end].trim().to_string();
+        if start > 0 {
+            snippet.insert_str(0, "...");
+        }
+        if end < self.text.len() {
+            snippet.push_str("...");
+        }
+        snippet
+    }
+}
+
+fn collect_pages(dir: &Path, out: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_pages(&path, out)?;
+        } else if matches!(path.extension().and_then(|e| e.to_str()), Some("html" | "htm" | "md" | "txt")) {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn between<'a>(s: &'a str, open: &str, close: &str) -> Option<&'a str> {
+    let lower = s.to_ascii_lowercase();
+    let start = lower.find(open)? + open.len();
+    let end = start + lower[start..].find(close)?;
+    Some(&s[start..end])
+}
+
+/// Visible text of an HTML page: tags dropped, `<script>`/`<style>`
+/// bodies skipped, entities decoded, whitespace collapsed.
+fn html_to_text(html: &str) -> String {
+    let lower = html.to_ascii_lowercase();
+    let mut out = String::new();
+    let mut i = 0;
+    while i < html.len() {
+        let Some(lt) = html[i..].find('<') else {
+            out.push_str(&html[i..]);
+            break;
+        };
+        out.push_str(&html[i..i + lt]);
+        out.push(' ');
+        i += lt;
+        let skip_to = ["script", "style"].iter().find(|tag| lower[i + 1..].starts_with(*tag)).and_then(|tag| {
+            let close = format!("</{}", tag);
+            lower[i..].find(&close).map(|end| i + end + close.len())
+        });
+        if let Some(after) = skip_to {
+            i = after;
+        }
+        match html[i..].find('>') {
+            Some(gt) => i += gt + 1,
+            None => break,
+        }
+    }
+    decode_entities(&out).split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn html_ids(html: &str) -> Vec<String> {
+    let mut ids = Vec::new();
+    for quote in ['"', '\''] {
+        let marker = format!("id={}", quote);
+        let mut rest = html;
+        while let Some(at) = rest.find(&marker) {
+            // Skip attributes merely ending in "id", like data-id.
+            let boundary = rest[..at].chars().next_back().is_none_or(|c| c.is_whitespace());
+            rest = &rest[at + marker.len()..];
+            if let Some(end) = rest.find(quote) {
+                if boundary {
+                    ids.push(rest[..end].to_string());
+                }
+                rest = &rest[end..];
+            }
+        }
+    }
+    ids
+}
+
+fn decode_entities(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// Decodes `%XX` escapes and, in query strings, `+` as space.
+fn percent_decode(s: &str, plus_as_space: bool) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' if i + 2 < bytes.len() => {
+                let hex = |b: u8| (b as char).to_digit(16);
+                match (hex(bytes[i + 1]), hex(bytes[i + 2])) {
+                    (Some(high), Some(low)) => {
+                        out.push((high * 16 + low) as u8);
+                        i += 3;
+                        continue;
+                    }
+                    _ => out.push(b'%'),
+                }
+            }
+            b'+' if plus_as_space => out.push(b' '),
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+const SEARCH_BOX: &str = r#"<form action="/search" style="position:fixed;top:8px;right:8px;z-index:9999;margin:0"><input name="q" placeholder="Search docs" style="padding:4px 8px;font:14px sans-serif"></form>"#;
+
+/// Serves the docs on `127.0.0.1:port` until interrupted, with a
+/// `/search?q=` page and a search box injected into every HTML page. Only
+/// loopback is bound; the pages are local files and there is nothing to
+/// share.
+pub fn serve(index: &DocIndex, port: u16, open: Option<&str>) -> Result<(), String> {
+    let listener = TcpListener::bind(("127.0.0.1", port)).map_err(|e| format!("Cannot listen on 127.0.0.1:{}: {}", port, e))?;
+    let addr = listener.local_addr().map_err(|e| e.to_string())?;
+    let base = format!("http://{}", addr);
+    println!("Serving {} pages from {} at {} (Ctrl+C to stop)", index.pages.len(), index.root.display(), base);
+    if let Some(page) = open {
+        let url = format!("{}/{}", base, page);
+        // Headless machines have no browser; the server is still useful.
+        if let Err(e) = super::open_in_browser(&url) {
+            output::warning(format!("{} (open {} manually)", e, url));
+        }
+    }
+    for stream in listener.incoming() {
+        let Ok(mut stream) = stream else { continue };
+        let _ = stream.set_read_timeout(Some(Duration::from_secs(5)));
+        if let Err(e) = handle(index, &mut stream) {
+            output::note(format!("docs server: {}", e));
+        }
+    }
+    Ok(())
+}
+
+fn handle(index: &DocIndex, stream: &mut TcpStream) -> std::io::Result<()> {
+    let mut request_line = String::new();
+    BufReader::new(&mut *stream).read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return respond(stream, "400 Bad Request", "text/plain; charset=utf-8", b"bad request");
+    };
+    if method != "GET" {
+        return respond(stream, "405 Method Not Allowed", "text/plain; charset=utf-8", b"method not allowed");
+    }
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let path = percent_decode(path, false);
+
+    if path == "/search" {
+        let q = query
+            .split('&')
+            .find_map(|kv| kv.strip_prefix("q="))
+            .map(|q| percent_decode(q, true))
+            .unwrap_or_default();
+        return respond(stream, "200 OK", "text/html; charset=utf-8", search_page(index, &q).as_bytes());
+    }
+    let rel = path.trim_start_matches('/');
+    let rel = if rel.is_empty() || rel.ends_with('/') { format!("{}index.html", rel) } else { rel.to_string() };
+    match read_doc_file(index.root(), &rel) {
+        Some(bytes) => {
+            let content_type = content_type(&rel);
+            if content_type.starts_with("text/html") {
+                let html = String::from_utf8_lossy(&bytes);
+                let html = match html.rfind("</body>") {
+                    Some(at) => format!("{}{}{}", &html[..at], SEARCH_BOX, &html[at..]),
+                    None => format!("{}{}", html, SEARCH_BOX),
+                };
+                respond(stream, "200 OK", content_type, html.as_bytes())
+            } else {
+                respond(stream, "200 OK", content_type, &bytes)
+            }
+        }
+        None if rel == "index.html" => respond(stream, "200 OK", "text/html; charset=utf-8", search_page(index, "").as_bytes()),
+        None => respond(stream, "404 Not Found", "text/plain; charset=utf-8", b"not found"),
+    }
+}
+
+/// Reads `rel` under `root`, refusing anything that would escape it.
+fn read_doc_file(root: &Path, rel: &str) -> Option<Vec<u8>> {
+    let rel = Path::new(rel);
+    if !rel.components().all(|c| matches!(c, Component::Normal(_))) {
+        return None;
+    }
+    let full = root.join(rel).canonicalize().ok()?;
+    if !full.starts_with(root.canonicalize().ok()?) || !full.is_file() {
+        return None;
+    }
+    std::fs::read(full).ok()
+}
+
+fn content_type(path: &str) -> &'static str {
+    match path.rsplit('.').next().map(|e| e.to_ascii_lowercase()).as_deref() {
+        Some("html" | "htm") => "text/html; charset=utf-8",
+        Some("css") => "text/css; charset=utf-8",
+        Some("js") => "text/javascript; charset=utf-8",
+        Some("json") => "application/json",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("ico") => "image/x-icon",
+        Some("woff2") => "font/woff2",
+        Some("md" | "txt") => "text/plain; charset=utf-8",
+        _ => "application/octet-stream",
+    }
+}
+
+fn search_page(index: &DocIndex, query: &str) -> String {
+    let mut body = format!(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>Search: {q}</title></head><body style=\"font-family:sans-serif;max-width:50em;margin:2em auto\">\
+         <h1>Kinetix documentation</h1><form action=\"/search\"><input name=\"q\" value=\"{q}\" size=\"40\" autofocus> <button>Search</button></form>",
+        q = escape_html(query)
+    );
+    if !query.trim().is_empty() {
+        let hits = index.search(query);
+        body.push_str(&format!("<p>{} result(s)</p><ol>", hits.len()));
+        for hit in hits {
+            body.push_str(&format!(
+                "<li><a href=\"/{}\">{}</a> <small>{}</small><br>{}</li>",
+                escape_html(&hit.page.path),
+                escape_html(&hit.page.title),
+                escape_html(&hit.page.path),
+                escape_html(&hit.snippet)
+            ));
+        }
+        body.push_str("</ol>");
+    }
+    body.push_str("</body></html>");
+    body
+}
+
+fn respond(stream: &mut TcpStream, status: &str, content_type: &str, body: &[u8]) -> std::io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    )?;
+    stream.write_all(body)?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn topics_resolve_to_pages_and_anchors_and_search_ranks_titles() {
+        let root = std::env::temp_dir().join(format!("kinetix-docs-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("modules")).unwrap();
+        std::fs::write(
+            root.join("modules/math.html"),
+            "<html><head><title>math module</title><style>.x{}</style></head><body>\
+             <h2 id=\"normalize\">normalize</h2><p>Scales a vector to length 1.</p>\
+             <h2 id=\"lerp\">lerp</h2><p>Linear interpolation &amp; blending.</p></body></html>",
+        )
+        .unwrap();
+        std::fs::write(root.join("strings.md"), "# Strings\n\nUse trim to drop whitespace around a vector of chars.\n").unwrap();
+        std::fs::write(root.join("index.html"), "<title>Kinetix</title><p>Welcome</p>").unwrap();
+
+        let index = DocIndex::build(&root).unwrap();
+        assert_eq!(index.resolve("math.normalize").as_deref(), Some("modules/math.html#normalize"));
+        assert_eq!(index.resolve("math").as_deref(), Some("modules/math.html"));
+        assert_eq!(index.resolve("lerp").as_deref(), Some("modules/math.html#lerp"));
+        assert_eq!(index.resolve("strings").as_deref(), Some("strings.md"));
+        assert_eq!(index.resolve("nope.missing"), None);
+
+        let hits = index.search("vector");
+        assert_eq!(hits.iter().map(|h| h.page.path.as_str()).collect::<Vec<_>>(), ["modules/math.html", "strings.md"]);
+        assert!(hits[0].snippet.contains("Scales a vector"), "{}", hits[0].snippet);
+        assert!(index.search("interpolation & blending").len() == 1, "entities are decoded");
+        assert!(index.search(".x{}").is_empty(), "style bodies aren't indexed");
+
+        assert!(read_doc_file(&root, "../etc/passwd").is_none());
+        assert!(read_doc_file(&root, "modules/math.html").is_some());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn percent_escapes_decode_and_multibyte_input_is_left_alone() {
+        assert_eq!(percent_decode("/math%2Enormalize", false), "/math.normalize");
+        assert_eq!(percent_decode("a+b%20c", true), "a b c");
+        assert_eq!(percent_decode("caf%C3%A9", false), "café");
+        assert_eq!(percent_decode("/%aé", false), "/%aé");
+        assert_eq!(percent_decode("/%é", false), "/%é");
+        assert_eq!(percent_decode("100%", false), "100%");
+    }
+}
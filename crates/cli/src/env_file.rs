@@ -0,0 +1,110 @@
+//! `--env-file` and `--clean-env` for `kivm run` and `kivm exec`: pin down
+//! exactly what `env.get` and programs run with `system.exec` see.
+
+use std::path::Path;
+
+/// The variables `text` sets, in order: one `NAME=value` per line,
+/// optionally prefixed by `export `, skipping blank lines and lines starting
+/// with `#`. A value in single quotes is literal; in double quotes `\n`,
+/// `\t`, `\"`, `\\` and `\$` are escapes; an unquoted value ends at ` #` and
+/// is trimmed.
+pub fn parse(text: &str) -> Result<Vec<(String, String)>, String> {
+    let mut vars = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (name, value) = line.split_once('=').ok_or_else(|| format!("line {}: expected NAME=value", i + 1))?;
+        let name = name.trim();
+        let valid = name.chars().next().is_some_and(|c| c == '_' || c.is_ascii_alphabetic())
+            && name.chars().all(|c| c == '_' || c.is_ascii_alphanumeric());
+        if !valid {
+            return Err(format!("line {}: invalid variable name '{}'", i + 1, name));
+        }
+        let value = parse_value(value.trim()).map_err(|e| format!("line {}: {}", i + 1, e))?;
+        vars.push((name.to_string(), value));
+    }
+    Ok(vars)
+}
+
+fn parse_value(raw: &str) -> Result<String, String> {
+    let Some(quote) = raw.chars().next().filter(|c| matches!(c, '\'' | '"')) else {
+        let end = raw.find(" #").unwrap_or(raw.len());
+        return Ok(raw[..end].trim_end().to_string());
+    };
+    let mut value = String::new();
+    let mut chars = raw[1..].chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c == quote => {
+                let rest = chars.as_str().trim_start();
+                if !rest.is_empty() && !rest.starts_with('#') {
+                    return Err(format!("unexpected '{}' after the closing quote", rest));
+                }
+                return Ok(value);
+            }
+            '\\' if quote == '"' => match chars.next() {
+                Some('n') => value.push('\n'),
+                Some('t') => value.push('\t'),
+                Some(escaped @ ('"' | '\\' | '$')) => value.push(escaped),
+                Some(other) => {
+                    value.push('\\');
+                    value.push(other);
+                }
+                None => break,
+            },
+            c => value.push(c),
+        }
+    }
+    Err(format!("unterminated {} quote", if quote == '"' { "double" } else { "single" }))
+}
+
+/// Clears kivm's environment when `clean` is set, so nothing is inherited,
+/// then sets the variables of `env_file`. Called first thing in `run`, while
+/// kivm is still single-threaded.
+pub fn apply(env_file: Option<&Path>, clean: bool) -> Result<(), String> {
+    let vars = match env_file {
+        Some(path) => {
+            let text = std::fs::read_to_string(path).map_err(|e| format!("Error reading {}: {}", path.display(), e))?;
+            parse(&text).map_err(|e| format!("{}: {}", path.display(), e))?
+        }
+        None => Vec::new(),
+    };
+    if clean {
+        for (name, _) in std::env::vars_os() {
+            // SAFETY: `apply` runs before `run` starts any thread (plugins,
+            // the --otlp exporter), so nothing reads the environment meanwhile.
+            unsafe { std::env::remove_var(name) };
+        }
+    }
+    for (name, value) in vars {
+        // SAFETY: as above, no other thread exists yet.
+        unsafe { std::env::set_var(name, value) };
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn env_files_are_parsed_like_dotenv() {
+        let text = "# settings\nAPI_URL=https://example.com/v1  # staging\n\nexport TOKEN='a b $x'\nGREETING=\"hi\\n\\\"you\\\"\"\nEMPTY=\n";
+        let vars = parse(text).unwrap();
+        let expected = [
+            ("API_URL", "https://example.com/v1"),
+            ("TOKEN", "a b $x"),
+            ("GREETING", "hi\n\"you\""),
+            ("EMPTY", ""),
+        ];
+        assert_eq!(vars, expected.map(|(n, v)| (n.to_string(), v.to_string())));
+
+        assert_eq!(parse("A=1\nno value\n").unwrap_err(), "line 2: expected NAME=value");
+        assert_eq!(parse("1A=x").unwrap_err(), "line 1: invalid variable name '1A'");
+        assert_eq!(parse("A=\"open").unwrap_err(), "line 1: unterminated double quote");
+        assert_eq!(parse("A='x' y").unwrap_err(), "line 1: unexpected 'y' after the closing quote");
+    }
+}
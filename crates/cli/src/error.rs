@@ -0,0 +1,126 @@
+//! Why a `kivm` command failed, and the exit code that tells scripts and CI
+//! which kind of failure it was.
+
+use kinetix_kicomp::output;
+use kinetix_language::diagnostic::Diagnostic;
+use std::fmt;
+use std::path::PathBuf;
+
+#[derive(Debug)]
+pub enum CliError {
+    /// Bad arguments, or the wrong kind of file for the command.
+    Usage(String),
+    /// A file or stream could not be read or written.
+    Io(String),
+    /// The program did not build: parse, type, borrow, sandbox or codegen
+    /// errors, or an `.exki` that does not load.
+    Compile(String),
+    /// A compiler pass rejected `file`.
+    Diagnostics { file: PathBuf, pass: &'static str, diagnostics: Vec<Diagnostic> },
+    /// The program built but failed while running.
+    Runtime(String),
+    /// kivm itself misbehaved.
+    Internal(String),
+}
+
+impl CliError {
+    /// Process exit code: 1 runtime (as for any failed script), 2 usage (as
+    /// clap reports bad arguments), 3 compile, 4 I/O, 5 internal.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CliError::Runtime(_) => 1,
+            CliError::Usage(_) => 2,
+            CliError::Compile(_) | CliError::Diagnostics { .. } => 3,
+            CliError::Io(_) => 4,
+            CliError::Internal(_) => 5,
+        }
+    }
+
+    /// The error for `--error-format json`: one JSON object per diagnostic
+    /// (see `output::json_diagnostic`), or a single one with no location or
+    /// code for errors that aren't about the source.
+    pub fn json_lines(&self) -> Vec<String> {
+        match self {
+            CliError::Diagnostics { file, diagnostics, .. } => {
+                let file = file.display().to_string();
+                diagnostics.iter().map(|d| output::json_diagnostic(&file, d)).collect()
+            }
+            other => vec![output::json_error(other)],
+        }
+    }
+}
+
+/// An import `module_graph::load` couldn't follow, reported against the
+/// importing file the diagnostic names.
+impl From<Diagnostic> for CliError {
+    fn from(d: Diagnostic) -> Self {
+        let file = PathBuf::from(d.file.clone().unwrap_or_default());
+        CliError::Diagnostics { file, pass: "Linker", diagnostics: vec![d] }
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CliError::Usage(msg) | CliError::Io(msg) | CliError::Compile(msg) => write!(f, "{}", msg),
+            CliError::Diagnostics { file, pass, diagnostics } => {
+                writeln!(f, "{}:", file.display())?;
+                for d in diagnostics {
+                    match (&d.file, d.span()) {
+                        (Some(origin), Some(span)) => {
+                            let at = if span.column > 0 { span.to_string() } else { span.line.to_string() };
+                            writeln!(f, "- Line {} of {}: {}", at, origin, d.message)?;
+                        }
+                        _ => writeln!(f, "- {}", d)?,
+                    }
+                }
+                write!(f, "{} error(s) in {}", diagnostics.len(), pass)
+            }
+            CliError::Runtime(msg) => write!(f, "Runtime error: {}", msg),
+            CliError::Internal(msg) => write!(f, "Internal error: {}", msg),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_kind_has_its_own_exit_code() {
+        let errors = [
+            CliError::Runtime("x".into()),
+            CliError::Usage("x".into()),
+            CliError::Compile("x".into()),
+            CliError::Io("x".into()),
+            CliError::Internal("x".into()),
+        ];
+        let codes: Vec<i32> = errors.iter().map(CliError::exit_code).collect();
+        assert_eq!(codes, [1, 2, 3, 4, 5]);
+        assert_eq!(CliError::Runtime("Division by zero".into()).to_string(), "Runtime error: Division by zero");
+        assert_eq!(CliError::Io("Error reading a.kix: not found".into()).to_string(), "Error reading a.kix: not found");
+
+        use kinetix_language::diagnostic::{codes, Diagnostic};
+        let rejected = CliError::Diagnostics {
+            file: "main.kix".into(),
+            pass: "Type Checker",
+            diagnostics: vec![
+                Diagnostic::error(codes::TYPE, "Type mismatch: string vs int").at_line(2),
+                Diagnostic::error(codes::TYPE, "Type mismatch: int vs bool").at_line(4).in_file("lib.kix"),
+            ],
+        };
+        assert_eq!(rejected.exit_code(), 3);
+        assert_eq!(
+            rejected.to_string(),
+            "main.kix:\n- Line 2: Type mismatch: string vs int\n- Line 4 of lib.kix: Type mismatch: int vs bool\n2 error(s) in Type Checker"
+        );
+        assert_eq!(rejected.json_lines(), [
+            r#"{"code":"E0006","column":null,"file":"main.kix","fixes":[],"help":null,"line":2,"message":"Type mismatch: string vs int","notes":[],"severity":"error"}"#,
+            r#"{"code":"E0006","column":null,"file":"lib.kix","fixes":[],"help":null,"line":4,"message":"Type mismatch: int vs bool","notes":[],"severity":"error"}"#,
+        ]);
+        assert_eq!(
+            CliError::Io("Error reading a.kix: not found".into()).json_lines(),
+            [r#"{"code":null,"column":null,"file":null,"fixes":[],"help":null,"line":null,"message":"Error reading a.kix: not found","notes":[],"severity":"error"}"#]
+        );
+    }
+}
@@ -0,0 +1,169 @@
+//! `#include` preprocessing for `kivm compile` and `kivm test`.
+
+use kinetix_language::ast::Statement;
+use kinetix_language::diagnostic::{codes, Diagnostic};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// File and 1-based line of each line of an expanded source.
+type Origins = Vec<(PathBuf, usize)>;
+
+/// The origins of each expanded source, by the root file it was expanded
+/// for. Read back by `origin` when a diagnostic is printed.
+static ORIGINS: Mutex<Vec<(PathBuf, Origins)>> = Mutex::new(Vec::new());
+
+#[derive(Default)]
+struct Expander {
+    out: String,
+    /// Where each line of `out` came from.
+    origins: Origins,
+    /// Files being expanded, outermost first, with the line of the
+    /// `#include` that entered the next one.
+    stack: Vec<(PathBuf, PathBuf, usize)>,
+    /// Files that declared `#pragma once` and have been expanded.
+    once: HashSet<PathBuf>,
+}
+
+/// Expands the `#include`s of `source`, the contents of `file`: each
+/// `#include "path"` line is replaced by the lines of that file (relative to
+/// the including file), recursively. A file containing `#pragma once` is
+/// spliced in at most once; including a file that is already being expanded
+/// is an error naming the whole chain. Where each line came from is kept for
+/// `origin`.
+pub fn preprocess(source: &str, file: &Path) -> Result<String, String> {
+    let mut expander = Expander::default();
+    expander.expand(source, file)?;
+    let mut origins = ORIGINS.lock().unwrap_or_else(|e| e.into_inner());
+    origins.retain(|(root, _)| root != file);
+    origins.push((file.to_path_buf(), expander.origins));
+    Ok(expander.out)
+}
+
+/// The file and line that line `line` of `file`'s expanded source came
+/// from: an included file, or `file` itself (further down than in the
+/// expanded source when something was included above it).
+pub fn origin(file: &Path, line: usize) -> Option<(PathBuf, usize)> {
+    let origins = ORIGINS.lock().unwrap_or_else(|e| e.into_inner());
+    let (_, lines) = origins.iter().find(|(root, _)| root == file)?;
+    lines.get(line.checked_sub(1)?).cloned()
+}
+
+/// Errors for the top-level functions, types and globals of `file`'s
+/// expanded source defined again in another file it includes (or that
+/// includes it): included files share the includer's globals, so the later
+/// definition would silently replace the earlier one.
+pub fn duplicate_definitions(file: &Path, statements: &[Statement<'_>]) -> Vec<Diagnostic> {
+    let mut defined: Vec<(&str, PathBuf, usize)> = Vec::new();
+    let mut errors = Vec::new();
+    for stmt in statements {
+        let (name, line) = match stmt {
+            Statement::Function { name, line, .. } | Statement::Class { name, line, .. } | Statement::Struct { name, line, .. }
+            | Statement::Enum { name, line, .. } | Statement::Trait { name, line, .. } | Statement::Let { name, line, .. } => (*name, *line),
+            _ => continue,
+        };
+        let (origin, origin_line) = origin(file, line).unwrap_or_else(|| (file.to_path_buf(), line));
+        if let Some((_, first, first_line)) = defined.iter().find(|(n, path, _)| *n == name && *path != origin) {
+            let message = format!("'{}' is already defined in {}, line {}; included files share one namespace", name, first.display(), first_line);
+            errors.push(Diagnostic::error(codes::SYMBOL, message).at(stmt.span()));
+        } else {
+            defined.push((name, origin, origin_line));
+        }
+    }
+    errors
+}
+
+/// The key a file is recognized by, however it was spelled.
+fn identity(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+impl Expander {
+    fn expand(&mut self, source: &str, file: &Path) -> Result<(), String> {
+        let id = identity(file);
+        if source.lines().any(|l| l.trim() == "#pragma once") {
+            self.once.insert(id.clone());
+        }
+        self.stack.push((id, file.to_path_buf(), 0));
+        for (i, line) in source.lines().enumerate() {
+            let trimmed = line.trim();
+            if trimmed.starts_with("#include") {
+                self.stack.last_mut().expect("pushed above").2 = i + 1;
+                self.include(trimmed, file, i + 1)?;
+                continue;
+            }
+            if trimmed != "#pragma once" {
+                self.out.push_str(line);
+            }
+            self.out.push('\n');
+            self.origins.push((file.to_path_buf(), i + 1));
+        }
+        self.stack.pop();
+        Ok(())
+    }
+
+    /// Expands the `#include` directive on line `line` of `file`.
+    fn include(&mut self, directive: &str, file: &Path, line: usize) -> Result<(), String> {
+        // Parse path: #include "path/to/file.kix"
+        let Some(path_str) = directive.split_whitespace().nth(1) else {
+            return Err(format!("{}:{}: Invalid include syntax", file.display(), line));
+        };
+        let include_path = file.parent().unwrap_or(Path::new(".")).join(path_str.trim_matches('"'));
+        if !include_path.exists() {
+            return Err(format!("{}:{}: Include not found: {}", file.display(), line, include_path.display()));
+        }
+        let id = identity(&include_path);
+        if self.once.contains(&id) {
+            return Ok(());
+        }
+        if let Some(start) = self.stack.iter().position(|(open, _, _)| *open == id) {
+            let chain: Vec<String> = self.stack[start..].iter()
+                .map(|(_, path, at)| format!("{}:{}", path.display(), at))
+                .chain(std::iter::once(include_path.display().to_string()))
+                .collect();
+            return Err(format!("Circular include: {} (add `#pragma once` to include a file only once)", chain.join(" -> ")));
+        }
+        let included_source = fs::read_to_string(&include_path)
+            .map_err(|e| format!("{}:{}: Failed to read include {}: {}", file.display(), line, include_path.display(), e))?;
+        self.expand(&included_source, &include_path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn includes_are_expanded_once_with_their_origin() {
+        let dir = std::env::temp_dir().join(format!("kinetix-include-{}", std::process::id()));
+        fs::create_dir_all(dir.join("lib")).unwrap();
+        fs::write(dir.join("lib/util.kix"), "#pragma once\nfn util() { }\n").unwrap();
+        fs::write(dir.join("lib/shapes.kix"), "#include \"util.kix\"\nfn area() { }\n").unwrap();
+        let main = dir.join("main.kix");
+        let source = "#include \"lib/shapes.kix\"\n#include \"lib/util.kix\"\nutil()\n";
+
+        let expanded = preprocess(source, &main).unwrap();
+        assert_eq!(expanded, "\nfn util() { }\nfn area() { }\nutil()\n");
+        assert_eq!(origin(&main, 3), Some((dir.join("lib/shapes.kix"), 2)));
+        assert_eq!(origin(&main, 4), Some((main.clone(), 3)));
+
+        let source = preprocess("#include \"lib/util.kix\"\nfn util() { }\nfn main_only() { }\n", &main).unwrap();
+        let arena = bumpalo::Bump::new();
+        let program = kinetix_language::parser::Parser::new(kinetix_language::lexer::Lexer::new(&source), &arena).parse_program();
+        assert_eq!(
+            duplicate_definitions(&main, &program.statements).iter().map(ToString::to_string).collect::<Vec<_>>(),
+            [format!("Line 3:1: 'util' is already defined in {}, line 2; included files share one namespace", dir.join("lib/util.kix").display())]
+        );
+
+        fs::write(dir.join("lib/a.kix"), "let a = 1\n#include \"b.kix\"\n").unwrap();
+        fs::write(dir.join("lib/b.kix"), "#include \"a.kix\"\n").unwrap();
+        let err = preprocess("#include \"lib/a.kix\"\n", &main).unwrap_err();
+        let a = dir.join("lib/a.kix").display().to_string();
+        assert_eq!(err, format!(
+            "Circular include: {}:2 -> {}:1 -> {} (add `#pragma once` to include a file only once)",
+            a, dir.join("lib/b.kix").display(), a
+        ));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,166 @@
+//! Background jobs of `kivm shell`: commands ending in `&`, and the `jobs`,
+//! `fg`, `bg` and `kill` builtins that manage them.
+
+use std::process::{Child, Command, ExitStatus, Stdio};
+
+struct Job {
+    id: usize,
+    command: String,
+    child: Child,
+}
+
+/// A job is named by its number, with or without `%`; the most recent one
+/// when no number is given.
+#[derive(Default)]
+pub struct JobTable {
+    jobs: Vec<Job>,
+}
+
+impl JobTable {
+    /// Spawns `command` (typed as `line`, without the `&`) in the background; returns
+    /// the `[id] pid` line to print. Its stdin is detached so it doesn't
+    /// compete with the prompt.
+    pub fn spawn(&mut self, mut command: Command, line: &str) -> Result<String, String> {
+        let child = command.stdin(Stdio::null()).spawn().map_err(|e| e.to_string())?;
+        let id = self.jobs.last().map_or(1, |job| job.id + 1);
+        let started = format!("[{}] {}", id, child.id());
+        self.jobs.push(Job { id, command: line.to_string(), child });
+        Ok(started)
+    }
+
+    /// Removes the jobs that have finished, returning a `Done` line for each.
+    pub fn reap(&mut self) -> Vec<String> {
+        let mut done = Vec::new();
+        self.jobs.retain_mut(|job| match job.child.try_wait() {
+            Ok(Some(status)) => {
+                done.push(format!("[{}]  {}  {}", job.id, describe(status), job.command));
+                false
+            }
+            Ok(None) => true,
+            Err(_) => false,
+        });
+        done
+    }
+
+    /// One line per job still running, after reaping the finished ones.
+    pub fn list(&mut self) -> Vec<String> {
+        let mut lines = self.reap();
+        lines.extend(self.jobs.iter().map(|job| format!("[{}]  Running  {}", job.id, job.command)));
+        lines
+    }
+
+    /// Waits for a job in the foreground; returns its command and status.
+    pub fn fg(&mut self, spec: Option<&str>) -> Result<(String, ExitStatus), String> {
+        let index = self.find("fg", spec)?;
+        let mut job = self.jobs.remove(index);
+        continue_process(&job.child)?;
+        let status = job.child.wait().map_err(|e| format!("fg: {}", e))?;
+        Ok((job.command, status))
+    }
+
+    /// Resumes a stopped job in the background.
+    pub fn bg(&mut self, spec: Option<&str>) -> Result<String, String> {
+        let job = &self.jobs[self.find("bg", spec)?];
+        continue_process(&job.child)?;
+        Ok(format!("[{}] {} &", job.id, job.command))
+    }
+
+    /// Kills a job and waits for it to exit.
+    pub fn kill(&mut self, spec: Option<&str>) -> Result<String, String> {
+        let index = self.find("kill", spec)?;
+        let mut job = self.jobs.remove(index);
+        job.child.kill().map_err(|e| format!("kill: {}", e))?;
+        let _ = job.child.wait();
+        Ok(format!("[{}]  Killed  {}", job.id, job.command))
+    }
+
+    /// Kills every job still running, when the shell exits; returns how many
+    /// there were.
+    pub fn kill_all(&mut self) -> usize {
+        self.reap();
+        let count = self.jobs.len();
+        for mut job in self.jobs.drain(..) {
+            let _ = job.child.kill();
+            let _ = job.child.wait();
+        }
+        count
+    }
+
+    fn find(&self, builtin: &str, spec: Option<&str>) -> Result<usize, String> {
+        let Some(spec) = spec else {
+            return self.jobs.len().checked_sub(1).ok_or_else(|| format!("{}: no current job", builtin));
+        };
+        let id: usize = spec.trim_start_matches('%').parse()
+            .map_err(|_| format!("{}: {}: not a job number", builtin, spec))?;
+        self.jobs.iter().position(|job| job.id == id).ok_or_else(|| format!("{}: %{}: no such job", builtin, id))
+    }
+}
+
+/// `Done`, `Exit 3` or, on Unix, the signal that ended the process.
+fn describe(status: ExitStatus) -> String {
+    if status.success() {
+        return "Done".to_string();
+    }
+    if let Some(code) = status.code() {
+        return format!("Exit {}", code);
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return format!("Signal {}", signal);
+        }
+    }
+    "Terminated".to_string()
+}
+
+/// Sends `SIGCONT`, so a job stopped from outside the shell runs again.
+#[cfg(unix)]
+fn continue_process(child: &Child) -> Result<(), String> {
+    // SAFETY: `kill` only reads its arguments; the pid is our own child,
+    // which has not been waited for yet.
+    if unsafe { libc::kill(child.id() as libc::pid_t, libc::SIGCONT) } == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error().to_string())
+    }
+}
+
+/// Processes can't be stopped from outside on this platform, so there is
+/// nothing to resume.
+#[cfg(not(unix))]
+fn continue_process(_child: &Child) -> Result<(), String> {
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn sh(script: &str) -> Command {
+        let mut command = Command::new("sh");
+        command.args(["-c", script]);
+        command
+    }
+
+    #[test]
+    fn jobs_are_reaped_waited_for_and_killed() {
+        let mut jobs = JobTable::default();
+        assert!(jobs.spawn(sh("exit 3"), "exit 3").unwrap().starts_with("[1] "));
+        assert!(jobs.spawn(sh("sleep 30"), "sleep 30").unwrap().starts_with("[2] "));
+        assert!(jobs.spawn(sh("true"), "true").unwrap().starts_with("[3] "));
+
+        let (command, status) = jobs.fg(Some("%3")).unwrap();
+        assert_eq!((command.as_str(), status.success()), ("true", true));
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        assert_eq!(jobs.list(), ["[1]  Exit 3  exit 3", "[2]  Running  sleep 30"]);
+
+        assert_eq!(jobs.bg(None), Ok("[2] sleep 30 &".to_string()));
+        assert_eq!(jobs.kill(Some("1")), Err("kill: %1: no such job".to_string()));
+        assert_eq!(jobs.kill(Some("2")), Ok("[2]  Killed  sleep 30".to_string()));
+        assert_eq!(jobs.fg(None).unwrap_err(), "fg: no current job");
+
+        jobs.spawn(sh("sleep 30"), "sleep 30").unwrap();
+        assert_eq!(jobs.kill_all(), 1);
+    }
+}
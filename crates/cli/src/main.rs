@@ -3,11 +3,32 @@
 
 use clap::Parser as ClapParser;
 use kinetix_kicomp::exn;
+use kinetix_kicomp::output::{self, paint};
+use kinetix_kicomp::plugin::{self, Stage, Unit};
 use kinetix_kivm::vm::VM;
+use kinetix_language::diagnostic::{codes, Diagnostic, Severity};
+use kinetix_kivm::builtins::modules::io::Buffering;
 use std::fs;
 use std::io::{Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
 use bumpalo::Bump;
+use error::CliError;
+
+mod bench;
+mod docs;
+mod env_file;
+mod error;
+mod include;
+mod jobs;
+mod package;
+mod permissions;
+mod plugins;
+mod shell_config;
+mod shell_editor;
+mod shell_state;
+mod shell_words;
+mod test_report;
+mod toolchain;
 
 // Magic signature for bundled executables (17 bytes)
 const BUNDLE_SIGNATURE: &[u8] = b"KINETIX_BUNDLE_V1";
@@ -19,6 +40,40 @@ const BUNDLE_SIGNATURE: &[u8] = b"KINETIX_BUNDLE_V1";
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    /// When to color diagnostics (auto honors NO_COLOR and whether stderr is a terminal)
+    #[arg(long, global = true, value_enum, default_value = "auto")]
+    color: output::ColorChoice,
+    /// Print errors only
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+    /// Also print notes, backend fallbacks and pipeline steps
+    #[arg(short, long, global = true)]
+    verbose: bool,
+    /// Raise a runtime error when a builtin gets the wrong number or types of arguments, instead of returning {err: ...}
+    #[arg(long, global = true)]
+    strict_builtins: bool,
+    /// How program output is buffered: auto (line on a terminal, full otherwise), line or full
+    #[arg(long, global = true, value_name = "MODE", value_parser = ["auto", "line", "full"], default_value = "auto")]
+    buffer: String,
+    /// Set a flag for `#if` blocks: `feature=NAME` (or just NAME), `os=NAME`, `arch=NAME`
+    #[arg(long = "cfg", global = true, value_name = "FLAG")]
+    cfg: Vec<String>,
+    /// Define a compile-time constant, overriding a `#define` of the same name
+    #[arg(long, global = true, value_name = "NAME=VALUE")]
+    define: Vec<String>,
+    /// How to print diagnostics: human (colored, with the source line) or json (one object per line)
+    #[arg(long, global = true, value_enum, value_name = "FORMAT", default_value = "human")]
+    error_format: output::ErrorFormat,
+    /// Report parameters without type annotations (errors on `pub fn`s), as `#strict` does for one file
+    #[arg(long, global = true)]
+    strict: bool,
+    /// Load compiler passes from a plugin library (built with kinetix_kicomp::declare_plugin!)
+    #[arg(long = "plugin", global = true, value_name = "FILE")]
+    plugins: Vec<PathBuf>,
+    /// Export spans and runtime metrics over OTLP/HTTP to ENDPOINT (default: $OTEL_EXPORTER_OTLP_ENDPOINT, else http://localhost:4318)
+    #[cfg(feature = "otlp")]
+    #[arg(long, global = true, value_name = "ENDPOINT", num_args = 0..=1)]
+    otlp: Option<Option<String>>,
 }
 
 #[derive(clap::Subcommand)]
@@ -27,9 +82,20 @@ enum Commands {
     Run {
         /// Path to the .exki file
         file: PathBuf,
-        /// Audit allocations and formal invariants
+        /// Audit allocations and formal invariants; with FILE, also log every capability-gated builtin call to it as JSON lines
+        #[arg(long, value_name = "FILE", num_args = 0..=1)]
+        audit: Option<Option<PathBuf>>,
+        /// Set the variables listed in this file (NAME=value lines) before the program starts
+        #[arg(long, value_name = "FILE")]
+        env_file: Option<PathBuf>,
+        /// Start from an empty environment instead of inheriting kivm's
         #[arg(long)]
-        audit: bool,
+        clean_env: bool,
+    },
+    /// Print the bytecode of an .exki file: functions, constants and instructions with their source lines
+    Disasm {
+        /// Path to the .exki file
+        file: PathBuf,
     },
     /// Compile and run a .kix source file directly
     Exec {
@@ -38,19 +104,65 @@ enum Commands {
         /// Audit allocations and formal invariants
         #[arg(long)]
         audit: bool,
-        /// Print compiler optimization metrics
-        #[arg(long)]
+        /// Print compiler optimization metrics: per-pass timings and stats
+        #[arg(long, alias = "time-passes")]
         metrics: bool,
-        /// Disable compiler optimizations
+        /// Disable compiler optimizations (same as -O0)
         #[arg(long)]
         no_opt: bool,
+        /// Bytecode optimization level: 0 none, 1 per-function passes, 2 also inline tiny functions
+        #[arg(short = 'O', value_name = "LEVEL", default_value_t = 1, value_parser = clap::value_parser!(u8).range(0..=2))]
+        opt_level: u8,
+        /// Bind all of stdin, as a string, to this variable before the script runs
+        #[arg(long, value_name = "NAME", value_parser = parse_identifier)]
+        stdin_var: Option<String>,
+        /// Grant only the --allow capabilities; in a terminal, ask before granting others
+        #[arg(long)]
+        sandbox: bool,
+        /// Capabilities for --sandbox: fs-read, fs-write, net, sysinfo, exec, threads
+        #[arg(long, value_name = "CAP", value_delimiter = ',', requires = "sandbox", value_parser = parse_capability)]
+        allow: Vec<kinetix_kicomp::capability::Capability>,
+        /// Quotas for --sandbox, e.g. net-bytes=10MB,fs-write-bytes=1MB,spawned-procs=0
+        #[arg(long, value_name = "NAME=AMOUNT", value_delimiter = ',', requires = "sandbox", value_parser = kinetix_kivm::quota::parse_limit)]
+        limit: Vec<(kinetix_kivm::quota::Resource, u64)>,
+        /// Set the variables listed in this file (NAME=value lines) before the program starts
+        #[arg(long, value_name = "FILE")]
+        env_file: Option<PathBuf>,
+        /// Start from an empty environment instead of inheriting kivm's
+        #[arg(long)]
+        clean_env: bool,
+        /// Add tracing to the program: `spans` logs every function's entry and exit, with its duration
+        #[arg(long, value_enum, value_name = "WHAT")]
+        instrument: Option<kinetix_kicomp::instrument::Instrument>,
+    },
+    /// Apply the fixes the compiler suggests (missing `match` arms, deprecated builtins, unqualified module functions) to a .kix file
+    Fix {
+        /// Path to the .kix source file
+        file: PathBuf,
+        /// Print the changes as a diff instead of writing them
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Compile and run a single expression or statement, printing its value
+    Eval {
+        /// Source to evaluate, e.g. 'math.sqrt(16) + 1'
+        code: String,
+        /// Audit allocations and formal invariants
+        #[arg(long)]
+        audit: bool,
+        /// Bind all of stdin, as a string, to this variable before the code runs
+        #[arg(long, value_name = "NAME", value_parser = parse_identifier)]
+        stdin_var: Option<String>,
     },
     /// Compile a .kix source file to .exki bytecode
     Compile {
-        /// Input .kix source file
+        /// Input .kix source file (repeatable); a directory compiles every .kix file in it
         #[arg(short, long)]
-        input: PathBuf,
-        /// Output .exki file (optional)
+        input: Vec<PathBuf>,
+        /// More input files or directories, as for `-i`
+        #[arg(value_name = "PATH")]
+        paths: Vec<PathBuf>,
+        /// Output .exki file (optional; single input only)
         #[arg(short, long)]
         output: Option<PathBuf>,
         /// Create a standalone executable (bundle)
@@ -65,12 +177,24 @@ enum Commands {
         /// Strip symbols from the linked native executable (native compilation only)
         #[arg(long)]
         strip: bool,
-        /// Print compiler optimization metrics
-        #[arg(long)]
+        /// Print compiler optimization metrics: per-pass timings and stats
+        #[arg(long, alias = "time-passes")]
         metrics: bool,
-        /// Disable compiler optimizations
+        /// Disable compiler optimizations (same as -O0)
         #[arg(long)]
         no_opt: bool,
+        /// Bytecode optimization level: 0 none, 1 per-function passes, 2 also inline tiny functions
+        #[arg(short = 'O', value_name = "LEVEL", default_value_t = 1, value_parser = clap::value_parser!(u8).range(0..=2))]
+        opt_level: u8,
+        /// Move line maps and local names into a `.kisym` file next to the output
+        #[arg(long)]
+        kisym: bool,
+        /// Recompile everything, without reading or updating the incremental cache
+        #[arg(long)]
+        no_cache: bool,
+        /// Add tracing to the program: `spans` logs every function's entry and exit, with its duration
+        #[arg(long, value_enum, value_name = "WHAT")]
+        instrument: Option<kinetix_kicomp::instrument::Instrument>,
     },
     /// Initialize a new Kinetix project with scaffolding
     Init {
@@ -90,6 +214,14 @@ enum Commands {
         #[arg(default_value = "project.kicomp")]
         config: PathBuf,
     },
+    /// Manage the package in ./Kinetix.toml and its dependencies
+    Pkg {
+        /// Registry directory or http(s) URL (default: $KINETIX_REGISTRY, then ~/.kinetix/registry)
+        #[arg(long, global = true)]
+        registry: Option<String>,
+        #[command(subcommand)]
+        action: PkgAction,
+    },
     /// Start the Language Server Protocol for IDE integration
     Lsp,
     /// Show version information
@@ -99,18 +231,122 @@ enum Commands {
         /// Path to the test file or directory
         #[arg(default_value = ".")]
         path: PathBuf,
+        /// Check every `.kix` against the diagnostics in the `.stderr` file next to it
+        #[arg(long)]
+        ui: bool,
+        /// With --ui, rewrite the `.stderr` files from the current diagnostics
+        #[arg(long, requires = "ui")]
+        bless: bool,
+        /// Run the spec programs under `path` on every backend and check stdout and exit code
+        #[arg(long, conflicts_with = "ui")]
+        conformance: bool,
+        /// Report format: human (streamed lines), junit (JUnit XML) or json, with per-test timing and captured output
+        #[arg(long, value_parser = ["human", "junit", "json"], default_value = "human")]
+        format: String,
+    },
+    /// Run a benchmark suite and print one `key=value` line per case
+    Bench {
+        /// Suite to run (available: vm, parser)
+        #[arg(long, default_value = "vm")]
+        suite: String,
+        /// Timed runs per case
+        #[arg(long, default_value_t = 10)]
+        iterations: u32,
+    },
+    /// Start an interactive Kinetix shell (terminal), or run a shell script
+    Shell {
+        /// Script of shell lines to run instead of prompting
+        script: Option<PathBuf>,
+        /// Arguments for the script, as $1, $2...
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true, requires = "script")]
+        args: Vec<String>,
     },
-    /// Start an interactive Kinetix shell (terminal)
-    Shell,
     /// Open the Kinetix documentation in the browser
     #[command(alias = "documentation")]
-    Docs,
+    Docs {
+        /// Builtin, module or page to jump to (e.g. math.normalize)
+        topic: Option<String>,
+        /// Serve the docs locally with full-text search instead of opening the file
+        #[arg(long)]
+        serve: bool,
+        /// Port for --serve (0 picks a free one)
+        #[arg(long, default_value_t = 7878, requires = "serve")]
+        port: u16,
+    },
+    /// Print version, install and PATH diagnostics for bug reports
+    Env,
+    /// Manage installed Kinetix builds and the build a project is pinned to
+    Toolchain {
+        #[command(subcommand)]
+        action: ToolchainAction,
+    },
+    /// References generated from the compiler's own tables
+    Internals {
+        #[command(subcommand)]
+        what: InternalsAction,
+    },
     /// Uninstall Kinetix from the system
     Uninstall,
     /// Repair or modify the Kinetix installation
     Repair,
 }
 
+#[derive(clap::Subcommand)]
+enum InternalsAction {
+    /// Print the VM instruction set: every opcode, its operands and effect (Markdown)
+    Opcodes,
+}
+
+#[derive(clap::Subcommand)]
+enum PkgAction {
+    /// Create Kinetix.toml and src/main.kix
+    Init {
+        /// Package name (defaults to current directory name)
+        #[arg(default_value = ".")]
+        name: String,
+    },
+    /// Add a dependency: NAME, NAME@REQUIREMENT (e.g. strings@^0.2) or NAME --path DIR
+    Add {
+        spec: String,
+        /// Depend on the package in DIR instead of the registry
+        #[arg(long, value_name = "DIR")]
+        path: Option<PathBuf>,
+    },
+    /// Remove a dependency
+    Remove {
+        name: String,
+    },
+    /// Resolve the dependencies and compile the package into NAME.exki
+    Build {
+        /// Run the program after building it
+        #[arg(long)]
+        run: bool,
+    },
+    /// Publish this version of the package to a registry directory
+    Publish,
+}
+
+#[derive(clap::Subcommand)]
+enum ToolchainAction {
+    /// Keep a build's kivm/kicomp under ~/.kinetix/toolchains (this kivm's build by default)
+    Install {
+        /// Build number; checked against the binaries being installed
+        build: Option<i64>,
+        /// Directory holding the build's kivm (and kicomp) instead of this kivm
+        #[arg(long)]
+        from: Option<PathBuf>,
+    },
+    /// List installed toolchains and which one is selected
+    List,
+    /// Pin a build in ./kinetix.toml (or make it the default with --global)
+    Use {
+        build: i64,
+        /// Set the default for every directory without a kinetix.toml pin
+        #[arg(long)]
+        global: bool,
+    },
+}
+
 #[cfg(target_os = "windows")]
 fn is_launched_from_explorer() -> bool {
     // If the console process list returns <= 1, it means we're the only process attached to this console.
@@ -127,7 +363,42 @@ fn is_launched_from_explorer() -> bool {
     }
 }
 
-fn fatal_error(msg: &str) {
+fn fatal_error(err: &CliError) {
+    report(err);
+    if output::error_format() == output::ErrorFormat::Json {
+        std::process::exit(err.exit_code());
+    }
+
+    // Footer
+    eprintln!();
+    eprintln!("{}: aborting due to previous error(s)", paint("1;31", "error[E0000]"));
+    eprintln!("{}", paint("2", format!("  Kinetix v{} ({})", env!("CARGO_PKG_VERSION"), option_env!("KINETIX_BUILD").unwrap_or("Dev"))));
+    eprintln!("{}", paint("36", "  For more information, open an issue: https://github.com/MisterY3515/Kinetix/issues"));
+    eprintln!();
+
+    #[cfg(target_os = "windows")]
+    {
+        if is_launched_from_explorer() {
+            let _ = std::process::Command::new("cmd.exe").arg("/c").arg("pause").status();
+        }
+    }
+    std::process::exit(err.exit_code());
+}
+
+/// Prints `err` without exiting.
+fn report(err: &CliError) {
+    if output::error_format() == output::ErrorFormat::Json {
+        err.json_lines().iter().for_each(|line| eprintln!("{}", line));
+        return;
+    }
+    match err {
+        CliError::Diagnostics { file, pass, diagnostics } => print_diagnostics(file, pass, diagnostics),
+        other => print_error(&other.to_string()),
+    }
+}
+
+/// Prints one diagnostic message without exiting.
+fn print_error(msg: &str) {
     // Auto-detect file from message: if first line looks like "path/to/file.kix:" extract it
     let first_line = msg.lines().next().unwrap_or("");
     let trimmed_first = first_line.trim();
@@ -137,21 +408,18 @@ fn fatal_error(msg: &str) {
         let file_path = &trimmed_first[..trimmed_first.len() - 1];
         // Skip the first line (filename) from the message body
         let rest = msg.lines().skip(1).collect::<Vec<_>>().join("\n");
-        fatal_error_in(Some(file_path), &rest);
+        print_error_in(Some(file_path), &rest);
     } else {
-        fatal_error_in(None, msg);
+        print_error_in(None, msg);
     }
 }
 
-fn fatal_error_in(file: Option<&str>, msg: &str) {
-    let version = env!("CARGO_PKG_VERSION");
-    let build = option_env!("KINETIX_BUILD").unwrap_or("Dev");
-
+fn print_error_in(file: Option<&str>, msg: &str) {
     eprintln!();
 
     // File header (if applicable)
     if let Some(f) = file {
-        eprintln!("\x1b[1;37m{}:\x1b[0m", f);
+        eprintln!("{}", paint("1;37", format!("{}:", f)));
     }
 
     // Structured error output — C++/Python-style diagnostics
@@ -162,116 +430,189 @@ fn fatal_error_in(file: Option<&str>, msg: &str) {
         } else if trimmed.starts_with("error") || trimmed.starts_with("Error")
             || trimmed.contains("Fatal Error") || trimmed.contains("errors:")
         {
-            eprintln!("\x1b[1;31merror\x1b[0m: {}", trimmed);
+            eprintln!("{}: {}", paint("1;31", "error"), trimmed);
         } else if trimmed.starts_with("warning") || trimmed.starts_with("Warning") {
-            eprintln!("\x1b[1;33mwarning\x1b[0m: {}", trimmed);
+            eprintln!("{}: {}", paint("1;33", "warning"), trimmed);
         } else if trimmed.starts_with("note") || trimmed.starts_with("Note") {
-            eprintln!("\x1b[1;36mnote\x1b[0m: {}", trimmed);
+            eprintln!("{}: {}", paint("1;36", "note"), trimmed);
         } else if trimmed.starts_with("--> ") {
             // Pre-formatted location reference
-            eprintln!("  \x1b[1;34m{}\x1b[0m", trimmed);
-        } else if trimmed.starts_with("- ") {
-            // Bullet item — likely an individual error
-            let detail = &trimmed[2..];
-            // Try to extract line number from "Line N:" pattern
-            if let Some(rest) = detail.strip_prefix("Line ") {
-                if let Some(colon_pos) = rest.find(':') {
-                    let line_no = &rest[..colon_pos];
-                    let msg_part = rest[colon_pos + 1..].trim();
-                    if let Some(f) = file {
-                        eprintln!("\x1b[1;31merror\x1b[0m: {}", msg_part);
-                        eprintln!("  \x1b[1;34m--> {}:{}\x1b[0m", f, line_no);
-                        
-                        if let Ok(line_num) = line_no.parse::<usize>() {
-                            if let Ok(source_content) = std::fs::read_to_string(f) {
-                                if let Some(source_line) = source_content.lines().nth(line_num.saturating_sub(1)) {
-                                    let trimmed_line = source_line.trim_start();
-                                    
-                                    // Try to find a target word in single quotes (e.g. 'math', 'a')
-                                    let mut target_word = None;
-                                    if let Some(start) = msg_part.find('\'') {
-                                        if let Some(end) = msg_part[start + 1..].find('\'') {
-                                            target_word = Some(&msg_part[start + 1 .. start + 1 + end]);
-                                        }
-                                    }
-
-                                    let mut indent = source_line.len() - trimmed_line.len();
-                                    let mut caret_len = trimmed_line.len().max(1);
-                                    
-                                    if let Some(word) = target_word {
-                                        if let Some(pos) = source_line.find(word) {
-                                            indent = pos;
-                                            caret_len = word.len();
-                                        }
-                                    }
-
-                                    eprintln!("   \x1b[1;34m|\x1b[0m");
-                                    eprintln!("\x1b[1;34m{:>2} |\x1b[0m {}", line_num, source_line);
-                                    let carets = "^".repeat(caret_len);
-                                    let spaces = " ".repeat(indent);
-                                    eprintln!("   \x1b[1;34m|\x1b[0m {}\x1b[1;31m{}\x1b[0m", spaces, carets);
-                                }
-                            }
-                        }
-                    } else {
-                        eprintln!("\x1b[1;31merror\x1b[0m: {}", msg_part);
-                        eprintln!("  \x1b[1;34m--> line {}\x1b[0m", line_no);
-                    }
-                } else {
-                    eprintln!("  {}", trimmed);
-                }
-            } else {
-                eprintln!("  {}", trimmed);
-            }
+            eprintln!("  {}", paint("1;34", trimmed));
         } else {
             eprintln!("  {}", line);
         }
     }
+}
 
-    // Footer
-    eprintln!();
-    eprintln!("\x1b[1;31merror[E0000]\x1b[0m: aborting due to previous error(s)");
-    eprintln!("\x1b[2m  Kinetix v{} ({})\x1b[0m", version, build);
-    eprintln!("\x1b[36m  For more information, open an issue: https://github.com/MisterY3515/Kinetix/issues\x1b[0m");
-    eprintln!();
-
-    #[cfg(target_os = "windows")]
-    {
-        if is_launched_from_explorer() {
-            let _ = std::process::Command::new("cmd.exe").arg("/c").arg("pause").status();
+/// `label: msg`, then where it is: `--> file:line[:column]` and the source
+/// line with a caret under the column, or under the word `msg` quotes.
+fn print_located(label: &str, msg: &str, file: Option<&str>, line_num: usize, column: Option<usize>) {
+    eprintln!("{}: {}", label, msg);
+    let at = line_column(line_num, column);
+    let Some(f) = file else {
+        eprintln!("  {}", paint("1;34", format!("--> line {}", at)));
+        return;
+    };
+    eprintln!("  {}", paint("1;34", format!("--> {}:{}", f, at)));
+
+    let Ok(source_content) = std::fs::read_to_string(f) else { return };
+    let Some(source_line) = source_content.lines().nth(line_num.saturating_sub(1)) else { return };
+    let trimmed_line = source_line.trim_start();
+    let mut indent = source_line.len() - trimmed_line.len();
+    let mut caret_len = trimmed_line.len().max(1);
+
+    if let Some(column) = column {
+        // Underline the token the column points at
+        let start = source_line.char_indices().nth(column - 1).map_or(source_line.len(), |(i, _)| i);
+        let word = source_line[start..].chars()
+            .take_while(|c| c.is_alphanumeric() || *c == '_')
+            .map(char::len_utf8)
+            .sum::<usize>();
+        indent = source_line[..start].chars().count();
+        caret_len = word.max(1);
+    } else if let Some(word) = quoted_word(msg) {
+        // Otherwise guess from a word in single quotes (e.g. 'math', 'a')
+        if let Some(pos) = source_line.find(word) {
+            indent = pos;
+            caret_len = word.len();
         }
     }
-    std::process::exit(1);
+
+    eprintln!("   {}", paint("1;34", "|"));
+    eprintln!("{} {}", paint("1;34", format!("{:>2} |", line_num)), source_line);
+    let carets = "^".repeat(caret_len);
+    let spaces = " ".repeat(indent);
+    eprintln!("   {} {}{}", paint("1;34", "|"), spaces, paint("1;31", carets));
 }
 
-/// Format a compile-pipeline error with filename context.
-fn format_pipeline_error(file: &std::path::Path, category: &str, errors: Vec<String>) -> String {
-    let fname = file.display().to_string();
-    let mut out = format!("{}:\n", fname);
-    for e in &errors {
-        // Check if error already contains "Line N:" pattern
-        let trimmed = e.trim();
-        if let Some(rest) = trimmed.strip_prefix("Line ") {
-            if let Some(colon_pos) = rest.find(':') {
-                let line_no = &rest[..colon_pos];
-                let msg = rest[colon_pos + 1..].trim();
-                out.push_str(&format!("- Line {}: {}\n", line_no, msg));
-                continue;
+/// A pass's diagnostics, each with its code, snippet, notes and help.
+fn print_diagnostics(file: &Path, pass: &str, diagnostics: &[Diagnostic]) {
+    eprintln!();
+    eprintln!("{}", paint("1;37", format!("{}:", file.display())));
+    let entry = file.display().to_string();
+    for d in diagnostics {
+        let label = match d.severity {
+            Severity::Error => paint("1;31", format!("error[{}]", d.code)),
+            Severity::Warning => paint("1;33", format!("warning[{}]", d.code)),
+        };
+        match d.span() {
+            Some(span) => {
+                let column = (span.column > 0).then_some(span.column);
+                print_located(&label, &d.message, Some(d.file.as_deref().unwrap_or(&entry)), span.line, column);
             }
+            None => eprintln!("{}: {}", label, d.message),
+        }
+        for note in &d.notes {
+            eprintln!("   {} note: {}", paint("1;34", "="), note);
+        }
+        if let Some(help) = &d.help {
+            eprintln!("   {} help: {}", paint("1;34", "="), help);
+        }
+        for fix in &d.fixes {
+            eprintln!("   {} fix: {}", paint("1;34", "="), fix.message);
         }
-        out.push_str(&format!("- {}\n", trimmed));
     }
-    out.push_str(&format!("{} error(s) in {}", errors.len(), category));
-    out
+    eprintln!("  {} error(s) in {}", diagnostics.len(), pass);
+}
+
+/// The first word in single quotes in `msg`, if any.
+fn quoted_word(msg: &str) -> Option<&str> {
+    let start = msg.find('\'')? + 1;
+    let end = msg[start..].find('\'')?;
+    Some(&msg[start..start + end])
+}
+
+/// "N" or "N:C".
+fn line_column(line: usize, column: Option<usize>) -> String {
+    match column {
+        Some(column) => format!("{}:{}", line, column),
+        None => line.to_string(),
+    }
+}
+
+/// The code a pass's errors are reported under.
+fn pass_code(pass: &str) -> &'static str {
+    match pass {
+        "Parser" => codes::PARSE,
+        "Version Gate" => codes::VERSION,
+        "Linker" => codes::LINK,
+        "Symbol Resolution" => codes::SYMBOL,
+        "Trait Resolver" => codes::TRAIT,
+        "Type Normalizer" | "Type Checker" => codes::TYPE,
+        "Method Resolution" => codes::METHOD,
+        "Exhaustiveness Checker" => codes::EXHAUSTIVENESS,
+        "Sandbox Audit Pass" => codes::CAPABILITY,
+        "Borrow Checker" => codes::BORROW,
+        "Strict Mode" => codes::STRICT,
+        _ => codes::INTERNAL,
+    }
+}
+
+/// Runs the strict-mode check when `--strict` or `#strict` asks for it, and
+/// returns its warnings.
+fn check_strict(file: &std::path::Path, program: &kinetix_language::ast::Program) -> Result<Vec<Diagnostic>, CliError> {
+    if !kinetix_kicomp::strict::enabled(program) {
+        return Ok(Vec::new());
+    }
+    kinetix_kicomp::strict::check(&program.statements)
+        .map_err(|errs| pipeline_error(file, "Strict Mode", errs))
+}
+
+/// The one error of a pass that reports a message without a location.
+fn pass_error(file: &std::path::Path, pass: &'static str, message: String) -> CliError {
+    pipeline_error(file, pass, vec![Diagnostic::error(pass_code(pass), message)])
+}
+
+/// `diagnostics` from `pass` over `file`, with lines of `#include`d files
+/// pointing back into them.
+fn pipeline_error(file: &std::path::Path, pass: &'static str, mut diagnostics: Vec<Diagnostic>) -> CliError {
+    for d in &mut diagnostics {
+        locate_diagnostic(file, d);
+    }
+    CliError::Diagnostics { file: file.to_path_buf(), pass, diagnostics }
+}
+
+/// Points `d` at the line it is about in the file as written (an included
+/// file, if it is about one), and its fixes at the lines they edit, where
+/// `kivm fix` applies them.
+fn locate_diagnostic(file: &Path, d: &mut Diagnostic) {
+    for fix in &mut d.fixes {
+        if let Some((_, line)) = include::origin(file, fix.at.line) {
+            fix.at.line = line;
+        }
+    }
+    let Some(span) = d.spans.first_mut() else { return };
+    if let Some((origin, line)) = include::origin(file, span.line) {
+        span.line = line;
+        if origin != file {
+            d.file = Some(origin.display().to_string());
+        }
+    }
+}
+
+/// Symbol resolution's errors; an undeclared name an imported module exports
+/// comes with the fix qualifying it, and a name it keeps private says so.
+fn symbol_error(file: &Path, statements: &[kinetix_language::ast::Statement], modules: &[kinetix_kicomp::module_graph::Module], mut diagnostics: Vec<Diagnostic>) -> CliError {
+    kinetix_kicomp::module_graph::qualify_undeclared(&mut diagnostics, statements, modules);
+    kinetix_kicomp::module_graph::explain_private(&mut diagnostics, modules);
+    pipeline_error(file, "Symbol Resolution", diagnostics)
+}
+
+/// Runs the plugin passes of `stage` (see `--plugin`) over `unit`; returns
+/// their warnings.
+fn plugin_stage(file: &Path, stage: plugin::Stage, unit: plugin::Unit) -> Result<Vec<Diagnostic>, CliError> {
+    plugin::run(stage, unit).map_err(|(pass, errors)| pipeline_error(file, pass, errors))
 }
 
 fn main() {
+    output::init_console();
+
     // 1. Check if we are running as a bundled executable
     if let Some(program) = check_for_bundle() {
         // Run the bundled program
         let mut vm = VM::new(program);
         if let Err(e) = vm.run() {
-            fatal_error(&format!("Runtime error:\n{}", e));
+            fatal_error(&CliError::Runtime(e));
         }
 
         #[cfg(target_os = "windows")]
@@ -281,9 +622,17 @@ fn main() {
         return;
     }
 
-    // 2. Otherwise/Normal CLI mode
+    // 2. Hand the command to the pinned toolchain when it is another build
+    let args: Vec<String> = std::env::args().collect();
+    match toolchain::dispatch(&args) {
+        Ok(Some(code)) => std::process::exit(code),
+        Ok(None) => {}
+        Err(e) => fatal_error(&CliError::Io(e)),
+    }
+
+    // 3. Otherwise/Normal CLI mode
     if let Err(e) = run() {
-        fatal_error(&format!("{}", e));
+        fatal_error(&e);
     }
 
     #[cfg(target_os = "windows")]
@@ -335,398 +684,142 @@ fn check_for_bundle() -> Option<kinetix_kicomp::ir::CompiledProgram> {
     exn::read_exn(&mut handle).ok()
 }
 
-fn run() -> Result<(), String> {
+/// Print non-fatal pipeline warnings with file context.
+fn print_warning_diagnostics(file: &std::path::Path, warnings: Vec<Diagnostic>) {
+    for mut warning in warnings {
+        locate_diagnostic(file, &mut warning);
+        if output::error_format() == output::ErrorFormat::Json {
+            if output::verbosity() != output::Verbosity::Quiet {
+                eprintln!("{}", output::json_diagnostic(&file.display().to_string(), &warning));
+            }
+            continue;
+        }
+        output::warning(&warning.message);
+        if output::verbosity() == output::Verbosity::Quiet {
+            continue;
+        }
+        match warning.span() {
+            Some(span) => {
+                let column = (span.column > 0).then_some(span.column);
+                let origin = warning.file.clone().unwrap_or_else(|| file.display().to_string());
+                eprintln!("  {}", paint("1;34", format!("--> {}:{}", origin, line_column(span.line, column))));
+            }
+            None => eprintln!("  {}", paint("1;34", format!("--> {}", file.display()))),
+        }
+        for fix in &warning.fixes {
+            eprintln!("   {} fix: {}", paint("1;34", "="), fix.message);
+        }
+    }
+}
+
+fn run() -> Result<(), CliError> {
     let cli = Cli::parse();
+    // Before anything below can start a thread (plugins, the --otlp exporter)
+    if let Commands::Run { env_file, clean_env, .. } | Commands::Exec { env_file, clean_env, .. } = &cli.command {
+        env_file::apply(env_file.as_deref(), *clean_env).map_err(CliError::Io)?;
+    }
+    let verbosity = if cli.quiet {
+        output::Verbosity::Quiet
+    } else if cli.verbose {
+        output::Verbosity::Verbose
+    } else {
+        output::Verbosity::Normal
+    };
+    output::configure(cli.color, verbosity);
+    output::set_error_format(cli.error_format);
+    kinetix_kivm::builtins::set_strict_default(cli.strict_builtins);
+    kinetix_kivm::builtins::modules::io::set_buffering(match cli.buffer.as_str() {
+        "line" => Some(Buffering::Line),
+        "full" => Some(Buffering::Full),
+        _ => None,
+    });
+    let mut cfg = kinetix_language::cfg::Cfg::from_flags(&cli.cfg).map_err(CliError::Usage)?;
+    for define in &cli.define {
+        cfg.define(define).map_err(CliError::Usage)?;
+    }
+    kinetix_language::cfg::set_default(cfg);
+    kinetix_kicomp::strict::set_enabled(cli.strict);
+    for path in &cli.plugins {
+        let count = plugins::load(path).map_err(CliError::Usage)?;
+        output::step(format!("{}: {} compiler pass(es) loaded", path.display(), count));
+    }
+    // Dropped when the command returns, sending the last batch
+    #[cfg(feature = "otlp")]
+    let _otlp = cli.otlp.clone().map(|endpoint| {
+        let endpoint = endpoint.or_else(|| std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT").ok()).unwrap_or_else(|| "http://localhost:4318".to_string());
+        let service = std::env::var("OTEL_SERVICE_NAME").unwrap_or_else(|_| "kinetix".to_string());
+        kinetix_kivm::otlp::export_to(&endpoint, &service)
+    });
 
     match cli.command {
-        Commands::Run { file, audit } => {
+        Commands::Run { file, audit, .. } => {
             if file.extension().map_or(false, |ext| ext == "kix") {
-                return Err(format!("'{}' is a source file. Use 'kivm exec {}' instead.", file.display(), file.display()));
+                return Err(CliError::Usage(format!("'{}' is a source file. Use 'kivm exec {}' instead.", file.display(), file.display())));
             }
 
-            let data = fs::read(&file).map_err(|e| format!("Error reading {}: {}", file.display(), e))?;
+            let data = fs::read(&file).map_err(|e| CliError::Io(format!("Error reading {}: {}", file.display(), e)))?;
             let mut cursor = std::io::Cursor::new(data);
-            let program = exn::read_exn(&mut cursor).map_err(|e| format!("Error loading .exki: {}", e))?;
+            let program = exn::read_exn(&mut cursor).map_err(|e| CliError::Compile(format!("Error loading .exki: {}", e)))?;
             let mut vm = VM::new(program);
-            vm.run().map_err(|e| format!("Runtime error: {}", e))?;
+            let symbols = kinetix_kicomp::kisym::path_for(&file);
+            if symbols.exists() {
+                vm.set_symbols_path(symbols);
+            }
+            if let Some(Some(log)) = &audit {
+                vm.audit = Some(std::sync::Arc::new(kinetix_kivm::audit::AuditLog::create(log).map_err(CliError::Io)?));
+            }
+            vm.run().map_err(CliError::Runtime)?;
             
-            if audit {
+            if audit.is_some() {
                 println!("\n=== Audit Report ===");
                 println!("Total Heap Allocations: {}", vm.mem_stats.total_heap_allocations);
             }
         }
-        Commands::Exec { file, audit, metrics, no_opt } => {
-            let source = fs::read_to_string(&file).map_err(|e| format!("Error reading {}: {}", file.display(), e))?;
-            
-            if source.trim_start().starts_with("{\\rtf") {
-                return Err(format!("'{}' appears to be a Rich Text Format (RTF) document, not a plain text source file. Please save it as plain text using a proper code editor (like VS Code or TextEdit in Plain Text mode).", file.display()));
-            }
-
-            use kinetix_kicomp::compiler::Compiler;
-
-            let lexer = kinetix_language::lexer::Lexer::new(&source);
-            let arena = Bump::new();
-            let mut parser = kinetix_language::parser::Parser::new(lexer, &arena);
-            let ast = parser.parse_program();
-
-            if !parser.errors.is_empty() {
-                let errs: Vec<String> = parser.errors.iter().map(|e| e.to_string()).collect();
-                return Err(format_pipeline_error(&file, "Parser", errs));
-            }
-
-            let symbols = kinetix_kicomp::symbol::resolve_program(&ast.statements)
-                .map_err(|errs| format_pipeline_error(&file, "Symbol Resolution", errs))?;
-
-            let mut traits = kinetix_kicomp::trait_solver::TraitEnvironment::new();
-            for stmt in &ast.statements {
-                if let kinetix_language::ast::Statement::Trait { .. } = stmt {
-                    if let Err(e) = traits.register_trait(stmt) {
-                        return Err(format_pipeline_error(&file, "Trait Resolver", vec![e]));
-                    }
-                }
-            }
-            for stmt in &ast.statements {
-                if let kinetix_language::ast::Statement::Impl { .. } = stmt {
-                    if let Err(e) = traits.register_impl(stmt) {
-                        return Err(format_pipeline_error(&file, "Trait Resolver", vec![e]));
-                    }
-                }
-            }
-            traits.validate_cycles().map_err(|e| format_pipeline_error(&file, "Trait Resolver", vec![e]))?;
-
-            let mut hir = kinetix_kicomp::hir::lower_to_hir(&ast.statements, &symbols, &traits);
-            kinetix_kicomp::type_normalize::normalize(&mut hir, &symbols).map_err(|e| format_pipeline_error(&file, "Type Normalizer", vec![e]))?;
-            let mut ctx = kinetix_kicomp::typeck::TypeContext::new();
-            let constraints = ctx.collect_constraints(&hir);
-            ctx.solve(&constraints).map_err(|errs| {
-                let msgs: Vec<String> = errs.iter().map(|e| e.to_string()).collect();
-                format_pipeline_error(&file, "Type Checker", msgs)
-            })?;
-
-            // Post-TypeChecker: resolve method calls now that types are concrete
-            kinetix_kicomp::type_normalize::resolve_method_calls(&mut hir, &symbols, &ctx.substitution)
-                .map_err(|e| format_pipeline_error(&file, "Method Resolution", vec![e]))?;
-
-            kinetix_kicomp::exhaustiveness::check_program_exhaustiveness(&hir, &symbols, &ctx.substitution)
-                .map_err(|e| format_pipeline_error(&file, "Exhaustiveness Checker", vec![e]))?;
-
-            // M2.6 Capability IR Enforcement Pass (Build 19)
-            let granted_caps = vec![
-                kinetix_kicomp::capability::Capability::FsRead,
-                kinetix_kicomp::capability::Capability::FsWrite,
-                kinetix_kicomp::capability::Capability::NetAccess,
-                kinetix_kicomp::capability::Capability::SysInfo,
-                kinetix_kicomp::capability::Capability::OsExecute,
-                kinetix_kicomp::capability::Capability::ThreadControl,
-            ];
-            let cap_validator = kinetix_kicomp::capability::CapabilityValidator::new(granted_caps.clone());
-            cap_validator.validate(&hir).map_err(|errs| {
-                let msgs: Vec<String> = errs.iter().map(|e| e.to_string()).collect();
-                format_pipeline_error(&file, "Sandbox Audit Pass", msgs)
-            })?;
-
-            // Build 20: HIR Integrity Validation Pass
-            kinetix_kicomp::hir_validate::validate(&hir).map_err(|errs| {
-                format_pipeline_error(&file, "HIR Integrity", errs)
-            })?;
-
-            let mir = kinetix_kicomp::mir::lower_to_mir(&hir, &ctx.substitution);
-            kinetix_kicomp::borrowck::check_mir(&mir).map_err(|errs| {
-                format_pipeline_error(&file, "Borrow Checker", errs)
-            })?;
-
-            let mir = kinetix_kicomp::monomorphize::monomorphize(&mir).map_err(|e| {
-                format_pipeline_error(&file, "Monomorphization Pass", vec![e])
-            })?;
-
-            kinetix_kicomp::mono_validate::validate(&mir).map_err(|e| {
-                format_pipeline_error(&file, "Post-Mono Validator", vec![e])
-            })?;
-
-            kinetix_kicomp::drop_verify::verify(&mir).map_err(|e| {
-                format_pipeline_error(&file, "Drop Order Verifier", vec![e])
-            })?;
-
-            // Build 20: MIR/SSA Integrity Validation Pass
-            kinetix_kicomp::ssa_validate::validate(&mir).map_err(|e| {
-                format_pipeline_error(&file, "MIR Integrity", vec![e])
-            })?;
-
-            #[cfg(feature = "llvm")]
-            {
-                // Try LLVM JIT first
-                let jit_res = kinetix_kicomp::llvm_codegen::run_program_jit(&ast.statements);
-                match jit_res {
-                    Ok(_) => return Ok(()), // JIT successful
-                    Err(e) => {
-                        // JIT failed (likely due to unimplemented AST nodes in LLVM backend yet).
-                        // Fallback to KiVM.
-                        // We do not print the fallback message unless in debug mode, to avoid noise,
-                        // but since it's experimental, let's log it lightly.
-                        // eprintln!("Note: LLVM JIT not available for this script ({}). Falling back to KiVM.", e);
-                    }
-                }
+        Commands::Disasm { file } => {
+            if file.extension().is_some_and(|ext| ext == "kix") {
+                return Err(CliError::Usage(format!("'{}' is a source file. Compile it first with 'kivm compile -i {}'.", file.display(), file.display())));
             }
-
-            let reactive_graph = kinetix_kicomp::reactive::build_reactive_graph(&hir)
-                .map_err(|e| format!("Reactive Graph Error: {}", e))?;
-                
-            let mut compiler = Compiler::new();
-            let compiled = compiler.compile(&ast.statements, Some(reactive_graph.to_compiled())).map_err(|e| format!("Compilation error: {}", e))?;
-
-            // Build 35: Bytecode Optimization Passes
-            let mut optimized = compiled.clone();
-            
-            let mut met = kinetix_kicomp::metrics::CompilerMetrics::new();
-            if metrics {
-                met.total_instructions_before = kinetix_kicomp::metrics::CompilerMetrics::count_instructions(&optimized);
-            }
-            
-            if !no_opt {
-                let (_, ms) = kinetix_kicomp::metrics::timed(|| {
-                    kinetix_kicomp::opt::optimize(&mut optimized);
-                });
-                if metrics {
-                    met.total_instructions_after = kinetix_kicomp::metrics::CompilerMetrics::count_instructions(&optimized);
-                    met.record_phase("All Bytecode Passes", met.total_instructions_before, met.total_instructions_after, ms);
-                }
-            } else if metrics {
-                met.total_instructions_after = met.total_instructions_before;
-            }
-            
-            if metrics {
-                met.print_report();
-            }
-
-            if audit {
-                println!("[✓] Formal Invariants Certified");
-            }
-            
-            let mut vm = VM::new(optimized);
-            vm.run().map_err(|e| format!("Runtime error: {}", e))?;
-            
-            if audit {
-                println!("\n=== Audit Report ===");
-                println!("Total Heap Allocations: {}", vm.mem_stats.total_heap_allocations);
+            let data = fs::read(&file).map_err(|e| CliError::Io(format!("Error reading {}: {}", file.display(), e)))?;
+            let mut program = exn::read_exn(&mut std::io::Cursor::new(data)).map_err(|e| CliError::Compile(format!("Error loading .exki: {}", e)))?;
+            // Compiled with --kisym: the line numbers are in the side file
+            let symbols = kinetix_kicomp::kisym::path_for(&file);
+            if symbols.exists() {
+                let syms = kinetix_kicomp::kisym::DebugSymbols::read(&symbols).map_err(|e| CliError::Io(format!("Error reading {}: {}", symbols.display(), e)))?;
+                syms.restore_line_maps(&mut program);
             }
+            print!("{}", kinetix_kicomp::ir::disassemble(&program));
         }
-        Commands::Compile { input, output, exe, native, o3, strip, metrics, no_opt } => {
-            let source = fs::read_to_string(&input).map_err(|e| format!("Error reading {}: {}", input.display(), e))?;
+        Commands::Exec { file, audit, metrics, no_opt, opt_level, stdin_var, sandbox, allow, limit, instrument, .. } => {
+            let source = fs::read_to_string(&file).map_err(|e| CliError::Io(format!("Error reading {}: {}", file.display(), e)))?;
             
             if source.trim_start().starts_with("{\\rtf") {
-                return Err(format!("'{}' appears to be a Rich Text Format (RTF) document, not a plain text source file. Please save it as plain text using a proper code editor.", input.display()));
-            }
-            
-            // Preprocess includes
-            let source = preprocess_includes(&source, input.parent().unwrap_or(Path::new(".")))
-                .map_err(|e| format!("Include error: {}", e))?;
-
-            use kinetix_kicomp::compiler::Compiler;
-
-            let lexer = kinetix_language::lexer::Lexer::new(&source);
-            let arena = Bump::new();
-            let mut parser = kinetix_language::parser::Parser::new(lexer, &arena);
-            let ast = parser.parse_program();
-
-            if !parser.errors.is_empty() {
-                let errs: Vec<String> = parser.errors.iter().map(|e| e.to_string()).collect();
-                return Err(format_pipeline_error(&input, "Parser", errs));
+                return Err(CliError::Usage(format!("'{}' appears to be a Rich Text Format (RTF) document, not a plain text source file. Please save it as plain text using a proper code editor (like VS Code or TextEdit in Plain Text mode).", file.display())));
             }
 
-            let symbols = kinetix_kicomp::symbol::resolve_program(&ast.statements)
-                .map_err(|errs| format_pipeline_error(&input, "Symbol Resolution", errs))?;
-
-            let mut traits = kinetix_kicomp::trait_solver::TraitEnvironment::new();
-            for stmt in &ast.statements {
-                if let kinetix_language::ast::Statement::Trait { .. } = stmt {
-                    if let Err(e) = traits.register_trait(stmt) {
-                        return Err(format_pipeline_error(&input, "Trait Resolver", vec![e]));
-                    }
-                }
-            }
-            for stmt in &ast.statements {
-                if let kinetix_language::ast::Statement::Impl { .. } = stmt {
-                    if let Err(e) = traits.register_impl(stmt) {
-                        return Err(format_pipeline_error(&input, "Trait Resolver", vec![e]));
-                    }
-                }
-            }
-            traits.validate_cycles().map_err(|e| format_pipeline_error(&input, "Trait Resolver", vec![e]))?;
-
-            let mut hir = kinetix_kicomp::hir::lower_to_hir(&ast.statements, &symbols, &traits);
-            kinetix_kicomp::type_normalize::normalize(&mut hir, &symbols).map_err(|e| format_pipeline_error(&input, "Type Normalizer", vec![e]))?;
-            let mut ctx = kinetix_kicomp::typeck::TypeContext::new();
-            let constraints = ctx.collect_constraints(&hir);
-            ctx.solve(&constraints).map_err(|errs| {
-                let msgs: Vec<String> = errs.iter().map(|e| e.to_string()).collect();
-                format_pipeline_error(&input, "Type Checker", msgs)
-            })?;
-
-            // Post-TypeChecker: resolve method calls now that types are concrete
-            kinetix_kicomp::type_normalize::resolve_method_calls(&mut hir, &symbols, &ctx.substitution)
-                .map_err(|e| format_pipeline_error(&input, "Method Resolution", vec![e]))?;
-
-            kinetix_kicomp::exhaustiveness::check_program_exhaustiveness(&hir, &symbols, &ctx.substitution)
-                .map_err(|e| format_pipeline_error(&input, "Exhaustiveness Checker", vec![e]))?;
-
-            // M2.6 Capability IR Enforcement Pass (Build 19)
-            let cap_validator = kinetix_kicomp::capability::CapabilityValidator::new(vec![
-                kinetix_kicomp::capability::Capability::FsRead,
-                kinetix_kicomp::capability::Capability::FsWrite,
-                kinetix_kicomp::capability::Capability::NetAccess,
-                kinetix_kicomp::capability::Capability::SysInfo,
-                kinetix_kicomp::capability::Capability::OsExecute,
-                kinetix_kicomp::capability::Capability::ThreadControl,
-            ]);
-            cap_validator.validate(&hir).map_err(|errs| {
-                let msgs: Vec<String> = errs.iter().map(|e| e.to_string()).collect();
-                format_pipeline_error(&input, "Sandbox Audit Pass", msgs)
-            })?;
-
-            // Build 20: HIR Integrity Validation Pass
-            kinetix_kicomp::hir_validate::validate(&hir).map_err(|errs| {
-                format_pipeline_error(&input, "HIR Integrity", errs)
-            })?;
-
-            let mir = kinetix_kicomp::mir::lower_to_mir(&hir, &ctx.substitution);
-            kinetix_kicomp::borrowck::check_mir(&mir).map_err(|errs| {
-                format_pipeline_error(&input, "Borrow Checker", errs)
-            })?;
-
-            let mir = kinetix_kicomp::monomorphize::monomorphize(&mir).map_err(|e| {
-                format_pipeline_error(&input, "Monomorphization Pass", vec![e])
-            })?;
-
-            kinetix_kicomp::mono_validate::validate(&mir).map_err(|e| {
-                format_pipeline_error(&input, "Post-Mono Validator", vec![e])
-            })?;
-
-            kinetix_kicomp::drop_verify::verify(&mir).map_err(|e| {
-                format_pipeline_error(&input, "Drop Order Verifier", vec![e])
-            })?;
-
-            // Build 20: MIR/SSA Integrity Validation Pass
-            kinetix_kicomp::ssa_validate::validate(&mir).map_err(|e| {
-                format_pipeline_error(&input, "MIR Integrity", vec![e])
-            })?;
-
-            let reactive_graph = kinetix_kicomp::reactive::build_reactive_graph(&hir)
-                .map_err(|e| format!("Reactive Graph Error: {}", e))?;
-
-            let mut compiler = Compiler::new();
-            let compiled = compiler.compile(&ast.statements, Some(reactive_graph.to_compiled()))
-                .map_err(|e| format!("Compilation error: {}", e))?;
-
-            // Build 35: Bytecode Optimization Passes
-            let mut optimized = compiled.clone();
-            
-            let mut met = kinetix_kicomp::metrics::CompilerMetrics::new();
-            if metrics {
-                met.total_instructions_before = kinetix_kicomp::metrics::CompilerMetrics::count_instructions(&optimized);
-            }
-            
-            if !no_opt {
-                let (_, ms) = kinetix_kicomp::metrics::timed(|| {
-                    kinetix_kicomp::opt::optimize(&mut optimized);
-                });
-                if metrics {
-                    met.total_instructions_after = kinetix_kicomp::metrics::CompilerMetrics::count_instructions(&optimized);
-                    met.record_phase("All Bytecode Passes", met.total_instructions_before, met.total_instructions_after, ms);
-                }
-            } else if metrics {
-                met.total_instructions_after = met.total_instructions_before;
-            }
-            
-            if metrics {
-                met.print_report();
+            for (resource, max) in limit {
+                kinetix_kivm::quota::set_limit(resource, max);
             }
-            
-            let compiled = &optimized;
-
-            if native {
-                #[cfg(feature = "llvm")]
-                {
-                    let output_path = output.unwrap_or_else(|| {
-                        if cfg!(target_os = "windows") {
-                            input.with_extension("exe")
-                        } else {
-                            input.with_extension("")
-                        }
-                    });
-
-                    // An explicit `.o` output keeps the old object-only behavior
-                    // (e.g. for embedding into an external build); anything else
-                    // goes through the real link step (Build 37).
-                    let emit_object_only = output_path.extension().map(|e| e == "o").unwrap_or(false);
-                    if emit_object_only {
-                        println!("Compiling to native object file: {}", output_path.display());
-                        kinetix_kicomp::llvm_codegen::compile_program_to_object(&ast.statements, &output_path, o3)
-                            .map_err(|e| format!("LLVM Codegen error: {}", e))?;
-                    } else {
-                        println!("Compiling to native executable: {}", output_path.display());
-                        kinetix_kicomp::llvm_codegen::compile_program_to_executable(&ast.statements, &output_path, o3, strip)
-                            .map_err(|e| format!("Native compilation error: {}", e))?;
-                    }
-
-                    println!("Native compilation successful.");
-                    return Ok(());
-                }
-                #[cfg(not(feature = "llvm"))]
-                {
-                   return Err("Native compilation requires 'llvm' feature. Rebuild with --features llvm.".to_string());
-                }
+            let sandbox = sandbox.then_some(allow);
+            // An exporter (--otlp) gets the spans without asking for them
+            let instrument = instrument.or(kinetix_kivm::log::metrics_enabled().then_some(kinetix_kicomp::instrument::Instrument::Spans));
+            let opts = ExecOptions { stdin_var: stdin_var.as_deref(), audit, metrics, opt_level: if no_opt { 0 } else { opt_level }, sandbox, instrument, ..ExecOptions::default() };
+            exec_source(&file, &source, &opts)?;
+        }
+        Commands::Eval { code, audit, stdin_var } => {
+            let opts = ExecOptions { stdin_var: stdin_var.as_deref(), echo_result: true, audit, ..ExecOptions::default() };
+            exec_source(Path::new("<eval>"), &code, &opts)?;
+        }
+        Commands::Compile { input, paths, output, exe, native, o3, strip, metrics, no_opt, opt_level, kisym, no_cache, instrument } => {
+            let opts = CompileOptions { exe, native, o3, strip, metrics, opt_level: if no_opt { 0 } else { opt_level }, kisym, no_cache, instrument };
+            let mut inputs = Vec::new();
+            for path in input.iter().chain(&paths) {
+                inputs.extend(kix_files(path).map_err(CliError::Io)?);
             }
-
-            if exe {
-                // Create standalone executable
-                let output_path = output.unwrap_or_else(|| {
-                    if cfg!(target_os = "windows") {
-                        input.with_extension("exe")
-                    } else {
-                        // On Unix, strip extension to produce a clean binary name
-                        input.with_extension("")
-                    }
-                });
-
-                // 1. Serialize bytecode to buffer
-                let mut bytecode_buf = Vec::new();
-                exn::write_exn(&mut bytecode_buf, compiled).map_err(|e| e.to_string())?;
-                let payload_size = bytecode_buf.len() as u64;
-
-                // 2. Read current executable (the stub)
-                let current_exe = std::env::current_exe().map_err(|e| e.to_string())?;
-                let mut stub_data = Vec::new();
-                fs::File::open(&current_exe).map_err(|e| e.to_string())?
-                    .read_to_end(&mut stub_data).map_err(|e| e.to_string())?;
-
-                // 3. Write [Stub] [Payload] [Size] [Sig]
-                let mut file = fs::File::create(&output_path).map_err(|e| format!("Error creating {}: {}", output_path.display(), e))?;
-                
-                file.write_all(&stub_data).map_err(|e| e.to_string())?;
-                file.write_all(&bytecode_buf).map_err(|e| e.to_string())?;
-                file.write_all(&payload_size.to_le_bytes()).map_err(|e| e.to_string())?;
-                file.write_all(BUNDLE_SIGNATURE).map_err(|e| e.to_string())?;
-
-                // 4. Make executable (Linux/Mac)
-                #[cfg(unix)]
-                {
-                    use std::os::unix::fs::PermissionsExt;
-                    let mut perms = fs::metadata(&output_path).unwrap().permissions();
-                    perms.set_mode(0o755);
-                    fs::set_permissions(&output_path, perms).unwrap();
-                }
-
-                println!("Bundle created successfully: {}", output_path.display());
-
-            } else {
-                // Normal .exki compilation
-                let output_path = output.unwrap_or_else(|| {
-                    input.with_extension("exki")
-                });
-
-                let mut file = fs::File::create(&output_path).map_err(|e| format!("Error creating {}: {}", output_path.display(), e))?;
-                exn::write_exn(&mut file, compiled).map_err(|e| format!("Error writing .exki: {}", e))?;
-                println!("Compiled successfully: {} -> {}", input.display(), output_path.display());
+            match inputs.as_slice() {
+                [] => return Err(CliError::Usage("No .kix files to compile".to_string())),
+                [single] => compile_file(single, output, &opts)?,
+                _ if output.is_some() => return Err(CliError::Usage("--output needs a single input file".to_string())),
+                _ => compile_many(&inputs, &opts)?,
             }
         }
         Commands::Init { name } => {
@@ -741,21 +834,21 @@ fn run() -> Result<(), String> {
             // Create directory structure
             let base = if name == "." { PathBuf::from(".") } else { PathBuf::from(&name) };
             let src_dir = base.join("src");
-            fs::create_dir_all(&src_dir).map_err(|e| format!("Cannot create directory: {}", e))?;
+            fs::create_dir_all(&src_dir).map_err(|e| CliError::Io(format!("Cannot create directory: {}", e)))?;
 
             // Write project.kicomp
             let kicomp_path = base.join("project.kicomp");
             if kicomp_path.exists() {
-                return Err(format!("project.kicomp already exists in '{}'", base.display()));
+                return Err(CliError::Usage(format!("project.kicomp already exists in '{}'", base.display())));
             }
             fs::write(&kicomp_path, kinetix_kicomp::project::scaffold_kicomp(&project_name))
-                .map_err(|e| format!("Cannot write project.kicomp: {}", e))?;
+                .map_err(|e| CliError::Io(format!("Cannot write project.kicomp: {}", e)))?;
 
             // Write src/main.kix
             let main_path = src_dir.join("main.kix");
             if !main_path.exists() {
                 fs::write(&main_path, kinetix_kicomp::project::scaffold_main_kix(&project_name))
-                    .map_err(|e| format!("Cannot write src/main.kix: {}", e))?;
+                    .map_err(|e| CliError::Io(format!("Cannot write src/main.kix: {}", e)))?;
             }
 
             println!("✓ Kinetix project '{}' initialized.", project_name);
@@ -769,37 +862,117 @@ fn run() -> Result<(), String> {
         Commands::Start { config } => {
             run_project(config, true)?;
         }
+        Commands::Pkg { registry, action } => {
+            run_pkg(registry.as_deref(), action)?;
+        }
+        Commands::Fix { file, dry_run } => fix_file(&file, dry_run)?,
         Commands::Lsp => {
-            kinetix_kicomp::lsp::start_server()?;
+            kinetix_kicomp::lsp::start_server().map_err(CliError::Io)?;
         }
         Commands::Version => {
             println!("  Kinetix v{} ({})", env!("CARGO_PKG_VERSION"), kinetix_kicomp::compiler::CURRENT_BUILD);
         }
-        Commands::Shell => {
+        Commands::Bench { suite, iterations } => {
+            run_bench(&suite, iterations)?;
+        }
+        Commands::Shell { script: None, .. } => {
             run_shell();
         }
-        Commands::Docs => {
-            open_docs()?;
+        Commands::Shell { script: Some(script), args } => {
+            let code = run_shell_script(&script, &args)?;
+            if code != 0 {
+                std::process::exit(code);
+            }
+        }
+        Commands::Docs { topic, serve, port } => {
+            open_docs(topic.as_deref(), serve, port).map_err(CliError::Io)?;
+        }
+        Commands::Env => {
+            print!("{}", env_report());
+        }
+        Commands::Toolchain { action } => match action {
+            ToolchainAction::Install { build, from } => println!("{}", toolchain::install(build, from.as_deref()).map_err(CliError::Io)?),
+            ToolchainAction::List => print!("{}", toolchain::list().map_err(CliError::Io)?),
+            ToolchainAction::Use { build, global } => println!("{}", toolchain::use_build(build, global).map_err(CliError::Io)?),
+        },
+        Commands::Internals { what: InternalsAction::Opcodes } => {
+            print!("{}", kinetix_kicomp::ir::opcode_reference());
         }
         Commands::Uninstall => {
-            open_installer("--uninstall")?;
+            open_installer("--uninstall").map_err(CliError::Io)?;
         }
         Commands::Repair => {
-            open_installer("--repair")?;
+            open_installer("--repair").map_err(CliError::Io)?;
         }
-        Commands::Test { path } => {
-             let mut passed = 0;
-             let mut failed = 0;
-             let start_time = std::time::Instant::now();
+        Commands::Test { path, ui, bless, conformance, format } => {
+             let format = test_report::Format::parse(&format).map_err(CliError::Usage)?;
+             let (suite, label, files): (_, _, Vec<TestTarget>) = if ui {
+                 ("ui", "Checking", kix_files(&path).map_err(CliError::Io)?.into_iter().map(TestTarget::file).collect())
+             } else if conformance {
+                 ("conformance", "Conformance", kix_files(&path).map_err(CliError::Io)?.into_iter().map(TestTarget::file).collect())
+             } else {
+                 let mut targets = Vec::new();
+                 unit_tests(&path, &mut targets).map_err(CliError::Io)?;
+                 ("unit", "Running", targets)
+             };
+             let run = |target: &TestTarget| {
+                 if ui {
+                     run_ui_test(&target.file, bless)
+                 } else if conformance {
+                     run_conformance_test(&target.file)
+                 } else {
+                     run_test_file(&target.file, target.block.as_ref().map(|(index, _)| *index))
+                 }
+             };
 
-             run_tests_recursive(&path, &mut passed, &mut failed)?;
+             let mut cases = Vec::new();
+             let start_time = std::time::Instant::now();
+             for target in files {
+                 if format == test_report::Format::Human {
+                     print!("{} {} ... ", label, target.name());
+                     std::io::stdout().flush().unwrap();
+                 }
+                 let case_start = std::time::Instant::now();
+                 let (result, output) = if format == test_report::Format::Human {
+                     (run(&target), String::new())
+                 } else {
+                     kinetix_kivm::builtins::modules::io::capture(|| run(&target))
+                 };
+                 let failure = result.err();
+                 if format == test_report::Format::Human {
+                     match &failure {
+                         None => println!("OK"),
+                         Some(e) => {
+                             println!("FAILED");
+                             if suite == "unit" {
+                                 println!("  Error: {}", e);
+                             } else {
+                                 println!("{}", e);
+                             }
+                         }
+                     }
+                 }
+                 cases.push(test_report::TestCase {
+                     name: target.name(),
+                     duration: case_start.elapsed(),
+                     failure,
+                     output,
+                 });
+             }
 
              let duration = start_time.elapsed();
-             println!("\nTest Summary:");
-             println!("  Passed: {}", passed);
-             println!("  Failed: {}", failed);
-             println!("  Time:   {:.2?}", duration);
-             
+             let failed = cases.iter().filter(|c| c.failure.is_some()).count();
+             match format {
+                 test_report::Format::Human => {
+                     println!("\nTest Summary:");
+                     println!("  Passed: {}", cases.len() - failed);
+                     println!("  Failed: {}", failed);
+                     println!("  Time:   {:.2?}", duration);
+                 }
+                 test_report::Format::Junit => print!("{}", test_report::junit(suite, &cases, duration)),
+                 test_report::Format::Json => println!("{}", test_report::json(suite, &cases, duration)),
+             }
+
              if failed > 0 {
                  std::process::exit(1);
              }
@@ -809,154 +982,406 @@ fn run() -> Result<(), String> {
     Ok(())
 }
 
-/// Build 33: Compile and optionally run a project from a .kicomp configuration file.
-fn run_project(config: PathBuf, should_run: bool) -> Result<(), String> {
-    use kinetix_kicomp::compiler::Compiler;
-
-    // Parse .kicomp project file
-    let project = kinetix_kicomp::project::parse_kicomp(&config)
-        .map_err(|e| format!("{}", e))?;
-
-    println!("Building '{}' v{} ...", project.name, project.version);
-
-    // Resolve dependencies
-    let modules = kinetix_kicomp::resolver::resolve_dependencies(&project)
-        .map_err(|e| format!("{}", e))?;
+/// Options shared by every input of one `kivm compile`.
+#[derive(Clone, Copy)]
+struct CompileOptions {
+    exe: bool,
+    native: bool,
+    o3: bool,
+    strip: bool,
+    metrics: bool,
+    opt_level: u8,
+    kisym: bool,
+    no_cache: bool,
+    instrument: Option<kinetix_kicomp::instrument::Instrument>,
+}
 
-    // Combine all module sources into a single compilation unit
-    let source = kinetix_kicomp::resolver::combine_sources(&modules);
+/// Compiles one `.kix` file to `output` (or a path derived from `input`).
+fn compile_file(input: &Path, output: Option<PathBuf>, opts: &CompileOptions) -> Result<(), CliError> {
+    let CompileOptions { exe, native, o3, strip, metrics, opt_level, kisym, no_cache, instrument } = *opts;
+    let source = fs::read_to_string(input).map_err(|e| CliError::Io(format!("Error reading {}: {}", input.display(), e)))?;
+    
+    if source.trim_start().starts_with("{\\rtf") {
+        return Err(CliError::Usage(format!("'{}' appears to be a Rich Text Format (RTF) document, not a plain text source file. Please save it as plain text using a proper code editor.", input.display())));
+    }
+    
+    // Preprocess includes
+    let source = include::preprocess(&source, input)
+        .map_err(|e| CliError::Compile(format!("Include error: {}", e)))?;
 
-    // Full compilation pipeline
     let lexer = kinetix_language::lexer::Lexer::new(&source);
     let arena = Bump::new();
     let mut parser = kinetix_language::parser::Parser::new(lexer, &arena);
     let ast = parser.parse_program();
 
     if !parser.errors.is_empty() {
-        let errs: Vec<String> = parser.errors.iter().map(|e| e.to_string()).collect();
-        return Err(format_pipeline_error(&config, "Parser", errs));
+        return Err(pipeline_error(input, "Parser", std::mem::take(&mut parser.errors)));
     }
+    let duplicates = include::duplicate_definitions(input, &ast.statements);
+    if !duplicates.is_empty() {
+        return Err(pipeline_error(input, "Symbol Resolution", duplicates));
+    }
+    let gate_warnings = kinetix_kicomp::version_gate::diagnose(&ast.statements, &source)
+        .map_err(|errs| pipeline_error(input, "Version Gate", errs))?;
+    print_warning_diagnostics(input, gate_warnings);
+    print_warning_diagnostics(input, kinetix_kicomp::global_lint::check(&ast.statements));
+    print_warning_diagnostics(input, check_strict(input, &ast)?);
+
+    let bundles = kinetix_kicomp::link::load_imports(&ast.statements, input.parent().unwrap_or(Path::new(".")))
+        .map_err(|errs| pipeline_error(input, "Linker", errs))?;
+    let modules = load_modules(input, &ast.statements, &kinetix_kicomp::capability::Capability::ALL)?;
+
+    let cache = kinetix_home().filter(|_| !no_cache && !plugin::active())
+        .map(|home| kinetix_kicomp::incremental::Cache::open(&home.join("cache").join("incremental"), input));
+    let mut optimized = check_and_lower(&ast.statements, Lowering {
+        file: input,
+        source: &source,
+        modules: &modules,
+        bundles: &bundles,
+        link: true,
+        granted: kinetix_kicomp::capability::Capability::ALL.to_vec(),
+        ask: false,
+        check_only: false,
+        optimize: Some((opt_level, metrics)),
+        cache,
+        warn: &|warnings| print_warning_diagnostics(input, warnings),
+    })?.ok_or_else(|| CliError::Internal("checked but not compiled".to_string()))?;
+
+    // After the cache: it keeps the program as written
+    if let Some(what) = instrument {
+        kinetix_kicomp::instrument::apply(&mut optimized, what);
+    }
+    let symbols = kisym.then(|| kinetix_kicomp::kisym::DebugSymbols::split(&mut optimized, input));
+    kinetix_kicomp::kisym::strip_locals(&mut optimized);
+    let write_symbols = |output_path: &Path| -> Result<(), CliError> {
+        if let Some(symbols) = &symbols {
+            let path = kinetix_kicomp::kisym::path_for(output_path);
+            symbols.write(&path).map_err(|e| CliError::Io(format!("Error writing {}: {}", path.display(), e)))?;
+            output::status(format!("Debug symbols: {}", path.display()));
+        }
+        Ok(())
+    };
+    
+    let compiled = &optimized;
 
-    let symbols = kinetix_kicomp::symbol::resolve_program(&ast.statements)
-        .map_err(|errs| format_pipeline_error(&config, "Symbol Resolution", errs))?;
-
-    let mut traits = kinetix_kicomp::trait_solver::TraitEnvironment::new();
-    for stmt in &ast.statements {
-        if let kinetix_language::ast::Statement::Trait { .. } = stmt {
-            if let Err(e) = traits.register_trait(stmt) {
-                return Err(format_pipeline_error(&config, "Trait Resolver", vec![e]));
+    if native {
+        #[cfg(feature = "llvm")]
+        {
+            let output_path = output.unwrap_or_else(|| {
+                if cfg!(target_os = "windows") {
+                    input.with_extension("exe")
+                } else {
+                    input.with_extension("")
+                }
+            });
+
+            // An explicit `.o` output keeps the old object-only behavior
+            // (e.g. for embedding into an external build); anything else
+            // goes through the real link step (Build 37).
+            let emit_object_only = output_path.extension().map(|e| e == "o").unwrap_or(false);
+            if emit_object_only {
+                output::status(format!("Compiling to native object file: {}", output_path.display()));
+                kinetix_kicomp::llvm_codegen::compile_program_to_object(&ast.statements, &output_path, o3)
+                    .map_err(|e| CliError::Compile(format!("LLVM Codegen error: {}", e)))?;
+            } else {
+                output::status(format!("Compiling to native executable: {}", output_path.display()));
+                kinetix_kicomp::llvm_codegen::compile_program_to_executable(&ast.statements, &output_path, o3, strip)
+                    .map_err(|e| CliError::Compile(format!("Native compilation error: {}", e)))?;
             }
+
+            output::status("Native compilation successful.");
+            return Ok(());
         }
-    }
-    for stmt in &ast.statements {
-        if let kinetix_language::ast::Statement::Impl { .. } = stmt {
-            if let Err(e) = traits.register_impl(stmt) {
-                return Err(format_pipeline_error(&config, "Trait Resolver", vec![e]));
-            }
+        #[cfg(not(feature = "llvm"))]
+        {
+           return Err(CliError::Usage("Native compilation requires 'llvm' feature. Rebuild with --features llvm.".to_string()));
         }
     }
-    traits.validate_cycles().map_err(|e| format_pipeline_error(&config, "Trait Resolver", vec![e]))?;
 
-    let mut hir = kinetix_kicomp::hir::lower_to_hir(&ast.statements, &symbols, &traits);
-    kinetix_kicomp::type_normalize::normalize(&mut hir, &symbols)
-        .map_err(|e| format_pipeline_error(&config, "Type Normalizer", vec![e]))?;
-    let mut ctx = kinetix_kicomp::typeck::TypeContext::new();
-    let constraints = ctx.collect_constraints(&hir);
-    ctx.solve(&constraints).map_err(|errs| {
-        let msgs: Vec<String> = errs.iter().map(|e| e.to_string()).collect();
-        format_pipeline_error(&config, "Type Checker", msgs)
-    })?;
+    if exe {
+        // Create standalone executable
+        let output_path = output.unwrap_or_else(|| {
+            if cfg!(target_os = "windows") {
+                input.with_extension("exe")
+            } else {
+                // On Unix, strip extension to produce a clean binary name
+                input.with_extension("")
+            }
+        });
+
+        // 1. Serialize bytecode to buffer
+        let mut bytecode_buf = Vec::new();
+        exn::write_exn(&mut bytecode_buf, compiled).map_err(|e| CliError::Io(e.to_string()))?;
+        let payload_size = bytecode_buf.len() as u64;
+
+        // 2. Read current executable (the stub)
+        let current_exe = std::env::current_exe().map_err(|e| CliError::Io(e.to_string()))?;
+        let mut stub_data = Vec::new();
+        fs::File::open(&current_exe).map_err(|e| CliError::Io(e.to_string()))?
+            .read_to_end(&mut stub_data).map_err(|e| CliError::Io(e.to_string()))?;
+
+        // 3. Write [Stub] [Payload] [Size] [Sig]
+        let mut file = fs::File::create(&output_path).map_err(|e| CliError::Io(format!("Error creating {}: {}", output_path.display(), e)))?;
+        
+        file.write_all(&stub_data).map_err(|e| CliError::Io(e.to_string()))?;
+        file.write_all(&bytecode_buf).map_err(|e| CliError::Io(e.to_string()))?;
+        file.write_all(&payload_size.to_le_bytes()).map_err(|e| CliError::Io(e.to_string()))?;
+        file.write_all(BUNDLE_SIGNATURE).map_err(|e| CliError::Io(e.to_string()))?;
+
+        // 4. Make executable (Linux/Mac)
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mut perms = fs::metadata(&output_path).unwrap().permissions();
+            perms.set_mode(0o755);
+            fs::set_permissions(&output_path, perms).unwrap();
+        }
 
-    kinetix_kicomp::type_normalize::resolve_method_calls(&mut hir, &symbols, &ctx.substitution)
-        .map_err(|e| format_pipeline_error(&config, "Method Resolution", vec![e]))?;
+        output::status(format!("Bundle created successfully: {}", output_path.display()));
+        write_symbols(&output_path)?;
 
-    kinetix_kicomp::exhaustiveness::check_program_exhaustiveness(&hir, &symbols, &ctx.substitution)
-        .map_err(|e| format_pipeline_error(&config, "Exhaustiveness Checker", vec![e]))?;
+    } else {
+        // Normal .exki compilation
+        let output_path = output.unwrap_or_else(|| {
+            input.with_extension("exki")
+        });
+
+        let mut file = fs::File::create(&output_path).map_err(|e| CliError::Io(format!("Error creating {}: {}", output_path.display(), e)))?;
+        exn::write_exn(&mut file, compiled).map_err(|e| CliError::Io(format!("Error writing .exki: {}", e)))?;
+        output::status(format!("Compiled successfully: {} -> {}", input.display(), output_path.display()));
+        write_symbols(&output_path)?;
+    }
+    Ok(())
+}
 
-    // Build 33: Capabilities derived from .kicomp sandbox section
-    let granted_caps = project.sandbox.to_capabilities();
-    let cap_validator = kinetix_kicomp::capability::CapabilityValidator::new(granted_caps);
-    cap_validator.validate(&hir).map_err(|errs| {
-        let msgs: Vec<String> = errs.iter().map(|e| e.to_string()).collect();
-        format_pipeline_error(&config, "Sandbox Audit Pass", msgs)
-    })?;
+/// `kivm compile` with several inputs: compiles them on up to one thread per
+/// core (one at a time with `--metrics`, so the reports don't interleave),
+/// then prints every failing file's diagnostics and a summary table.
+fn compile_many(inputs: &[PathBuf], opts: &CompileOptions) -> Result<(), CliError> {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{Duration, Instant};
 
-    kinetix_kicomp::hir_validate::validate(&hir).map_err(|errs| {
-        format_pipeline_error(&config, "HIR Integrity", errs)
-    })?;
+    let workers = if opts.metrics {
+        1
+    } else {
+        std::thread::available_parallelism().map_or(1, |n| n.get()).min(inputs.len())
+    };
+    let next = AtomicUsize::new(0);
+    let start = Instant::now();
+    let mut results: Vec<(usize, Result<(), CliError>, Duration)> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| scope.spawn(|| {
+                let mut done = Vec::new();
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(input) = inputs.get(index) else { break };
+                    let file_start = Instant::now();
+                    done.push((index, compile_file(input, None, opts), file_start.elapsed()));
+                }
+                done
+            }))
+            .collect();
+        handles.into_iter().flat_map(|h| h.join().unwrap_or_else(|e| std::panic::resume_unwind(e))).collect()
+    });
+    results.sort_by_key(|(index, ..)| *index);
 
-    let mir = kinetix_kicomp::mir::lower_to_mir(&hir, &ctx.substitution);
-    kinetix_kicomp::borrowck::check_mir(&mir).map_err(|errs| {
-        format_pipeline_error(&config, "Borrow Checker", errs)
-    })?;
+    for (_, result, _) in &results {
+        if let Err(e) = result {
+            report(e);
+        }
+    }
+    let failed = results.iter().filter(|(_, r, _)| r.is_err()).count();
+    let width = inputs.iter().map(|i| i.display().to_string().len()).max().unwrap_or(0);
+    output::status("");
+    for (index, result, duration) in &results {
+        let status = if result.is_ok() { paint("32", "ok    ") } else { paint("1;31", "FAILED") };
+        output::status(format!("  {}  {:<width$}  {:>10.2?}", status, inputs[*index].display().to_string(), duration, width = width));
+    }
+    output::status(format!("  {} compiled, {} failed in {:.2?}", results.len() - failed, failed, start.elapsed()));
 
-    let mir = kinetix_kicomp::monomorphize::monomorphize(&mir).map_err(|e| {
-        format_pipeline_error(&config, "Monomorphization Pass", vec![e])
-    })?;
+    if failed > 0 {
+        return Err(CliError::Compile(format!("{} of {} files failed to compile", failed, inputs.len())));
+    }
+    Ok(())
+}
 
-    kinetix_kicomp::mono_validate::validate(&mir).map_err(|e| {
-        format_pipeline_error(&config, "Post-Mono Validator", vec![e])
-    })?;
+/// Build 33: Compile and optionally run a project from a .kicomp configuration file.
+fn run_project(config: PathBuf, should_run: bool) -> Result<(), CliError> {
+    // Parse .kicomp project file
+    let project = kinetix_kicomp::project::parse_kicomp(&config)
+        .map_err(|e| CliError::Compile(e.to_string()))?;
+    build_project(&config, &project, None, should_run)
+}
 
-    kinetix_kicomp::drop_verify::verify(&mir).map_err(|e| {
-        format_pipeline_error(&config, "Drop Order Verifier", vec![e])
-    })?;
+/// Compiles `project`, described by the file `config`, into `output` when
+/// given, and runs it when `should_run`.
+fn build_project(config: &Path, project: &kinetix_kicomp::project::ProjectConfig, output: Option<&Path>, should_run: bool) -> Result<(), CliError> {
+    output::status(format!("Building '{}' v{} ...", project.name, project.version));
 
-    kinetix_kicomp::ssa_validate::validate(&mir).map_err(|e| {
-        format_pipeline_error(&config, "MIR Integrity", vec![e])
-    })?;
+    // Resolve dependencies
+    let mut modules = kinetix_kicomp::resolver::resolve_dependencies(project)
+        .map_err(|e| CliError::Compile(e.to_string()))?;
+    for module in &mut modules {
+        module.source = include::preprocess(&module.source, &module.entry_path).map_err(CliError::Compile)?;
+    }
 
-    let reactive_graph = kinetix_kicomp::reactive::build_reactive_graph(&hir)
-        .map_err(|e| format!("Reactive Graph Error: {}", e))?;
+    // Combine all module sources into a single compilation unit
+    let source = kinetix_kicomp::resolver::combine_sources(&modules);
 
-    let mut compiler = Compiler::new();
-    let compiled = compiler.compile(&ast.statements, Some(reactive_graph.to_compiled()))
-        .map_err(|e| format!("Compilation error: {}", e))?;
+    // Full compilation pipeline
+    let lexer = kinetix_language::lexer::Lexer::new(&source);
+    let arena = Bump::new();
+    let mut parser = kinetix_language::parser::Parser::new(lexer, &arena);
+    let ast = parser.parse_program();
 
-    println!("✓ Build successful: '{}' v{}", project.name, project.version);
+    if !parser.errors.is_empty() {
+        return Err(pipeline_error(config, "Parser", std::mem::take(&mut parser.errors)));
+    }
+    let gate_warnings = kinetix_kicomp::version_gate::diagnose(&ast.statements, &source)
+        .map_err(|errs| pipeline_error(config, "Version Gate", errs))?;
+    print_warning_diagnostics(config, gate_warnings);
+    print_warning_diagnostics(config, kinetix_kicomp::global_lint::check(&ast.statements));
+    print_warning_diagnostics(config, check_strict(config, &ast)?);
+
+    let bundles = kinetix_kicomp::link::load_imports(&ast.statements, config.parent().unwrap_or(Path::new(".")))
+        .map_err(|errs| pipeline_error(config, "Linker", errs))?;
+    let granted_caps = project.sandbox.to_capabilities();
+    let modules = load_modules(config, &ast.statements, &granted_caps)?;
+
+    // Build 33: Capabilities derived from .kicomp sandbox section
+    let compiled = check_and_lower(&ast.statements, Lowering {
+        file: config,
+        source: &source,
+        modules: &modules,
+        bundles: &bundles,
+        link: true,
+        granted: granted_caps,
+        ask: false,
+        check_only: false,
+        optimize: None,
+        cache: None,
+        warn: &|warnings| print_warning_diagnostics(config, warnings),
+    })?.ok_or_else(|| CliError::Internal("checked but not compiled".to_string()))?;
+    let compiled = &compiled;
+
+    output::status(format!("✓ Build successful: '{}' v{}", project.name, project.version));
+
+    if let Some(path) = output {
+        let mut file = fs::File::create(path).map_err(|e| CliError::Io(format!("Error creating {}: {}", path.display(), e)))?;
+        exn::write_exn(&mut file, compiled).map_err(|e| CliError::Io(format!("Error writing .exki: {}", e)))?;
+        output::status(format!("  -> {}", path.display()));
+    }
 
     if should_run {
-        println!("--- Running ---");
+        output::status("--- Running ---");
         let mut vm = VM::new(compiled.clone());
-        vm.run().map_err(|e| format!("Runtime error: {}", e))?;
+        vm.run().map_err(CliError::Runtime)?;
     }
 
     Ok(())
 }
 
-fn run_tests_recursive(path: &Path, passed: &mut usize, failed: &mut usize) -> Result<(), String> {
+/// `kivm pkg`, on the package in the current directory.
+fn run_pkg(registry: Option<&str>, action: PkgAction) -> Result<(), CliError> {
+    let dir = Path::new(".");
+    let registry = package::Registry::select(registry).map_err(CliError::Usage)?;
+    let message = match action {
+        PkgAction::Init { name } => {
+            let name = if name == "." {
+                std::env::current_dir().ok().and_then(|p| Some(p.file_name()?.to_string_lossy().to_string())).unwrap_or_else(|| "my_package".to_string())
+            } else {
+                name
+            };
+            package::init(dir, &name)
+        }
+        PkgAction::Add { spec, path } => {
+            let (name, requirement) = match (spec.split_once('@'), path) {
+                (Some(_), Some(_)) => return Err(CliError::Usage("give either NAME@REQUIREMENT or --path, not both".to_string())),
+                (Some((name, req)), None) => (name, Some(package::Requirement::Version(req.to_string()))),
+                (None, path) => (spec.as_str(), path.map(package::Requirement::Path)),
+            };
+            package::add(dir, name, requirement, &registry)
+        }
+        PkgAction::Remove { name } => package::remove(dir, &name),
+        PkgAction::Build { run } => {
+            let manifest = Path::new(package::MANIFEST);
+            let project = package::project_config(dir, &registry).map_err(CliError::Compile)?;
+            let exki = PathBuf::from(format!("{}.exki", project.name));
+            return build_project(manifest, &project, Some(&exki), run);
+        }
+        PkgAction::Publish => package::publish(dir, &registry),
+    };
+    println!("{}", message.map_err(CliError::Usage)?);
+    Ok(())
+}
+
+/// One thing `kivm test` runs: a whole file, or one `test` block in it
+/// (its index and name).
+struct TestTarget {
+    file: PathBuf,
+    block: Option<(usize, String)>,
+}
+
+impl TestTarget {
+    fn file(file: PathBuf) -> Self {
+        TestTarget { file, block: None }
+    }
+
+    fn name(&self) -> String {
+        match &self.block {
+            Some((_, name)) => format!("{}::{}", self.file.display(), name),
+            None => self.file.display().to_string(),
+        }
+    }
+}
+
+/// Collects the unit tests under `path` (or in `path` itself), in directory
+/// order: every `test "name" { ... }` block in any `.kix` file, and each
+/// `test_*.kix` file without such blocks as a whole. A file declaring blocks
+/// that does not parse is kept whole, so the run reports its errors.
+fn unit_tests(path: &Path, targets: &mut Vec<TestTarget>) -> Result<(), String> {
     if path.is_dir() {
         for entry in fs::read_dir(path).map_err(|e| format!("Error reading dir {}: {}", path.display(), e))? {
             let entry = entry.map_err(|e| e.to_string())?;
-            let path = entry.path();
-            if path.is_dir() {
-                run_tests_recursive(&path, passed, failed)?;
-            } else {
-                run_tests_recursive(&path, passed, failed)?;
-            }
+            unit_tests(&entry.path(), targets)?;
         }
-    } else if let Some(ext) = path.extension() {
-        if ext == "kix" && path.file_name().unwrap().to_str().unwrap().starts_with("test_") {
-             print!("Running {} ... ", path.display());
-             std::io::stdout().flush().unwrap();
-
-             match run_test_file(path) {
-                 Ok(_) => {
-                     println!("OK");
-                     *passed += 1;
-                 },
-                 Err(e) => {
-                     println!("FAILED");
-                     println!("  Error: {}", e);
-                     *failed += 1;
-                 }
-             }
+        return Ok(());
+    }
+    if path.extension().is_none_or(|ext| ext != "kix") {
+        return Ok(());
+    }
+    let test_file = path.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.starts_with("test_"));
+    let source = fs::read_to_string(path).map_err(|e| format!("Error reading {}: {}", path.display(), e))?;
+    // Most files declare no blocks; skip parsing them
+    if !source.contains("test \"") {
+        if test_file {
+            targets.push(TestTarget::file(path.to_path_buf()));
+        }
+        return Ok(());
+    }
+    let names = include::preprocess(&source, path).ok().and_then(|source| {
+        let arena = Bump::new();
+        let mut parser = kinetix_language::parser::Parser::new(kinetix_language::lexer::Lexer::new(&source), &arena);
+        let ast = parser.parse_program();
+        parser.errors.is_empty().then(|| ast.tests.into_iter().map(|t| t.name).collect::<Vec<_>>())
+    });
+    match names {
+        Some(names) if !names.is_empty() => {
+            targets.extend(names.into_iter().enumerate().map(|(index, name)| TestTarget {
+                file: path.to_path_buf(),
+                block: Some((index, name)),
+            }));
         }
+        Some(_) if !test_file => {}
+        _ => targets.push(TestTarget::file(path.to_path_buf())),
     }
     Ok(())
 }
 
-fn run_test_file(path: &Path) -> Result<(), String> {
+/// Runs a test file, or with `block`, its top-level code followed by that
+/// `test` block compiled as a function of its own (so the block's locals
+/// stay out of the program and of the other blocks).
+fn run_test_file(path: &Path, block: Option<usize>) -> Result<(), String> {
     let source = fs::read_to_string(path).map_err(|e| e.to_string())?;
     
     if source.trim_start().starts_with("{\\rtf") {
@@ -964,7 +1389,7 @@ fn run_test_file(path: &Path) -> Result<(), String> {
     }
     
     // Preprocess includes
-    let source = preprocess_includes(&source, path.parent().unwrap_or(Path::new(".")))?;
+    let source = include::preprocess(&source, path)?;
 
     use kinetix_kicomp::compiler::Compiler;
 
@@ -972,22 +1397,62 @@ fn run_test_file(path: &Path) -> Result<(), String> {
     let lexer = kinetix_language::lexer::Lexer::new(&source);
     let arena = Bump::new();
     let mut parser = kinetix_language::parser::Parser::new(lexer, &arena);
-    let ast = parser.parse_program();
+    let mut ast = parser.parse_program();
 
     if !parser.errors.is_empty() {
         return Err(format!("Parser errors: {:?}", parser.errors));
     }
+    let duplicates = include::duplicate_definitions(path, &ast.statements);
+    if !duplicates.is_empty() {
+        return Err(format!("Symbol errors: {:?}", duplicates.iter().map(ToString::to_string).collect::<Vec<_>>()));
+    }
+    let statements = match block {
+        Some(index) => {
+            use kinetix_language::ast::{Expression, ExpressionKind, Statement};
+            let test = ast.tests.get(index).ok_or_else(|| format!("No test block #{} in {}", index, path.display()))?;
+            let function = arena.alloc_str(&format!("__test_{}", index));
+            let mut statements = std::mem::take(&mut ast.statements);
+            statements.push(Statement::Function {
+                name: function,
+                parameters: Vec::new(),
+                body: test.body,
+                return_type: "void".to_string(),
+                public: false,
+                line: test.line,
+                span: test.body.span(),
+            });
+            statements.push(Statement::Expression {
+                expression: Expression::synthetic(ExpressionKind::Call { function: arena.alloc(Expression::synthetic(ExpressionKind::Identifier(function))), arguments: Vec::new() }),
+                line: test.line,
+                span: test.body.span(),
+            });
+            statements
+        }
+        None => ast.statements,
+    };
 
-    let symbols = kinetix_kicomp::symbol::resolve_program(&ast.statements)
-        .map_err(|errs| format!("Symbol errors: {:?}", errs))?;
+    let bundles = kinetix_kicomp::link::load_imports(&statements, path.parent().unwrap_or(Path::new(".")))
+        .map_err(|errs| format!("Linker errors: {:?}", errs.iter().map(ToString::to_string).collect::<Vec<_>>()))?;
+    let modules = load_modules(path, &statements, &kinetix_kicomp::capability::Capability::ALL).map_err(|e| e.to_string())?;
+    let mut externs = kinetix_kicomp::link::externs(&bundles);
+    externs.extend(kinetix_kicomp::module_graph::externs(&modules));
+    let symbols = kinetix_kicomp::symbol::resolve_program_with_externs(&statements, &externs)
+        .map_err(|errs| format!("Symbol errors: {:?}", errs.iter().map(ToString::to_string).collect::<Vec<_>>()))?;
     let traits = kinetix_kicomp::trait_solver::TraitEnvironment::new();
-    let hir = kinetix_kicomp::hir::lower_to_hir(&ast.statements, &symbols, &traits);
+    let hir = kinetix_kicomp::hir::lower_to_hir(&statements, &symbols, &traits);
     let reactive_graph = kinetix_kicomp::reactive::build_reactive_graph(&hir)
-        .map_err(|e| format!("Reactive Graph Error: {}", e))?;
+        .map_err(|errs| format!("Reactive graph errors: {:?}", errs.iter().map(ToString::to_string).collect::<Vec<_>>()))?;
 
     let mut compiler = Compiler::new();
-    let compiled = compiler.compile(&ast.statements, Some(reactive_graph.to_compiled()))
-        .map_err(|e| format!("Compilation error: {}", e))?;
+    compiler.close_on_return(kinetix_kicomp::resource_lint::closes_on_return(&hir));
+    for module in &modules {
+        compiler.link_module(module);
+    }
+    for bundle in &bundles {
+        compiler.link_bundle(bundle);
+    }
+    let compiled = compiler.compile(&statements, Some(reactive_graph.to_compiled()))
+        .map_err(|errs| format!("Compilation errors: {:?}", errs.iter().map(ToString::to_string).collect::<Vec<_>>()))?;
 
     // 3. Running
     let mut vm = VM::new(compiled.clone());
@@ -997,48 +1462,659 @@ fn run_test_file(path: &Path) -> Result<(), String> {
     Ok(())
 }
 
-fn preprocess_includes(source: &str, base_path: &Path) -> Result<String, String> {
-    let mut result = String::new();
-    for line in source.lines() {
-        if line.trim().starts_with("#include") {
-            // Parse path: #include "path/to/file.kix"
-            let parts: Vec<&str> = line.split_whitespace().collect();
-            if parts.len() >= 2 {
-                let path_str = parts[1].trim_matches('"');
-                let include_path = base_path.join(path_str);
-                
-                if include_path.exists() {
-                     let included_source = fs::read_to_string(&include_path)
-                        .map_err(|e| format!("Failed to read include {}: {}", include_path.display(), e))?;
-                     // Recursive include
-                     let processed = preprocess_includes(&included_source, include_path.parent().unwrap_or(Path::new(".")))?;
-                     result.push_str(&processed);
-                     result.push('\n');
-                } else {
-                    return Err(format!("Include not found: {}", include_path.display()));
+/// `.kix` files under `path` (or `path` itself), sorted, for `kivm test --ui`
+/// and `kivm compile`.
+fn kix_files(path: &Path) -> Result<Vec<PathBuf>, String> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut files = Vec::new();
+    for entry in fs::read_dir(path).map_err(|e| format!("Error reading dir {}: {}", path.display(), e))? {
+        let entry = entry.map_err(|e| e.to_string())?.path();
+        if entry.is_dir() {
+            files.extend(kix_files(&entry)?);
+        } else if entry.extension().is_some_and(|ext| ext == "kix") {
+            files.push(entry);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Golden-file check: runs `file` through the `exec` pipeline and compares
+/// the diagnostic it ends with (empty if it runs cleanly) against
+/// `file.stderr`, a missing file meaning "no diagnostic". The file's
+/// directory prints as `$DIR` so expectations don't depend on where the
+/// tree is checked out. With `bless`, the `.stderr` file is rewritten
+/// instead (and removed when there is nothing to expect).
+fn run_ui_test(file: &Path, bless: bool) -> Result<(), String> {
+    let source = fs::read_to_string(file).map_err(|e| format!("Error reading {}: {}", file.display(), e))?;
+    let mut actual = match exec_source(file, &source, &ExecOptions::default()) {
+        Ok(()) => String::new(),
+        Err(e) => format!("{}\n", e),
+    };
+    if let Some(dir) = file.parent().filter(|d| !d.as_os_str().is_empty()) {
+        actual = actual.replace(&format!("{}{}", dir.display(), std::path::MAIN_SEPARATOR), "$DIR/");
+    }
+
+    let expected_path = file.with_extension("stderr");
+    if bless {
+        let result = if actual.is_empty() {
+            fs::remove_file(&expected_path).or_else(|e| if e.kind() == std::io::ErrorKind::NotFound { Ok(()) } else { Err(e) })
+        } else {
+            fs::write(&expected_path, &actual)
+        };
+        return result.map_err(|e| format!("Error writing {}: {}", expected_path.display(), e));
+    }
+    let expected = fs::read_to_string(&expected_path).unwrap_or_default();
+    if expected == actual {
+        return Ok(());
+    }
+    let mut diff = format!("  Diagnostics differ from {} (rerun with --bless to accept):\n", expected_path.display());
+    for line in expected.lines() {
+        diff.push_str(&format!("  - {}\n", line));
+    }
+    for line in actual.lines() {
+        diff.push_str(&format!("  + {}\n", line));
+    }
+    Err(diff.trim_end().to_string())
+}
+
+/// Backends `kivm test --conformance` runs every spec program on.
+const CONFORMANCE_BACKENDS: &[&str] = if cfg!(feature = "llvm") { &["vm", "llvm"] } else { &["vm"] };
+
+/// Runs the spec program `file` with `kivm exec` once per backend
+/// (selected through `KINETIX_BACKEND`) and checks its stdout against
+/// `file.stdout` and its exit code against `file.exit` (0 when absent).
+/// Backends that disagree with each other are reported as such, so a
+/// divergence isn't mistaken for a wrong expectation.
+fn run_conformance_test(file: &Path) -> Result<(), String> {
+    let expected_stdout = fs::read_to_string(file.with_extension("stdout")).unwrap_or_default();
+    let expected_exit = match fs::read_to_string(file.with_extension("exit")) {
+        Ok(code) => code.trim().parse::<i32>().map_err(|_| format!("  {}: exit code must be an integer", file.with_extension("exit").display()))?,
+        Err(_) => 0,
+    };
+    let exe = std::env::current_exe().map_err(|e| format!("Cannot locate kivm: {}", e))?;
+
+    let mut results = Vec::new();
+    for backend in CONFORMANCE_BACKENDS {
+        let out = std::process::Command::new(&exe)
+            .arg("exec")
+            .arg(file)
+            .env("KINETIX_BACKEND", backend)
+            .output()
+            .map_err(|e| format!("  Cannot run kivm: {}", e))?;
+        let stdout = String::from_utf8_lossy(&out.stdout).replace("\r\n", "\n");
+        results.push((*backend, stdout, out.status.code().unwrap_or(-1)));
+    }
+
+    let mut report = String::new();
+    for (backend, stdout, code) in &results {
+        if *stdout != expected_stdout {
+            report.push_str(&format!("  [{}] stdout differs:\n", backend));
+            for line in expected_stdout.lines() {
+                report.push_str(&format!("  - {}\n", line));
+            }
+            for line in stdout.lines() {
+                report.push_str(&format!("  + {}\n", line));
+            }
+        }
+        if *code != expected_exit {
+            report.push_str(&format!("  [{}] exit code {} (expected {})\n", backend, code, expected_exit));
+        }
+    }
+    if let Some((first, rest)) = results.split_first() {
+        for other in rest.iter().filter(|r| r.1 != first.1 || r.2 != first.2) {
+            report.push_str(&format!("  backends disagree: {} and {}\n", first.0, other.0));
+        }
+    }
+    if report.is_empty() { Ok(()) } else { Err(report.trim_end().to_string()) }
+}
+
+/// Runs the bytecode passes for `-O` level `opt_level` (0: none, 1: the
+/// per-function passes, 2: also inlining), printing the metrics report when
+/// asked.
+fn optimize_program(program: &mut kinetix_kicomp::ir::CompiledProgram, opt_level: u8, metrics: bool, session_phases: &[kinetix_kicomp::session::CachedPhase]) {
+    use kinetix_kicomp::metrics::{timed, CompilerMetrics};
+
+    let mut met = CompilerMetrics::new();
+    met.total_instructions_before = CompilerMetrics::count_instructions(program);
+    if opt_level >= 1 {
+        let (_, ms) = timed(|| kinetix_kicomp::opt::optimize(program));
+        met.record_phase("All Bytecode Passes", met.total_instructions_before, CompilerMetrics::count_instructions(program), ms);
+    }
+    if opt_level >= 2 {
+        let before = CompilerMetrics::count_instructions(program);
+        let (inlined, ms) = timed(|| kinetix_kicomp::opt::inline_small_functions(program));
+        met.inlined_calls = inlined;
+        met.record_phase("Inlining (-O2)", before, CompilerMetrics::count_instructions(program), ms);
+    }
+    met.total_instructions_after = CompilerMetrics::count_instructions(program);
+    met.session_phases = session_phases.to_vec();
+    if metrics {
+        met.print_report();
+    }
+}
+
+/// How `exec_source` builds and runs a program.
+struct ExecOptions<'a> {
+    /// Binds stdin to this variable.
+    stdin_var: Option<&'a str>,
+    /// Print the value of a trailing expression (used by `eval`).
+    echo_result: bool,
+    audit: bool,
+    metrics: bool,
+    opt_level: u8,
+    /// With `--sandbox`, the capabilities granted up front; others are
+    /// asked for in a terminal. `None` grants every capability.
+    sandbox: Option<Vec<kinetix_kicomp::capability::Capability>>,
+    /// Stop after the checks, before compiling and running (`kivm fix`).
+    check_only: bool,
+    /// Collects the warnings instead of printing them (`kivm fix`).
+    warnings: Option<&'a std::cell::RefCell<Vec<Diagnostic>>>,
+    instrument: Option<kinetix_kicomp::instrument::Instrument>,
+}
+
+impl Default for ExecOptions<'_> {
+    fn default() -> Self {
+        ExecOptions { stdin_var: None, echo_result: false, audit: false, metrics: false, opt_level: 1, sandbox: None, check_only: false, warnings: None, instrument: None }
+    }
+}
+
+/// How many times `kivm fix` checks a file again after fixing it: a fix
+/// can let the checks get further, to problems they didn't reach before.
+const FIX_ROUNDS: usize = 8;
+
+/// Checks `file` and applies the first fix of each error and warning that
+/// has one, then checks again until nothing is left to fix. Errors without
+/// a fix, or in another file, are left for the user and fail the command.
+fn fix_file(file: &Path, dry_run: bool) -> Result<(), CliError> {
+    use kinetix_language::diagnostic::Fix;
+
+    let original = fs::read_to_string(file).map_err(|e| CliError::Io(format!("Error reading {}: {}", file.display(), e)))?;
+    let mut source = original.clone();
+    let mut applied = Vec::new();
+    let mut unfixed = 0;
+    for _ in 0..FIX_ROUNDS {
+        let warnings = std::cell::RefCell::new(Vec::new());
+        let opts = ExecOptions { check_only: true, warnings: Some(&warnings), ..ExecOptions::default() };
+        let errors = match exec_source(file, &source, &opts) {
+            Ok(()) => Vec::new(),
+            Err(CliError::Diagnostics { diagnostics, .. }) => diagnostics,
+            Err(e) => return Err(e),
+        };
+        let diagnostics: Vec<Diagnostic> = errors.into_iter().chain(warnings.into_inner()).collect();
+        let fixed: Vec<(&Diagnostic, &Fix)> = diagnostics.iter()
+            .filter(|d| d.file.is_none())
+            .filter_map(|d| Some((d, d.fixes.first()?)))
+            .collect();
+        unfixed = diagnostics.iter().filter(|d| d.severity == Severity::Error && (d.file.is_some() || d.fixes.is_empty())).count();
+        if fixed.is_empty() {
+            break;
+        }
+        applied.extend(fixed.iter().map(|(d, fix)| {
+            let line = d.span().map_or(fix.at.line, |span| span.line);
+            format!("{}:{}: {}", file.display(), line, fix.message)
+        }));
+        source = Fix::apply(&source, &fixed.iter().map(|(_, fix)| *fix).collect::<Vec<_>>());
+    }
+
+    if dry_run {
+        print!("{}", unified_diff(&file.display().to_string(), &original, &source));
+    } else if applied.is_empty() {
+        output::status(format!("{}: nothing to fix", file.display()));
+    } else {
+        fs::write(file, &source).map_err(|e| CliError::Io(format!("Error writing {}: {}", file.display(), e)))?;
+        applied.iter().for_each(output::status);
+    }
+    if unfixed > 0 {
+        return Err(CliError::Compile(format!("{} error(s) have no fix; run `kivm exec {}` to see them", unfixed, file.display())));
+    }
+    Ok(())
+}
+
+/// Lines of context around each change in `unified_diff`.
+const DIFF_CONTEXT: usize = 2;
+
+/// The changes from `old` to `new` as a unified diff of `name`, or nothing
+/// when they are the same.
+fn unified_diff(name: &str, old: &str, new: &str) -> String {
+    let (old, new): (Vec<&str>, Vec<&str>) = (old.lines().collect(), new.lines().collect());
+    // Walk both; where they differ, skip the fewest lines on either side
+    // that brings them back in step.
+    let mut ops: Vec<(char, &str)> = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            ops.push((' ', old[i]));
+            (i, j) = (i + 1, j + 1);
+            continue;
+        }
+        let remaining = (old.len() - i) + (new.len() - j);
+        let (skip_old, skip_new) = (1..remaining)
+            .find_map(|k| (0..=k).map(|x| (x, k - x)).find(|&(x, y)| i + x < old.len() && j + y < new.len() && old[i + x] == new[j + y]))
+            .unwrap_or((old.len() - i, new.len() - j));
+        ops.extend(old[i..i + skip_old].iter().map(|line| ('-', *line)));
+        ops.extend(new[j..j + skip_new].iter().map(|line| ('+', *line)));
+        (i, j) = (i + skip_old, j + skip_new);
+    }
+
+    let changed: Vec<usize> = (0..ops.len()).filter(|&k| ops[k].0 != ' ').collect();
+    let Some(&first) = changed.first() else { return String::new() };
+    let mut out = format!("--- {}\n+++ {}\n", name, name);
+    let mut hunk = (first.saturating_sub(DIFF_CONTEXT), first);
+    let mut hunks = Vec::new();
+    for &k in &changed[1..] {
+        if k - hunk.1 > 2 * DIFF_CONTEXT {
+            hunks.push(hunk);
+            hunk.0 = k - DIFF_CONTEXT;
+        }
+        hunk.1 = k;
+    }
+    hunks.push(hunk);
+    for (start, last) in hunks {
+        let end = (last + DIFF_CONTEXT + 1).min(ops.len());
+        let line_of = |side: char| ops[..start].iter().filter(|(op, _)| *op == ' ' || *op == side).count() + 1;
+        let count = |side: char| ops[start..end].iter().filter(|(op, _)| *op == ' ' || *op == side).count();
+        out.push_str(&format!("@@ -{},{} +{},{} @@\n", line_of('-'), count('-'), line_of('+'), count('+')));
+        for (op, line) in &ops[start..end] {
+            out.push_str(&format!("{}{}\n", op, line));
+        }
+    }
+    out
+}
+
+/// `--allow` values.
+fn parse_capability(name: &str) -> Result<kinetix_kicomp::capability::Capability, String> {
+    kinetix_kicomp::capability::Capability::from_flag(name)
+        .ok_or_else(|| format!("unknown capability '{}' (expected fs-read, fs-write, net, sysinfo, exec or threads)", name))
+}
+
+/// `--stdin-var` names: a single identifier, not a keyword.
+fn parse_identifier(name: &str) -> Result<String, String> {
+    let mut lexer = kinetix_language::lexer::Lexer::new(name);
+    match (lexer.next_token(), lexer.next_token()) {
+        (kinetix_language::lexer::Token::Identifier(ident), kinetix_language::lexer::Token::EOF) if ident == name => Ok(name.to_string()),
+        _ => Err(format!("'{}' is not a valid variable name", name)),
+    }
+}
+
+/// How `check_and_lower` treats a program.
+struct Lowering<'a> {
+    /// Names the program in diagnostics.
+    file: &'a Path,
+    source: &'a str,
+    /// The source modules and bundles the program imports.
+    modules: &'a [kinetix_kicomp::module_graph::Module],
+    bundles: &'a [kinetix_kicomp::link::Bundle],
+    /// Whether `modules` and `bundles` are linked into the program; not
+    /// for a module, whose importer links them.
+    link: bool,
+    /// The capabilities the program may use.
+    granted: Vec<kinetix_kicomp::capability::Capability>,
+    /// In a terminal, ask for the capabilities missing from `granted`.
+    ask: bool,
+    /// Stop after the checks, before compiling.
+    check_only: bool,
+    /// The optimization level and `--metrics`; `None` keeps the bytecode
+    /// as compiled.
+    optimize: Option<(u8, bool)>,
+    /// The bytecode and MIR checks of an earlier compile, for `kivm compile`.
+    cache: Option<kinetix_kicomp::incremental::Cache>,
+    warn: &'a dyn Fn(Vec<Diagnostic>),
+}
+
+/// The checking pipeline every compile goes through, from symbol resolution
+/// to bytecode, for the program `statements`. `None` with `check_only`.
+fn check_and_lower(statements: &[kinetix_language::ast::Statement], lowering: Lowering) -> Result<Option<kinetix_kicomp::ir::CompiledProgram>, CliError> {
+    use kinetix_kicomp::capability::CapabilityValidator;
+    use kinetix_kicomp::incremental;
+    let Lowering { file, source, modules, bundles, link, mut granted, ask, check_only, optimize, mut cache, warn } = lowering;
+
+    let mut externs = kinetix_kicomp::link::externs(bundles);
+    externs.extend(kinetix_kicomp::module_graph::externs(modules));
+    let symbols = kinetix_kicomp::symbol::resolve_program_with_externs(statements, &externs)
+        .map_err(|errs| symbol_error(file, statements, modules, errs))?;
+
+    let mut session = kinetix_kicomp::session::Session::new();
+    let traits = session.trait_environment(statements)
+        .map_err(|e| pass_error(file, "Trait Resolver", e))?;
+
+    let mut hir = kinetix_kicomp::hir::lower_to_hir(statements, &symbols, traits);
+    kinetix_kicomp::type_normalize::normalize(&mut hir, &symbols).map_err(|e| pass_error(file, "Type Normalizer", e))?;
+    let mut ctx = kinetix_kicomp::typeck::TypeContext::new();
+    let constraints = ctx.collect_constraints(&hir);
+    ctx.solve(&constraints).map_err(|errs| pipeline_error(file, "Type Checker", errs))?;
+    kinetix_kicomp::symbol::check_typed_field_access(&hir, &symbols, &ctx.substitution)
+        .map_err(|errs| pipeline_error(file, "Symbol Resolution", errs))?;
+
+    // Post-TypeChecker: resolve method calls now that types are concrete
+    kinetix_kicomp::type_normalize::resolve_method_calls(&mut hir, &symbols, &ctx.substitution)
+        .map_err(|e| pass_error(file, "Method Resolution", e))?;
+
+    kinetix_kicomp::exhaustiveness::check_program_exhaustiveness(&hir, &symbols, &ctx.substitution)
+        .map_err(|errs| pipeline_error(file, "Exhaustiveness Checker", errs))?;
+    output::step("resolved symbols, traits and types");
+
+    // M2.6 Capability IR Enforcement Pass (Build 19)
+    let mut cap_result = CapabilityValidator::new(granted.clone()).validate(&hir);
+    if let Err(errs) = &cap_result
+        && ask
+        && permissions::interactive()
+    {
+        let store = permissions::store_path().map_err(CliError::Io)?;
+        granted.extend(permissions::request(file, source, errs, &store, permissions::ask_terminal).map_err(CliError::Io)?);
+        cap_result = CapabilityValidator::new(granted).validate(&hir);
+    }
+    cap_result.map_err(|errs| pipeline_error(file, "Sandbox Audit Pass", errs.iter().map(|e| e.diagnostic()).collect()))?;
+
+    // Build 20: HIR Integrity Validation Pass
+    warn(kinetix_kicomp::hir_validate::validate(&hir).map_err(|errs| pipeline_error(file, "HIR Integrity", errs))?);
+    warn(kinetix_kicomp::resource_lint::check(&hir));
+    warn(plugin_stage(file, Stage::Hir, Unit::Hir(&mut hir))?);
+
+    // Unchanged since the last compile: reuse its bytecode. Otherwise
+    // re-check only the MIR functions that changed.
+    let (opt_level, metrics) = optimize.unwrap_or((0, false));
+    let key = cache.as_ref().map(|_| {
+        let linked: Vec<&kinetix_kicomp::ir::CompiledProgram> = modules.iter().map(|m| &m.program)
+            .chain(bundles.iter().map(|b| &b.program))
+            .collect();
+        incremental::Cache::key(&hir, source, &linked, opt_level)
+    });
+    if let (Some(cache), Some(key)) = (&mut cache, key)
+        && let Some(mut program) = cache.program(key)
+    {
+        cache.reused_program();
+        output::note(format!("{}: unchanged, reusing the cached bytecode", file.display()));
+        let phases = [session.phases.as_slice(), cache.phases.as_slice()].concat();
+        optimize_program(&mut program, 0, metrics, &phases);
+        return Ok(Some(program));
+    }
+
+    let mut mir = kinetix_kicomp::mir::lower_to_mir(&hir, &ctx.substitution);
+    warn(plugin_stage(file, Stage::Mir, Unit::Mir(&mut mir))?);
+    incremental::run_check(cache.as_mut(), incremental::Check::Borrow, &mir, kinetix_kicomp::borrowck::check_mir)
+        .map_err(|errs| pipeline_error(file, "Borrow Checker", errs))?;
+    let mut mir = kinetix_kicomp::monomorphize::monomorphize(&mir).map_err(|e| pass_error(file, "Monomorphization Pass", e))?;
+    kinetix_kicomp::mono_validate::validate(&mir).map_err(|e| pass_error(file, "Post-Mono Validator", e))?;
+    incremental::run_check(cache.as_mut(), incremental::Check::Integrity, &mir, |mir| {
+        kinetix_kicomp::drop_verify::verify(mir).map_err(|e| pass_error(file, "Drop Order Verifier", e))?;
+        // Build 20: MIR/SSA Integrity Validation Pass
+        kinetix_kicomp::ssa_validate::validate(mir).map_err(|e| pass_error(file, "MIR Integrity", e))
+    })?;
+    warn(plugin_stage(file, Stage::CheckedMir, Unit::Mir(&mut mir))?);
+    output::step("borrow check and MIR validation passed");
+    if check_only {
+        return Ok(None);
+    }
+
+    let reactive_graph = session.reactive_graph(&hir)
+        .map_err(|errs| pipeline_error(file, "Reactive Graph", errs))?;
+    let mut compiler = kinetix_kicomp::compiler::Compiler::new();
+    compiler.close_on_return(kinetix_kicomp::resource_lint::closes_on_return(&hir));
+    if link {
+        for module in modules {
+            compiler.link_module(module);
+        }
+        for bundle in bundles {
+            compiler.link_bundle(bundle);
+        }
+    }
+    compiler.compile(statements, Some(reactive_graph.to_compiled()))
+        .map_err(|errs| pipeline_error(file, "Compiler", errs))?;
+    let mut program = compiler.program;
+    warn(plugin_stage(file, Stage::Bytecode, Unit::Bytecode(&mut program))?);
+
+    // Build 35: Bytecode Optimization Passes
+    if optimize.is_some() {
+        let phases = [session.phases.as_slice(), cache.as_ref().map_or(&[], |c| c.phases.as_slice())].concat();
+        optimize_program(&mut program, opt_level, metrics, &phases);
+    }
+    if let (Some(cache), Some(key)) = (&mut cache, key)
+        && let Err(e) = cache.store(key, &program)
+    {
+        output::warning(format!("incremental cache not updated: {}", e));
+    }
+    Ok(Some(program))
+}
+
+/// Loads the source modules the program in `file` imports (`import a.b`),
+/// each checked and compiled as its own unit under the capabilities `granted`.
+fn load_modules(file: &Path, statements: &[kinetix_language::ast::Statement], granted: &[kinetix_kicomp::capability::Capability]) -> Result<Vec<kinetix_kicomp::module_graph::Module>, CliError> {
+    kinetix_kicomp::module_graph::load(file, statements, |path, source, loaded| compile_module(path, source, loaded, granted))
+}
+
+/// One imported module through the checking pipeline. Its diagnostics name
+/// its own file and lines.
+fn compile_module(
+    file: &Path,
+    source: &str,
+    loaded: &[kinetix_kicomp::module_graph::Module],
+    granted: &[kinetix_kicomp::capability::Capability],
+) -> Result<kinetix_kicomp::ir::CompiledProgram, CliError> {
+    use kinetix_language::ast::Statement;
+
+    let arena = Bump::new();
+    let mut parser = kinetix_language::parser::Parser::new(kinetix_language::lexer::Lexer::new(source), &arena);
+    let ast = parser.parse_program();
+    if !parser.errors.is_empty() {
+        return Err(pipeline_error(file, "Parser", std::mem::take(&mut parser.errors)));
+    }
+    let bundles: Vec<Diagnostic> = ast.statements.iter()
+        .filter_map(|s| match s {
+            Statement::ImportBundle { span, .. } => Some(
+                Diagnostic::error(codes::LINK, "`import bundle` is only supported in the program being built, not in a module").at(*span)
+            ),
+            _ => None,
+        })
+        .collect();
+    if !bundles.is_empty() {
+        return Err(pipeline_error(file, "Linker", bundles));
+    }
+    let gate_warnings = kinetix_kicomp::version_gate::diagnose(&ast.statements, source)
+        .map_err(|errs| pipeline_error(file, "Version Gate", errs))?;
+    print_warning_diagnostics(file, gate_warnings);
+    print_warning_diagnostics(file, kinetix_kicomp::global_lint::check(&ast.statements));
+    print_warning_diagnostics(file, check_strict(file, &ast)?);
+
+    // A module runs with the importer's capabilities, no more; the importer
+    // links what it imports
+    check_and_lower(&ast.statements, Lowering {
+        file,
+        source,
+        modules: loaded,
+        bundles: &[],
+        link: false,
+        granted: granted.to_vec(),
+        ask: false,
+        check_only: false,
+        optimize: None,
+        cache: None,
+        warn: &|warnings| print_warning_diagnostics(file, warnings),
+    }).map_err(|e| match e {
+        CliError::Compile(message) => CliError::Compile(format!("{}: {}", file.display(), message)),
+        e => e,
+    })?.ok_or_else(|| CliError::Internal("checked but not compiled".to_string()))
+}
+
+/// The `exec` pipeline: checks, compiles and runs `source`. `file` names it
+/// in diagnostics and anchors relative bundle imports.
+fn exec_source(file: &Path, source: &str, opts: &ExecOptions) -> Result<(), CliError> {
+    let ExecOptions { stdin_var, echo_result, audit, metrics, opt_level, instrument, .. } = *opts;
+    let warn = |warnings: Vec<Diagnostic>| match opts.warnings {
+        Some(collected) => collected.borrow_mut().extend(warnings.into_iter().map(|mut w| {
+            locate_diagnostic(file, &mut w);
+            w
+        })),
+        None => print_warning_diagnostics(file, warnings),
+    };
+
+    let lexer = kinetix_language::lexer::Lexer::new(source);
+    let arena = Bump::new();
+    let mut parser = kinetix_language::parser::Parser::new(lexer, &arena);
+    let mut ast = parser.parse_program();
+
+    if !parser.errors.is_empty() {
+        return Err(pipeline_error(file, "Parser", std::mem::take(&mut parser.errors)));
+    }
+    let gate_warnings = kinetix_kicomp::version_gate::diagnose(&ast.statements, source)
+        .map_err(|errs| pipeline_error(file, "Version Gate", errs))?;
+    warn(gate_warnings);
+    warn(kinetix_kicomp::global_lint::check(&ast.statements));
+    warn(check_strict(file, &ast)?);
+    output::step(format!("parsed {} top-level statements", ast.statements.len()));
+    if let Some(name) = stdin_var {
+        ast.statements.insert(0, bind_stdin(name, &arena));
+    }
+    if echo_result {
+        echo_last_expression(&mut ast.statements, &arena);
+    }
+
+    let bundles = kinetix_kicomp::link::load_imports(&ast.statements, file.parent().unwrap_or(Path::new(".")))
+        .map_err(|errs| pipeline_error(file, "Linker", errs))?;
+    let granted = opts.sandbox.clone().unwrap_or_else(|| kinetix_kicomp::capability::Capability::ALL.to_vec());
+    let modules = load_modules(file, &ast.statements, &granted)?;
+
+    let lowered = check_and_lower(&ast.statements, Lowering {
+        file,
+        source,
+        modules: &modules,
+        bundles: &bundles,
+        link: true,
+        granted,
+        ask: opts.sandbox.is_some(),
+        check_only: opts.check_only,
+        optimize: Some((opt_level, metrics)),
+        cache: None,
+        warn: &warn,
+    })?;
+    let Some(mut optimized) = lowered else { return Ok(()) };
+
+    #[cfg(feature = "llvm")]
+    {
+        // KINETIX_BACKEND=vm skips the JIT; KINETIX_BACKEND=llvm turns a JIT
+        // failure into an error instead of a fallback (conformance runs).
+        let backend = std::env::var("KINETIX_BACKEND").unwrap_or_default();
+        if backend != "vm" {
+            // Try LLVM JIT first
+            let jit_res = kinetix_kicomp::llvm_codegen::run_program_jit(&ast.statements);
+            match jit_res {
+                Ok(_) => return Ok(()), // JIT successful
+                Err(e) if backend == "llvm" => return Err(CliError::Compile(format!("LLVM backend: {}", e))),
+                Err(e) => {
+                    // JIT failed (likely due to unimplemented AST nodes in LLVM backend yet).
+                    output::note(format!("LLVM JIT not available for this script ({}); falling back to KiVM", e));
                 }
-            } else {
-                 return Err("Invalid include syntax".to_string());
             }
-        } else {
-            result.push_str(line);
-            result.push('\n');
         }
     }
-    Ok(result)
+
+    if let Some(what) = instrument {
+        kinetix_kicomp::instrument::apply(&mut optimized, what);
+    }
+
+    if audit {
+        println!("[✓] Formal Invariants Certified");
+    }
+
+    output::step("running on KiVM");
+    let mut vm = VM::new(optimized);
+    vm.run().map_err(CliError::Runtime)?;
+    
+    if audit {
+        println!("\n=== Audit Report ===");
+        println!("Total Heap Allocations: {}", vm.mem_stats.total_heap_allocations);
+    }
+    Ok(())
+}
+
+/// `let <name>: str = io.stdin_all()`, prepended for `--stdin-var`.
+fn bind_stdin<'a>(name: &str, arena: &'a Bump) -> kinetix_language::ast::Statement<'a> {
+    use kinetix_language::ast::{Expression, ExpressionKind, Statement};
+    let io = arena.alloc(Expression::synthetic(ExpressionKind::Identifier("io")));
+    let stdin_all = arena.alloc(Expression::synthetic(ExpressionKind::MemberAccess { object: io, member: "stdin_all" }));
+    Statement::Let {
+        name: arena.alloc_str(name),
+        mutable: false,
+        type_hint: Some("str".to_string()),
+        value: Expression::synthetic(ExpressionKind::Call { function: stdin_all, arguments: vec![] }),
+        public: false,
+        line: 1,
+        span: kinetix_language::span::Span::default(),
+    }
+}
+
+/// Rewrites a trailing expression statement `e` that doesn't already print
+/// into `io.echo(e)`, which prints it unless it is null.
+fn echo_last_expression<'a>(statements: &mut [kinetix_language::ast::Statement<'a>], arena: &'a Bump) {
+    use kinetix_language::ast::{Expression, ExpressionKind, Statement};
+    let Some(Statement::Expression { expression, .. }) = statements.last_mut() else { return };
+    if matches!(&expression.kind, ExpressionKind::Call { function: Expression { kind: ExpressionKind::Identifier(name), .. }, .. } if *name == "println" || *name == "print") {
+        return;
+    }
+    let value = std::mem::replace(expression, Expression::synthetic(ExpressionKind::Null));
+    let span = value.span;
+    let io = arena.alloc(Expression::synthetic(ExpressionKind::Identifier("io")));
+    *expression = Expression::new(ExpressionKind::Call {
+        function: arena.alloc(Expression::synthetic(ExpressionKind::MemberAccess { object: io, member: "echo" })),
+        arguments: vec![value],
+    }, span);
+}
+
+/// `kivm bench`: prints the build, then one line per case (see `bench.rs`).
+fn run_bench(suite: &str, iterations: u32) -> Result<(), CliError> {
+    println!("suite={} version={} build={}", suite, env!("CARGO_PKG_VERSION"), kinetix_kicomp::compiler::CURRENT_BUILD);
+    match suite {
+        "vm" => run_vm_bench(iterations).map_err(CliError::Internal),
+        "parser" => run_parser_bench(iterations).map_err(CliError::Internal),
+        other => Err(CliError::Usage(format!("Unknown benchmark suite '{}' (available: vm, parser)", other))),
+    }
+}
+
+fn run_vm_bench(iterations: u32) -> Result<(), String> {
+    for result in bench::run_suite("vm", bench::VM_SUITE, iterations, bench::compile)? {
+        println!("{}", result);
+    }
+    Ok(())
+}
+
+/// Times lexing + parsing only: each VM case repeated `PARSER_REPEAT` times,
+/// so the input is large enough for allocation costs to show.
+fn run_parser_bench(iterations: u32) -> Result<(), String> {
+    const PARSER_REPEAT: usize = 500;
+    for case in bench::VM_SUITE {
+        let source = case.source.repeat(PARSER_REPEAT);
+        let result = bench::time(&format!("parser/{}", case.name), iterations, || {
+            let arena = Bump::new();
+            let start = std::time::Instant::now();
+            let mut parser = kinetix_language::parser::Parser::new(kinetix_language::lexer::Lexer::new(&source), &arena);
+            parser.parse_program();
+            let elapsed = start.elapsed();
+            if !parser.errors.is_empty() {
+                return Err(parser.errors.iter().map(|e| e.to_string()).collect::<Vec<_>>().join("; "));
+            }
+            Ok(elapsed)
+        })?;
+        println!("{}", result);
+    }
+    Ok(())
 }
 
 /// Interactive Kinetix Shell — a terminal REPL with bash-like commands + Kinetix expressions.
 fn run_shell() {
-    use kinetix_kicomp::compiler::Compiler;
     use rustyline::error::ReadlineError;
-    use rustyline::{Cmd, DefaultEditor, EventHandler, KeyEvent, Movement};
+    use rustyline::history::DefaultHistory;
+    use rustyline::{Cmd, Editor, EventHandler, KeyEvent, Movement};
 
     let build = option_env!("KINETIX_BUILD").unwrap_or("Dev");
-    println!("\x1b[1;35mKinetix Shell\x1b[0m v{} ({})", env!("CARGO_PKG_VERSION"), build);
-    println!("Type \x1b[36mexit\x1b[0m to quit, \x1b[36mhelp\x1b[0m for commands.\n");
+    println!("{} v{} ({})", paint("1;35", "Kinetix Shell"), env!("CARGO_PKG_VERSION"), build);
+    println!("Type {} to quit, {} for commands.\n", paint("36", "exit"), paint("36", "help"));
+    kinetix_kivm::pretty::set_print_limits(kinetix_kivm::pretty::Limits::SHELL);
 
-    let mut rl = DefaultEditor::new().expect("Failed to initialize shell line editor");
+    let mut rl: Editor<shell_editor::ShellEditor, DefaultHistory> = Editor::new().expect("Failed to initialize shell line editor");
+    // Tab completion and syntax colors
+    rl.set_helper(Some(shell_editor::ShellEditor::default()));
     // Ctrl+X: cut the whole line (paste it back with Ctrl+Y, already bound by default).
     // Ctrl+Z: undo the last edit. Neither is bound by rustyline's default emacs keymap
     // (plain Ctrl+X is normally an unused prefix key; Ctrl+Z is left to job-control, which
@@ -1050,8 +2126,24 @@ fn run_shell() {
     // the raw Ctrl+V keypress to instead.
     rl.bind_sequence(KeyEvent::ctrl('X'), EventHandler::Simple(Cmd::Kill(Movement::WholeLine)));
     rl.bind_sequence(KeyEvent::ctrl('Z'), EventHandler::Simple(Cmd::Undo(1)));
+    // Up/Down and Ctrl+R search the lines of earlier sessions too
+    let history = kinetix_home().map(|home| home.join("shell_history"));
+    if let Some(history) = &history {
+        let _ = rl.load_history(history);
+    }
+    let mut shell = ShellSession::default();
+    if let Some(rc) = shell_config::path().filter(|rc| rc.exists()) {
+        shell.run_startup_file(&rc);
+    }
 
     loop {
+        for done in shell.jobs.reap() {
+            println!("{}", done);
+        }
+        if let Some(editor) = rl.helper_mut() {
+            editor.set_globals(shell.kinetix.globals());
+        }
+
         // Prompt: show current dir
         let cwd = std::env::current_dir()
             .map(|p| {
@@ -1066,8 +2158,8 @@ fn run_shell() {
             })
             .unwrap_or_else(|_| "?".into());
 
-        let prompt = format!("\x1b[1;34m{}\x1b[0m \x1b[1;33m❯\x1b[0m ", cwd);
-        let line = match rl.readline(&prompt) {
+        let prompt = format!("{} {} ", paint("1;34", cwd), paint("1;33", "❯"));
+        let mut line = match rl.readline(&prompt) {
             Ok(line) => line,
             // Ctrl+C: cancel the current line and show a fresh prompt, like a normal shell --
             // don't kill the whole session.
@@ -1079,6 +2171,18 @@ fn run_shell() {
             }
             Err(_) => break,
         };
+        // An open bracket or backtick string continues on the next line;
+        // Ctrl+C drops the whole entry.
+        while shell_state::incomplete(&line) {
+            match rl.readline(&format!("{} ", paint("1;33", "…"))) {
+                Ok(more) => {
+                    line.push('\n');
+                    line.push_str(&more);
+                }
+                Err(ReadlineError::Interrupted) => line.clear(),
+                Err(_) => break,
+            }
+        }
 
         let input = line.trim();
         if input.is_empty() { continue; }
@@ -1090,129 +2194,329 @@ fn run_shell() {
             break;
         }
 
-        // Help
-        if input == "help" {
-            println!("\x1b[1mBuilt-in commands:\x1b[0m");
-            println!("  ls [dir]          List directory contents");
-            println!("  cd [dir]          Change directory");
-            println!("  pwd               Print working directory");
-            println!("  cat <file>        Show file contents");
-            println!("  head <file> [n]   Show first n lines");
-            println!("  tail <file> [n]   Show last n lines");
-            println!("  mkdir <dir>       Create directory");
-            println!("  touch <file>      Create empty file");
-            println!("  rm <path>         Remove file or directory");
-            println!("  cp <src> <dst>    Copy file");
-            println!("  mv <src> <dst>    Move/rename file");
-            println!("  echo <text>       Print text");
-            println!("  grep <pat> <file> Search in file");
-            println!("  wc <file>         Word/line/byte count");
-            println!("  which <cmd>       Find command in PATH");
-            println!("  whoami            Current user name");
-            println!("  clear             Clear screen");
-            println!("  env               Show environment variables");
-            println!("  kivm <args>       Run kivm subcommands");
-            println!("  exit              Exit shell");
-            println!("\n  Anything else is evaluated as a Kinetix expression.");
-            continue;
+        if let Err(e) = run_shell_line(input, &mut shell) {
+            eprintln!("{}", paint("31", e));
         }
+    }
 
-        // Parse command and arguments
-        let parts: Vec<&str> = input.split_whitespace().collect();
-        let cmd = parts[0];
-        let cmd_args: Vec<kinetix_kivm::vm::Value> = parts[1..]
-            .iter()
-            .map(|s| kinetix_kivm::vm::Value::Str(s.to_string()))
-            .collect();
+    if let Some(history) = &history {
+        let saved = history.parent().map_or(Ok(()), fs::create_dir_all).and_then(|()| rl.save_history(history).map_err(std::io::Error::other));
+        if let Err(e) = saved {
+            eprintln!("{}", paint("33", format!("Could not save history to {}: {}", history.display(), e)));
+        }
+    }
 
-        // Try bash-like commands first
+    let killed = shell.jobs.kill_all();
+    if killed > 0 {
+        println!("Killed {} background job(s)", killed);
+    }
+}
+
+/// What a shell session keeps from one line to the next.
+#[derive(Default)]
+struct ShellSession {
+    /// `$0` followed by the script arguments; empty at the prompt.
+    params: Vec<String>,
+    jobs: jobs::JobTable,
+    config: shell_config::ShellConfig,
+    /// The globals and functions of the Kinetix lines run so far.
+    kinetix: shell_state::KinetixState,
+}
+
+impl ShellSession {
+    /// Runs `~/.kinetixrc`; a line that fails is reported and skipped.
+    fn run_startup_file(&mut self, rc: &Path) {
+        let source = match fs::read_to_string(rc) {
+            Ok(source) => source,
+            Err(e) => return eprintln!("{}", paint("33", format!("{}: {}", rc.display(), e))),
+        };
+        for (line_number, line) in self.config.load(&source) {
+            if let Err(e) = line.and_then(|line| run_shell_line(&line, self)) {
+                eprintln!("{}", paint("33", format!("{}:{}: {}", rc.display(), line_number, e)));
+            }
+        }
+    }
+}
+
+/// Runs one line of shell input, interactive or from a script: a builtin,
+/// a `kivm` subcommand, a Kinetix expression or a system command. Aliases
+/// are expanded first. Commands are split into words by `shell_words`;
+/// Kinetix source only has the script parameters substituted. A failed
+/// command, or one that exits with a non-zero code, is an error.
+fn run_shell_line(input: &str, shell: &mut ShellSession) -> Result<(), String> {
+    let input = shell.config.expand_aliases(input);
+    let input = input.as_str();
+
+    // Help
+    if input == "help" {
+        println!("{}", paint("1", "Built-in commands:"));
+        println!("  ls [dir]          List directory contents");
+        println!("  cd [dir]          Change directory");
+        println!("  pwd               Print working directory");
+        println!("  cat <file>        Show file contents");
+        println!("  head <file> [n]   Show first n lines");
+        println!("  tail <file> [n]   Show last n lines");
+        println!("  mkdir <dir>       Create directory");
+        println!("  touch <file>      Create empty file");
+        println!("  rm <path>         Remove file or directory");
+        println!("  cp <src> <dst>    Copy file");
+        println!("  mv <src> <dst>    Move/rename file");
+        println!("  echo <text>       Print text");
+        println!("  true / false      Succeed / fail (false stops a script)");
+        println!("  grep <pat> <file> Search in file");
+        println!("  wc <file>         Word/line/byte count");
+        println!("  which <cmd>       Find command in PATH");
+        println!("  whoami            Current user name");
+        println!("  clear             Clear screen");
+        println!("  env               Show environment variables");
+        println!("  kivm <args>       Run kivm subcommands");
+        println!("  <command> &       Run kivm or a system command in the background");
+        println!("  jobs              List background jobs");
+        println!("  fg [%n]           Wait for a background job");
+        println!("  bg [%n]           Resume a stopped background job");
+        println!("  kill [%n]         Kill a background job");
+        println!("  alias [name=cmd]  Define or list aliases (unalias <name> removes one)");
+        println!("  export NAME=value Set an environment variable");
+        println!("  help <builtin>    Describe a builtin (e.g. help str.split)");
+        println!("  exit              Exit shell");
+        println!("\n  Anything else is evaluated as a Kinetix expression.");
+        return Ok(());
+    }
+
+    if let Some(name) = input.strip_prefix("help ") {
+        println!("{}", kinetix_kivm::builtins::help_text(name.trim().trim_matches('"')));
+        return Ok(());
+    }
+
+    // A trailing `&` (but not `&&`) runs the command in the background
+    let (input, background) = match input.strip_suffix('&') {
+        Some(rest) if !rest.ends_with(['&', '\\']) && !rest.trim().is_empty() => (rest.trim_end(), true),
+        _ => (input, false),
+    };
+
+    // Parse command and arguments
+    let words = match shell_words::split(input, &shell.params) {
+        Ok(words) => words,
+        // Not a well-formed command line, but it may be Kinetix source
+        Err(e) => return run_shell_source(input, shell).unwrap_or(Err(e)),
+    };
+    let parts: Vec<&str> = words.iter().map(String::as_str).collect();
+    let Some(&cmd) = parts.first() else {
+        return Ok(());
+    };
+
+    match cmd {
+        "alias" => {
+            shell.config.alias(&parts[1..])?.iter().for_each(|alias| println!("{}", alias));
+            return Ok(());
+        }
+        "unalias" => return shell.config.unalias(&parts[1..]),
+        "export" => {
+            for assignment in &parts[1..] {
+                let (name, value) = assignment.split_once('=')
+                    .filter(|(name, _)| !name.is_empty() && !assignment.contains('\0'))
+                    .ok_or_else(|| format!("export: {}: expected NAME=value", assignment))?;
+                unsafe { std::env::set_var(name, value); }
+            }
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    let jobs = &mut shell.jobs;
+    if matches!(cmd, "jobs" | "fg" | "bg" | "kill") {
+        let spec = parts.get(1).copied();
         match cmd {
-            "ls" | "cd" | "pwd" | "cat" | "mkdir" | "rm" | "cp" | "mv" |
-            "echo" | "touch" | "which" | "whoami" | "clear" | "env" |
-            "head" | "tail" | "wc" | "grep" => {
-                match kinetix_kivm::builtins::modules::term::call(cmd, &cmd_args) {
-                    Ok(kinetix_kivm::vm::Value::Null) => {},
-                    Ok(val) => println!("{}", val),
-                    Err(e) => eprintln!("\x1b[31m{}\x1b[0m", e),
-                }
+            "jobs" => jobs.list().iter().for_each(|job| println!("{}", job)),
+            "fg" => {
+                let (command, status) = jobs.fg(spec)?;
+                println!("{}", command);
+                check_exit_status("Process", status)?;
             }
-            "kivm" => {
-                // Forward to a child process
-                let child_args: Vec<&str> = parts[1..].to_vec();
-                let exe = std::env::current_exe().unwrap_or_else(|_| PathBuf::from("kivm"));
-                match std::process::Command::new(&exe).args(&child_args).status() {
-                    Ok(status) => {
-                        if !status.success() {
-                            if let Some(code) = status.code() {
-                                eprintln!("kivm exited with code {}", code);
-                            }
-                        }
-                    }
-                    Err(e) => eprintln!("\x1b[31mFailed to run kivm: {}\x1b[0m", e),
-                }
+            "bg" => println!("{}", jobs.bg(spec)?),
+            _ => println!("{}", jobs.kill(spec)?),
+        }
+        return Ok(());
+    }
+
+    let cmd_args: Vec<kinetix_kivm::vm::Value> = parts[1..]
+        .iter()
+        .map(|s| kinetix_kivm::vm::Value::Str(s.to_string()))
+        .collect();
+
+    // Try bash-like commands first
+    match cmd {
+        "ls" | "cd" | "pwd" | "cat" | "mkdir" | "rm" | "cp" | "mv" |
+        "echo" | "touch" | "which" | "whoami" | "clear" | "env" |
+        "head" | "tail" | "wc" | "grep" if background => {
+            Err(format!("{} is a shell builtin and can't run in the background", cmd))
+        }
+        "ls" | "cd" | "pwd" | "cat" | "mkdir" | "rm" | "cp" | "mv" |
+        "echo" | "touch" | "which" | "whoami" | "clear" | "env" |
+        "head" | "tail" | "wc" | "grep" => {
+            // `echo` writes its line itself and returns it for Kinetix callers
+            match kinetix_kivm::builtins::modules::term::call(cmd, &cmd_args)? {
+                kinetix_kivm::vm::Value::Null => {},
+                _ if cmd == "echo" => {},
+                val => kinetix_kivm::builtins::modules::io::write_line(&val.to_string()),
             }
-            _ => {
-                // Try to evaluate as Kinetix source
-                let source = input.to_string();
-                let lexer = kinetix_language::lexer::Lexer::new(&source);
-                let arena = Bump::new();
-                let mut parser = kinetix_language::parser::Parser::new(lexer, &arena);
-                let ast = parser.parse_program();
-
-                if !parser.errors.is_empty() {
-                    // Not valid Kinetix — try as system command
-                    match std::process::Command::new(cmd)
-                        .args(&parts[1..])
-                        .status()
-                    {
-                        Ok(status) => {
-                            if !status.success() {
-                                if let Some(code) = status.code() {
-                                    eprintln!("Process exited with code {}", code);
-                                }
-                            }
-                        }
-                        Err(_) => {
-                            eprintln!("\x1b[31mUnknown command: {}\x1b[0m", cmd);
-                        }
-                    }
-                } else {
-                    let mut compiler = Compiler::new();
-                    match compiler.compile(&ast.statements, None) {
-                        Ok(compiled) => {
-                            let mut vm = VM::new(compiled.clone());
-                            if let Err(e) = vm.run() {
-                                eprintln!("\x1b[31mRuntime error: {}\x1b[0m", e);
-                            }
-                        }
-                        Err(e) => eprintln!("\x1b[31mCompilation error: {}\x1b[0m", e),
-                    }
-                }
+            Ok(())
+        }
+        // Bare `true`/`false` are commands with an exit status, as in sh
+        "true" if parts.len() == 1 && !background => Ok(()),
+        "false" if parts.len() == 1 && !background => Err("false exited with code 1".to_string()),
+        "kivm" => {
+            // Forward to a child process
+            let child_args: Vec<&str> = parts[1..].to_vec();
+            let exe = std::env::current_exe().unwrap_or_else(|_| PathBuf::from("kivm"));
+            let mut command = std::process::Command::new(&exe);
+            command.args(&child_args);
+            if background {
+                println!("{}", jobs.spawn(command, input).map_err(|e| format!("Failed to run kivm: {}", e))?);
+                return Ok(());
             }
+            let status = command.status().map_err(|e| format!("Failed to run kivm: {}", e))?;
+            check_exit_status("kivm", status)
         }
+        _ if background => {
+            let mut command = std::process::Command::new(cmd);
+            command.args(&parts[1..]);
+            println!("{}", jobs.spawn(command, input).map_err(|_| format!("Unknown command: {}", cmd))?);
+            Ok(())
+        }
+        // Try to evaluate as Kinetix source
+        _ => run_shell_source(input, shell).unwrap_or_else(|| {
+            // Not valid Kinetix — try as system command
+            let status = std::process::Command::new(cmd)
+                .args(&parts[1..])
+                .status()
+                .map_err(|_| format!("Unknown command: {}", cmd))?;
+            check_exit_status("Process", status)
+        }),
     }
 }
 
-/// Open the installed documentation in the default browser.
-fn open_docs() -> Result<(), String> {
-    let docs_path = if let Some(dirs) = directories::BaseDirs::new() {
-        dirs.home_dir().join(".kinetix").join("docs").join("index.html")
-    } else {
-        return Err("Cannot determine home directory".into());
+/// Compiles and runs a shell line as Kinetix source, after the lines run
+/// before it (the first one after the functions of the startup file), or
+/// `None` when it does not parse as Kinetix.
+fn run_shell_source(input: &str, shell: &mut ShellSession) -> Option<Result<(), String>> {
+    let prelude = if shell.kinetix.is_empty() { shell.config.prelude() } else { "" };
+    let source = format!("{}{}", prelude, shell_words::substitute_params(input, &shell.params));
+    let lexer = kinetix_language::lexer::Lexer::new(&source);
+    let arena = Bump::new();
+    let mut parser = kinetix_language::parser::Parser::new(lexer, &arena);
+    let ast = parser.parse_program();
+    if !parser.errors.is_empty() {
+        return None;
+    }
+    Some(shell.kinetix.run(&ast.statements))
+}
+
+/// An error naming `what` when a child process did not succeed.
+fn check_exit_status(what: &str, status: std::process::ExitStatus) -> Result<(), String> {
+    match status.code() {
+        _ if status.success() => Ok(()),
+        Some(code) => Err(format!("{} exited with code {}", what, code)),
+        None => Err(format!("{} was terminated by a signal", what)),
+    }
+}
+
+/// Runs a shell script non-interactively: each line as if typed at the
+/// shell prompt, with `$0` the script path, `$1`... its arguments, `$#` how
+/// many there are and `$@` all of them (see `shell_words`). Blank lines and lines starting with
+/// `#` (including a `#!` line) are skipped. Like `set -e`, the first line
+/// that fails (a failing command, `false` or a Kinetix error) stops the
+/// script; `exit [code]` stops it early. Returns the exit code.
+fn run_shell_script(script: &Path, args: &[String]) -> Result<i32, CliError> {
+    let source = fs::read_to_string(script)
+        .map_err(|e| CliError::Io(format!("Error reading {}: {}", script.display(), e)))?;
+    let mut shell = ShellSession {
+        params: std::iter::once(script.display().to_string()).chain(args.iter().cloned()).collect(),
+        ..ShellSession::default()
     };
+    let mut result = Ok(0);
+    for (i, line) in source.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        for done in shell.jobs.reap() {
+            println!("{}", done);
+        }
+        let words = shell_words::split(line, &shell.params).unwrap_or_default();
+        if matches!(words.first().map(String::as_str), Some("exit" | "quit")) {
+            result = match words.get(1).map(|code| code.parse::<i32>()) {
+                None => Ok(0),
+                Some(Ok(code)) => Ok(code),
+                Some(Err(_)) => Err(CliError::Usage(format!("{}:{}: exit: numeric code expected", script.display(), i + 1))),
+            };
+            break;
+        }
+        if let Err(e) = run_shell_line(line, &mut shell) {
+            result = Err(CliError::Runtime(format!("{}:{}: {}", script.display(), i + 1, e)));
+            break;
+        }
+    }
+    shell.jobs.kill_all();
+    result
+}
 
-    if !docs_path.exists() {
+/// The installer's default install directory, `~/.kinetix`.
+fn kinetix_home() -> Option<PathBuf> {
+    directories::BaseDirs::new().map(|dirs| dirs.home_dir().join(".kinetix"))
+}
+
+/// Open the installed documentation in the default browser, at `topic`'s
+/// page when given, or serve it locally with search.
+fn open_docs(topic: Option<&str>, serve: bool, port: u16) -> Result<(), String> {
+    let docs_root = kinetix_home().ok_or("Cannot determine home directory")?.join("docs");
+
+    if !docs_root.join("index.html").exists() {
         return Err(format!(
             "Documentation not found at {}.\nInstall it via the Kinetix Installer (enable 'Documentation').",
-            docs_path.display()
+            docs_root.join("index.html").display()
         ));
     }
 
-    // Open in default browser
+    let index = docs::DocIndex::build(&docs_root)?;
+    let page = match topic {
+        Some(topic) => Some(index.resolve(topic).ok_or_else(|| {
+            let hits = index.search(topic);
+            let mut msg = format!("No documentation page for '{}'", topic);
+            if !hits.is_empty() {
+                msg.push_str(". Pages mentioning it:");
+                for hit in hits.iter().take(5) {
+                    msg.push_str(&format!("\n  {} ({})", hit.page.title, hit.page.path));
+                }
+            }
+            msg
+        })?),
+        None => None,
+    };
+
+    if serve {
+        return docs::serve(&index, port, page.as_deref());
+    }
+
+    let page = page.unwrap_or_else(|| "index.html".to_string());
+    let (file, anchor) = page.split_once('#').map_or((page.as_str(), None), |(f, a)| (f, Some(a)));
+    let path = docs_root.join(file);
+    let mut url = format!("file:///{}", path.display().to_string().replace('\\', "/").trim_start_matches('/'));
+    if let Some(anchor) = anchor {
+        url.push('#');
+        url.push_str(anchor);
+    }
+    open_in_browser(&url)?;
+    println!("Opening documentation: {}", path.display());
+    Ok(())
+}
+
+/// Hand `target` (a URL or file path) to the platform's default browser.
+fn open_in_browser(target: &str) -> Result<(), String> {
     #[cfg(target_os = "windows")]
     {
         std::process::Command::new("cmd")
-            .args(["/C", "start", "", &docs_path.to_string_lossy()])
+            .args(["/C", "start", "", target])
             .spawn()
             .map_err(|e| format!("Failed to open browser: {}", e))?;
     }
@@ -1220,7 +2524,7 @@ fn open_docs() -> Result<(), String> {
     #[cfg(target_os = "macos")]
     {
         std::process::Command::new("open")
-            .arg(&docs_path)
+            .arg(target)
             .spawn()
             .map_err(|e| format!("Failed to open browser: {}", e))?;
     }
@@ -1228,15 +2532,116 @@ fn open_docs() -> Result<(), String> {
     #[cfg(target_os = "linux")]
     {
         std::process::Command::new("xdg-open")
-            .arg(&docs_path)
+            .arg(target)
             .spawn()
             .map_err(|e| format!("Failed to open browser: {}", e))?;
     }
 
-    println!("Opening documentation: {}", docs_path.display());
     Ok(())
 }
 
+/// `kivm env`: plain `key: value` lines meant to be pasted into a bug report.
+fn env_report() -> String {
+    let mut out = String::new();
+    let mut line = |key: &str, value: String| {
+        let label = if key.is_empty() { String::new() } else { format!("{}:", key) };
+        out.push_str(&format!("{:<16}{}\n", label, value));
+    };
+
+    line("version", format!("{} (build {})", env!("CARGO_PKG_VERSION"), kinetix_kicomp::compiler::CURRENT_BUILD));
+    line("host", format!("{} {}", std::env::consts::OS, std::env::consts::ARCH));
+    line("features", format!("llvm={}", if cfg!(feature = "llvm") { "on" } else { "off" }));
+    let current_exe = std::env::current_exe().ok();
+    line("executable", current_exe.as_ref().map_or("unknown".to_string(), |p| p.display().to_string()));
+
+    line("toolchain", match toolchain::selected() {
+        Ok(Some(selection)) => format!("{} (selected by {})", selection.build, selection.source),
+        Ok(None) => "none pinned".to_string(),
+        Err(e) => e,
+    });
+
+    let home = kinetix_home();
+    line("install dir", match &home {
+        Some(home) if home.is_dir() => home.display().to_string(),
+        Some(home) => format!("{} (not found)", home.display()),
+        None => "unknown (no home directory)".to_string(),
+    });
+
+    // Same scan as the installer's "Check Conflicts" step: the first match
+    // on PATH is the one the shell runs, every later one is shadowed.
+    let same_file = |a: &Path, b: &Path| fs::canonicalize(a).ok().is_some_and(|a| Some(a) == fs::canonicalize(b).ok());
+    for tool in ["kivm", "kicomp"] {
+        let exe = format!("{}{}", tool, std::env::consts::EXE_SUFFIX);
+        let found: Vec<PathBuf> = std::env::var_os("PATH")
+            .map(|path| std::env::split_paths(&path).map(|dir| dir.join(&exe)).filter(|p| p.is_file()).collect())
+            .unwrap_or_default();
+        let key = format!("{} on PATH", tool);
+        if found.is_empty() {
+            line(&key, "not found".to_string());
+        }
+        for (i, path) in found.iter().enumerate() {
+            let mut notes = vec![if i == 0 { "active" } else { "shadowed" }];
+            if home.as_ref().is_some_and(|home| path.starts_with(home)) {
+                notes.push("install dir");
+            }
+            if tool == "kivm" && current_exe.as_ref().is_some_and(|exe| same_file(path, exe)) {
+                notes.push("this executable");
+            }
+            line(if i == 0 { &key } else { "" }, format!("{} ({})", path.display(), notes.join(", ")));
+        }
+    }
+
+    let docs = home.as_ref().map(|home| home.join("docs"));
+    line("docs", match &docs {
+        Some(docs) if docs.join("index.html").is_file() => {
+            let (files, bytes) = dir_usage(docs);
+            format!("{} ({} files, {})", docs.display(), files, format_size(bytes))
+        }
+        Some(docs) => format!("{} (missing)", docs.display()),
+        None => "unknown".to_string(),
+    });
+    let cache = home.as_ref().map(|home| home.join("cache"));
+    line("cache", match &cache {
+        Some(cache) if cache.is_dir() => {
+            let (files, bytes) = dir_usage(cache);
+            format!("{} ({} files, {})", cache.display(), files, format_size(bytes))
+        }
+        Some(cache) => format!("{} (none)", cache.display()),
+        None => "unknown".to_string(),
+    });
+    out
+}
+
+/// File count and total size of everything under `dir`.
+fn dir_usage(dir: &Path) -> (usize, u64) {
+    let mut usage = (0, 0);
+    for entry in fs::read_dir(dir).into_iter().flatten().flatten() {
+        match entry.metadata() {
+            Ok(meta) if meta.is_dir() => {
+                let (files, bytes) = dir_usage(&entry.path());
+                usage = (usage.0 + files, usage.1 + bytes);
+            }
+            Ok(meta) => usage = (usage.0 + 1, usage.1 + meta.len()),
+            Err(_) => {}
+        }
+    }
+    usage
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
 fn open_installer(arg: &str) -> Result<(), String> {
     let exe_path = std::env::current_exe().map_err(|e| e.to_string())?;
     
@@ -1261,3 +2666,111 @@ fn open_installer(arg: &str) -> Result<(), String> {
         .map_err(|e| format!("Failed to spawn installer: {}", e))?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `tests/ui` golden files; set `KINETIX_BLESS=1` to regenerate them.
+    #[test]
+    fn ui_diagnostics_match_golden_files() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../tests/ui");
+        let bless = std::env::var_os("KINETIX_BLESS").is_some();
+        let failures: Vec<String> = kix_files(&dir)
+            .expect("tests/ui is readable")
+            .iter()
+            .filter_map(|file| run_ui_test(file, bless).err().map(|e| format!("{}:\n{}", file.display(), e)))
+            .collect();
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+
+    #[test]
+    fn test_blocks_run_one_by_one() {
+        let dir = std::env::temp_dir().join(format!("kinetix-test-blocks-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let lib = dir.join("lib.kix");
+        fs::write(&lib, "fn double(n: int) -> int {\n  return n * 2\n}\ntest \"doubles\" {\n  let x = 4\n  assert(double(2) == x)\n}\ntest \"fails\" {\n  assert(x == 4)\n}\n").unwrap();
+        fs::write(dir.join("plain.kix"), "print(1)\n").unwrap();
+
+        let mut targets = Vec::new();
+        unit_tests(&dir, &mut targets).unwrap();
+        let names: Vec<String> = targets.iter().map(TestTarget::name).collect();
+        assert_eq!(names, [format!("{}::doubles", lib.display()), format!("{}::fails", lib.display())]);
+        assert_eq!(run_test_file(&lib, Some(0)), Ok(()));
+        // The first block's `x` is not visible in the second
+        assert!(run_test_file(&lib, Some(1)).is_err());
+        assert_eq!(run_test_file(&lib, None), Ok(()));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn diffs_show_each_change_with_context() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\n}\n";
+        let new = "a\nB\nc\nd\ne\nf\ng\nh\n  x\n}\n";
+        assert_eq!(unified_diff("f.kix", old, new), "--- f.kix\n+++ f.kix\n@@ -1,4 +1,4 @@\n a\n-b\n+B\n c\n d\n@@ -7,3 +7,4 @@\n g\n h\n+  x\n }\n");
+        assert_eq!(unified_diff("f.kix", old, old), "");
+    }
+
+    #[test]
+    fn fix_adds_the_missing_match_arms() {
+        let dir = std::env::temp_dir().join(format!("kinetix-fix-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("colors.kix");
+        fs::write(&file, "enum Color { Red, Green, Blue }\nlet c = Red\nlet n = match c {\n    Red => 1,\n}\nprint(n)\n").unwrap();
+        assert!(fix_file(&file, false).is_ok());
+        assert_eq!(fs::read_to_string(&file).unwrap(), "enum Color { Red, Green, Blue }\nlet c = Red\nlet n = match c {\n    Red => 1,\n    Green => assert(false, \"unhandled: Green\")\n    Blue => assert(false, \"unhandled: Blue\")\n}\nprint(n)\n");
+        assert!(exec_source(&file, &fs::read_to_string(&file).unwrap(), &ExecOptions { check_only: true, ..ExecOptions::default() }).is_ok());
+
+        // An error without a fix fails the command
+        fs::write(&file, "print(m)\n").unwrap();
+        assert_eq!(fix_file(&file, false).map_err(|e| e.exit_code()), Err(3));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn shell_script_echo_prints_its_line_once() {
+        let dir = std::env::temp_dir().join(format!("kinetix-shell-echo-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let script = dir.join("greet.ks");
+        fs::write(&script, "echo hello $1\n").unwrap();
+        let (result, output) = kinetix_kivm::builtins::modules::io::capture(|| run_shell_script(&script, &["shell".to_string()]));
+        assert_eq!(result.unwrap(), 0);
+        assert_eq!(output, "hello shell\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn shell_scripts_stop_at_the_first_failing_line() {
+        let dir = std::env::temp_dir().join(format!("kinetix-shell-script-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let script = dir.join("setup.ks");
+        let args = [dir.display().to_string(), "out".to_string()];
+        fs::write(&script, "#!/usr/bin/env -S kivm shell
+
+mkdir $1/$2
+cat $1/missing.txt
+touch $1/$2/after
+").unwrap();
+        let err = run_shell_script(&script, &args).unwrap_err();
+        assert!(err.to_string().starts_with(&format!("Runtime error: {}:4: ", script.display())), "{}", err);
+        assert!(dir.join("out").is_dir());
+        assert!(!dir.join("out/after").exists());
+
+        fs::write(&script, "touch $1/$2/after
+exit 3
+touch $1/$2/never
+").unwrap();
+        assert_eq!(run_shell_script(&script, &args).unwrap(), 3);
+        assert!(dir.join("out/after").exists());
+        assert!(!dir.join("out/never").exists());
+
+        fs::write(&script, "true
+false
+touch $1/$2/never
+").unwrap();
+        let err = run_shell_script(&script, &args).unwrap_err();
+        assert!(err.to_string().starts_with(&format!("Runtime error: {}:2: ", script.display())), "{}", err);
+        assert!(!dir.join("out/never").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,566 @@
+//! Packages for `kivm pkg`: the `Kinetix.toml` manifest, registries and
+//! dependency resolution.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use kinetix_kicomp::capability::Capability;
+use kinetix_kicomp::project::{Dependency, DependencySource, OptLevel, OutputType, ProjectConfig, SandboxConfig};
+
+pub const MANIFEST: &str = "Kinetix.toml";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Requirement {
+    Path(PathBuf),
+    /// A version requirement on a registry package.
+    Version(String),
+}
+
+type Dependencies = Vec<(String, Requirement)>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Manifest {
+    pub name: String,
+    pub version: String,
+    pub entry: String,
+    pub lib: String,
+    pub allow: Vec<Capability>,
+    pub dependencies: Dependencies,
+}
+
+/// A package the build compiles in: dependencies first, each before its
+/// dependents.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Resolved {
+    pub name: String,
+    pub version: String,
+    pub entry: PathBuf,
+}
+
+// ─── Manifest ────────────────────────────────────────────────────────────
+
+pub fn parse_manifest(text: &str) -> Result<Manifest, String> {
+    let mut manifest = Manifest {
+        name: String::new(),
+        version: "0.1.0".to_string(),
+        entry: "src/main.kix".to_string(),
+        lib: "src/lib.kix".to_string(),
+        allow: Vec::new(),
+        dependencies: Vec::new(),
+    };
+    let mut section = String::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            section = name.trim().to_string();
+            continue;
+        }
+        let at = |e: String| format!("{}:{}: {}", MANIFEST, i + 1, e);
+        let (key, value) = line.split_once('=').ok_or_else(|| at("expected key = value".to_string()))?;
+        let (key, value) = (key.trim().trim_matches('"'), value.trim());
+        match (section.as_str(), key) {
+            ("package", "name") => manifest.name = string(value).map_err(at)?,
+            ("package", "version") => {
+                manifest.version = string(value).map_err(at)?;
+                parse_version(&manifest.version).ok_or_else(|| at(format!("'{}' is not a version", manifest.version)))?;
+            }
+            ("package", "entry") => manifest.entry = string(value).map_err(at)?,
+            ("package", "lib") => manifest.lib = string(value).map_err(at)?,
+            ("package", "allow") => {
+                let list = value.strip_prefix('[').and_then(|v| v.strip_suffix(']')).ok_or_else(|| at("expected a list of capabilities".to_string()))?;
+                for item in list.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+                    let flag = string(item).map_err(at)?;
+                    manifest.allow.push(Capability::from_flag(&flag).ok_or_else(|| at(format!("unknown capability '{}'", flag)))?);
+                }
+            }
+            ("dependencies", name) => {
+                let requirement = match value.strip_prefix('{').and_then(|v| v.strip_suffix('}')) {
+                    Some(table) => {
+                        let (field, path) = table.split_once('=').ok_or_else(|| at("expected { path = \"...\" }".to_string()))?;
+                        if field.trim() != "path" {
+                            return Err(at(format!("unknown dependency field '{}'", field.trim())));
+                        }
+                        Requirement::Path(PathBuf::from(string(path.trim()).map_err(at)?))
+                    }
+                    None => Requirement::Version(string(value).map_err(at)?),
+                };
+                manifest.dependencies.push((name.to_string(), requirement));
+            }
+            // Other sections and top-level keys (such as a toolchain pin) are
+            // not the package's
+            _ => {}
+        }
+    }
+    if !valid_name(&manifest.name) {
+        return Err(format!("{}: [package] needs a name of lowercase letters, digits, '-' and '_'", MANIFEST));
+    }
+    Ok(manifest)
+}
+
+pub fn load(dir: &Path) -> Result<Manifest, String> {
+    let path = dir.join(MANIFEST);
+    let text = fs::read_to_string(&path).map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+    parse_manifest(&text).map_err(|e| format!("{}: {}", dir.display(), e))
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+fn string(value: &str) -> Result<String, String> {
+    value.strip_prefix('"').and_then(|v| v.strip_suffix('"')).map(str::to_string)
+        .ok_or_else(|| format!("expected a quoted string, got {}", value))
+}
+
+fn valid_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+}
+
+/// Writes `Kinetix.toml` and `src/main.kix` in `dir`.
+pub fn init(dir: &Path, name: &str) -> Result<String, String> {
+    if !valid_name(name) {
+        return Err(format!("'{}' is not a package name (use lowercase letters, digits, '-' and '_')", name));
+    }
+    let manifest = dir.join(MANIFEST);
+    if manifest.exists() {
+        return Err(format!("{} already exists", manifest.display()));
+    }
+    fs::create_dir_all(dir.join("src")).map_err(|e| format!("Cannot create {}: {}", dir.join("src").display(), e))?;
+    let text = format!("[package]\nname = \"{}\"\nversion = \"0.1.0\"\nentry = \"src/main.kix\"\n\n[dependencies]\n", name);
+    fs::write(&manifest, text).map_err(|e| format!("Cannot write {}: {}", manifest.display(), e))?;
+    let main = dir.join("src/main.kix");
+    if !main.exists() {
+        fs::write(&main, kinetix_kicomp::project::scaffold_main_kix(name)).map_err(|e| format!("Cannot write {}: {}", main.display(), e))?;
+    }
+    Ok(format!("Created package '{}' ({})", name, manifest.display()))
+}
+
+/// Adds `name` to `[dependencies]`, replacing an earlier entry. A registry
+/// dependency without a requirement gets `^` the newest published version.
+pub fn add(dir: &Path, name: &str, requirement: Option<Requirement>, registry: &Registry) -> Result<String, String> {
+    load(dir)?;
+    if !valid_name(name) {
+        return Err(format!("'{}' is not a package name", name));
+    }
+    let requirement = match requirement {
+        Some(Requirement::Version(req)) => {
+            matches(&req, Version(0, 0, 0))?;
+            Requirement::Version(req)
+        }
+        Some(path) => path,
+        None => {
+            let newest = registry.versions(name)?.into_iter().max().ok_or_else(|| format!("'{}' has no published versions", name))?;
+            Requirement::Version(format!("^{}", newest))
+        }
+    };
+    let value = match &requirement {
+        Requirement::Path(path) => format!("{{ path = \"{}\" }}", path.display()),
+        Requirement::Version(req) => format!("\"{}\"", req),
+    };
+    let path = dir.join(MANIFEST);
+    let text = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    let mut lines = without_dependency(&text, name);
+    let section = lines.iter().position(|l| l.trim() == "[dependencies]");
+    let at = match section {
+        Some(start) => {
+            let end = lines[start + 1..].iter().position(|l| l.trim_start().starts_with('[')).map_or(lines.len(), |i| start + 1 + i);
+            // Before the blank lines that end the section
+            (start + 1..end).rev().find(|&i| !lines[i].trim().is_empty()).map_or(start + 1, |i| i + 1)
+        }
+        None => {
+            if lines.last().is_some_and(|l| !l.trim().is_empty()) {
+                lines.push(String::new());
+            }
+            lines.push("[dependencies]".to_string());
+            lines.len()
+        }
+    };
+    lines.insert(at, format!("{} = {}", name, value));
+    fs::write(&path, lines.join("\n") + "\n").map_err(|e| format!("Cannot write {}: {}", path.display(), e))?;
+    Ok(format!("Added {} = {}", name, value))
+}
+
+/// Removes `name` from `[dependencies]`.
+pub fn remove(dir: &Path, name: &str) -> Result<String, String> {
+    let manifest = load(dir)?;
+    if !manifest.dependencies.iter().any(|(dep, _)| dep == name) {
+        return Err(format!("'{}' is not a dependency of '{}'", name, manifest.name));
+    }
+    let path = dir.join(MANIFEST);
+    let text = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    fs::write(&path, without_dependency(&text, name).join("\n") + "\n").map_err(|e| format!("Cannot write {}: {}", path.display(), e))?;
+    Ok(format!("Removed {}", name))
+}
+
+/// The lines of `text` without `name`'s entry in `[dependencies]`.
+fn without_dependency(text: &str, name: &str) -> Vec<String> {
+    let mut section = String::new();
+    let mut lines = Vec::new();
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') {
+            section = trimmed.to_string();
+        } else if section == "[dependencies]" && trimmed.split_once('=').is_some_and(|(key, _)| key.trim().trim_matches('"') == name) {
+            continue;
+        }
+        lines.push(line.to_string());
+    }
+    lines
+}
+
+// ─── Versions ────────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version(u64, u64, u64);
+
+impl std::fmt::Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.0, self.1, self.2)
+    }
+}
+
+/// `1`, `1.2` or `1.2.3`.
+fn parse_version(text: &str) -> Option<Version> {
+    let mut parts = text.trim().split('.').map(|p| p.parse::<u64>());
+    let version = Version(parts.next()?.ok()?, parts.next().unwrap_or(Ok(0)).ok()?, parts.next().unwrap_or(Ok(0)).ok()?);
+    parts.next().is_none().then_some(version)
+}
+
+/// Whether `version` meets requirement `req`.
+fn matches(req: &str, version: Version) -> Result<bool, String> {
+    let req = req.trim();
+    if req == "*" {
+        return Ok(true);
+    }
+    let (op, rest) = ["=", ">=", "^"].iter().find_map(|op| req.strip_prefix(op).map(|rest| (*op, rest))).unwrap_or(("^", req));
+    let wanted = parse_version(rest).ok_or_else(|| format!("'{}' is not a version requirement", req))?;
+    Ok(match op {
+        "=" => version == wanted,
+        ">=" => version >= wanted,
+        _ if wanted.0 > 0 => version.0 == wanted.0 && version >= wanted,
+        _ => version.0 == 0 && version.1 == wanted.1 && version >= wanted,
+    })
+}
+
+// ─── Registries ──────────────────────────────────────────────────────────
+
+/// A directory, or an http(s) URL serving one, laid out as
+/// `<name>/index.json` (the published versions) and `<name>/<version>.json`
+/// (the package: its `lib`, its registry dependencies and the text of every
+/// file under its `src/`).
+pub enum Registry {
+    Local(PathBuf),
+    Remote(String),
+}
+
+impl Registry {
+    /// `--registry`, else `KINETIX_REGISTRY`, else `~/.kinetix/registry`.
+    pub fn select(arg: Option<&str>) -> Result<Registry, String> {
+        let location = match arg.map(str::to_string).or_else(|| std::env::var("KINETIX_REGISTRY").ok()) {
+            Some(location) => location,
+            None => return Ok(Registry::Local(crate::kinetix_home().ok_or("Cannot determine home directory")?.join("registry"))),
+        };
+        Ok(if location.starts_with("http://") || location.starts_with("https://") {
+            Registry::Remote(location.trim_end_matches('/').to_string())
+        } else {
+            Registry::Local(PathBuf::from(location))
+        })
+    }
+
+    fn read(&self, file: &str) -> Result<Option<serde_json::Value>, String> {
+        let text = match self {
+            Registry::Local(dir) => match fs::read_to_string(dir.join(file)) {
+                Ok(text) => text,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+                Err(e) => return Err(format!("{}: {}", dir.join(file).display(), e)),
+            },
+            Registry::Remote(url) => match ureq::get(&format!("{}/{}", url, file)).call() {
+                Ok(response) => response.into_string().map_err(|e| format!("{}/{}: {}", url, file, e))?,
+                Err(ureq::Error::Status(404, _)) => return Ok(None),
+                Err(e) => return Err(format!("{}/{}: {}", url, file, e)),
+            },
+        };
+        serde_json::from_str(&text).map(Some).map_err(|e| format!("Invalid {} in the registry: {}", file, e))
+    }
+
+    /// The published versions of `name`.
+    fn versions(&self, name: &str) -> Result<Vec<Version>, String> {
+        let index = self.read(&format!("{}/index.json", name))?.ok_or_else(|| format!("No package '{}' in the registry", name))?;
+        Ok(index.as_array().into_iter().flatten().filter_map(|v| parse_version(v.as_str()?)).collect())
+    }
+}
+
+// ─── Resolution ──────────────────────────────────────────────────────────
+
+struct Resolver<'a> {
+    registry: &'a Registry,
+    cache: &'a Path,
+    done: Vec<(Resolved, Option<Version>)>,
+    /// Packages being resolved, outermost first.
+    stack: Vec<String>,
+}
+
+/// Resolves the dependencies of the package in `dir`, unpacking registry
+/// packages under `cache`.
+pub fn resolve(dir: &Path, registry: &Registry, cache: &Path) -> Result<Vec<Resolved>, String> {
+    let manifest = load(dir)?;
+    let mut resolver = Resolver { registry, cache, done: Vec::new(), stack: vec![manifest.name.clone()] };
+    for (name, requirement) in &manifest.dependencies {
+        resolver.visit(name, requirement, dir, &manifest.name)?;
+    }
+    Ok(resolver.done.into_iter().map(|(resolved, _)| resolved).collect())
+}
+
+impl Resolver<'_> {
+    fn visit(&mut self, name: &str, requirement: &Requirement, base: &Path, parent: &str) -> Result<(), String> {
+        if let Some(start) = self.stack.iter().position(|open| open == name) {
+            let chain: Vec<&str> = self.stack[start..].iter().map(String::as_str).chain([name]).collect();
+            return Err(format!("Circular dependency: {}", chain.join(" -> ")));
+        }
+        if let Some((resolved, version)) = self.done.iter().find(|(r, _)| r.name == name) {
+            let compatible = match (requirement, version) {
+                (Requirement::Version(req), Some(version)) => matches(req, *version)?,
+                (Requirement::Path(path), None) => resolved.entry.starts_with(base.join(path).canonicalize().unwrap_or_default()),
+                _ => false,
+            };
+            if !compatible {
+                return Err(format!("'{}' requires {} {}, which conflicts with the {} already resolved", parent, name, describe(requirement), resolved.version));
+            }
+            return Ok(());
+        }
+
+        let (root, lib, version, dependencies) = match requirement {
+            Requirement::Path(path) => {
+                let root = base.join(path).canonicalize().map_err(|e| format!("Dependency '{}' of '{}': {}: {}", name, parent, base.join(path).display(), e))?;
+                let manifest = load(&root)?;
+                if manifest.name != name {
+                    return Err(format!("Dependency '{}' of '{}': {} is package '{}'", name, parent, root.display(), manifest.name));
+                }
+                (root, manifest.lib, None, manifest.dependencies)
+            }
+            Requirement::Version(req) => {
+                let mut candidates = Vec::new();
+                for version in self.registry.versions(name)? {
+                    if matches(req, version)? {
+                        candidates.push(version);
+                    }
+                }
+                let version = candidates.into_iter().max()
+                    .ok_or_else(|| format!("No version of '{}' matches {} (required by '{}')", name, req, parent))?;
+                let (root, lib, dependencies) = self.unpack(name, version)?;
+                (root, lib, Some(version), dependencies)
+            }
+        };
+
+        self.stack.push(name.to_string());
+        for (dep, requirement) in &dependencies {
+            self.visit(dep, requirement, &root, name)?;
+        }
+        self.stack.pop();
+
+        let entry = root.join(&lib);
+        if !entry.is_file() {
+            return Err(format!("Package '{}' has no {}", name, lib));
+        }
+        let shown = version.map_or_else(|| root.display().to_string(), |v| v.to_string());
+        self.done.push((Resolved { name: name.to_string(), version: shown, entry }, version));
+        Ok(())
+    }
+
+    /// Unpacks `name` `version` into the cache, unless it already is; returns
+    /// its directory, `lib` and dependencies.
+    fn unpack(&self, name: &str, version: Version) -> Result<(PathBuf, String, Dependencies), String> {
+        let file = format!("{}/{}.json", name, version);
+        let package = self.registry.read(&file)?.ok_or_else(|| format!("The registry lists {} {} but has no {}", name, version, file))?;
+        let lib = package["lib"].as_str().unwrap_or("src/lib.kix").to_string();
+        let dependencies = package["dependencies"].as_object().into_iter().flatten()
+            .map(|(dep, req)| (dep.clone(), Requirement::Version(req.as_str().unwrap_or("*").to_string())))
+            .collect();
+
+        let root = self.cache.join(format!("{}-{}", name, version));
+        if !root.join(&lib).is_file() {
+            for (path, text) in package["files"].as_object().into_iter().flatten() {
+                let relative = Path::new(path);
+                if !relative.components().all(|c| matches!(c, Component::Normal(_))) {
+                    return Err(format!("{} {}: refusing to unpack '{}' outside the package", name, version, path));
+                }
+                let target = root.join(relative);
+                if let Some(parent) = target.parent() {
+                    fs::create_dir_all(parent).map_err(|e| format!("Cannot create {}: {}", parent.display(), e))?;
+                }
+                fs::write(&target, text.as_str().unwrap_or_default()).map_err(|e| format!("Cannot write {}: {}", target.display(), e))?;
+            }
+        }
+        Ok((root, lib, dependencies))
+    }
+}
+
+fn describe(requirement: &Requirement) -> String {
+    match requirement {
+        Requirement::Path(path) => format!("at {}", path.display()),
+        Requirement::Version(req) => req.clone(),
+    }
+}
+
+/// Where registry packages are unpacked.
+pub fn cache_dir() -> Result<PathBuf, String> {
+    Ok(crate::kinetix_home().ok_or("Cannot determine home directory")?.join("packages"))
+}
+
+/// The package in `dir` as a project for the build pipeline: its entry, with
+/// the resolved packages as local dependencies, in order.
+pub fn project_config(dir: &Path, registry: &Registry) -> Result<ProjectConfig, String> {
+    let manifest = load(dir)?;
+    let resolved = resolve(dir, registry, &cache_dir()?)?;
+    let entry = dir.join(&manifest.entry);
+    if !entry.is_file() {
+        return Err(format!("Entry point {} not found", entry.display()));
+    }
+    let allowed = |cap: Capability| manifest.allow.contains(&cap);
+    let sandbox = SandboxConfig {
+        allow_network: allowed(Capability::NetAccess),
+        allow_fs_read: true,
+        allow_fs_write: if allowed(Capability::FsWrite) { vec!["*".to_string()] } else { Vec::new() },
+        allow_audio: false,
+        allow_exec: allowed(Capability::OsExecute),
+        allow_threads: allowed(Capability::ThreadControl),
+    };
+    Ok(ProjectConfig {
+        name: manifest.name,
+        version: manifest.version,
+        author: None,
+        entry,
+        output_type: OutputType::Kivm,
+        optimize: OptLevel::Debug,
+        dependencies: resolved.into_iter().map(|r| Dependency { name: r.name, source: DependencySource::Local(r.entry) }).collect(),
+        sandbox,
+    })
+}
+
+// ─── Publishing ──────────────────────────────────────────────────────────
+
+/// Adds this version of the package in `dir` to a directory registry.
+pub fn publish(dir: &Path, registry: &Registry) -> Result<String, String> {
+    let Registry::Local(root) = registry else {
+        return Err("Publishing needs a directory registry (--registry DIR); serve the directory to share it over HTTP".to_string());
+    };
+    let manifest = load(dir)?;
+    let mut dependencies = serde_json::Map::new();
+    for (name, requirement) in &manifest.dependencies {
+        match requirement {
+            Requirement::Version(req) => dependencies.insert(name.clone(), req.clone().into()),
+            Requirement::Path(path) => return Err(format!("Cannot publish with the path dependency '{}' ({}); publish it and depend on a version", name, path.display())),
+        };
+    }
+    if !dir.join(&manifest.lib).is_file() {
+        return Err(format!("Cannot publish '{}': it has no {} for dependents to compile", manifest.name, manifest.lib));
+    }
+
+    let mut files = BTreeMap::new();
+    collect_files(dir, &dir.join("src"), &mut files)?;
+    let package_dir = root.join(&manifest.name);
+    let version = parse_version(&manifest.version).expect("checked by parse_manifest");
+    let target = package_dir.join(format!("{}.json", version));
+    if target.exists() {
+        return Err(format!("{} {} is already published", manifest.name, version));
+    }
+    let package = serde_json::json!({
+        "name": manifest.name,
+        "version": version.to_string(),
+        "lib": manifest.lib,
+        "dependencies": dependencies,
+        "files": files,
+    });
+    fs::create_dir_all(&package_dir).map_err(|e| format!("Cannot create {}: {}", package_dir.display(), e))?;
+    fs::write(&target, serde_json::to_string_pretty(&package).map_err(|e| e.to_string())? + "\n")
+        .map_err(|e| format!("Cannot write {}: {}", target.display(), e))?;
+
+    let mut versions = registry.versions(&manifest.name).unwrap_or_default();
+    versions.push(version);
+    versions.sort();
+    let index: Vec<String> = versions.iter().map(Version::to_string).collect();
+    fs::write(package_dir.join("index.json"), serde_json::to_string(&index).map_err(|e| e.to_string())? + "\n")
+        .map_err(|e| format!("Cannot write {}: {}", package_dir.join("index.json").display(), e))?;
+    Ok(format!("Published {} {} ({} files) to {}", manifest.name, version, files.len(), root.display()))
+}
+
+/// The text files under `dir`, by their `/`-separated path from `base`.
+fn collect_files(base: &Path, dir: &Path, files: &mut BTreeMap<String, String>) -> Result<(), String> {
+    let entries = fs::read_dir(dir).map_err(|e| format!("Cannot read {}: {}", dir.display(), e))?;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_files(base, &path, files)?;
+            continue;
+        }
+        let text = fs::read_to_string(&path).map_err(|e| format!("Cannot publish {}: {}", path.display(), e))?;
+        let name = path.strip_prefix(base).map_err(|e| e.to_string())?
+            .components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/");
+        files.insert(name, text);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package(dir: &Path, manifest: &str, lib: &str) {
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join(MANIFEST), manifest).unwrap();
+        fs::write(dir.join("src/lib.kix"), lib).unwrap();
+    }
+
+    #[test]
+    fn packages_are_published_resolved_and_edited() {
+        let tmp = std::env::temp_dir().join(format!("kinetix-pkg-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&tmp);
+        let registry = Registry::Local(tmp.join("registry"));
+
+        for version in ["0.1.0", "0.1.4", "0.2.0"] {
+            package(&tmp.join("strings"), &format!("[package]\nname = \"strings\"\nversion = \"{}\"\n", version), "fn shout(s: str) -> str { return s }\n");
+            assert!(publish(&tmp.join("strings"), &registry).unwrap().starts_with(&format!("Published strings {} (1 files)", version)));
+        }
+        assert_eq!(publish(&tmp.join("strings"), &registry).unwrap_err(), "strings 0.2.0 is already published");
+
+        package(&tmp.join("geo"), "[package]\nname = \"geo\"\n\n[dependencies]\nstrings = \"0.1\"\n", "fn area() -> int { return 1 }\n");
+        let app = tmp.join("app");
+        assert!(init(&app, "app").is_ok());
+        assert_eq!(add(&app, "geo", Some(Requirement::Path("../geo".into())), &registry).unwrap(), "Added geo = { path = \"../geo\" }");
+        assert_eq!(add(&app, "strings", None, &registry).unwrap(), "Added strings = \"^0.2.0\"");
+
+        // geo wants strings 0.1.x, the app now wants 0.2
+        let err = resolve(&app, &registry, &tmp.join("cache")).unwrap_err();
+        assert_eq!(err, "'app' requires strings ^0.2.0, which conflicts with the 0.1.4 already resolved");
+
+        add(&app, "strings", Some(Requirement::Version("0.1".into())), &registry).unwrap();
+        let resolved = resolve(&app, &registry, &tmp.join("cache")).unwrap();
+        let names: Vec<(&str, &str)> = resolved.iter().map(|r| (r.name.as_str(), r.version.as_str())).collect();
+        assert_eq!(names[0], ("strings", "0.1.4"));
+        assert_eq!(names[1].0, "geo");
+        assert_eq!(resolved[0].entry, tmp.join("cache/strings-0.1.4/src/lib.kix"));
+        assert!(resolved[0].entry.is_file());
+
+        assert_eq!(remove(&app, "geo").unwrap(), "Removed geo");
+        assert_eq!(fs::read_to_string(app.join(MANIFEST)).unwrap(), "[package]\nname = \"app\"\nversion = \"0.1.0\"\nentry = \"src/main.kix\"\n\n[dependencies]\nstrings = \"0.1\"\n");
+        assert!(remove(&app, "geo").is_err());
+
+        assert_eq!(matches("^1.2", Version(1, 9, 0)), Ok(true));
+        assert_eq!(matches("1.2.3", Version(2, 0, 0)), Ok(false));
+        assert_eq!(matches(">=0.3", Version(1, 0, 0)), Ok(true));
+        assert!(parse_manifest("[package]\nname = \"Bad Name\"\n").is_err());
+        fs::remove_dir_all(&tmp).unwrap();
+    }
+}
@@ -0,0 +1,199 @@
+//! Permission prompts for `kivm exec --sandbox`: allow a missing capability
+//! once, always or not at all.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+use kinetix_kicomp::capability::{Capability, CapabilityError};
+use kinetix_language::atomic::write_atomic;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// An answer to a permission prompt.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Decision {
+    Once,
+    Always,
+    Deny,
+}
+
+/// The capabilities allowed "always" for one version of a script.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Grant {
+    /// Hex sha256 of the source the grant was given to.
+    sha256: String,
+    /// Capability names, as displayed.
+    capabilities: Vec<String>,
+}
+
+/// Grants by canonical script path, as kept in `~/.kinetix/permissions.json`:
+///
+/// ```json
+/// { "/home/me/tools/fetch.kix": { "sha256": "9f86d0…", "capabilities": ["NetAccess"] } }
+/// ```
+type Store = BTreeMap<String, Grant>;
+
+fn sha256_hex(source: &str) -> String {
+    Sha256::digest(source.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Where "always" decisions are kept.
+pub fn store_path() -> Result<PathBuf, String> {
+    Ok(crate::kinetix_home().ok_or("Cannot determine home directory")?.join("permissions.json"))
+}
+
+/// Whether the user can be asked: stdin and stderr are both terminals.
+/// Otherwise nothing is asked and missing capabilities stay compile errors,
+/// as the flags alone decide.
+pub fn interactive() -> bool {
+    std::io::stdin().is_terminal() && std::io::stderr().is_terminal()
+}
+
+/// Asks `question` on the terminal; anything but once or always denies.
+pub fn ask_terminal(question: &str) -> Decision {
+    eprint!("{}", question);
+    let _ = std::io::stderr().flush();
+    let mut answer = String::new();
+    if std::io::stdin().read_line(&mut answer).is_err() {
+        return Decision::Deny;
+    }
+    match answer.trim().to_ascii_lowercase().as_str() {
+        "o" | "once" => Decision::Once,
+        "a" | "always" => Decision::Always,
+        _ => Decision::Deny,
+    }
+}
+
+/// The capabilities missing in `errors` that `script` may use after all:
+/// those allowed "always" before to this same `source`, then those `ask`
+/// allows now. New "always" answers are saved to `store` with the sha256 of
+/// `source`; once the script is edited its hash no longer matches, so its
+/// grants are forgotten and it is asked again.
+pub fn request(script: &Path, source: &str, errors: &[CapabilityError], store: &Path, mut ask: impl FnMut(&str) -> Decision) -> Result<Vec<Capability>, String> {
+    let key = script.canonicalize().unwrap_or_else(|_| script.to_path_buf()).display().to_string();
+    let hash = sha256_hex(source);
+    let mut decisions: Store = match fs::read_to_string(store) {
+        Ok(text) => serde_json::from_str(&text).map_err(|e| format!("Invalid {}: {}", store.display(), e))?,
+        Err(_) => Store::new(),
+    };
+    // Grants given to an earlier version of the script no longer apply
+    let mut changed = decisions.get(&key).is_some_and(|grant| grant.sha256 != hash);
+    if changed {
+        decisions.remove(&key);
+    }
+
+    let mut missing: Vec<(Capability, Vec<usize>)> = Vec::new();
+    for error in errors {
+        match missing.iter_mut().find(|(cap, _)| *cap == error.capability) {
+            Some((_, lines)) => lines.push(error.span.line),
+            None => missing.push((error.capability.clone(), vec![error.span.line])),
+        }
+    }
+
+    let mut granted = Vec::new();
+    for (cap, lines) in missing {
+        let remembered = decisions.get(&key).is_some_and(|grant| grant.capabilities.contains(&cap.to_string()));
+        let decision = if remembered {
+            Decision::Once
+        } else {
+            let lines: Vec<String> = lines.iter().map(usize::to_string).collect();
+            let name = script.file_name().map_or(key.clone(), |name| name.to_string_lossy().to_string());
+            ask(&format!(
+                "{} wants {} (line {}) — allow [o]nce, [a]lways or [d]eny? ",
+                name, cap.description(), lines.join(", ")
+            ))
+        };
+        if decision == Decision::Always {
+            let grant = decisions.entry(key.clone()).or_default();
+            grant.sha256.clone_from(&hash);
+            grant.capabilities.push(cap.to_string());
+            changed = true;
+        }
+        if decision != Decision::Deny {
+            granted.push(cap);
+        }
+    }
+
+    if changed {
+        if let Some(dir) = store.parent() {
+            fs::create_dir_all(dir).map_err(|e| format!("Cannot create {}: {}", dir.display(), e))?;
+        }
+        let text = serde_json::to_string_pretty(&decisions).map_err(|e| e.to_string())?;
+        write_atomic(store, (text + "\n").as_bytes()).map_err(|e| format!("Cannot write {}: {}", store.display(), e))?;
+    }
+    Ok(granted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kinetix_language::span::Span;
+
+    const SOURCE: &str = "let page = net.get(\"https://example.com\")\n";
+
+    fn missing(capability: Capability, line: usize) -> CapabilityError {
+        CapabilityError { message: String::new(), span: Span { line, ..Span::default() }, capability }
+    }
+
+    #[test]
+    fn always_is_remembered_per_script_and_once_is_not() {
+        let dir = std::env::temp_dir().join(format!("kinetix-permissions-{}", std::process::id()));
+        let store = dir.join("permissions.json");
+        let script = dir.join("fetch.kix");
+        let errors = [missing(Capability::NetAccess, 3), missing(Capability::OsExecute, 5), missing(Capability::NetAccess, 9)];
+
+        let mut questions = Vec::new();
+        let granted = request(&script, SOURCE, &errors, &store, |q| {
+            questions.push(q.to_string());
+            if q.contains("Network") { Decision::Always } else { Decision::Deny }
+        }).unwrap();
+        assert_eq!(granted, [Capability::NetAccess]);
+        assert_eq!(questions, [
+            "fetch.kix wants Network access (line 3, 9) — allow [o]nce, [a]lways or [d]eny? ",
+            "fetch.kix wants Permission to run programs (line 5) — allow [o]nce, [a]lways or [d]eny? ",
+        ]);
+
+        // Network is remembered; running programs is asked again
+        let granted = request(&script, SOURCE, &errors, &store, |q| {
+            assert!(q.contains("run programs"), "{}", q);
+            Decision::Once
+        }).unwrap();
+        assert_eq!(granted, [Capability::NetAccess, Capability::OsExecute]);
+
+        // Another script shares nothing
+        let granted = request(&dir.join("other.kix"), SOURCE, &errors[..1], &store, |_| Decision::Deny).unwrap();
+        assert!(granted.is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn always_is_forgotten_once_the_script_is_edited() {
+        let dir = std::env::temp_dir().join(format!("kinetix-permissions-edited-{}", std::process::id()));
+        let store = dir.join("permissions.json");
+        let script = dir.join("fetch.kix");
+        let errors = [missing(Capability::NetAccess, 1)];
+
+        let granted = request(&script, SOURCE, &errors, &store, |_| Decision::Always).unwrap();
+        assert_eq!(granted, [Capability::NetAccess]);
+        let granted = request(&script, SOURCE, &errors, &store, |q| panic!("asked again: {}", q)).unwrap();
+        assert_eq!(granted, [Capability::NetAccess]);
+
+        // The same path with other contents is asked again, and a deny drops the old grant
+        let edited = format!("{}os.exec(\"rm\", [\"-rf\", \"~\"])\n", SOURCE);
+        let mut asked = 0;
+        let granted = request(&script, &edited, &errors, &store, |_| {
+            asked += 1;
+            Decision::Deny
+        }).unwrap();
+        assert_eq!((asked, granted), (1, vec![]));
+        let mut asked = 0;
+        request(&script, SOURCE, &errors, &store, |_| {
+            asked += 1;
+            Decision::Deny
+        }).unwrap();
+        assert_eq!(asked, 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
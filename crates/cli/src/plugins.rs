@@ -0,0 +1,45 @@
+//! `--plugin FILE`: loads compiler passes from a dynamic library built with
+//! `kinetix_kicomp::declare_plugin!` (see `kinetix_kicomp::plugin`).
+
+use std::path::Path;
+
+use kinetix_kicomp::plugin::{self, Plugin, API_VERSION};
+
+/// Registers the passes of the plugin at `path`. Returns how many there are.
+/// The plugin runs with kivm's permissions, so only files named on the
+/// command line are loaded; the library stays loaded until kivm exits.
+pub fn load(path: &Path) -> Result<usize, String> {
+    let fail = |e: &dyn std::fmt::Display| format!("plugin {}: {}", path.display(), e);
+    // SAFETY: the library is trusted as much as kivm itself (it was named
+    // with --plugin). Its version is checked through plain C functions
+    // before anything with a Rust layout crosses the boundary.
+    unsafe {
+        let library = libloading::Library::new(path).map_err(|e| fail(&e))?;
+        let api = library.get::<extern "C" fn() -> u32>(b"kinetix_plugin_api").map_err(|e| fail(&e))?;
+        if api() != API_VERSION {
+            return Err(fail(&format!("built for plugin API {}, this kivm has {}", api(), API_VERSION)));
+        }
+        let build = library.get::<extern "C" fn() -> i64>(b"kinetix_plugin_build").map_err(|e| fail(&e))?;
+        if build() != kinetix_kicomp::compiler::CURRENT_BUILD {
+            return Err(fail(&format!("built against build {}, this kivm is build {}", build(), kinetix_kicomp::compiler::CURRENT_BUILD)));
+        }
+        let entry = library.get::<fn() -> Plugin>(b"kinetix_plugin").map_err(|e| fail(&e))?;
+        let passes = entry().passes;
+        let count = passes.len();
+        passes.into_iter().for_each(plugin::register);
+        // The passes' code lives in the library
+        std::mem::forget(library);
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_library_without_the_plugin_entry_points_is_refused() {
+        let missing = load(Path::new("/nonexistent/libplugin.so")).unwrap_err();
+        assert!(missing.starts_with("plugin /nonexistent/libplugin.so: "), "{}", missing);
+    }
+}
@@ -0,0 +1,150 @@
+//! `~/.kinetixrc`, the startup file of the interactive shell, and the
+//! aliases it (or the `alias` builtin) defines.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use bumpalo::Bump;
+
+#[derive(Default)]
+pub struct ShellConfig {
+    aliases: BTreeMap<String, String>,
+    /// The `fn` definitions from the startup file.
+    prelude: String,
+}
+
+/// The startup file's location, `~/.kinetixrc`.
+pub fn path() -> Option<PathBuf> {
+    directories::BaseDirs::new().map(|dirs| dirs.home_dir().join(".kinetixrc"))
+}
+
+impl ShellConfig {
+    /// Keeps the `fn` definitions of `source` as the prelude and returns
+    /// the other non-blank, non-comment lines with their line numbers, to
+    /// be run as shell lines (`alias ll='ls -l'`, `export EDITOR=vim`) when
+    /// `kivm shell` starts without a script. A definition that doesn't parse
+    /// is an error for its first line.
+    pub fn load(&mut self, source: &str) -> Vec<(usize, Result<String, String>)> {
+        let mut lines = Vec::new();
+        let mut definition: Option<(usize, String, i32)> = None;
+        for (i, line) in source.lines().enumerate() {
+            let trimmed = line.trim();
+            if definition.is_none() {
+                if trimmed.is_empty() || trimmed.starts_with('#') {
+                    continue;
+                }
+                if !trimmed.starts_with("fn ") {
+                    lines.push((i + 1, Ok(trimmed.to_string())));
+                    continue;
+                }
+                definition = Some((i + 1, String::new(), 0));
+            }
+            let (_, text, depth) = definition.as_mut().expect("set above");
+            text.push_str(line);
+            text.push('\n');
+            *depth += line.matches('{').count() as i32 - line.matches('}').count() as i32;
+            if *depth <= 0 && text.contains('{') {
+                let (start, text, _) = definition.take().expect("set above");
+                match parse_errors(&text) {
+                    None => self.prelude.push_str(&text),
+                    Some(e) => lines.push((start, Err(e))),
+                }
+            }
+        }
+        if let Some((start, _, _)) = definition {
+            lines.push((start, Err("unterminated fn definition".to_string())));
+        }
+        lines
+    }
+
+    /// The `fn` definitions every Kinetix line typed at the prompt is
+    /// compiled with, so they can be called there.
+    pub fn prelude(&self) -> &str {
+        &self.prelude
+    }
+
+    /// `line` with its first word replaced by the alias of that name, if
+    /// any, repeatedly; an alias is not expanded inside its own expansion.
+    pub fn expand_aliases(&self, line: &str) -> String {
+        let mut line = line.to_string();
+        let mut expanded = Vec::new();
+        loop {
+            let name = line.split_whitespace().next().unwrap_or("");
+            let Some(value) = self.aliases.get(name).filter(|_| !expanded.contains(&name.to_string())) else {
+                return line;
+            };
+            expanded.push(name.to_string());
+            line = format!("{}{}", value, &line.trim_start()[name.len()..]);
+        }
+    }
+
+    /// The `alias` builtin: with no arguments, lists every alias; `name`
+    /// shows one and `name=value` defines it. Returns the lines to print.
+    pub fn alias(&mut self, args: &[&str]) -> Result<Vec<String>, String> {
+        if args.is_empty() {
+            return Ok(self.aliases.iter().map(|(name, value)| describe(name, value)).collect());
+        }
+        let mut shown = Vec::new();
+        for arg in args {
+            match arg.split_once('=') {
+                Some((name, _)) if name.is_empty() || name.contains(['\'', '"', '/', '$']) => {
+                    return Err(format!("alias: {}: invalid alias name", name));
+                }
+                Some((name, value)) => {
+                    self.aliases.insert(name.to_string(), value.to_string());
+                }
+                None => match self.aliases.get(*arg) {
+                    Some(value) => shown.push(describe(arg, value)),
+                    None => return Err(format!("alias: {}: not found", arg)),
+                },
+            }
+        }
+        Ok(shown)
+    }
+
+    /// The `unalias` builtin.
+    pub fn unalias(&mut self, args: &[&str]) -> Result<(), String> {
+        for arg in args {
+            self.aliases.remove(*arg).ok_or_else(|| format!("unalias: {}: not found", arg))?;
+        }
+        Ok(())
+    }
+}
+
+/// An alias as the `alias` builtin would define it.
+fn describe(name: &str, value: &str) -> String {
+    format!("alias {}='{}'", name, value.replace('\'', "'\\''"))
+}
+
+/// The parser errors of Kinetix `source`, if any.
+fn parse_errors(source: &str) -> Option<String> {
+    let arena = Bump::new();
+    let mut parser = kinetix_language::parser::Parser::new(kinetix_language::lexer::Lexer::new(source), &arena);
+    parser.parse_program();
+    (!parser.errors.is_empty()).then(|| parser.errors.iter().map(|e| e.to_string()).collect::<Vec<_>>().join("; "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn startup_file_defines_functions_and_aliases() {
+        let mut config = ShellConfig::default();
+        let source = "# aliases\nalias ll='ls -l'\n\nfn greet(name: str) {\n  if name == \"\" {\n    println(\"hi\")\n  }\n}\nfn broken( {\n}\nexport A=1\n";
+        let lines = config.load(source);
+        assert_eq!(lines[0], (2, Ok("alias ll='ls -l'".to_string())));
+        assert_eq!(lines[1].0, 9);
+        assert!(lines[1].1.is_err());
+        assert_eq!(lines[2], (11, Ok("export A=1".to_string())));
+        assert!(config.prelude().starts_with("fn greet(name: str) {\n") && config.prelude().ends_with("  }\n}\n"));
+
+        assert_eq!(config.alias(&["ll=ls -l", "la=ll -a", "ls=ls -F"]), Ok(vec![]));
+        assert_eq!(config.expand_aliases("la  src"), "ls -F -l -a  src");
+        assert_eq!(config.expand_aliases("println(1)"), "println(1)");
+        assert_eq!(config.alias(&["ll"]), Ok(vec!["alias ll='ls -l'".to_string()]));
+        assert_eq!(config.unalias(&["la"]), Ok(()));
+        assert_eq!(config.alias(&["la"]), Err("alias: la: not found".to_string()));
+        assert_eq!(config.alias(&[]).unwrap(), ["alias ll='ls -l'", "alias ls='ls -F'"]);
+    }
+}
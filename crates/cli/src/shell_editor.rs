@@ -0,0 +1,185 @@
+//! The shell's line editor helper: tab completion and syntax colors for
+//! Kinetix source as it is typed.
+
+use std::borrow::Cow;
+
+use kinetix_kicomp::output::color_enabled;
+use kinetix_language::lexer::{Lexer, Token};
+use rustyline::completion::{Completer, FilenameCompleter, Pair};
+use rustyline::highlight::{CmdKind, Highlighter};
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Helper};
+
+/// The commands `run_shell_line` handles itself, besides Kinetix source.
+const COMMANDS: &[&str] = &[
+    "alias", "bg", "cat", "cd", "clear", "cp", "echo", "env", "exit", "export", "false", "fg", "grep", "head", "help",
+    "jobs", "kill", "kivm", "ls", "mkdir", "mv", "pwd", "quit", "rm", "tail", "touch", "true", "unalias", "wc", "which",
+    "whoami",
+];
+
+pub struct ShellEditor {
+    files: FilenameCompleter,
+    /// Globals of the Kinetix lines run so far; see `set_globals`.
+    globals: Vec<String>,
+}
+
+impl Default for ShellEditor {
+    fn default() -> Self {
+        ShellEditor { files: FilenameCompleter::new(), globals: Vec::new() }
+    }
+}
+
+impl ShellEditor {
+    /// What completes as a session global from now on.
+    pub fn set_globals(&mut self, globals: Vec<String>) {
+        self.globals = globals;
+    }
+
+    /// Builtins, commands and globals starting with `prefix`, sorted. A
+    /// builtin of a capitalized module (`Math.sqrt`) also completes in
+    /// lowercase, as it can be called.
+    fn names(&self, prefix: &str) -> Vec<Pair> {
+        let builtins = kinetix_kivm::builtins::builtin_names().flat_map(|name| {
+            let lower = name.split_once('.')
+                .filter(|(module, _)| module.chars().any(char::is_uppercase))
+                .map(|(module, rest)| format!("{}.{}", module.to_ascii_lowercase(), rest));
+            std::iter::once(name.to_string()).chain(lower)
+        });
+        let mut names: Vec<String> = builtins
+            .chain(COMMANDS.iter().map(|name| name.to_string()))
+            .chain(self.globals.iter().cloned())
+            .filter(|name| name.starts_with(prefix))
+            .collect();
+        names.sort_unstable();
+        names.dedup();
+        names.into_iter().map(|name| Pair { display: name.clone(), replacement: name }).collect()
+    }
+}
+
+/// Completes the word before the cursor. The first word of a line
+/// completes to a shell command, a builtin (`math.sq` to `math.sqrt`) or a
+/// global defined earlier in the session; the arguments of a shell command,
+/// and text inside a `"` string, complete to file paths.
+impl Completer for ShellEditor {
+    type Candidate = Pair;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let before = &line[..pos];
+        let start = before.char_indices().rev()
+            .take_while(|&(_, c)| c.is_alphanumeric() || c == '_' || c == '.')
+            .last()
+            .map_or(pos, |(i, _)| i);
+        let first_word = before.split_whitespace().next().unwrap_or("");
+        let in_string = before.matches('"').count() % 2 == 1;
+        let after_command = COMMANDS.contains(&first_word) && before.trim_start().len() > first_word.len();
+        if in_string || after_command {
+            return self.files.complete_path(line, pos);
+        }
+        if start == pos {
+            return Ok((pos, Vec::new()));
+        }
+        Ok((start, self.names(&before[start..])))
+    }
+}
+
+impl Highlighter for ShellEditor {
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        if !color_enabled() {
+            return Cow::Borrowed(line);
+        }
+        Cow::Owned(colorize(line))
+    }
+
+    fn highlight_char(&self, _line: &str, _pos: usize, _kind: CmdKind) -> bool {
+        true
+    }
+}
+
+impl Hinter for ShellEditor {
+    type Hint = String;
+}
+
+impl Validator for ShellEditor {}
+
+impl Helper for ShellEditor {}
+
+/// `line` with its Kinetix tokens wrapped in colors, whether or not colors
+/// are on (`highlight` checks). Text between tokens is copied as is, a `//`
+/// comment dimmed.
+fn colorize(line: &str) -> String {
+    let paint = |style: &str, text: &str| format!("\x1b[{}m{}\x1b[0m", style, text);
+    let mut out = String::with_capacity(line.len() * 2);
+    let mut lexer = Lexer::new(line);
+    let mut copied = 0;
+    loop {
+        let token = lexer.next_spanned();
+        let (start, end) = (token.span.start.max(copied), token.span.end.min(line.len()));
+        let gap = &line[copied..start];
+        match gap.find("//") {
+            Some(comment) => {
+                out.push_str(&gap[..comment]);
+                out.push_str(&paint("2", &gap[comment..]));
+            }
+            None => out.push_str(gap),
+        }
+        if token.node == Token::EOF || end <= start {
+            out.push_str(&line[start..]);
+            break;
+        }
+        let text = &line[start..end];
+        match token.node {
+            Token::Let | Token::Mut | Token::Fn | Token::Return | Token::If | Token::Else | Token::While | Token::For
+            | Token::In | Token::Class | Token::Struct | Token::Enum | Token::Trait | Token::Impl | Token::Import
+            | Token::Include | Token::Pub | Token::Break | Token::Continue | Token::As | Token::Match
+            | Token::State | Token::Computed | Token::Effect => out.push_str(&paint("1;35", text)),
+            Token::True | Token::False | Token::Null | Token::Integer(_) | Token::Float(_) => out.push_str(&paint("36", text)),
+            Token::String(_) | Token::BacktickString(_) => out.push_str(&paint("32", text)),
+            _ => out.push_str(text),
+        }
+        copied = end;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustyline::history::DefaultHistory;
+
+    fn complete(editor: &ShellEditor, line: &str) -> (usize, Vec<String>) {
+        let history = DefaultHistory::new();
+        let (start, pairs) = editor.complete(line, line.len(), &Context::new(&history)).unwrap();
+        (start, pairs.into_iter().map(|p| p.replacement).collect())
+    }
+
+    #[test]
+    fn completes_builtins_commands_globals_and_paths() {
+        let mut editor = ShellEditor::default();
+        editor.set_globals(vec!["total".to_string(), "double".to_string()]);
+        assert!(complete(&editor, "println(math.sq").1.contains(&"math.sqrt".to_string()));
+        assert_eq!(complete(&editor, "let x = math.sq").0, 8);
+        assert_eq!(complete(&editor, "unal").1, ["unalias"]);
+        assert_eq!(complete(&editor, "println(doub").1, ["double"]);
+        assert!(complete(&editor, "x + ").1.is_empty());
+
+        let dir = std::env::temp_dir().join(format!("kinetix-complete-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("notes.txt"), "").unwrap();
+        let prefix = format!("{}/no", dir.display());
+        assert!(complete(&editor, &format!("cat {}", prefix)).1.iter().any(|p| p.ends_with("notes.txt")));
+        assert!(complete(&editor, &format!("fs.read(\"{}", prefix)).1.iter().any(|p| p.ends_with("notes.txt")));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn colors_keywords_literals_and_comments() {
+        assert_eq!(
+            colorize("let s = \"hi\" // note"),
+            "\x1b[1;35mlet\x1b[0m s = \x1b[32m\"hi\"\x1b[0m \x1b[2m// note\x1b[0m"
+        );
+        assert_eq!(colorize("ls -la"), "ls -la");
+        assert_eq!(colorize("if n > 1 { return true }"),
+            "\x1b[1;35mif\x1b[0m n > \x1b[36m1\x1b[0m { \x1b[1;35mreturn\x1b[0m \x1b[36mtrue\x1b[0m }");
+    }
+}
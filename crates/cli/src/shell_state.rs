@@ -0,0 +1,116 @@
+//! What the Kinetix lines typed at the shell keep from one to the next.
+
+use kinetix_kicomp::compiler::Compiler;
+use kinetix_kivm::vm::VM;
+use kinetix_language::ast::Statement;
+
+/// Every line is compiled as a new chunk of one growing program (see
+/// `Compiler::next_chunk`) and run on the same VM, so `let x = 5` on one
+/// line can be used on the next.
+pub struct KinetixState {
+    compiler: Compiler,
+    /// Created by the first line that compiles.
+    vm: Option<VM>,
+}
+
+impl Default for KinetixState {
+    fn default() -> Self {
+        KinetixState { compiler: Compiler::new(), vm: None }
+    }
+}
+
+impl KinetixState {
+    /// Whether no line has run yet.
+    pub fn is_empty(&self) -> bool {
+        self.vm.is_none()
+    }
+
+    /// The globals and functions defined so far, for completion.
+    pub fn globals(&self) -> Vec<String> {
+        self.vm.iter().flat_map(|vm| vm.global_names()).map(str::to_string).collect()
+    }
+
+    /// Compiles `statements` after the lines before and runs them. A line
+    /// that fails to compile leaves the session as it was; one that fails at
+    /// runtime keeps whatever it assigned before the error.
+    pub fn run(&mut self, statements: &[Statement<'_>]) -> Result<(), String> {
+        let mut compiler = self.compiler.clone();
+        compiler.next_chunk();
+        let program = compiler.compile(statements, None)
+            .map_err(|errs| format!("Compilation error: {}", errs.iter().map(ToString::to_string).collect::<Vec<_>>().join("; ")))?
+            .clone();
+        self.compiler = compiler;
+        match &mut self.vm {
+            Some(vm) => vm.load(program),
+            None => self.vm = Some(VM::new(program)),
+        }
+        let vm = self.vm.as_mut().expect("set above");
+        vm.run().map_err(|e| format!("Runtime error: {}", e))
+    }
+}
+
+/// Whether `source` stops inside brackets or a backtick string, so the
+/// prompt should read another line. `"` strings and `//` comments end with
+/// their line; a closing bracket too many is left for the parser to report.
+pub fn incomplete(source: &str) -> bool {
+    let mut depth = 0i32;
+    let mut quote: Option<char> = None;
+    let mut chars = source.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(_), '\\') => {
+                chars.next();
+            }
+            (Some(q), c) if c == q => quote = None,
+            (Some('"'), '\n') => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '`') => quote = Some(c),
+            (None, '/') if chars.peek() == Some(&'/') => {
+                chars.by_ref().find(|&c| c == '\n');
+            }
+            (None, '{' | '[' | '(') => depth += 1,
+            (None, '}' | ']' | ')') => depth -= 1,
+            (None, _) => {}
+        }
+    }
+    depth > 0 || quote == Some('`')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bumpalo::Bump;
+    use kinetix_language::lexer::Lexer;
+    use kinetix_language::parser::Parser;
+
+    #[test]
+    fn open_brackets_and_backtick_strings_continue() {
+        assert!(incomplete("fn double(n: int) {"));
+        assert!(incomplete("let xs = [1,\n  2,"));
+        assert!(incomplete("let s = `first line"));
+        assert!(!incomplete("fn double(n: int) {\n  return n * 2\n}"));
+        assert!(!incomplete("println(\"{ (\") // [ unclosed in a comment"));
+        assert!(!incomplete("let s = \"unterminated"));
+        assert!(!incomplete("}"));
+    }
+
+    #[test]
+    fn globals_and_functions_survive_across_lines() {
+        let mut state = KinetixState::default();
+        let mut run = |source: &str| {
+            let arena = Bump::new();
+            let mut parser = Parser::new(Lexer::new(source), &arena);
+            let program = parser.parse_program();
+            assert!(parser.errors.is_empty(), "Parser errors: {:?}", parser.errors);
+            state.run(&program.statements)
+        };
+        run("let x = 5").unwrap();
+        run("fn double(n: int) -> int {\n  return n * 2\n}").unwrap();
+        run("let y = double(x)").unwrap();
+        run("assert(y == 10)").unwrap();
+        assert!(run("assert(y == 11)").is_err());
+        run("let z = double(y)\nassert(z == 20)").unwrap();
+        let globals = state.globals();
+        assert!(["double", "x", "y", "z"].iter().all(|name| globals.iter().any(|g| g == name)), "{:?}", globals);
+    }
+}
@@ -0,0 +1,146 @@
+//! Word splitting and expansion for `kivm shell` command lines.
+
+/// Splits `line` into words, expanding variables. `params` holds `$0`
+/// followed by the script arguments; it is empty at the interactive prompt.
+///
+/// Words are separated by unquoted whitespace. Inside single quotes every
+/// character is literal; inside double quotes `$` expansions still apply
+/// and a backslash escapes only `"`, `\`, `$` and `` ` ``; outside quotes a
+/// backslash escapes any character. `$NAME` and `${NAME}` expand to the
+/// environment variable (empty when unset), and an expansion is never split
+/// further, so a path with spaces stays one word. The script parameters
+/// are `$0`..`$9` (and `${10}`...), `$#` and `$@`, which expands to one
+/// word per argument.
+pub fn split(line: &str, params: &[String]) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    // The word being built, if one has started (`""` starts an empty word)
+    let mut word: Option<String> = None;
+    let mut chars = line.chars().peekable();
+    let mut quote: Option<char> = None;
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (None, c) if c.is_whitespace() => {
+                words.extend(word.take());
+            }
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (Some(q), c) if c == q => quote = None,
+            (None, '\\') => {
+                let escaped = chars.next().ok_or("trailing backslash")?;
+                word.get_or_insert_with(String::new).push(escaped);
+            }
+            (Some('"'), '\\') => {
+                let text = word.get_or_insert_with(String::new);
+                match chars.next_if(|next| matches!(next, '"' | '\\' | '$' | '`')) {
+                    Some(escaped) => text.push(escaped),
+                    None => text.push('\\'),
+                }
+            }
+            (None | Some('"'), '$') if chars.peek() == Some(&'@') => {
+                chars.next();
+                let mut args = params.iter().skip(1);
+                if let Some(first) = args.next() {
+                    word.get_or_insert_with(String::new).push_str(first);
+                    for arg in args {
+                        words.extend(word.replace(arg.clone()));
+                    }
+                }
+            }
+            (None | Some('"'), '$') => match parameter(&mut chars)? {
+                Some(name) => {
+                    // An empty expansion outside quotes is no word at all
+                    let value = lookup(&name, params);
+                    if !value.is_empty() {
+                        word.get_or_insert_with(String::new).push_str(&value);
+                    }
+                }
+                None => word.get_or_insert_with(String::new).push('$'),
+            },
+            (_, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if let Some(q) = quote {
+        return Err(format!("unterminated {} quote", if q == '"' { "double" } else { "single" }));
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+/// Substitutes the script parameters (`$0`, `$1`..., `$#`, `$@`) in `line`
+/// as plain text, ignoring quotes. Used for lines that are Kinetix source
+/// rather than commands, where `"$1"` is a string literal.
+pub fn substitute_params(line: &str, params: &[String]) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match chars.next_if(|next| c == '$' && (next.is_ascii_digit() || matches!(next, '#' | '@'))) {
+            Some(name) => out.push_str(&lookup(&name.to_string(), params)),
+            None => out.push(c),
+        }
+    }
+    out
+}
+
+/// Reads the name after a `$`: `NAME`, `{NAME}`, one digit or `#`. `None`
+/// when nothing that names a parameter follows, so the `$` is literal.
+fn parameter(chars: &mut std::iter::Peekable<std::str::Chars>) -> Result<Option<String>, String> {
+    if chars.next_if_eq(&'{').is_some() {
+        let mut name = String::new();
+        loop {
+            match chars.next() {
+                Some('}') if !name.is_empty() => return Ok(Some(name)),
+                Some(c) if c == '_' || c == '#' || c.is_ascii_alphanumeric() => name.push(c),
+                _ => return Err(format!("bad substitution: ${{{}", name)),
+            }
+        }
+    }
+    if let Some(c) = chars.next_if(|c| c.is_ascii_digit() || *c == '#') {
+        return Ok(Some(c.to_string()));
+    }
+    let mut name = String::new();
+    while let Some(c) = chars.next_if(|c| *c == '_' || c.is_ascii_alphanumeric()) {
+        name.push(c);
+    }
+    Ok((!name.is_empty()).then_some(name))
+}
+
+/// The value of parameter or environment variable `name`; empty when unset.
+fn lookup(name: &str, params: &[String]) -> String {
+    match name {
+        "#" => params.len().saturating_sub(1).to_string(),
+        "@" => params.iter().skip(1).cloned().collect::<Vec<_>>().join(" "),
+        _ => match name.parse::<usize>() {
+            Ok(index) => params.get(index).cloned().unwrap_or_default(),
+            Err(_) => std::env::var(name).unwrap_or_default(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn words_are_split_quoted_and_expanded() {
+        let params: Vec<String> = ["deploy.ks", "my dir", "b"].map(String::from).to_vec();
+        let home = std::env::var("HOME").unwrap_or_default();
+        let cases = [
+            ("cp 'a b.txt' \"$1\"/x", vec!["cp", "a b.txt", "my dir/x"]),
+            ("echo \\$HOME\\ x '$HOME' \"\\$\\q\"", vec!["echo", "$HOME x", "$HOME", "$\\q"]),
+            ("ls \"\" $UNSET_KINETIX_VAR ${10}", vec!["ls", ""]),
+            ("echo $# a$@z $ 5$", vec!["echo", "2", "amy dir", "bz", "$", "5$"]),
+        ];
+        for (line, words) in cases {
+            assert_eq!(split(line, &params).unwrap(), words, "{}", line);
+        }
+        assert_eq!(split("cd ${HOME}/src", &params).unwrap(), ["cd".to_string(), format!("{}/src", home)]);
+        assert_eq!(split("echo \"oops", &params).unwrap_err(), "unterminated double quote");
+        assert_eq!(split("echo ${A B}", &params).unwrap_err(), "bad substitution: ${A");
+        assert_eq!(
+            substitute_params("println(\"$0: $1 of $#, $@ $9$\")", &params),
+            "println(\"deploy.ks: my dir of 2, my dir b $\")"
+        );
+    }
+}
@@ -0,0 +1,135 @@
+//! Machine-readable `kivm test` results: `--format junit` and
+//! `--format json`.
+
+use std::time::Duration;
+
+/// `Human` streams `Running ... OK` lines instead and is not built here.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Human,
+    Junit,
+    Json,
+}
+
+impl Format {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "human" => Ok(Format::Human),
+            "junit" => Ok(Format::Junit),
+            "json" => Ok(Format::Json),
+            other => Err(format!("Unknown test format '{}' (expected human, junit or json)", other)),
+        }
+    }
+}
+
+/// One test file's result.
+pub struct TestCase {
+    pub name: String,
+    pub duration: Duration,
+    /// The failure message; `None` when the test passed.
+    pub failure: Option<String>,
+    /// What the test printed.
+    pub output: String,
+}
+
+/// One JUnit XML document, in the dialect CI servers read: `testsuites` >
+/// `testsuite` > `testcase`, with `failure` and `system-out`.
+pub fn junit(suite: &str, cases: &[TestCase], total: Duration) -> String {
+    let failures = cases.iter().filter(|c| c.failure.is_some()).count();
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!(
+        "<testsuites tests=\"{}\" failures=\"{}\" time=\"{:.3}\">\n",
+        cases.len(), failures, total.as_secs_f64()
+    ));
+    xml.push_str(&format!(
+        "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" time=\"{:.3}\">\n",
+        escape(suite), cases.len(), failures, total.as_secs_f64()
+    ));
+    for case in cases {
+        xml.push_str(&format!(
+            "    <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\">\n",
+            escape(&case.name), escape(suite), case.duration.as_secs_f64()
+        ));
+        if let Some(message) = &case.failure {
+            let summary = message.lines().next().unwrap_or("");
+            xml.push_str(&format!("      <failure message=\"{}\">{}</failure>\n", escape(summary), escape(message)));
+        }
+        if !case.output.is_empty() {
+            xml.push_str(&format!("      <system-out>{}</system-out>\n", escape(&case.output)));
+        }
+        xml.push_str("    </testcase>\n");
+    }
+    xml.push_str("  </testsuite>\n</testsuites>\n");
+    xml
+}
+
+pub fn json(suite: &str, cases: &[TestCase], total: Duration) -> String {
+    let tests: Vec<serde_json::Value> = cases.iter().map(|case| serde_json::json!({
+        "name": case.name,
+        "status": if case.failure.is_some() { "failed" } else { "passed" },
+        "duration_ms": case.duration.as_secs_f64() * 1000.0,
+        "failure": case.failure,
+        "output": case.output,
+    })).collect();
+    let passed = cases.iter().filter(|c| c.failure.is_none()).count();
+    let report = serde_json::json!({
+        "suite": suite,
+        "passed": passed,
+        "failed": cases.len() - passed,
+        "duration_ms": total.as_secs_f64() * 1000.0,
+        "tests": tests,
+    });
+    serde_json::to_string_pretty(&report).unwrap_or_default()
+}
+
+/// Escapes text for an XML attribute or element; control characters other
+/// than tab and newline are not allowed in XML 1.0 and are dropped.
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            '\t' | '\n' | '\r' => out.push(c),
+            c if c.is_control() => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cases() -> Vec<TestCase> {
+        vec![
+            TestCase { name: "tests/test_ok.kix".into(), duration: Duration::from_millis(12), failure: None, output: "hi <there>\n".into() },
+            TestCase { name: "tests/test_bad.kix".into(), duration: Duration::from_millis(3), failure: Some("Runtime error: \"x\" & y\nmore".into()), output: String::new() },
+        ]
+    }
+
+    #[test]
+    fn junit_reports_failures_and_escapes_text() {
+        let xml = junit("unit", &cases(), Duration::from_millis(15));
+        assert!(xml.contains("<testsuites tests=\"2\" failures=\"1\" time=\"0.015\">"), "{}", xml);
+        assert!(xml.contains("<testcase name=\"tests/test_ok.kix\" classname=\"unit\" time=\"0.012\">"));
+        assert!(xml.contains("<system-out>hi &lt;there&gt;\n</system-out>"));
+        assert!(xml.contains("<failure message=\"Runtime error: &quot;x&quot; &amp; y\">Runtime error: &quot;x&quot; &amp; y\nmore</failure>"));
+    }
+
+    #[test]
+    fn json_lists_every_test_with_its_status() {
+        let report: serde_json::Value = serde_json::from_str(&json("unit", &cases(), Duration::from_millis(15))).unwrap();
+        assert_eq!(report["passed"], 1);
+        assert_eq!(report["failed"], 1);
+        assert_eq!(report["tests"][0]["status"], "passed");
+        assert_eq!(report["tests"][0]["output"], "hi <there>\n");
+        assert_eq!(report["tests"][1]["status"], "failed");
+        assert_eq!(report["tests"][1]["failure"], "Runtime error: \"x\" & y\nmore");
+        assert!(Format::parse("tap").is_err());
+    }
+}
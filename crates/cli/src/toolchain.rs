@@ -0,0 +1,246 @@
+//! Toolchains: several Kinetix builds side by side, each under
+//! `~/.kinetix/toolchains/<build>/`, selected per project or globally.
+
+use kinetix_kicomp::compiler::CURRENT_BUILD;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const PROJECT_FILE: &str = "kinetix.toml";
+
+/// Set on a dispatched child so a mislabelled toolchain cannot loop.
+const DISPATCHED_VAR: &str = "KINETIX_TOOLCHAIN_DISPATCHED";
+
+/// The selected build and what selected it (for messages).
+pub struct Selection {
+    pub build: i64,
+    pub source: String,
+}
+
+pub fn root() -> Result<PathBuf, String> {
+    Ok(crate::kinetix_home().ok_or("Cannot determine home directory")?.join("toolchains"))
+}
+
+fn exe_name(tool: &str) -> String {
+    format!("{}{}", tool, std::env::consts::EXE_SUFFIX)
+}
+
+pub fn exe_path(build: i64, tool: &str) -> Result<PathBuf, String> {
+    Ok(root()?.join(build.to_string()).join(exe_name(tool)))
+}
+
+/// Installed builds, oldest first.
+pub fn installed() -> Vec<i64> {
+    let mut builds: Vec<i64> = root().ok()
+        .and_then(|root| fs::read_dir(root).ok())
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| entry.file_name().to_str()?.parse().ok())
+        .filter(|build| exe_path(*build, "kivm").is_ok_and(|p| p.is_file()))
+        .collect();
+    builds.sort_unstable();
+    builds
+}
+
+/// `KINETIX_TOOLCHAIN`, else the nearest `kinetix.toml` pin
+/// (`toolchain = "<build>"`), else the global default that `use --global`
+/// writes to `~/.kinetix/toolchains/default`; `None` when nothing selects a
+/// build.
+pub fn selected() -> Result<Option<Selection>, String> {
+    if let Ok(value) = std::env::var("KINETIX_TOOLCHAIN") {
+        let build = parse_build(&value).ok_or_else(|| format!("KINETIX_TOOLCHAIN: '{}' is not a build number", value))?;
+        return Ok(Some(Selection { build, source: "KINETIX_TOOLCHAIN".to_string() }));
+    }
+    let cwd = std::env::current_dir().map_err(|e| e.to_string())?;
+    for dir in cwd.ancestors() {
+        let file = dir.join(PROJECT_FILE);
+        let Ok(text) = fs::read_to_string(&file) else { continue };
+        if let Some(build) = read_pin(&text).map_err(|e| format!("{}: {}", file.display(), e))? {
+            return Ok(Some(Selection { build, source: file.display().to_string() }));
+        }
+    }
+    let default = root()?.join("default");
+    match fs::read_to_string(&default) {
+        Ok(text) => {
+            let build = parse_build(text.trim()).ok_or_else(|| format!("{}: not a build number", default.display()))?;
+            Ok(Some(Selection { build, source: "global default".to_string() }))
+        }
+        Err(_) => Ok(None),
+    }
+}
+
+/// Runs the command line with the selected build's `kivm` when that is not
+/// this build, returning its exit code, so the `kivm` on PATH acts as a shim. `kivm toolchain ...` always runs
+/// here, so a missing or broken pin can be fixed.
+pub fn dispatch(args: &[String]) -> Result<Option<i32>, String> {
+    if args.get(1).is_some_and(|a| a == "toolchain") || std::env::var_os(DISPATCHED_VAR).is_some() {
+        return Ok(None);
+    }
+    let Some(selection) = selected()? else { return Ok(None) };
+    if selection.build == CURRENT_BUILD {
+        return Ok(None);
+    }
+    let exe = exe_path(selection.build, "kivm")?;
+    if !exe.is_file() {
+        return Err(format!(
+            "Toolchain {} (selected by {}) is not installed.\nInstall it with `kivm toolchain install {} --from <dir>`, or pick another with `kivm toolchain use <build>`.",
+            selection.build, selection.source, selection.build
+        ));
+    }
+    let status = std::process::Command::new(&exe)
+        .args(&args[1..])
+        .env("KINETIX_TOOLCHAIN", selection.build.to_string())
+        .env(DISPATCHED_VAR, "1")
+        .status()
+        .map_err(|e| format!("Failed to run toolchain {} ({}): {}", selection.build, exe.display(), e))?;
+    Ok(Some(status.code().unwrap_or(1)))
+}
+
+/// Copies `kivm` (and `kicomp`, when present) from `from`, or this binary and
+/// its sibling `kicomp` when `from` is `None`, into the build's toolchain
+/// directory. The build is read from the binary itself and must match
+/// `build` when one is given.
+pub fn install(build: Option<i64>, from: Option<&Path>) -> Result<String, String> {
+    let (kivm, detected) = match from {
+        Some(dir) => {
+            let kivm = dir.join(exe_name("kivm"));
+            if !kivm.is_file() {
+                return Err(format!("No {} in {}", exe_name("kivm"), dir.display()));
+            }
+            let detected = detect_build(&kivm)?;
+            (kivm, detected)
+        }
+        None => (std::env::current_exe().map_err(|e| e.to_string())?, CURRENT_BUILD),
+    };
+    if let Some(build) = build.filter(|b| *b != detected) {
+        return Err(match from {
+            Some(_) => format!("{} is build {}, not {}", kivm.display(), detected, build),
+            None => format!("This kivm is build {}; pass --from <dir> with build {}'s binaries", detected, build),
+        });
+    }
+
+    let dest = root()?.join(detected.to_string());
+    fs::create_dir_all(&dest).map_err(|e| format!("Cannot create {}: {}", dest.display(), e))?;
+    let mut copied = Vec::new();
+    for tool in ["kivm", "kicomp"] {
+        let src = kivm.with_file_name(exe_name(tool));
+        if !src.is_file() {
+            continue;
+        }
+        let target = dest.join(exe_name(tool));
+        if fs::canonicalize(&src).ok() != fs::canonicalize(&target).ok() {
+            fs::copy(&src, &target).map_err(|e| format!("Cannot copy {}: {}", src.display(), e))?;
+        }
+        copied.push(tool);
+    }
+    Ok(format!("Installed toolchain {} ({}) to {}", detected, copied.join(", "), dest.display()))
+}
+
+/// Asks a `kivm` for its build: `kivm version` prints `Kinetix vX (BUILD)`.
+fn detect_build(kivm: &Path) -> Result<i64, String> {
+    let output = std::process::Command::new(kivm)
+        .arg("version")
+        .env(DISPATCHED_VAR, "1")
+        .output()
+        .map_err(|e| format!("Cannot run {}: {}", kivm.display(), e))?;
+    let text = String::from_utf8_lossy(&output.stdout);
+    text.rsplit_once('(')
+        .and_then(|(_, rest)| parse_build(rest.split(')').next()?))
+        .ok_or_else(|| format!("Cannot read the build of {} from `version`: {}", kivm.display(), text.trim()))
+}
+
+pub fn list() -> Result<String, String> {
+    let selection = selected()?;
+    let builds = installed();
+    let mut out = String::new();
+    if builds.is_empty() {
+        out.push_str(&format!("No toolchains installed in {}\n", root()?.display()));
+    }
+    for build in &builds {
+        let mut notes = Vec::new();
+        if *build == CURRENT_BUILD {
+            notes.push("this kivm".to_string());
+        }
+        if let Some(s) = selection.as_ref().filter(|s| s.build == *build) {
+            notes.push(format!("selected by {}", s.source));
+        }
+        let notes = if notes.is_empty() { String::new() } else { format!("  ({})", notes.join(", ")) };
+        out.push_str(&format!("  {}{}\n", build, notes));
+    }
+    if let Some(s) = selection.filter(|s| !builds.contains(&s.build)) {
+        out.push_str(&format!("Selected toolchain {} ({}) is not installed\n", s.build, s.source));
+    }
+    Ok(out)
+}
+
+/// Pins `build` in `./kinetix.toml`, or makes it the global default.
+pub fn use_build(build: i64, global: bool) -> Result<String, String> {
+    if build != CURRENT_BUILD && !exe_path(build, "kivm")?.is_file() {
+        return Err(format!("Toolchain {} is not installed (installed: {})", build, describe(&installed())));
+    }
+    if global {
+        let root = root()?;
+        fs::create_dir_all(&root).map_err(|e| e.to_string())?;
+        fs::write(root.join("default"), format!("{}\n", build)).map_err(|e| e.to_string())?;
+        return Ok(format!("Default toolchain is now {}", build));
+    }
+    let file = PathBuf::from(PROJECT_FILE);
+    let text = fs::read_to_string(&file).unwrap_or_default();
+    fs::write(&file, write_pin(&text, build)).map_err(|e| format!("Cannot write {}: {}", PROJECT_FILE, e))?;
+    Ok(format!("Pinned toolchain {} in {}", build, PROJECT_FILE))
+}
+
+fn describe(builds: &[i64]) -> String {
+    if builds.is_empty() {
+        "none".to_string()
+    } else {
+        builds.iter().map(i64::to_string).collect::<Vec<_>>().join(", ")
+    }
+}
+
+fn parse_build(value: &str) -> Option<i64> {
+    value.trim().trim_matches('"').parse().ok().filter(|b| *b > 0)
+}
+
+/// The top-level `toolchain = "<build>"` key of a `kinetix.toml`.
+fn read_pin(text: &str) -> Result<Option<i64>, String> {
+    for line in text.lines().map(|l| l.split('#').next().unwrap_or("").trim()) {
+        if line.starts_with('[') {
+            break;
+        }
+        if let Some((_, value)) = line.split_once('=').filter(|(key, _)| key.trim() == "toolchain") {
+            return parse_build(value).map(Some).ok_or_else(|| format!("toolchain = {} is not a build number", value.trim()));
+        }
+    }
+    Ok(None)
+}
+
+/// `text` with its top-level `toolchain` key set to `build`, added at the top
+/// when missing; everything else is kept as is.
+fn write_pin(text: &str, build: i64) -> String {
+    let pin = format!("toolchain = \"{}\"", build);
+    let mut lines: Vec<String> = text.lines().map(str::to_string).collect();
+    let top_level = lines.iter().position(|l| l.trim_start().starts_with('[')).unwrap_or(lines.len());
+    match lines[..top_level].iter().position(|l| l.split_once('=').is_some_and(|(k, _)| k.trim() == "toolchain")) {
+        Some(i) => lines[i] = pin,
+        None => lines.insert(0, pin),
+    }
+    lines.join("\n") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pins_are_read_and_rewritten_at_top_level_only() {
+        assert_eq!(read_pin("toolchain = \"36\"\n"), Ok(Some(36)));
+        assert_eq!(read_pin("# pinned\ntoolchain=35 # old\n[deps]\n"), Ok(Some(35)));
+        assert_eq!(read_pin("[deps]\ntoolchain = \"36\"\n"), Ok(None));
+        assert!(read_pin("toolchain = \"latest\"\n").is_err());
+
+        assert_eq!(write_pin("", 36), "toolchain = \"36\"\n");
+        assert_eq!(write_pin("name = \"app\"\ntoolchain = \"35\"\n[deps]\n", 36), "name = \"app\"\ntoolchain = \"36\"\n[deps]\n");
+        assert_eq!(write_pin("[deps]\ntoolchain = 1\n", 36), "toolchain = \"36\"\n[deps]\ntoolchain = 1\n");
+    }
+}
@@ -0,0 +1,16 @@
+/// Runs the language conformance suite (`spec/`) through the real `kivm`
+/// binary, exactly as `kivm test --conformance spec` does, on every backend
+/// this build has.
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn spec_programs_conform_on_every_backend() {
+    let spec = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../spec");
+    let out = Command::new(env!("CARGO_BIN_EXE_kivm"))
+        .args(["test", "--conformance"])
+        .arg(&spec)
+        .output()
+        .expect("kivm runs");
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stdout));
+}
@@ -3,6 +3,8 @@
 use eframe::egui;
 use std::path::{Path, PathBuf};
 use std::fs;
+use sha2::{Digest, Sha256};
+use kinetix_language::atomic::write_atomic;
 
 #[cfg(target_os = "windows")]
 use winreg::enums::*;
@@ -166,6 +168,7 @@ enum InstallState {
     Done,
     Failed(String),
     Repair,
+    Repaired { problems: Vec<String>, errors: Vec<String> },
     Uninstall,
 }
 
@@ -178,6 +181,17 @@ struct InstallerApp {
     state: InstallState,
     agreed_license: bool,
     launch_shell_after: bool,
+    /// Skip downloads; documentation comes only from a local folder.
+    offline: bool,
+    /// Base URL serving `<build>.zip` documentation archives instead of
+    /// GitHub (`KINETIX_DOCS_MIRROR`, editable in the Config screen).
+    docs_mirror: String,
+    /// What the documentation step did, shown once installing is over.
+    docs_report: Vec<String>,
+    docs_installed: bool,
+    /// Errors from the uninstall, once it has run (it runs on the first frame
+    /// of the Uninstall screen).
+    uninstall_errors: Option<Vec<String>>,
 }
 
 impl InstallerApp {
@@ -199,6 +213,11 @@ impl InstallerApp {
             state: initial_state,
             agreed_license: false,
             launch_shell_after: true,
+            offline: args.iter().any(|arg| arg == "--offline"),
+            docs_mirror: std::env::var("KINETIX_DOCS_MIRROR").unwrap_or_default(),
+            docs_report: Vec::new(),
+            docs_installed: false,
+            uninstall_errors: None,
         }
     }
 
@@ -278,7 +297,7 @@ impl InstallerApp {
             if self.install_kivm {
                 let cli_path = bin_dir.join(cli_filename());
                 self.log(format!("Writing kivm to {:?}", cli_path));
-                fs::write(&cli_path, CLI_BYTES)?;
+                write_atomic(&cli_path, CLI_BYTES)?;
                 #[cfg(unix)]
                 set_executable(&cli_path)?;
             } else {
@@ -291,7 +310,7 @@ impl InstallerApp {
             if self.install_kicomp {
                 let comp_path = bin_dir.join(kicomp_filename());
                 self.log(format!("Writing kicomp to {:?}", comp_path));
-                fs::write(&comp_path, KICOMP_BYTES)?;
+                write_atomic(&comp_path, KICOMP_BYTES)?;
                 #[cfg(unix)]
                 set_executable(&comp_path)?;
             } else {
@@ -303,7 +322,7 @@ impl InstallerApp {
         step!("Install Icon", {
             let icon_path = self.install_path.join("assets").join("KiFile.png");
             self.log(format!("Writing icon to {:?}", icon_path));
-            let _ = fs::write(&icon_path, ICON_BYTES);
+            let _ = write_atomic(&icon_path, ICON_BYTES);
         });
 
         // Step 4: Configure PATH
@@ -412,114 +431,7 @@ impl InstallerApp {
         // Step 7: Install documentation
         step!("Install Documentation", {
             if self.install_docs {
-                let docs_dest = self.install_path.join("docs");
-                self.log(format!("Creating docs directory at {:?}", docs_dest));
-                let _ = fs::remove_dir_all(&docs_dest); // Clear old docs
-                fs::create_dir_all(&docs_dest)?;
-
-                // Try GitHub download
-                let build_no = option_env!("KINETIX_BUILD").unwrap_or("10"); // Defaults to 10 if not set via cargo build
-                
-                // GitHub automatically generates "Source code (zip)" for releases.
-                // The URL for this is /archive/refs/tags/{TAG}.zip
-                let url_tag = format!("https://github.com/MisterY3515/Kinetix-Documentation/archive/refs/tags/{}.zip", build_no);
-                
-                // Fallback to main branch archive just in case
-                let url_main = "https://github.com/MisterY3515/Kinetix-Documentation/archive/refs/heads/main.zip";
-
-                self.log(format!("Downloading docs for build {}...", build_no));
-                
-                let mut download_success = false;
-                
-                // Helper closure to download and extract
-                // `strip_root` boolean tells us whether to strip the first folder component
-                // (source archives have a root folder, release assets typically do not).
-                let download_and_extract = |url: &str, strip_root: bool| -> Result<(), Box<dyn std::error::Error>> {
-                    let response = ureq::get(url).call()?;
-                    let mut reader = response.into_reader();
-                    let mut zip_bytes = Vec::new();
-                    std::io::copy(&mut reader, &mut zip_bytes)?;
-                    
-                    let cursor = std::io::Cursor::new(zip_bytes);
-                    let mut archive = zip::ZipArchive::new(cursor)?;
-                    
-                    for i in 0..archive.len() {
-                        let mut file = archive.by_index(i)?;
-                        let outpath = match file.enclosed_name() {
-                            Some(path) => path.to_owned(),
-                            None => continue,
-                        };
-                        
-                        let final_path = if strip_root {
-                            let components: Vec<_> = outpath.components().collect();
-                            if components.is_empty() { continue; }
-                            let stripped_path: PathBuf = components[1..].iter().collect();
-                            if stripped_path.as_os_str().is_empty() { continue; }
-                            stripped_path
-                        } else {
-                            outpath
-                        };
-                        
-                        let dest_path = docs_dest.join(final_path);
-                        
-                        if (*file.name()).ends_with('/') {
-                            fs::create_dir_all(&dest_path)?;
-                        } else {
-                            if let Some(p) = dest_path.parent() {
-                                if !p.exists() {
-                                    fs::create_dir_all(p)?;
-                                }
-                            }
-                            let mut outfile = fs::File::create(&dest_path)?;
-                            std::io::copy(&mut file, &mut outfile)?;
-                        }
-                    }
-                    Ok(())
-                };
-
-                // Try tagged release
-                if let Err(_) = download_and_extract(&url_tag, true) {
-                    self.log(format!("Failed to download tag {}. Trying main branch archive...", build_no));
-                    if let Err(_) = download_and_extract(url_main, true) {
-                        self.log("Failed to download main branch.");
-                    } else {
-                        self.log("Successfully downloaded and extracted docs from main branch.");
-                        download_success = true;
-                    }
-                } else {
-                    self.log(format!("Successfully downloaded and extracted docs from tag {}.", build_no));
-                    download_success = true;
-                }
-
-                // Fallback to local
-                if !download_success {
-                    self.log("Falling back to local documentation...");
-                    let exe_dir = std::env::current_exe()
-                        .ok()
-                        .and_then(|p| p.parent().map(|p| p.to_path_buf()));
-                    
-                    if let Some(exe_parent) = &exe_dir {
-                        let docs_src = exe_parent.join("docs");
-                        if docs_src.exists() {
-                            self.log(format!("Found local docs at {:?}", docs_src));
-                            copy_dir_recursive(&docs_src, &docs_dest)?;
-                        } else {
-                            // Also try parent/Documentation
-                            let docs_src2 = exe_parent.parent()
-                                .map(|p| p.join("Documentation"));
-                            if let Some(ref src2) = docs_src2 {
-                                if src2.exists() {
-                                    self.log(format!("Found local docs at {:?}", src2));
-                                    copy_dir_recursive(src2, &docs_dest)?;
-                                } else {
-                                    self.log("Warning: Local documentation folder not found.");
-                                }
-                            } else {
-                                self.log("Warning: Could not determine local Documentation path.");
-                            }
-                        }
-                    }
-                }
+                self.install_documentation()?;
             } else {
                 self.log("Skipping documentation");
             }
@@ -532,7 +444,7 @@ impl InstallerApp {
             // 1. Copy installer itself into the bin directory so it can be invoked for uninstalls without the original setup.exe
             if let Ok(current_exe) = std::env::current_exe() {
                 let bin_dir = self.install_path.join("bin");
-                let _ = fs::copy(&current_exe, bin_dir.join("installer.exe"));
+                let _ = fs::read(&current_exe).and_then(|bytes| write_atomic(&bin_dir.join("installer.exe"), &bytes));
             }
 
             // 2. Add Windows Add/Remove programs entry
@@ -551,10 +463,161 @@ impl InstallerApp {
                     let _ = key.set_value("ModifyPath", &format!("\"{}\" --repair", uninstaller.display()));
                 }
             }
+
+            // 3. Record what was installed so repair and uninstall know what is ours
+            self.log(format!("Writing install manifest {:?}", self.install_path.join(MANIFEST_NAME)));
+            write_manifest(&self.install_path)?;
         });
 
         self.state = InstallState::Done;
     }
+
+    /// Verifies the installed files against the install manifest and
+    /// rewrites only the components with missing or corrupted files. An
+    /// install without a manifest (older installers) gets a full reinstall.
+    fn repair(&mut self) {
+        let entries = match read_manifest(&self.install_path) {
+            Ok(entries) => entries,
+            Err(e) => {
+                self.log(format!("No usable install manifest ({}), falling back to a full reinstall", e));
+                self.state = InstallState::Config;
+                return;
+            }
+        };
+
+        let mut problems = Vec::new();
+        let mut broken = Vec::new();
+        for (rel, problem) in verify_manifest(&self.install_path, &entries) {
+            let component = Component::of(&rel);
+            problems.push(format!("{} ({}): {}", rel, component.map_or("unknown", Component::label), problem));
+            if let Some(c) = component.filter(|c| !broken.contains(c)) {
+                broken.push(c);
+            }
+        }
+
+        let mut errors = Vec::new();
+        for component in &broken {
+            self.log(format!("Repairing {}...", component.label()));
+            if let Err(e) = self.repair_component(*component) {
+                errors.push(format!("{}: {}", component.label(), e));
+            }
+        }
+        if !broken.is_empty() {
+            if let Err(e) = write_manifest(&self.install_path) {
+                errors.push(format!("Could not update the install manifest: {}", e));
+            }
+        }
+        self.state = InstallState::Repaired { problems, errors };
+    }
+
+    fn repair_component(&mut self, component: Component) -> std::io::Result<()> {
+        let bin_dir = self.install_path.join("bin");
+        match component {
+            Component::KiVM | Component::KiComp => {
+                let (name, bytes) = if component == Component::KiVM {
+                    (cli_filename(), CLI_BYTES)
+                } else {
+                    (kicomp_filename(), KICOMP_BYTES)
+                };
+                fs::create_dir_all(&bin_dir)?;
+                let path = bin_dir.join(name);
+                write_atomic(&path, bytes)?;
+                #[cfg(unix)]
+                set_executable(&path)?;
+            }
+            Component::Icon => {
+                fs::create_dir_all(self.install_path.join("assets"))?;
+                write_atomic(&self.install_path.join("assets").join("KiFile.png"), ICON_BYTES)?;
+            }
+            Component::Docs => {
+                self.install_documentation()?;
+                if !self.docs_installed {
+                    return Err(std::io::Error::other(self.docs_report.join(" ")));
+                }
+            }
+            Component::Installer => {
+                let target = bin_dir.join("installer.exe");
+                let current_exe = std::env::current_exe()?;
+                // Repairing from the installed copy itself: it is running, so it is intact
+                if fs::canonicalize(&target).ok() != fs::canonicalize(&current_exe).ok() {
+                    write_atomic(&target, &fs::read(&current_exe)?)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Downloads (or copies) the documentation into `<install>/docs`,
+    /// replacing whatever is there. What happened is left in `docs_report`
+    /// and `docs_installed` for the Done and Repair screens; failing to find
+    /// any documentation is not an install error.
+    fn install_documentation(&mut self) -> std::io::Result<()> {
+        let docs_dest = self.install_path.join("docs");
+        self.docs_report.clear();
+        self.docs_installed = false;
+
+        let build_no = option_env!("KINETIX_BUILD").unwrap_or("10"); // Defaults to 10 if not set via cargo build
+        let mirror = self.docs_mirror.trim().trim_end_matches('/').to_string();
+        let sources: Vec<String> = if self.offline {
+            self.docs_report.push("Offline mode: documentation download skipped.".to_string());
+            Vec::new()
+        } else if !mirror.is_empty() {
+            // A mirror serves the same archives as GitHub, named by build
+            vec![format!("{}/{}.zip", mirror, build_no)]
+        } else {
+            // GitHub automatically generates "Source code (zip)" for releases.
+            // The URL for this is /archive/refs/tags/{TAG}.zip, with the main
+            // branch archive as a fallback.
+            vec![
+                format!("https://github.com/MisterY3515/Kinetix-Documentation/archive/refs/tags/{}.zip", build_no),
+                "https://github.com/MisterY3515/Kinetix-Documentation/archive/refs/heads/main.zip".to_string(),
+            ]
+        };
+
+        for url in &sources {
+            self.log(format!("Downloading docs from {}...", url));
+            let _ = fs::remove_dir_all(&docs_dest); // Clear old docs
+            fs::create_dir_all(&docs_dest)?;
+            match download_docs_archive(url, &docs_dest) {
+                Ok(verified) => {
+                    let how = if verified { "SHA-256 verified" } else { "no checksum published, not verified" };
+                    self.log(format!("Successfully downloaded and extracted docs from {} ({}).", url, how));
+                    self.docs_report.push(format!("Downloaded from {} ({}).", url, how));
+                    self.docs_installed = true;
+                    return Ok(());
+                }
+                Err(e) => {
+                    self.log(format!("Failed to download {}: {}", url, e));
+                    self.docs_report.push(format!("{}: {}", url, e));
+                }
+            }
+        }
+
+        // Fall back to a documentation folder shipped next to the installer
+        let _ = fs::remove_dir_all(&docs_dest);
+        fs::create_dir_all(&docs_dest)?;
+        self.log("Falling back to local documentation...");
+        let exe_parent = std::env::current_exe()
+            .ok()
+            .and_then(|p| p.parent().map(|p| p.to_path_buf()));
+        let local = exe_parent.iter()
+            .flat_map(|dir| [Some(dir.join("docs")), dir.parent().map(|p| p.join("Documentation"))])
+            .flatten()
+            .find(|p| p.exists());
+        match local {
+            Some(src) => {
+                self.log(format!("Found local docs at {:?}", src));
+                copy_dir_recursive(&src, &docs_dest)?;
+                self.docs_report.push(format!("Copied from the local folder {}.", src.display()));
+                self.docs_installed = true;
+            }
+            None => {
+                self.log("Warning: Local documentation folder not found.");
+                self.docs_report.push("No local documentation folder found next to the installer.".to_string());
+            }
+        }
+        Ok(())
+    }
 }
 
 impl eframe::App for InstallerApp {
@@ -610,6 +673,10 @@ impl eframe::App for InstallerApp {
                         self.draw_failed(ui, &msg);
                     }
                     InstallState::Repair => self.draw_repair(ui),
+                    InstallState::Repaired { problems, errors } => {
+                        let (problems, errors) = (problems.clone(), errors.clone());
+                        self.draw_repaired(ui, &problems, &errors);
+                    }
                     InstallState::Uninstall => self.draw_uninstall(ui),
                 }
 
@@ -734,6 +801,20 @@ impl InstallerApp {
                 ui.checkbox(&mut self.install_kivm, egui::RichText::new("KiVM — Interpreter, CLI & Shell").size(14.0));
                 ui.checkbox(&mut self.install_kicomp, egui::RichText::new("KiComp — Compiler & Build System").size(14.0));
                 ui.checkbox(&mut self.install_docs, egui::RichText::new("Documentation (offline, opens with kivm docs)").size(14.0));
+                if self.install_docs {
+                    ui.indent("docs_source", |ui| {
+                        ui.checkbox(&mut self.offline, egui::RichText::new("Offline — only use documentation shipped with the installer").size(12.0));
+                        ui.horizontal(|ui| {
+                            ui.label(egui::RichText::new("Mirror").size(12.0).color(TEXT_SECONDARY));
+                            ui.add_enabled(
+                                !self.offline,
+                                egui::TextEdit::singleline(&mut self.docs_mirror)
+                                    .hint_text("GitHub (default)")
+                                    .desired_width(280.0),
+                            );
+                        });
+                    });
+                }
             });
 
         // Integration card
@@ -877,6 +958,19 @@ impl InstallerApp {
                     .monospace()
             );
             
+            if self.install_docs {
+                ui.add_space(12.0);
+                let (headline, color) = if self.docs_installed {
+                    ("Documentation installed", SUCCESS)
+                } else {
+                    ("Documentation not installed", ERROR_COLOR)
+                };
+                ui.label(egui::RichText::new(headline).size(13.0).color(color));
+                for note in &self.docs_report {
+                    ui.label(egui::RichText::new(note).size(11.0).color(TEXT_DIM));
+                }
+            }
+
             ui.add_space(20.0);
             ui.checkbox(&mut self.launch_shell_after, egui::RichText::new("Launch Kinetix Shell now").size(14.0).color(TEXT_PRIMARY));
             
@@ -980,7 +1074,7 @@ impl InstallerApp {
             ui.add_space(32.0);
 
             let reinstall_btn = egui::Button::new(
-                egui::RichText::new("⟳  Repair / Reinstall")
+                egui::RichText::new("⟳  Repair")
                     .size(15.0)
                     .color(TEXT_PRIMARY)
             )
@@ -989,7 +1083,7 @@ impl InstallerApp {
             .min_size(egui::vec2(220.0, 42.0));
 
             if ui.add(reinstall_btn).clicked() {
-                self.state = InstallState::Config;
+                self.repair();
             }
 
             ui.add_space(16.0);
@@ -1010,38 +1104,99 @@ impl InstallerApp {
         });
     }
 
-    fn draw_uninstall(&mut self, ui: &mut egui::Ui) {
+    fn draw_repaired(&mut self, ui: &mut egui::Ui, problems: &[String], errors: &[String]) {
         ui.add_space(40.0);
         ui.vertical_centered(|ui| {
             ui.label(
-                egui::RichText::new("Uninstalling Kinetix")
+                egui::RichText::new("Repair Kinetix")
                     .size(22.0)
                     .strong()
                     .color(TEXT_PRIMARY)
             );
-            
+
             ui.add_space(20.0);
 
-            // Here we run the actual uninstall logic if we just entered this state
-            // For simplicity, we trigger it inline
-            let mut errors = vec![];
-            
-            let path_exists = self.install_path.exists();
-            if path_exists {
-                if let Err(e) = std::fs::remove_dir_all(&self.install_path) {
-                    errors.push(format!("Could not delete install directory: {}", e));
+            if problems.is_empty() {
+                ui.label(
+                    egui::RichText::new("All installed files match the install manifest. Nothing to repair.")
+                        .size(14.0)
+                        .color(SUCCESS)
+                );
+            } else {
+                ui.label(
+                    egui::RichText::new(format!("{} file(s) were missing or corrupted:", problems.len()))
+                        .size(14.0)
+                        .color(TEXT_SECONDARY)
+                );
+                egui::ScrollArea::vertical().max_height(160.0).show(ui, |ui| {
+                    for problem in problems {
+                        ui.label(egui::RichText::new(problem).size(11.0).color(TEXT_DIM).monospace());
+                    }
+                });
+                ui.add_space(12.0);
+                if errors.is_empty() {
+                    ui.label(
+                        egui::RichText::new("The affected components have been reinstalled.")
+                            .size(14.0)
+                            .color(SUCCESS)
+                    );
+                } else {
+                    ui.label(
+                        egui::RichText::new("Repair encountered some errors.")
+                            .size(14.0)
+                            .color(ERROR_COLOR)
+                    );
+                    for err in errors {
+                        ui.label(egui::RichText::new(err).size(11.0).color(TEXT_DIM));
+                    }
                 }
             }
 
-            #[cfg(target_os = "windows")]
-            {
-                // Unregister Add/Remove Programs
-                let hkcu = winreg::RegKey::predef(winreg::enums::HKEY_CURRENT_USER);
-                let _ = hkcu.delete_subkey(r"Software\Microsoft\Windows\CurrentVersion\Uninstall\Kinetix");
-                
-                // We'll leave PATH cleanup for manual to avoid dangerous string replace for now
-                // Also leave Terminal profile (it's in LocalAppData)
+            ui.add_space(32.0);
+
+            let finish_btn = egui::Button::new(
+                egui::RichText::new("Close")
+                    .size(15.0)
+                    .color(TEXT_PRIMARY)
+                    .strong()
+            )
+            .fill(ACCENT)
+            .rounding(6.0)
+            .min_size(egui::vec2(160.0, 42.0));
+
+            if ui.add(finish_btn).clicked() {
+                std::process::exit(0);
             }
+        });
+    }
+
+    fn draw_uninstall(&mut self, ui: &mut egui::Ui) {
+        ui.add_space(40.0);
+        ui.vertical_centered(|ui| {
+            ui.label(
+                egui::RichText::new("Uninstalling Kinetix")
+                    .size(22.0)
+                    .strong()
+                    .color(TEXT_PRIMARY)
+            );
+            
+            ui.add_space(20.0);
+
+            // Run the actual uninstall the first time this state is drawn
+            let errors = self.uninstall_errors.get_or_insert_with(|| {
+                let errors = uninstall_files(&self.install_path);
+
+                #[cfg(target_os = "windows")]
+                {
+                    // Unregister Add/Remove Programs
+                    let hkcu = winreg::RegKey::predef(winreg::enums::HKEY_CURRENT_USER);
+                    let _ = hkcu.delete_subkey(r"Software\Microsoft\Windows\CurrentVersion\Uninstall\Kinetix");
+
+                    // We'll leave PATH cleanup for manual to avoid dangerous string replace for now
+                    // Also leave Terminal profile (it's in LocalAppData)
+                }
+                errors
+            }).clone();
 
             if errors.is_empty() {
                 ui.label(
@@ -1091,6 +1246,56 @@ fn default_install_path() -> PathBuf {
     }
 }
 
+/// Downloads a documentation source archive (with its root folder, as
+/// GitHub's source archives have) and extracts it into `docs_dest`. The
+/// archive is checked against `KINETIX_DOCS_SHA256` when set, otherwise
+/// against a `<url>.sha256` file published next to it; a mismatch refuses
+/// the archive. Returns whether a checksum was available to verify.
+fn download_docs_archive(url: &str, docs_dest: &Path) -> Result<bool, String> {
+    let fetch = |url: &str| -> Result<Vec<u8>, String> {
+        let response = ureq::get(url).call().map_err(|e| e.to_string())?;
+        let mut bytes = Vec::new();
+        std::io::copy(&mut response.into_reader(), &mut bytes).map_err(|e| e.to_string())?;
+        Ok(bytes)
+    };
+
+    let zip_bytes = fetch(url)?;
+    let expected = std::env::var("KINETIX_DOCS_SHA256").ok()
+        .or_else(|| fetch(&format!("{}.sha256", url)).ok()
+            .and_then(|sum| String::from_utf8_lossy(&sum).split_whitespace().next().map(str::to_string)))
+        .map(|sum| sum.to_ascii_lowercase());
+    if let Some(expected) = &expected {
+        let actual = sha256_hex(&zip_bytes);
+        if *expected != actual {
+            return Err(format!("checksum mismatch (expected {}, got {})", expected, actual));
+        }
+    }
+
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(zip_bytes)).map_err(|e| e.to_string())?;
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i).map_err(|e| e.to_string())?;
+        let outpath = match file.enclosed_name() {
+            Some(path) => path.to_owned(),
+            None => continue,
+        };
+        // Strip the archive's root folder
+        let final_path: PathBuf = outpath.components().skip(1).collect();
+        if final_path.as_os_str().is_empty() { continue; }
+
+        let dest_path = docs_dest.join(final_path);
+        if file.name().ends_with('/') {
+            fs::create_dir_all(&dest_path).map_err(|e| e.to_string())?;
+        } else {
+            if let Some(p) = dest_path.parent() {
+                fs::create_dir_all(p).map_err(|e| e.to_string())?;
+            }
+            let mut outfile = fs::File::create(&dest_path).map_err(|e| e.to_string())?;
+            std::io::copy(&mut file, &mut outfile).map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(expected.is_some())
+}
+
 fn copy_dir_recursive(src: &Path, dst: &Path) -> std::io::Result<()> {
     fs::create_dir_all(dst)?;
     for entry in fs::read_dir(src)? {
@@ -1108,6 +1313,160 @@ fn copy_dir_recursive(src: &Path, dst: &Path) -> std::io::Result<()> {
     Ok(())
 }
 
+// ─── Install Manifest ──────────────────────────────────────────────────────
+
+/// Written to the install root when an install finishes: one
+/// `<sha256>  <path>` line per installed file, paths relative to the root
+/// with `/` separators (the format `sha256sum -c` reads). Repair verifies
+/// against it and uninstall removes exactly the files it lists.
+const MANIFEST_NAME: &str = "manifest.sha256";
+
+/// The install directories whose files the manifest records.
+const MANIFEST_DIRS: &[&str] = &["bin", "assets", "docs"];
+
+/// The unit repair reinstalls when any of its files is broken.
+#[derive(Clone, Copy, PartialEq)]
+enum Component {
+    KiVM,
+    KiComp,
+    Icon,
+    Docs,
+    Installer,
+}
+
+impl Component {
+    fn of(rel: &str) -> Option<Component> {
+        let (dir, name) = rel.split_once('/')?;
+        match dir {
+            "bin" if name == cli_filename() => Some(Component::KiVM),
+            "bin" if name == kicomp_filename() => Some(Component::KiComp),
+            "bin" if name == "installer.exe" => Some(Component::Installer),
+            "assets" => Some(Component::Icon),
+            "docs" => Some(Component::Docs),
+            _ => None,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Component::KiVM => "KiVM",
+            Component::KiComp => "KiComp",
+            Component::Icon => "icon",
+            Component::Docs => "documentation",
+            Component::Installer => "installer",
+        }
+    }
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn write_manifest(install_path: &Path) -> std::io::Result<()> {
+    fn walk(root: &Path, rel: &str, out: &mut String) -> std::io::Result<()> {
+        let mut entries: Vec<_> = fs::read_dir(root.join(rel))?.collect::<Result<_, _>>()?;
+        entries.sort_by_key(|e| e.file_name());
+        for entry in entries {
+            let child = format!("{}/{}", rel, entry.file_name().to_string_lossy());
+            if entry.file_type()?.is_dir() {
+                walk(root, &child, out)?;
+            } else {
+                out.push_str(&format!("{}  {}\n", sha256_hex(&fs::read(entry.path())?), child));
+            }
+        }
+        Ok(())
+    }
+
+    let mut manifest = String::new();
+    for dir in MANIFEST_DIRS {
+        if install_path.join(dir).is_dir() {
+            walk(install_path, dir, &mut manifest)?;
+        }
+    }
+    write_atomic(&install_path.join(MANIFEST_NAME), manifest.as_bytes())
+}
+
+/// `(hash, relative path)` pairs. Paths that could point outside the
+/// install directories are refused, since uninstall deletes what is listed.
+fn read_manifest(install_path: &Path) -> std::io::Result<Vec<(String, String)>> {
+    let invalid = |msg: String| std::io::Error::new(std::io::ErrorKind::InvalidData, msg);
+    let text = fs::read_to_string(install_path.join(MANIFEST_NAME))?;
+    let mut entries = Vec::new();
+    for (i, line) in text.lines().enumerate().filter(|(_, l)| !l.is_empty()) {
+        let (hash, rel) = line.split_once("  ")
+            .ok_or_else(|| invalid(format!("{} line {}: expected `<sha256>  <path>`", MANIFEST_NAME, i + 1)))?;
+        let safe = rel.split('/').all(|part| !part.is_empty() && part != "." && part != ".." && !part.contains('\\'))
+            && MANIFEST_DIRS.iter().any(|dir| rel.starts_with(&format!("{}/", dir)));
+        if !safe {
+            return Err(invalid(format!("{} line {}: refusing path {:?}", MANIFEST_NAME, i + 1, rel)));
+        }
+        entries.push((hash.to_string(), rel.to_string()));
+    }
+    Ok(entries)
+}
+
+/// The manifest entries whose file is gone or no longer matches its hash.
+fn verify_manifest(install_path: &Path, entries: &[(String, String)]) -> Vec<(String, &'static str)> {
+    entries.iter().filter_map(|(hash, rel)| match fs::read(install_path.join(rel)) {
+        Ok(bytes) if sha256_hex(&bytes) == *hash => None,
+        Ok(_) => Some((rel.clone(), "corrupted")),
+        Err(_) => Some((rel.clone(), "missing")),
+    }).collect()
+}
+
+/// Removes the files the manifest lists, then any directories that left
+/// empty. Files the user added to the install directory stay. Installs from
+/// before the manifest existed are removed wholesale, as they used to be.
+fn uninstall_files(install_path: &Path) -> Vec<String> {
+    let mut errors = Vec::new();
+    if !install_path.exists() {
+        return errors;
+    }
+    let entries = match read_manifest(install_path) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            if let Err(e) = fs::remove_dir_all(install_path) {
+                errors.push(format!("Could not delete install directory: {}", e));
+            }
+            return errors;
+        }
+        Err(e) => {
+            errors.push(format!("Could not read the install manifest: {}", e));
+            return errors;
+        }
+    };
+
+    for (_, rel) in &entries {
+        match fs::remove_file(install_path.join(rel)) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => errors.push(format!("Could not delete {}: {}", rel, e)),
+        }
+    }
+    if errors.is_empty() {
+        let _ = fs::remove_file(install_path.join(MANIFEST_NAME));
+    }
+
+    fn remove_empty_dirs(dir: &Path) {
+        if let Ok(entries) = fs::read_dir(dir) {
+            for entry in entries.flatten() {
+                if entry.file_type().is_ok_and(|t| t.is_dir()) {
+                    remove_empty_dirs(&entry.path());
+                }
+            }
+        }
+        let _ = fs::remove_dir(dir); // Fails (and is kept) when not empty
+    }
+    for dir in MANIFEST_DIRS {
+        remove_empty_dirs(&install_path.join(dir));
+    }
+    let _ = fs::remove_dir(install_path);
+    if errors.is_empty() && install_path.exists() {
+        errors.push(format!("Kept {:?}: it contains files Kinetix did not install.", install_path));
+    }
+    errors
+}
+
 #[cfg(unix)]
 fn set_executable(path: &Path) -> std::io::Result<()> {
     use std::os::unix::fs::PermissionsExt;
@@ -31,7 +31,7 @@ fn benchmark_10k_struct_instantiations() {
     let mut compiler = Compiler::new();
     match compiler.compile(&ast.statements, None) {
         Ok(_) => {},
-        Err(e) => panic!("Compilation failed: {}", e),
+        Err(e) => panic!("Compilation failed: {:?}", e),
     }
 
     let duration = start.elapsed();
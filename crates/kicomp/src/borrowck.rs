@@ -1,4 +1,7 @@
-use crate::mir::{MirProgram, MirFunction, BasicBlockData, StatementKind, TerminatorKind, RValue, Operand};
+use crate::mir::{MirProgram, MirFunction, BasicBlockData, StatementKind, TerminatorKind, RValue, Operand, Mutability};
+use kinetix_language::diagnostic::{codes, Diagnostic};
+use kinetix_language::span::Span;
+use rayon::prelude::*;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum LocalState {
@@ -27,14 +30,20 @@ impl BorrowChecker {
         Self
     }
 
-    pub fn check_program(&mut self, program: &MirProgram) -> Result<(), Vec<String>> {
-        let mut errors = Vec::new();
-
-        for func in &program.functions {
-            self.check_function(func, &mut errors);
-        }
-
-        self.check_function(&program.main_block, &mut errors);
+    /// Functions are checked in parallel; their errors are concatenated in
+    /// program order (functions, then the main block), as a sequential walk
+    /// would report them.
+    pub fn check_program(&mut self, program: &MirProgram) -> Result<(), Vec<Diagnostic>> {
+        let per_function: Vec<Vec<Diagnostic>> = program.functions
+            .par_iter()
+            .chain(rayon::iter::once(&program.main_block))
+            .map(|func| {
+                let mut errors = Vec::new();
+                BorrowChecker::new().check_function(func, &mut errors);
+                errors
+            })
+            .collect();
+        let errors: Vec<Diagnostic> = per_function.into_iter().flatten().collect();
 
         if errors.is_empty() {
             Ok(())
@@ -113,7 +122,7 @@ impl BorrowChecker {
         }
     }
 
-    fn check_function(&mut self, func: &MirFunction, errors: &mut Vec<String>) {
+    fn check_function(&mut self, func: &MirFunction, errors: &mut Vec<Diagnostic>) {
         if func.basic_blocks.is_empty() { return; }
 
         let n = func.basic_blocks.len();
@@ -160,11 +169,11 @@ impl BorrowChecker {
             for stmt in &block.statements {
                 match &stmt.kind {
                     StatementKind::Assign(place, rvalue) => {
-                        self.check_rvalue(rvalue, stmt.line, &func.locals, &mut states, errors);
+                        self.check_rvalue(rvalue, stmt.span, &func.locals, &mut states, errors);
                         states[place.local.0] = LocalState::Initialized;
                     }
                     StatementKind::Expression(rvalue) => {
-                        self.check_rvalue(rvalue, stmt.line, &func.locals, &mut states, errors);
+                        self.check_rvalue(rvalue, stmt.span, &func.locals, &mut states, errors);
                     }
                     StatementKind::Drop(_place) => {
                         // Drop allows a Moved value (it's a no-op at runtime),
@@ -175,52 +184,58 @@ impl BorrowChecker {
         }
     }
 
-    fn check_rvalue(&self, rvalue: &RValue, line: usize, locals: &[crate::mir::LocalDecl], states: &mut [LocalState], errors: &mut Vec<String>) {
+    fn check_rvalue(&self, rvalue: &RValue, span: Span, locals: &[crate::mir::LocalDecl], states: &mut [LocalState], errors: &mut Vec<Diagnostic>) {
         match rvalue {
-            RValue::Use(op) => self.check_operand(op, line, locals, states, errors),
-            RValue::UnaryOp(_, op) => self.check_operand(op, line, locals, states, errors),
+            RValue::Use(op) => self.check_operand(op, span, locals, states, errors),
+            RValue::UnaryOp(_, op) => self.check_operand(op, span, locals, states, errors),
             RValue::BinaryOp(_, lhs, rhs) => {
-                self.check_operand(lhs, line, locals, states, errors);
-                self.check_operand(rhs, line, locals, states, errors);
+                self.check_operand(lhs, span, locals, states, errors);
+                self.check_operand(rhs, span, locals, states, errors);
             }
             RValue::Call(func_op, args) => {
-                self.check_operand(func_op, line, locals, states, errors);
+                self.check_operand(func_op, span, locals, states, errors);
                 for arg in args {
-                    self.check_operand(arg, line, locals, states, errors);
+                    self.check_operand(arg, span, locals, states, errors);
                 }
             }
             RValue::Array(elems) => {
                 for elem in elems {
-                    self.check_operand(elem, line, locals, states, errors);
+                    self.check_operand(elem, span, locals, states, errors);
                 }
             }
             RValue::Aggregate(_, ops) => {
                 for op in ops {
-                    self.check_operand(op, line, locals, states, errors);
+                    self.check_operand(op, span, locals, states, errors);
                 }
             }
         }
     }
 
-    fn check_operand(&self, op: &Operand, line: usize, locals: &[crate::mir::LocalDecl], states: &mut [LocalState], errors: &mut Vec<String>) {
+    fn check_operand(&self, op: &Operand, span: Span, locals: &[crate::mir::LocalDecl], states: &mut [LocalState], errors: &mut Vec<Diagnostic>) {
         match op {
             Operand::Copy(p) => {
                 if states[p.local.0] != LocalState::Initialized {
                     let name = locals[p.local.0].name.as_deref().unwrap_or("unknown");
-                    errors.push(format!("Line {}: Cannot copy from an uninitialized or moved variable '{}'", line, name));
+                    errors.push(Diagnostic::error(codes::BORROW, format!("Cannot copy from an uninitialized or moved variable '{}'", name)).at(span));
                 }
             }
             Operand::Move(p) => {
                 if states[p.local.0] != LocalState::Initialized {
                     let name = locals[p.local.0].name.as_deref().unwrap_or("unknown");
-                    errors.push(format!("Line {}: Use of uninitialized or moved variable '{}'", line, name));
+                    errors.push(Diagnostic::error(codes::BORROW, format!("Use of uninitialized or moved variable '{}'", name)).at(span));
                 }
                 states[p.local.0] = LocalState::Moved;
             }
-            Operand::Borrow(p, _) => {
+            Operand::Borrow(p, mutability) => {
+                let local = &locals[p.local.0];
                 if states[p.local.0] != LocalState::Initialized {
-                    let name = locals[p.local.0].name.as_deref().unwrap_or("unknown");
-                    errors.push(format!("Line {}: Borrow of uninitialized or moved variable '{}'", line, name));
+                    let name = local.name.as_deref().unwrap_or("unknown");
+                    errors.push(Diagnostic::error(codes::BORROW, format!("Borrow of uninitialized or moved variable '{}'", name)).at(span));
+                }
+                // Unnamed temporaries are never reassigned, so only named
+                // bindings must be `mut` to be borrowed mutably.
+                if let (Mutability::Mut, Mutability::Not, Some(name)) = (mutability, local.mutability, &local.name) {
+                    errors.push(Diagnostic::error(codes::BORROW, format!("Cannot borrow immutable variable '{}' as mutable (declare it with `mut {}`)", name, name)).at(span));
                 }
             }
             Operand::Constant(_) => {}
@@ -228,7 +243,7 @@ impl BorrowChecker {
     }
 }
 
-pub fn check_mir(program: &MirProgram) -> Result<(), Vec<String>> {
+pub fn check_mir(program: &MirProgram) -> Result<(), Vec<Diagnostic>> {
     let mut borrowck = BorrowChecker::new();
     borrowck.check_program(program)
 }
@@ -271,7 +286,7 @@ mod tests {
         let result = check_mir(&mir);
         assert!(result.is_err(), "Borrow Checker failed to catch use-after-move");
         let errs = result.unwrap_err();
-        assert!(errs[0].contains("Use of uninitialized or moved variable"));
+        assert!(errs[0].message.contains("Use of uninitialized or moved variable"));
     }
 
     // Build 38 Phase B1: real join-point merging. `s` is moved on the `if` branch
@@ -296,4 +311,11 @@ mod tests {
         );
         assert!(check_mir(&mir).is_ok());
     }
+
+    #[test]
+    fn test_borrowck_rejects_mutable_borrow_of_immutable_binding() {
+        let errs = check_mir(&compile_to_mir("let x = 42\nlet y = &mut x")).unwrap_err();
+        assert!(errs[0].message.contains("Cannot borrow immutable variable 'x' as mutable"), "{:?}", errs);
+        assert!(check_mir(&compile_to_mir("mut x = 42\nlet y = &mut x\nlet z = &x")).is_ok());
+    }
 }
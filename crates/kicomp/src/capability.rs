@@ -10,6 +10,8 @@
 
 use crate::hir::*;
 use crate::types::Type;
+use kinetix_language::diagnostic::{codes, Diagnostic};
+use kinetix_language::span::Span;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Capability {
@@ -34,14 +36,55 @@ impl std::fmt::Display for Capability {
     }
 }
 
+impl Capability {
+    /// Every capability.
+    pub const ALL: [Capability; 6] = [
+        Capability::FsRead,
+        Capability::FsWrite,
+        Capability::NetAccess,
+        Capability::SysInfo,
+        Capability::OsExecute,
+        Capability::ThreadControl,
+    ];
+
+    /// The capability named on the command line (`kivm exec --allow`):
+    /// `fs-read`, `fs-write`, `net`, `sysinfo`, `exec` or `threads`.
+    pub fn from_flag(name: &str) -> Option<Capability> {
+        match name {
+            "fs-read" => Some(Capability::FsRead),
+            "fs-write" => Some(Capability::FsWrite),
+            "net" => Some(Capability::NetAccess),
+            "sysinfo" => Some(Capability::SysInfo),
+            "exec" => Some(Capability::OsExecute),
+            "threads" => Some(Capability::ThreadControl),
+            _ => None,
+        }
+    }
+
+    /// What the capability gives a program, as a permission prompt puts it.
+    pub fn description(&self) -> &'static str {
+        match self {
+            Capability::FsRead => "File read access",
+            Capability::FsWrite => "File write access",
+            Capability::NetAccess => "Network access",
+            Capability::SysInfo => "System information access",
+            Capability::OsExecute => "Permission to run programs",
+            Capability::ThreadControl => "Thread control",
+        }
+    }
+}
+
 pub struct CapabilityError {
     pub message: String,
-    pub line: usize,
+    /// The statement that needs it.
+    pub span: Span,
+    /// The capability that was missing.
+    pub capability: Capability,
 }
 
-impl std::fmt::Display for CapabilityError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Line {}: Capability Violation - {}", self.line, self.message)
+impl CapabilityError {
+    pub fn diagnostic(&self) -> Diagnostic {
+        Diagnostic::error(codes::CAPABILITY, format!("Capability Violation - {}", self.message)).at(self.span)
     }
 }
 
@@ -67,12 +110,12 @@ impl CapabilityValidator {
 
     fn validate_stmt(&self, stmt: &HirStatement, errors: &mut Vec<CapabilityError>) {
         match &stmt.kind {
-            HirStmtKind::Let { value, .. } => self.validate_expr(value, stmt.line, errors),
-            HirStmtKind::State { value, .. } => self.validate_expr(value, stmt.line, errors),
-            HirStmtKind::Computed { value, .. } => self.validate_expr(value, stmt.line, errors),
+            HirStmtKind::Let { value, .. } => self.validate_expr(value, stmt.location(), errors),
+            HirStmtKind::State { value, .. } => self.validate_expr(value, stmt.location(), errors),
+            HirStmtKind::Computed { value, .. } => self.validate_expr(value, stmt.location(), errors),
             HirStmtKind::Effect { body, .. } => self.validate_stmt(body, errors),
-            HirStmtKind::Return { value: Some(v) } => self.validate_expr(v, stmt.line, errors),
-            HirStmtKind::Expression { expression } => self.validate_expr(expression, stmt.line, errors),
+            HirStmtKind::Return { value: Some(v) } => self.validate_expr(v, stmt.location(), errors),
+            HirStmtKind::Expression { expression } => self.validate_expr(expression, stmt.location(), errors),
             HirStmtKind::Block { statements } => {
                 for s in statements { self.validate_stmt(s, errors); }
             }
@@ -80,84 +123,84 @@ impl CapabilityValidator {
             HirStmtKind::Class { methods, .. } => {
                 for m in methods { self.validate_stmt(m, errors); }
             }
-            HirStmtKind::While { condition, body } => {
-                self.validate_expr(condition, stmt.line, errors);
+            HirStmtKind::While { condition, body, .. } => {
+                self.validate_expr(condition, stmt.location(), errors);
                 self.validate_stmt(body, errors);
             }
             HirStmtKind::For { range, body, .. } => {
-                self.validate_expr(range, stmt.line, errors);
+                self.validate_expr(range, stmt.location(), errors);
                 self.validate_stmt(body, errors);
             }
             _ => {}
         }
     }
 
-    fn validate_expr(&self, expr: &HirExpression, line: usize, errors: &mut Vec<CapabilityError>) {
+    fn validate_expr(&self, expr: &HirExpression, span: Span, errors: &mut Vec<CapabilityError>) {
         match &expr.kind {
             HirExprKind::MethodCall { object, method, arguments } => {
                 // Check if this is a capability-protected builtin invocation
                 if let Type::Custom { name, .. } = &object.ty {
-                    self.check_builtin_capability(name.as_str(), method.as_str(), line, errors);
+                    self.check_builtin_capability(name.as_str(), method.as_str(), span, errors);
                 }
                 
-                self.validate_expr(object, line, errors);
+                self.validate_expr(object, span, errors);
                 for arg in arguments {
-                    self.validate_expr(arg, line, errors);
+                    self.validate_expr(arg, span, errors);
                 }
             }
             HirExprKind::Call { function, arguments } => {
                 // Build 24: intercept flattened system.* global calls
                 if let HirExprKind::Identifier(ref name) = function.kind {
-                    self.check_flattened_call(name, line, errors);
+                    self.check_flattened_call(name, span, errors);
                 }
-                self.validate_expr(function, line, errors);
-                for arg in arguments { self.validate_expr(arg, line, errors); }
+                self.validate_expr(function, span, errors);
+                for arg in arguments { self.validate_expr(arg, span, errors); }
             }
             HirExprKind::Infix { left, right, .. } => {
-                self.validate_expr(left, line, errors);
-                self.validate_expr(right, line, errors);
+                self.validate_expr(left, span, errors);
+                self.validate_expr(right, span, errors);
             }
-            HirExprKind::Prefix { right, .. } => self.validate_expr(right, line, errors),
+            HirExprKind::Prefix { right, .. } => self.validate_expr(right, span, errors),
             HirExprKind::If { condition, consequence, alternative } => {
-                self.validate_expr(condition, line, errors);
+                self.validate_expr(condition, span, errors);
                 self.validate_stmt(consequence, errors);
                 if let Some(alt) = alternative { self.validate_stmt(alt, errors); }
             }
             HirExprKind::Assign { target, value } => {
-                self.validate_expr(target, line, errors);
-                self.validate_expr(value, line, errors);
+                self.validate_expr(target, span, errors);
+                self.validate_expr(value, span, errors);
             }
             HirExprKind::Index { left, index } => {
-                self.validate_expr(left, line, errors);
-                self.validate_expr(index, line, errors);
+                self.validate_expr(left, span, errors);
+                self.validate_expr(index, span, errors);
             }
             HirExprKind::ArrayLiteral(elems) => {
-                for e in elems { self.validate_expr(e, line, errors); }
+                for e in elems { self.validate_expr(e, span, errors); }
             }
             HirExprKind::StructLiteral(_, fields) => {
-                for (_, f) in fields { self.validate_expr(f, line, errors); }
+                for (_, f) in fields { self.validate_expr(f, span, errors); }
             }
             HirExprKind::MapLiteral(entries) => {
                 for (k, v) in entries {
-                    self.validate_expr(k, line, errors);
-                    self.validate_expr(v, line, errors);
+                    self.validate_expr(k, span, errors);
+                    self.validate_expr(v, span, errors);
                 }
             }
-            HirExprKind::MemberAccess { object, .. } => self.validate_expr(object, line, errors),
+            HirExprKind::MemberAccess { object, .. } => self.validate_expr(object, span, errors),
             HirExprKind::FunctionLiteral { body, .. } => self.validate_stmt(body, errors),
             HirExprKind::Range { start, end } => {
-                self.validate_expr(start, line, errors);
-                self.validate_expr(end, line, errors);
+                self.validate_expr(start, span, errors);
+                self.validate_expr(end, span, errors);
             }
-            HirExprKind::Match { value, arms } => {
-                self.validate_expr(value, line, errors);
+            HirExprKind::Match { value, arms, .. } => {
+                self.validate_expr(value, span, errors);
                 for (_, body) in arms { self.validate_stmt(body, errors); }
             }
             _ => {}
         }
     }
 
-    fn check_builtin_capability(&self, module: &str, method: &str, line: usize, errors: &mut Vec<CapabilityError>) {
+    fn check_builtin_capability(&self, module: &str, method: &str, span: Span, errors: &mut Vec<CapabilityError>) {
         let req = match (module, method) {
             // Data IO (New file sub-namespace)
             ("data", "file.read" | "file.exists" | "file.copy") => Some(Capability::FsRead),
@@ -170,6 +213,7 @@ impl CapabilityValidator {
             ("data", "write_text") => Some(Capability::FsWrite),
             // OS / System
             ("system" | "os", "cpu_usage" | "memory_free" | "memory_total" | "os_name" | "os_version" | "hostname" | "user_name" | "uptime") => Some(Capability::SysInfo),
+            ("crypto", "hash_file") => Some(Capability::FsRead),
             // DB
             ("db", "connect" | "query" | "execute") => Some(Capability::FsRead), // SQLite accesses FS
             ("db_conn", _) => Some(Capability::FsRead),
@@ -183,7 +227,8 @@ impl CapabilityValidator {
             if !self.granted.contains(&cap) {
                 errors.push(CapabilityError {
                     message: format!("Sandbox missing '{}' capability for {}.{}", cap, module, method),
-                    line,
+                    span,
+                    capability: cap,
                 });
             }
         }
@@ -191,11 +236,12 @@ impl CapabilityValidator {
 
     /// Build 24: Check flattened multi-level global function calls (e.g. "system.os.name").
     /// The HIR flattens `system.os.name()` into `Call(Identifier("system.os.name"), args)`.
-    /// Build 26: Extended audit — covers all BUILTIN_NAMES syscall surface.
-    fn check_flattened_call(&self, name: &str, line: usize, errors: &mut Vec<CapabilityError>) {
+    /// Build 26: Extended audit — covers all kivm BUILTINS syscall surface.
+    fn check_flattened_call(&self, name: &str, span: Span, errors: &mut Vec<CapabilityError>) {
         let req = match name {
             // OS info queries
-            "system.os.name" | "system.os.arch" | "system.os.isWindows" | "system.os.isLinux" | "system.os.isMac" => Some(Capability::SysInfo),
+            "system.os.name" | "system.os.arch" | "system.os.isWindows" | "system.os.isLinux" | "system.os.isMac"
+            | "system.os.memory" | "system.os.disks" | "system.os.cpu" | "system.os.uptime" | "system.os.battery" => Some(Capability::SysInfo),
             // OS execution
             "system.exec" => Some(Capability::OsExecute),
             // Thread control
@@ -204,6 +250,13 @@ impl CapabilityValidator {
             "system.defer" => Some(Capability::ThreadControl),
             // Environment access (Build 26 audit)
             "env.get" | "env.set" | "env.args" => Some(Capability::SysInfo),
+            // Streaming CSV files
+            "csv.open" => Some(Capability::FsRead),
+            "csv.create" | "csv.write" => Some(Capability::FsWrite),
+            "i18n.load" | "crypto.hash_file" | "fs.watch" | "fs.glob" | "fs.walk" | "archive.zip.list" | "archive.tar.list" => Some(Capability::FsRead),
+            "fs.write_atomic" | "archive.zip.create" | "archive.zip.extract" | "archive.tar.create" | "archive.tar.extract" => Some(Capability::FsWrite),
+            "ipc.spawn" => Some(Capability::OsExecute),
+            "ipc.connect" | "ipc.accept" => Some(Capability::NetAccess),
             // Time/System info (Build 26 audit)
             "System.time" | "time.now" | "time.ticks" | "time.sleep" => Some(Capability::SysInfo),
             // Net TCP/UDP/HTTP/Utils (Build 28-30)
@@ -215,7 +268,8 @@ impl CapabilityValidator {
             if !self.granted.contains(&cap) {
                 errors.push(CapabilityError {
                     message: format!("Sandbox missing '{}' capability for {}", cap, name),
-                    line,
+                    span,
+                    capability: cap,
                 });
             }
         }
@@ -246,6 +300,21 @@ pub fn static_syscall_map() -> Vec<(&'static str, Capability)> {
         ("data.exists", Capability::FsRead),
         ("data.copy", Capability::FsRead),
         ("data.write_text", Capability::FsWrite),
+        ("csv.open", Capability::FsRead),
+        ("csv.create", Capability::FsWrite),
+        ("csv.write", Capability::FsWrite),
+        ("i18n.load", Capability::FsRead),
+        ("crypto.hash_file", Capability::FsRead),
+        ("fs.watch", Capability::FsRead),
+        ("fs.glob", Capability::FsRead),
+        ("fs.walk", Capability::FsRead),
+        ("fs.write_atomic", Capability::FsWrite),
+        ("archive.zip.create", Capability::FsWrite),
+        ("archive.zip.list", Capability::FsRead),
+        ("archive.zip.extract", Capability::FsWrite),
+        ("archive.tar.create", Capability::FsWrite),
+        ("archive.tar.list", Capability::FsRead),
+        ("archive.tar.extract", Capability::FsWrite),
         ("db.connect", Capability::FsRead),
         ("db.query", Capability::FsRead),
         ("db.execute", Capability::FsRead),
@@ -259,6 +328,11 @@ pub fn static_syscall_map() -> Vec<(&'static str, Capability)> {
         ("system.os.isWindows", Capability::SysInfo),
         ("system.os.isLinux", Capability::SysInfo),
         ("system.os.isMac", Capability::SysInfo),
+        ("system.os.memory", Capability::SysInfo),
+        ("system.os.disks", Capability::SysInfo),
+        ("system.os.cpu", Capability::SysInfo),
+        ("system.os.uptime", Capability::SysInfo),
+        ("system.os.battery", Capability::SysInfo),
         ("env.get", Capability::SysInfo),
         ("env.set", Capability::SysInfo),
         ("env.args", Capability::SysInfo),
@@ -268,6 +342,9 @@ pub fn static_syscall_map() -> Vec<(&'static str, Capability)> {
         ("time.sleep", Capability::SysInfo),
         // OS Execution
         ("system.exec", Capability::OsExecute),
+        ("ipc.spawn", Capability::OsExecute),
+        ("ipc.connect", Capability::NetAccess),
+        ("ipc.accept", Capability::NetAccess),
         // Thread / Defer
         ("system.thread.spawn", Capability::ThreadControl),
         ("system.thread.join", Capability::ThreadControl),
@@ -1,6 +1,8 @@
 /// KiComp Compiler: walks the AST and emits register-based bytecode.
 
-use kinetix_language::ast::{Statement, Expression};
+use kinetix_language::ast::{Statement, Expression, ExpressionKind};
+use kinetix_language::diagnostic::{codes, Diagnostic};
+use kinetix_language::span::Span;
 use crate::ir::*;
 use std::collections::HashMap;
 
@@ -31,7 +33,7 @@ struct LocalInfo {
 }
 
 /// Scope for tracking local variable slots.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct Scope {
     locals: HashMap<String, LocalInfo>,
     next_register: u16,
@@ -48,19 +50,23 @@ impl Scope {
     fn define(&mut self, name: &str) -> u16 {
         let reg = self.next_register;
         self.locals.insert(name.to_string(), LocalInfo { reg, moved: false });
-        self.next_register += 1;
+        self.next_register = self.next_register.saturating_add(1);
         reg
     }
 }
 
 /// Tracks the backpatch targets for `break`/`continue` inside one loop, so
 /// nested loops each patch their own jumps to their own exit/continue point.
+#[derive(Clone)]
 struct LoopContext {
     break_jumps: Vec<usize>,
     continue_jumps: Vec<usize>,
+    /// `'outer` in `'outer: while ...`; labeled jumps search the stack for it.
+    label: Option<String>,
 }
 
 /// The main compiler struct.
+#[derive(Clone)]
 pub struct Compiler {
     pub program: CompiledProgram,
     scopes: Vec<Scope>,
@@ -68,15 +74,39 @@ pub struct Compiler {
     max_temp: u16,
     /// Current source line number being compiled (for line_map).
     pub current_line: u32,
+    /// Column of the statement being compiled (for column_map), 0 if unknown.
+    pub current_column: u32,
     /// Names of no-payload enum variants (`Red` in `enum Color { Red, ... }`,
     /// or the built-in `None`), collected by a pre-scan in `compile()`. A bare
     /// identifier match-arm pattern is ambiguous at the AST level (`None` vs a
     /// catch-all binding `x`) -- this set disambiguates it, mirroring the fix
     /// applied to the same ambiguity in `hir.rs` (`SymbolTable::is_nullary_variant`).
     known_nullary_variants: std::collections::HashSet<String>,
+    /// Names of user-declared enums, so `Color.from_str(s)` compiles to a call
+    /// of the flattened `Color::from_str` global instead of a builtin module
+    /// lookup (`LoadConst "Color.from_str"`).
+    known_enums: std::collections::HashSet<String>,
+    /// Names of user-declared classes, so `Config.default()` and `Config.MAX`
+    /// read the flattened `Config::default`/`Config::MAX` globals.
+    known_classes: std::collections::HashSet<String>,
+    /// `import` aliases and the modules they name, so `m.square(3)` calls the
+    /// linked `util.math::square` global.
+    module_aliases: std::collections::HashMap<String, String>,
     /// Stack of enclosing loops, innermost last, so `break`/`continue` patch
     /// against the nearest loop only.
     loop_stack: Vec<LoopContext>,
+    /// Resources to close when the function binding them returns, matched by
+    /// `let` line and name (see `resource_lint::closes_on_return`).
+    closes: Vec<crate::resource_lint::ScopeClose>,
+    /// The function being compiled registers deferred calls, so a
+    /// `return f(..)` in it must not drop its frame first (`TailCall`).
+    defers: bool,
+    /// Top-level variables named like a builtin module (`let db =
+    /// db.connect(..)`), so `db.x` still reads the variable.
+    module_globals: std::collections::HashSet<String>,
+    /// Where the error being returned comes from: the innermost statement or
+    /// expression it passed through.
+    error_span: Option<Span>,
 }
 
 impl Compiler {
@@ -87,8 +117,90 @@ impl Compiler {
             next_temp: 0,
             max_temp: 0,
             current_line: 1,
+            current_column: 0,
             known_nullary_variants: std::collections::HashSet::new(),
+            known_enums: std::collections::HashSet::new(),
+            known_classes: std::collections::HashSet::new(),
+            module_aliases: std::collections::HashMap::new(),
             loop_stack: vec![],
+            closes: vec![],
+            defers: false,
+            module_globals: std::collections::HashSet::new(),
+            error_span: None,
+        }
+    }
+
+    /// Starts a new top-level chunk: the next `compile` gets a fresh `main`
+    /// and appends its functions after the ones compiled so far, so the
+    /// function indices a VM holds from the earlier chunks stay valid (see
+    /// `VM::load`). Classes, enums and import aliases carry over. The
+    /// interactive shell compiles each line this way.
+    pub fn next_chunk(&mut self) {
+        self.program.main = CompiledFunction::new("<main>".to_string(), 0);
+        self.scopes = vec![Scope::new(0)];
+        self.next_temp = 0;
+        self.max_temp = 0;
+        self.loop_stack.clear();
+    }
+
+    /// Links a precompiled bundle (see `link.rs`): merges its functions,
+    /// emits a call to its init chunk and binds its exports. Must run before
+    /// `compile`, once per bundle, so they exist before any user code.
+    pub fn link_bundle(&mut self, bundle: &crate::link::Bundle) {
+        let (init_idx, module) = crate::link::merge(&mut self.program, &bundle.program, &bundle.label());
+        let reg = self.alloc_register();
+        let idx = self.current_fn().add_constant(Constant::Function(init_idx));
+        self.emit_instr(Instruction::ab(Opcode::LoadConst, reg, idx));
+        self.emit_instr(Instruction::ab(Opcode::Call, reg, 0));
+        // Only the exports are visible unqualified; everything else the
+        // bundle defines stays under `module::`.
+        for (name, _) in bundle.exports() {
+            let qualified = self.current_fn().add_constant(Constant::String(crate::link::qualify(&module, &name)));
+            let plain = self.current_fn().add_constant(Constant::String(name));
+            self.emit_instr(Instruction::ab(Opcode::GetGlobal, reg, qualified));
+            self.emit_instr(Instruction::ab(Opcode::SetGlobal, plain, reg));
+        }
+        self.next_temp = reg;
+    }
+
+    /// Links a compiled source module (see `module_graph.rs`): merges it
+    /// under its module name and emits a call to its init chunk. Its exports
+    /// stay qualified; the importer reaches them through its `import` alias.
+    /// Modules are linked before bundles, in dependency order, so each keeps
+    /// its name as namespace.
+    pub fn link_module(&mut self, module: &crate::module_graph::Module) {
+        let (init_idx, _) = crate::link::merge(&mut self.program, &module.program, &module.name);
+        let reg = self.alloc_register();
+        let idx = self.current_fn().add_constant(Constant::Function(init_idx));
+        self.emit_instr(Instruction::ab(Opcode::LoadConst, reg, idx));
+        self.emit_instr(Instruction::ab(Opcode::Call, reg, 0));
+        self.next_temp = reg;
+    }
+
+    /// The linked global `alias.member` names when `alias` is an `import`
+    /// alias not shadowed by a local.
+    fn module_member(&mut self, object: &Expression, member: &str) -> Result<Option<String>, String> {
+        let ExpressionKind::Identifier(alias) = &object.kind else { return Ok(None) };
+        let Some(module) = self.module_aliases.get(*alias).cloned() else { return Ok(None) };
+        Ok(self.resolve_use(alias)?.is_none().then(|| crate::link::qualify(&module, member)))
+    }
+
+    /// Closes each resource `closes_on_return` found when the function
+    /// binding it returns, through `system.defer`. Must run before `compile`.
+    pub fn close_on_return(&mut self, closes: Vec<crate::resource_lint::ScopeClose>) {
+        self.closes = closes;
+    }
+
+    /// The builtin module path `object` names (`net.tcp`), unless its root
+    /// is a variable.
+    fn builtin_path(&mut self, object: &Expression) -> Result<Option<String>, String> {
+        match &object.kind {
+            ExpressionKind::Identifier(module) => Ok((crate::symbol::BUILTIN_MODULES.contains(module)
+                && !self.module_globals.contains(*module)
+                && self.resolve_use(module)?.is_none())
+                .then(|| module.to_string())),
+            ExpressionKind::MemberAccess { object, member } => Ok(self.builtin_path(object)?.map(|path| format!("{}.{}", path, member))),
+            _ => Ok(None),
         }
     }
 
@@ -96,7 +208,8 @@ impl Compiler {
         &mut self,
         statements: &[Statement<'_>],
         reactive_graph: Option<crate::ir::CompiledReactiveGraph>,
-    ) -> Result<&CompiledProgram, String> {
+    ) -> Result<&CompiledProgram, Vec<Diagnostic>> {
+        self.error_span = None;
         if let Some(rg) = reactive_graph {
             self.program.reactive_graph = rg;
         }
@@ -104,16 +217,38 @@ impl Compiler {
         // Pre-scan: collect nullary variant names before compiling anything,
         // so a match expression can classify a bare-identifier pattern
         // correctly regardless of where its enum is declared in the file.
+        // Also records the `pub fn` exports for when this program is linked.
         self.known_nullary_variants.insert("None".to_string());
+        let mut user_enums = Vec::new();
+        let mut exports = Vec::new();
+        for (module, alias) in crate::module_graph::imports(statements) {
+            self.module_aliases.insert(alias.to_string(), module.to_string());
+        }
         for stmt in statements {
-            if let Statement::Enum { variants, .. } = stmt {
+            if let Statement::Function { name, public: true, .. } = stmt {
+                exports.push(name.to_string());
+            }
+            if let Statement::Class { name, .. } = stmt {
+                self.known_classes.insert(name.to_string());
+            }
+            if let Statement::Let { name, .. } = stmt
+                && crate::symbol::BUILTIN_MODULES.contains(name)
+            {
+                self.module_globals.insert(name.to_string());
+            }
+            if let Statement::Enum { name, variants, .. } = stmt {
+                let mut nullary = Vec::new();
                 for (vname, payload) in variants {
                     if payload.is_none() {
                         self.known_nullary_variants.insert(vname.clone());
+                        nullary.push(vname.clone());
                     }
                 }
+                self.known_enums.insert(name.to_string());
+                user_enums.push((*name, nullary));
             }
         }
+        self.program.exports = Some(exports);
 
         // Built-in Option/Result constructors: always available at runtime,
         // regardless of whether the user also declares `enum Option<T> {...}`
@@ -127,8 +262,18 @@ impl Compiler {
         self.compile_variant_constructor("Result", "Ok");
         self.compile_variant_constructor("Result", "Err");
 
+        // Derived `EnumName::from_str` for every user enum, emitted before any
+        // user code so an explicit `impl EnumName { fn from_str(...) }` (a
+        // later `SetGlobal` of the same flattened name) still takes precedence.
+        for (enum_name, nullary) in &user_enums {
+            self.compile_enum_from_str(enum_name, nullary);
+        }
+
         for stmt in statements {
-            self.compile_statement(stmt)?;
+            if let Err(message) = self.compile_statement(stmt) {
+                let at = self.error_span.take().unwrap_or_default();
+                return Err(vec![Diagnostic::error(codes::CODEGEN, message).at(at)]);
+            }
             if let Some(scope) = self.scopes.last() {
                 self.next_temp = scope.next_register;
             }
@@ -136,6 +281,12 @@ impl Compiler {
         self.program.main.locals = self.max_temp;
         self.emit_instr(Instruction::a_only(Opcode::Halt, 0));
 
+        for func in self.program.functions.iter().chain(std::iter::once(&self.program.main)) {
+            func.check_operand_widths()
+                .and_then(|()| func.check_exception_table())
+                .map_err(|message| vec![Diagnostic::error(codes::CODEGEN, message)])?;
+        }
+
         // Static VTable Build Post-Monomorphization equivalent for AST pipeline
         self.program.vtable = crate::vtable::build_vtable(&self.program);
 
@@ -182,11 +333,12 @@ impl Compiler {
         let saved_main = std::mem::replace(&mut self.program.main, func);
         let saved_temp = self.next_temp;
         let saved_max = self.max_temp;
+        let saved_defers = std::mem::replace(&mut self.defers, false);
         self.next_temp = 0;
         self.max_temp = 0;
 
         self.scopes.push(Scope::new(0));
-        self.current_scope_mut().define("payload"); // register 0
+        self.define_local("payload"); // register 0
         self.next_temp = 1;
         self.max_temp = 1;
 
@@ -200,6 +352,7 @@ impl Compiler {
         compiled_func.locals = self.max_temp;
         self.next_temp = saved_temp;
         self.max_temp = saved_max;
+        self.defers = saved_defers;
 
         let func_idx = self.program.functions.len();
         self.program.functions.push(compiled_func);
@@ -211,17 +364,83 @@ impl Compiler {
         self.emit_instr(Instruction::ab(Opcode::SetGlobal, name_const, reg));
     }
 
+    /// Compiles the derived 1-arity `EnumName::from_str(name)`: returns
+    /// `Some(variant)` for the nullary variant whose name matches exactly,
+    /// `None` otherwise (payload variants can't be built from a bare name).
+    /// Registered as a global the same way `compile_variant_constructor` does.
+    fn compile_enum_from_str(&mut self, enum_name: &str, nullary_variants: &[String]) {
+        let func_name = format!("{}::from_str", enum_name);
+        let mut func = CompiledFunction::new(func_name.clone(), 1);
+        func.param_names = vec!["name".to_string()];
+
+        let saved_main = std::mem::replace(&mut self.program.main, func);
+        let saved_temp = self.next_temp;
+        let saved_max = self.max_temp;
+        self.next_temp = 0;
+        self.max_temp = 0;
+
+        self.scopes.push(Scope::new(0));
+        self.define_local("name"); // register 0
+        self.next_temp = 1;
+        self.max_temp = 1;
+
+        let payload_key = self.current_fn().add_constant(Constant::String("__payload__".to_string()));
+        for variant_name in nullary_variants {
+            let lit_idx = self.current_fn().add_constant(Constant::String(variant_name.clone()));
+            let lit_reg = self.alloc_register();
+            self.emit_instr(Instruction::ab(Opcode::LoadConst, lit_reg, lit_idx));
+            let cond_reg = self.alloc_register();
+            self.emit_instr(Instruction::new(Opcode::Eq, cond_reg, 0, lit_reg));
+            let jump_next = self.emit_instr(Instruction::ab(Opcode::JumpIfFalse, 0, cond_reg));
+
+            let value_reg = self.emit_nullary_variant_value(enum_name, variant_name);
+            let some_reg = self.emit_nullary_variant_value("Option", "Some");
+            self.emit_instr(Instruction::new(Opcode::SetMember, some_reg, payload_key, value_reg));
+            self.emit_instr(Instruction::a_only(Opcode::Return, some_reg));
+
+            let next_pos = self.current_fn().instructions.len();
+            self.current_fn().instructions[jump_next].a = next_pos as u16;
+            self.next_temp = 1;
+        }
+        let none_reg = self.emit_nullary_variant_value("Option", "None");
+        self.emit_instr(Instruction::a_only(Opcode::Return, none_reg));
+        self.scopes.pop();
+
+        let mut compiled_func = std::mem::replace(&mut self.program.main, saved_main);
+        compiled_func.locals = self.max_temp;
+        self.next_temp = saved_temp;
+        self.max_temp = saved_max;
+
+        let func_idx = self.program.functions.len();
+        self.program.functions.push(compiled_func);
+
+        let name_const = self.current_fn().add_constant(Constant::String(func_name));
+        let reg = self.alloc_register();
+        let idx_const = self.current_fn().add_constant(Constant::Function(func_idx));
+        self.emit_instr(Instruction::ab(Opcode::LoadConst, reg, idx_const));
+        self.emit_instr(Instruction::ab(Opcode::SetGlobal, name_const, reg));
+    }
+
     fn current_fn(&mut self) -> &mut CompiledFunction {
         &mut self.program.main
     }
 
-    /// Emit an instruction and record the current source line in line_map.
+    /// Declares `name` in the innermost scope and records it for debug info.
+    fn define_local(&mut self, name: &str) -> u16 {
+        let reg = self.current_scope_mut().define(name);
+        self.current_fn().local_names.push((name.to_string(), reg));
+        reg
+    }
+
+    /// Emit an instruction and record the current source position in
+    /// line_map and column_map.
     fn emit_instr(&mut self, instr: Instruction) -> usize {
-        let line = self.current_line;
+        let (line, column) = (self.current_line, self.current_column);
         let func = &mut self.program.main;
         let idx = func.instructions.len();
         func.instructions.push(instr);
         func.line_map.push(line);
+        func.column_map.push(column);
         idx
     }
 
@@ -236,7 +455,7 @@ impl Compiler {
 
     fn alloc_register(&mut self) -> u16 {
         let r = self.next_temp;
-        self.next_temp += 1;
+        self.next_temp = self.next_temp.saturating_add(1);
         if self.next_temp > self.max_temp {
             self.max_temp = self.next_temp;
         }
@@ -265,28 +484,28 @@ impl Compiler {
 
     /// Emits the store instructions to write `val_reg` into `target` (Identifier/
     /// MemberAccess/Index), plus the reactive State tick if `target` is a known
-    /// State node. Shared by `Expression::Assign` and by mutating array method
+    /// State node. Shared by `ExpressionKind::Assign` and by mutating array method
     /// calls (`arr.push(x)`) that need to write their result back into `arr`.
     fn emit_assign_to_target(&mut self, target: &Expression<'_>, val_reg: u16) -> Result<(), String> {
         let mut target_name = None;
 
-        match target {
-            Expression::Identifier(name) => {
-                target_name = Some(name.clone());
+        match &target.kind {
+            ExpressionKind::Identifier(name) => {
+                target_name = Some(*name);
                 if let Some(slot) = self.resolve_assign(name) {
                     self.emit_instr(Instruction::ab(Opcode::SetLocal, slot, val_reg));
                 } else {
-                    let name_idx = self.current_fn().add_constant(Constant::String(name.clone()));
+                    let name_idx = self.current_fn().add_constant(Constant::String(name.to_string()));
                     self.emit_instr(Instruction::ab(Opcode::SetGlobal, name_idx, val_reg));
                 }
             }
-            Expression::MemberAccess { object, member } => {
+            ExpressionKind::MemberAccess { object, member } => {
                 let obj_reg = self.compile_expression(object)?;
-                let member_idx = self.current_fn().add_constant(Constant::String(member.clone()));
+                let member_idx = self.current_fn().add_constant(Constant::String(member.to_string()));
                 self.emit_instr(Instruction::new(Opcode::SetMember, obj_reg, member_idx, val_reg));
                 self.writeback_global_root(object, obj_reg);
             }
-            Expression::Index { left, index } => {
+            ExpressionKind::Index { left, index } => {
                 let obj_reg = self.compile_expression(left)?;
                 let idx_reg = self.compile_expression(index)?;
                 self.emit_instr(Instruction::new(Opcode::SetIndex, obj_reg, idx_reg, val_reg));
@@ -297,13 +516,12 @@ impl Compiler {
 
         // --- REACTIVE STATE TRACKING ---
         // If the user manually mutates a known State, tell the VM so it can tick
-        if let Some(name) = target_name {
-            if let Some(node) = self.program.reactive_graph.nodes.get(&name) {
-                if matches!(node.kind, crate::ir::ReactiveNodeKind::State) {
-                    let name_idx = self.current_fn().add_constant(Constant::String(name));
-                    self.emit_instr(Instruction::ab(Opcode::UpdateState, name_idx, val_reg));
-                }
-            }
+        if let Some(name) = target_name
+            && let Some(node) = self.program.reactive_graph.nodes.get(name)
+            && matches!(node.kind, crate::ir::ReactiveNodeKind::State)
+        {
+            let name_idx = self.current_fn().add_constant(Constant::String(name.to_string()));
+            self.emit_instr(Instruction::ab(Opcode::UpdateState, name_idx, val_reg));
         }
 
         Ok(())
@@ -319,11 +537,11 @@ impl Compiler {
     /// (`a.b.field = x`) are unaffected since they were already unreachable
     /// (no parser support for member-access assignment targets).
     fn writeback_global_root(&mut self, root: &Expression<'_>, reg: u16) {
-        if let Expression::Identifier(name) = root {
-            if self.resolve_use(name).ok().flatten().is_none() {
-                let name_idx = self.current_fn().add_constant(Constant::String(name.clone()));
-                self.emit_instr(Instruction::ab(Opcode::SetGlobal, name_idx, reg));
-            }
+        if let ExpressionKind::Identifier(name) = &root.kind
+            && self.resolve_use(name).ok().flatten().is_none()
+        {
+            let name_idx = self.current_fn().add_constant(Constant::String(name.to_string()));
+            self.emit_instr(Instruction::ab(Opcode::SetGlobal, name_idx, reg));
         }
     }
 
@@ -371,7 +589,21 @@ impl Compiler {
     // ========== Statements ==========
 
     fn compile_statement(&mut self, stmt: &Statement<'_>) -> Result<(), String> {
-        // Update current_line from the AST node
+        let result = self.compile_statement_kind(stmt);
+        self.note_error_span(&result, stmt.span());
+        result
+    }
+
+    /// Records `span` as where a failed `result` comes from, unless a node
+    /// inside it already was.
+    fn note_error_span<T>(&mut self, result: &Result<T, String>, span: Span) {
+        if result.is_err() && self.error_span.is_none() && span.line > 0 {
+            self.error_span = Some(span);
+        }
+    }
+
+    fn compile_statement_kind(&mut self, stmt: &Statement<'_>) -> Result<(), String> {
+        // Update current_line and current_column from the AST node
         match stmt {
             Statement::Let { line, .. } | Statement::Return { line, .. }
             | Statement::Expression { line, .. } | Statement::Block { line, .. }
@@ -379,22 +611,23 @@ impl Compiler {
             | Statement::For { line, .. } | Statement::Include { line, .. }
             | Statement::Class { line, .. } | Statement::Struct { line, .. }
             | Statement::Enum { line, .. } | Statement::Trait { line, .. } | Statement::Impl { line, .. }
-            | Statement::Break { line } | Statement::Continue { line }
-            | Statement::Version { line, .. }
+            | Statement::Break { line, .. } | Statement::Continue { line, .. }
+            | Statement::Version { line, .. } | Statement::ImportBundle { line, .. } | Statement::Import { line, .. }
             | Statement::State { line, .. } | Statement::Computed { line, .. } | Statement::Effect { line, .. } => {
                 self.current_line = *line as u32;
             }
         }
+        self.current_column = stmt.span().column as u32;
         match stmt {
             Statement::State { name, value, .. } => {
                 let reg = self.compile_expression(value)?;
-                let name_idx = self.current_fn().add_constant(Constant::String(name.clone()));
+                let name_idx = self.current_fn().add_constant(Constant::String(name.to_string()));
                 self.emit_instr(Instruction::ab(Opcode::SetState, name_idx, reg));
                 
                 if self.scopes.len() == 1 {
                     self.emit_instr(Instruction::ab(Opcode::SetGlobal, name_idx, reg));
                 } else {
-                    let slot = self.current_scope_mut().define(name);
+                    let slot = self.define_local(name);
                     if self.current_scope_mut().next_register > self.max_temp {
                         self.max_temp = self.current_scope_mut().next_register; 
                     }
@@ -431,13 +664,13 @@ impl Compiler {
                 self.emit_instr(Instruction::ab(Opcode::LoadConst, closure_reg, idx_const));
                 self.emit_instr(Instruction::ab(Opcode::MakeClosure, closure_reg, 0));
                 
-                let name_idx = self.current_fn().add_constant(Constant::String(name.clone()));
+                let name_idx = self.current_fn().add_constant(Constant::String(name.to_string()));
                 self.emit_instr(Instruction::ab(Opcode::InitComputed, name_idx, closure_reg));
                 
                 if self.scopes.len() == 1 {
                     self.emit_instr(Instruction::ab(Opcode::SetGlobal, name_idx, closure_reg));
                 } else {
-                    let slot = self.current_scope_mut().define(name);
+                    let slot = self.define_local(name);
                     if self.current_scope_mut().next_register > self.max_temp {
                         self.max_temp = self.current_scope_mut().next_register; 
                     }
@@ -503,21 +736,25 @@ impl Compiler {
                 
                 self.emit_instr(Instruction::ab(Opcode::InitEffect, deps_reg, closure_reg));
             }
-            Statement::Let { name, value, mutable: _, type_hint: _, .. } => {
+            Statement::Let { name, value, line, .. } => {
                 let reg = self.compile_expression(value)?;
                 if self.scopes.len() == 1 {
                     // Global scope -> SetGlobal
-                    let name_idx = self.current_fn().add_constant(Constant::String(name.clone()));
+                    let name_idx = self.current_fn().add_constant(Constant::String(name.to_string()));
                     self.emit_instr(Instruction::ab(Opcode::SetGlobal, name_idx, reg));
                 } else {
                     // Local scope
-                    let slot = self.current_scope_mut().define(name);
+                    let slot = self.define_local(name);
                     if self.current_scope_mut().next_register > self.max_temp {
                         self.max_temp = self.current_scope_mut().next_register; 
                     }
                     if slot != reg {
                         self.emit_instr(Instruction::ab(Opcode::SetLocal, slot, reg));
                     }
+                    if let Some(close) = self.closes.iter().position(|c| c.line == *line && c.name == *name) {
+                        let close = self.closes.swap_remove(close);
+                        self.emit_deferred_close(&close, slot);
+                    }
                 }
             }
             Statement::Function { name, parameters, body, return_type: _, .. } => {
@@ -526,7 +763,9 @@ impl Compiler {
             Statement::Return { value, .. } => {
                 if let Some(val) = value {
                     // TCO: if the return value is a function call, emit TailCall instead
-                    if let Expression::Call { function, arguments } = val {
+                    if let ExpressionKind::Call { function, arguments } = &val.kind
+                        && !self.defers
+                    {
                         // Compile the function reference
                         let func_reg = self.compile_expression(function)?;
                         let call_reg = self.alloc_register();
@@ -563,20 +802,37 @@ impl Compiler {
                 }
                 self.scopes.pop();
             }
-            Statement::While { condition, body, .. } => {
-                self.compile_while(condition, body)?;
+            Statement::While { condition, body, label, .. } => {
+                self.compile_while(condition, body, label.as_deref())?;
             }
-            Statement::For { iterator, range, body, .. } => {
-                self.compile_for(iterator, range, body)?;
+            Statement::For { iterator, range, body, label, .. } => {
+                self.compile_for(iterator, range, body, label.as_deref())?;
             }
             Statement::Include { .. } => {
                 // Includes resolved at higher level
             }
-            Statement::Class { name: class_name, methods, .. } => {
+            Statement::ImportBundle { .. } => {
+                // Bundles are merged up front by `link_bundle`
+            }
+            Statement::Import { .. } => {
+                // Modules are merged up front by `link_module`
+            }
+            Statement::Class { name: class_name, parent, methods, fields, constants, .. } => {
+                let info = self.program.class_mut(class_name);
+                info.parent = parent.map(str::to_string);
+                info.fields = fields.iter().map(|(_, name, _, _)| name.clone()).collect();
+                for constant in constants {
+                    if let Statement::Let { name: const_name, value, .. } = constant {
+                        let val_reg = self.compile_expression(value)?;
+                        let name_idx = self.current_fn().add_constant(Constant::String(format!("{}::{}", class_name, const_name)));
+                        self.emit_instr(Instruction::ab(Opcode::SetGlobal, name_idx, val_reg));
+                    }
+                }
                 for method in methods {
                     if let Statement::Function { name: method_name, parameters, body, .. } = method {
                         let flat_name = format!("{}::{}", class_name, method_name);
-                        self.compile_function(&flat_name, parameters, body)?;
+                        let func_idx = self.compile_function(&flat_name, parameters, body)?;
+                        self.program.class_mut(class_name).methods.insert(method_name.to_string(), func_idx);
                     }
                 }
             }
@@ -605,33 +861,28 @@ impl Compiler {
                 for method in methods {
                     if let Statement::Function { name: method_name, parameters, body, .. } = method {
                         let flat_name = format!("{}::{}", target_name, method_name);
-                        self.compile_function(&flat_name, parameters, body)?;
+                        let func_idx = self.compile_function(&flat_name, parameters, body)?;
+                        self.program.class_mut(target_name).methods.insert(method_name.to_string(), func_idx);
                     }
                 }
             }
             // `struct`/`trait` declarations have no runtime footprint of their
-            // own: a struct's instantiation is `Expression::StructLiteral`
+            // own: a struct's instantiation is `ExpressionKind::StructLiteral`
             // (compiled independently of how/whether the type was declared),
             // and a trait is a pure compile-time interface (method signatures
             // only, no bodies -- those live in `impl` blocks, above).
             Statement::Struct { .. } | Statement::Trait { .. } => {}
-            Statement::Break { .. } => {
+            Statement::Break { label, .. } => {
                 let jump_idx = self.emit_instr(Instruction::a_only(Opcode::Jump, 0));
-                match self.loop_stack.last_mut() {
-                    Some(ctx) => ctx.break_jumps.push(jump_idx),
-                    None => return Err("'break' used outside of a loop".to_string()),
-                }
+                self.find_loop("break", label.as_deref())?.break_jumps.push(jump_idx);
             }
-            Statement::Continue { .. } => {
+            Statement::Continue { label, .. } => {
                 let jump_idx = self.emit_instr(Instruction::a_only(Opcode::Jump, 0));
-                match self.loop_stack.last_mut() {
-                    Some(ctx) => ctx.continue_jumps.push(jump_idx),
-                    None => return Err("'continue' used outside of a loop".to_string()),
-                }
+                self.find_loop("continue", label.as_deref())?.continue_jumps.push(jump_idx);
             }
             Statement::Version { build, .. } => {
                 if *build > CURRENT_BUILD {
-                    eprintln!("Warning: Script requires build {}, but you are running build {}. Some features may not work.", build, CURRENT_BUILD);
+                    crate::output::warning(format!("script requires build {}, but you are running build {}. Some features may not work.", build, CURRENT_BUILD));
                 }
             }
 
@@ -639,12 +890,14 @@ impl Compiler {
         Ok(())
     }
 
+    /// Compiles a function and binds it to the global `name`; returns its
+    /// index in `program.functions`.
     fn compile_function(
         &mut self,
         name: &str,
         parameters: &[(String, String)],
         body: &Statement<'_>,
-    ) -> Result<(), String> {
+    ) -> Result<usize, String> {
         let mut func = CompiledFunction::new(name.to_string(), parameters.len() as u16);
         func.param_names = parameters.iter().map(|(n, _)| n.clone()).collect();
 
@@ -658,7 +911,7 @@ impl Compiler {
         // Parameters occupy registers 0..arity
         self.scopes.push(Scope::new(0));
         for (pname, _) in parameters {
-            self.current_scope_mut().define(pname);
+            self.define_local(pname);
             self.next_temp += 1;
         }
         if self.next_temp > self.max_temp { self.max_temp = self.next_temp; }
@@ -694,15 +947,47 @@ impl Compiler {
         self.emit_instr(Instruction::ab(Opcode::LoadConst, reg, idx_const));
         self.emit_instr(Instruction::ab(Opcode::SetGlobal, name_const, reg));
 
-        Ok(())
+        Ok(func_idx)
     }
 
-    fn compile_while(&mut self, condition: &Expression<'_>, body: &Statement<'_>) -> Result<(), String> {
+    /// `system.defer(<closer>, <local>)`, or `<local>.ok` when the local holds
+    /// a `net.*` result: the resource is closed when the function returns.
+    fn emit_deferred_close(&mut self, close: &crate::resource_lint::ScopeClose, local: u16) {
+        self.defers = true;
+        let call_reg = self.alloc_register();
+        let defer_idx = self.current_fn().add_constant(Constant::String("system.defer".to_string()));
+        self.emit_instr(Instruction::ab(Opcode::GetGlobal, call_reg, defer_idx));
+        let closer_reg = self.alloc_register();
+        let closer_idx = self.current_fn().add_constant(Constant::String(close.closer.to_string()));
+        self.emit_instr(Instruction::ab(Opcode::GetGlobal, closer_reg, closer_idx));
+        let id_reg = self.alloc_register();
+        if close.ok {
+            let ok_idx = self.current_fn().add_constant(Constant::String("ok".to_string()));
+            self.emit_instr(Instruction::new(Opcode::GetMember, id_reg, local, ok_idx));
+        } else {
+            self.emit_instr(Instruction::ab(Opcode::SetLocal, id_reg, local));
+        }
+        self.emit_instr(Instruction::ab(Opcode::Call, call_reg, 2));
+    }
+
+    /// The loop a `break`/`continue` (`keyword`) jumps out of or back to: the
+    /// innermost one, or for a labeled jump the innermost one with that label.
+    fn find_loop(&mut self, keyword: &str, label: Option<&str>) -> Result<&mut LoopContext, String> {
+        match label {
+            None => self.loop_stack.last_mut()
+                .ok_or_else(|| format!("'{}' used outside of a loop", keyword)),
+            Some(name) => self.loop_stack.iter_mut().rev()
+                .find(|ctx| ctx.label.as_deref() == Some(name))
+                .ok_or_else(|| format!("Unknown loop label '{}' in '{}'", name, keyword)),
+        }
+    }
+
+    fn compile_while(&mut self, condition: &Expression<'_>, body: &Statement<'_>, label: Option<&str>) -> Result<(), String> {
         let loop_start = self.current_fn().instructions.len();
         let cond_reg = self.compile_expression(condition)?;
         let jump_idx = self.emit_instr(Instruction::ab(Opcode::JumpIfFalse, 0, cond_reg));
 
-        self.loop_stack.push(LoopContext { break_jumps: vec![], continue_jumps: vec![] });
+        self.loop_stack.push(LoopContext { break_jumps: vec![], continue_jumps: vec![], label: label.map(str::to_string) });
         if let Statement::Block { statements, .. } = body {
             for s in statements {
                 self.compile_statement(s)?;
@@ -726,7 +1011,7 @@ impl Compiler {
         Ok(())
     }
 
-    fn compile_for(&mut self, variable: &str, iterable: &Expression<'_>, body: &Statement<'_>) -> Result<(), String> {
+    fn compile_for(&mut self, variable: &str, iterable: &Expression<'_>, body: &Statement<'_>, label: Option<&str>) -> Result<(), String> {
         let iter_reg = self.compile_expression(iterable)?;
 
         // Compute the length once via the `len` builtin instead of relying on
@@ -769,7 +1054,7 @@ impl Compiler {
         // later by `mut i = 0` would keep resolving `i` to the loop's stale
         // register instead of the new global.
         let previous_binding = self.current_scope_mut().locals.get(variable).copied();
-        let var_reg = self.current_scope_mut().define(variable);
+        let var_reg = self.define_local(variable);
         self.next_temp = self.current_scope_mut().next_register;
         if self.next_temp > self.max_temp {
             self.max_temp = self.next_temp;
@@ -781,7 +1066,7 @@ impl Compiler {
         let jump_idx = self.emit_instr(Instruction::ab(Opcode::JumpIfFalse, 0, cond_reg));
         self.emit_instr(Instruction::new(Opcode::GetIndex, var_reg, iter_reg, idx_reg));
 
-        self.loop_stack.push(LoopContext { break_jumps: vec![], continue_jumps: vec![] });
+        self.loop_stack.push(LoopContext { break_jumps: vec![], continue_jumps: vec![], label: label.map(str::to_string) });
         if let Statement::Block { statements, .. } = body {
             for s in statements {
                 self.compile_statement(s)?;
@@ -819,39 +1104,45 @@ impl Compiler {
     // ========== Expressions ==========
 
     fn compile_expression(&mut self, expr: &Expression<'_>) -> Result<u16, String> {
-        match expr {
-            Expression::Integer(val) => {
+        let result = self.compile_expression_kind(expr);
+        self.note_error_span(&result, expr.span);
+        result
+    }
+
+    fn compile_expression_kind(&mut self, expr: &Expression<'_>) -> Result<u16, String> {
+        match &expr.kind {
+            ExpressionKind::Integer(val) => {
                 let reg = self.alloc_register();
                 let idx = self.current_fn().add_constant(Constant::Integer(*val));
                 self.emit_instr(Instruction::ab(Opcode::LoadConst, reg, idx));
                 Ok(reg)
             }
-            Expression::Try { value } => self.compile_expression(value), // TEMPORARY stub
-            Expression::Float(val) => {
+            ExpressionKind::Try { value } => self.compile_expression(value), // TEMPORARY stub
+            ExpressionKind::Float(val) => {
                 let reg = self.alloc_register();
                 let idx = self.current_fn().add_constant(Constant::Float(*val));
                 self.emit_instr(Instruction::ab(Opcode::LoadConst, reg, idx));
                 Ok(reg)
             }
-            Expression::String(val) => {
+            ExpressionKind::String(val) => {
                 let reg = self.alloc_register();
-                let idx = self.current_fn().add_constant(Constant::String(val.clone()));
+                let idx = self.current_fn().add_constant(Constant::String(val.to_string()));
                 self.emit_instr(Instruction::ab(Opcode::LoadConst, reg, idx));
                 Ok(reg)
             }
-            Expression::Boolean(val) => {
+            ExpressionKind::Boolean(val) => {
                 let reg = self.alloc_register();
                 let opcode = if *val { Opcode::LoadTrue } else { Opcode::LoadFalse };
                 self.emit_instr(Instruction::a_only(opcode, reg));
                 Ok(reg)
             }
-            Expression::StructLiteral { name, fields, .. } => {
+            ExpressionKind::StructLiteral { name, fields, .. } => {
                 let obj_reg = self.alloc_register();
                 self.emit_instr(Instruction::ab(Opcode::MakeMap, obj_reg, 0));
                 
                 // Add __class__ hidden field
                 let class_key_idx = self.current_fn().add_constant(Constant::String("__class__".to_string()));
-                let class_val_idx = self.current_fn().add_constant(Constant::String(name.clone()));
+                let class_val_idx = self.current_fn().add_constant(Constant::String(name.to_string()));
                 let class_val_reg = self.alloc_register();
                 self.emit_instr(Instruction::ab(Opcode::LoadConst, class_val_reg, class_val_idx));
                 self.emit_instr(Instruction::new(Opcode::SetMember, obj_reg, class_key_idx, class_val_reg));
@@ -863,34 +1154,34 @@ impl Compiler {
                 }
                 Ok(obj_reg)
             }
-            Expression::Null => {
+            ExpressionKind::Null => {
                 let reg = self.alloc_register();
                 self.emit_instr(Instruction::a_only(Opcode::LoadNull, reg));
                 Ok(reg)
             }
-            Expression::Identifier(name) => {
+            ExpressionKind::Identifier(name) => {
                 if let Some(reg) = self.resolve_use(name)? {
                     return Ok(reg);
                 }
                 // Global lookup (Globals are strict-const or unsafe-shared, we allow access)
                 let reg = self.alloc_register();
-                let name_idx = self.current_fn().add_constant(Constant::String(name.clone()));
+                let name_idx = self.current_fn().add_constant(Constant::String(name.to_string()));
                 self.emit_instr(Instruction::ab(Opcode::GetGlobal, reg, name_idx));
                 Ok(reg)
             }
-            Expression::Prefix { operator, right } => {
+            ExpressionKind::Prefix { operator, right } => {
                 let right_reg = self.compile_expression(right)?;
-                if operator == "&" || operator == "&mut" {
+                if *operator == "&" || *operator == "&mut" {
                     // This VM has no pointer/aliasing value: locals ARE registers and
                     // calls clone arguments into the callee's frame, so a reference is
                     // erased to the same register as its operand. `&mut` additionally
                     // marks the argument, at its call site, for write-back of the
-                    // call's return value (see Expression::Call below) -- the only
+                    // call's return value (see ExpressionKind::Call below) -- the only
                     // channel by which a callee can appear to mutate it.
                     return Ok(right_reg);
                 }
                 let result = self.alloc_register();
-                let opcode = match operator.as_str() {
+                let opcode = match *operator {
                     "-" => Opcode::Neg,
                     "!" => Opcode::Not,
                     _ => return Err(format!("Unknown prefix operator: {}", operator)),
@@ -898,11 +1189,11 @@ impl Compiler {
                 self.emit_instr(Instruction::ab(opcode, result, right_reg));
                 Ok(result)
             }
-            Expression::Infix { left, operator, right } => {
+            ExpressionKind::Infix { left, operator, right } => {
                 let left_reg = self.compile_expression(left)?;
                 let right_reg = self.compile_expression(right)?;
                 let result = self.alloc_register();
-                let opcode = match operator.as_str() {
+                let opcode = match *operator {
                     "+" => Opcode::Add,
                     "-" => Opcode::Sub,
                     "*" => Opcode::Mul,
@@ -921,18 +1212,18 @@ impl Compiler {
                 self.emit_instr(Instruction::new(opcode, result, left_reg, right_reg));
                 Ok(result)
             }
-            Expression::Assign { target, value } => {
+            ExpressionKind::Assign { target, value } => {
                 let val_reg = self.compile_expression(value)?;
                 self.emit_assign_to_target(target, val_reg)?;
                 Ok(val_reg)
             }
-            Expression::Call { function, arguments } => {
+            ExpressionKind::Call { function, arguments } => {
 
                 // Helper: flatten multi-level MemberAccess into a dot-separated string
                 fn stringify_member_access(expr: &Expression) -> Option<String> {
-                    match expr {
-                        Expression::Identifier(name) => Some(name.clone()),
-                        Expression::MemberAccess { object, member } => {
+                    match &expr.kind {
+                        ExpressionKind::Identifier(name) => Some(name.to_string()),
+                        ExpressionKind::MemberAccess { object, member } => {
                             let parent = stringify_member_access(object)?;
                             Some(format!("{}.{}", parent, member))
                         }
@@ -941,18 +1232,46 @@ impl Compiler {
                 }
 
                 // Module builtins vs Method calling on Instance
-                if let Expression::MemberAccess { object, member } = *function {
+                if let ExpressionKind::MemberAccess { object, member } = &function.kind {
+                    // `m.square(3)` on an `import util.math as m`
+                    if let Some(flat_name) = self.module_member(object, member)? {
+                        let call_reg = self.alloc_register();
+                        let name_idx = self.current_fn().add_constant(Constant::String(flat_name));
+                        self.emit_instr(Instruction::ab(Opcode::GetGlobal, call_reg, name_idx));
+                        for (i, arg) in arguments.iter().enumerate() {
+                            let expected_reg = call_reg + 1 + i as u16;
+                            let arg_reg = self.compile_expression(arg)?;
+                            if arg_reg != expected_reg {
+                                while self.next_temp <= expected_reg {
+                                    self.alloc_register();
+                                }
+                                self.emit_instr(Instruction::ab(Opcode::SetLocal, expected_reg, arg_reg));
+                            }
+                        }
+                        self.emit_instr(Instruction::ab(Opcode::Call, call_reg, arguments.len() as u16));
+                        return Ok(call_reg);
+                    }
+
                     // First, check for multi-level builtin calls like system.os.isWindows()
                     let full_path = stringify_member_access(function);
-                    let is_multilevel_builtin = full_path.as_ref().map_or(false, |p| {
-                        p.starts_with("system.") || p.starts_with("Math.") || p.starts_with("math.")
+                    // `net.tcp.connect(..)`: any nested builtin module
+                    let nested_builtin = !matches!(&object.kind, ExpressionKind::Identifier(_)) && self.builtin_path(object)?.is_some();
+                    let is_multilevel_builtin = nested_builtin || full_path.as_ref().is_some_and(|p| {
+                        p.starts_with("system.") || p.starts_with("Math.") || p.starts_with("math.") || p.starts_with("data.") || p.starts_with("crypto.") || p.starts_with("archive.")
                     });
 
                     if is_multilevel_builtin {
                         let flat_name = full_path.unwrap();
+                        if flat_name == "system.defer" {
+                            self.defers = true;
+                        }
+                        // `data.*` and `crypto.*` are dispatching namespaces, not
+                        // tables of globals: call them by name, as single-level
+                        // modules are.
+                        let load = if flat_name.starts_with("data.") || flat_name.starts_with("crypto.") { Opcode::LoadConst } else { Opcode::GetGlobal };
                         let call_reg = self.alloc_register();
                         let name_idx = self.current_fn().add_constant(Constant::String(flat_name));
-                        self.emit_instr(Instruction::ab(Opcode::GetGlobal, call_reg, name_idx));
+                        self.emit_instr(Instruction::ab(load, call_reg, name_idx));
                         for (i, arg) in arguments.iter().enumerate() {
                             let expected_reg = call_reg + 1 + i as u16;
                             let arg_reg = self.compile_expression(arg)?;
@@ -968,11 +1287,11 @@ impl Compiler {
                     }
 
                     let mut is_local_obj = false;
-                    if let Expression::Identifier(name) = &**object {
+                    if let ExpressionKind::Identifier(name) = &object.kind {
                         let is_capitalized = name.chars().next().unwrap_or('a').is_uppercase();
-                        if self.resolve_use(name)?.is_some() || self.program.reactive_graph.nodes.contains_key(name) {
+                        if self.resolve_use(name)?.is_some() || self.program.reactive_graph.nodes.contains_key(*name) {
                             is_local_obj = true;
-                        } else if !is_capitalized {
+                        } else if !is_capitalized && !crate::symbol::BUILTIN_MODULES.contains(name) {
                             // Se è minuscolo ed è globale (es. 'let p = Point...; p.greet()'), NON è un module
                             is_local_obj = true;
                         }
@@ -981,7 +1300,32 @@ impl Compiler {
                     }
 
                     if !is_local_obj {
-                        let Expression::Identifier(module_name) = &**object else { unreachable!() };
+                        let ExpressionKind::Identifier(module_name) = &object.kind else { unreachable!() };
+
+                        // `EnumName.member(...)` on a user enum or
+                        // `ClassName.member(...)` on a class: a call of the
+                        // flattened `Type::member` global (e.g. the derived
+                        // `from_str`, or an associated function), not a
+                        // builtin module function.
+                        if self.known_enums.contains(*module_name) || self.known_classes.contains(*module_name) {
+                            let flat_name = format!("{}::{}", module_name, member);
+                            let call_reg = self.alloc_register();
+                            let name_idx = self.current_fn().add_constant(Constant::String(flat_name));
+                            self.emit_instr(Instruction::ab(Opcode::GetGlobal, call_reg, name_idx));
+                            for (i, arg) in arguments.iter().enumerate() {
+                                let expected_reg = call_reg + 1 + i as u16;
+                                let arg_reg = self.compile_expression(arg)?;
+                                if arg_reg != expected_reg {
+                                    while self.next_temp <= expected_reg {
+                                        self.alloc_register();
+                                    }
+                                    self.emit_instr(Instruction::ab(Opcode::SetLocal, expected_reg, arg_reg));
+                                }
+                            }
+                            self.emit_instr(Instruction::ab(Opcode::Call, call_reg, arguments.len() as u16));
+                            return Ok(call_reg);
+                        }
+
                         let mut chars = module_name.chars();
                         let cap_module = match chars.next() {
                             None => String::new(),
@@ -1006,7 +1350,7 @@ impl Compiler {
                     } else {
                         // OOP Method Call
                         let obj_reg = self.compile_expression(object)?;
-                        let method_idx = self.current_fn().add_constant(Constant::String(member.clone()));
+                        let method_idx = self.current_fn().add_constant(Constant::String(member.to_string()));
                         let call_reg = self.alloc_register();
                         self.emit_instr(Instruction::new(Opcode::LoadMethod, call_reg, obj_reg, method_idx));
                         
@@ -1025,7 +1369,7 @@ impl Compiler {
                         // Array mutating methods (push/pop/remove_at/insert/reverse/sort) are
                         // implemented natively as functional (return a new array) -- write the
                         // result back into the receiver so the call also mutates it in place.
-                        if matches!(member.as_ref(), "push" | "pop" | "remove_at" | "insert" | "reverse" | "sort") {
+                        if matches!(*member, "push" | "pop" | "remove_at" | "insert" | "reverse" | "sort") {
                             self.emit_assign_to_target(object, call_reg)?;
                         }
 
@@ -1046,16 +1390,16 @@ impl Compiler {
                         }
                         self.emit_instr(Instruction::ab(Opcode::SetLocal, expected_reg, arg_reg));
                     }
-                    if let Expression::Prefix { operator, right } = arg {
-                        if operator == "&mut" {
-                            if !matches!(**right, Expression::Identifier(_) | Expression::MemberAccess { .. } | Expression::Index { .. }) {
-                                return Err("'&mut' requires a mutable place (a variable, field, or index expression)".to_string());
-                            }
-                            if mut_ref_target.is_some() {
-                                return Err("at most one '&mut' argument is supported per call (the callee has a single return value to write back)".to_string());
-                            }
-                            mut_ref_target = Some(right);
+                    if let ExpressionKind::Prefix { operator, right } = &arg.kind
+                        && *operator == "&mut"
+                    {
+                        if !matches!(&right.kind, ExpressionKind::Identifier(_) | ExpressionKind::MemberAccess { .. } | ExpressionKind::Index { .. }) {
+                            return Err("'&mut' requires a mutable place (a variable, field, or index expression)".to_string());
+                        }
+                        if mut_ref_target.is_some() {
+                            return Err("at most one '&mut' argument is supported per call (the callee has a single return value to write back)".to_string());
                         }
+                        mut_ref_target = Some(right);
                     }
                 }
                 self.emit_instr(Instruction::ab(Opcode::Call, call_reg, arguments.len() as u16));
@@ -1071,7 +1415,7 @@ impl Compiler {
 
                 Ok(call_reg)
             }
-            Expression::If { condition, consequence, alternative } => {
+            ExpressionKind::If { condition, consequence, alternative } => {
                 let cond_reg = self.compile_expression(condition)?;
                 let result_reg = self.alloc_register();
                 let jump_else = self.emit_instr(Instruction::ab(Opcode::JumpIfFalse, 0, cond_reg));
@@ -1094,29 +1438,60 @@ impl Compiler {
 
                 Ok(result_reg)
             }
-            Expression::Index { left, index } => {
+            ExpressionKind::Index { left, index } => {
                 let left_reg = self.compile_expression(left)?;
                 let idx_reg = self.compile_expression(index)?;
                 let result = self.alloc_register();
                 self.emit_instr(Instruction::new(Opcode::GetIndex, result, left_reg, idx_reg));
                 Ok(result)
             }
-            Expression::MemberAccess { object, member } => {
+            ExpressionKind::MemberAccess { object, member } => {
+                // `ClassName.NAME`: a class constant, stored as the
+                // flattened `ClassName::NAME` global; `m.square` on an
+                // `import` alias, the module's linked function.
+                let class_const = match &object.kind {
+                    ExpressionKind::Identifier(class_name) if self.known_classes.contains(*class_name) => {
+                        self.resolve_use(class_name)?.is_none().then(|| format!("{}::{}", class_name, member))
+                    }
+                    _ => self.module_member(object, member)?,
+                };
+                // `net.tcp.close` as a value: the builtin itself, e.g. to
+                // hand to `system.defer`
+                let class_const = match class_const {
+                    None => self.builtin_path(object)?.map(|path| format!("{}.{}", path, member)),
+                    flat => flat,
+                };
+                if let Some(flat_name) = class_const {
+                    let reg = self.alloc_register();
+                    let name_idx = self.current_fn().add_constant(Constant::String(flat_name));
+                    self.emit_instr(Instruction::ab(Opcode::GetGlobal, reg, name_idx));
+                    return Ok(reg);
+                }
                 let obj_reg = self.compile_expression(object)?;
-                let name_idx = self.current_fn().add_constant(Constant::String(member.clone()));
+                let name_idx = self.current_fn().add_constant(Constant::String(member.to_string()));
                 let result = self.alloc_register();
                 self.emit_instr(Instruction::new(Opcode::GetMember, result, obj_reg, name_idx));
                 Ok(result)
             }
-            Expression::ArrayLiteral(elements) => {
+            ExpressionKind::ArrayLiteral(elements) => {
+                // `MakeArray` reads contiguous registers, but an element that
+                // needs temps of its own (a nested array, a call) lands past
+                // its slot: move it back, as call arguments do
                 let start_reg = self.next_temp;
-                for elem in elements {
-                    self.compile_expression(elem)?;
+                for (i, elem) in elements.iter().enumerate() {
+                    let expected_reg = start_reg + i as u16;
+                    let elem_reg = self.compile_expression(elem)?;
+                    if elem_reg != expected_reg {
+                        while self.next_temp <= expected_reg {
+                            self.alloc_register();
+                        }
+                        self.emit_instr(Instruction::ab(Opcode::SetLocal, expected_reg, elem_reg));
+                    }
                 }
                 self.emit_instr(Instruction::ab(Opcode::MakeArray, start_reg, elements.len() as u16));
                 Ok(start_reg)
             }
-            Expression::FunctionLiteral { parameters, body, return_type: _ } => {
+            ExpressionKind::FunctionLiteral { parameters, body, return_type: _ } => {
                 let name = format!("<lambda_{}>", self.program.functions.len());
                 self.compile_function(&name, parameters, body)?;
                 let reg = self.alloc_register();
@@ -1125,15 +1500,15 @@ impl Compiler {
                 self.emit_instr(Instruction::ab(Opcode::LoadConst, reg, idx));
                 Ok(reg)
             }
-            Expression::Match { value, arms } => self.compile_match(value, arms),
-            Expression::Range { start, end } => {
+            ExpressionKind::Match { value, arms, by_type, .. } => self.compile_match(value, arms, *by_type),
+            ExpressionKind::Range { start, end } => {
                 let start_reg = self.compile_expression(start)?;
                 let end_reg = self.compile_expression(end)?;
                 let result = self.alloc_register();
                 self.emit_instr(Instruction::new(Opcode::MakeRange, result, start_reg, end_reg));
                 Ok(result)
             }
-            Expression::MapLiteral(_) => {
+            ExpressionKind::MapLiteral(_) => {
                 let reg = self.alloc_register();
                 self.emit_instr(Instruction::a_only(Opcode::LoadNull, reg));
                 Ok(reg)
@@ -1141,94 +1516,101 @@ impl Compiler {
         }
     }
 
-    /// Defines `name` as a new local bound to `value_reg` in the *current*
-    /// scope, keeping `next_temp` in sync with the scope's own counter (see
+    /// Defines `name` as a new local slot in the *current* scope, keeping
+    /// `next_temp` in sync with the scope's own counter (see
     /// `compile_function`'s parameter registration for the same pattern) --
     /// `Scope::define` alone would silently let a later `alloc_register()`
     /// reuse the same slot, aliasing the binding.
-    fn bind_local(&mut self, name: &str, value_reg: u16) {
-        let slot = self.current_scope_mut().define(name);
+    fn reserve_local(&mut self, name: &str) -> u16 {
+        let slot = self.define_local(name);
         if self.next_temp <= slot {
             self.next_temp = slot + 1;
         }
         if self.next_temp > self.max_temp {
             self.max_temp = self.next_temp;
         }
-        if slot != value_reg {
-            self.emit_instr(Instruction::ab(Opcode::SetLocal, slot, value_reg));
-        }
+        slot
     }
 
     /// Real bytecode for `match` (Phase 2 ADTs: previously a `LoadNull` stub).
     /// Each arm's pattern is classified exactly as `hir.rs` classifies
     /// `HirPattern` (same nullary-variant-vs-binding disambiguation via
-    /// `known_nullary_variants`), then compiled to a runtime test:
-    /// - Wildcard/Binding: no test, always matches.
-    /// - Literal: `Eq` against the compiled literal.
-    /// - Variant: `Eq` on the tagged value's `__variant__` field, with an
-    ///   optional payload binding read from `__payload__`.
+    /// `known_nullary_variants`), then compiled to a runtime test by
+    /// `compile_pattern_test`. Every name the pattern binds gets its local slot
+    /// reserved up front, before any test temporaries are allocated, so a
+    /// binding deep inside a nested pattern can't clobber a register an outer
+    /// test is still reading from. A failed test jumps to the next arm.
+    /// A type switch (`match x as { ... }`, `by_type`) calls `typeof` once up
+    /// front and each arm compares that name against its own type.
     /// All arms write into the same `result_reg` (via `compile_block_as_value`)
     /// and jump to the end, giving `match` a real value in expression position.
-    fn compile_match(&mut self, value: &Expression<'_>, arms: &[(Expression<'_>, &Statement<'_>)]) -> Result<u16, String> {
+    fn compile_match(&mut self, value: &Expression<'_>, arms: &[(Expression<'_>, &Statement<'_>)], by_type: bool) -> Result<u16, String> {
         let value_reg = self.compile_expression(value)?;
         let result_reg = self.alloc_register();
         self.emit_instr(Instruction::a_only(Opcode::LoadNull, result_reg));
 
+        let type_reg = if by_type {
+            let name_idx = self.current_fn().add_constant(Constant::String("typeof".to_string()));
+            let call_reg = self.alloc_register();
+            self.emit_instr(Instruction::ab(Opcode::GetGlobal, call_reg, name_idx));
+            let arg_reg = self.alloc_register();
+            self.emit_instr(Instruction::ab(Opcode::SetLocal, arg_reg, value_reg));
+            self.emit_instr(Instruction::ab(Opcode::Call, call_reg, 1));
+            Some(call_reg)
+        } else {
+            None
+        };
+
+        // Dense leading integer arms dispatch through a jump table straight
+        // to their bodies; anything the table doesn't cover (a hole, an
+        // out-of-range or non-int value) falls into the arm-by-arm tests.
+        let table = if by_type { None } else { dense_int_arms(arms) };
+        let mut table_entries = None;
+        if let Some((low, slots)) = &table {
+            let low_idx = self.current_fn().add_constant(Constant::Integer(*low));
+            self.emit_instr(Instruction::new(Opcode::JumpTable, value_reg, low_idx, slots.len() as u16));
+            let first = self.current_fn().instructions.len();
+            for _ in slots {
+                self.emit_instr(Instruction::a_only(Opcode::Jump, 0));
+            }
+            table_entries = Some(first);
+        }
+        let chain_start = self.current_fn().instructions.len();
+        let mut body_starts = Vec::with_capacity(arms.len());
+
         let mut end_jumps = Vec::new();
 
         for (pattern, body) in arms {
-            use crate::pattern::ArmPattern;
-            let classified = crate::pattern::classify_pattern(pattern, |n| self.known_nullary_variants.contains(n));
-
-            match classified {
-                ArmPattern::Wildcard => {
-                    self.compile_block_as_value(body, result_reg)?;
-                    end_jumps.push(self.emit_instr(Instruction::a_only(Opcode::Jump, 0)));
-                }
-                ArmPattern::Binding(name) => {
-                    self.scopes.push(Scope::new(self.next_temp));
-                    self.bind_local(&name, value_reg);
-                    self.compile_block_as_value(body, result_reg)?;
-                    self.scopes.pop();
-                    end_jumps.push(self.emit_instr(Instruction::a_only(Opcode::Jump, 0)));
-                }
-                ArmPattern::Literal(lit_expr) => {
-                    let lit_reg = self.compile_expression(lit_expr)?;
-                    let cond_reg = self.alloc_register();
-                    self.emit_instr(Instruction::new(Opcode::Eq, cond_reg, value_reg, lit_reg));
-                    let jump_next = self.emit_instr(Instruction::ab(Opcode::JumpIfFalse, 0, cond_reg));
+            let classified = crate::pattern::classify_arm(pattern, by_type, |n| self.known_nullary_variants.contains(n));
 
-                    self.compile_block_as_value(body, result_reg)?;
-                    end_jumps.push(self.emit_instr(Instruction::a_only(Opcode::Jump, 0)));
+            self.scopes.push(Scope::new(self.next_temp));
+            for name in classified.bindings() {
+                self.reserve_local(&name);
+            }
 
-                    let next_pos = self.current_fn().instructions.len();
-                    self.current_fn().instructions[jump_next].a = next_pos as u16;
-                }
-                ArmPattern::Variant { name, binding } => {
-                    let variant_key = self.current_fn().add_constant(Constant::String("__variant__".to_string()));
-                    let tag_reg = self.alloc_register();
-                    self.emit_instr(Instruction::new(Opcode::GetMember, tag_reg, value_reg, variant_key));
-                    let name_idx = self.current_fn().add_constant(Constant::String(name));
+            let mut fail_jumps = Vec::new();
+            match (&classified, type_reg) {
+                (crate::pattern::ArmPattern::Type { name, binding }, Some(type_reg)) => {
+                    let name_idx = self.current_fn().add_constant(Constant::String(
+                        crate::pattern::runtime_type_name(name).to_string(),
+                    ));
                     let name_reg = self.alloc_register();
                     self.emit_instr(Instruction::ab(Opcode::LoadConst, name_reg, name_idx));
-                    let cond_reg = self.alloc_register();
-                    self.emit_instr(Instruction::new(Opcode::Eq, cond_reg, tag_reg, name_reg));
-                    let jump_next = self.emit_instr(Instruction::ab(Opcode::JumpIfFalse, 0, cond_reg));
-
-                    self.scopes.push(Scope::new(self.next_temp));
+                    self.emit_compare_test(Opcode::Eq, type_reg, name_reg, &mut fail_jumps);
                     if let Some(bname) = binding {
-                        let payload_key = self.current_fn().add_constant(Constant::String("__payload__".to_string()));
-                        let payload_reg = self.alloc_register();
-                        self.emit_instr(Instruction::new(Opcode::GetMember, payload_reg, value_reg, payload_key));
-                        self.bind_local(&bname, payload_reg);
+                        self.compile_pattern_test(&crate::pattern::ArmPattern::Binding(bname.clone()), value_reg, &mut fail_jumps)?;
                     }
-                    self.compile_block_as_value(body, result_reg)?;
-                    self.scopes.pop();
-
-                    end_jumps.push(self.emit_instr(Instruction::a_only(Opcode::Jump, 0)));
-                    let next_pos = self.current_fn().instructions.len();
-                    self.current_fn().instructions[jump_next].a = next_pos as u16;
                 }
+                _ => self.compile_pattern_test(&classified, value_reg, &mut fail_jumps)?,
+            }
+            body_starts.push(self.current_fn().instructions.len());
+            self.compile_block_as_value(body, result_reg)?;
+            self.scopes.pop();
+
+            end_jumps.push(self.emit_instr(Instruction::a_only(Opcode::Jump, 0)));
+            let next_pos = self.current_fn().instructions.len();
+            for idx in fail_jumps {
+                self.current_fn().instructions[idx].a = next_pos as u16;
             }
         }
 
@@ -1236,9 +1618,118 @@ impl Compiler {
         for idx in end_jumps {
             self.current_fn().instructions[idx].a = end_pos as u16;
         }
+        if let (Some((_, slots)), Some(first)) = (table, table_entries) {
+            for (i, slot) in slots.iter().enumerate() {
+                let target = slot.map_or(chain_start, |arm| body_starts[arm]);
+                self.current_fn().instructions[first + i].a = target as u16;
+            }
+        }
 
         Ok(result_reg)
     }
+
+    /// Emits the runtime test for one (possibly nested) pattern against the
+    /// value in `value_reg`, pushing a `JumpIfFalse` index onto `fail_jumps`
+    /// for every check that can fail:
+    /// - Wildcard: no test, always matches.
+    /// - Binding: no test; copies the value into the slot reserved by `compile_match`.
+    /// - Literal: `Eq` against the compiled literal.
+    /// - Range: `Gte` the start and `Lt` the end (half-open, like `MakeRange`).
+    /// - Variant: `Eq` on the tagged value's `__variant__` field, then the
+    ///   payload pattern against `__payload__`.
+    /// - Struct: `Eq` on `__class__`, then each listed field's pattern.
+    fn compile_pattern_test(&mut self, pattern: &crate::pattern::ArmPattern<'_>, value_reg: u16, fail_jumps: &mut Vec<usize>) -> Result<(), String> {
+        use crate::pattern::ArmPattern;
+        match pattern {
+            ArmPattern::Wildcard => {}
+            ArmPattern::Binding(name) => {
+                let slot = self.current_scope_mut().locals.get(name).map(|info| info.reg)
+                    .ok_or_else(|| format!("Pattern binding '{}' has no reserved slot", name))?;
+                if slot != value_reg {
+                    self.emit_instr(Instruction::ab(Opcode::SetLocal, slot, value_reg));
+                }
+            }
+            ArmPattern::Literal(lit_expr) => {
+                let lit_reg = self.compile_expression(lit_expr)?;
+                self.emit_compare_test(Opcode::Eq, value_reg, lit_reg, fail_jumps);
+            }
+            ArmPattern::Range { start, end } => {
+                let start_reg = self.compile_expression(start)?;
+                self.emit_compare_test(Opcode::Gte, value_reg, start_reg, fail_jumps);
+                let end_reg = self.compile_expression(end)?;
+                self.emit_compare_test(Opcode::Lt, value_reg, end_reg, fail_jumps);
+            }
+            ArmPattern::Variant { name, payload } => {
+                self.emit_tag_test(value_reg, "__variant__", name, fail_jumps);
+                if let Some(payload) = payload {
+                    let payload_key = self.current_fn().add_constant(Constant::String("__payload__".to_string()));
+                    let payload_reg = self.alloc_register();
+                    self.emit_instr(Instruction::new(Opcode::GetMember, payload_reg, value_reg, payload_key));
+                    self.compile_pattern_test(payload, payload_reg, fail_jumps)?;
+                }
+            }
+            ArmPattern::Type { .. } => {
+                return Err("Type-switch arms are only allowed directly in `match ... as { }`".to_string());
+            }
+            ArmPattern::Struct { name, fields } => {
+                self.emit_tag_test(value_reg, "__class__", name, fail_jumps);
+                for (field, sub) in fields {
+                    let field_key = self.current_fn().add_constant(Constant::String(field.clone()));
+                    let field_reg = self.alloc_register();
+                    self.emit_instr(Instruction::new(Opcode::GetMember, field_reg, value_reg, field_key));
+                    self.compile_pattern_test(sub, field_reg, fail_jumps)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// `cond = left <op> right; if !cond goto <patched later>`.
+    fn emit_compare_test(&mut self, op: Opcode, left: u16, right: u16, fail_jumps: &mut Vec<usize>) {
+        let cond_reg = self.alloc_register();
+        self.emit_instr(Instruction::new(op, cond_reg, left, right));
+        fail_jumps.push(self.emit_instr(Instruction::ab(Opcode::JumpIfFalse, 0, cond_reg)));
+    }
+
+    /// Tests that the hidden tag field `key` (`__variant__`/`__class__`) of the
+    /// map in `value_reg` equals `expected`.
+    fn emit_tag_test(&mut self, value_reg: u16, key: &str, expected: &str, fail_jumps: &mut Vec<usize>) {
+        let key_idx = self.current_fn().add_constant(Constant::String(key.to_string()));
+        let tag_reg = self.alloc_register();
+        self.emit_instr(Instruction::new(Opcode::GetMember, tag_reg, value_reg, key_idx));
+        let name_idx = self.current_fn().add_constant(Constant::String(expected.to_string()));
+        let name_reg = self.alloc_register();
+        self.emit_instr(Instruction::ab(Opcode::LoadConst, name_reg, name_idx));
+        self.emit_compare_test(Opcode::Eq, tag_reg, name_reg, fail_jumps);
+    }
+}
+
+/// A `match` needs at least this many leading integer arms for a jump table.
+const MIN_JUMP_TABLE_ARMS: usize = 4;
+
+/// When a `match` starts with at least `MIN_JUMP_TABLE_ARMS` integer-literal
+/// arms whose values are dense (the span is at most twice their number),
+/// the lowest value and, for each value in the span, the first arm matching
+/// it (`None` for holes).
+fn dense_int_arms(arms: &[(Expression<'_>, &Statement<'_>)]) -> Option<(i64, Vec<Option<usize>>)> {
+    let values: Vec<i64> = arms.iter()
+        .map_while(|(pattern, _)| match &pattern.kind {
+            ExpressionKind::Integer(n) => Some(*n),
+            ExpressionKind::Prefix { operator: "-", right: Expression { kind: ExpressionKind::Integer(n), .. } } => n.checked_neg(),
+            _ => None,
+        })
+        .collect();
+    let distinct: std::collections::HashSet<i64> = values.iter().copied().collect();
+    let (low, high) = (*values.iter().min()?, *values.iter().max()?);
+    let span = usize::try_from(high.checked_sub(low)?).ok()?.checked_add(1)?;
+    if distinct.len() < MIN_JUMP_TABLE_ARMS || span > 2 * distinct.len() {
+        return None;
+    }
+    let mut slots = vec![None; span];
+    for (arm, value) in values.iter().enumerate().rev() {
+        slots[(value - low) as usize] = Some(arm);
+    }
+    Some((low, slots))
 }
 
 #[cfg(test)]
@@ -1253,7 +1744,7 @@ mod tests {
         let ast = parser.parse_program();
         assert!(parser.errors.is_empty(), "parse errors: {:?}", parser.errors);
         let mut compiler = Compiler::new();
-        compiler.compile(&ast.statements, None).map(|p| p.clone())
+        compiler.compile(&ast.statements, None).map(|p| p.clone()).map_err(|mut errs| errs.remove(0).message)
     }
 
     fn compile_source(source: &str) -> CompiledProgram {
@@ -1265,10 +1756,41 @@ mod tests {
         let program = compile_source("let r = 0..5;");
         assert!(
             program.main.instructions.iter().any(|i| i.opcode == Opcode::MakeRange),
-            "Expression::Range should emit MakeRange, not fall through to the LoadNull stub"
+            "ExpressionKind::Range should emit MakeRange, not fall through to the LoadNull stub"
         );
     }
 
+    #[test]
+    fn test_dense_integer_match_compiles_to_a_jump_table() {
+        let jump_tables = |src: &str| compile_source(src).main.instructions.iter().filter(|i| i.opcode == Opcode::JumpTable).count();
+        assert_eq!(jump_tables("let x = 3
+let y = match x {
+    1 => 10,
+    2 => 20,
+    4 => 40,
+    5 => 50,
+    _ => 0
+}
+"), 1);
+        assert_eq!(jump_tables("let x = 3
+let y = match x {
+    1 => 10,
+    2 => 20,
+    _ => 0
+}
+"), 0, "too few arms");
+        assert_eq!(jump_tables("let x = 3
+let y = match x {
+    1 => 10,
+    20 => 20,
+    40 => 40,
+    90 => 50,
+    _ => 0
+}
+"), 0, "too sparse");
+        assert_eq!(dense_int_arms(&[]), None);
+    }
+
     #[test]
     fn test_break_emits_jump_patched_to_loop_exit() {
         let program = compile_source("mut i = 0\nwhile i < 10 {\n    break\n}\n");
@@ -1311,6 +1833,102 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_enum_from_str_is_derived_and_called_as_flattened_global() {
+        let program = compile_source("enum Color { Red, Green }\nlet c = Color.from_str(\"Red\")\n");
+        assert!(
+            program.functions.iter().any(|f| f.name == "Color::from_str" && f.arity == 1),
+            "every user enum should get a derived Color::from_str function"
+        );
+        assert!(
+            program.main.constants.contains(&Constant::String("Color::from_str".to_string()))
+                && !program.main.constants.contains(&Constant::String("Color.from_str".to_string())),
+            "Color.from_str(...) should resolve to the flattened global, not a builtin module call"
+        );
+    }
+
+    #[test]
+    fn test_range_pattern_tests_both_bounds() {
+        let program = compile_source("let n = 5\nlet r = match n {\n    0..10 => 1,\n    _ => 2\n}\n");
+        let ops: Vec<Opcode> = program.main.instructions.iter().map(|i| i.opcode).collect();
+        let gte = ops.iter().position(|op| *op == Opcode::Gte).expect("range arm should test its start with Gte");
+        let lt = ops.iter().position(|op| *op == Opcode::Lt).expect("range arm should test its end with Lt");
+        assert!(gte < lt, "the start bound is checked before the end bound");
+        assert_eq!(ops.iter().filter(|op| **op == Opcode::JumpIfFalse).count(), 2, "each bound can fail the arm");
+    }
+
+    #[test]
+    fn test_nested_variant_pattern_checks_inner_tag_and_reserves_binding() {
+        let program = compile_source("let o = Some(Some(4))\nlet r = match o {\n    Some(Some(v)) => v,\n    _ => 0\n}\n");
+        let instrs = &program.main.instructions;
+        let variant_key = program.main.constants.iter()
+            .position(|c| *c == Constant::String("__variant__".to_string()))
+            .expect("variant patterns read the __variant__ tag") as u16;
+        let tag_reads = instrs.iter().filter(|i| i.opcode == Opcode::GetMember && i.c == variant_key).count();
+        assert_eq!(tag_reads, 2, "outer and inner Some(..) should each test their own tag");
+
+        // `v` is bound after both tag tests, into a slot reserved below every
+        // temporary those tests allocated.
+        let last_test = instrs.iter().rposition(|i| i.opcode == Opcode::JumpIfFalse).expect("pattern tests emit JumpIfFalse");
+        let set = instrs[last_test..].iter().find(|i| i.opcode == Opcode::SetLocal).expect("`v` should be bound with SetLocal");
+        assert!(set.a < set.b, "binding slot must be reserved before the test temporaries");
+    }
+
+    #[test]
+    fn test_type_switch_calls_typeof_once_and_maps_str() {
+        let program = compile_source("let v = \"a\"\nlet r = match v as {\n    int(n) => n,\n    str(s) => 1,\n    _ => 0\n}\n");
+        let main = &program.main;
+        let typeof_name = main.constants.iter()
+            .position(|c| *c == Constant::String("typeof".to_string()))
+            .expect("type switch resolves typeof") as u16;
+        let lookups = main.instructions.iter().filter(|i| i.opcode == Opcode::GetGlobal && i.b == typeof_name).count();
+        assert_eq!(lookups, 1, "the scrutinee's type is computed once for all arms");
+        assert!(main.constants.contains(&Constant::String("string".to_string())), "`str` arms compare against the runtime name `string`");
+        assert_eq!(main.instructions.iter().filter(|i| i.opcode == Opcode::Eq).count(), 2);
+    }
+
+    #[test]
+    fn test_static_members_use_flattened_globals() {
+        let program = compile_source("class Config {\n    let MAX: int = 10\n    fn make(n: int) -> int { return n }\n}\nlet a = Config.make(Config.MAX)\n");
+        let main = &program.main;
+        let const_idx = |name: &str| main.constants.iter()
+            .position(|c| *c == Constant::String(name.to_string()))
+            .unwrap_or_else(|| panic!("missing constant {}", name)) as u16;
+        let (max, make) = (const_idx("Config::MAX"), const_idx("Config::make"));
+        assert!(main.instructions.iter().any(|i| i.opcode == Opcode::SetGlobal && i.a == max), "class constant is stored as a global");
+        assert!(main.instructions.iter().any(|i| i.opcode == Opcode::GetGlobal && i.b == max), "`Config.MAX` reads the global");
+        assert!(main.instructions.iter().any(|i| i.opcode == Opcode::GetGlobal && i.b == make), "`Config.make` calls the flattened function");
+        assert!(!main.instructions.iter().any(|i| i.opcode == Opcode::LoadMethod), "no instance is involved");
+    }
+
+    #[test]
+    fn test_labeled_break_patches_to_outer_loop_exit() {
+        let program = compile_source("'outer: while true {\n    while true {\n        break 'outer\n    }\n}\n");
+        let instrs = &program.main.instructions;
+        let halt_idx = instrs.len() - 1;
+        assert_eq!(instrs[halt_idx].opcode, Opcode::Halt);
+        let first_jump = instrs.iter().find(|i| i.opcode == Opcode::Jump).expect("break emits a Jump");
+        assert_eq!(first_jump.a as usize, halt_idx, "break 'outer should leave both loops, not just the inner one");
+    }
+
+    #[test]
+    fn test_unknown_loop_label_is_compile_error() {
+        let err = try_compile_source("while true {\n    continue 'missing\n}\n").unwrap_err();
+        assert!(err.contains("'missing"), "error should name the unknown label: {}", err);
+    }
+
+    #[test]
+    fn test_compile_errors_point_at_the_innermost_statement() {
+        let arena = Bump::new();
+        let source = "fn f() {\n    let x = 1\n    continue\n}\n";
+        let mut parser = kinetix_language::parser::Parser::new(kinetix_language::lexer::Lexer::new(source), &arena);
+        let ast = parser.parse_program();
+        let errs = Compiler::new().compile(&ast.statements, None).unwrap_err();
+        assert_eq!(errs[0].code, codes::CODEGEN);
+        assert_eq!(errs[0].message, "'continue' used outside of a loop");
+        assert_eq!(errs[0].span().map(|s| (s.line, s.column)), Some((3, 5)));
+    }
+
     #[test]
     fn test_break_outside_loop_is_compile_error() {
         let result = try_compile_source("break\n");
@@ -1322,4 +1940,42 @@ mod tests {
         let result = try_compile_source("continue\n");
         assert!(result.is_err(), "bare top-level `continue` should be a compile error");
     }
+
+    #[test]
+    fn test_function_past_operand_limit_is_compile_error() {
+        // Each assignment is a load and a move, so the loop's exit target
+        // runs out before anything else does.
+        let body = "        x = 1\n".repeat(MAX_OPERAND / 2 + 1);
+        let source = format!("fn big() {{\n    let x = 0\n    while x < 1 {{\n{}    }}\n}}\n", body);
+        let err = try_compile_source(&source).unwrap_err();
+        assert!(err.starts_with("Function 'big' needs") && err.contains("instructions"), "{}", err);
+    }
+
+    #[test]
+    fn test_class_and_impl_methods_are_recorded_as_metadata() {
+        let program = compile_source("class Base { }\nclass Point : Base {\n    x: int\n    y: int\n    fn sum(self) -> int { return self.x + self.y }\n}\nimpl Point {\n    fn twice(self) -> int { return self.sum() * 2 }\n}\n");
+        let point = program.class("Point").expect("Point has metadata");
+        assert_eq!(point.parent.as_deref(), Some("Base"));
+        assert_eq!(point.fields, vec!["x", "y"]);
+        let names: Vec<&str> = point.methods.iter().map(|(m, &idx)| { assert_eq!(program.functions[idx].name, format!("Point::{}", m)); m.as_str() }).collect();
+        assert_eq!(names, vec!["sum", "twice"]);
+        assert_eq!(program.vtable["Point"]["twice"], point.methods["twice"]);
+    }
+
+    #[test]
+    fn test_scope_close_is_deferred_and_keeps_the_frame() {
+        let arena = Bump::new();
+        let source = "fn ping() -> int {\n    let r = net.tcp.connect(\"h\", 1)\n    return len(\"x\")\n}\n";
+        let ast = kinetix_language::parser::Parser::new(kinetix_language::lexer::Lexer::new(source), &arena).parse_program();
+        let mut compiler = Compiler::new();
+        compiler.close_on_return(vec![crate::resource_lint::ScopeClose { line: 2, name: "r".to_string(), closer: "net.tcp.close", ok: true }]);
+        let program = compiler.compile(&ast.statements, None).unwrap();
+        let ping = program.functions.iter().find(|f| f.name == "ping").unwrap();
+        for name in ["system.defer", "net.tcp.close", "ok"] {
+            assert!(ping.constants.contains(&Constant::String(name.to_string())), "{:?}", ping.constants);
+        }
+        assert!(ping.instructions.iter().all(|i| i.opcode != Opcode::TailCall));
+        let plain = compile_source(source);
+        assert!(plain.functions.iter().find(|f| f.name == "ping").unwrap().instructions.iter().any(|i| i.opcode == Opcode::TailCall));
+    }
 }
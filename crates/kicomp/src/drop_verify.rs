@@ -21,13 +21,17 @@
 
 use crate::mir::{MirProgram, MirFunction, BasicBlockData, StatementKind, TerminatorKind, RValue, Operand, LocalId};
 use std::collections::HashSet;
+use rayon::prelude::*;
 
+/// Functions are verified in parallel; the error reported is the first one
+/// in program order, so the result doesn't depend on scheduling.
 pub fn verify(program: &MirProgram) -> Result<(), String> {
-    for func in &program.functions {
-        verify_function(func)?;
-    }
-    verify_function(&program.main_block)?;
-    Ok(())
+    let results: Vec<Result<(), String>> = program.functions
+        .par_iter()
+        .chain(rayon::iter::once(&program.main_block))
+        .map(verify_function)
+        .collect();
+    results.into_iter().collect()
 }
 
 fn successors(block: &BasicBlockData) -> Vec<usize> {
@@ -156,16 +160,21 @@ fn verify_function(func: &MirFunction) -> Result<(), String> {
 mod tests {
     use super::*;
     use crate::mir::{BasicBlockData, LocalId, MirStatement, Place};
+    use kinetix_language::span::Span;
+
+    fn at(line: usize) -> Span {
+        Span { line, ..Span::default() }
+    }
 
     #[test]
     fn test_double_drop_caught() {
         let stmt1 = MirStatement {
             kind: StatementKind::Drop(Place { local: LocalId(1) }),
-            line: 0,
+            span: at(0),
         };
         let stmt2 = MirStatement {
             kind: StatementKind::Drop(Place { local: LocalId(1) }),
-            line: 1,
+            span: at(1),
         };
         
         let block = BasicBlockData {
@@ -193,12 +202,12 @@ mod tests {
         use crate::mir::{BasicBlock, Terminator};
 
         let block0 = BasicBlockData {
-            statements: vec![MirStatement { kind: StatementKind::Drop(Place { local: LocalId(0) }), line: 0 }],
-            terminator: Some(Terminator { kind: TerminatorKind::Goto(BasicBlock(1)), line: 0 }),
+            statements: vec![MirStatement { kind: StatementKind::Drop(Place { local: LocalId(0) }), span: at(0) }],
+            terminator: Some(Terminator { kind: TerminatorKind::Goto(BasicBlock(1)), span: at(0) }),
         };
         let block1 = BasicBlockData {
-            statements: vec![MirStatement { kind: StatementKind::Drop(Place { local: LocalId(0) }), line: 1 }],
-            terminator: Some(Terminator { kind: TerminatorKind::Return(None), line: 1 }),
+            statements: vec![MirStatement { kind: StatementKind::Drop(Place { local: LocalId(0) }), span: at(1) }],
+            terminator: Some(Terminator { kind: TerminatorKind::Return(None), span: at(1) }),
         };
 
         let f = MirFunction {
@@ -227,20 +236,20 @@ mod tests {
                     then_block: BasicBlock(1),
                     else_block: BasicBlock(2),
                 },
-                line: 0,
+                span: at(0),
             }),
         };
         let then_block = BasicBlockData {
-            statements: vec![MirStatement { kind: StatementKind::Drop(Place { local: LocalId(0) }), line: 1 }],
-            terminator: Some(Terminator { kind: TerminatorKind::Goto(BasicBlock(3)), line: 1 }),
+            statements: vec![MirStatement { kind: StatementKind::Drop(Place { local: LocalId(0) }), span: at(1) }],
+            terminator: Some(Terminator { kind: TerminatorKind::Goto(BasicBlock(3)), span: at(1) }),
         };
         let else_block = BasicBlockData {
             statements: vec![],
-            terminator: Some(Terminator { kind: TerminatorKind::Goto(BasicBlock(3)), line: 2 }),
+            terminator: Some(Terminator { kind: TerminatorKind::Goto(BasicBlock(3)), span: at(2) }),
         };
         let merge_block = BasicBlockData {
             statements: vec![],
-            terminator: Some(Terminator { kind: TerminatorKind::Return(None), line: 3 }),
+            terminator: Some(Terminator { kind: TerminatorKind::Return(None), span: at(3) }),
         };
 
         let f = MirFunction {
@@ -1,17 +1,57 @@
 use crate::types::Type;
 use crate::hir::HirPattern;
 use crate::symbol::SymbolTable;
+use kinetix_language::diagnostic::{codes, Diagnostic};
+use kinetix_language::span::Span;
+
+/// Values a `match` leaves out.
+#[derive(Debug, PartialEq)]
+pub struct Gap {
+    pub message: String,
+    /// Patterns for them, as arms would write them (`Green`, `Some(false)`),
+    /// when they can be listed; empty for ints, strings and the like, where
+    /// only a `_` arm covers the rest.
+    pub missing: Vec<String>,
+    /// Whether a `_` arm would fix it (not for a type that can't be matched).
+    pub wildcard: bool,
+}
+
+impl Gap {
+    fn new(message: impl Into<String>) -> Self {
+        Gap { message: message.into(), missing: Vec::new(), wildcard: true }
+    }
+
+    fn missing(message: &str, missing: Vec<String>) -> Self {
+        Gap { message: format!("{}: {}", message, missing.join(", ")), missing, wildcard: true }
+    }
+}
 
 /// Validates that a list of match arms exhaustively covers the structural space of `match_ty`.
-/// For Kinetix Phase 2, we implement a 1D mapping matrix to evaluate Enum variant coverage
-/// and Primitive infinite domain coverage.
 pub fn check_exhaustiveness(match_ty: &Type, arms: &[HirPattern], symbols: &SymbolTable) -> Result<(), String> {
+    find_gap(match_ty, arms, symbols).map_err(|gap| gap.message)
+}
+
+/// The values `arms` leave out of `match_ty`, if any. Enums are checked
+/// variant by variant, recursing into payload patterns, and bools by value;
+/// ints, floats and strings are infinite domains that only `_` or a binding
+/// covers.
+pub fn find_gap(match_ty: &Type, arms: &[HirPattern], symbols: &SymbolTable) -> Result<(), Gap> {
     // 1. Wildcard (`_`) or unconditional binding (`x`) provides immediate exhaustive coverage.
-    for arm in arms {
-        match arm {
-            HirPattern::Wildcard | HirPattern::Binding(_) => return Ok(()),
-            _ => {}
+    if arms.iter().any(HirPattern::is_irrefutable) {
+        return Ok(());
+    }
+
+    // 2. A type switch (`match x as { ... }`) is only exhaustive without `_`
+    // when the scrutinee's static type is known and one arm names it.
+    if arms.iter().any(|arm| matches!(arm, HirPattern::Type { .. })) {
+        let covered = arms.iter().any(|arm| match arm {
+            HirPattern::Type { name, .. } => type_arm_covers(name, match_ty),
+            _ => false,
+        });
+        if covered {
+            return Ok(());
         }
+        return Err(Gap::new("Non-exhaustive type switch. Add a `_` arm to handle values of other types"));
     }
 
     match match_ty {
@@ -25,162 +65,323 @@ pub fn check_exhaustiveness(match_ty: &Type, arms: &[HirPattern], symbols: &Symb
                     }
                 }
             }
-            if !has_true { return Err("Missing coverage for: true".to_string()); }
-            if !has_false { return Err("Missing coverage for: false".to_string()); }
-            Ok(())
+            let missing: Vec<String> = [(has_true, "true"), (has_false, "false")].iter()
+                .filter(|(has, _)| !has)
+                .map(|(_, b)| b.to_string())
+                .collect();
+            if missing.is_empty() { Ok(()) } else { Err(Gap::missing("Missing coverage for", missing)) }
         }
-        Type::Custom { name, .. } => {
+        Type::Custom { name, args } => {
             // Any registered enum (built-in Option/Result or user-declared via
             // `enum`) is checked structurally against its variant list.
             let Some(enum_def) = symbols.enums.get(name) else {
-                // For custom user structs/classes (not enums), a wildcard or binding is required.
-                return Err(format!("Non-exhaustive match. Type '{}' requires a catch-all bound", name));
+                // For custom user structs/classes (not enums), a wildcard, binding,
+                // or a destructuring pattern with only irrefutable fields is required.
+                let destructured = arms.iter().any(|arm| matches!(
+                    arm,
+                    HirPattern::Struct { name: sname, fields } if sname == name && fields.iter().all(|(_, p)| p.is_irrefutable())
+                ));
+                if destructured {
+                    return Ok(());
+                }
+                return Err(Gap::new(format!("Non-exhaustive match. Type '{}' requires a catch-all bound", name)));
             };
 
-            let mut covered_variants = std::collections::HashSet::new();
-            for arm in arms {
-                if let HirPattern::Variant { name: var_name, .. } = arm {
-                    covered_variants.insert(var_name.clone());
+            // Generic payloads (`Some(T)`, `Err(E)`) are type variables; map
+            // each one, in order of first appearance, to the matched type's args.
+            let mut generic_vars: Vec<&Type> = Vec::new();
+            for (_, payload) in &enum_def.variants {
+                match payload {
+                    Some(ty @ Type::Var(_)) if !generic_vars.contains(&ty) => generic_vars.push(ty),
+                    _ => {}
                 }
             }
 
-            for (vname, _) in &enum_def.variants {
-                if !covered_variants.contains(vname) {
-                    return Err(format!("Missing coverage for variant: {}", vname));
+            let mut missing = Vec::new();
+            for (vname, payload_ty) in &enum_def.variants {
+                let mut payloads = Vec::new();
+                let mut covered = false;
+                for arm in arms {
+                    if let HirPattern::Variant { name: var_name, payload } = arm {
+                        if var_name != vname {
+                            continue;
+                        }
+                        match payload {
+                            None => covered = true,
+                            Some(p) if p.is_irrefutable() => covered = true,
+                            Some(p) => payloads.push((**p).clone()),
+                        }
+                    }
+                }
+                if covered {
+                    continue;
                 }
+                // Only refutable payload patterns (`Some(0)`, `Ok(Red)`) for this
+                // variant: covered only if they are exhaustive for the payload type.
+                let payload_ty = payload_ty.as_ref().map(|ty| {
+                    generic_vars.iter().position(|v| *v == ty)
+                        .and_then(|i| args.get(i))
+                        .unwrap_or(ty)
+                });
+                match payload_ty {
+                    Some(ty) if !payloads.is_empty() => match find_gap(ty, &payloads, symbols) {
+                        Ok(()) => {}
+                        Err(gap) if gap.missing.is_empty() => missing.push(format!("{}(_)", vname)),
+                        Err(gap) => missing.extend(gap.missing.iter().map(|p| format!("{}({})", vname, p))),
+                    },
+                    Some(_) => missing.push(format!("{}(_)", vname)),
+                    None => missing.push(vname.clone()),
+                }
+            }
+            match missing.len() {
+                0 => Ok(()),
+                1 => Err(Gap::missing("Missing coverage for variant", missing)),
+                _ => Err(Gap::missing("Missing coverage for variants", missing)),
             }
-            Ok(())
         }
-        Type::Int | Type::Float | Type::Str => {
+        Type::Int => {
+            // The int domain is unbounded in practice, so literals and ranges can
+            // never cover it alone -- but we can point at the first value the
+            // arms leave out, which is far more useful than a generic message.
+            Err(Gap::new(format!(
+                "Non-exhaustive match. {} not covered. Add a `_` arm to cover all cases for type {}",
+                describe_int_gap(arms), match_ty
+            )))
+        }
+        Type::Float | Type::Str => {
             // Infinite domains cannot be exhaustively matched by structural literals alone.
-            Err(format!("Non-exhaustive match. Add a `_` arm to cover all cases for type {}", match_ty))
+            Err(Gap::new(format!("Non-exhaustive match. Add a `_` arm to cover all cases for type {}", match_ty)))
         }
-        _ => Err(format!("Cannot match against type {:?}", match_ty)),
+        _ => Err(Gap { wildcard: false, ..Gap::new(format!("Cannot match against type {}", match_ty)) }),
     }
 }
 
-pub fn check_program_exhaustiveness(
-    hir: &crate::hir::HirProgram,
-    symbols: &SymbolTable,
-    sub: &crate::types::Substitution,
-) -> Result<(), String> {
-    for stmt in &hir.statements {
-        check_statement(stmt, symbols, sub)?;
+/// Whether a type-switch arm naming `name` matches every value of `ty`.
+fn type_arm_covers(name: &str, ty: &Type) -> bool {
+    match (crate::types::parse_type_hint(name), ty) {
+        (Type::Int, Type::Int) | (Type::Float, Type::Float) | (Type::Str, Type::Str) | (Type::Bool, Type::Bool) => true,
+        (Type::Custom { name: arm, .. }, Type::Custom { name: scrutinee, .. }) => arm == *scrutinee,
+        _ => false,
     }
-    Ok(())
 }
 
-fn check_statement(
-    stmt: &crate::hir::HirStatement,
-    symbols: &SymbolTable,
-    sub: &crate::types::Substitution,
-) -> Result<(), String> {
-    use crate::hir::HirStmtKind;
-    match &stmt.kind {
-        HirStmtKind::Let { value, .. } | HirStmtKind::Return { value: Some(value) } | HirStmtKind::State { value, .. } | HirStmtKind::Computed { value, .. } => {
-            check_expression(value, symbols, sub)?;
-        }
-        HirStmtKind::Effect { body, .. } => {
-            check_statement(body, symbols, sub)?;
-        }
-        HirStmtKind::Expression { expression } => {
-            check_expression(expression, symbols, sub)?;
-        }
-        HirStmtKind::Block { statements } => {
-            for s in statements {
-                check_statement(s, symbols, sub)?;
-            }
-        }
-        HirStmtKind::Function { body, .. } => {
-            check_statement(body, symbols, sub)?;
-        }
-        HirStmtKind::Class { methods, .. } => {
-            for m in methods {
-                check_statement(m, symbols, sub)?;
+/// Reads a constant integer out of a pattern bound (`3`, `-3`).
+fn const_int(expr: &crate::hir::HirExpression) -> Option<i64> {
+    use crate::hir::HirExprKind;
+    match &expr.kind {
+        HirExprKind::Integer(v) => Some(*v),
+        HirExprKind::Prefix { operator, right } if operator == "-" => const_int(right).and_then(i64::checked_neg),
+        _ => None,
+    }
+}
+
+/// Merges the integer literals and constant ranges among `arms` into
+/// half-open intervals and describes the first hole in their coverage:
+/// a gap between two intervals, else the values below the lowest one,
+/// else the values from the highest one upward.
+fn describe_int_gap(arms: &[HirPattern]) -> String {
+    let mut intervals: Vec<(i64, i64)> = Vec::new();
+    for arm in arms {
+        match arm {
+            HirPattern::Literal(lit) => {
+                if let Some(v) = const_int(lit) {
+                    intervals.push((v, v.saturating_add(1)));
+                }
             }
+            HirPattern::Range { start, end } => match (const_int(start), const_int(end)) {
+                (Some(lo), Some(hi)) if lo < hi => intervals.push((lo, hi)),
+                _ => {}
+            },
+            _ => {}
         }
-        HirStmtKind::While { condition, body } => {
-            check_expression(condition, symbols, sub)?;
-            check_statement(body, symbols, sub)?;
-        }
-        HirStmtKind::For { range, body, .. } => {
-            check_expression(range, symbols, sub)?;
-            check_statement(body, symbols, sub)?;
+    }
+    if intervals.is_empty() {
+        return "Integer values are".to_string();
+    }
+    intervals.sort();
+    let mut merged: Vec<(i64, i64)> = Vec::new();
+    for (lo, hi) in intervals {
+        match merged.last_mut() {
+            Some(last) if lo <= last.1 => last.1 = last.1.max(hi),
+            _ => merged.push((lo, hi)),
         }
-        _ => {}
     }
-    Ok(())
+    if let Some(w) = merged.windows(2).next() {
+        return format!("Integer values {}..{} are", w[0].1, w[1].0);
+    }
+    let (lo, hi) = merged[0];
+    if lo > i64::MIN {
+        format!("Integer values below {} are", lo)
+    } else {
+        format!("Integer values from {} upward are", hi)
+    }
 }
 
-fn check_expression(
-    expr: &crate::hir::HirExpression,
+/// Checks every `match` in `hir`. Each one that leaves values out is an
+/// error at the statement holding it, with fixes that add arms for the
+/// missing patterns, or a `_` arm, before its closing `}`.
+pub fn check_program_exhaustiveness(
+    hir: &crate::hir::HirProgram,
     symbols: &SymbolTable,
     sub: &crate::types::Substitution,
-) -> Result<(), String> {
-    use crate::hir::HirExprKind;
-    match &expr.kind {
-        HirExprKind::If { condition, consequence, alternative } => {
-            check_expression(condition, symbols, sub)?;
-            check_statement(consequence, symbols, sub)?;
-            if let Some(alt) = alternative {
-                check_statement(alt, symbols, sub)?;
+) -> Result<(), Vec<Diagnostic>> {
+    let mut checker = Checker { symbols, sub, errors: Vec::new() };
+    for stmt in &hir.statements {
+        checker.check_statement(stmt);
+    }
+    if checker.errors.is_empty() { Ok(()) } else { Err(checker.errors) }
+}
+
+struct Checker<'a> {
+    symbols: &'a SymbolTable,
+    sub: &'a crate::types::Substitution,
+    errors: Vec<Diagnostic>,
+}
+
+impl Checker<'_> {
+    fn check_statement(&mut self, stmt: &crate::hir::HirStatement) {
+        use crate::hir::HirStmtKind;
+        let at = if stmt.span.line > 0 { stmt.span } else { Span { line: stmt.line, column: 1, ..Span::default() } };
+        match &stmt.kind {
+            HirStmtKind::Let { value, .. } | HirStmtKind::Return { value: Some(value) } | HirStmtKind::State { value, .. } | HirStmtKind::Computed { value, .. } => {
+                self.check_expression(value, at);
             }
-        }
-        HirExprKind::Match { value, arms } => {
-            check_expression(value, symbols, sub)?;
-            let resolved_ty = sub.apply(&value.ty);
-            let patterns: Vec<HirPattern> = arms.iter().map(|(p, _)| p.clone()).collect();
-            check_exhaustiveness(&resolved_ty, &patterns, symbols)?;
-            
-            for (_, body) in arms {
-                check_statement(body, symbols, sub)?;
+            HirStmtKind::Effect { body, .. } => self.check_statement(body),
+            HirStmtKind::Expression { expression } => self.check_expression(expression, at),
+            HirStmtKind::Block { statements } => statements.iter().for_each(|s| self.check_statement(s)),
+            HirStmtKind::Function { body, .. } => self.check_statement(body),
+            HirStmtKind::Class { methods, .. } => methods.iter().for_each(|m| self.check_statement(m)),
+            HirStmtKind::While { condition, body, .. } => {
+                self.check_expression(condition, at);
+                self.check_statement(body);
             }
-        }
-        HirExprKind::Prefix { right, .. } => check_expression(right, symbols, sub)?,
-        HirExprKind::Infix { left, right, .. } => {
-            check_expression(left, symbols, sub)?;
-            check_expression(right, symbols, sub)?;
-        }
-        HirExprKind::Call { function, arguments } => {
-            check_expression(function, symbols, sub)?;
-            for arg in arguments {
-                check_expression(arg, symbols, sub)?;
+            HirStmtKind::For { range, body, .. } => {
+                self.check_expression(range, at);
+                self.check_statement(body);
             }
+            _ => {}
         }
-        HirExprKind::MethodCall { object, arguments, .. } => {
-            check_expression(object, symbols, sub)?;
-            for arg in arguments {
-                check_expression(arg, symbols, sub)?;
+    }
+
+    /// `at` is where the statement holding `expr` starts.
+    fn check_expression(&mut self, expr: &crate::hir::HirExpression, at: Span) {
+        use crate::hir::HirExprKind;
+        match &expr.kind {
+            HirExprKind::If { condition, consequence, alternative } => {
+                self.check_expression(condition, at);
+                self.check_statement(consequence);
+                if let Some(alt) = alternative {
+                    self.check_statement(alt);
+                }
             }
-        }
-        HirExprKind::FunctionLiteral { body, .. } => {
-            check_statement(body, symbols, sub)?;
-        }
-        HirExprKind::ArrayLiteral(elems) => {
-            for e in elems { check_expression(e, symbols, sub)?; }
-        }
-        HirExprKind::MapLiteral(pairs) => {
-            for (k, v) in pairs {
-                check_expression(k, symbols, sub)?;
-                check_expression(v, symbols, sub)?;
+            HirExprKind::Match { value, arms, end } => {
+                self.check_expression(value, at);
+                let resolved_ty = self.sub.apply(&value.ty);
+                let patterns: Vec<HirPattern> = arms.iter().map(|(p, _)| p.clone()).collect();
+                if let Err(gap) = find_gap(&resolved_ty, &patterns, self.symbols) {
+                    let inline = arms.last().is_none_or(|(_, body)| body.span.line == end.line);
+                    self.errors.push(non_exhaustive(gap, at, *end, inline));
+                }
+                for (_, body) in arms {
+                    self.check_statement(body);
+                }
             }
+            HirExprKind::Prefix { right, .. } => self.check_expression(right, at),
+            HirExprKind::Infix { left, right, .. } | HirExprKind::Index { left, index: right } | HirExprKind::Assign { target: left, value: right } | HirExprKind::Range { start: left, end: right } => {
+                self.check_expression(left, at);
+                self.check_expression(right, at);
+            }
+            HirExprKind::Call { function: object, arguments } | HirExprKind::MethodCall { object, arguments, .. } => {
+                self.check_expression(object, at);
+                arguments.iter().for_each(|arg| self.check_expression(arg, at));
+            }
+            HirExprKind::FunctionLiteral { body, .. } => self.check_statement(body),
+            HirExprKind::ArrayLiteral(elems) => elems.iter().for_each(|e| self.check_expression(e, at)),
+            HirExprKind::MapLiteral(pairs) => pairs.iter().for_each(|(k, v)| {
+                self.check_expression(k, at);
+                self.check_expression(v, at);
+            }),
+            HirExprKind::MemberAccess { object, .. } => self.check_expression(object, at),
+            _ => {} // primitive literals etc.
         }
-        HirExprKind::Index { left, index } => {
-            check_expression(left, symbols, sub)?;
-            check_expression(index, symbols, sub)?;
-        }
-        HirExprKind::MemberAccess { object, .. } => {
-            check_expression(object, symbols, sub)?;
-        }
-        HirExprKind::Assign { target, value } => {
-            check_expression(target, symbols, sub)?;
-            check_expression(value, symbols, sub)?;
-        }
-        HirExprKind::Range { start, end } => {
-            check_expression(start, symbols, sub)?;
-            check_expression(end, symbols, sub)?;
-        }
-        _ => {} // primitive literals etc.
     }
-    Ok(())
+}
+
+/// The error for a `match` in the statement starting at `at`, whose
+/// closing `}` is at `end`. The fixes put new arms on lines of their own
+/// before that `}`, indented one level past it; when the last arm shares
+/// the `}`'s line (`inline`), the `}` moves to a line of its own, indented
+/// like the statement. New arms fail an `assert` naming their pattern until
+/// they are filled in.
+/// A desugared `match` (no `end`) gets no fixes.
+fn non_exhaustive(gap: Gap, at: Span, end: Span, inline: bool) -> Diagnostic {
+    let mut diagnostic = Diagnostic::error(codes::EXHAUSTIVENESS, gap.message).at_line(at.line);
+    if end.line == 0 {
+        return diagnostic;
+    }
+    let (insert_at, brace_indent) = if inline {
+        (end, at.column.saturating_sub(1))
+    } else {
+        (Span { column: 1, ..end }, end.column.saturating_sub(1))
+    };
+    let arms = |patterns: &[String]| {
+        let arms: String = patterns.iter()
+            .map(|p| format!("{}{} => assert(false, \"unhandled: {}\")\n", " ".repeat(brace_indent + 4), p, p.replace('\\', "\\\\").replace('"', "\\\"")))
+            .collect();
+        if inline { format!("\n{}{}", arms, " ".repeat(brace_indent)) } else { arms }
+    };
+    if !gap.missing.is_empty() {
+        diagnostic = diagnostic.with_fix(format!("add arms for {}", gap.missing.join(", ")), insert_at, arms(&gap.missing));
+    }
+    if gap.wildcard {
+        diagnostic = diagnostic.with_fix("add a `_` arm", insert_at, arms(&["_".to_string()]));
+    }
+    diagnostic
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hir::{HirExpression, HirExprKind};
+
+    fn int(v: i64) -> HirExpression {
+        HirExpression { kind: HirExprKind::Integer(v), ty: Type::Int, span: Span::default() }
+    }
+
+    fn range(lo: i64, hi: i64) -> HirPattern {
+        HirPattern::Range { start: int(lo), end: int(hi) }
+    }
+
+    #[test]
+    fn int_ranges_report_first_gap() {
+        assert_eq!(describe_int_gap(&[range(0, 10), range(20, 30)]), "Integer values 10..20 are");
+        assert_eq!(describe_int_gap(&[range(0, 10), HirPattern::Literal(int(10)), range(11, 30)]), "Integer values below 0 are");
+        assert_eq!(describe_int_gap(&[range(i64::MIN, 5)]), "Integer values from 5 upward are");
+    }
+
+    #[test]
+    fn refutable_payload_does_not_cover_variant() {
+        let symbols = crate::symbol::resolve_program(&[]).expect("empty program resolves");
+        let option_int = Type::Custom { name: "Option".to_string(), args: vec![Type::Int] };
+        let some_zero = HirPattern::Variant { name: "Some".to_string(), payload: Some(Box::new(HirPattern::Literal(int(0)))) };
+        let none = HirPattern::Variant { name: "None".to_string(), payload: None };
+        assert!(check_exhaustiveness(&option_int, &[some_zero, none.clone()], &symbols).is_err());
+
+        let option_bool = Type::Custom { name: "Option".to_string(), args: vec![Type::Bool] };
+        let some_bool = |b: bool| HirPattern::Variant {
+            name: "Some".to_string(),
+            payload: Some(Box::new(HirPattern::Literal(HirExpression { kind: HirExprKind::Boolean(b), ty: Type::Bool, span: Span::default() }))),
+        };
+        assert!(check_exhaustiveness(&option_bool, &[some_bool(true), some_bool(false), none], &symbols).is_ok());
+    }
+
+    #[test]
+    fn type_switch_needs_matching_arm_or_wildcard() {
+        let symbols = crate::symbol::resolve_program(&[]).expect("empty program resolves");
+        let ty_arm = |n: &str| HirPattern::Type { name: n.to_string(), binding: None };
+        assert!(check_exhaustiveness(&Type::Int, &[ty_arm("int")], &symbols).is_ok());
+        assert!(check_exhaustiveness(&Type::Str, &[ty_arm("str")], &symbols).is_ok());
+        assert!(check_exhaustiveness(&Type::Str, &[ty_arm("int")], &symbols).is_err());
+        assert!(check_exhaustiveness(&Type::Str, &[ty_arm("int"), HirPattern::Wildcard], &symbols).is_ok());
+    }
 }
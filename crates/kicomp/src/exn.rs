@@ -11,6 +11,11 @@ use std::io::{self, Read, Write};
 
 const MAGIC: &[u8; 4] = b"KNTX";
 
+/// Bytecode format named in the manifest. v4 adds per-function exception
+/// tables; v3 stores constants in one program-wide pool; v2 and v3 files
+/// still load.
+pub const FORMAT: &str = "kivm-bytecode-v4";
+
 /// Serialize a CompiledProgram to the .exki binary format.
 pub fn write_exn<W: Write>(writer: &mut W, program: &CompiledProgram) -> io::Result<()> {
     // 1. Magic number
@@ -20,7 +25,7 @@ pub fn write_exn<W: Write>(writer: &mut W, program: &CompiledProgram) -> io::Res
     let manifest = serde_json::json!({
         "version": program.version,
         "functions": program.functions.len(),
-        "format": "kivm-bytecode-v2",
+        "format": FORMAT,
         "optimized": program.is_optimized,
         "compiler_build": crate::compiler::CURRENT_BUILD,
     });
@@ -30,8 +35,11 @@ pub fn write_exn<W: Write>(writer: &mut W, program: &CompiledProgram) -> io::Res
     writer.write_all(&manifest_len.to_le_bytes())?;
     writer.write_all(&manifest_bytes)?;
 
-    // 3. Bytecode: serialize the entire program as JSON (simple, portable)
-    let bytecode = serde_json::to_vec(program)
+    // 3. Bytecode: serialize the entire program as JSON (simple, portable),
+    // with the constants of all functions in one pool
+    let mut pooled = program.clone();
+    pooled.pool_constants();
+    let bytecode = serde_json::to_vec(&pooled)
         .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
     let bytecode_len = bytecode.len() as u32;
     writer.write_all(&bytecode_len.to_le_bytes())?;
@@ -42,6 +50,12 @@ pub fn write_exn<W: Write>(writer: &mut W, program: &CompiledProgram) -> io::Res
 
 /// Deserialize a CompiledProgram from .exki binary format.
 pub fn read_exn<R: Read>(reader: &mut R) -> io::Result<CompiledProgram> {
+    read_exn_with_manifest(reader).map(|(_, program)| program)
+}
+
+/// Like `read_exn`, also returning the parsed JSON manifest (used by the
+/// linker to check a bundle's format and compiler build).
+pub fn read_exn_with_manifest<R: Read>(reader: &mut R) -> io::Result<(serde_json::Value, CompiledProgram)> {
     // 1. Validate magic
     let mut magic = [0u8; 4];
     reader.read_exact(&mut magic)?;
@@ -58,7 +72,9 @@ pub fn read_exn<R: Read>(reader: &mut R) -> io::Result<CompiledProgram> {
     let manifest_len = u32::from_le_bytes(manifest_len_bytes) as usize;
     let mut manifest_bytes = vec![0u8; manifest_len];
     reader.read_exact(&mut manifest_bytes)?;
-    // Manifest is informational; we don't strictly need it to run.
+    // Informational for running; the linker checks it before merging.
+    let manifest: serde_json::Value = serde_json::from_slice(&manifest_bytes)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
 
     // 3. Read bytecode
     let mut bytecode_len_bytes = [0u8; 4];
@@ -67,10 +83,16 @@ pub fn read_exn<R: Read>(reader: &mut R) -> io::Result<CompiledProgram> {
     let mut bytecode_bytes = vec![0u8; bytecode_len];
     reader.read_exact(&mut bytecode_bytes)?;
 
-    let program: CompiledProgram = serde_json::from_slice(&bytecode_bytes)
+    let mut program: CompiledProgram = serde_json::from_slice(&bytecode_bytes)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    program.unpool_constants()
         .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    // A bad table would send the unwinder out of the function
+    for func in program.functions.iter().chain(std::iter::once(&program.main)) {
+        func.check_exception_table().map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    }
 
-    Ok(program)
+    Ok((manifest, program))
 }
 
 #[cfg(test)]
@@ -98,6 +120,51 @@ mod tests {
         assert_eq!(loaded.version, "0.1.0");
     }
 
+    #[test]
+    fn test_exn_shares_constants_across_functions() {
+        let mut program = CompiledProgram::new();
+        for name in ["area", "perimeter"] {
+            let mut func = CompiledFunction::new(name.to_string(), 1);
+            func.add_constant(Constant::String("__class__".to_string()));
+            func.add_constant(Constant::Float(0.5));
+            program.functions.push(func);
+        }
+        program.main.add_constant(Constant::Float(-0.5));
+        program.main.add_constant(Constant::String("__class__".to_string()));
+
+        let mut buf: Vec<u8> = Vec::new();
+        write_exn(&mut buf, &program).expect("write failed");
+        assert_eq!(buf.windows(9).filter(|w| w == b"__class__").count(), 1);
+
+        let loaded = read_exn(&mut buf.as_slice()).expect("read failed");
+        assert!(loaded.constants.is_empty());
+        for (loaded, original) in loaded.functions.iter().chain([&loaded.main]).zip(program.functions.iter().chain([&program.main])) {
+            assert_eq!(loaded.constants, original.constants);
+            assert!(loaded.constant_refs.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_exn_keeps_and_checks_exception_tables() {
+        let mut program = CompiledProgram::new();
+        program.main.locals = 2;
+        for _ in 0..3 {
+            program.main.emit(Instruction::a_only(Opcode::LoadNull, 0));
+        }
+        program.main.exception_table.push(ExceptionHandler { start: 0, end: 2, handler: 2, register: 1 });
+
+        let mut buf: Vec<u8> = Vec::new();
+        write_exn(&mut buf, &program).expect("write failed");
+        let loaded = read_exn(&mut buf.as_slice()).expect("read failed");
+        assert_eq!(loaded.main.exception_table, program.main.exception_table);
+
+        program.main.exception_table[0].handler = 1;
+        let mut buf: Vec<u8> = Vec::new();
+        write_exn(&mut buf, &program).expect("write failed");
+        let err = read_exn(&mut buf.as_slice()).unwrap_err();
+        assert_eq!(err.to_string(), "Function '<main>': exception handler 0 (0..2 -> 1) lands inside the range it handles");
+    }
+
     #[test]
     fn test_exn_invalid_magic() {
         let buf = b"BAAD\x00\x00\x00\x00";
@@ -0,0 +1,187 @@
+//! Global mutation lint: warns when a function assigns a top-level `mut` that
+//! isn't shared with `pub mut` or `state`.
+
+use kinetix_language::ast::{Expression, ExpressionKind, Statement};
+use kinetix_language::diagnostic::{codes, Diagnostic};
+use kinetix_language::span::Span;
+use std::collections::{HashMap, HashSet};
+
+/// Returns one warning per assignment to an undeclared-shared global from
+/// inside a function.
+pub fn check(statements: &[Statement<'_>]) -> Vec<Diagnostic> {
+    let mut globals = HashMap::new();
+    for stmt in statements {
+        if let Statement::Let { name, mutable: true, public: false, line, .. } = stmt {
+            globals.insert(*name, *line);
+        }
+    }
+    let mut warnings = Vec::new();
+    if !globals.is_empty() {
+        let mut lint = Lint { globals, warnings: &mut warnings };
+        for stmt in statements {
+            lint.visit_stmt(stmt, None, &HashSet::new());
+        }
+    }
+    warnings
+}
+
+struct Lint<'g, 'w> {
+    /// Top-level plain `mut` bindings and their lines.
+    globals: HashMap<&'g str, usize>,
+    warnings: &'w mut Vec<Diagnostic>,
+}
+
+impl<'g> Lint<'g, '_> {
+    /// Enters a function body: its parameters and every name it declares
+    /// shadow the globals, as do those of the functions around it.
+    fn visit_function(&mut self, name: &str, parameters: &[(String, String)], body: &Statement<'_>, outer: &HashSet<String>) {
+        let mut locals = outer.clone();
+        locals.extend(parameters.iter().map(|(p, _)| p.clone()));
+        declared_names(body, &mut locals);
+        self.visit_stmt(body, Some(name), &locals);
+    }
+
+    /// `function` is the enclosing function's name, `None` at the top level.
+    fn visit_stmt(&mut self, stmt: &Statement<'_>, function: Option<&str>, locals: &HashSet<String>) {
+        match stmt {
+            Statement::Function { name, parameters, body, .. } => self.visit_function(name, parameters, body, locals),
+            Statement::Class { methods, constants, .. } => {
+                methods.iter().chain(constants).for_each(|s| self.visit_stmt(s, function, locals));
+            }
+            Statement::Impl { methods, .. } => methods.iter().for_each(|s| self.visit_stmt(s, function, locals)),
+            Statement::Let { value, span, .. }
+            | Statement::State { value, span, .. }
+            | Statement::Computed { value, span, .. }
+            | Statement::Expression { expression: value, span, .. } => self.visit_expr(value, *span, function, locals),
+            Statement::Return { value, span, .. } => {
+                if let Some(value) = value {
+                    self.visit_expr(value, *span, function, locals);
+                }
+            }
+            Statement::Effect { body, .. } => self.visit_stmt(body, function, locals),
+            Statement::Block { statements, .. } => statements.iter().for_each(|s| self.visit_stmt(s, function, locals)),
+            Statement::While { condition: value, body, span, .. } | Statement::For { range: value, body, span, .. } => {
+                self.visit_expr(value, *span, function, locals);
+                self.visit_stmt(body, function, locals);
+            }
+            Statement::Struct { .. }
+            | Statement::Include { .. }
+            | Statement::Version { .. }
+            | Statement::ImportBundle { .. }
+            | Statement::Import { .. }
+            | Statement::Enum { .. }
+            | Statement::Trait { .. }
+            | Statement::Break { .. }
+            | Statement::Continue { .. } => {}
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &Expression<'_>, span: Span, function: Option<&str>, locals: &HashSet<String>) {
+        match &expr.kind {
+            ExpressionKind::Assign { target, value } => {
+                let global = match &target.kind {
+                    ExpressionKind::Identifier(name) if !locals.contains(*name) => self.globals.get_key_value(name),
+                    _ => None,
+                };
+                if let (Some((name, declared)), Some(function)) = (global, function) {
+                    let message = format!(
+                        "function '{}' assigns the global '{}' (declared on line {}); declare it `pub mut {}` to share it with functions, or `state {}` to track it reactively",
+                        function, name, declared, name, name
+                    );
+                    self.warnings.push(Diagnostic::warning(codes::LINT, message).at(if expr.span.line > 0 { expr.span } else { span }));
+                }
+                self.visit_expr(target, span, function, locals);
+                self.visit_expr(value, span, function, locals);
+            }
+            ExpressionKind::FunctionLiteral { parameters, body, .. } => self.visit_function("<lambda>", parameters, body, locals),
+            ExpressionKind::Prefix { right, .. } | ExpressionKind::Try { value: right } => self.visit_expr(right, span, function, locals),
+            ExpressionKind::MemberAccess { object, .. } => self.visit_expr(object, span, function, locals),
+            ExpressionKind::Infix { left, right, .. } | ExpressionKind::Index { left, index: right } | ExpressionKind::Range { start: left, end: right } => {
+                self.visit_expr(left, span, function, locals);
+                self.visit_expr(right, span, function, locals);
+            }
+            ExpressionKind::If { condition, consequence, alternative } => {
+                self.visit_expr(condition, span, function, locals);
+                self.visit_stmt(consequence, function, locals);
+                if let Some(alt) = alternative {
+                    self.visit_stmt(alt, function, locals);
+                }
+            }
+            ExpressionKind::Call { function: callee, arguments } => {
+                self.visit_expr(callee, span, function, locals);
+                arguments.iter().for_each(|a| self.visit_expr(a, span, function, locals));
+            }
+            ExpressionKind::StructLiteral { fields, .. } => fields.iter().for_each(|(_, v)| self.visit_expr(v, span, function, locals)),
+            ExpressionKind::ArrayLiteral(items) => items.iter().for_each(|i| self.visit_expr(i, span, function, locals)),
+            ExpressionKind::MapLiteral(entries) => entries.iter().for_each(|(k, v)| {
+                self.visit_expr(k, span, function, locals);
+                self.visit_expr(v, span, function, locals);
+            }),
+            ExpressionKind::Match { value, arms, .. } => {
+                self.visit_expr(value, span, function, locals);
+                arms.iter().for_each(|(_, body)| self.visit_stmt(body, function, locals));
+            }
+            ExpressionKind::Identifier(_)
+            | ExpressionKind::Integer(_)
+            | ExpressionKind::Float(_)
+            | ExpressionKind::String(_)
+            | ExpressionKind::Boolean(_)
+            | ExpressionKind::Null => {}
+        }
+    }
+}
+
+/// Names a function body declares (`let`, `mut`, `state`, `computed`, loop
+/// variables), without descending into nested functions.
+fn declared_names(stmt: &Statement<'_>, names: &mut HashSet<String>) {
+    match stmt {
+        Statement::Let { name, .. } | Statement::State { name, .. } | Statement::Computed { name, .. } => {
+            names.insert(name.to_string());
+        }
+        Statement::For { iterator, body, .. } => {
+            names.insert(iterator.to_string());
+            declared_names(body, names);
+        }
+        Statement::While { body, .. } | Statement::Effect { body, .. } => declared_names(body, names),
+        Statement::Block { statements, .. } => statements.iter().for_each(|s| declared_names(s, names)),
+        Statement::Expression { expression: Expression { kind: ExpressionKind::If { consequence, alternative, .. }, .. }, .. } => {
+            declared_names(consequence, names);
+            if let Some(alt) = alternative {
+                declared_names(alt, names);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bumpalo::Bump;
+    use kinetix_language::lexer::Lexer;
+    use kinetix_language::parser::Parser;
+
+    fn lint(src: &str) -> Vec<String> {
+        let arena = Bump::new();
+        let mut parser = Parser::new(Lexer::new(src), &arena);
+        let program = parser.parse_program();
+        assert!(parser.errors.is_empty(), "Parser errors: {:?}", parser.errors);
+        check(&program.statements).iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn functions_assigning_plain_globals_are_flagged() {
+        let warnings = lint("mut count = 0\nfn bump() {\n  count = count + 1\n}\ncount = 5\n");
+        assert_eq!(warnings, vec![
+            "Line 3:3: function 'bump' assigns the global 'count' (declared on line 1); declare it `pub mut count` to share it with functions, or `state count` to track it reactively".to_string(),
+        ]);
+    }
+
+    #[test]
+    fn shared_shadowed_and_reactive_globals_are_not() {
+        assert!(lint("pub mut count = 0\nfn bump() {\n  count = count + 1\n}\n").is_empty());
+        assert!(lint("state count = 0\nfn bump() {\n  count = count + 1\n}\n").is_empty());
+        assert!(lint("mut count = 0\nfn reset(count: int) {\n  count = 0\n}\n").is_empty());
+        assert!(lint("mut total = 0\nfn sum() -> int {\n  mut total = 0\n  for i in 0..3 {\n    total = total + i\n  }\n  return total\n}\n").is_empty());
+    }
+}
@@ -6,7 +6,8 @@
 
 use crate::types::{Type, TypeVarId, parse_type_hint};
 use crate::symbol::SymbolTable;
-use kinetix_language::ast::{Statement, Expression};
+use kinetix_language::ast::{Statement, Expression, ExpressionKind};
+use kinetix_language::span::Span;
 
 /// A fresh type variable counter for HIR lowering.
 struct FreshCounter {
@@ -61,6 +62,15 @@ pub struct HirStatement {
     pub kind: HirStmtKind,
     pub ty: Type,
     pub line: usize,
+    /// The source statement's span; `Span::default()` for desugared code.
+    pub span: Span,
+}
+
+impl HirStatement {
+    /// Where the statement is: its span, or only its line for desugared code.
+    pub fn location(&self) -> Span {
+        if self.span.line > 0 { self.span } else { Span { line: self.line, ..Span::default() } }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -104,20 +114,33 @@ pub enum HirStmtKind {
     While {
         condition: HirExpression,
         body: Box<HirStatement>,
+        label: Option<String>,
     },
     For {
         iterator: String,
         range: HirExpression,
         body: Box<HirStatement>,
+        label: Option<String>,
     },
-    Break,
-    Continue,
+    /// `break`, optionally targeting an enclosing labeled loop.
+    Break { label: Option<String> },
+    /// `continue`, optionally targeting an enclosing labeled loop.
+    Continue { label: Option<String> },
 }
 
 #[derive(Debug, Clone)]
 pub struct HirExpression {
     pub kind: HirExprKind,
     pub ty: Type,
+    /// The source expression's span; `Span::default()` for desugared code.
+    pub span: Span,
+}
+
+impl HirExpression {
+    /// Where the expression is: its span, or `fallback` for desugared code.
+    pub fn location(&self, fallback: Span) -> Span {
+        if self.span.line > 0 { self.span } else { fallback }
+    }
 }
 
 /// Pattern representation for match arms.
@@ -125,14 +148,27 @@ pub struct HirExpression {
 pub enum HirPattern {
     /// A literal value: 1, "hello", true
     Literal(HirExpression),
-    /// A variant constructor with optional binding: Some(x), None, Ok(val)
-    Variant { name: String, binding: Option<String> },
+    /// Half-open integer range: 0..10
+    Range { start: HirExpression, end: HirExpression },
+    /// A variant constructor with an optional payload pattern: Some(x), None, Ok(Some(v))
+    Variant { name: String, payload: Option<Box<HirPattern>> },
+    /// Struct/class destructuring: Point { x: 0, y: py }
+    Struct { name: String, fields: Vec<(String, HirPattern)> },
+    /// Type-switch arm (`match x as { ... }`): int(n), string, Point(p)
+    Type { name: String, binding: Option<String> },
     /// Wildcard: _
     Wildcard,
     /// Simple identifier binding (catches everything and binds to name)
     Binding(String),
 }
 
+impl HirPattern {
+    /// True if this pattern matches every value of its type (`_`, `x`).
+    pub fn is_irrefutable(&self) -> bool {
+        matches!(self, HirPattern::Wildcard | HirPattern::Binding(_))
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum HirExprKind {
     Identifier(String),
@@ -191,6 +227,8 @@ pub enum HirExprKind {
     Match {
         value: Box<HirExpression>,
         arms: Vec<(HirPattern, HirStatement)>,
+        /// The source `match`'s closing `}` (see `ExpressionKind::Match`).
+        end: Span,
     },
 }
 
@@ -207,6 +245,27 @@ pub fn lower_to_hir<'a>(statements: &[Statement<'a>], symbols: &SymbolTable, tra
     HirProgram { statements: stmts }
 }
 
+/// `alias.member` on an `import` alias (not shadowed by a local): the
+/// module's linked `module::member` function.
+fn lower_module_member(
+    object: &Expression,
+    member: &str,
+    symbols: &SymbolTable,
+    fresh: &mut FreshCounter,
+    env: &std::collections::HashMap<String, Type>,
+) -> Option<HirExpression> {
+    let ExpressionKind::Identifier(alias) = &object.kind else { return None };
+    if env.contains_key(*alias) {
+        return None;
+    }
+    let name = crate::link::qualify(symbols.module_of(alias)?, member);
+    let ty = match symbols.resolve(&name) {
+        Some(symbol) => instantiate_fresh(&symbol.ty, fresh),
+        None => fresh.fresh(),
+    };
+    Some(HirExpression { kind: HirExprKind::Identifier(name), ty, span: Span::default() })
+}
+
 fn get_line(stmt: &Statement) -> usize {
     match stmt {
         Statement::Let { line, .. } => *line,
@@ -223,8 +282,10 @@ fn get_line(stmt: &Statement) -> usize {
         Statement::Impl { line, .. } => *line,
         Statement::Include { line, .. } => *line,
         Statement::Version { line, .. } => *line,
-        Statement::Break { line } => *line,
-        Statement::Continue { line } => *line,
+        Statement::ImportBundle { line, .. } => *line,
+        Statement::Import { line, .. } => *line,
+        Statement::Break { line, .. } => *line,
+        Statement::Continue { line, .. } => *line,
         Statement::State { line, .. } => *line,
         Statement::Computed { line, .. } => *line,
         Statement::Effect { line, .. } => *line,
@@ -233,6 +294,7 @@ fn get_line(stmt: &Statement) -> usize {
 
 fn lower_statement<'a>(stmt: &Statement<'a>, symbols: &SymbolTable, traits: &crate::trait_solver::TraitEnvironment, fresh: &mut FreshCounter, env: &mut std::collections::HashMap<String, Type>) -> HirStatement {
     let line = get_line(stmt);
+    let span = stmt.span();
     match stmt {
         Statement::State { name, type_hint, value, .. } => {
             let hir_val = lower_expression(value, symbols, traits, fresh, env);
@@ -240,11 +302,12 @@ fn lower_statement<'a>(stmt: &Statement<'a>, symbols: &SymbolTable, traits: &cra
                 Some(hint) => parse_type_hint(hint),
                 None => fresh.fresh(),
             };
-            env.insert(name.clone(), ty.clone());
+            env.insert(name.to_string(), ty.clone());
             HirStatement {
-                kind: HirStmtKind::State { name: name.clone(), value: hir_val },
+                kind: HirStmtKind::State { name: name.to_string(), value: hir_val },
                 ty,
                 line,
+                span,
             }
         }
         Statement::Computed { name, type_hint, value, .. } => {
@@ -253,11 +316,12 @@ fn lower_statement<'a>(stmt: &Statement<'a>, symbols: &SymbolTable, traits: &cra
                 Some(hint) => parse_type_hint(hint),
                 None => fresh.fresh(),
             };
-            env.insert(name.clone(), ty.clone());
+            env.insert(name.to_string(), ty.clone());
             HirStatement {
-                kind: HirStmtKind::Computed { name: name.clone(), value: hir_val },
+                kind: HirStmtKind::Computed { name: name.to_string(), value: hir_val },
                 ty,
                 line,
+                span,
             }
         }
         Statement::Effect { dependencies, body, .. } => {
@@ -266,6 +330,7 @@ fn lower_statement<'a>(stmt: &Statement<'a>, symbols: &SymbolTable, traits: &cra
                 kind: HirStmtKind::Effect { dependencies: dependencies.clone(), body: Box::new(hir_body) },
                 ty: Type::Void,
                 line,
+                span,
             }
         }
         Statement::Let { name, mutable, type_hint, value, .. } => {
@@ -275,11 +340,12 @@ fn lower_statement<'a>(stmt: &Statement<'a>, symbols: &SymbolTable, traits: &cra
                 None => fresh.fresh(),
             };
             // Register this variable in the type environment
-            env.insert(name.clone(), ty.clone());
+            env.insert(name.to_string(), ty.clone());
             HirStatement {
-                kind: HirStmtKind::Let { name: name.clone(), mutable: *mutable, value: val },
+                kind: HirStmtKind::Let { name: name.to_string(), mutable: *mutable, value: val },
                 ty,
                 line,
+                span,
             }
         }
         Statement::Return { value, .. } => {
@@ -288,19 +354,19 @@ fn lower_statement<'a>(stmt: &Statement<'a>, symbols: &SymbolTable, traits: &cra
                 Some(e) => e.ty.clone(),
                 None => Type::Void,
             };
-            HirStatement { kind: HirStmtKind::Return { value: val }, ty, line }
+            HirStatement { kind: HirStmtKind::Return { value: val }, ty, line, span }
         }
         Statement::Expression { expression, .. } => {
             let expr = lower_expression(expression, symbols, traits, fresh, env);
             let ty = expr.ty.clone();
-            HirStatement { kind: HirStmtKind::Expression { expression: expr }, ty, line }
+            HirStatement { kind: HirStmtKind::Expression { expression: expr }, ty, line, span }
         }
         Statement::Block { statements, .. } => {
             let stmts: Vec<HirStatement> = statements.iter()
                 .map(|s| lower_statement(s, symbols, traits, fresh, env))
                 .collect();
             let ty = stmts.last().map(|s| s.ty.clone()).unwrap_or(Type::Void);
-            HirStatement { kind: HirStmtKind::Block { statements: stmts }, ty, line }
+            HirStatement { kind: HirStmtKind::Block { statements: stmts }, ty, line, span }
         }
         Statement::Function { name, parameters, body, return_type, .. } => {
             let params: Vec<(String, Type)> = parameters.iter()
@@ -316,54 +382,120 @@ fn lower_statement<'a>(stmt: &Statement<'a>, symbols: &SymbolTable, traits: &cra
 
             let hir_body = Box::new(lower_statement(body, symbols, traits, fresh, &mut func_env));
             HirStatement {
-                kind: HirStmtKind::Function { name: name.clone(), parameters: params, body: hir_body, return_type: ret.clone() },
+                kind: HirStmtKind::Function { name: name.to_string(), parameters: params, body: hir_body, return_type: ret.clone() },
                 ty: Type::Void, // function definitions don't produce a value
                 line,
+                span,
             }
         }
-        Statement::Class { name, methods, .. } => {
-            let hir_methods: Vec<HirStatement> = methods.iter()
-                .map(|m| lower_statement(m, symbols, traits, fresh, env))
-                .collect();
+        Statement::Class { name, methods, constants, .. } => {
+            // Class constants lower to `Let`s of their flattened `Class::NAME`
+            // global, ahead of the methods, so `ClassName.NAME` reads share
+            // the type the initializer is checked against.
+            let mut hir_methods = Vec::new();
+            for c in constants {
+                if let Statement::Let { name: c_name, type_hint, value, line: c_line, .. } = c {
+                    let val = lower_expression(value, symbols, traits, fresh, env);
+                    let ty = match type_hint {
+                        Some(hint) => parse_type_hint(hint),
+                        None => fresh.fresh(),
+                    };
+                    let flat_name = format!("{}::{}", name, c_name);
+                    env.insert(flat_name.clone(), ty.clone());
+                    hir_methods.push(HirStatement {
+                        kind: HirStmtKind::Let { name: flat_name, mutable: false, value: val },
+                        ty,
+                        line: *c_line,
+                        span: c.span(),
+                    });
+                }
+            }
+            hir_methods.extend(methods.iter().map(|m| lower_statement(m, symbols, traits, fresh, env)));
             HirStatement {
-                kind: HirStmtKind::Class { name: name.clone(), methods: hir_methods },
+                kind: HirStmtKind::Class { name: name.to_string(), methods: hir_methods },
                 ty: Type::Void,
                 line,
+                span,
             }
         }
-        Statement::While { condition, body, .. } => {
+        Statement::While { condition, body, label, .. } => {
             let cond = lower_expression(condition, symbols, traits, fresh, env);
             let b = Box::new(lower_statement(body, symbols, traits, fresh, env));
-            HirStatement { kind: HirStmtKind::While { condition: cond, body: b }, ty: Type::Void, line }
+            HirStatement { kind: HirStmtKind::While { condition: cond, body: b, label: label.map(str::to_string) }, ty: Type::Void, line, span }
         }
-        Statement::For { iterator, range, body, .. } => {
+        Statement::For { iterator, range, body, label, .. } => {
             let r = lower_expression(range, symbols, traits, fresh, env);
             
             // Scope iter var
             let mut for_env = env.clone();
-            for_env.insert(iterator.clone(), Type::Int); // Iterators over Ranges are Int
+            for_env.insert(iterator.to_string(), Type::Int); // Iterators over Ranges are Int
             let b = Box::new(lower_statement(body, symbols, traits, fresh, &mut for_env));
             
             HirStatement {
-                kind: HirStmtKind::For { iterator: iterator.clone(), range: r, body: b },
+                kind: HirStmtKind::For { iterator: iterator.to_string(), range: r, body: b, label: label.map(str::to_string) },
                 ty: Type::Void, line,
+                span,
             }
         }
-        Statement::Break { .. } => HirStatement { kind: HirStmtKind::Break, ty: Type::Void, line },
-        Statement::Continue { .. } => HirStatement { kind: HirStmtKind::Continue, ty: Type::Void, line },
+        Statement::Break { label, .. } => HirStatement { kind: HirStmtKind::Break { label: label.map(str::to_string) }, ty: Type::Void, line, span },
+        Statement::Continue { label, .. } => HirStatement { kind: HirStmtKind::Continue { label: label.map(str::to_string) }, ty: Type::Void, line, span },
         // Class, Struct, Include, Version — skip for now
-        _ => HirStatement { kind: HirStmtKind::Break, ty: Type::Void, line },
+        _ => HirStatement { kind: HirStmtKind::Break { label: None }, ty: Type::Void, line, span },
+    }
+}
+
+/// Lowers a classified match pattern, giving every name it binds a fresh
+/// type variable in `arm_env`.
+fn lower_pattern(pattern: &crate::pattern::ArmPattern<'_>, symbols: &SymbolTable, traits: &crate::trait_solver::TraitEnvironment, fresh: &mut FreshCounter, arm_env: &mut std::collections::HashMap<String, Type>) -> HirPattern {
+    use crate::pattern::ArmPattern;
+    match pattern {
+        ArmPattern::Wildcard => HirPattern::Wildcard,
+        ArmPattern::Binding(name) => {
+            arm_env.insert(name.clone(), fresh.fresh());
+            HirPattern::Binding(name.clone())
+        }
+        ArmPattern::Literal(lit_expr) => HirPattern::Literal(lower_expression(lit_expr, symbols, traits, fresh, arm_env)),
+        ArmPattern::Range { start, end } => HirPattern::Range {
+            start: lower_expression(start, symbols, traits, fresh, arm_env),
+            end: lower_expression(end, symbols, traits, fresh, arm_env),
+        },
+        ArmPattern::Variant { name, payload } => HirPattern::Variant {
+            name: name.clone(),
+            payload: payload.as_ref().map(|p| Box::new(lower_pattern(p, symbols, traits, fresh, arm_env))),
+        },
+        ArmPattern::Struct { name, fields } => HirPattern::Struct {
+            name: name.clone(),
+            fields: fields.iter().map(|(f, p)| (f.clone(), lower_pattern(p, symbols, traits, fresh, arm_env))).collect(),
+        },
+        ArmPattern::Type { name, binding } => {
+            // The binding is narrowed to the arm's type, independently of the
+            // scrutinee's (usually unknown) static type and of every other arm.
+            if let Some(bname) = binding {
+                let narrowed = match name.as_str() {
+                    "array" | "map" | "function" | "null" => fresh.fresh(),
+                    other => crate::types::parse_type_hint(other),
+                };
+                arm_env.insert(bname.clone(), narrowed);
+            }
+            HirPattern::Type { name: name.clone(), binding: binding.clone() }
+        }
     }
 }
 
 fn lower_expression<'a>(expr: &Expression<'a>, symbols: &SymbolTable, traits: &crate::trait_solver::TraitEnvironment, fresh: &mut FreshCounter, env: &mut std::collections::HashMap<String, Type>) -> HirExpression {
-    match expr {
-        Expression::Integer(v) => HirExpression { kind: HirExprKind::Integer(*v), ty: Type::Int },
-        Expression::Float(v) => HirExpression { kind: HirExprKind::Float(*v), ty: Type::Float },
-        Expression::String(v) => HirExpression { kind: HirExprKind::String(v.clone()), ty: Type::Str },
-        Expression::Boolean(v) => HirExpression { kind: HirExprKind::Boolean(*v), ty: Type::Bool },
-        Expression::Null => HirExpression { kind: HirExprKind::Null, ty: Type::Void },
-        Expression::Try { value } => {
+    let mut hir = lower_expression_kind(expr, symbols, traits, fresh, env);
+    hir.span = expr.span;
+    hir
+}
+
+fn lower_expression_kind<'a>(expr: &Expression<'a>, symbols: &SymbolTable, traits: &crate::trait_solver::TraitEnvironment, fresh: &mut FreshCounter, env: &mut std::collections::HashMap<String, Type>) -> HirExpression {
+    match &expr.kind {
+        ExpressionKind::Integer(v) => HirExpression { kind: HirExprKind::Integer(*v), ty: Type::Int, span: Span::default() },
+        ExpressionKind::Float(v) => HirExpression { kind: HirExprKind::Float(*v), ty: Type::Float, span: Span::default() },
+        ExpressionKind::String(v) => HirExpression { kind: HirExprKind::String(v.to_string()), ty: Type::Str, span: Span::default() },
+        ExpressionKind::Boolean(v) => HirExpression { kind: HirExprKind::Boolean(*v), ty: Type::Bool, span: Span::default() },
+        ExpressionKind::Null => HirExpression { kind: HirExprKind::Null, ty: Type::Void, span: Span::default() },
+        ExpressionKind::Try { value } => {
             // Desugar `expr?` into:
             //   match expr { Ok(v) => v, Err(e) => return Err(e) }
             let inner = lower_expression(value, symbols, traits, fresh, env);
@@ -371,40 +503,45 @@ fn lower_expression<'a>(expr: &Expression<'a>, symbols: &SymbolTable, traits: &c
             let err_ty = fresh.fresh();    // E in Result<T,E>
             
             let ok_arm = (
-                HirPattern::Variant { name: "Ok".to_string(), binding: Some("__ok_val".to_string()) },
+                HirPattern::Variant { name: "Ok".to_string(), payload: Some(Box::new(HirPattern::Binding("__ok_val".to_string()))) },
                 HirStatement {
                     kind: HirStmtKind::Expression {
-                        expression: HirExpression { kind: HirExprKind::Identifier("__ok_val".to_string()), ty: result_ty.clone() },
+                        expression: HirExpression { kind: HirExprKind::Identifier("__ok_val".to_string()), ty: result_ty.clone(), span: Span::default() },
                     },
                     ty: result_ty.clone(),
                     line: 0,
+                    span: Span::default(),
                 },
             );
             let err_arm = (
-                HirPattern::Variant { name: "Err".to_string(), binding: Some("__err_val".to_string()) },
+                HirPattern::Variant { name: "Err".to_string(), payload: Some(Box::new(HirPattern::Binding("__err_val".to_string()))) },
                 HirStatement {
                     kind: HirStmtKind::Return {
                         value: Some(HirExpression {
                             kind: HirExprKind::Call {
-                                function: Box::new(HirExpression { kind: HirExprKind::Identifier("Err".to_string()), ty: fresh.fresh() }),
-                                arguments: vec![HirExpression { kind: HirExprKind::Identifier("__err_val".to_string()), ty: err_ty.clone() }],
+                                function: Box::new(HirExpression { kind: HirExprKind::Identifier("Err".to_string()), ty: fresh.fresh(), span: Span::default() }),
+                                arguments: vec![HirExpression { kind: HirExprKind::Identifier("__err_val".to_string()), ty: err_ty.clone(), span: Span::default() }],
                             },
                             ty: fresh.fresh(),
+                            span: Span::default(),
                         }),
                     },
                     ty: Type::Void,
                     line: 0,
+                    span: Span::default(),
                 },
             );
             HirExpression {
                 kind: HirExprKind::Match {
                     value: Box::new(inner),
                     arms: vec![ok_arm, err_arm],
+                    end: Span::default(),
                 },
                 ty: result_ty,
+                span: Span::default(),
             }
         }
-        Expression::Match { value, arms } => {
+        ExpressionKind::Match { value, arms, by_type, end } => {
             let val = lower_expression(value, symbols, traits, fresh, env);
             let match_ty = fresh.fresh();
             let hir_arms: Vec<(HirPattern, HirStatement)> = arms.iter().map(|(pat_expr, body_stmt)| {
@@ -412,46 +549,33 @@ fn lower_expression<'a>(expr: &Expression<'a>, symbols: &SymbolTable, traits: &c
                 // binding name reused across arms (`Circle(r) => .., Square(r) => ..`)
                 // doesn't spuriously share one type variable between them.
                 let mut arm_env = env.clone();
-                let pattern = match crate::pattern::classify_pattern(pat_expr, |n| symbols.is_nullary_variant(n)) {
-                    crate::pattern::ArmPattern::Wildcard => HirPattern::Wildcard,
-                    crate::pattern::ArmPattern::Binding(name) => {
-                        arm_env.insert(name.clone(), fresh.fresh());
-                        HirPattern::Binding(name)
-                    }
-                    crate::pattern::ArmPattern::Variant { name, binding } => {
-                        if let Some(bname) = &binding {
-                            arm_env.insert(bname.clone(), fresh.fresh());
-                        }
-                        HirPattern::Variant { name, binding }
-                    }
-                    crate::pattern::ArmPattern::Literal(lit_expr) => {
-                        let lit = lower_expression(lit_expr, symbols, traits, fresh, &mut arm_env);
-                        HirPattern::Literal(lit)
-                    }
-                };
+                let classified = crate::pattern::classify_arm(pat_expr, *by_type, |n| symbols.is_nullary_variant(n));
+                let pattern = lower_pattern(&classified, symbols, traits, fresh, &mut arm_env);
                 let body = lower_statement(body_stmt, symbols, traits, fresh, &mut arm_env);
                 (pattern, body)
             }).collect();
             HirExpression {
-                kind: HirExprKind::Match { value: Box::new(val), arms: hir_arms },
+                kind: HirExprKind::Match { value: Box::new(val), arms: hir_arms, end: *end },
                 ty: match_ty,
+                span: Span::default(),
             }
         }
-        Expression::StructLiteral { name, fields } => {
+        ExpressionKind::StructLiteral { name, fields } => {
             let mut hir_fields = vec![];
             for (fname, fexpr) in fields {
                 hir_fields.push((fname.clone(), lower_expression(fexpr, symbols, traits, fresh, env)));
             }
             HirExpression {
-                kind: HirExprKind::StructLiteral(name.clone(), hir_fields),
-                ty: Type::Custom { name: name.clone(), args: vec![] },
+                kind: HirExprKind::StructLiteral(name.to_string(), hir_fields),
+                ty: Type::Custom { name: name.to_string(), args: vec![] },
+                span: Span::default(),
             }
         }
-        Expression::Identifier(name) => {
-            let ty = if name == "println" || name == "print" {
+        ExpressionKind::Identifier(name) => {
+            let ty = if *name == "println" || *name == "print" {
                 // println/print accept any argument, so we give them a fresh type variable parameter
                 Type::Fn(vec![fresh.fresh()], Box::new(Type::Void))
-            } else if let Some(t) = env.get(name) {
+            } else if let Some(t) = env.get(*name) {
                 // Prefer the HIR-level type environment so unification uses the same type
                 // variable across all usages of a Let-defined variable.
                 t.clone()
@@ -466,61 +590,60 @@ fn lower_expression<'a>(expr: &Expression<'a>, symbols: &SymbolTable, traits: &c
                 instantiate_fresh(&t, fresh)
             } else {
                 let fr = fresh.fresh();
-                env.insert(name.clone(), fr.clone());
+                env.insert(name.to_string(), fr.clone());
                 fr
             };
-            HirExpression { kind: HirExprKind::Identifier(name.clone()), ty }
+            HirExpression { kind: HirExprKind::Identifier(name.to_string()), ty, span: Span::default() }
         }
-        Expression::Prefix { operator, right } => {
+        ExpressionKind::Prefix { operator, right } => {
             let r = lower_expression(right, symbols, traits, fresh, env);
-            let ty = match operator.as_str() {
+            let ty = match *operator {
                 "&" => Type::Ref(Box::new(r.ty.clone())),
                 "&mut" => Type::MutRef(Box::new(r.ty.clone())),
                 _ => r.ty.clone(),
             };
             HirExpression {
-                kind: HirExprKind::Prefix { operator: operator.clone(), right: Box::new(r) },
+                kind: HirExprKind::Prefix { operator: operator.to_string(), right: Box::new(r) },
                 ty,
+                span: Span::default(),
             }
         }
-        Expression::Infix { left, operator, right } => {
+        ExpressionKind::Infix { left, operator, right } => {
             let l = lower_expression(left, symbols, traits, fresh, env);
             let r = lower_expression(right, symbols, traits, fresh, env);
             let ty = fresh.fresh(); // will be constrained later
             HirExpression {
-                kind: HirExprKind::Infix { left: Box::new(l), operator: operator.clone(), right: Box::new(r) },
+                kind: HirExprKind::Infix { left: Box::new(l), operator: operator.to_string(), right: Box::new(r) },
                 ty,
+                span: Span::default(),
             }
         }
-        Expression::If { condition, consequence, alternative } => {
+        ExpressionKind::If { condition, consequence, alternative } => {
             // Constant Folding per Compile-Time OS branch elimination
             let mut is_compile_time_const = None;
             
-            if let Expression::Call { function, arguments } = &**condition {
-                if arguments.is_empty() {
-                    // Check if the callee looks like: `system.os.isWindows`
-                    fn is_os_call(expr: &Expression, method: &str) -> bool {
-                        if let Expression::MemberAccess { object, member } = expr {
-                            if member == method {
-                                if let Expression::MemberAccess { object: inner_obj, member: inner_member } = &**object {
-                                    if inner_member == "os" {
-                                        if let Expression::Identifier(root) = &**inner_obj {
-                                            return root == "system";
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                        false
+            if let ExpressionKind::Call { function, arguments } = &condition.kind
+                && arguments.is_empty()
+            {
+                // Check if the callee looks like: `system.os.isWindows`
+                fn is_os_call(expr: &Expression, method: &str) -> bool {
+                    if let ExpressionKind::MemberAccess { object, member } = &expr.kind
+                        && *member == method
+                        && let ExpressionKind::MemberAccess { object: inner_obj, member: inner_member } = &object.kind
+                        && *inner_member == "os"
+                        && let ExpressionKind::Identifier(root) = &inner_obj.kind
+                    {
+                        return *root == "system";
                     }
+                    false
+                }
 
-                    if is_os_call(&**function, "isWindows") {
-                        is_compile_time_const = Some(cfg!(windows));
-                    } else if is_os_call(&**function, "isLinux") {
-                        is_compile_time_const = Some(cfg!(target_os = "linux"));
-                    } else if is_os_call(&**function, "isMac") {
-                        is_compile_time_const = Some(cfg!(target_os = "macos"));
-                    }
+                if is_os_call(function, "isWindows") {
+                    is_compile_time_const = Some(cfg!(windows));
+                } else if is_os_call(function, "isLinux") {
+                    is_compile_time_const = Some(cfg!(target_os = "linux"));
+                } else if is_os_call(function, "isMac") {
+                    is_compile_time_const = Some(cfg!(target_os = "macos"));
                 }
             }
 
@@ -532,11 +655,12 @@ fn lower_expression<'a>(expr: &Expression<'a>, symbols: &SymbolTable, traits: &c
                     let ty = cons.ty.clone();
                     return HirExpression {
                         kind: HirExprKind::If {
-                            condition: Box::new(HirExpression { kind: HirExprKind::Boolean(true), ty: Type::Bool }),
+                            condition: Box::new(HirExpression { kind: HirExprKind::Boolean(true), ty: Type::Bool, span: Span::default() }),
                             consequence: Box::new(cons),
                             alternative: None, // Il ramo Else svanisce nel nulla
                         },
                         ty,
+                        span: Span::default(),
                     };
                 } else {
                     // Solo alternative (se esiste), oppure rimpiazzato con un Null.
@@ -545,20 +669,23 @@ fn lower_expression<'a>(expr: &Expression<'a>, symbols: &SymbolTable, traits: &c
                         let ty = alt_stmt.ty.clone();
                         return HirExpression {
                             kind: HirExprKind::If {
-                                condition: Box::new(HirExpression { kind: HirExprKind::Boolean(false), ty: Type::Bool }),
+                                condition: Box::new(HirExpression { kind: HirExprKind::Boolean(false), ty: Type::Bool, span: Span::default() }),
                                 consequence: Box::new(HirStatement { 
-                                    kind: HirStmtKind::Expression { expression: HirExpression { kind: HirExprKind::Null, ty: Type::Void } },
+                                    kind: HirStmtKind::Expression { expression: HirExpression { kind: HirExprKind::Null, ty: Type::Void, span: Span::default() } },
                                     ty: Type::Void,
-                                    line: 0
+                                    line: 0,
+                                    span: Span::default(),
                                 }),
                                 alternative: Some(Box::new(alt_stmt)),
                             },
                             ty,
+                            span: Span::default(),
                         };
                     } else {
                         return HirExpression {
                             kind: HirExprKind::Null,
                             ty: Type::Void,
+                            span: Span::default(),
                         };
                     }
                 }
@@ -571,15 +698,33 @@ fn lower_expression<'a>(expr: &Expression<'a>, symbols: &SymbolTable, traits: &c
             HirExpression {
                 kind: HirExprKind::If { condition: Box::new(cond), consequence: Box::new(cons), alternative: alt },
                 ty,
+                span: Span::default(),
             }
         }
-        Expression::Call { function, arguments } => {
-            if let Expression::MemberAccess { object, member } = &**function {
+        ExpressionKind::Call { function, arguments } => {
+            if let ExpressionKind::MemberAccess { object, member } = &function.kind {
+                // `m.square(3)` on an `import`: a plain call of the linked
+                // function, typed like any extern.
+                if let Some(func) = lower_module_member(object, member, symbols, fresh, env) {
+                    let args: Vec<HirExpression> = arguments.iter()
+                        .map(|a| lower_expression(a, symbols, traits, fresh, env))
+                        .collect();
+                    let ty = match &func.ty {
+                        Type::Fn(_, ret) => (**ret).clone(),
+                        _ => fresh.fresh(),
+                    };
+                    return HirExpression {
+                        kind: HirExprKind::Call { function: Box::new(func), arguments: args },
+                        ty,
+                        span: Span::default(),
+                    };
+                }
+
                 // Flatten Builtin Methods at AST Boundary
                 fn stringify_ast_access(expr: &Expression) -> Option<String> {
-                    match expr {
-                        Expression::Identifier(name) => Some(name.clone()),
-                        Expression::MemberAccess { object, member } => {
+                    match &expr.kind {
+                        ExpressionKind::Identifier(name) => Some(name.to_string()),
+                        ExpressionKind::MemberAccess { object, member } => {
                             let base = stringify_ast_access(object)?;
                             Some(format!("{}.{}", base, member))
                         }
@@ -587,18 +732,89 @@ fn lower_expression<'a>(expr: &Expression<'a>, symbols: &SymbolTable, traits: &c
                     }
                 }
 
+                // Derived `EnumName.from_str(s)` (reflection-lite): a direct call
+                // of the compiler-generated `EnumName::from_str` global, typed
+                // `fn(str) -> Option<EnumName>` so a `match` on the result
+                // type-checks. An explicit `impl EnumName { fn from_str }` wins.
+                if let ExpressionKind::Identifier(type_name) = &object.kind {
+                    let user_defined = symbols.custom_types.get(*type_name)
+                        .is_some_and(|def| def.methods.contains_key(*member));
+                    let enum_ty = if *member == "from_str" && !user_defined && symbols.enums.contains_key(*type_name) {
+                        symbols.resolve(type_name).map(|s| instantiate_fresh(&s.ty, fresh))
+                    } else {
+                        None
+                    };
+                    if let Some(enum_ty) = enum_ty {
+                        let args: Vec<HirExpression> = arguments.iter()
+                            .map(|a| lower_expression(a, symbols, traits, fresh, env))
+                            .collect();
+                        let option_ty = Type::Custom { name: "Option".to_string(), args: vec![enum_ty] };
+                        return HirExpression {
+                            kind: HirExprKind::Call {
+                                function: Box::new(HirExpression {
+                                    kind: HirExprKind::Identifier(format!("{}::from_str", type_name)),
+                                    ty: Type::Fn(vec![Type::Str], Box::new(option_ty.clone())),
+                                    span: Span::default(),
+                                }),
+                                arguments: args,
+                            },
+                            ty: option_ty,
+                            span: Span::default(),
+                        };
+                    }
+
+                    // `ClassName.make(..)`: an associated function (no `self`
+                    // parameter) is a plain call of the flattened
+                    // `ClassName::make` global, typed by its declared signature.
+                    let static_ty = symbols.custom_types.get(*type_name)
+                        .filter(|def| def.static_methods.contains(*member) && !env.contains_key(*type_name))
+                        .and_then(|def| def.methods.get(*member))
+                        .map(|t| instantiate_fresh(t, fresh));
+                    if let Some(Type::Fn(params, ret)) = static_ty {
+                        let args: Vec<HirExpression> = arguments.iter()
+                            .map(|a| lower_expression(a, symbols, traits, fresh, env))
+                            .collect();
+                        return HirExpression {
+                            kind: HirExprKind::Call {
+                                function: Box::new(HirExpression {
+                                    kind: HirExprKind::Identifier(format!("{}::{}", type_name, member)),
+                                    ty: Type::Fn(params, ret.clone()),
+                                    span: Span::default(),
+                                }),
+                                arguments: args,
+                            },
+                            ty: *ret,
+                            span: Span::default(),
+                        };
+                    }
+                }
+
                 if let Some(parent_path) = stringify_ast_access(object) {
                     let full_path = format!("{}.{}", parent_path, member);
                     let is_builtin = full_path.starts_with("system.") 
-                        || full_path.starts_with("math.") 
+                        || full_path.starts_with("math.")
                         || full_path.starts_with("env.")
                         || full_path.starts_with("str.")
                         || full_path.starts_with("array.")
                         || full_path.starts_with("db.")
+                        || full_path.starts_with("io.")
+                        || full_path.starts_with("json.")
                         || full_path.starts_with("crypto.")
                         || full_path.starts_with("audio.")
                         || full_path.starts_with("graph.")
-                        || full_path.starts_with("llm.");
+                        || full_path.starts_with("llm.")
+                        || full_path.starts_with("sb.")
+                        || full_path.starts_with("random.")
+                        || full_path.starts_with("stats.")
+                        || full_path.starts_with("csv.")
+                        || full_path.starts_with("html.")
+                        || full_path.starts_with("template.")
+                        || full_path.starts_with("i18n.")
+                        || full_path.starts_with("decimal.")
+                        || full_path.starts_with("fs.")
+                        || full_path.starts_with("archive.")
+                        || full_path.starts_with("ipc.")
+                        || full_path.starts_with("data.xml.");
 
                     if is_builtin {
                         let args: Vec<HirExpression> = arguments.iter()
@@ -610,10 +826,12 @@ fn lower_expression<'a>(expr: &Expression<'a>, symbols: &SymbolTable, traits: &c
                                 function: Box::new(HirExpression {
                                     kind: HirExprKind::Identifier(full_path),
                                     ty: fresh.fresh(),
+                                    span: Span::default(),
                                 }),
                                 arguments: args,
                             },
                             ty,
+                            span: Span::default(),
                         };
                     }
                 }
@@ -624,8 +842,9 @@ fn lower_expression<'a>(expr: &Expression<'a>, symbols: &SymbolTable, traits: &c
                     .collect();
                 let ty = fresh.fresh();
                 return HirExpression {
-                    kind: HirExprKind::MethodCall { object: Box::new(obj), method: member.clone(), arguments: args },
+                    kind: HirExprKind::MethodCall { object: Box::new(obj), method: member.to_string(), arguments: args },
                     ty,
+                    span: Span::default(),
                 };
             }
 
@@ -637,9 +856,10 @@ fn lower_expression<'a>(expr: &Expression<'a>, symbols: &SymbolTable, traits: &c
             HirExpression {
                 kind: HirExprKind::Call { function: Box::new(func), arguments: args },
                 ty,
+                span: Span::default(),
             }
         }
-        Expression::FunctionLiteral { parameters, body, return_type } => {
+        ExpressionKind::FunctionLiteral { parameters, body, return_type } => {
             let params: Vec<(String, Type)> = parameters.iter()
                 .map(|(n, t)| (n.clone(), parse_type_hint(t)))
                 .collect();
@@ -655,9 +875,10 @@ fn lower_expression<'a>(expr: &Expression<'a>, symbols: &SymbolTable, traits: &c
             HirExpression {
                 kind: HirExprKind::FunctionLiteral { parameters: params, body: Box::new(hir_body), return_type: ret.clone() },
                 ty: Type::Fn(param_types, Box::new(ret)),
+                span: Span::default(),
             }
         }
-        Expression::ArrayLiteral(elems) => {
+        ExpressionKind::ArrayLiteral(elems) => {
             let hir_elems: Vec<HirExpression> = elems.iter()
                 .map(|e| lower_expression(e, symbols, traits, fresh, env))
                 .collect();
@@ -665,9 +886,10 @@ fn lower_expression<'a>(expr: &Expression<'a>, symbols: &SymbolTable, traits: &c
             HirExpression {
                 kind: HirExprKind::ArrayLiteral(hir_elems),
                 ty: Type::Array(Box::new(elem_ty)),
+                span: Span::default(),
             }
         }
-        Expression::MapLiteral(pairs) => {
+        ExpressionKind::MapLiteral(pairs) => {
             let hir_pairs: Vec<(HirExpression, HirExpression)> = pairs.iter()
                 .map(|(k, v)| (lower_expression(k, symbols, traits, fresh, env), lower_expression(v, symbols, traits, fresh, env)))
                 .collect();
@@ -677,21 +899,41 @@ fn lower_expression<'a>(expr: &Expression<'a>, symbols: &SymbolTable, traits: &c
             HirExpression {
                 kind: HirExprKind::MapLiteral(hir_pairs),
                 ty: Type::Map(Box::new(kt), Box::new(vt)),
+                span: Span::default(),
             }
         }
-        Expression::Index { left, index } => {
+        ExpressionKind::Index { left, index } => {
             let l = lower_expression(left, symbols, traits, fresh, env);
             let i = lower_expression(index, symbols, traits, fresh, env);
             let ty = fresh.fresh();
             HirExpression {
                 kind: HirExprKind::Index { left: Box::new(l), index: Box::new(i) },
                 ty,
+                span: Span::default(),
             }
         }
-        Expression::MemberAccess { object, member } => {
+        ExpressionKind::MemberAccess { object, member } => {
+            if let Some(func) = lower_module_member(object, member, symbols, fresh, env) {
+                return func;
+            }
             let obj = lower_expression(object, symbols, traits, fresh, env);
             let mut ty = fresh.fresh();
 
+            // `ClassName.NAME`: a class constant, typed like its declaration;
+            // read before the class is lowered, by its type annotation.
+            let class_const = match &object.kind {
+                ExpressionKind::Identifier(class_name) => env.get(&format!("{}::{}", class_name, member)).cloned().or_else(|| {
+                    symbols.custom_types.get(*class_name)
+                        .and_then(|def| def.constants.get(*member))
+                        .filter(|t| !matches!(t, Type::Var(_)))
+                        .cloned()
+                }),
+                _ => None,
+            };
+            if let Some(t) = class_const {
+                ty = t;
+            }
+
             // M2.3: Trait / Impl Method Resolution
             // If the object's type is known as Custom, we can attempt static dispatch
             if let Type::Custom { name: obj_type_name, .. } = &obj.ty {
@@ -702,7 +944,7 @@ fn lower_expression<'a>(expr: &Expression<'a>, symbols: &SymbolTable, traits: &c
             } else if let HirExprKind::Identifier(ref obj_name) = obj.kind {
                 // Intercept built-in standard library methods so Type Checker knows their signature
                 match obj_name.as_str() {
-                    "math" => match member.as_str() {
+                    "math" => match *member {
                         "sin" | "cos" | "tan" | "sqrt" | "abs" | "floor" | "ceil" | "round" 
                         | "asin" | "acos" | "atan" | "log" | "log10" | "exp" => {
                             ty = Type::Fn(vec![Type::Float], Box::new(Type::Float));
@@ -712,12 +954,12 @@ fn lower_expression<'a>(expr: &Expression<'a>, symbols: &SymbolTable, traits: &c
                         }
                         _ => {}
                     },
-                    "system" => match member.as_str() {
+                    "system" => match *member {
                         "time" => ty = Type::Fn(vec![], Box::new(Type::Float)),
                         "exit" => ty = Type::Fn(vec![Type::Int], Box::new(Type::Void)),
                         _ => {}
                     },
-                    "auth" => match member.as_str() {
+                    "auth" => match *member {
                         "login" => ty = Type::Fn(vec![Type::Str, Type::Str], Box::new(Type::Bool)),
                         "role" => ty = Type::Fn(vec![], Box::new(Type::Str)),
                         _ => {}
@@ -727,25 +969,28 @@ fn lower_expression<'a>(expr: &Expression<'a>, symbols: &SymbolTable, traits: &c
             }
 
             HirExpression {
-                kind: HirExprKind::MemberAccess { object: Box::new(obj), member: member.clone() },
+                kind: HirExprKind::MemberAccess { object: Box::new(obj), member: member.to_string() },
                 ty,
+                span: Span::default(),
             }
         }
-        Expression::Assign { target, value } => {
+        ExpressionKind::Assign { target, value } => {
             let t = lower_expression(target, symbols, traits, fresh, env);
             let v = lower_expression(value, symbols, traits, fresh, env);
             let ty = v.ty.clone();
             HirExpression {
                 kind: HirExprKind::Assign { target: Box::new(t), value: Box::new(v) },
                 ty,
+                span: Span::default(),
             }
         }
-        Expression::Range { start, end } => {
+        ExpressionKind::Range { start, end } => {
             let s = lower_expression(start, symbols, traits, fresh, env);
             let e = lower_expression(end, symbols, traits, fresh, env);
             HirExpression {
                 kind: HirExprKind::Range { start: Box::new(s), end: Box::new(e) },
-                ty: Type::Array(Box::new(Type::Int)), // ranges are int arrays
+                ty: Type::Array(Box::new(Type::Int)), // ranges are int arrays,
+                span: Span::default(),
             }
         }
     }
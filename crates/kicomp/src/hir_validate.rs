@@ -5,30 +5,36 @@
 /// Checks enforced:
 /// 1. **Duplicate function parameters** — Two params with the same name.
 /// 2. **Unreachable statements** — Code after unconditional `return` or `break` in a block.
+/// 3. **Unknown loop labels** — `break 'x` / `continue 'x` with no enclosing `'x:` loop.
 ///
 /// Note: Type::Var in the HIR is expected (Hindley-Milner). Unresolved type variables
 /// are caught later by mono_validate after MIR lowering + monomorphization.
 
 use crate::hir::{HirProgram, HirStatement, HirStmtKind, HirExpression, HirExprKind, HirPattern};
 use crate::types::Type;
+use kinetix_language::diagnostic::{codes, Diagnostic, Severity};
+use kinetix_language::span::Span;
 
 /// Validate an entire HIR program.
-/// Returns `Ok(())` if the HIR is structurally sound, or a list of diagnostic errors.
-pub fn validate(program: &HirProgram) -> Result<(), Vec<String>> {
-    let mut errors: Vec<String> = Vec::new();
+/// Returns `Ok(warnings)` (unreachable code) if the HIR is structurally
+/// sound, or the errors otherwise.
+pub fn validate(program: &HirProgram) -> Result<Vec<Diagnostic>, Vec<Diagnostic>> {
+    let mut diagnostics: Vec<Diagnostic> = Vec::new();
 
     for stmt in &program.statements {
-        validate_statement(stmt, &mut errors);
+        validate_statement(stmt, &mut diagnostics);
+        check_loop_labels(stmt, &mut Vec::new(), &mut diagnostics);
     }
 
+    let (warnings, errors): (Vec<_>, Vec<_>) = diagnostics.into_iter().partition(|d| d.severity == Severity::Warning);
     if errors.is_empty() {
-        Ok(())
+        Ok(warnings)
     } else {
         Err(errors)
     }
 }
 
-fn validate_statement(stmt: &HirStatement, errors: &mut Vec<String>) {
+fn validate_statement(stmt: &HirStatement, errors: &mut Vec<Diagnostic>) {
     match &stmt.kind {
         HirStmtKind::Let { value, .. }
         | HirStmtKind::State { value, .. }
@@ -38,7 +44,7 @@ fn validate_statement(stmt: &HirStatement, errors: &mut Vec<String>) {
 
         HirStmtKind::Function { name, parameters, body, .. } => {
             // Check for duplicate parameter names
-            check_duplicate_params(name, parameters, stmt.line, errors);
+            check_duplicate_params(name, parameters, stmt.location(), errors);
             validate_statement(body, errors);
         }
 
@@ -65,7 +71,7 @@ fn validate_statement(stmt: &HirStatement, errors: &mut Vec<String>) {
             validate_expression(expression, errors);
         }
 
-        HirStmtKind::While { condition, body } => {
+        HirStmtKind::While { condition, body, .. } => {
             validate_expression(condition, errors);
             validate_statement(body, errors);
         }
@@ -79,11 +85,11 @@ fn validate_statement(stmt: &HirStatement, errors: &mut Vec<String>) {
             validate_statement(body, errors);
         }
 
-        HirStmtKind::Break | HirStmtKind::Continue => {}
+        HirStmtKind::Break { .. } | HirStmtKind::Continue { .. } => {}
     }
 }
 
-fn validate_expression(expr: &HirExpression, errors: &mut Vec<String>) {
+fn validate_expression(expr: &HirExpression, errors: &mut Vec<Diagnostic>) {
     match &expr.kind {
         HirExprKind::Integer(_) | HirExprKind::Float(_) | HirExprKind::String(_)
         | HirExprKind::Boolean(_) | HirExprKind::Null | HirExprKind::Identifier(_) => {}
@@ -113,7 +119,7 @@ fn validate_expression(expr: &HirExpression, errors: &mut Vec<String>) {
         }
 
         HirExprKind::FunctionLiteral { parameters, body, .. } => {
-            check_duplicate_params("<lambda>", parameters, 0, errors);
+            check_duplicate_params("<lambda>", parameters, body.location(), errors);
             validate_statement(body, errors);
         }
 
@@ -162,7 +168,7 @@ fn validate_expression(expr: &HirExpression, errors: &mut Vec<String>) {
             validate_expression(end, errors);
         }
 
-        HirExprKind::Match { value, arms } => {
+        HirExprKind::Match { value, arms, .. } => {
             validate_expression(value, errors);
             for (pattern, body) in arms {
                 validate_pattern(pattern, errors);
@@ -172,46 +178,111 @@ fn validate_expression(expr: &HirExpression, errors: &mut Vec<String>) {
     }
 }
 
-fn validate_pattern(pattern: &HirPattern, errors: &mut Vec<String>) {
+fn validate_pattern(pattern: &HirPattern, errors: &mut Vec<Diagnostic>) {
     match pattern {
         HirPattern::Literal(expr) => validate_expression(expr, errors),
-        HirPattern::Variant { .. } | HirPattern::Wildcard | HirPattern::Binding(_) => {}
+        HirPattern::Range { start, end } => {
+            validate_expression(start, errors);
+            validate_expression(end, errors);
+        }
+        HirPattern::Variant { payload: Some(p), .. } => validate_pattern(p, errors),
+        HirPattern::Struct { fields, .. } => {
+            for (_, p) in fields {
+                validate_pattern(p, errors);
+            }
+        }
+        HirPattern::Variant { payload: None, .. } | HirPattern::Type { .. } | HirPattern::Wildcard | HirPattern::Binding(_) => {}
     }
 }
 
 
 /// Check for duplicate parameter names in a function.
-fn check_duplicate_params(fn_name: &str, params: &[(String, Type)], line: usize, errors: &mut Vec<String>) {
+fn check_duplicate_params(fn_name: &str, params: &[(String, Type)], at: Span, errors: &mut Vec<Diagnostic>) {
     let mut seen = std::collections::HashSet::new();
     for (name, _) in params {
         if !seen.insert(name.as_str()) {
-            errors.push(format!(
-                "HIR Integrity Error (line {}): Duplicate parameter '{}' in function '{}'",
-                line, name, fn_name
-            ));
+            let message = format!("Duplicate parameter '{}' in function '{}'", name, fn_name);
+            errors.push(Diagnostic::error(codes::SYMBOL, message).at(at));
+        }
+    }
+}
+
+/// Check that every labeled `break`/`continue` names a loop enclosing it.
+/// `labels` is the stack of loop labels in scope; a function body starts a
+/// fresh stack, since a jump can never leave the function it's in.
+fn check_loop_labels(stmt: &HirStatement, labels: &mut Vec<Option<String>>, errors: &mut Vec<Diagnostic>) {
+    match &stmt.kind {
+        HirStmtKind::While { body, label, .. } | HirStmtKind::For { body, label, .. } => {
+            labels.push(label.clone());
+            check_loop_labels(body, labels, errors);
+            labels.pop();
+        }
+        HirStmtKind::Break { label: Some(name) } | HirStmtKind::Continue { label: Some(name) }
+            if !labels.iter().any(|l| l.as_deref() == Some(name.as_str())) =>
+        {
+            let keyword = if matches!(stmt.kind, HirStmtKind::Break { .. }) { "break" } else { "continue" };
+            let message = format!("Unknown loop label '{}' in '{}'", name, keyword);
+            errors.push(Diagnostic::error(codes::SYMBOL, message).at(stmt.location()));
         }
+        HirStmtKind::Block { statements } => {
+            for s in statements {
+                check_loop_labels(s, labels, errors);
+            }
+        }
+        HirStmtKind::Function { body, .. } | HirStmtKind::Effect { body, .. } => {
+            check_loop_labels(body, &mut Vec::new(), errors);
+        }
+        HirStmtKind::Class { methods, .. } => {
+            for m in methods {
+                check_loop_labels(m, &mut Vec::new(), errors);
+            }
+        }
+        HirStmtKind::Expression { expression: value }
+        | HirStmtKind::Let { value, .. }
+        | HirStmtKind::Return { value: Some(value) } => check_expression_labels(value, labels, errors),
+        _ => {}
+    }
+}
+
+/// The statement-bearing expressions (`if`, `match`, lambdas) that
+/// `check_loop_labels` has to look inside.
+fn check_expression_labels(expr: &HirExpression, labels: &mut Vec<Option<String>>, errors: &mut Vec<Diagnostic>) {
+    match &expr.kind {
+        HirExprKind::If { consequence, alternative, .. } => {
+            check_loop_labels(consequence, labels, errors);
+            if let Some(alt) = alternative {
+                check_loop_labels(alt, labels, errors);
+            }
+        }
+        HirExprKind::Match { arms, .. } => {
+            for (_, body) in arms {
+                check_loop_labels(body, labels, errors);
+            }
+        }
+        HirExprKind::FunctionLiteral { body, .. } => check_loop_labels(body, &mut Vec::new(), errors),
+        _ => {}
     }
 }
 
 /// Check for unreachable statements after an unconditional `return` or `break`.
-fn check_unreachable_stmts(stmts: &[HirStatement], errors: &mut Vec<String>) {
+fn check_unreachable_stmts(stmts: &[HirStatement], errors: &mut Vec<Diagnostic>) {
     for (i, stmt) in stmts.iter().enumerate() {
         let is_terminal = matches!(&stmt.kind,
-            HirStmtKind::Return { .. } | HirStmtKind::Break | HirStmtKind::Continue
+            HirStmtKind::Return { .. } | HirStmtKind::Break { .. } | HirStmtKind::Continue { .. }
         );
         if is_terminal && i + 1 < stmts.len() {
             let next = &stmts[i + 1];
-            errors.push(format!(
-                "HIR Integrity Warning (line {}): Unreachable statement after {:?} at line {}",
-                next.line,
+            let message = format!(
+                "Unreachable statement after '{}' on line {}",
                 match &stmt.kind {
                     HirStmtKind::Return { .. } => "return",
-                    HirStmtKind::Break => "break",
-                    HirStmtKind::Continue => "continue",
+                    HirStmtKind::Break { .. } => "break",
+                    HirStmtKind::Continue { .. } => "continue",
                     _ => "terminal",
                 },
-                stmt.line
-            ));
+                stmt.location().line
+            );
+            errors.push(Diagnostic::warning(codes::LINT, message).at(next.location()));
             break; // Only report the first unreachable statement
         }
     }
@@ -224,11 +295,11 @@ mod tests {
     use crate::types::Type;
 
     fn make_expr(kind: HirExprKind, ty: Type) -> HirExpression {
-        HirExpression { kind, ty }
+        HirExpression { kind, ty, span: Span::default() }
     }
 
     fn make_stmt(kind: HirStmtKind, ty: Type, line: usize) -> HirStatement {
-        HirStatement { kind, ty, line }
+        HirStatement { kind, ty, line, span: Default::default() }
     }
 
     #[test]
@@ -292,10 +363,8 @@ mod tests {
                 ),
             ],
         };
-        let result = validate(&program);
-        assert!(result.is_err());
-        let errs = result.unwrap_err();
-        assert!(errs.iter().any(|e| e.contains("Duplicate parameter 'a'")));
+        let errs = validate(&program).unwrap_err();
+        assert!(errs.iter().any(|e| e.code == codes::SYMBOL && e.message == "Duplicate parameter 'a' in function 'bad_fn'"));
     }
 
     #[test]
@@ -322,9 +391,35 @@ mod tests {
                 ),
             ],
         };
-        let result = validate(&program);
-        assert!(result.is_err());
-        let errs = result.unwrap_err();
-        assert!(errs.iter().any(|e| e.contains("Unreachable")));
+        let warnings = validate(&program).expect("unreachable code is only a warning");
+        assert_eq!(warnings.len(), 1);
+        assert_eq!((warnings[0].code, warnings[0].severity), (codes::LINT, Severity::Warning));
+        assert_eq!(warnings[0].message, "Unreachable statement after 'return' on line 1");
+    }
+
+    #[test]
+    fn test_unknown_loop_label() {
+        let labeled_break = |name: &str, line| make_stmt(HirStmtKind::Break { label: Some(name.to_string()) }, Type::Void, line);
+        let program = HirProgram {
+            statements: vec![
+                make_stmt(
+                    HirStmtKind::While {
+                        condition: make_expr(HirExprKind::Boolean(true), Type::Bool),
+                        body: Box::new(make_stmt(
+                            HirStmtKind::Block { statements: vec![labeled_break("outer", 2), labeled_break("nope", 3)] },
+                            Type::Void,
+                            1,
+                        )),
+                        label: Some("outer".to_string()),
+                    },
+                    Type::Void,
+                    1,
+                ),
+            ],
+        };
+        let errs = validate(&program).unwrap_err();
+        let unknown = errs.iter().find(|e| e.message == "Unknown loop label 'nope' in 'break'").expect("unknown label reported");
+        assert_eq!((unknown.code, unknown.span().map(|s| s.line)), (codes::SYMBOL, Some(3)));
+        assert!(!errs.iter().any(|e| e.message.contains("'outer")), "'outer encloses the first break");
     }
 }
@@ -0,0 +1,219 @@
+//! On-disk cache for `kivm compile`: reuses a file's bytecode, or the MIR
+//! checks of unchanged functions, across compiles.
+
+use crate::ir::CompiledProgram;
+use crate::ir_hash::DeterministicHasher;
+use crate::mir::{BasicBlockData, MirFunction, MirProgram, Terminator, TerminatorKind};
+use crate::session::CachedPhase;
+use kinetix_language::span::Span;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+/// How many checked function hashes are kept; the oldest go first.
+const MAX_CHECKED: usize = 1 << 16;
+
+/// A MIR check whose per-function results are cached.
+#[derive(Debug, Clone, Copy, Hash)]
+pub enum Check {
+    /// The borrow checker, on MIR before monomorphization.
+    Borrow,
+    /// Drop order and SSA integrity, on monomorphized MIR.
+    Integrity,
+}
+
+impl Check {
+    fn name(self) -> &'static str {
+        match self {
+            Check::Borrow => "Borrow Check",
+            Check::Integrity => "MIR Integrity",
+        }
+    }
+}
+
+/// The cache of one input. Entries carry the compiler build, and a missing
+/// or unreadable cache is an empty one.
+pub struct Cache {
+    dir: PathBuf,
+    /// Names this input's bytecode files: a hash of its path.
+    unit: String,
+    /// Hashes of the (check, function) pairs that passed, oldest first.
+    checked: Vec<u64>,
+    known: HashSet<u64>,
+    /// What each cached step did, for `--time-passes`.
+    pub phases: Vec<CachedPhase>,
+}
+
+impl Cache {
+    /// The cache in `dir` as seen by `input`.
+    pub fn open(dir: &Path, input: &Path) -> Self {
+        let mut path = DeterministicHasher::new();
+        input.canonicalize().unwrap_or_else(|_| input.to_path_buf()).hash(&mut path);
+        let checked = read_checked(&dir.join("checked"));
+        Cache {
+            dir: dir.to_path_buf(),
+            unit: format!("{:016x}", path.finish()),
+            known: checked.iter().copied().collect(),
+            checked,
+            phases: Vec::new(),
+        }
+    }
+
+    /// The key of a file's bytecode: its typed HIR, its source (for line
+    /// maps), the programs it links and the optimization level.
+    pub fn key(hir: &crate::hir::HirProgram, source: &str, linked: &[&CompiledProgram], opt_level: u8) -> u64 {
+        let mut hasher = DeterministicHasher::new();
+        (crate::compiler::CURRENT_BUILD, crate::ir_hash::hash_hir_program(hir), source, opt_level).hash(&mut hasher);
+        for program in linked {
+            // Linked programs only matter through what gets compiled in
+            let mut bytes = Vec::new();
+            if crate::exn::write_exn(&mut bytes, program).is_ok() {
+                bytes.hash(&mut hasher);
+            }
+        }
+        hasher.finish()
+    }
+
+    /// The bytecode stored under `key`, if any.
+    pub fn program(&self, key: u64) -> Option<CompiledProgram> {
+        let mut file = std::fs::File::open(self.program_path(key)).ok()?;
+        crate::exn::read_exn(&mut file).ok()
+    }
+
+    /// Stores `program` under `key`, replacing what this input stored
+    /// before, and saves the checked functions.
+    pub fn store(&mut self, key: u64, program: &CompiledProgram) -> Result<(), String> {
+        std::fs::create_dir_all(&self.dir).map_err(|e| format!("Error creating {}: {}", self.dir.display(), e))?;
+        let path = self.program_path(key);
+        let mut bytes = Vec::new();
+        crate::exn::write_exn(&mut bytes, program).map_err(|e| e.to_string())?;
+        write_atomically(&path, &bytes)?;
+        for entry in std::fs::read_dir(&self.dir).into_iter().flatten().flatten() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.starts_with(&self.unit) && entry.path() != path {
+                let _ = std::fs::remove_file(entry.path());
+            }
+        }
+        self.save_checked()
+    }
+
+    /// Records that a file's bytecode came from the cache.
+    pub fn reused_program(&mut self) {
+        self.phases.push(CachedPhase { name: "Bytecode", duration_ms: 0.0, reused: 1, rebuilt: 0 });
+    }
+
+    /// `program` with the functions that passed `check` before reduced to a
+    /// bare `return`, so running the check on it checks only the others. Also returns how
+    /// many were skipped.
+    fn unchecked(&self, check: Check, program: &MirProgram) -> (MirProgram, usize) {
+        let mut skipped = 0;
+        let mut keep = |func: &MirFunction| {
+            if self.known.contains(&function_key(check, func)) {
+                skipped += 1;
+                let block = BasicBlockData {
+                    statements: Vec::new(),
+                    terminator: Some(Terminator { kind: TerminatorKind::Return(None), span: Span::default() }),
+                };
+                MirFunction { name: func.name.clone(), args: Vec::new(), return_ty: func.return_ty.clone(), locals: Vec::new(), basic_blocks: vec![block] }
+            } else {
+                func.clone()
+            }
+        };
+        let functions = program.functions.iter().map(&mut keep).collect();
+        let main_block = keep(&program.main_block);
+        (MirProgram { functions, main_block }, skipped)
+    }
+
+    /// Records that every function of `program` passed `check`.
+    fn mark_checked(&mut self, check: Check, program: &MirProgram) {
+        for func in program.functions.iter().chain(std::iter::once(&program.main_block)) {
+            let key = function_key(check, func);
+            if self.known.insert(key) {
+                self.checked.push(key);
+            }
+        }
+    }
+
+    fn program_path(&self, key: u64) -> PathBuf {
+        self.dir.join(format!("{}-{:016x}.exki", self.unit, key))
+    }
+
+    /// Merges with what other compiles saved meanwhile, then writes.
+    fn save_checked(&mut self) -> Result<(), String> {
+        let path = self.dir.join("checked");
+        let mut checked = read_checked(&path);
+        let mut seen: HashSet<u64> = checked.iter().copied().collect();
+        checked.extend(self.checked.iter().filter(|key| seen.insert(**key)));
+        let start = checked.len().saturating_sub(MAX_CHECKED);
+        let text: String = checked[start..].iter().map(|key| format!("{:016x}\n", key)).collect();
+        write_atomically(&path, text.as_bytes())
+    }
+}
+
+/// Runs `run` on the functions of `program` that haven't passed `check`
+/// before, and records them once it succeeds; without a cache, on all of them.
+pub fn run_check<E>(cache: Option<&mut Cache>, check: Check, program: &MirProgram, run: impl FnOnce(&MirProgram) -> Result<(), E>) -> Result<(), E> {
+    let Some(cache) = cache else { return run(program) };
+    let start = Instant::now();
+    let (unchecked, reused) = cache.unchecked(check, program);
+    run(&unchecked)?;
+    cache.mark_checked(check, program);
+    let rebuilt = program.functions.len() + 1 - reused;
+    cache.phases.push(CachedPhase { name: check.name(), duration_ms: start.elapsed().as_secs_f64() * 1000.0, reused, rebuilt });
+    Ok(())
+}
+
+fn function_key(check: Check, func: &MirFunction) -> u64 {
+    let mut hasher = DeterministicHasher::new();
+    (crate::compiler::CURRENT_BUILD, check, func).hash(&mut hasher);
+    hasher.finish()
+}
+
+fn read_checked(path: &Path) -> Vec<u64> {
+    std::fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| u64::from_str_radix(line, 16).ok())
+        .collect()
+}
+
+/// Writes through `write_atomic`, so a concurrent compile never reads half a
+/// file and a crash never leaves one.
+fn write_atomically(path: &Path, bytes: &[u8]) -> Result<(), String> {
+    kinetix_language::atomic::write_atomic(path, bytes).map_err(|e| format!("Error writing {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Type;
+
+    fn function(name: &str) -> MirFunction {
+        MirFunction { name: name.to_string(), args: Vec::new(), return_ty: Type::Void, locals: Vec::new(), basic_blocks: Vec::new() }
+    }
+
+    #[test]
+    fn checked_functions_are_skipped_across_compiles() {
+        let dir = std::env::temp_dir().join(format!("kinetix-incremental-{}", std::process::id()));
+        let input = dir.join("app.kix");
+        let program = MirProgram { functions: vec![function("a"), function("b")], main_block: function("main") };
+
+        let mut cache = Cache::open(&dir, &input);
+        assert_eq!(cache.unchecked(Check::Borrow, &program).1, 0);
+        cache.mark_checked(Check::Borrow, &program);
+        let compiled = CompiledProgram::new();
+        cache.store(7, &compiled).unwrap();
+        cache.store(8, &compiled).unwrap();
+
+        let mut edited = program.clone();
+        edited.functions[1].name = "c".to_string();
+        let cache = Cache::open(&dir, &input);
+        assert_eq!(cache.unchecked(Check::Borrow, &edited).1, 2);
+        assert_eq!(cache.unchecked(Check::Integrity, &edited).1, 0);
+        // Only the latest bytecode of an input is kept
+        assert!(cache.program(7).is_none());
+        assert!(cache.program(8).is_some());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,156 @@
+//! `--instrument`: code the compiler adds to a program for observability.
+
+use crate::ir::{CompiledFunction, CompiledProgram, Constant, Instruction, Opcode, Operand, MAX_OPERAND};
+
+/// What `--instrument` adds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Instrument {
+    /// Enter/exit events with durations for every function
+    Spans,
+}
+
+/// Adds `what` to `program`.
+pub fn apply(program: &mut CompiledProgram, what: Instrument) {
+    match what {
+        Instrument::Spans => {
+            spans(program);
+        }
+    }
+}
+
+/// Adds span calls to every function of `program`; returns how many.
+/// `main`, bundle init chunks and the built-in `Option`/`Result`
+/// constructors are left alone.
+///
+/// Each function starts by calling `log.span_enter(name)` and calls
+/// `log.span_exit(name, start)` before each `return` (and tail call), so the
+/// runtime log sees every call with its duration. This runs on the finished
+/// bytecode, after the optimizer, so neither the checks nor inlining see the
+/// extra calls. The calls use four registers past the function's own; a
+/// function that would outgrow the operand range is left as it is.
+pub fn spans(program: &mut CompiledProgram) -> usize {
+    program.functions.iter_mut()
+        .filter(|func| !func.name.starts_with('<') && !func.name.starts_with("Option::") && !func.name.starts_with("Result::"))
+        .filter_map(|func| instrument_function(func).then_some(()))
+        .count()
+}
+
+fn instrument_function(func: &mut CompiledFunction) -> bool {
+    let exits = func.instructions.iter()
+        .filter(|i| matches!(i.opcode, Opcode::Return | Opcode::ReturnVoid | Opcode::TailCall))
+        .count();
+    if func.locals as usize + 4 > MAX_OPERAND || func.instructions.len() + 3 + 4 * exits > MAX_OPERAND {
+        return false;
+    }
+    // `start` holds what span_enter returned; the calls go in `call`..`call + 2`
+    let start = func.locals;
+    let call = start + 1;
+    func.locals += 4;
+    let enter = func.add_constant(Constant::String("log.span_enter".to_string()));
+    let exit = func.add_constant(Constant::String("log.span_exit".to_string()));
+    let name = func.add_constant(Constant::String(func.name.clone()));
+
+    let old = std::mem::take(&mut func.instructions);
+    let has_lines = func.line_map.len() == old.len();
+    let old_lines = std::mem::take(&mut func.line_map);
+    let first_line = old_lines.first().copied().unwrap_or(0);
+    let has_columns = func.column_map.len() == old.len();
+    let old_columns = std::mem::take(&mut func.column_map);
+    let first_column = old_columns.first().copied().unwrap_or(0);
+    func.instructions.extend([
+        Instruction::ab(Opcode::GetGlobal, call, enter),
+        Instruction::ab(Opcode::LoadConst, call + 1, name),
+        Instruction::ab(Opcode::Call, call, 1),
+        Instruction::ab(Opcode::SetLocal, start, call),
+    ]);
+    if has_lines {
+        func.line_map.extend([first_line; 4]);
+    }
+    if has_columns {
+        func.column_map.extend([first_column; 4]);
+    }
+    // Old index -> new index; jumps to a return land on its span_exit
+    let mut index_map = Vec::with_capacity(old.len() + 1);
+    for (i, instr) in old.iter().enumerate() {
+        index_map.push(func.instructions.len());
+        let mut emitted = 1;
+        if matches!(instr.opcode, Opcode::Return | Opcode::ReturnVoid | Opcode::TailCall) {
+            func.instructions.extend([
+                Instruction::ab(Opcode::GetGlobal, call, exit),
+                Instruction::ab(Opcode::LoadConst, call + 1, name),
+                Instruction::ab(Opcode::SetLocal, call + 2, start),
+                Instruction::ab(Opcode::Call, call, 2),
+            ]);
+            emitted += 4;
+        }
+        func.instructions.push(*instr);
+        if has_lines {
+            func.line_map.extend(std::iter::repeat_n(old_lines[i], emitted));
+        }
+        if has_columns {
+            func.column_map.extend(std::iter::repeat_n(old_columns[i], emitted));
+        }
+    }
+    index_map.push(func.instructions.len());
+
+    for h in &mut func.exception_table {
+        h.start = index_map[h.start as usize] as u32;
+        h.end = index_map[h.end as usize] as u32;
+        h.handler = index_map[h.handler as usize] as u32;
+    }
+    for instr in func.instructions.iter_mut() {
+        let info = instr.opcode.info();
+        for (kind, operand) in [(info.a, &mut instr.a), (info.b, &mut instr.b), (info.c, &mut instr.c)] {
+            if kind == Operand::Target && let Some(&new) = index_map.get(*operand as usize) {
+                *operand = new as u16;
+            }
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::Compiler;
+    use bumpalo::Bump;
+    use kinetix_language::lexer::Lexer;
+    use kinetix_language::parser::Parser;
+
+    fn compile(source: &str) -> CompiledProgram {
+        let arena = Bump::new();
+        let mut parser = Parser::new(Lexer::new(source), &arena);
+        let program = parser.parse_program();
+        assert!(parser.errors.is_empty(), "Parser errors: {:?}", parser.errors);
+        Compiler::new().compile(&program.statements, None).expect("compiles").clone()
+    }
+
+    #[test]
+    fn every_exit_ends_the_span_and_jumps_still_land() {
+        let mut program = compile("fn sign(n: int) -> int {\n  if n < 0 { return -1 }\n  if n == 0 { return 0 }\n  return 1\n}\nprintln(sign(3))\n");
+        let before = program.clone();
+        assert_eq!(spans(&mut program), 1);
+        assert_eq!(program.main.instructions.len(), before.main.instructions.len());
+
+        let at = program.functions.iter().position(|f| f.name == "sign").expect("sign compiled");
+        let (old, func) = (&before.functions[at], &program.functions[at]);
+        assert_eq!(func.locals, old.locals + 4);
+        assert_eq!(func.line_map.len(), func.instructions.len());
+        assert_eq!(func.instructions[2].opcode, Opcode::Call);
+        for (i, instr) in func.instructions.iter().enumerate() {
+            if matches!(instr.opcode, Opcode::Return | Opcode::ReturnVoid) {
+                let end = func.instructions[i - 1];
+                assert_eq!((end.opcode, end.a, end.b), (Opcode::Call, old.locals + 1, 2));
+            }
+            let info = instr.opcode.info();
+            for (kind, target) in [(info.a, instr.a), (info.b, instr.b), (info.c, instr.c)] {
+                if kind == Operand::Target {
+                    // Either the same instruction as before or the span_exit ahead of a return
+                    let landed = func.instructions[target as usize].opcode;
+                    assert!(landed != Opcode::Call, "jump into the span prologue");
+                    assert!(old.instructions.iter().any(|o| o.opcode == landed), "{:?}", landed);
+                }
+            }
+        }
+    }
+}
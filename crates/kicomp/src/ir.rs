@@ -2,6 +2,7 @@
 /// Register-based bytecode format for KiVM.
 
 use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
 
 /// Each opcode encodes an operation for the register-based VM.
 /// Instruction format: (Opcode, A, B, C) where A/B/C are register indices
@@ -15,6 +16,7 @@ use serde::{Serialize, Deserialize};
 // discriminant, and nothing else in the codebase casts an Opcode to its u8
 // value -- verified via grep before this change. New opcodes should just be
 // appended at the end; there's no serialization reason to reserve gaps.
+// Append them to `Opcode::ALL` too and describe them in `Opcode::info`.
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 #[repr(u8)]
 pub enum Opcode {
@@ -129,6 +131,10 @@ pub enum Opcode {
     Nop,
     /// Halt execution
     Halt,
+
+    /// Jump table: the C instructions that follow are `Jump`s; land on the
+    /// (A - const[B])th when A is an int in that range, else skip past them
+    JumpTable,
 }
 
 /// A single bytecode instruction: opcode + 3 operands.
@@ -156,6 +162,148 @@ impl Instruction {
     }
 }
 
+/// Operands are `u16`, so a function can hold at most this many constants,
+/// registers and, once it jumps, instructions (a jump may target one past
+/// the last). Both
+/// code generators saturate register numbers here rather than wrap, and
+/// `CompiledFunction::check_operand_widths` turns that into an error.
+pub const MAX_OPERAND: usize = u16::MAX as usize;
+
+/// What an operand slot of an instruction holds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operand {
+    /// Not used by the opcode (emitted as 0).
+    Unused,
+    /// A register that is read.
+    Read,
+    /// A register that is written.
+    Write,
+    /// A register that is read and then overwritten in place.
+    Update,
+    /// An index into the function's constant pool.
+    Const,
+    /// An absolute instruction index to jump to.
+    Target,
+    /// A count of consecutive registers (arguments, elements, pairs).
+    Count,
+}
+
+impl Operand {
+    pub fn name(self) -> &'static str {
+        match self {
+            Operand::Unused => "-",
+            Operand::Read => "reg (read)",
+            Operand::Write => "reg (write)",
+            Operand::Update => "reg (read/write)",
+            Operand::Const => "const",
+            Operand::Target => "target",
+            Operand::Count => "count",
+        }
+    }
+}
+
+/// The structured description of an opcode: its A/B/C operands and its
+/// effect, as the VM implements it.
+#[derive(Debug, Clone, Copy)]
+pub struct OpcodeInfo {
+    pub a: Operand,
+    pub b: Operand,
+    pub c: Operand,
+    pub effect: &'static str,
+}
+
+impl Opcode {
+    /// Every opcode, in discriminant order.
+    pub const ALL: &'static [Opcode] = &[
+        Opcode::LoadConst, Opcode::LoadNull, Opcode::LoadTrue, Opcode::LoadFalse,
+        Opcode::Add, Opcode::Sub, Opcode::Mul, Opcode::Div, Opcode::Mod, Opcode::Neg,
+        Opcode::Eq, Opcode::Neq, Opcode::Lt, Opcode::Gt, Opcode::Lte, Opcode::Gte,
+        Opcode::Not, Opcode::And, Opcode::Or, Opcode::Concat,
+        Opcode::GetLocal, Opcode::SetLocal, Opcode::GetGlobal, Opcode::SetGlobal,
+        Opcode::SetState, Opcode::InitComputed, Opcode::UpdateState, Opcode::InitEffect,
+        Opcode::GetMember, Opcode::SetMember, Opcode::GetIndex, Opcode::SetIndex,
+        Opcode::MakeArray, Opcode::MakeMap, Opcode::MakeRange, Opcode::GetIter, Opcode::IterNext,
+        Opcode::Jump, Opcode::JumpIfFalse, Opcode::JumpIfTrue,
+        Opcode::Call, Opcode::Return, Opcode::ReturnVoid, Opcode::MakeClosure, Opcode::TailCall, Opcode::LoadMethod,
+        Opcode::Print, Opcode::Pop, Opcode::Nop, Opcode::Halt,
+        Opcode::JumpTable,
+    ];
+
+    /// The operand and effect annotation of this opcode. The match is
+    /// exhaustive, so a new opcode cannot be added without describing it;
+    /// `kivm internals opcodes` prints these as the instruction reference.
+    pub fn info(self) -> OpcodeInfo {
+        use Operand::*;
+        let (a, b, c, effect) = match self {
+            Opcode::LoadConst => (Write, Const, Unused, "A = const[B]"),
+            Opcode::LoadNull => (Write, Unused, Unused, "A = null"),
+            Opcode::LoadTrue => (Write, Unused, Unused, "A = true"),
+            Opcode::LoadFalse => (Write, Unused, Unused, "A = false"),
+            Opcode::Add => (Write, Read, Read, "A = B + C"),
+            Opcode::Sub => (Write, Read, Read, "A = B - C"),
+            Opcode::Mul => (Write, Read, Read, "A = B * C"),
+            Opcode::Div => (Write, Read, Read, "A = B / C"),
+            Opcode::Mod => (Write, Read, Read, "A = B % C"),
+            Opcode::Neg => (Write, Read, Unused, "A = -B"),
+            Opcode::Eq => (Write, Read, Read, "A = B == C"),
+            Opcode::Neq => (Write, Read, Read, "A = B != C"),
+            Opcode::Lt => (Write, Read, Read, "A = B < C"),
+            Opcode::Gt => (Write, Read, Read, "A = B > C"),
+            Opcode::Lte => (Write, Read, Read, "A = B <= C"),
+            Opcode::Gte => (Write, Read, Read, "A = B >= C"),
+            Opcode::Not => (Write, Read, Unused, "A = !B"),
+            Opcode::And => (Write, Read, Read, "A = B && C"),
+            Opcode::Or => (Write, Read, Read, "A = B || C"),
+            Opcode::Concat => (Write, Read, Read, "A = B + C (strings)"),
+            Opcode::GetLocal => (Write, Read, Unused, "A = B (local slot B)"),
+            Opcode::SetLocal => (Write, Read, Unused, "A = B (local slot A)"),
+            Opcode::GetGlobal => (Write, Const, Unused, "A = global named const[B]"),
+            Opcode::SetGlobal => (Const, Read, Unused, "global named const[A] = B"),
+            Opcode::SetState => (Const, Update, Unused, "state const[A] = B on the first tick; later ticks restore B from the state"),
+            Opcode::InitComputed => (Const, Read, Unused, "marks computed const[A]; no effect yet"),
+            Opcode::UpdateState => (Const, Read, Unused, "state const[A] = B, marking it dirty if it changed"),
+            Opcode::InitEffect => (Read, Read, Unused, "registers effect B with dependency array A; no effect yet"),
+            Opcode::GetMember => (Write, Read, Const, "A = B.const[C]"),
+            Opcode::SetMember => (Update, Const, Read, "A.const[B] = C"),
+            Opcode::GetIndex => (Write, Read, Read, "A = B[C]"),
+            Opcode::SetIndex => (Update, Read, Read, "A[B] = C"),
+            Opcode::MakeArray => (Update, Count, Unused, "A = [A, A+1, .., A+B-1]"),
+            Opcode::MakeMap => (Update, Count, Unused, "A = {A: A+1, .., A+2B-2: A+2B-1}"),
+            Opcode::MakeRange => (Write, Read, Read, "A = [B, B+1, .., C-1]"),
+            Opcode::GetIter => (Write, Read, Unused, "A = iter(B)"),
+            Opcode::IterNext => (Write, Read, Target, "A = next(B), or jump to C when done"),
+            Opcode::Jump => (Target, Unused, Unused, "jump to A"),
+            Opcode::JumpIfFalse => (Target, Read, Unused, "jump to A if B is falsy"),
+            Opcode::JumpIfTrue => (Target, Read, Unused, "jump to A if B is truthy"),
+            Opcode::Call => (Update, Count, Unused, "A = A(A+1, .., A+B)"),
+            Opcode::Return => (Read, Unused, Unused, "return A"),
+            Opcode::ReturnVoid => (Unused, Unused, Unused, "return null"),
+            Opcode::MakeClosure => (Update, Unused, Unused, "marks the function in A as a closure; no capture yet"),
+            Opcode::TailCall => (Read, Count, Unused, "return A(A+1, .., A+B), reusing the frame"),
+            Opcode::LoadMethod => (Write, Read, Const, "A = method const[C] bound to B"),
+            Opcode::Print => (Read, Unused, Unused, "print A"),
+            Opcode::Pop => (Read, Unused, Unused, "discard A"),
+            Opcode::Nop => (Unused, Unused, Unused, "nothing"),
+            Opcode::Halt => (Unused, Unused, Unused, "stop the program"),
+            Opcode::JumpTable => (Read, Const, Count, "run the (A - const[B])th of the C Jumps that follow if A is an int in range, else skip them"),
+        };
+        OpcodeInfo { a, b, c, effect }
+    }
+}
+
+/// The instruction set reference: one Markdown table row per opcode.
+pub fn opcode_reference() -> String {
+    let mut out = String::from("| # | Opcode | A | B | C | Effect |\n|---|---|---|---|---|---|\n");
+    for (i, op) in Opcode::ALL.iter().enumerate() {
+        let info = op.info();
+        out.push_str(&format!(
+            "| {} | {:?} | {} | {} | {} | {} |\n",
+            i, op, info.a.name(), info.b.name(), info.c.name(), info.effect.replace('|', "\\|")
+        ));
+    }
+    out
+}
+
 /// Runtime value stored in the constant pool.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Constant {
@@ -174,6 +322,33 @@ pub enum Constant {
     },
 }
 
+/// Deduplication key for `add_constant` and `pool_constants`: `Constant`
+/// holds floats, so it is not `Hash`. Floats compare by bit pattern; classes
+/// are never shared.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum PoolKey {
+    Integer(i64),
+    Float(u64),
+    String(String),
+    Boolean(bool),
+    Null,
+    Function(usize),
+}
+
+impl PoolKey {
+    fn of(constant: &Constant) -> Option<Self> {
+        Some(match constant {
+            Constant::Integer(i) => PoolKey::Integer(*i),
+            Constant::Float(f) => PoolKey::Float(f.to_bits()),
+            Constant::String(s) => PoolKey::String(s.clone()),
+            Constant::Boolean(b) => PoolKey::Boolean(*b),
+            Constant::Null => PoolKey::Null,
+            Constant::Function(idx) => PoolKey::Function(*idx),
+            Constant::Class { .. } => return None,
+        })
+    }
+}
+
 /// A compiled function: its bytecode, constants, and metadata.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CompiledFunction {
@@ -181,11 +356,45 @@ pub struct CompiledFunction {
     pub arity: u16,          // number of parameters
     pub locals: u16,         // number of local variable slots
     pub instructions: Vec<Instruction>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub constants: Vec<Constant>,
+    /// In a `.exki` file, the function's constants as indices into
+    /// `CompiledProgram::constants` (see `pool_constants`); empty in memory.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub constant_refs: Vec<u32>,
     pub param_names: Vec<String>,
     /// Maps each instruction index to a source line number (1-based).
     #[serde(default)]
     pub line_map: Vec<u32>,
+    /// Source column (1-based) of the statement each instruction came from,
+    /// 0 where unknown; parallel to `line_map`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub column_map: Vec<u32>,
+    /// `(name, register)` of every local the function declares, in order.
+    /// Debug-only: moved to the `.kisym` side file (or dropped) on compile.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub local_names: Vec<(String, u16)>,
+    /// Where errors raised inside a `try` go. Entries are innermost first;
+    /// the first whose range covers the faulting instruction handles it.
+    /// Code that can't fail pays nothing: the table is only read while an
+    /// error unwinds.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exception_table: Vec<ExceptionHandler>,
+    /// `add_constant`'s lookup from constant to index, and how many entries
+    /// of `constants` it covers; rebuilt if `constants` changed behind its back.
+    #[serde(skip)]
+    constant_index: (HashMap<PoolKey, u16>, usize),
+}
+
+/// One entry of a function's exception table: an error raised by an
+/// instruction in `start..end` (or by a call it makes) resumes execution at
+/// `handler`, with the error message in `register`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ExceptionHandler {
+    pub start: u32,
+    pub end: u32,
+    pub handler: u32,
+    pub register: u16,
 }
 
 impl CompiledFunction {
@@ -196,21 +405,37 @@ impl CompiledFunction {
             locals: 0,
             instructions: vec![],
             constants: vec![],
+            constant_refs: vec![],
             param_names: vec![],
             line_map: vec![],
+            column_map: vec![],
+            local_names: vec![],
+            exception_table: vec![],
+            constant_index: Default::default(),
         }
     }
 
-    /// Add a constant and return its index.
+    /// Add a constant and return its index, reusing an equal one if present.
     pub fn add_constant(&mut self, c: Constant) -> u16 {
-        // Deduplicate
-        for (i, existing) in self.constants.iter().enumerate() {
-            if existing == &c {
-                return i as u16;
+        let (index, covered) = &mut self.constant_index;
+        if *covered != self.constants.len() {
+            index.clear();
+            for (i, existing) in self.constants.iter().enumerate() {
+                if let Some(key) = PoolKey::of(existing) {
+                    index.entry(key).or_insert(i as u16);
+                }
             }
         }
         let idx = self.constants.len() as u16;
+        if let Some(key) = PoolKey::of(&c) {
+            if let Some(&existing) = index.get(&key) {
+                *covered = self.constants.len();
+                return existing;
+            }
+            index.insert(key, idx);
+        }
         self.constants.push(c);
+        *covered = self.constants.len();
         idx
     }
 
@@ -220,6 +445,73 @@ impl CompiledFunction {
         self.instructions.push(instr);
         idx
     }
+
+    /// Errors if a constant, register or jump target of this function may
+    /// not fit in a `u16` operand. Code generation narrows those indices
+    /// with `as u16`, so past the limit they would silently alias others.
+    pub fn check_operand_widths(&self) -> Result<(), String> {
+        let too_many = |what: &str, count: usize| {
+            format!(
+                "Function '{}' needs {} {}, but an instruction operand can address at most {}; split it into smaller functions",
+                self.name, count, what, MAX_OPERAND
+            )
+        };
+        if self.constants.len() > MAX_OPERAND {
+            return Err(too_many("constants", self.constants.len()));
+        }
+        if self.locals as usize >= MAX_OPERAND {
+            return Err(too_many("registers", self.locals as usize));
+        }
+        // Straight-line code may run past the limit: only jump targets
+        // address instructions.
+        let jumps = || self.instructions.iter().any(|i| {
+            let info = i.opcode.info();
+            [info.a, info.b, info.c].contains(&Operand::Target)
+        });
+        if self.instructions.len() > MAX_OPERAND && jumps() {
+            return Err(too_many("instructions", self.instructions.len()));
+        }
+        Ok(())
+    }
+
+    /// The handler for an error raised at instruction `ip`, if one covers it.
+    pub fn handler_for(&self, ip: usize) -> Option<&ExceptionHandler> {
+        self.exception_table.iter().find(|h| (h.start as usize..h.end as usize).contains(&ip))
+    }
+
+    /// Errors unless every exception table entry covers a non-empty range
+    /// of this function, lands on one of its instructions outside that
+    /// range with a register it has, and nests with the other entries,
+    /// inner ones first. Checked on compile and when a `.exki` is loaded,
+    /// so the VM can unwind without bounds checks.
+    pub fn check_exception_table(&self) -> Result<(), String> {
+        let len = self.instructions.len();
+        for (i, h) in self.exception_table.iter().enumerate() {
+            let bad = |why: &str| Err(format!("Function '{}': exception handler {} ({}..{} -> {}) {}", self.name, i, h.start, h.end, h.handler, why));
+            let (start, end, handler) = (h.start as usize, h.end as usize, h.handler as usize);
+            if start >= end || end > len {
+                return bad(&format!("has an empty range or one past the {} instructions", len));
+            }
+            if handler >= len {
+                return bad(&format!("lands past the last of {} instructions", len));
+            }
+            if (start..end).contains(&handler) {
+                return bad("lands inside the range it handles");
+            }
+            if h.register >= self.locals.max(self.arity) {
+                return bad(&format!("writes register {} of {}", h.register, self.locals.max(self.arity)));
+            }
+            for outer in &self.exception_table[..i] {
+                let (o_start, o_end) = (outer.start as usize, outer.end as usize);
+                let disjoint = end <= o_start || o_end <= start;
+                let inside = o_start >= start && o_end <= end && (o_start, o_end) != (start, end);
+                if !disjoint && !inside {
+                    return bad("overlaps an earlier entry without enclosing it");
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 /// Runtime metadata for a reactive node.
@@ -256,6 +548,20 @@ impl CompiledReactiveGraph {
     }
 }
 
+/// Metadata for a class, or for any type an `impl` block gives methods:
+/// what `LoadMethod` dispatch (through the vtable built from it) and
+/// reflection read instead of the flattened `Class::method` globals.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ClassInfo {
+    pub name: String,
+    /// The class named after `:` in `class Player : Entity`.
+    pub parent: Option<String>,
+    /// Declared field names, in declaration order.
+    pub fields: Vec<String>,
+    /// Method name → index into `CompiledProgram::functions`.
+    pub methods: std::collections::BTreeMap<String, usize>,
+}
+
 /// A compiled program: a list of functions + a top-level "main" chunk.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CompiledProgram {
@@ -263,12 +569,27 @@ pub struct CompiledProgram {
     pub functions: Vec<CompiledFunction>,
     pub version: String,
     pub reactive_graph: CompiledReactiveGraph,
+    /// Program-wide constant pool, only used in `.exki` files: functions
+    /// refer into it through `constant_refs`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub constants: Vec<Constant>,
+    /// Classes and impl targets, in declaration order.
+    #[serde(default)]
+    pub classes: Vec<ClassInfo>,
     /// Static VTable: maps (class_name, method_name) → function_index
     #[serde(default)]
     pub vtable: std::collections::HashMap<String, std::collections::HashMap<String, usize>>,
     /// Build 35: Flag indicating if compiler optimization passes were applied
     #[serde(default)]
     pub is_optimized: bool,
+    /// Top-level `pub fn` names, visible to programs that link this one as a
+    /// bundle. `None` for bundles written before exports were recorded.
+    #[serde(default)]
+    pub exports: Option<Vec<String>>,
+    /// Namespaces of the bundles linked into this program, in link order.
+    /// A bundle's own globals live under `module::name` (see `link.rs`).
+    #[serde(default)]
+    pub modules: Vec<String>,
 }
 
 impl CompiledProgram {
@@ -278,8 +599,240 @@ impl CompiledProgram {
             functions: vec![],
             version: "0.1.0".to_string(), // will be updated by compiler
             reactive_graph: CompiledReactiveGraph::new(),
+            constants: vec![],
+            classes: vec![],
             vtable: std::collections::HashMap::new(),
             is_optimized: false,
+            exports: None,
+            modules: vec![],
+        }
+    }
+
+    /// Moves every function's constants into the program-wide pool, each
+    /// function keeping the indices of its own in `constant_refs`, so a
+    /// name or literal used by many functions is stored once.
+    pub fn pool_constants(&mut self) {
+        let mut index: std::collections::HashMap<PoolKey, u32> = std::collections::HashMap::new();
+        let pool = &mut self.constants;
+        for func in self.functions.iter_mut().chain(std::iter::once(&mut self.main)) {
+            func.constant_refs = func.constants.drain(..).map(|c| {
+                let key = PoolKey::of(&c);
+                if let Some(&idx) = key.as_ref().and_then(|k| index.get(k)) {
+                    return idx;
+                }
+                let idx = pool.len() as u32;
+                pool.push(c);
+                if let Some(key) = key {
+                    index.insert(key, idx);
+                }
+                idx
+            }).collect();
+        }
+    }
+
+    /// Undoes `pool_constants`. A program without a pool is left as is.
+    pub fn unpool_constants(&mut self) -> Result<(), String> {
+        let pool = std::mem::take(&mut self.constants);
+        for func in self.functions.iter_mut().chain(std::iter::once(&mut self.main)) {
+            for idx in std::mem::take(&mut func.constant_refs) {
+                let constant = pool.get(idx as usize)
+                    .ok_or_else(|| format!("Function '{}' refers to constant {} of a pool of {}", func.name, idx, pool.len()))?;
+                func.constants.push(constant.clone());
+            }
+        }
+        Ok(())
+    }
+
+    /// The metadata of the class or impl target called `name`.
+    pub fn class(&self, name: &str) -> Option<&ClassInfo> {
+        self.classes.iter().find(|c| c.name == name)
+    }
+
+    /// The metadata entry for `name`, created empty on first use.
+    pub fn class_mut(&mut self, name: &str) -> &mut ClassInfo {
+        match self.classes.iter().position(|c| c.name == name) {
+            Some(i) => &mut self.classes[i],
+            None => {
+                self.classes.push(ClassInfo { name: name.to_string(), ..ClassInfo::default() });
+                self.classes.last_mut().expect("just pushed")
+            }
+        }
+    }
+}
+
+/// A readable listing of `program` (what `kivm disasm` prints): the function
+/// table, then each function's constants, instructions and exception table.
+/// An operand is shown by kind: `r3` a register, `#2` a constant, `@0012` a
+/// jump target, a bare number a count; the constants an instruction uses
+/// follow it as a comment. The source line of each instruction comes from
+/// `line_map`, when the program still has one.
+pub fn disassemble(program: &CompiledProgram) -> String {
+    use std::fmt::Write;
+    let mut out = String::new();
+    let _ = writeln!(out, "; version {}, {} functions{}", program.version, program.functions.len(),
+        if program.is_optimized { ", optimized" } else { "" });
+    if !program.modules.is_empty() {
+        let _ = writeln!(out, "; linked modules: {}", program.modules.join(", "));
+    }
+    out.push_str("\nfunctions:\n");
+    for (i, func) in program.functions.iter().enumerate() {
+        let _ = writeln!(out, "  {:>4}  {}({})  arity {}, {} registers", i, func.name, func.param_names.join(", "), func.arity, func.locals);
+    }
+    for func in std::iter::once(&program.main).chain(&program.functions) {
+        out.push('\n');
+        disassemble_function(&mut out, program, func);
+    }
+    out
+}
+
+fn disassemble_function(out: &mut String, program: &CompiledProgram, func: &CompiledFunction) {
+    use std::fmt::Write;
+    let _ = writeln!(out, "fn {} (arity {}, {} registers)", func.name, func.arity, func.locals);
+    if !func.constants.is_empty() {
+        out.push_str("  constants:\n");
+        for (i, constant) in func.constants.iter().enumerate() {
+            let _ = writeln!(out, "    #{:<4} {}", i, describe_constant(program, constant));
+        }
+    }
+    out.push_str("  code:\n");
+    for (ip, instr) in func.instructions.iter().enumerate() {
+        let info = instr.opcode.info();
+        let mut operands = Vec::new();
+        let mut comments = Vec::new();
+        for (kind, value) in [(info.a, instr.a), (info.b, instr.b), (info.c, instr.c)] {
+            operands.push(match kind {
+                Operand::Unused => continue,
+                Operand::Read | Operand::Write | Operand::Update => format!("r{}", value),
+                Operand::Const => {
+                    comments.push(func.constants.get(value as usize)
+                        .map_or_else(|| "<missing constant>".to_string(), |c| describe_constant(program, c)));
+                    format!("#{}", value)
+                }
+                Operand::Target => format!("@{:04}", value),
+                Operand::Count => value.to_string(),
+            });
+        }
+        let line = func.line_map.get(ip).filter(|&&l| l > 0).map_or(String::new(), |l| format!("L{}", l));
+        let text = format!("    {:04}  {:<5} {:<13} {}", ip, line, format!("{:?}", instr.opcode), operands.join(", "));
+        if comments.is_empty() {
+            let _ = writeln!(out, "{}", text.trim_end());
+        } else {
+            let _ = writeln!(out, "{:<44} ; {}", text, comments.join(", "));
         }
     }
+    if !func.exception_table.is_empty() {
+        out.push_str("  exception table:\n");
+        for h in &func.exception_table {
+            let _ = writeln!(out, "    @{:04}..@{:04} -> @{:04}, error in r{}", h.start, h.end, h.handler, h.register);
+        }
+    }
+}
+
+/// A constant as the disassembly shows it: literals as in source, functions
+/// and classes by name.
+fn describe_constant(program: &CompiledProgram, constant: &Constant) -> String {
+    match constant {
+        Constant::Integer(i) => i.to_string(),
+        Constant::Float(f) => format!("{:?}", f),
+        Constant::String(s) => format!("{:?}", s),
+        Constant::Boolean(b) => b.to_string(),
+        Constant::Null => "null".to_string(),
+        Constant::Function(idx) => match program.functions.get(*idx) {
+            Some(func) => format!("fn {}", func.name),
+            None => format!("fn <missing {}>", idx),
+        },
+        Constant::Class { name, .. } => format!("class {}", name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_opcode_is_listed_in_discriminant_order() {
+        for (i, op) in Opcode::ALL.iter().enumerate() {
+            assert_eq!(*op as usize, i, "Opcode::ALL is out of order at {:?}", op);
+        }
+        let table = opcode_reference();
+        assert_eq!(table.lines().count(), Opcode::ALL.len() + 2);
+        assert!(table.contains("| 36 | IterNext | reg (write) | reg (read) | target | A = next(B), or jump to C when done |"));
+    }
+
+    #[test]
+    fn add_constant_reuses_equal_constants_even_after_direct_pushes() {
+        let mut func = CompiledFunction::new("f".to_string(), 0);
+        assert_eq!(func.add_constant(Constant::Integer(1)), 0);
+        assert_eq!(func.add_constant(Constant::String("a".to_string())), 1);
+        assert_eq!(func.add_constant(Constant::Integer(1)), 0);
+        func.constants.push(Constant::Float(2.5));
+        assert_eq!(func.add_constant(Constant::Float(2.5)), 2);
+        assert_eq!(func.add_constant(Constant::String("a".to_string())), 1);
+        assert_eq!(func.constants.len(), 3);
+    }
+
+    #[test]
+    fn disassembly_names_operands_constants_and_lines() {
+        let mut program = CompiledProgram::new();
+        let mut double = CompiledFunction::new("double".to_string(), 1);
+        double.param_names = vec!["n".to_string()];
+        double.locals = 2;
+        double.emit(Instruction::new(Opcode::Add, 1, 0, 0));
+        double.emit(Instruction::a_only(Opcode::Return, 1));
+        program.functions.push(double);
+        let f = program.main.add_constant(Constant::Function(0));
+        let name = program.main.add_constant(Constant::String("double".to_string()));
+        program.main.locals = 2;
+        program.main.emit(Instruction::ab(Opcode::LoadConst, 0, f));
+        program.main.emit(Instruction::ab(Opcode::SetGlobal, name, 0));
+        program.main.emit(Instruction::ab(Opcode::JumpIfFalse, 3, 1));
+        program.main.emit(Instruction::a_only(Opcode::Halt, 0));
+        program.main.line_map = vec![1, 1, 2, 0];
+        program.main.exception_table = vec![ExceptionHandler { start: 0, end: 2, handler: 3, register: 1 }];
+
+        let text = disassemble(&program);
+        let lines: Vec<&str> = text.lines().collect();
+        assert!(lines.contains(&"     0  double(n)  arity 1, 2 registers"), "{}", text);
+        assert!(lines.contains(&"    #0    fn double"), "{}", text);
+        assert!(lines.contains(&"    0001  L1    SetGlobal     #1, r0         ; \"double\""), "{}", text);
+        assert!(lines.contains(&"    0002  L2    JumpIfFalse   @0003, r1"), "{}", text);
+        assert!(lines.contains(&"    0003        Halt"), "{}", text);
+        assert!(lines.contains(&"    @0000..@0002 -> @0003, error in r1"), "{}", text);
+        assert!(lines.contains(&"    0000        Add           r1, r0, r0"), "{}", text);
+    }
+
+    #[test]
+    fn exception_tables_must_stay_inside_the_function_and_nest() {
+        let mut func = CompiledFunction::new("guarded".to_string(), 0);
+        func.locals = 2;
+        for _ in 0..6 {
+            func.emit(Instruction::a_only(Opcode::LoadNull, 0));
+        }
+        let entry = |start, end, handler| ExceptionHandler { start, end, handler, register: 1 };
+        func.exception_table = vec![entry(1, 2, 3), entry(0, 3, 4)];
+        assert_eq!(func.check_exception_table(), Ok(()));
+        assert_eq!(func.handler_for(1).map(|h| h.handler), Some(3));
+        assert_eq!(func.handler_for(2).map(|h| h.handler), Some(4));
+        assert!(func.handler_for(5).is_none());
+
+        let reason = |table: Vec<ExceptionHandler>| {
+            let mut func = func.clone();
+            func.exception_table = table;
+            func.check_exception_table().unwrap_err().split(") ").nth(1).unwrap().to_string()
+        };
+        assert_eq!(reason(vec![entry(0, 3, 4), entry(1, 2, 3)]), "overlaps an earlier entry without enclosing it");
+        assert_eq!(reason(vec![entry(2, 2, 4)]), "has an empty range or one past the 6 instructions");
+        assert_eq!(reason(vec![entry(0, 2, 6)]), "lands past the last of 6 instructions");
+        assert_eq!(reason(vec![ExceptionHandler { register: 2, ..entry(0, 2, 3) }]), "writes register 2 of 2");
+    }
+
+    #[test]
+    fn saturated_register_count_fails_the_operand_check() {
+        let mut func = CompiledFunction::new("wide".to_string(), 0);
+        func.locals = (MAX_OPERAND - 1) as u16;
+        assert!(func.check_operand_widths().is_ok());
+        func.locals = u16::MAX;
+        let err = func.check_operand_widths().unwrap_err();
+        assert!(err.starts_with("Function 'wide' needs 65535 registers"), "{}", err);
+    }
 }
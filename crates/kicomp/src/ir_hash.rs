@@ -113,7 +113,7 @@ pub fn hash_hir_program(program: &crate::hir::HirProgram) -> u64 {
             HirExprKind::Range { start, end } => {
                 h.write(b"rng"); hash_expr(start, h); hash_expr(end, h);
             }
-            HirExprKind::Match { value, arms } => {
+            HirExprKind::Match { value, arms, .. } => {
                 h.write(b"match"); hash_expr(value, h);
                 for (pat, body) in arms { hash_pattern(pat, h); hash_stmt(body, h); }
             }
@@ -123,8 +123,17 @@ pub fn hash_hir_program(program: &crate::hir::HirProgram) -> u64 {
     fn hash_pattern(pat: &HirPattern, h: &mut DeterministicHasher) {
         match pat {
             HirPattern::Literal(e) => { h.write(b"plit"); hash_expr(e, h); }
-            HirPattern::Variant { name, binding } => {
+            HirPattern::Range { start, end } => { h.write(b"prange"); hash_expr(start, h); hash_expr(end, h); }
+            HirPattern::Variant { name, payload } => {
                 h.write(b"pvar"); h.write(name.as_bytes());
+                if let Some(p) = payload { hash_pattern(p, h); }
+            }
+            HirPattern::Struct { name, fields } => {
+                h.write(b"pstruct"); h.write(name.as_bytes());
+                for (f, p) in fields { h.write(f.as_bytes()); hash_pattern(p, h); }
+            }
+            HirPattern::Type { name, binding } => {
+                h.write(b"ptype"); h.write(name.as_bytes());
                 if let Some(b) = binding { h.write(b.as_bytes()); }
             }
             HirPattern::Wildcard => { h.write(b"pwild"); }
@@ -172,15 +181,23 @@ pub fn hash_hir_program(program: &crate::hir::HirProgram) -> u64 {
                 hash_stmt(body, h);
                 hash_type(return_type, h);
             }
-            HirStmtKind::While { condition, body } => {
+            HirStmtKind::While { condition, body, label } => {
                 h.write(b"while"); hash_expr(condition, h); hash_stmt(body, h);
+                if let Some(l) = label { h.write(l.as_bytes()); }
             }
-            HirStmtKind::For { iterator, range, body } => {
+            HirStmtKind::For { iterator, range, body, label } => {
                 h.write(b"for"); h.write(iterator.as_bytes());
                 hash_expr(range, h); hash_stmt(body, h);
+                if let Some(l) = label { h.write(l.as_bytes()); }
+            }
+            HirStmtKind::Break { label } => {
+                h.write(b"break");
+                if let Some(l) = label { h.write(l.as_bytes()); }
+            }
+            HirStmtKind::Continue { label } => {
+                h.write(b"continue");
+                if let Some(l) = label { h.write(l.as_bytes()); }
             }
-            HirStmtKind::Break => { h.write(b"break"); }
-            HirStmtKind::Continue => { h.write(b"continue"); }
         }
     }
 
@@ -0,0 +1,147 @@
+//! Debug symbols side file (`.kisym`): the line, column and local-name maps
+//! that `kivm compile --kisym` moves out of the `.exki`.
+
+use crate::ir::{CompiledFunction, CompiledProgram};
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub const FORMAT: &str = "kisym-v1";
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FunctionSymbols {
+    pub name: String,
+    /// Source line of each instruction's statement, as in
+    /// `CompiledFunction::line_map`.
+    pub line_map: Vec<u32>,
+    /// Source column of each instruction, as in `CompiledFunction::column_map`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub column_map: Vec<u32>,
+    /// `(name, register)` of each declared local.
+    pub locals: Vec<(String, u16)>,
+}
+
+impl FunctionSymbols {
+    fn take_from(func: &mut CompiledFunction) -> Self {
+        Self {
+            name: func.name.clone(),
+            line_map: std::mem::take(&mut func.line_map),
+            column_map: std::mem::take(&mut func.column_map),
+            locals: std::mem::take(&mut func.local_names),
+        }
+    }
+
+    pub fn line_at(&self, ip: usize) -> Option<u32> {
+        self.line_map.get(ip).copied().filter(|&l| l > 0)
+    }
+
+    pub fn column_at(&self, ip: usize) -> Option<u32> {
+        self.column_map.get(ip).copied().filter(|&c| c > 0)
+    }
+}
+
+/// The contents of `<name>.kisym`. `kivm run` loads it the first time a
+/// runtime error, a traced instruction or a profiler span has to report a
+/// line or name a local, so the bytecode that ships stays lean.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DebugSymbols {
+    pub format: String,
+    /// Source file the program was compiled from.
+    pub source: String,
+    pub main: FunctionSymbols,
+    pub functions: Vec<FunctionSymbols>,
+}
+
+impl DebugSymbols {
+    /// Moves the debug info out of `program`, leaving it stripped.
+    pub fn split(program: &mut CompiledProgram, source: &Path) -> Self {
+        Self {
+            format: FORMAT.to_string(),
+            source: source.display().to_string(),
+            main: FunctionSymbols::take_from(&mut program.main),
+            functions: program.functions.iter_mut().map(FunctionSymbols::take_from).collect(),
+        }
+    }
+
+    /// Symbols of `functions[index]`, or of `main` for `None`, provided the
+    /// name still matches `name`: a stale side file is ignored rather than
+    /// misattributing lines. Call frames keep their function's index, so the
+    /// lookup is direct.
+    pub fn function(&self, index: Option<usize>, name: &str) -> Option<&FunctionSymbols> {
+        let syms = match index {
+            Some(i) => self.functions.get(i)?,
+            None => &self.main,
+        };
+        (syms.name == name).then_some(syms)
+    }
+
+    /// Puts the line and column maps back into `program`, for tools that
+    /// read them there; a function whose name no longer matches is skipped.
+    pub fn restore_line_maps(&self, program: &mut CompiledProgram) {
+        if let Some(syms) = self.function(None, &program.main.name) {
+            program.main.line_map = syms.line_map.clone();
+            program.main.column_map = syms.column_map.clone();
+        }
+        for (i, func) in program.functions.iter_mut().enumerate() {
+            if let Some(syms) = self.function(Some(i), &func.name) {
+                func.line_map = syms.line_map.clone();
+                func.column_map = syms.column_map.clone();
+            }
+        }
+    }
+
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let json = serde_json::to_vec(self).map_err(io::Error::other)?;
+        std::fs::write(path, json)
+    }
+
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let syms: Self = serde_json::from_slice(&std::fs::read(path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        if syms.format != FORMAT {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("unsupported debug symbols format '{}'", syms.format)));
+        }
+        Ok(syms)
+    }
+}
+
+/// Drops local variable names, which only a `.kisym` file carries.
+pub fn strip_locals(program: &mut CompiledProgram) {
+    for func in std::iter::once(&mut program.main).chain(program.functions.iter_mut()) {
+        func.local_names.clear();
+    }
+}
+
+/// `app.exki` -> `app.kisym`.
+pub fn path_for(exki: &Path) -> PathBuf {
+    exki.with_extension("kisym")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::Compiler;
+    use bumpalo::Bump;
+    use kinetix_language::lexer::Lexer;
+    use kinetix_language::parser::Parser;
+
+    #[test]
+    fn split_moves_lines_and_locals_out_of_the_program() {
+        let arena = Bump::new();
+        let mut parser = Parser::new(Lexer::new("fn add(a: int, b: int) -> int {\n    let sum = a + b\n    return sum\n}\nlet x = add(1, 2)\n"), &arena);
+        let ast = parser.parse_program();
+        let mut program = Compiler::new().compile(&ast.statements, None).cloned().expect("compiles");
+
+        let syms = DebugSymbols::split(&mut program, Path::new("add.kix"));
+        assert!(program.functions.iter().all(|f| f.line_map.is_empty() && f.local_names.is_empty()));
+        assert!(program.main.line_map.is_empty() && program.main.column_map.is_empty());
+
+        let idx = program.functions.iter().position(|f| f.name == "add").expect("add compiled");
+        let add = syms.function(Some(idx), "add").expect("symbols for add");
+        let names: Vec<&str> = add.locals.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, ["a", "b", "sum"]);
+        let ip = add.line_map.iter().position(|&l| l == 2).expect("an instruction on line 2");
+        assert_eq!(add.column_at(ip), Some(5), "`let sum` starts in column 5");
+        assert!(syms.function(Some(idx), "renamed").is_none(), "stale symbols are ignored");
+    }
+}
@@ -6,6 +6,9 @@ pub mod compiler;
 #[cfg(feature = "llvm")]
 pub mod llvm_codegen;
 pub mod exn;
+pub mod link;
+pub mod module_graph;
+pub mod kisym;
 pub mod types;
 pub mod symbol;
 pub mod pattern;
@@ -20,8 +23,16 @@ pub mod monomorphize;
 pub mod ssa_validate;
 pub mod mono_validate;
 pub mod drop_verify;
+pub mod resource_lint;
+pub mod global_lint;
+pub mod version_gate;
+pub mod strict;
 pub mod benchmarks;
 pub mod reactive;
+pub mod session;
+pub mod incremental;
+pub mod instrument;
+pub mod plugin;
 pub mod ir_hash;
 pub mod capability;
 pub mod hir_validate;
@@ -33,3 +44,4 @@ pub mod opt;
 pub mod mir_opt;
 pub mod mir_codegen;
 pub mod metrics;
+pub mod output;
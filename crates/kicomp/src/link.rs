@@ -0,0 +1,328 @@
+//! Bundle linking: `import bundle "mathlib.exki"` merges a program built
+//! earlier with `kivm compile` into the importer instead of recompiling it.
+
+use crate::compiler::CURRENT_BUILD;
+use crate::ir::{CompiledFunction, CompiledProgram, Constant, Opcode};
+use kinetix_language::ast::Statement;
+use kinetix_language::diagnostic::{codes, Diagnostic};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// Bytecode formats `exn::read_exn` loads: the current one first.
+const BUNDLE_FORMATS: &[&str] = &[crate::exn::FORMAT, "kivm-bytecode-v3", "kivm-bytecode-v2"];
+
+/// A loaded, compatibility-checked bundle.
+pub struct Bundle {
+    /// Canonicalized path; identifies the bundle for de-duplication.
+    pub path: PathBuf,
+    pub program: CompiledProgram,
+}
+
+impl Bundle {
+    /// Reads and checks a `.exki` file.
+    pub fn load(path: &Path) -> Result<Bundle, String> {
+        let path = path.canonicalize().map_err(|e| format!("Bundle not found: {} ({})", path.display(), e))?;
+        let mut file = std::fs::File::open(&path).map_err(|e| format!("Error reading bundle {}: {}", path.display(), e))?;
+        let (manifest, program) = crate::exn::read_exn_with_manifest(&mut file)
+            .map_err(|e| format!("Invalid bundle {}: {}", path.display(), e))?;
+        Self::check(&path, &manifest, &program)?;
+        Ok(Bundle { path, program })
+    }
+
+    /// Rejects bundles built by a newer compiler, whose bytecode may use
+    /// opcodes this VM doesn't have.
+    fn check(path: &Path, manifest: &serde_json::Value, program: &CompiledProgram) -> Result<(), String> {
+        let format = manifest["format"].as_str().unwrap_or("unknown");
+        if !BUNDLE_FORMATS.contains(&format) {
+            return Err(format!("Bundle {} uses bytecode format '{}', expected '{}'", path.display(), format, BUNDLE_FORMATS[0]));
+        }
+        // Bundles written before the manifest carried a build are accepted.
+        let build = manifest["compiler_build"].as_i64().unwrap_or(0);
+        if build > CURRENT_BUILD {
+            return Err(format!(
+                "Bundle {} was compiled by build {}, newer than this compiler (build {}); recompile it or upgrade",
+                path.display(), build, CURRENT_BUILD
+            ));
+        }
+        if !program.reactive_graph.nodes.is_empty() {
+            return Err(format!("Bundle {} declares reactive state; only functions and classes can be linked", path.display()));
+        }
+        Ok(())
+    }
+
+    /// The bundle's file stem, used as its namespace.
+    pub fn label(&self) -> String {
+        self.path.file_stem().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default()
+    }
+
+    /// The bundle's exported functions, as `(name, arity)`. Bundles written
+    /// before exports were recorded export every plain top-level function.
+    pub fn exports(&self) -> Vec<(String, usize)> {
+        exports(&self.program)
+    }
+}
+
+/// `program`'s exported functions, as `(name, arity)`; see `Bundle::exports`.
+pub fn exports(program: &CompiledProgram) -> Vec<(String, usize)> {
+    program.functions.iter()
+        .filter(|f| match &program.exports {
+            Some(exports) => exports.contains(&f.name),
+            None => is_plain_identifier(&f.name),
+        })
+        .map(|f| (f.name.clone(), f.arity as usize))
+        .collect()
+}
+
+/// The name a bundle's global `name` has inside the importer.
+pub fn qualify(module: &str, name: &str) -> String {
+    format!("{}::{}", module, name)
+}
+
+fn is_plain_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Loads every `import bundle` in `statements`, with relative paths resolved
+/// against `base_dir`; a bundle imported twice is loaded once. Fails if two
+/// bundles export the same name.
+pub fn load_imports(statements: &[Statement<'_>], base_dir: &Path) -> Result<Vec<Bundle>, Vec<Diagnostic>> {
+    let mut bundles: Vec<Bundle> = Vec::new();
+    let mut errors = Vec::new();
+    for stmt in statements {
+        let Statement::ImportBundle { path, span, .. } = stmt else { continue };
+        let bundle = match Bundle::load(&base_dir.join(path)) {
+            Ok(b) => b,
+            Err(e) => {
+                errors.push(Diagnostic::error(codes::LINK, e).at(*span));
+                continue;
+            }
+        };
+        if bundles.iter().any(|b| b.path == bundle.path) {
+            continue;
+        }
+        for (name, _) in bundle.exports() {
+            if let Some(other) = bundles.iter().find(|b| b.exports().iter().any(|(n, _)| *n == name)) {
+                let message = format!("'{}' is exported by both {} and {}", name, other.label(), bundle.label());
+                errors.push(Diagnostic::error(codes::LINK, message).at(*span));
+            }
+        }
+        bundles.push(bundle);
+    }
+    if errors.is_empty() { Ok(bundles) } else { Err(errors) }
+}
+
+/// All exports of `bundles`, for `symbol::resolve_program_with_externs`.
+pub fn externs(bundles: &[Bundle]) -> Vec<(String, usize)> {
+    bundles.iter().flat_map(Bundle::exports).collect()
+}
+
+/// Appends `lib`'s functions and its top-level chunk (as a zero-arity init
+/// function named `<bundle:module>`) to `program`, with the bundle's globals
+/// moved into their own namespace: its `helper` becomes `module::helper`, so
+/// two bundles, or a bundle and the importer, can both define `helper()`.
+/// Flattened class members (`Class::method`) keep their names. `label` is
+/// the preferred namespace; it is suffixed if another linked bundle already
+/// uses it. Returns the init function's index (the caller is responsible for
+/// calling it, before the importer's own code) and the namespace.
+///
+/// Constants are not de-duplicated here: the VM reads each function's own
+/// constants, so they stay with their function. Writing the linked program
+/// out (`exn::write_exn`) pools the constants of every function, the
+/// bundle's included, into one de-duplicated table (see
+/// `CompiledProgram::pool_constants`).
+pub fn merge(program: &mut CompiledProgram, lib: &CompiledProgram, label: &str) -> (usize, String) {
+    let mut module = label.to_string();
+    let mut n = 1;
+    while program.modules.contains(&module) {
+        n += 1;
+        module = format!("{}_{}", label, n);
+    }
+    program.modules.push(module.clone());
+
+    let defined = defined_globals(lib);
+    let offset = program.functions.len();
+    for func in &lib.functions {
+        program.functions.push(relocate(func, offset, &module, &defined));
+    }
+    // Method globals (`Class::method`) are not namespaced, so neither are
+    // the classes that describe them.
+    for class in &lib.classes {
+        let info = program.class_mut(&class.name);
+        info.parent = class.parent.clone();
+        info.fields = class.fields.clone();
+        info.methods.extend(class.methods.iter().map(|(name, &idx)| (name.clone(), idx + offset)));
+    }
+
+    let mut init = relocate(&lib.main, offset, &module, &defined);
+    init.name = format!("<bundle:{}>", module);
+    init.arity = 0;
+    for instr in &mut init.instructions {
+        if instr.opcode == Opcode::Halt {
+            instr.opcode = Opcode::ReturnVoid;
+        }
+    }
+    program.functions.push(init);
+    (program.functions.len() - 1, module)
+}
+
+/// Plain names the bundle assigns with `SetGlobal` anywhere in its code.
+fn defined_globals(lib: &CompiledProgram) -> HashSet<String> {
+    let mut defined = HashSet::new();
+    for func in std::iter::once(&lib.main).chain(&lib.functions) {
+        for instr in &func.instructions {
+            if instr.opcode != Opcode::SetGlobal {
+                continue;
+            }
+            match func.constants.get(instr.a as usize) {
+                Some(Constant::String(name)) if is_plain_identifier(name) => {
+                    defined.insert(name.clone());
+                }
+                _ => {}
+            }
+        }
+    }
+    defined
+}
+
+/// Copies `func`, shifting every function index in its constant pool by
+/// `offset` and qualifying its accesses to the bundle's own globals.
+fn relocate(func: &CompiledFunction, offset: usize, module: &str, defined: &HashSet<String>) -> CompiledFunction {
+    let mut func = func.clone();
+    for constant in &mut func.constants {
+        match constant {
+            Constant::Function(idx) => *idx += offset,
+            Constant::Class { methods, .. } => {
+                for idx in methods {
+                    *idx += offset;
+                }
+            }
+            _ => {}
+        }
+    }
+    // The name constant may be shared with an unrelated string literal, so
+    // point the instruction at a new (deduplicated) constant instead of
+    // rewriting the shared one.
+    for i in 0..func.instructions.len() {
+        let instr = func.instructions[i];
+        let operand = match instr.opcode {
+            Opcode::GetGlobal => instr.b,
+            Opcode::SetGlobal => instr.a,
+            _ => continue,
+        };
+        let Some(Constant::String(name)) = func.constants.get(operand as usize) else { continue };
+        if !defined.contains(name) {
+            continue;
+        }
+        let qualified = func.add_constant(Constant::String(qualify(module, name)));
+        match instr.opcode {
+            Opcode::GetGlobal => func.instructions[i].b = qualified,
+            _ => func.instructions[i].a = qualified,
+        }
+    }
+    func
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::Compiler;
+    use bumpalo::Bump;
+    use kinetix_language::lexer::Lexer;
+    use kinetix_language::parser::Parser;
+
+    fn compile_src(src: &str) -> CompiledProgram {
+        let arena = Bump::new();
+        let mut parser = Parser::new(Lexer::new(src), &arena);
+        let program = parser.parse_program();
+        assert!(parser.errors.is_empty(), "Parser errors: {:?}", parser.errors);
+        let mut compiler = Compiler::new();
+        compiler.compile(&program.statements, None).expect("compiles").clone()
+    }
+
+    fn bundle(src: &str) -> Bundle {
+        Bundle { path: PathBuf::from("mathlib.exki"), program: compile_src(src) }
+    }
+
+    #[test]
+    fn exports_are_the_pub_functions() {
+        let lib = bundle("pub fn square(x: int) -> int { return x * x }\nfn helper() { }\nclass P { fn get(self) -> int { return 1 } }\n");
+        assert_eq!(lib.exports(), vec![("square".to_string(), 1)]);
+
+        let mut legacy = bundle("fn square(x: int) -> int { return x * x }\nfn helper() { }\n");
+        legacy.program.exports = None;
+        let names: Vec<String> = legacy.exports().into_iter().map(|(n, _)| n).collect();
+        assert!(names.contains(&"square".to_string()) && names.contains(&"helper".to_string()), "{:?}", names);
+        assert!(names.iter().all(|n| !n.contains("::")), "{:?}", names);
+    }
+
+    fn global_names(func: &CompiledFunction, opcode: Opcode) -> Vec<String> {
+        func.instructions.iter()
+            .filter(|i| i.opcode == opcode)
+            .filter_map(|i| match func.constants.get(if opcode == Opcode::SetGlobal { i.a } else { i.b } as usize) {
+                Some(Constant::String(s)) => Some(s.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn merge_relocates_and_namespaces_the_bundle() {
+        let lib = bundle("fn helper(x: int) -> int { return x * x }\npub fn quad(x: int) -> int { return helper(helper(x)) }\nprintln(\"helper\")\n");
+        let mut program = compile_src("fn helper() { }\n");
+        let before = program.functions.len();
+
+        let (init, module) = merge(&mut program, &lib.program, &lib.label());
+        assert_eq!(module, "mathlib");
+        assert_eq!(init, before + lib.program.functions.len());
+        let init_fn = &program.functions[init];
+        assert_eq!(init_fn.name, "<bundle:mathlib>");
+        assert!(init_fn.instructions.iter().all(|i| i.opcode != Opcode::Halt), "init must return, not halt");
+
+        let helper = program.functions.iter().rposition(|f| f.name == "helper").unwrap();
+        assert!(helper >= before && init_fn.constants.contains(&Constant::Function(helper)), "init must register the relocated 'helper'");
+        let defined = global_names(init_fn, Opcode::SetGlobal);
+        assert!(defined.contains(&"mathlib::helper".to_string()) && !defined.contains(&"helper".to_string()), "{:?}", defined);
+        assert!(init_fn.constants.contains(&Constant::String("helper".to_string())), "the string literal must survive");
+
+        let quad = program.functions.iter().find(|f| f.name == "quad").unwrap();
+        assert_eq!(global_names(quad, Opcode::GetGlobal), vec!["mathlib::helper", "mathlib::helper"]);
+        assert!(global_names(&program.main, Opcode::SetGlobal).contains(&"helper".to_string()), "the importer's own helper is untouched");
+
+        let shapes = bundle("class P { fn get(self) -> int { return 1 } }\n");
+        merge(&mut program, &shapes.program, "shapes");
+        let get = program.class("P").expect("the bundle's class is linked").methods["get"];
+        assert_eq!(program.functions[get].name, "P::get");
+
+        let (_, second) = merge(&mut program, &lib.program, "mathlib");
+        assert_eq!(second, "mathlib_2");
+        assert_eq!(program.modules, vec!["mathlib", "shapes", "mathlib_2"]);
+    }
+
+    #[test]
+    fn linked_constants_share_the_file_pool() {
+        let lib = bundle("pub fn greet() { println(\"hello\") }\n");
+        let mut program = compile_src("println(\"hello\")\n");
+        merge(&mut program, &lib.program, &lib.label());
+        program.pool_constants();
+        let hello = program.constants.iter().filter(|c| **c == Constant::String("hello".to_string())).count();
+        assert_eq!(hello, 1, "{:?}", program.constants);
+        program.unpool_constants().unwrap();
+        let greet = program.functions.iter().find(|f| f.name == "greet").unwrap();
+        assert!(greet.constants.contains(&Constant::String("hello".to_string())));
+    }
+
+    #[test]
+    fn newer_build_is_rejected() {
+        let lib = bundle("fn f() { }\n");
+        let manifest = serde_json::json!({ "format": crate::exn::FORMAT, "compiler_build": CURRENT_BUILD + 1 });
+        let err = Bundle::check(&lib.path, &manifest, &lib.program).unwrap_err();
+        assert!(err.contains("newer than this compiler"), "{}", err);
+
+        let manifest = serde_json::json!({ "format": crate::exn::FORMAT, "compiler_build": CURRENT_BUILD });
+        assert!(Bundle::check(&lib.path, &manifest, &lib.program).is_ok());
+        let legacy = serde_json::json!({ "format": "kivm-bytecode-v2", "compiler_build": CURRENT_BUILD });
+        assert!(Bundle::check(&lib.path, &legacy, &lib.program).is_ok(), "v2 bundles still load");
+    }
+}
@@ -7,7 +7,7 @@ use inkwell::FloatPredicate;
 use inkwell::IntPredicate;
 use std::collections::HashMap;
 use std::path::Path;
-use kinetix_language::ast::{Statement, Expression};
+use kinetix_language::ast::{Statement, Expression, ExpressionKind};
 use crate::ir::Constant;
 
 /// LLVM Code Generator
@@ -129,7 +129,7 @@ impl<'ctx> LLVMCodegen<'ctx> {
                 
                 self.builder.build_store(alloca, init_val).map_err(|e| e.to_string())?;
                 
-                self.variables.insert(name.clone(), alloca);
+                self.variables.insert(name.to_string(), alloca);
                 Ok(())
             }
             Statement::While { condition, body, .. } => {
@@ -224,12 +224,12 @@ impl<'ctx> LLVMCodegen<'ctx> {
 
 
     fn compile_expression(&mut self, expr: &Expression) -> Result<BasicValueEnum<'ctx>, String> {
-        match expr {
-            Expression::Try { value } => self.compile_expression(value), // TEMPORARY stub
-            Expression::Integer(i) => Ok(self.context.i64_type().const_int(*i as u64, false).into()),
-            Expression::Float(f) => Ok(self.context.f64_type().const_float(*f).into()),
-            Expression::Boolean(b) => Ok(self.context.bool_type().const_int(if *b { 1 } else { 0 }, false).into()),
-            Expression::String(s) => {
+        match &expr.kind {
+            ExpressionKind::Try { value } => self.compile_expression(value), // TEMPORARY stub
+            ExpressionKind::Integer(i) => Ok(self.context.i64_type().const_int(*i as u64, false).into()),
+            ExpressionKind::Float(f) => Ok(self.context.f64_type().const_float(*f).into()),
+            ExpressionKind::Boolean(b) => Ok(self.context.bool_type().const_int(if *b { 1 } else { 0 }, false).into()),
+            ExpressionKind::String(s) => {
                 let string_type = self.context.get_struct_type("String").unwrap();
                 let i64_type = self.context.i64_type();
                 let mut str_struct = string_type.get_undef();
@@ -246,7 +246,7 @@ impl<'ctx> LLVMCodegen<'ctx> {
                 
                 Ok(str_struct.into())
             }
-            Expression::ArrayLiteral(elements) => {
+            ExpressionKind::ArrayLiteral(elements) => {
                 let array_type = self.context.get_struct_type("Array").unwrap();
                 let i64_type = self.context.i64_type();
                 let mut arr_struct = array_type.get_undef();
@@ -289,8 +289,8 @@ impl<'ctx> LLVMCodegen<'ctx> {
                 
                 Ok(arr_struct.into())
             }
-            Expression::Identifier(name) => {
-                match self.variables.get(name) {
+            ExpressionKind::Identifier(name) => {
+                match self.variables.get(*name) {
                     Some(ptr) => {
                          let load = self.builder.build_load(self.context.i64_type(), *ptr, name) // Assume i64 for now? Need type tracking.
                              .map_err(|e| e.to_string())?;
@@ -299,15 +299,15 @@ impl<'ctx> LLVMCodegen<'ctx> {
                     None => Err(format!("Undefined variable: {}", name)),
                 }
             }
-            Expression::Infix { left, operator, right } => {
+            ExpressionKind::Infix { left, operator, right } => {
                 let l = self.compile_expression(left)?;
                 let r = self.compile_expression(right)?;
                 self.compile_binary_op(l, operator, r)
             }
-            Expression::Call { function, arguments } => {
-                if let Expression::Identifier(name) = *function {
-                    if name == "print" || name == "println" {
-                        return self.compile_print(arguments, name == "println");
+            ExpressionKind::Call { function, arguments } => {
+                if let ExpressionKind::Identifier(name) = &function.kind {
+                    if *name == "print" || *name == "println" {
+                        return self.compile_print(arguments, *name == "println");
                     }
                     if name.starts_with("math.") {
                         let fn_name = name.strip_prefix("math.").unwrap();
@@ -353,7 +353,7 @@ impl<'ctx> LLVMCodegen<'ctx> {
                 }
                 Err(format!("Cannot resolve function call: {:?}", function))
             }
-            Expression::StructLiteral { name, fields } => {
+            ExpressionKind::StructLiteral { name, fields } => {
                 // In Phase 3 Step 6, we map Kinetix Struct -> LLVM StructType.
                 // For MVP nominals, we define an opaque struct or auto-populate it 
                 // heavily relying on i64 primitive pointers (to match Kinetix v0.0.6 primitives).
@@ -440,7 +440,7 @@ impl<'ctx> LLVMCodegen<'ctx> {
                      printf_args.push(val.into());
                      "%f".to_string()
                  } else if val.is_struct_value() && val.into_struct_value().get_type() == self.context.get_struct_type("String").unwrap() {
-                     // Kinetix string: { i64 len, i8* ptr } (see Expression::String
+                     // Kinetix string: { i64 len, i8* ptr } (see ExpressionKind::String
                      // codegen above) -- printf only needs the pointer field. Struct
                      // *type* must be checked explicitly: `Array` is also a struct
                      // ({ i64 len, i64 cap, i64* data }) but its field 1 is an i64,
@@ -3,12 +3,18 @@
 
 use std::io::{self, Read, Write, BufRead};
 use serde_json::{Value, json};
+use std::collections::HashMap;
+use crate::session::Session;
+use kinetix_language::diagnostic::{codes, Diagnostic, Severity};
 
 pub fn start_server() -> Result<(), String> {
-    eprintln!("Kinetix LSP server starting...");
+    crate::output::step("Kinetix LSP server starting...");
 
     let stdin = io::stdin();
     let stdout = io::stdout();
+    // One session per open document, so re-checking it after an edit reuses
+    // the trait environment built for the previous version.
+    let mut sessions: HashMap<String, Session> = HashMap::new();
 
     // Loop over incoming LSP messages from stdin
     loop {
@@ -53,6 +59,7 @@ pub fn start_server() -> Result<(), String> {
                             "result": {
                                 "capabilities": {
                                     "textDocumentSync": 1, // Full document sync for now
+                                    "codeActionProvider": true,
                                 }
                             }
                         });
@@ -60,15 +67,16 @@ pub fn start_server() -> Result<(), String> {
                     }
                 }
                 "initialized" => {
-                    eprintln!("LSP client initialized.");
+                    crate::output::step("LSP client initialized.");
                 }
                 "textDocument/didOpen" => {
                     if let Some(params) = msg.get("params") {
                         if let Some(doc) = params.get("textDocument") {
                             let uri = doc.get("uri").and_then(|v| v.as_str()).unwrap_or("");
                             let text = doc.get("text").and_then(|v| v.as_str()).unwrap_or("");
-                            eprintln!("File opened: {}", uri);
-                            process_document(uri, text, &mut stdout.lock())?;
+                            crate::output::step(format!("File opened: {}", uri));
+                            let session = sessions.entry(uri.to_string()).or_default();
+                            process_document(uri, text, session, &mut stdout.lock())?;
                         }
                     }
                 }
@@ -80,12 +88,28 @@ pub fn start_server() -> Result<(), String> {
                             if let Some(changes) = params.get("contentChanges").and_then(|c| c.as_array()) {
                                 if let Some(first_change) = changes.get(0) {
                                     let text = first_change.get("text").and_then(|v| v.as_str()).unwrap_or("");
-                                    process_document(uri, text, &mut stdout.lock())?;
+                                    let session = sessions.entry(uri.to_string()).or_default();
+                                    process_document(uri, text, session, &mut stdout.lock())?;
                                 }
                             }
                         }
                     }
                 }
+                "textDocument/didClose" => {
+                    if let Some(uri) = msg.pointer("/params/textDocument/uri").and_then(|v| v.as_str()) {
+                        sessions.remove(uri);
+                    }
+                }
+                "textDocument/codeAction" => {
+                    if let (Some(id), Some(params)) = (msg.get("id"), msg.get("params")) {
+                        let response = json!({
+                            "jsonrpc": "2.0",
+                            "id": id,
+                            "result": code_actions(params)
+                        });
+                        send_response(&mut stdout.lock(), response)?;
+                    }
+                }
                 "shutdown" => {
                     if let Some(id) = msg.get("id") {
                         let response = json!({
@@ -97,7 +121,7 @@ pub fn start_server() -> Result<(), String> {
                     }
                 }
                 "exit" => {
-                    eprintln!("LSP server exiting.");
+                    crate::output::step("LSP server exiting.");
                     break;
                 }
                 _ => {}
@@ -108,7 +132,87 @@ pub fn start_server() -> Result<(), String> {
     Ok(())
 }
 
-fn process_document(uri: &str, text: &str, stdout: &mut impl Write) -> Result<(), String> {
+/// `d` as an LSP diagnostic. LSP positions are 0-based; a span without a
+/// column covers its whole line, and no span at all means the first line.
+/// Its fixes ride along in `data`, where `code_actions` finds them when the
+/// client sends the diagnostic back.
+fn lsp_diagnostic(d: &Diagnostic, source: &str) -> Value {
+    let (line, start, end) = match d.span() {
+        Some(span) if span.column > 0 => {
+            let start = span.column - 1;
+            (span.line.saturating_sub(1), start, start + span.end.saturating_sub(span.start).max(1))
+        }
+        Some(span) => (span.line.saturating_sub(1), 0, 100),
+        None => (0, 0, 100),
+    };
+    json!({
+        "range": {
+            "start": { "line": line, "character": start },
+            "end": { "line": line, "character": end }
+        },
+        "severity": if d.severity == Severity::Error { 1 } else { 2 },
+        "code": d.code,
+        "message": d.message,
+        "source": source,
+        "data": {
+            "fixes": d.fixes.iter().map(|fix| json!({
+                "title": fix.message,
+                "line": fix.at.line.saturating_sub(1),
+                "character": fix.at.column.saturating_sub(1),
+                "replace": fix.replace,
+                "insert": fix.insert,
+            })).collect::<Vec<_>>()
+        }
+    })
+}
+
+/// The quick fixes for a `textDocument/codeAction` request: one per fix of
+/// each diagnostic in its context, inserting the fix's text.
+fn code_actions(params: &Value) -> Value {
+    let uri = params.pointer("/textDocument/uri").and_then(Value::as_str).unwrap_or("");
+    let diagnostics = params.pointer("/context/diagnostics").and_then(Value::as_array).map(Vec::as_slice).unwrap_or(&[]);
+    let mut actions = Vec::new();
+    for diagnostic in diagnostics {
+        let fixes = diagnostic.pointer("/data/fixes").and_then(Value::as_array).map(Vec::as_slice).unwrap_or(&[]);
+        for fix in fixes {
+            let character = fix["character"].as_u64().unwrap_or(0);
+            let start = json!({ "line": fix["line"], "character": character });
+            let end = json!({ "line": fix["line"], "character": character + fix["replace"].as_u64().unwrap_or(0) });
+            actions.push(json!({
+                "title": fix["title"],
+                "kind": "quickfix",
+                "diagnostics": [diagnostic],
+                "edit": { "changes": { uri: [{ "range": { "start": start, "end": end }, "newText": fix["insert"] }] } }
+            }));
+        }
+    }
+    Value::Array(actions)
+}
+
+/// The checks after symbol and trait resolution, as far as the first one
+/// that fails: types, field visibility on typed receivers, method calls,
+/// then `match` exhaustiveness.
+fn check_types(statements: &[kinetix_language::ast::Statement], symbols: &crate::symbol::SymbolTable, traits: &crate::trait_solver::TraitEnvironment) -> Vec<Diagnostic> {
+    let error = |code, e: String| vec![Diagnostic::error(code, e)];
+    let mut hir = crate::hir::lower_to_hir(statements, symbols, traits);
+    if let Err(e) = crate::type_normalize::normalize(&mut hir, symbols) {
+        return error(codes::TYPE, e);
+    }
+    let mut ctx = crate::typeck::TypeContext::new();
+    let constraints = ctx.collect_constraints(&hir);
+    if let Err(errs) = ctx.solve(&constraints) {
+        return errs;
+    }
+    if let Err(errs) = crate::symbol::check_typed_field_access(&hir, symbols, &ctx.substitution) {
+        return errs;
+    }
+    if let Err(e) = crate::type_normalize::resolve_method_calls(&mut hir, symbols, &ctx.substitution) {
+        return error(codes::METHOD, e);
+    }
+    crate::exhaustiveness::check_program_exhaustiveness(&hir, symbols, &ctx.substitution).err().unwrap_or_default()
+}
+
+fn process_document(uri: &str, text: &str, session: &mut Session, stdout: &mut impl Write) -> Result<(), String> {
     use kinetix_language::lexer::Lexer;
     use kinetix_language::parser::Parser;
     use bumpalo::Bump;
@@ -126,42 +230,26 @@ fn process_document(uri: &str, text: &str, stdout: &mut impl Write) -> Result<()
     if !parser.errors.is_empty() {
         // Map parser errors to LSP diagnostics
         for err in &parser.errors {
-            let msg = err.to_string();
-            diagnostics.push(json!({
-                "range": {
-                    "start": { "line": 0, "character": 0 },
-                    "end": { "line": 0, "character": 100 }
-                },
-                "severity": 1, // Error
-                "message": msg,
-                "source": "HM-Kinetix Syntax"
-            }));
+            diagnostics.push(lsp_diagnostic(err, "HM-Kinetix Syntax"));
         }
     } else {
         // Syntax valid: Proceed to Semantic Analysis & Type Checking (HM)
         let symbols = crate::symbol::resolve_program(&ast.statements);
         match symbols {
-            Ok(_sym_table) => {
-                let _traits = crate::trait_solver::TraitEnvironment::new();
-                // Lower to HIR and execute HM type inferencing
-                // Although lower_to_hir panics on extreme type errors currently, we capture safe errors where possible
-                // For a more robust LSP, lower_to_hir would need to return Result<_, Vec<Error>>.
-                // Kinetix architecture currently handles many type errors explicitly in the compiler tree.
-                // We'll simulate capturing type errors by intercepting any missing symbols or basic type mismatches.
-                // (Assuming resolve_program already caught unmapped variables/functions).
+            Ok(sym_table) => match session.trait_environment(&ast.statements) {
+                Ok(traits) => {
+                    for err in check_types(&ast.statements, &sym_table, traits) {
+                        diagnostics.push(lsp_diagnostic(&err, "HM-Kinetix Typeck"));
+                    }
+                }
+                Err(e) => {
+                    let err = Diagnostic::error(codes::TRAIT, e);
+                    diagnostics.push(lsp_diagnostic(&err, "HM-Kinetix Traits"));
+                }
             },
             Err(sym_errs) => {
                 for err in sym_errs {
-                    let msg = err.to_string();
-                    diagnostics.push(json!({
-                        "range": {
-                            "start": { "line": 0, "character": 0 },
-                            "end": { "line": 0, "character": 100 }
-                        },
-                        "severity": 1, // Error
-                        "message": msg,
-                        "source": "HM-Kinetix Typeck"
-                    }));
+                    diagnostics.push(lsp_diagnostic(&err, "HM-Kinetix Typeck"));
                 }
             }
         }
@@ -191,3 +279,28 @@ fn send_response(stdout: &mut impl Write, message: Value) -> Result<(), String>
     
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn non_exhaustive_matches_come_with_quick_fixes() {
+        let mut published = Vec::new();
+        let source = "enum Color { Red, Green }\nlet c = Red\nlet n = match c {\n    Red => 1,\n}\n";
+        process_document("file:///a.kix", source, &mut Session::new(), &mut published).unwrap();
+        let published = String::from_utf8(published).unwrap();
+        let notification: Value = serde_json::from_str(&published[published.find('{').unwrap()..]).unwrap();
+        let diagnostic = &notification.pointer("/params/diagnostics/0").unwrap();
+        assert_eq!(diagnostic["code"], "E0008");
+        assert_eq!(diagnostic.pointer("/range/start/line"), Some(&json!(2)));
+
+        let request = json!({ "textDocument": { "uri": "file:///a.kix" }, "context": { "diagnostics": [diagnostic] } });
+        let actions = code_actions(&request);
+        assert_eq!(actions.pointer("/0/title"), Some(&json!("add arms for Green")));
+        assert_eq!(actions.pointer("/1/title"), Some(&json!("add a `_` arm")));
+        let edit = actions.pointer("/0/edit/changes/file:~1~1~1a.kix/0").unwrap();
+        assert_eq!(edit["range"]["start"], json!({ "line": 4, "character": 0 }));
+        assert_eq!(edit["newText"], "    Green => assert(false, \"unhandled: Green\")\n");
+    }
+}
@@ -6,6 +6,7 @@ use kinetix_language::lexer::Lexer;
 use kinetix_language::parser::Parser;
 use kinetix_kicomp::compiler::Compiler;
 use kinetix_kicomp::exn;
+use kinetix_kicomp::output::{self, ErrorFormat};
 use std::fs;
 use std::path::PathBuf;
 use bumpalo::Bump;
@@ -25,6 +26,10 @@ struct Cli {
     /// Show version information
     #[arg(long)]
     version: bool,
+
+    /// How to print errors: human, or json (one object per line)
+    #[arg(long, value_enum, value_name = "FORMAT", default_value = "human")]
+    error_format: ErrorFormat,
 }
 
 fn main() {
@@ -39,7 +44,12 @@ fn main() {
     let source = match fs::read_to_string(&cli.input) {
         Ok(s) => s,
         Err(e) => {
-            eprintln!("Error reading {}: {}", cli.input.display(), e);
+            let msg = format!("Error reading {}: {}", cli.input.display(), e);
+            if cli.error_format == ErrorFormat::Json {
+                eprintln!("{}", output::json_error(msg));
+            } else {
+                eprintln!("{}", msg);
+            }
             std::process::exit(1);
         }
     };
@@ -53,9 +63,14 @@ fn main() {
     let program = parser.parse_program();
 
     if !parser.errors.is_empty() {
-        eprintln!("Parser errors:");
-        for err in &parser.errors {
-            eprintln!("  - {}", err);
+        if cli.error_format == ErrorFormat::Json {
+            let file = cli.input.display().to_string();
+            parser.errors.iter().for_each(|err| eprintln!("{}", output::json_diagnostic(&file, err)));
+        } else {
+            eprintln!("Parser errors:");
+            for err in &parser.errors {
+                eprintln!("  - {}", err);
+            }
         }
         std::process::exit(1);
     }
@@ -87,8 +102,16 @@ fn main() {
             println!("  Functions: {}", compiled.functions.len());
             println!("  Main instructions: {}", compiled.main.instructions.len());
         }
-        Err(e) => {
-            eprintln!("Compilation error: {}", e);
+        Err(errors) => {
+            if cli.error_format == ErrorFormat::Json {
+                let file = cli.input.display().to_string();
+                errors.iter().for_each(|err| eprintln!("{}", output::json_diagnostic(&file, err)));
+            } else {
+                eprintln!("Compilation errors:");
+                for err in &errors {
+                    eprintln!("  - {}", err);
+                }
+            }
             std::process::exit(1);
         }
     }
@@ -1,6 +1,7 @@
 /// Kinetix Compiler Metrics — Build 35
 /// Collects and reports optimization statistics.
 
+use crate::output::paint;
 use std::time::Instant;
 
 /// Compiler metrics collector.
@@ -12,6 +13,10 @@ pub struct CompilerMetrics {
     pub monomorphization_count: usize,
     pub trait_cache_hits: usize,
     pub trait_cache_misses: usize,
+    pub inlined_calls: usize,
+    /// Steps served by a `Session` (see `crate::session`) or the incremental
+    /// cache (`crate::incremental`).
+    pub session_phases: Vec<crate::session::CachedPhase>,
 }
 
 #[derive(Debug, Clone)]
@@ -31,6 +36,8 @@ impl CompilerMetrics {
             monomorphization_count: 0,
             trait_cache_hits: 0,
             trait_cache_misses: 0,
+            inlined_calls: 0,
+            session_phases: Vec::new(),
         }
     }
 
@@ -54,13 +61,13 @@ impl CompilerMetrics {
 
     pub fn print_report(&self) {
         eprintln!();
-        eprintln!("\x1b[1;36m╔══════════════════════════════════════════════════╗\x1b[0m");
-        eprintln!("\x1b[1;36m║         Kinetix Compiler Metrics (Build 35)      ║\x1b[0m");
-        eprintln!("\x1b[1;36m╚══════════════════════════════════════════════════╝\x1b[0m");
+        eprintln!("{}", paint("1;36", "╔══════════════════════════════════════════════════╗"));
+        eprintln!("{}", paint("1;36", "║         Kinetix Compiler Metrics (Build 35)      ║"));
+        eprintln!("{}", paint("1;36", "╚══════════════════════════════════════════════════╝"));
         eprintln!();
 
         if !self.phases.is_empty() {
-            eprintln!("\x1b[1;33m  Optimization Passes:\x1b[0m");
+            eprintln!("{}", paint("1;33", "  Optimization Passes:"));
             for phase in &self.phases {
                 let delta = phase.instructions_before as i64 - phase.instructions_after as i64;
                 let pct = if phase.instructions_before > 0 {
@@ -69,18 +76,32 @@ impl CompilerMetrics {
                     0.0
                 };
                 eprintln!(
-                    "    \x1b[37m{:<30}\x1b[0m {:>5} → {:>5} instrs  \x1b[32m(-{:.1}%)\x1b[0m  {:.2}ms",
-                    phase.name,
+                    "    {} {:>5} → {:>5} instrs  {}  {:.2}ms",
+                    paint("37", format!("{:<30}", phase.name)),
                     phase.instructions_before,
                     phase.instructions_after,
-                    pct,
+                    paint("32", format!("({:+.1}%)", -pct)),
                     phase.duration_ms
                 );
             }
             eprintln!();
         }
 
-        eprintln!("\x1b[1;33m  Summary:\x1b[0m");
+        if !self.session_phases.is_empty() {
+            eprintln!("{}", paint("1;33", "  Cached Steps:"));
+            for phase in &self.session_phases {
+                eprintln!(
+                    "    {} {:>5} reused  {:>5} rebuilt  {:.2}ms",
+                    paint("37", format!("{:<30}", phase.name)),
+                    phase.reused,
+                    phase.rebuilt,
+                    phase.duration_ms
+                );
+            }
+            eprintln!();
+        }
+
+        eprintln!("{}", paint("1;33", "  Summary:"));
         eprintln!("    Instructions before opt: {}", self.total_instructions_before);
         eprintln!("    Instructions after opt:  {}", self.total_instructions_after);
         let total_delta = self.total_instructions_before as i64 - self.total_instructions_after as i64;
@@ -89,8 +110,11 @@ impl CompilerMetrics {
         } else {
             0.0
         };
-        eprintln!("    Total reduction:         \x1b[1;32m{} instructions ({:.1}%)\x1b[0m", total_delta, total_pct);
+        eprintln!("    Total reduction:         {}", paint("1;32", format!("{} instructions ({:.1}%)", total_delta, total_pct)));
 
+        if self.inlined_calls > 0 {
+            eprintln!("    Inlined call sites:      {}", self.inlined_calls);
+        }
         if self.monomorphization_count > 0 {
             eprintln!("    Monomorphizations:       {}", self.monomorphization_count);
         }
@@ -1,6 +1,7 @@
 use crate::types::Type;
 use crate::hir::{HirProgram, HirStatement, HirStmtKind, HirExpression, HirExprKind};
 use crate::ir_hash::DeterministicHasher;
+use kinetix_language::span::Span;
 
 use std::hash::{Hash, Hasher};
 
@@ -117,7 +118,8 @@ pub enum StatementKind {
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct MirStatement {
     pub kind: StatementKind,
-    pub line: usize,
+    /// The source statement it was lowered from.
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -142,7 +144,7 @@ pub enum TerminatorKind {
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Terminator {
     pub kind: TerminatorKind,
-    pub line: usize,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -199,6 +201,7 @@ pub fn is_trivially_copyable(ty: &Type) -> bool {
 
 use crate::types::Substitution;
 use std::collections::HashMap;
+use rayon::prelude::*;
 
 /// Tracks the Goto targets for `break`/`continue` inside the loop currently
 /// being lowered (innermost last), mirroring `Compiler::loop_stack` in
@@ -207,6 +210,7 @@ use std::collections::HashMap;
 struct MirLoopContext {
     continue_target: BasicBlock,
     break_target: BasicBlock,
+    label: Option<String>,
 }
 
 pub struct MirBuilder<'a> {
@@ -217,6 +221,9 @@ pub struct MirBuilder<'a> {
     substitution: &'a Substitution,
     scopes: Vec<Vec<LocalId>>,
     loop_stack: Vec<MirLoopContext>,
+    /// The statement being lowered; the temporaries, drops and jumps made
+    /// for it point at it.
+    span: Span,
     pub functions: Vec<MirFunction>,
 }
 
@@ -231,6 +238,7 @@ impl<'a> MirBuilder<'a> {
             substitution,
             scopes: vec![vec![]], // the root function scope
             loop_stack: vec![],
+            span: Span::default(),
             functions: vec![],
         }
     }
@@ -265,10 +273,10 @@ impl<'a> MirBuilder<'a> {
     /// block is terminated it stays terminated; later cleanup code (e.g. scope
     /// drops on exit) may still append trailing statements after the terminator
     /// is set, which is fine since `statements`/`terminator` are separate fields.
-    fn terminate_current(&mut self, kind: TerminatorKind, line: usize) {
+    fn terminate_current(&mut self, kind: TerminatorKind, span: Span) {
         let block = &mut self.basic_blocks[self.current_block.0];
         if block.terminator.is_none() {
-            block.terminator = Some(Terminator { kind, line });
+            block.terminator = Some(Terminator { kind, span });
         }
     }
 
@@ -276,6 +284,15 @@ impl<'a> MirBuilder<'a> {
         self.basic_blocks[self.current_block.0].terminator.is_some()
     }
 
+    /// The loop a `break`/`continue` targets: the innermost one, or the
+    /// innermost one carrying `label` when the jump is labeled.
+    fn find_loop(&self, label: Option<&str>) -> Option<&MirLoopContext> {
+        match label {
+            None => self.loop_stack.last(),
+            Some(name) => self.loop_stack.iter().rev().find(|ctx| ctx.label.as_deref() == Some(name)),
+        }
+    }
+
     /// Like `lower_expression_to_operand`, but for a bare local it borrows the
     /// place instead of copying/moving it -- used where the caller only needs
     /// to *read through* the value (e.g. indexing into an array) without
@@ -291,15 +308,28 @@ impl<'a> MirBuilder<'a> {
     }
 
     pub fn build(mut self, hir: &HirProgram) -> (MirFunction, Vec<MirFunction>) {
-        for stmt in &hir.statements {
+        // Top-level functions are independent of the main block, so they are
+        // lowered in parallel up front and picked up in order by the walk.
+        let substitution = self.substitution;
+        let lowered: Vec<Option<Vec<MirFunction>>> = hir.statements
+            .par_iter()
+            .map(|stmt| match stmt.kind {
+                HirStmtKind::Function { .. } => Some(Self::lower_function(substitution, stmt)),
+                _ => None,
+            })
+            .collect();
+        for (stmt, functions) in hir.statements.iter().zip(lowered) {
             if self.current_block_terminated() { break; }
-            self.lower_statement(stmt);
+            match functions {
+                Some(functions) => self.functions.extend(functions),
+                None => self.lower_statement(stmt),
+            }
         }
 
         // Drop root scope variables before returning
-        self.drop_current_scope(0); // 0 is line number for function exit
+        self.drop_current_scope(Span::default()); // the exit has no statement
 
-        self.terminate_current(TerminatorKind::Return(None), 0);
+        self.terminate_current(TerminatorKind::Return(None), Span::default());
 
         let main_fn = MirFunction {
             name: "<main>".to_string(),
@@ -311,14 +341,44 @@ impl<'a> MirBuilder<'a> {
         (main_fn, self.functions)
     }
 
-    fn drop_current_scope(&mut self, line: usize) {
+    /// Lowers a `Function` statement with a builder of its own (a function
+    /// sees none of the enclosing locals): the function itself, followed by
+    /// any functions nested in it.
+    fn lower_function(substitution: &'a Substitution, stmt: &HirStatement) -> Vec<MirFunction> {
+        let HirStmtKind::Function { name, parameters, body, return_type } = &stmt.kind else {
+            return Vec::new();
+        };
+        let mut sub_builder = MirBuilder::new(substitution);
+        sub_builder.span = stmt.location();
+        let mut arg_ids = Vec::new();
+        for (param_name, ty) in parameters {
+            let id = sub_builder.push_local(Some(param_name.clone()), ty.clone(), Mutability::Not);
+            arg_ids.push(id);
+        }
+        sub_builder.lower_statement(body);
+        sub_builder.drop_current_scope(stmt.location());
+        sub_builder.terminate_current(TerminatorKind::Return(None), stmt.location());
+
+        let mut functions = vec![MirFunction {
+            name: name.clone(),
+            args: arg_ids,
+            return_ty: substitution.apply_default(return_type),
+            locals: sub_builder.locals,
+            basic_blocks: sub_builder.basic_blocks,
+        }];
+        // Also pull up any deeply nested functions
+        functions.extend(sub_builder.functions);
+        functions
+    }
+
+    fn drop_current_scope(&mut self, span: Span) {
         if let Some(exiting_scope) = self.scopes.pop() {
             for local_id in exiting_scope.into_iter().rev() {
                 let ty = &self.locals[local_id.0].ty;
                 if !is_trivially_copyable(ty) {
                     self.push_statement(MirStatement {
                         kind: StatementKind::Drop(Place { local: local_id }),
-                        line,
+                        span,
                     });
                 }
             }
@@ -326,6 +386,12 @@ impl<'a> MirBuilder<'a> {
     }
 
     fn lower_statement(&mut self, stmt: &HirStatement) {
+        let outer = std::mem::replace(&mut self.span, stmt.location());
+        self.lower_statement_kind(stmt);
+        self.span = outer;
+    }
+
+    fn lower_statement_kind(&mut self, stmt: &HirStatement) {
         match &stmt.kind {
             HirStmtKind::Let { name, mutable, value } => {
                 let mutability = if *mutable { Mutability::Mut } else { Mutability::Not };
@@ -335,14 +401,14 @@ impl<'a> MirBuilder<'a> {
                 let rvalue = self.lower_expression_to_rvalue(value);
                 self.push_statement(MirStatement {
                     kind: StatementKind::Assign(place, rvalue),
-                    line: stmt.line,
+                    span: self.span,
                 });
             }
             HirStmtKind::Expression { expression } => {
                 let rvalue = self.lower_expression_to_rvalue(expression);
                 self.push_statement(MirStatement {
                     kind: StatementKind::Expression(rvalue),
-                    line: stmt.line,
+                    span: self.span,
                 });
             }
             HirStmtKind::Block { statements } => {
@@ -351,38 +417,20 @@ impl<'a> MirBuilder<'a> {
                     if self.current_block_terminated() { break; }
                     self.lower_statement(s);
                 }
-                self.drop_current_scope(stmt.line);
-            }
-            HirStmtKind::Function { name, parameters, body, return_type } => {
-                let mut sub_builder = MirBuilder::new(self.substitution);
-                let mut arg_ids = Vec::new();
-                for (param_name, ty) in parameters {
-                    let id = sub_builder.push_local(Some(param_name.clone()), ty.clone(), Mutability::Not);
-                    arg_ids.push(id);
-                }
-                sub_builder.lower_statement(body);
-                sub_builder.drop_current_scope(stmt.line);
-                sub_builder.terminate_current(TerminatorKind::Return(None), stmt.line);
-
-                let mir_fn = MirFunction {
-                    name: name.clone(),
-                    args: arg_ids,
-                    return_ty: self.substitution.apply_default(return_type),
-                    locals: sub_builder.locals,
-                    basic_blocks: sub_builder.basic_blocks,
-                };
-                self.functions.push(mir_fn);
-                // Also pull up any deeply nested functions
-                self.functions.extend(sub_builder.functions);
+                self.drop_current_scope(self.span);
+            }
+            HirStmtKind::Function { .. } => {
+                let functions = Self::lower_function(self.substitution, stmt);
+                self.functions.extend(functions);
             }
             HirStmtKind::Class { methods, .. } => {
                 for m in methods {
                     self.lower_statement(m);
                 }
             }
-            HirStmtKind::While { condition, body } => {
+            HirStmtKind::While { condition, body, label } => {
                 let header = self.new_block();
-                self.terminate_current(TerminatorKind::Goto(header), stmt.line);
+                self.terminate_current(TerminatorKind::Goto(header), self.span);
                 self.current_block = header;
 
                 let cond_op = self.lower_expression_to_operand(condition);
@@ -390,20 +438,20 @@ impl<'a> MirBuilder<'a> {
                 let exit_block = self.new_block();
                 self.terminate_current(
                     TerminatorKind::Branch { cond: cond_op, then_block: body_block, else_block: exit_block },
-                    stmt.line,
+                    self.span,
                 );
 
                 self.current_block = body_block;
-                self.loop_stack.push(MirLoopContext { continue_target: header, break_target: exit_block });
+                self.loop_stack.push(MirLoopContext { continue_target: header, break_target: exit_block, label: label.clone() });
                 self.lower_statement(body);
                 self.loop_stack.pop();
                 if !self.current_block_terminated() {
-                    self.terminate_current(TerminatorKind::Goto(header), stmt.line);
+                    self.terminate_current(TerminatorKind::Goto(header), self.span);
                 }
 
                 self.current_block = exit_block;
             }
-            HirStmtKind::For { iterator, range, body } => {
+            HirStmtKind::For { iterator, range, body, label } => {
                 // Evaluate the iterable once into a temp place (borrowed, not moved,
                 // so it can still be indexed on every iteration below).
                 let iter_ty = range.ty.clone();
@@ -412,14 +460,14 @@ impl<'a> MirBuilder<'a> {
                 let range_rvalue = self.lower_expression_to_rvalue(range);
                 self.push_statement(MirStatement {
                     kind: StatementKind::Assign(iter_place.clone(), range_rvalue),
-                    line: stmt.line,
+                    span: self.span,
                 });
 
                 let idx_local = self.push_local(None, Type::Int, Mutability::Mut);
                 let idx_place = Place { local: idx_local };
                 self.push_statement(MirStatement {
                     kind: StatementKind::Assign(idx_place.clone(), RValue::Use(Operand::Constant(Constant::Int(0)))),
-                    line: stmt.line,
+                    span: self.span,
                 });
 
                 // len(iter) -- modeled the same way any other unresolved builtin call
@@ -433,11 +481,11 @@ impl<'a> MirBuilder<'a> {
                 );
                 self.push_statement(MirStatement {
                     kind: StatementKind::Assign(len_place.clone(), len_call),
-                    line: stmt.line,
+                    span: self.span,
                 });
 
                 let header = self.new_block();
-                self.terminate_current(TerminatorKind::Goto(header), stmt.line);
+                self.terminate_current(TerminatorKind::Goto(header), self.span);
                 self.current_block = header;
 
                 let cond_local = self.push_local(None, Type::Bool, Mutability::Not);
@@ -447,14 +495,14 @@ impl<'a> MirBuilder<'a> {
                         cond_place.clone(),
                         RValue::BinaryOp("<".to_string(), Operand::Copy(idx_place.clone()), Operand::Copy(len_place.clone())),
                     ),
-                    line: stmt.line,
+                    span: self.span,
                 });
 
                 let body_block = self.new_block();
                 let exit_block = self.new_block();
                 self.terminate_current(
                     TerminatorKind::Branch { cond: Operand::Copy(cond_place), then_block: body_block, else_block: exit_block },
-                    stmt.line,
+                    self.span,
                 );
 
                 self.current_block = body_block;
@@ -467,15 +515,15 @@ impl<'a> MirBuilder<'a> {
                         Place { local: iter_var_local },
                         RValue::BinaryOp("[]".to_string(), Operand::Borrow(iter_place.clone(), Mutability::Not), Operand::Copy(idx_place.clone())),
                     ),
-                    line: stmt.line,
+                    span: self.span,
                 });
 
                 let increment_block = self.new_block();
-                self.loop_stack.push(MirLoopContext { continue_target: increment_block, break_target: exit_block });
+                self.loop_stack.push(MirLoopContext { continue_target: increment_block, break_target: exit_block, label: label.clone() });
                 self.lower_statement(body);
                 self.loop_stack.pop();
                 if !self.current_block_terminated() {
-                    self.terminate_current(TerminatorKind::Goto(increment_block), stmt.line);
+                    self.terminate_current(TerminatorKind::Goto(increment_block), self.span);
                 }
 
                 self.current_block = increment_block;
@@ -484,31 +532,32 @@ impl<'a> MirBuilder<'a> {
                         idx_place.clone(),
                         RValue::BinaryOp("+".to_string(), Operand::Copy(idx_place.clone()), Operand::Constant(Constant::Int(1))),
                     ),
-                    line: stmt.line,
+                    span: self.span,
                 });
-                self.terminate_current(TerminatorKind::Goto(header), stmt.line);
+                self.terminate_current(TerminatorKind::Goto(header), self.span);
 
                 self.current_block = exit_block;
             }
-            HirStmtKind::Break => {
-                // A `break`/`continue` outside any loop is a real error, but it's
-                // already caught at the bytecode-codegen layer (`compiler.rs`'s own
-                // `loop_stack` check), which runs independently over the same AST --
-                // no need to duplicate that check in this (validation-only) MIR pass.
-                if let Some(ctx) = self.loop_stack.last() {
+            HirStmtKind::Break { label } => {
+                // A `break`/`continue` outside any loop (or naming an unknown
+                // label) is a real error, but it's already caught at the
+                // bytecode-codegen layer (`compiler.rs`'s own `loop_stack` check),
+                // which runs independently over the same AST -- no need to
+                // duplicate that check in this (validation-only) MIR pass.
+                if let Some(ctx) = self.find_loop(label.as_deref()) {
                     let target = ctx.break_target;
-                    self.terminate_current(TerminatorKind::Goto(target), stmt.line);
+                    self.terminate_current(TerminatorKind::Goto(target), self.span);
                 }
             }
-            HirStmtKind::Continue => {
-                if let Some(ctx) = self.loop_stack.last() {
+            HirStmtKind::Continue { label } => {
+                if let Some(ctx) = self.find_loop(label.as_deref()) {
                     let target = ctx.continue_target;
-                    self.terminate_current(TerminatorKind::Goto(target), stmt.line);
+                    self.terminate_current(TerminatorKind::Goto(target), self.span);
                 }
             }
             HirStmtKind::Return { value } => {
                 let ret_operand = value.as_ref().map(|val_expr| self.lower_expression_to_operand(val_expr));
-                self.terminate_current(TerminatorKind::Return(ret_operand), stmt.line);
+                self.terminate_current(TerminatorKind::Return(ret_operand), self.span);
             }
             // State/Computed/Effect are intentionally not lowered into MIR at all
             // (see `check_reactive_isolation` in ssa_validate.rs).
@@ -522,6 +571,7 @@ impl<'a> MirBuilder<'a> {
     /// if-expressions (`let x = if cond { 1 } else { 2 }`) a real value instead of
     /// the previous `Null` placeholder.
     fn lower_statement_as_value(&mut self, stmt: &HirStatement, result_place: Option<&Place>) {
+        let outer = std::mem::replace(&mut self.span, stmt.location());
         match &stmt.kind {
             HirStmtKind::Block { statements } => {
                 self.scopes.push(vec![]);
@@ -534,7 +584,7 @@ impl<'a> MirBuilder<'a> {
                         self.lower_statement_as_value(last, result_place);
                     }
                 }
-                self.drop_current_scope(stmt.line);
+                self.drop_current_scope(self.span);
             }
             HirStmtKind::Expression { expression } => {
                 let rvalue = self.lower_expression_to_rvalue(expression);
@@ -542,11 +592,11 @@ impl<'a> MirBuilder<'a> {
                     Some(place) => {
                         self.push_statement(MirStatement {
                             kind: StatementKind::Assign(place.clone(), rvalue),
-                            line: stmt.line,
+                            span: self.span,
                         });
                     }
                     None => {
-                        self.push_statement(MirStatement { kind: StatementKind::Expression(rvalue), line: stmt.line });
+                        self.push_statement(MirStatement { kind: StatementKind::Expression(rvalue), span: self.span });
                     }
                 }
             }
@@ -567,13 +617,14 @@ impl<'a> MirBuilder<'a> {
                         };
                         self.push_statement(MirStatement {
                             kind: StatementKind::Assign(place.clone(), RValue::Use(operand)),
-                            line: stmt.line,
+                            span: self.span,
                         });
                     }
                 }
             }
             _ => self.lower_statement(stmt),
         }
+        self.span = outer;
     }
 
     fn lower_expression_to_rvalue(&mut self, expr: &HirExpression) -> RValue {
@@ -649,8 +700,15 @@ impl<'a> MirBuilder<'a> {
                     .collect();
                 RValue::Aggregate(name.clone(), ops)
             }
-            HirExprKind::MethodCall { .. } => {
-                unreachable!("MethodCall should have been statically dispatched to Call in Type Normalizer.");
+            HirExprKind::MethodCall { object, method, arguments } => {
+                // The Type Normalizer rewrites every method call whose receiver
+                // is a class instance or a dot path; what is left is dispatched
+                // at runtime on a computed receiver (`doc.first("a").text()`).
+                // Same convention as `"[]"`: a `Call` of a `"."`-prefixed name
+                // tag with the receiver, read-only, as its first argument.
+                let mut ops = vec![self.lower_expression_to_borrowed_operand(object)];
+                ops.extend(arguments.iter().map(|a| self.lower_expression_to_operand(a)));
+                RValue::Call(Operand::Constant(Constant::String(format!(".{}", method))), ops)
             }
             HirExprKind::If { condition, consequence, alternative } => {
                 let cond_op = self.lower_expression_to_operand(condition);
@@ -666,13 +724,13 @@ impl<'a> MirBuilder<'a> {
                 let merge_block = self.new_block();
                 self.terminate_current(
                     TerminatorKind::Branch { cond: cond_op, then_block, else_block },
-                    0,
+                    self.span,
                 );
 
                 self.current_block = then_block;
                 self.lower_statement_as_value(consequence, result_place.as_ref());
                 if !self.current_block_terminated() {
-                    self.terminate_current(TerminatorKind::Goto(merge_block), 0);
+                    self.terminate_current(TerminatorKind::Goto(merge_block), self.span);
                 }
 
                 self.current_block = else_block;
@@ -692,13 +750,13 @@ impl<'a> MirBuilder<'a> {
                         if let Some(place) = &result_place {
                             self.push_statement(MirStatement {
                                 kind: StatementKind::Assign(place.clone(), RValue::Use(Operand::Constant(Constant::Null))),
-                                line: 0,
+                                span: self.span,
                             });
                         }
                     }
                 }
                 if !self.current_block_terminated() {
-                    self.terminate_current(TerminatorKind::Goto(merge_block), 0);
+                    self.terminate_current(TerminatorKind::Goto(merge_block), self.span);
                 }
 
                 self.current_block = merge_block;
@@ -741,7 +799,7 @@ impl<'a> MirBuilder<'a> {
                         let rvalue = self.lower_expression_to_rvalue(value);
                         self.push_statement(MirStatement {
                             kind: StatementKind::Assign(place, rvalue),
-                            line: 0,
+                            span: self.span,
                         });
                         return RValue::Use(Operand::Constant(Constant::Null));
                     }
@@ -759,7 +817,7 @@ impl<'a> MirBuilder<'a> {
         let place = Place { local: temp_id };
         self.push_statement(MirStatement {
             kind: StatementKind::Assign(place.clone(), rvalue),
-            line: 0, // temp
+            span: self.span,
         });
         
         let resolved_ty = self.substitution.apply_default(&expr.ty);
@@ -946,4 +1004,16 @@ mod tests {
         });
         assert!(has_len_callee, "the for-loop's implicit len() bounds check should carry its callee name");
     }
+
+    #[test]
+    fn test_mir_method_call_on_computed_receiver() {
+        let mir = compile_to_mir("let doc = html.parse(\"<a>x</a>\")\nlet t = doc.first(\"a\").text()");
+        let has_method_tag = mir.main_block.basic_blocks.iter().any(|b| {
+            b.statements.iter().any(|s| matches!(
+                &s.kind,
+                StatementKind::Assign(_, RValue::Call(Operand::Constant(Constant::String(name)), args)) if name == ".text" && args.len() == 1
+            ))
+        });
+        assert!(has_method_tag, "a method call on a call result should lower to a '.'-tagged Call with its receiver");
+    }
 }
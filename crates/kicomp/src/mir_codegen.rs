@@ -25,8 +25,9 @@ use crate::mir::{
     MirProgram, MirFunction, MirStatement, StatementKind, Terminator, TerminatorKind,
     RValue, Operand, LocalId, Constant as MirConstant,
 };
-use crate::ir::{CompiledProgram, CompiledFunction, Instruction, Opcode, Constant as IrConstant};
+use crate::ir::{CompiledProgram, CompiledFunction, Instruction, Opcode, Constant as IrConstant, MAX_OPERAND};
 use crate::types::Type;
+use kinetix_language::span::Span;
 
 /// Lowers a full `MirProgram` (already validated) to a `CompiledProgram`
 /// runnable by the same VM `compiler.rs`'s output runs on.
@@ -86,13 +87,13 @@ fn compile_function_with_prologue(
         .map(|id| mir_func.locals[id.0].name.clone().unwrap_or_default())
         .collect();
 
-    let locals_len = mir_func.locals.len() as u16;
+    let locals_len = mir_func.locals.len().min(MAX_OPERAND) as u16;
     let mut cg = FnCodegen {
         func,
         mir_locals: &mir_func.locals,
         next_scratch: locals_len,
         max_register: locals_len,
-        current_line: 0,
+        current_span: Span::default(),
         block_offsets: vec![0; mir_func.basic_blocks.len()],
         pending_jumps: vec![],
     };
@@ -103,7 +104,7 @@ fn compile_function_with_prologue(
     for (i, block) in mir_func.basic_blocks.iter().enumerate() {
         cg.block_offsets[i] = cg.func.instructions.len();
         for stmt in &block.statements {
-            cg.current_line = stmt.line;
+            cg.current_span = stmt.span;
             cg.compile_statement(stmt)?;
             cg.next_scratch = locals_len;
         }
@@ -117,6 +118,8 @@ fn compile_function_with_prologue(
     }
 
     cg.func.locals = cg.max_register;
+    cg.func.check_operand_widths()?;
+    cg.func.check_exception_table()?;
     Ok(cg.func)
 }
 
@@ -135,7 +138,7 @@ struct FnCodegen<'a> {
     mir_locals: &'a [crate::mir::LocalDecl],
     next_scratch: u16,
     max_register: u16,
-    current_line: usize,
+    current_span: Span,
     /// `block_offsets[i]` = instruction index where MIR block `i`'s first
     /// instruction landed.
     block_offsets: Vec<usize>,
@@ -153,7 +156,7 @@ impl<'a> FnCodegen<'a> {
 
     fn alloc_scratch(&mut self) -> u16 {
         let r = self.next_scratch;
-        self.next_scratch += 1;
+        self.next_scratch = self.next_scratch.saturating_add(1);
         if self.next_scratch > self.max_register {
             self.max_register = self.next_scratch;
         }
@@ -162,7 +165,8 @@ impl<'a> FnCodegen<'a> {
 
     fn emit(&mut self, instr: Instruction) -> usize {
         let idx = self.func.emit(instr);
-        self.func.line_map.push(self.current_line as u32);
+        self.func.line_map.push(self.current_span.line as u32);
+        self.func.column_map.push(self.current_span.column as u32);
         idx
     }
 
@@ -189,7 +193,7 @@ impl<'a> FnCodegen<'a> {
         let term = term.ok_or_else(|| {
             "mir_codegen: basic block has no terminator (malformed MIR)".to_string()
         })?;
-        self.current_line = term.line;
+        self.current_span = term.span;
         match &term.kind {
             TerminatorKind::Return(Some(op)) => {
                 let reg = self.operand_to_register(op);
@@ -231,6 +235,9 @@ impl<'a> FnCodegen<'a> {
                 let opcode = unop_to_opcode(op)?;
                 self.emit(Instruction::ab(opcode, dst, r_reg));
             }
+            RValue::Call(Operand::Constant(MirConstant::String(name)), _) if name.starts_with('.') => {
+                return Err(format!("mir_codegen: method call '{}' on a computed receiver is not supported", name));
+            }
             RValue::Call(func_op, args) => {
                 let result_reg = self.compile_call(func_op, args);
                 if result_reg != dst {
@@ -323,7 +330,7 @@ impl<'a> FnCodegen<'a> {
         }
         // VM calling convention: the callee occupies `call_reg`, arguments
         // occupy `call_reg+1..=call_reg+n` contiguously (mirrors
-        // `compiler.rs`'s own `Expression::Call` lowering exactly).
+        // `compiler.rs`'s own `ExpressionKind::Call` lowering exactly).
         for arg in args {
             let expected_reg = self.alloc_scratch();
             self.load_operand_into(arg, expected_reg);
@@ -333,7 +340,7 @@ impl<'a> FnCodegen<'a> {
     }
 
     /// `MakeArray` reads `n` contiguous registers starting at its `A` operand
-    /// (see `compiler.rs`'s `Expression::ArrayLiteral`), but MIR's elements
+    /// (see `compiler.rs`'s `ExpressionKind::ArrayLiteral`), but MIR's elements
     /// are already-materialized locals scattered arbitrarily across the
     /// register space -- unlike `compiler.rs`, which computes each element
     /// fresh into naturally-contiguous temps. Stage a fresh contiguous range
@@ -0,0 +1,298 @@
+//! Source modules (`import util.math as m`): each is checked and compiled as its
+//! own unit, in dependency order, and linked under its module name.
+
+use crate::ir::CompiledProgram;
+use bumpalo::Bump;
+use kinetix_language::ast::Statement;
+use kinetix_language::diagnostic::{codes, Diagnostic, Subject};
+use kinetix_language::lexer::Lexer;
+use kinetix_language::parser::Parser;
+use std::path::{Path, PathBuf};
+
+/// A compiled source module.
+pub struct Module {
+    /// Dotted module path, as written after `import`; also its namespace.
+    pub name: String,
+    pub path: PathBuf,
+    pub program: CompiledProgram,
+    /// `(kind, name, line)` of its top-level items an importer can't reach:
+    /// functions without `pub`, and every type (only functions are exported).
+    pub private: Vec<(&'static str, String, usize)>,
+}
+
+impl Module {
+    /// The module's `pub fn`s, as `(name, arity)`.
+    pub fn exports(&self) -> Vec<(String, usize)> {
+        crate::link::exports(&self.program)
+    }
+}
+
+/// The name an import is referred to by: its `as` alias, or the last segment
+/// of the module path.
+pub fn alias<'s>(module: &'s str, alias: Option<&'s str>) -> &'s str {
+    alias.unwrap_or_else(|| module.rsplit('.').next().unwrap_or(module))
+}
+
+/// `(module, alias)` of every `import` in `statements`.
+pub fn imports<'s>(statements: &'s [Statement<'_>]) -> Vec<(&'s str, &'s str)> {
+    statements.iter()
+        .filter_map(|stmt| match stmt {
+            Statement::Import { module, alias: name, .. } => Some((module.as_str(), alias(module, name.as_deref()))),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The file module `module` is read from.
+pub fn path_of(root: &Path, module: &str) -> PathBuf {
+    let mut path = root.to_path_buf();
+    path.extend(module.split('.'));
+    path.set_extension("kix");
+    path
+}
+
+/// Every module's exports under their qualified names, for
+/// `symbol::resolve_program_with_externs`.
+pub fn externs(modules: &[Module]) -> Vec<(String, usize)> {
+    modules.iter()
+        .flat_map(|m| m.exports().into_iter().map(|(name, arity)| (crate::link::qualify(&m.name, &name), arity)))
+        .collect()
+}
+
+/// `Module::private` of a module whose source is `statements`.
+fn private_items(statements: &[Statement<'_>]) -> Vec<(&'static str, String, usize)> {
+    statements.iter()
+        .filter_map(|stmt| match stmt {
+            Statement::Function { name, public: false, line, .. } => Some(("Function", name.to_string(), *line)),
+            Statement::Class { name, line, .. } => Some(("Class", name.to_string(), *line)),
+            Statement::Struct { name, line, .. } => Some(("Struct", name.to_string(), *line)),
+            Statement::Enum { name, line, .. } => Some(("Enum", name.to_string(), *line)),
+            Statement::Trait { name, line, .. } => Some(("Trait", name.to_string(), *line)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Rewrites each "has no public function" error about an item the module
+/// does define but doesn't export to say it is private, with a note
+/// pointing at its declaration.
+pub fn explain_private(diagnostics: &mut [Diagnostic], modules: &[Module]) {
+    for d in diagnostics.iter_mut() {
+        let Some(Subject::ModuleMember { module, member }) = &d.subject else { continue };
+        let Some(m) = modules.iter().find(|m| m.name == *module) else { continue };
+        let Some((kind, _, line)) = m.private.iter().find(|(_, name, _)| name == member) else { continue };
+        let message = match *kind {
+            "Function" => format!("Function '{}' of module '{}' is private (declared without `pub` at line {})", member, module, line),
+            _ => format!("{} '{}' of module '{}' is private (declared at line {}; only `pub fn`s are exported)", kind, member, module, line),
+        };
+        let note = format!("'{}' is declared at {}:{}", member, m.path.display(), line);
+        d.message = message;
+        d.notes.push(note);
+    }
+}
+
+/// Adds to each "Undeclared variable" error about a name an imported module
+/// exports the fix qualifying it with that import's alias (`square` becomes
+/// `m.square`). When several imports export it, the name is ambiguous: the
+/// error gets a help line naming them instead.
+pub fn qualify_undeclared(diagnostics: &mut [Diagnostic], statements: &[Statement<'_>], modules: &[Module]) {
+    let imports = imports(statements);
+    for d in diagnostics.iter_mut() {
+        let Some(Subject::Undeclared(name)) = d.subject.clone() else { continue };
+        let name = name.as_str();
+        let aliases: Vec<&str> = imports.iter()
+            .filter(|(module, _)| modules.iter().any(|m| m.name == *module && m.exports().iter().any(|(export, _)| export == name)))
+            .map(|(_, alias)| *alias)
+            .collect();
+        match aliases.as_slice() {
+            [] => {}
+            [alias] => {
+                // The error points at the identifier, when the parser saw it
+                if let Some(span) = d.span().filter(|span| span.column > 0) {
+                    let fix = format!("{}.{}", alias, name);
+                    *d = d.clone().with_replacement(format!("use {}", fix), span, name.chars().count(), fix);
+                }
+            }
+            _ => {
+                let options: Vec<String> = aliases.iter().map(|alias| format!("{}.{}", alias, name)).collect();
+                *d = d.clone().with_help(format!("'{}' is exported by more than one import: {}", name, options.join(", ")));
+            }
+        }
+    }
+}
+
+/// Loads the modules `statements` (the program in `entry`) imports, directly
+/// or not. `compile` turns a module's source into a unit, given the modules
+/// loaded so far (which include everything that module imports). Returns the
+/// modules in dependency order: each after the modules it imports. An import
+/// that can't be loaded (a cycle, a missing file) is an `E0003` diagnostic
+/// in the importing file; `compile`'s own errors pass through.
+pub fn load<E: From<Diagnostic>>(
+    entry: &Path,
+    statements: &[Statement<'_>],
+    mut compile: impl FnMut(&Path, &str, &[Module]) -> Result<CompiledProgram, E>,
+) -> Result<Vec<Module>, E> {
+    let mut loader = Loader {
+        root: entry.parent().unwrap_or(Path::new(".")).to_path_buf(),
+        modules: Vec::new(),
+        stack: Vec::new(),
+        compile: &mut compile,
+    };
+    loader.visit(entry, statements)?;
+    Ok(loader.modules)
+}
+
+type Compile<'c, E> = dyn FnMut(&Path, &str, &[Module]) -> Result<CompiledProgram, E> + 'c;
+
+struct Loader<'c, E> {
+    root: PathBuf,
+    modules: Vec<Module>,
+    /// Modules being loaded, outermost first, for cycle reports.
+    stack: Vec<String>,
+    compile: &'c mut Compile<'c, E>,
+}
+
+impl<E: From<Diagnostic>> Loader<'_, E> {
+    fn visit(&mut self, importer: &Path, statements: &[Statement<'_>]) -> Result<(), E> {
+        for stmt in statements {
+            let Statement::Import { module, line, .. } = stmt else { continue };
+            let located = |msg: String| E::from(Diagnostic::error(codes::LINK, msg).at_line(*line).in_file(importer.display().to_string()));
+            if self.modules.iter().any(|m| m.name == *module) {
+                continue;
+            }
+            if let Some(start) = self.stack.iter().position(|m| m == module) {
+                let cycle = self.stack[start..].join(" -> ");
+                return Err(located(format!("Import cycle: {} -> {}", cycle, module)));
+            }
+            let path = path_of(&self.root, module);
+            let source = std::fs::read_to_string(&path)
+                .map_err(|e| located(format!("Cannot import '{}' from {}: {}", module, path.display(), e)))?;
+
+            // Its imports come first; parse errors are left to `compile`.
+            let arena = Bump::new();
+            let program = Parser::new(Lexer::new(&source), &arena).parse_program();
+            self.stack.push(module.clone());
+            self.visit(&path, &program.statements)?;
+            self.stack.pop();
+
+            let private = private_items(&program.statements);
+            let program = (self.compile)(&path, &source, &self.modules)?;
+            if !program.reactive_graph.nodes.is_empty() {
+                return Err(located(format!("Module '{}' declares reactive state; only functions and classes can be imported", module)));
+            }
+            self.modules.push(Module { name: module.clone(), path, program, private });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::Compiler;
+    use crate::ir::{Constant, Opcode};
+
+    fn compile(source: &str) -> CompiledProgram {
+        let arena = Bump::new();
+        let mut parser = Parser::new(Lexer::new(source), &arena);
+        let program = parser.parse_program();
+        assert!(parser.errors.is_empty(), "Parser errors: {:?}", parser.errors);
+        Compiler::new().compile(&program.statements, None).expect("compiles").clone()
+    }
+
+    #[test]
+    fn modules_load_in_dependency_order_once_and_link_under_their_names() {
+        let root = std::env::temp_dir().join(format!("kinetix-modules-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("util")).unwrap();
+        std::fs::write(root.join("util/math.kix"), "pub fn square(x: int) -> int { return x * x }\n").unwrap();
+        std::fs::write(root.join("util/fmt.kix"), "import util.math as um\npub fn show(x: int) -> str { return str(um.square(x)) }\n").unwrap();
+        let entry = root.join("main.kix");
+        let source = "import util.fmt as f\nimport util.math as m\nprintln(f.show(m.square(2)))\n";
+
+        let arena = Bump::new();
+        let program = Parser::new(Lexer::new(source), &arena).parse_program();
+        let mut compiled = Vec::new();
+        let modules = load(&entry, &program.statements, |path, source, loaded| {
+            compiled.push((path.to_path_buf(), loaded.len()));
+            Ok::<_, Box<Diagnostic>>(compile(source))
+        }).unwrap();
+        let names: Vec<&str> = modules.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, ["util.math", "util.fmt"]);
+        assert_eq!(compiled, [(root.join("util/math.kix"), 0), (root.join("util/fmt.kix"), 1)]);
+        assert_eq!(externs(&modules), [("util.math::square".to_string(), 1), ("util.fmt::show".to_string(), 1)]);
+
+        // `f.show` and `m.square` reach the namespaced globals
+        let mut compiler = Compiler::new();
+        for module in &modules {
+            compiler.link_module(module);
+        }
+        let linked = compiler.compile(&program.statements, None).unwrap();
+        let globals: Vec<&Constant> = linked.main.instructions.iter()
+            .filter(|i| i.opcode == Opcode::GetGlobal)
+            .map(|i| &linked.main.constants[i.b as usize])
+            .collect();
+        for name in ["util.fmt::show", "util.math::square"] {
+            assert!(globals.contains(&&Constant::String(name.to_string())), "{} in {:?}", name, globals);
+        }
+        assert_eq!(linked.modules, ["util.math", "util.fmt"]);
+
+        std::fs::write(root.join("util/math.kix"), "import util.fmt\n").unwrap();
+        let err: Box<Diagnostic> = load(&entry, &program.statements, |_, source, _| Ok(compile(source))).err().unwrap();
+        assert_eq!((err.code, err.message.as_str()), (codes::LINK, "Import cycle: util.fmt -> util.math -> util.fmt"));
+        assert_eq!((err.file, err.spans[0].line), (Some(root.join("util/math.kix").display().to_string()), 1));
+
+        let program = Parser::new(Lexer::new("import util.missing\n"), &arena).parse_program();
+        let err: Box<Diagnostic> = load(&entry, &program.statements, |_, source, _| Ok(compile(source))).err().unwrap();
+        assert!(err.message.starts_with("Cannot import 'util.missing'"), "{}", err);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn undeclared_names_a_module_exports_get_qualified() {
+        let module = |name: &str, source: &str| Module { name: name.to_string(), path: PathBuf::new(), program: compile(source), private: Vec::new() };
+        let modules = [
+            module("util.math", "pub fn square(x: int) -> int { return x * x }\npub fn half(x: int) -> int { return x / 2 }\n"),
+            module("util.geo", "pub fn half(x: int) -> int { return x / 2 }\n"),
+        ];
+        let source = "import util.math as m\nimport util.geo\nprintln(square(2) + square(3) + half(4) + cube(1))\n";
+        let arena = Bump::new();
+        let program = Parser::new(Lexer::new(source), &arena).parse_program();
+        let mut diagnostics = crate::symbol::resolve_program_with_externs(&program.statements, &[]).unwrap_err();
+        assert_eq!(diagnostics.len(), 4);
+        qualify_undeclared(&mut diagnostics, &program.statements, &modules);
+
+        let fixes: Vec<_> = diagnostics.iter().flat_map(|d| &d.fixes).collect();
+        assert_eq!(fixes.iter().map(|f| (f.message.as_str(), f.at.column)).collect::<Vec<_>>(), [("use m.square", 9), ("use m.square", 21)]);
+        assert_eq!(diagnostics[2].help.as_deref(), Some("'half' is exported by more than one import: m.half, geo.half"));
+        assert!(diagnostics[3].fixes.is_empty() && diagnostics[3].help.is_none());
+        assert_eq!(
+            kinetix_language::diagnostic::Fix::apply(source, &fixes),
+            "import util.math as m\nimport util.geo\nprintln(m.square(2) + m.square(3) + half(4) + cube(1))\n"
+        );
+    }
+
+    #[test]
+    fn names_a_module_keeps_private_point_at_their_declaration() {
+        let source = "fn helper(x: int) -> int { return x }\nclass Point { pub x: int }\npub fn square(x: int) -> int { return helper(x) * x }\n";
+        let arena = Bump::new();
+        let program = Parser::new(Lexer::new(source), &arena).parse_program();
+        let path = PathBuf::from("util/math.kix");
+        let modules = [Module { name: "util.math".to_string(), path: path.clone(), program: compile(source), private: private_items(&program.statements) }];
+        let mut diagnostics: Vec<Diagnostic> = ["helper", "Point", "cube"].iter()
+            .map(|name| {
+                let subject = Subject::ModuleMember { module: "util.math".to_string(), member: name.to_string() };
+                Diagnostic::error(codes::SYMBOL, format!("Module 'util.math' has no public function '{}'", name)).at_line(2).about(subject)
+            })
+            .collect();
+        explain_private(&mut diagnostics, &modules);
+
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(messages, [
+            "Function 'helper' of module 'util.math' is private (declared without `pub` at line 1)",
+            "Class 'Point' of module 'util.math' is private (declared at line 2; only `pub fn`s are exported)",
+            "Module 'util.math' has no public function 'cube'",
+        ]);
+        assert_eq!(diagnostics[0].notes, [format!("'helper' is declared at {}:1", path.display())]);
+        assert!(diagnostics[2].notes.is_empty());
+    }
+}
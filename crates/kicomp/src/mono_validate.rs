@@ -39,7 +39,7 @@ fn validate_function(func: &MirFunction) -> Result<(), String> {
                 StatementKind::Assign(_, rval) => {
                     // Check if an RValue evaluation resulted in something strictly illegal in a register
                     validate_rvalue(rval).map_err(|e| {
-                        format!("Monomorphization Validation Error in '{}' at line {}: {}", func.name, stmt.line, e)
+                        format!("Monomorphization Validation Error in '{}' at line {}: {}", func.name, stmt.span.line, e)
                     })?;
                 }
                 _ => {}
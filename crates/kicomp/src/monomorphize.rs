@@ -196,12 +196,25 @@ pub fn mangle_name(base: &str, args: &[Type]) -> String {
     
     let mut mangled = format!("{}_", base);
     for arg in args {
-        mangled.push_str(&arg.to_string().replace("<", "_").replace(">", "_").replace(", ", "_"));
+        mangled.push_str(&mangle_type(arg));
         mangled.push('_');
     }
     mangled
 }
 
+/// A type's part of a mangled name. Spelled out here rather than taken from
+/// `Display`, which is free to change with what users are shown.
+fn mangle_type(ty: &Type) -> String {
+    match ty {
+        Type::Str => "str".to_string(),
+        Type::Var(id) => format!("T{}", id),
+        Type::Array(inner) => mangle_name("Array", std::slice::from_ref(&**inner)),
+        Type::Map(k, v) => mangle_name("Map", &[(**k).clone(), (**v).clone()]),
+        Type::Custom { name, args } => mangle_name(name, args),
+        other => other.to_string(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
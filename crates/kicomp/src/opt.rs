@@ -2,16 +2,17 @@
 /// Operates on `CompiledProgram` after compilation, before serialization/execution.
 /// Each pass is a pure transformation on the instruction stream.
 
-use crate::ir::{CompiledProgram, CompiledFunction, Instruction, Opcode, Constant};
+use crate::ir::{CompiledProgram, CompiledFunction, Instruction, Opcode, Operand, Constant};
+use std::collections::HashMap;
+use rayon::prelude::*;
 
 // ─── Public API ──────────────────────────────────────────────────────────────
 
-/// Run all optimization passes on a compiled program.
+/// Run all optimization passes on a compiled program, one function per
+/// thread: every pass only touches the function it is given.
 pub fn optimize(program: &mut CompiledProgram) {
     optimize_function(&mut program.main);
-    for func in program.functions.iter_mut() {
-        optimize_function(func);
-    }
+    program.functions.par_iter_mut().for_each(optimize_function);
     program.is_optimized = true;
 }
 
@@ -32,14 +33,20 @@ fn optimize_function(func: &mut CompiledFunction) {
 fn dead_code_elimination(func: &mut CompiledFunction) {
     // First: collect all jump targets so we know which instructions are reachable entry points
     let mut jump_targets = std::collections::HashSet::new();
-    for instr in &func.instructions {
+    for (i, instr) in func.instructions.iter().enumerate() {
         match instr.opcode {
             Opcode::Jump | Opcode::JumpIfFalse | Opcode::JumpIfTrue => {
                 jump_targets.insert(instr.a as usize);
             }
+            // Every entry of the table, and the code after it, is reachable
+            Opcode::JumpTable => {
+                jump_targets.extend(i + 1..=i + 1 + instr.c as usize);
+            }
             _ => {}
         }
     }
+    // Handlers are entered by the unwinder
+    jump_targets.extend(func.exception_table.iter().map(|h| h.handler as usize));
 
     let mut dead = false;
     for i in 0..func.instructions.len() {
@@ -77,10 +84,14 @@ fn constant_folding(func: &mut CompiledFunction) {
     // Track which register holds which constant index (after a LoadConst)
     let mut reg_const: std::collections::HashMap<u16, usize> = std::collections::HashMap::new();
 
+    let handlers = handler_entries(func);
     let len = func.instructions.len();
     let mut i = 0;
     while i < len {
         let instr = func.instructions[i];
+        if handlers.contains(&i) {
+            reg_const.clear();
+        }
 
         match instr.opcode {
             Opcode::LoadConst => {
@@ -170,9 +181,13 @@ fn fold_arithmetic(constants: &[Constant], a: usize, b: usize, op: Opcode) -> Op
 fn redundant_load_elimination(func: &mut CompiledFunction) {
     // Track what each register currently holds (opcode + operand)
     let mut reg_state: std::collections::HashMap<u16, (Opcode, u16)> = std::collections::HashMap::new();
+    let handlers = handler_entries(func);
 
     for i in 0..func.instructions.len() {
         let instr = func.instructions[i];
+        if handlers.contains(&i) {
+            reg_state.clear();
+        }
         match instr.opcode {
             Opcode::LoadConst => {
                 let key = (Opcode::LoadConst, instr.b);
@@ -254,7 +269,7 @@ fn nop_elimination(func: &mut CompiledFunction) {
     }
 
     // Build a mapping: old_index → new_index
-    let mut index_map: Vec<usize> = Vec::with_capacity(func.instructions.len());
+    let mut index_map: Vec<usize> = Vec::with_capacity(func.instructions.len() + 1);
     let mut new_idx = 0usize;
     for instr in &func.instructions {
         index_map.push(new_idx);
@@ -262,36 +277,31 @@ fn nop_elimination(func: &mut CompiledFunction) {
             new_idx += 1;
         }
     }
+    index_map.push(new_idx);
+    remap_exception_table(func, &index_map);
 
     // Update jump targets
     for instr in func.instructions.iter_mut() {
-        match instr.opcode {
-            Opcode::Jump | Opcode::JumpIfFalse | Opcode::JumpIfTrue => {
-                let old_target = instr.a as usize;
-                if old_target < index_map.len() {
-                    instr.a = index_map[old_target] as u16;
-                }
-            }
-            Opcode::IterNext => {
-                let old_target = instr.c as usize;
-                if old_target < index_map.len() {
-                    instr.c = index_map[old_target] as u16;
-                }
+        let info = instr.opcode.info();
+        for (kind, operand) in [(info.a, &mut instr.a), (info.b, &mut instr.b), (info.c, &mut instr.c)] {
+            if kind == Operand::Target && (*operand as usize) < index_map.len() {
+                *operand = index_map[*operand as usize] as u16;
             }
-            _ => {}
         }
     }
 
     // Remove Nops
     func.instructions.retain(|i| i.opcode != Opcode::Nop);
 
-    // Also compact line_map if it exists
-    if !func.line_map.is_empty() && func.line_map.len() == index_map.len() {
-        let old_line_map = std::mem::take(&mut func.line_map);
-        func.line_map = old_line_map.into_iter()
-            .zip(func.instructions.iter()) // won't work because we already removed nops
-            .map(|(line, _)| line)
-            .collect();
+    // Also compact line_map and column_map if they exist
+    for map in [&mut func.line_map, &mut func.column_map] {
+        if !map.is_empty() && map.len() + 1 == index_map.len() {
+            let old_map = std::mem::take(map);
+            *map = old_map.into_iter()
+                .zip(func.instructions.iter()) // won't work because we already removed nops
+                .map(|(line, _)| line)
+                .collect();
+        }
     }
 }
 
@@ -326,8 +336,239 @@ fn drop_redundancy_elimination(func: &mut CompiledFunction) {
     }
 }
 
+// ─── Inlining (-O2) ─────────────────────────────────────────────────────────
+/// Bodies longer than this (not counting the final `Return`) are not inlined.
+pub const INLINE_MAX_INSTRUCTIONS: usize = 8;
+
+/// Replace calls of tiny top-level functions with a copy of their body.
+/// Runs after the per-function passes, over the whole program.
+///
+/// A function is inlined when it is defined once with `fn name` at top level
+/// (and its global never reassigned), its body is straight-line (no jumps,
+/// calls or early returns) of at most `INLINE_MAX_INSTRUCTIONS`, and it reads
+/// no register besides its parameters before writing it. A call site
+/// qualifies when it calls the function's global directly with exactly its
+/// arity. The body's registers move up to just past the call register, where
+/// the arguments already are, and its `Return` becomes a move into the call
+/// register. The `GetGlobal` of the callee stays, so calling a function
+/// before its definition still fails the same way. Inlined code reports the
+/// call site's line and has no frame of its own in stack traces.
+///
+/// Returns how many call sites were inlined.
+pub fn inline_small_functions(program: &mut CompiledProgram) -> usize {
+    let candidates = inline_candidates(program);
+    if candidates.is_empty() {
+        return 0;
+    }
+    let mut inlined = inline_calls(&mut program.main, &candidates);
+    for func in program.functions.iter_mut() {
+        inlined += inline_calls(func, &candidates);
+    }
+    inlined
+}
+
+/// Inlinable functions by global name.
+fn inline_candidates(program: &CompiledProgram) -> HashMap<String, CompiledFunction> {
+    // `None` once a name is assigned anything but its own `fn` definition.
+    let mut definitions: HashMap<&str, Option<usize>> = HashMap::new();
+    let functions = std::iter::once((true, &program.main)).chain(program.functions.iter().map(|f| (false, f)));
+    for (is_main, func) in functions {
+        for (k, instr) in func.instructions.iter().enumerate() {
+            if instr.opcode != Opcode::SetGlobal {
+                continue;
+            }
+            let Some(Constant::String(name)) = func.constants.get(instr.a as usize) else { continue };
+            let defined = match k.checked_sub(1).map(|p| func.instructions[p]) {
+                Some(prev) if is_main && prev.opcode == Opcode::LoadConst && prev.a == instr.b => {
+                    match func.constants.get(prev.b as usize) {
+                        Some(Constant::Function(idx)) => Some(*idx),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            };
+            definitions.entry(name).and_modify(|d| *d = None).or_insert(defined);
+        }
+    }
+    definitions.into_iter()
+        .filter_map(|(name, idx)| Some((name.to_string(), program.functions.get(idx?)?)))
+        .filter(|(_, func)| is_inlinable(func))
+        .map(|(name, func)| (name, func.clone()))
+        .collect()
+}
+
+fn is_inlinable(func: &CompiledFunction) -> bool {
+    let Some((last, body)) = func.instructions.split_last() else { return false };
+    if !func.exception_table.is_empty() {
+        return false;
+    }
+    if body.len() > INLINE_MAX_INSTRUCTIONS || !matches!(last.opcode, Opcode::Return | Opcode::ReturnVoid) {
+        return false;
+    }
+    let mut defined: std::collections::HashSet<u16> = (0..func.arity).collect();
+    for instr in func.instructions.iter() {
+        let straight_line = !matches!(instr.opcode,
+            Opcode::Jump | Opcode::JumpIfFalse | Opcode::JumpIfTrue | Opcode::IterNext | Opcode::JumpTable
+            | Opcode::Call | Opcode::TailCall | Opcode::Halt | Opcode::MakeClosure
+            | Opcode::SetState | Opcode::UpdateState | Opcode::InitComputed | Opcode::InitEffect
+        );
+        if !straight_line {
+            return false;
+        }
+        let (reads, writes) = register_operands(instr);
+        if !reads.iter().all(|r| defined.contains(r)) {
+            return false;
+        }
+        defined.extend(writes);
+    }
+    true
+}
+
+/// The registers an instruction reads and writes, from `Opcode::info`.
+/// `MakeArray`/`MakeMap` read the consecutive registers their count covers.
+fn register_operands(instr: &Instruction) -> (Vec<u16>, Vec<u16>) {
+    let (mut reads, mut writes) = (Vec::new(), Vec::new());
+    match instr.opcode {
+        Opcode::MakeArray | Opcode::MakeMap => {
+            let per_item = if instr.opcode == Opcode::MakeMap { 2 } else { 1 };
+            reads.extend((0..instr.b.saturating_mul(per_item)).map(|i| instr.a.saturating_add(i)));
+            writes.push(instr.a);
+        }
+        _ => {
+            let info = instr.opcode.info();
+            for (kind, reg) in [(info.a, instr.a), (info.b, instr.b), (info.c, instr.c)] {
+                match kind {
+                    Operand::Read => reads.push(reg),
+                    Operand::Write => writes.push(reg),
+                    Operand::Update => {
+                        reads.push(reg);
+                        writes.push(reg);
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+    (reads, writes)
+}
+
+fn inline_calls(func: &mut CompiledFunction, candidates: &HashMap<String, CompiledFunction>) -> usize {
+    let targets: std::collections::HashSet<usize> = func.instructions.iter()
+        .flat_map(|instr| {
+            let info = instr.opcode.info();
+            [(info.a, instr.a), (info.b, instr.b), (info.c, instr.c)]
+        })
+        .filter(|(kind, _)| *kind == Operand::Target)
+        .map(|(_, target)| target as usize)
+        .collect();
+
+    let mut sites: HashMap<usize, &CompiledFunction> = HashMap::new();
+    for (i, call) in func.instructions.iter().enumerate() {
+        if call.opcode != Opcode::Call {
+            continue;
+        }
+        // The compiler loads a direct callee as `GetGlobal rX, name` then
+        // `SetLocal call, rX` (or, for `Type.member(..)`, straight into the
+        // call register), followed by the argument code.
+        let Some(j) = (0..i).rev().find(|&j| register_operands(&func.instructions[j]).1.contains(&call.a)) else { continue };
+        let load = match func.instructions[j] {
+            load if load.opcode == Opcode::GetGlobal => load,
+            mov if mov.opcode == Opcode::SetLocal && j > 0 && func.instructions[j - 1].a == mov.b => func.instructions[j - 1],
+            _ => continue,
+        };
+        if load.opcode != Opcode::GetGlobal {
+            continue;
+        }
+        if (j + 1..=i).any(|k| targets.contains(&k)) {
+            continue;
+        }
+        let Some(Constant::String(name)) = func.constants.get(load.b as usize) else { continue };
+        let Some(callee) = candidates.get(name).filter(|c| c.arity == call.b) else { continue };
+        if call.a as usize + 1 + callee.locals as usize >= u16::MAX as usize {
+            continue;
+        }
+        sites.insert(i, callee);
+    }
+    if sites.is_empty() {
+        return 0;
+    }
+
+    let old = std::mem::take(&mut func.instructions);
+    let has_lines = func.line_map.len() == old.len();
+    let old_lines = std::mem::take(&mut func.line_map);
+    let has_columns = func.column_map.len() == old.len();
+    let old_columns = std::mem::take(&mut func.column_map);
+    let mut index_map = Vec::with_capacity(old.len() + 1);
+    for (i, instr) in old.iter().enumerate() {
+        index_map.push(func.instructions.len());
+        let emitted = match sites.get(&i) {
+            Some(callee) => {
+                let base = instr.a + 1;
+                for body in &callee.instructions {
+                    let info = body.opcode.info();
+                    let mut operands = [body.a, body.b, body.c];
+                    for (kind, operand) in [info.a, info.b, info.c].into_iter().zip(operands.iter_mut()) {
+                        match kind {
+                            Operand::Read | Operand::Write | Operand::Update => *operand += base,
+                            Operand::Const => *operand = func.add_constant(callee.constants[*operand as usize].clone()),
+                            _ => {}
+                        }
+                    }
+                    let [a, b, c] = operands;
+                    func.instructions.push(match body.opcode {
+                        Opcode::Return => Instruction::ab(Opcode::SetLocal, instr.a, a),
+                        Opcode::ReturnVoid => Instruction::a_only(Opcode::LoadNull, instr.a),
+                        opcode => Instruction::new(opcode, a, b, c),
+                    });
+                }
+                func.locals = func.locals.max(base + callee.locals);
+                callee.instructions.len()
+            }
+            None => {
+                func.instructions.push(*instr);
+                1
+            }
+        };
+        if has_lines {
+            func.line_map.extend(std::iter::repeat_n(old_lines[i], emitted));
+        }
+        if has_columns {
+            func.column_map.extend(std::iter::repeat_n(old_columns[i], emitted));
+        }
+    }
+    index_map.push(func.instructions.len());
+    remap_exception_table(func, &index_map);
+
+    for instr in func.instructions.iter_mut() {
+        let info = instr.opcode.info();
+        for (kind, operand) in [(info.a, &mut instr.a), (info.b, &mut instr.b), (info.c, &mut instr.c)] {
+            let Some(&new) = index_map.get(*operand as usize).filter(|_| kind == Operand::Target) else { continue };
+            *operand = new as u16;
+        }
+    }
+    sites.len()
+}
+
 // ─── Helpers ────────────────────────────────────────────────────────────────
 
+/// Instructions the unwinder jumps to: what registers hold there depends on
+/// where the error came from.
+fn handler_entries(func: &CompiledFunction) -> std::collections::HashSet<usize> {
+    func.exception_table.iter().map(|h| h.handler as usize).collect()
+}
+
+/// Moves the exception table along with the instructions, `index_map`
+/// giving each old index (and the end) its new one.
+fn remap_exception_table(func: &mut CompiledFunction, index_map: &[usize]) {
+    for h in &mut func.exception_table {
+        h.start = index_map[h.start as usize] as u32;
+        h.end = index_map[h.end as usize] as u32;
+        h.handler = index_map[h.handler as usize] as u32;
+    }
+    // A range whose code was all removed can't raise
+    func.exception_table.retain(|h| h.start < h.end);
+}
+
 /// Returns true if the opcode writes a result to register A.
 fn writes_to_register(op: Opcode) -> bool {
     matches!(op,
@@ -368,3 +609,39 @@ fn reads_register_c(op: Opcode, reg: u16, c: u16) -> bool {
         | Opcode::SetMember | Opcode::SetIndex
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bumpalo::Bump;
+
+    fn compile_optimized(source: &str) -> CompiledProgram {
+        let arena = Bump::new();
+        let mut parser = kinetix_language::parser::Parser::new(kinetix_language::lexer::Lexer::new(source), &arena);
+        let ast = parser.parse_program();
+        assert!(parser.errors.is_empty(), "parse errors: {:?}", parser.errors);
+        let mut program = crate::compiler::Compiler::new().compile(&ast.statements, None).cloned().expect("compile");
+        optimize(&mut program);
+        program
+    }
+
+    fn calls(func: &CompiledFunction) -> usize {
+        func.instructions.iter().filter(|i| i.opcode == Opcode::Call).count()
+    }
+
+    #[test]
+    fn tiny_functions_are_inlined_and_recursive_or_reassigned_ones_are_not() {
+        let mut program = compile_optimized(
+            "fn sq(x: int) -> int { return x * x }\n\
+             fn fact(n: int) -> int { if n < 2 { return 1 } return n * fact(n - 1) }\n\
+             let a = sq(3)\nlet b = fact(4)\nlet c = sq(a)\n",
+        );
+        let before = calls(&program.main);
+        assert_eq!(inline_small_functions(&mut program), 2);
+        assert_eq!(calls(&program.main), before - 2, "only the fact call should remain");
+        assert_eq!(program.main.line_map.len(), program.main.instructions.len());
+
+        let mut program = compile_optimized("fn one() -> int { return 1 }\nlet a = one()\none = 2\n");
+        assert_eq!(inline_small_functions(&mut program), 0);
+    }
+}
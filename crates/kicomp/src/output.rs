@@ -0,0 +1,224 @@
+//! Process-wide output settings: whether diagnostics and status lines use
+//! ANSI colors, and how much the tools say besides errors.
+
+use kinetix_language::diagnostic::Diagnostic;
+use std::fmt;
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ColorChoice {
+    /// Color when stderr is an ANSI-capable terminal and `NO_COLOR` is
+    /// unset or empty.
+    Auto,
+    Always,
+    Never,
+}
+
+/// `Quiet` prints errors only; `Normal` adds warnings and status lines;
+/// `Verbose` adds notes, backend fallbacks and pipeline step logs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
+}
+
+const UNSET: u8 = u8::MAX;
+static COLOR: AtomicU8 = AtomicU8::new(UNSET);
+static VERBOSITY: AtomicU8 = AtomicU8::new(Verbosity::Normal as u8);
+/// Cleared by `init_console` when the console can't interpret ANSI.
+static ANSI_CONSOLE: AtomicBool = AtomicBool::new(true);
+
+/// Prepares the console; call once at startup, before anything prints.
+///
+/// On Windows this switches the console to UTF-8 (code page 65001, like
+/// `chcp 65001`, for the rest of the console session) so box drawing and
+/// non-ASCII text don't garble, and turns on virtual terminal processing
+/// for stdout and stderr. A console that refuses it (conhost before
+/// Windows 10) would print escape codes raw, so `auto` colors stay off
+/// there. Elsewhere this does nothing.
+pub fn init_console() {
+    #[cfg(windows)]
+    ANSI_CONSOLE.store(windows_console::enable_utf8_and_ansi(), Ordering::Relaxed);
+}
+
+#[cfg(windows)]
+mod windows_console {
+    use std::ffi::c_void;
+
+    const STD_OUTPUT_HANDLE: u32 = -11i32 as u32;
+    const STD_ERROR_HANDLE: u32 = -12i32 as u32;
+    const ENABLE_VIRTUAL_TERMINAL_PROCESSING: u32 = 0x0004;
+    const CP_UTF8: u32 = 65001;
+
+    #[link(name = "kernel32")]
+    unsafe extern "system" {
+        fn GetStdHandle(std_handle: u32) -> *mut c_void;
+        fn GetConsoleMode(handle: *mut c_void, mode: *mut u32) -> i32;
+        fn SetConsoleMode(handle: *mut c_void, mode: u32) -> i32;
+        fn SetConsoleOutputCP(code_page: u32) -> i32;
+        fn SetConsoleCP(code_page: u32) -> i32;
+    }
+
+    /// Whether every console handle accepted ANSI; redirected handles
+    /// (not consoles) don't count against it.
+    pub fn enable_utf8_and_ansi() -> bool {
+        unsafe {
+            SetConsoleOutputCP(CP_UTF8);
+            SetConsoleCP(CP_UTF8);
+            let mut ansi = true;
+            for id in [STD_OUTPUT_HANDLE, STD_ERROR_HANDLE] {
+                let handle = GetStdHandle(id);
+                let mut mode = 0u32;
+                if GetConsoleMode(handle, &mut mode) == 0 {
+                    continue;
+                }
+                if SetConsoleMode(handle, mode | ENABLE_VIRTUAL_TERMINAL_PROCESSING) == 0 {
+                    ansi = false;
+                }
+            }
+            ansi
+        }
+    }
+}
+
+/// Sets colors and verbosity for everything that prints (kivm's diagnostics
+/// and shell, kicomp's metrics and notes). The CLI calls it once from
+/// `--color`, `NO_COLOR` and `-q`/`-v`; unconfigured, colors are `auto` and
+/// verbosity is `Normal`.
+pub fn configure(color: ColorChoice, verbosity: Verbosity) {
+    let enabled = match color {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => auto_color(),
+    };
+    COLOR.store(enabled as u8, Ordering::Relaxed);
+    VERBOSITY.store(verbosity as u8, Ordering::Relaxed);
+}
+
+fn auto_color() -> bool {
+    std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+        && std::io::stderr().is_terminal()
+        && ANSI_CONSOLE.load(Ordering::Relaxed)
+}
+
+pub fn color_enabled() -> bool {
+    match COLOR.load(Ordering::Relaxed) {
+        UNSET => {
+            let enabled = auto_color();
+            COLOR.store(enabled as u8, Ordering::Relaxed);
+            enabled
+        }
+        enabled => enabled != 0,
+    }
+}
+
+pub fn verbosity() -> Verbosity {
+    match VERBOSITY.load(Ordering::Relaxed) {
+        0 => Verbosity::Quiet,
+        1 => Verbosity::Normal,
+        _ => Verbosity::Verbose,
+    }
+}
+
+/// `text` wrapped in the SGR sequence `style` (e.g. `"1;31"` for bold red),
+/// or plain when colors are off.
+pub fn paint(style: &str, text: impl fmt::Display) -> String {
+    if color_enabled() {
+        format!("\x1b[{}m{}\x1b[0m", style, text)
+    } else {
+        text.to_string()
+    }
+}
+
+/// How diagnostics are printed: `human` (colored, with the source line) or
+/// `json` (one object per line, for editors and other tools).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ErrorFormat {
+    Human,
+    Json,
+}
+
+static JSON_ERRORS: AtomicBool = AtomicBool::new(false);
+
+pub fn set_error_format(format: ErrorFormat) {
+    JSON_ERRORS.store(format == ErrorFormat::Json, Ordering::Relaxed);
+}
+
+pub fn error_format() -> ErrorFormat {
+    if JSON_ERRORS.load(Ordering::Relaxed) { ErrorFormat::Json } else { ErrorFormat::Human }
+}
+
+/// `d` as one line of JSON: `file` (the diagnostic's own file when it has
+/// one), 1-based `line` and `column` (`null` when unknown), `code`,
+/// `severity`, `message`, `notes`, `help` and `fixes`: text to `insert` at
+/// `line`:`column` in place of `replace` characters, with a `message`
+/// saying what it does.
+pub fn json_diagnostic(file: &str, d: &Diagnostic) -> String {
+    let span = d.span();
+    serde_json::json!({
+        "file": d.file.as_deref().unwrap_or(file),
+        "line": span.map(|s| s.line),
+        "column": span.map(|s| s.column).filter(|&c| c > 0),
+        "code": d.code,
+        "severity": d.severity,
+        "message": d.message,
+        "notes": d.notes,
+        "help": d.help,
+        "fixes": d.fixes.iter().map(|fix| serde_json::json!({
+            "message": fix.message,
+            "line": fix.at.line,
+            "column": fix.at.column,
+            "replace": fix.replace,
+            "insert": fix.insert,
+        })).collect::<Vec<_>>(),
+    })
+    .to_string()
+}
+
+/// An error that isn't about a place in the source (a file that can't be
+/// read, a runtime error), in the same shape as `json_diagnostic` with no
+/// file, location or code.
+pub fn json_error(message: impl fmt::Display) -> String {
+    serde_json::json!({
+        "file": null,
+        "line": null,
+        "column": null,
+        "code": null,
+        "severity": "error",
+        "message": message.to_string(),
+        "notes": [],
+        "help": null,
+        "fixes": [],
+    })
+    .to_string()
+}
+
+/// A status line on stdout ("Compiled successfully: ..."); hidden by `-q`.
+pub fn status(msg: impl fmt::Display) {
+    if verbosity() >= Verbosity::Normal {
+        println!("{}", msg);
+    }
+}
+
+/// A warning on stderr; hidden by `-q`.
+pub fn warning(msg: impl fmt::Display) {
+    if verbosity() >= Verbosity::Normal {
+        eprintln!("{}: {}", paint("1;33", "warning"), msg);
+    }
+}
+
+/// A note on stderr (backend fallbacks and the like); shown with `-v`.
+pub fn note(msg: impl fmt::Display) {
+    if verbosity() >= Verbosity::Verbose {
+        eprintln!("{}: {}", paint("1;36", "note"), msg);
+    }
+}
+
+/// A pipeline step log on stderr; shown with `-v`.
+pub fn step(msg: impl fmt::Display) {
+    if verbosity() >= Verbosity::Verbose {
+        eprintln!("{} {}", paint("2", "[step]"), msg);
+    }
+}
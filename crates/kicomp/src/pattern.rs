@@ -2,41 +2,116 @@
 ///
 /// A match arm's pattern parses as a plain `Expression` (there is no dedicated
 /// pattern AST node) -- `Some(x)` is `Call{function: Identifier("Some"), ...}`,
-/// `None`/`Red`/`x`/`_` are all bare `Identifier`s, and everything else is a
-/// literal. Symbol resolution, HIR lowering, and bytecode codegen each need to
-/// tell these apart the same way, so the classification lives here once
+/// `None`/`Red`/`x`/`_` are all bare `Identifier`s, `0..10` is a `Range`,
+/// `Point { x: 0, y: py }` is a `StructLiteral`, and everything else is a
+/// literal. Payloads and struct fields are classified recursively, so
+/// `Some(Circle(r))` destructures two levels deep. Symbol resolution, HIR
+/// lowering, and bytecode codegen each need to tell these apart the same way, so the classification lives here once
 /// instead of three times.
-use kinetix_language::ast::Expression;
+use kinetix_language::ast::{Expression, ExpressionKind};
 
 #[derive(Debug, Clone)]
 pub enum ArmPattern<'a> {
     Wildcard,
     Binding(String),
     Literal(&'a Expression<'a>),
-    Variant { name: String, binding: Option<String> },
+    /// Half-open integer range `start..end`, matching `start <= v < end`
+    /// (the same bounds `for i in start..end` iterates over).
+    Range { start: &'a Expression<'a>, end: &'a Expression<'a> },
+    /// `Some(x)`, `None`, `Circle(Point { x: 0, y: py })`: the payload is itself a
+    /// pattern, so destructuring nests arbitrarily.
+    Variant { name: String, payload: Option<Box<ArmPattern<'a>>> },
+    /// `Point { x: 0, y: py }`: matches a struct/class instance of `name` whose
+    /// listed fields match their sub-patterns. Unlisted fields are ignored.
+    Struct { name: String, fields: Vec<(String, ArmPattern<'a>)> },
+    /// A `match x as { ... }` arm: `int(n)` matches when the value's runtime
+    /// type is `int`, binding it (statically narrowed) as `n`.
+    Type { name: String, binding: Option<String> },
+}
+
+impl<'a> ArmPattern<'a> {
+    /// Every name this pattern binds, in source order.
+    pub fn bindings(&self) -> Vec<String> {
+        let mut out = Vec::new();
+        self.collect_bindings(&mut out);
+        out
+    }
+
+    fn collect_bindings(&self, out: &mut Vec<String>) {
+        match self {
+            ArmPattern::Binding(name) | ArmPattern::Type { binding: Some(name), .. } => out.push(name.clone()),
+            ArmPattern::Variant { payload: Some(p), .. } => p.collect_bindings(out),
+            ArmPattern::Struct { fields, .. } => {
+                for (_, p) in fields {
+                    p.collect_bindings(out);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Classifies one arm of a `match`: a type-switch arm when the match was
+/// written `match x as { ... }` (`by_type`), a value pattern otherwise.
+pub fn classify_arm<'a>(pat: &'a Expression<'a>, by_type: bool, is_nullary_variant: impl Fn(&str) -> bool + Copy) -> ArmPattern<'a> {
+    if by_type {
+        classify_type_arm(pat)
+    } else {
+        classify_pattern(pat, is_nullary_variant)
+    }
+}
+
+/// Classifies a type-switch arm: `_`, a bare type name (`int`), or a type
+/// name with a binding (`int(n)`). The parser has already rejected any other
+/// shape, so anything unexpected degrades to a wildcard.
+pub fn classify_type_arm<'a>(pat: &'a Expression<'a>) -> ArmPattern<'a> {
+    match &pat.kind {
+        ExpressionKind::Identifier(name) if *name == "_" => ArmPattern::Wildcard,
+        ExpressionKind::Identifier(name) => ArmPattern::Type { name: name.to_string(), binding: None },
+        ExpressionKind::Null => ArmPattern::Type { name: "null".to_string(), binding: None },
+        ExpressionKind::Call { function, arguments } => match (&**function, arguments.first()) {
+            (Expression { kind: ExpressionKind::Identifier(name), .. }, Some(Expression { kind: ExpressionKind::Identifier(b), .. })) => {
+                ArmPattern::Type { name: name.to_string(), binding: Some(b.to_string()) }
+            }
+            _ => ArmPattern::Wildcard,
+        },
+        _ => ArmPattern::Wildcard,
+    }
+}
+
+/// The name `typeof` reports at runtime for a type-switch arm's type --
+/// the arm may use either spelling of the string type.
+pub fn runtime_type_name(name: &str) -> &str {
+    match name {
+        "str" => "string",
+        other => other,
+    }
 }
 
 /// Classifies a match-arm pattern expression. `is_nullary_variant` distinguishes
 /// a bare identifier naming a no-payload enum variant (`None`, `Red`) from an
 /// ordinary catch-all binding (`x`) -- both parse identically as a bare
-/// `Expression::Identifier`, so this can't be told apart syntactically alone.
-pub fn classify_pattern<'a>(pat: &'a Expression<'a>, is_nullary_variant: impl Fn(&str) -> bool) -> ArmPattern<'a> {
-    match pat {
-        Expression::Identifier(name) if name == "_" => ArmPattern::Wildcard,
-        Expression::Identifier(name) if is_nullary_variant(name) => {
-            ArmPattern::Variant { name: name.clone(), binding: None }
+/// `ExpressionKind::Identifier`, so this can't be told apart syntactically alone.
+pub fn classify_pattern<'a>(pat: &'a Expression<'a>, is_nullary_variant: impl Fn(&str) -> bool + Copy) -> ArmPattern<'a> {
+    match &pat.kind {
+        ExpressionKind::Identifier(name) if *name == "_" => ArmPattern::Wildcard,
+        ExpressionKind::Identifier(name) if is_nullary_variant(name) => {
+            ArmPattern::Variant { name: name.to_string(), payload: None }
         }
-        Expression::Identifier(name) => ArmPattern::Binding(name.clone()),
-        Expression::Call { function, arguments } => {
-            if let Expression::Identifier(vname) = &**function {
-                let binding = arguments.first().and_then(|a| {
-                    if let Expression::Identifier(b) = a { Some(b.clone()) } else { None }
-                });
-                ArmPattern::Variant { name: vname.clone(), binding }
+        ExpressionKind::Identifier(name) => ArmPattern::Binding(name.to_string()),
+        ExpressionKind::Call { function, arguments } => {
+            if let ExpressionKind::Identifier(vname) = &function.kind {
+                let payload = arguments.first().map(|a| Box::new(classify_pattern(a, is_nullary_variant)));
+                ArmPattern::Variant { name: vname.to_string(), payload }
             } else {
                 ArmPattern::Wildcard
             }
         }
-        other => ArmPattern::Literal(other),
+        ExpressionKind::Range { start, end } => ArmPattern::Range { start, end },
+        ExpressionKind::StructLiteral { name, fields } => ArmPattern::Struct {
+            name: name.to_string(),
+            fields: fields.iter().map(|(f, p)| (f.clone(), classify_pattern(p, is_nullary_variant))).collect(),
+        },
+        _ => ArmPattern::Literal(pat),
     }
 }
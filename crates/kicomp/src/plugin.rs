@@ -0,0 +1,188 @@
+//! Compiler plugins: passes that live outside the compiler, run at fixed
+//! points of the pipeline.
+
+use crate::hir::HirProgram;
+use crate::ir::CompiledProgram;
+use crate::mir::MirProgram;
+use kinetix_language::diagnostic::{Diagnostic, Severity};
+use std::sync::{Arc, RwLock};
+
+/// Changes whenever `Pass`, `Unit` or `PassContext` change shape; a plugin
+/// declaring another version is refused.
+pub const API_VERSION: u32 = 1;
+
+/// Where in the pipeline a pass runs. A pass may rewrite what it is given,
+/// and the built-in passes after it see the result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Stage {
+    /// Typed HIR, after type checking, the sandbox audit, HIR validation and
+    /// the resource lint; before MIR lowering.
+    Hir,
+    /// MIR as lowered, before the borrow checker.
+    Mir,
+    /// Monomorphized MIR, after every built-in MIR check.
+    CheckedMir,
+    /// The compiled program, before the optimizer. Bytecode is generated from
+    /// the source, so code meant to run (instrumentation, injected calls) is
+    /// added here.
+    Bytecode,
+}
+
+/// What a pass works on: the program at its stage.
+pub enum Unit<'a> {
+    Hir(&'a mut HirProgram),
+    /// `Stage::Mir` and `Stage::CheckedMir`.
+    Mir(&'a mut MirProgram),
+    Bytecode(&'a mut CompiledProgram),
+}
+
+pub trait Pass: Send + Sync {
+    /// Names the pass in errors, e.g. "Unit Lint".
+    fn name(&self) -> &'static str;
+    fn stage(&self) -> Stage;
+    fn run(&self, unit: Unit<'_>, cx: &mut PassContext);
+}
+
+/// What the passes of one stage have reported so far. Warnings print with
+/// the compiler's; an error stops the compile after the pass that reported
+/// it, with the pass's name as the failing phase.
+#[derive(Default)]
+pub struct PassContext {
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl PassContext {
+    pub fn report(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.push(diagnostic);
+    }
+
+    /// Everything reported at this stage, by this pass and the ones before.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+}
+
+/// What `declare_plugin!` exports.
+pub struct Plugin {
+    pub passes: Vec<Box<dyn Pass>>,
+}
+
+static PASSES: RwLock<Vec<Arc<dyn Pass>>> = RwLock::new(Vec::new());
+
+/// Adds `pass` after the ones registered before; passes of one stage run in
+/// registration order.
+pub fn register(pass: Box<dyn Pass>) {
+    PASSES.write().unwrap_or_else(|e| e.into_inner()).push(Arc::from(pass));
+}
+
+/// Whether any pass is registered.
+pub fn active() -> bool {
+    !PASSES.read().unwrap_or_else(|e| e.into_inner()).is_empty()
+}
+
+/// Runs the passes registered for `stage` over `unit`. Returns `Ok(warnings)`,
+/// or the name of the first pass that reported an error with what it
+/// reported.
+pub fn run(stage: Stage, mut unit: Unit<'_>) -> Result<Vec<Diagnostic>, (&'static str, Vec<Diagnostic>)> {
+    let passes: Vec<Arc<dyn Pass>> = PASSES.read().unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .filter(|pass| pass.stage() == stage)
+        .cloned()
+        .collect();
+    let mut cx = PassContext::default();
+    for pass in passes {
+        let reported = cx.diagnostics.len();
+        let unit = match &mut unit {
+            Unit::Hir(hir) => Unit::Hir(hir),
+            Unit::Mir(mir) => Unit::Mir(mir),
+            Unit::Bytecode(program) => Unit::Bytecode(program),
+        };
+        pass.run(unit, &mut cx);
+        if cx.diagnostics[reported..].iter().any(|d| d.severity == Severity::Error) {
+            let errors = cx.diagnostics.split_off(reported).into_iter().filter(|d| d.severity == Severity::Error).collect();
+            return Err((pass.name(), errors));
+        }
+    }
+    Ok(cx.diagnostics)
+}
+
+/// Exports the passes of a `cdylib` plugin for `kivm --plugin`. Passes cross
+/// the library boundary as Rust trait objects, so the plugin must be built
+/// against the same compiler build, and Rust toolchain, as the `kivm` that
+/// loads it:
+///
+/// ```ignore
+/// kinetix_kicomp::declare_plugin!(UnitLint, CallCounter);
+/// ```
+#[macro_export]
+macro_rules! declare_plugin {
+    ($($pass:expr),* $(,)?) => {
+        #[unsafe(no_mangle)]
+        pub extern "C" fn kinetix_plugin_api() -> u32 {
+            $crate::plugin::API_VERSION
+        }
+
+        #[unsafe(no_mangle)]
+        pub extern "C" fn kinetix_plugin_build() -> i64 {
+            $crate::compiler::CURRENT_BUILD
+        }
+
+        #[unsafe(no_mangle)]
+        pub fn kinetix_plugin() -> $crate::plugin::Plugin {
+            $crate::plugin::Plugin { passes: vec![$(Box::new($pass)),*] }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kinetix_language::diagnostic::codes;
+
+    /// Counts the instructions of `main` and warns when there are none;
+    /// with `limit`, more than that many is an error.
+    struct Budget {
+        limit: Option<usize>,
+    }
+
+    impl Pass for Budget {
+        fn name(&self) -> &'static str {
+            "Instruction Budget"
+        }
+
+        fn stage(&self) -> Stage {
+            Stage::Bytecode
+        }
+
+        fn run(&self, unit: Unit<'_>, cx: &mut PassContext) {
+            let Unit::Bytecode(program) = unit else { return };
+            let count = program.main.instructions.len();
+            if count == 0 && cx.diagnostics().is_empty() {
+                cx.report(Diagnostic::warning(codes::LINT, "main is empty"));
+            }
+            if self.limit.is_some_and(|limit| count > limit) {
+                cx.report(Diagnostic::error(codes::LINT, format!("main has {} instructions", count)));
+            }
+        }
+    }
+
+    #[test]
+    fn passes_run_at_their_stage_in_order() {
+        register(Box::new(Budget { limit: None }));
+        register(Box::new(Budget { limit: Some(0) }));
+        let mut empty = CompiledProgram::new();
+        let warnings = run(Stage::Bytecode, Unit::Bytecode(&mut empty)).unwrap();
+        // The second pass saw the first one's warning and didn't repeat it
+        assert_eq!(warnings.iter().map(|d| d.message.as_str()).collect::<Vec<_>>(), ["main is empty"]);
+
+        let mut program = CompiledProgram::new();
+        program.main.instructions.push(crate::ir::Instruction::a_only(crate::ir::Opcode::Halt, 0));
+        let (pass, errors) = run(Stage::Bytecode, Unit::Bytecode(&mut program)).unwrap_err();
+        assert_eq!((pass, errors[0].message.as_str()), ("Instruction Budget", "main has 1 instructions"));
+
+        let mut mir = MirProgram { functions: Vec::new(), main_block: crate::mir::MirFunction {
+            name: "main".to_string(), args: Vec::new(), return_ty: crate::types::Type::Void, locals: Vec::new(), basic_blocks: Vec::new(),
+        } };
+        assert_eq!(run(Stage::Mir, Unit::Mir(&mut mir)), Ok(Vec::new()));
+    }
+}
@@ -13,11 +13,13 @@
 
 use std::collections::{HashMap, HashSet, VecDeque};
 use crate::hir::{HirProgram, HirStatement, HirStmtKind, HirExpression, HirExprKind};
+use kinetix_language::diagnostic::{codes, Diagnostic};
+use kinetix_language::span::Span;
 
 // ──────────────────── Data Structures ────────────────────
 
 /// A single reactive node in the dependency graph.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ReactiveNodeKind {
     /// A mutable reactive variable (source).
     State,
@@ -26,7 +28,7 @@ pub enum ReactiveNodeKind {
 }
 
 /// Metadata for a reactive variable.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ReactiveNode {
     pub name: String,
     pub kind: ReactiveNodeKind,
@@ -73,22 +75,29 @@ impl ReactiveGraph {
 // ──────────────────── Graph Construction ────────────────────
 
 /// Analyze a HIR program and build the reactive dependency graph.
-/// Returns Ok(ReactiveGraph) if the graph is valid, or Err(String) if cycles are detected.
-pub fn build_reactive_graph(program: &HirProgram) -> Result<ReactiveGraph, String> {
+/// Returns Ok(ReactiveGraph) if the graph is valid, or the error if cycles are detected.
+pub fn build_reactive_graph(program: &HirProgram) -> Result<ReactiveGraph, Vec<Diagnostic>> {
+    let (nodes, dependencies) = reactive_dependencies(program)?;
+    assemble_graph(nodes, dependencies)
+}
+
+/// Reactive nodes by name, and the state names each computed node reads:
+/// everything the graph is made from (see `crate::session`, which reuses the
+/// graph while these stay the same).
+pub type ReactiveInputs = (HashMap<String, ReactiveNode>, HashMap<String, HashSet<String>>);
+
+/// Passes 1 and 2 of `build_reactive_graph`: the nodes and their direct
+/// dependencies, rejecting a computed value that reads itself.
+pub fn reactive_dependencies(program: &HirProgram) -> Result<ReactiveInputs, Vec<Diagnostic>> {
     let mut nodes = HashMap::new();
-    let mut computed_exprs: Vec<(String, &HirExpression, usize)> = Vec::new();
+    let mut computed_exprs: Vec<(String, &HirExpression, Span)> = Vec::new();
 
     // Pass 1: Collect all state and computed declarations
     collect_reactive_nodes(&program.statements, &mut nodes, &mut computed_exprs);
 
-    // If there are no reactive nodes, return an empty graph (no-op)
+    // If there are no reactive nodes, there is nothing to resolve (no-op)
     if nodes.is_empty() {
-        return Ok(ReactiveGraph {
-            nodes,
-            dependencies: HashMap::new(),
-            dependents: HashMap::new(),
-            update_order: Vec::new(),
-        });
+        return Ok((nodes, HashMap::new()));
     }
 
     // Pass 2: For each computed node, resolve which state variables it references
@@ -98,9 +107,8 @@ pub fn build_reactive_graph(program: &HirProgram) -> Result<ReactiveGraph, Strin
         .collect();
 
     let mut dependencies: HashMap<String, HashSet<String>> = HashMap::new();
-    let mut dependents: HashMap<String, HashSet<String>> = HashMap::new();
 
-    for (computed_name, expr, line) in &computed_exprs {
+    for (computed_name, expr, span) in &computed_exprs {
         let mut deps = HashSet::new();
         collect_identifier_refs(expr, &state_names, &mut deps);
 
@@ -111,24 +119,33 @@ pub fn build_reactive_graph(program: &HirProgram) -> Result<ReactiveGraph, Strin
 
         // Check for self-reference (computed depending on itself)
         if deps.contains(computed_name) {
-            return Err(format!(
-                "Line {}: computed variable '{}' cannot reference itself (cycle detected)",
-                line, computed_name
-            ));
+            let message = format!("computed variable '{}' cannot reference itself (cycle detected)", computed_name);
+            return Err(vec![Diagnostic::error(codes::SYMBOL, message).at(*span)]);
         }
 
-        // Build reverse map
-        for state_name in &deps {
+        dependencies.insert(computed_name.clone(), deps);
+    }
+
+    Ok((nodes, dependencies))
+}
+
+/// Pass 3 of `build_reactive_graph`: the reverse map and the update order.
+pub fn assemble_graph(
+    nodes: HashMap<String, ReactiveNode>,
+    dependencies: HashMap<String, HashSet<String>>,
+) -> Result<ReactiveGraph, Vec<Diagnostic>> {
+    // Build reverse map
+    let mut dependents: HashMap<String, HashSet<String>> = HashMap::new();
+    for (computed_name, deps) in &dependencies {
+        for state_name in deps {
             dependents
                 .entry(state_name.clone())
                 .or_insert_with(HashSet::new)
                 .insert(computed_name.clone());
         }
-
-        dependencies.insert(computed_name.clone(), deps);
     }
 
-    // Pass 3: Topological sort (Kahn's algorithm) for update order
+    // Topological sort (Kahn's algorithm) for update order
     let update_order = topological_sort(&nodes, &dependencies)?;
 
     Ok(ReactiveGraph {
@@ -145,7 +162,7 @@ pub fn build_reactive_graph(program: &HirProgram) -> Result<ReactiveGraph, Strin
 fn collect_reactive_nodes<'a>(
     statements: &'a [HirStatement],
     nodes: &mut HashMap<String, ReactiveNode>,
-    computed_exprs: &mut Vec<(String, &'a HirExpression, usize)>,
+    computed_exprs: &mut Vec<(String, &'a HirExpression, Span)>,
 ) {
     for stmt in statements {
         match &stmt.kind {
@@ -163,7 +180,7 @@ fn collect_reactive_nodes<'a>(
                     kind: ReactiveNodeKind::Computed,
                     line: stmt.line,
                 });
-                computed_exprs.push((name.clone(), value, stmt.line));
+                computed_exprs.push((name.clone(), value, stmt.location()));
             }
             HirStmtKind::Effect { .. } => {}
             HirStmtKind::Block { statements: inner } => {
@@ -242,7 +259,7 @@ fn collect_identifier_refs(
             collect_identifier_refs(target, state_names, refs);
             collect_identifier_refs(value, state_names, refs);
         }
-        HirExprKind::Match { value, arms } => {
+        HirExprKind::Match { value, arms, .. } => {
             collect_identifier_refs(value, state_names, refs);
             for (_, arm_body) in arms {
                 collect_stmt_refs(arm_body, state_names, refs);
@@ -290,7 +307,7 @@ fn collect_stmt_refs(
                 collect_stmt_refs(s, state_names, refs);
             }
         }
-        HirStmtKind::While { condition, body } => {
+        HirStmtKind::While { condition, body, .. } => {
             collect_identifier_refs(condition, state_names, refs);
             collect_stmt_refs(body, state_names, refs);
         }
@@ -306,7 +323,7 @@ fn collect_stmt_refs(
                 collect_stmt_refs(m, state_names, refs);
             }
         }
-        HirStmtKind::Break | HirStmtKind::Continue => {}
+        HirStmtKind::Break { .. } | HirStmtKind::Continue { .. } => {}
     }
 }
 
@@ -317,7 +334,7 @@ fn collect_stmt_refs(
 fn topological_sort(
     nodes: &HashMap<String, ReactiveNode>,
     dependencies: &HashMap<String, HashSet<String>>,
-) -> Result<Vec<String>, String> {
+) -> Result<Vec<String>, Vec<Diagnostic>> {
     let mut order = Vec::new();
     
     // State nodes have no dependencies, they go first
@@ -371,7 +388,14 @@ fn topological_sort(
     }
 
     if sorted_computed.len() != computed_names.len() {
-        return Err("Cycle detected in computed dependency graph. Computed values cannot form circular references.".to_string());
+        // Point at the first computed value left on the cycle
+        let line = computed_names.iter()
+            .filter(|name| !sorted_computed.contains(name))
+            .map(|name| nodes[name].line)
+            .min()
+            .unwrap_or(0);
+        let message = "Cycle detected in computed dependency graph. Computed values cannot form circular references.";
+        return Err(vec![Diagnostic::error(codes::SYMBOL, message).at_line(line)]);
     }
 
     order.extend(sorted_computed);
@@ -391,19 +415,19 @@ pub fn collect_reactive_from_ast<'a>(
     for stmt in statements {
         match stmt {
             kinetix_language::ast::Statement::State { name, line, .. } => {
-                nodes.insert(name.clone(), ReactiveNode {
-                    name: name.clone(),
+                nodes.insert(name.to_string(), ReactiveNode {
+                    name: name.to_string(),
                     kind: ReactiveNodeKind::State,
                     line: *line,
                 });
             }
             kinetix_language::ast::Statement::Computed { name, line, .. } => {
-                nodes.insert(name.clone(), ReactiveNode {
-                    name: name.clone(),
+                nodes.insert(name.to_string(), ReactiveNode {
+                    name: name.to_string(),
                     kind: ReactiveNodeKind::Computed,
                     line: *line,
                 });
-                computed_names.push((name.clone(), *line));
+                computed_names.push((name.to_string(), *line));
             }
             kinetix_language::ast::Statement::Block { statements: inner, .. } => {
                 let (inner_nodes, inner_computed) = collect_reactive_from_ast(inner);
@@ -0,0 +1,457 @@
+//! Unclosed resource lint: warns about a resource bound with `let` that its
+//! function neither closes nor hands off.
+
+use crate::hir::{HirExprKind, HirExpression, HirProgram, HirStatement, HirStmtKind};
+use kinetix_language::diagnostic::{codes, Diagnostic};
+use kinetix_language::span::Span;
+
+/// How a resource is released.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Close {
+    /// A method of the native handle (`conn.close()`). The handle is also
+    /// released once its last reference goes away, at the latest when the
+    /// frame holding it returns.
+    Method(&'static [&'static str]),
+    /// A builtin taking the handle's id (`net.tcp.close(sock)`); nothing
+    /// else releases it. `result`: the opener returns a result whose `ok`
+    /// holds the id. `blocks`: closing waits (for a worker to exit), so the
+    /// compiler never closes it on the program's behalf.
+    Builtin { name: &'static str, result: bool, blocks: bool },
+}
+
+/// Builtins that return a resource the caller is responsible for.
+pub const RESOURCES: &[(&str, Close)] = &[
+    ("db.connect", Close::Method(&["close"])),
+    ("csv.open", Close::Method(&["close"])),
+    ("csv.create", Close::Method(&["close"])),
+    ("crypto.hasher.new", Close::Method(&["finalize", "close"])),
+    ("net.tcp.connect", Close::Builtin { name: "net.tcp.close", result: true, blocks: false }),
+    ("net.tcp.listen", Close::Builtin { name: "net.tcp.close", result: true, blocks: false }),
+    ("net.tcp.accept", Close::Builtin { name: "net.tcp.close", result: true, blocks: false }),
+    ("net.udp.bind", Close::Builtin { name: "net.udp.close", result: true, blocks: false }),
+    ("ipc.connect", Close::Builtin { name: "ipc.close", result: false, blocks: false }),
+    ("ipc.accept", Close::Builtin { name: "ipc.close", result: false, blocks: false }),
+    ("ipc.spawn", Close::Builtin { name: "ipc.close", result: false, blocks: true }),
+];
+
+/// Builtins that keep or hand their arguments to other code, so passing a
+/// handle to them hands it off.
+const STORING_BUILTINS: &[&str] = &["system.thread.spawn"];
+
+/// A resource the compiler closes when its function returns (see
+/// `Compiler::close_on_return`): closed by a builtin, bound in a function
+/// body and neither closed nor handed off there.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScopeClose {
+    pub line: usize,
+    pub name: String,
+    pub closer: &'static str,
+    /// The id is the binding's `ok` field, not the binding itself.
+    pub ok: bool,
+}
+
+/// A `let` binding of a resource.
+struct Open {
+    name: String,
+    opener: &'static str,
+    close: Close,
+    span: Span,
+    /// The id is the binding's `ok` field (`let r = net.tcp.connect(..)`).
+    ok: bool,
+}
+
+#[derive(Default)]
+struct Usage {
+    closed: bool,
+    escaped: bool,
+}
+
+/// An unclosed, not handed off resource, and whether it is local to a
+/// function (rather than top-level code).
+struct Leak {
+    open: Open,
+    in_function: bool,
+}
+
+/// Returns one warning per unclosed resource.
+///
+/// A resource is closed by its closing method or builtin anywhere in the same
+/// body, including inside a `system.defer` callback or as
+/// `system.defer(net.tcp.close, sock)`. It is handed off when its handle is
+/// used as a value (returned, passed to a function, stored, re-bound), so
+/// ownership moved elsewhere. Method calls on it and passing it to builtins
+/// (`net.tcp.send(sock, ..)`) are neither.
+pub fn check(program: &HirProgram) -> Vec<Diagnostic> {
+    leaks(program).into_iter().map(|Leak { open, in_function }| {
+        let (until, call) = match open.close {
+            Close::Method(methods) => ("its last reference goes away", format!("{}.{}()", open.name, methods[0])),
+            Close::Builtin { name, blocks, .. } => {
+                let until = if in_function && !blocks { "the function returns" } else { "the program exits" };
+                (until, format!("{}({}{})", name, open.name, if open.ok { ".ok" } else { "" }))
+            }
+        };
+        let message = format!(
+            "resource '{}' opened by {} is never closed, so it stays open until {}; call `{}` or register it with `system.defer`",
+            open.name, open.opener, until, call
+        );
+        Diagnostic::warning(codes::LINT, message).at(open.span)
+    }).collect()
+}
+
+/// The resources the compiler closes when their function returns.
+pub fn closes_on_return(program: &HirProgram) -> Vec<ScopeClose> {
+    leaks(program).into_iter()
+        .filter_map(|Leak { open, in_function }| match open.close {
+            Close::Builtin { name, blocks: false, .. } if in_function => Some(ScopeClose { line: open.span.line, name: open.name, closer: name, ok: open.ok }),
+            _ => None,
+        })
+        .collect()
+}
+
+fn leaks(program: &HirProgram) -> Vec<Leak> {
+    let mut leaks = Vec::new();
+    check_body(&program.statements, false, &mut leaks);
+    leaks
+}
+
+/// Checks one function body (or the top level), then every function nested
+/// in it as a body of its own.
+fn check_body(statements: &[HirStatement], in_function: bool, leaks: &mut Vec<Leak>) {
+    let mut opens = Vec::new();
+    for s in statements {
+        collect_opens(s, &mut opens);
+    }
+    for open in opens {
+        let mut usage = Usage::default();
+        for s in statements {
+            scan_stmt(s, &open, &mut usage);
+        }
+        if !usage.closed && !usage.escaped {
+            leaks.push(Leak { open, in_function });
+        }
+    }
+    for s in statements {
+        check_nested_functions(s, leaks);
+    }
+}
+
+fn check_nested_functions(stmt: &HirStatement, leaks: &mut Vec<Leak>) {
+    match &stmt.kind {
+        HirStmtKind::Function { body, .. } => check_body(std::slice::from_ref(body), true, leaks),
+        HirStmtKind::Class { methods, .. } => {
+            for m in methods {
+                check_nested_functions(m, leaks);
+            }
+        }
+        HirStmtKind::Block { statements } => {
+            for s in statements {
+                check_nested_functions(s, leaks);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The name of a builtin (`net.tcp.close`), as a callee or a value
+/// (`system.defer(net.tcp.close, id)`): lowering flattens some into one
+/// identifier and leaves others as member accesses on the module.
+fn builtin_path(expr: &HirExpression) -> Option<String> {
+    match &expr.kind {
+        HirExprKind::Identifier(name) if name.contains('.') && !name.contains("::") => Some(name.clone()),
+        HirExprKind::Identifier(name) if crate::symbol::BUILTIN_MODULES.contains(&name.as_str()) => Some(name.clone()),
+        HirExprKind::MemberAccess { object, member } => Some(format!("{}.{}", builtin_path(object)?, member)),
+        _ => None,
+    }
+}
+
+/// The builtin a call calls, and its arguments.
+fn builtin_call(expr: &HirExpression) -> Option<(String, &[HirExpression])> {
+    match &expr.kind {
+        HirExprKind::Call { function, arguments } => Some((builtin_path(function)?, arguments)),
+        HirExprKind::MethodCall { object, method, arguments } => Some((format!("{}.{}", builtin_path(object)?, method), arguments)),
+        _ => None,
+    }
+}
+
+/// The opener and id location of a resource-opening `let` value.
+fn opened(value: &HirExpression) -> Option<(&'static str, Close, bool)> {
+    let (call, field) = match &value.kind {
+        HirExprKind::MemberAccess { object, member } => (&**object, Some(member.as_str())),
+        _ => (value, None),
+    };
+    let (path, _) = builtin_call(call)?;
+    let &(opener, close) = RESOURCES.iter().find(|(o, _)| *o == path)?;
+    match (close, field) {
+        (_, None) => Some((opener, close, matches!(close, Close::Builtin { result: true, .. }))),
+        (Close::Builtin { result: true, .. }, Some("ok")) => Some((opener, close, false)),
+        _ => None,
+    }
+}
+
+/// `let` bindings of an opener call in this body, not descending into nested
+/// function declarations (they're checked separately).
+fn collect_opens(stmt: &HirStatement, out: &mut Vec<Open>) {
+    match &stmt.kind {
+        HirStmtKind::Let { name, value, .. } => {
+            if let Some((opener, close, ok)) = opened(value) {
+                out.push(Open { name: name.clone(), opener, close, span: stmt.location(), ok });
+            }
+            collect_opens_expr(value, out);
+        }
+        HirStmtKind::Block { statements } => {
+            for s in statements {
+                collect_opens(s, out);
+            }
+        }
+        HirStmtKind::While { condition, body, .. } => {
+            collect_opens_expr(condition, out);
+            collect_opens(body, out);
+        }
+        HirStmtKind::For { range, body, .. } => {
+            collect_opens_expr(range, out);
+            collect_opens(body, out);
+        }
+        HirStmtKind::Expression { expression } => collect_opens_expr(expression, out),
+        HirStmtKind::Effect { body, .. } => collect_opens(body, out),
+        _ => {}
+    }
+}
+
+fn collect_opens_expr(expr: &HirExpression, out: &mut Vec<Open>) {
+    match &expr.kind {
+        HirExprKind::If { consequence, alternative, .. } => {
+            collect_opens(consequence, out);
+            if let Some(alt) = alternative {
+                collect_opens(alt, out);
+            }
+        }
+        HirExprKind::Match { arms, .. } => {
+            for (_, body) in arms {
+                collect_opens(body, out);
+            }
+        }
+        HirExprKind::FunctionLiteral { body, .. } => collect_opens(body, out),
+        _ => {}
+    }
+}
+
+fn is_receiver(object: &HirExpression, name: &str) -> bool {
+    matches!(&object.kind, HirExprKind::Identifier(n) if n == name)
+}
+
+/// Whether `expr` is the resource's handle or id as a value: the binding, or
+/// its `ok` field when that holds the id.
+fn is_handle(expr: &HirExpression, open: &Open) -> bool {
+    match &expr.kind {
+        HirExprKind::Identifier(n) => *n == open.name,
+        HirExprKind::MemberAccess { object, member } => open.ok && member == "ok" && is_receiver(object, &open.name),
+        _ => false,
+    }
+}
+
+fn scan_stmt(stmt: &HirStatement, open: &Open, usage: &mut Usage) {
+    match &stmt.kind {
+        HirStmtKind::Let { value, .. }
+        | HirStmtKind::State { value, .. }
+        | HirStmtKind::Computed { value, .. } => scan_expr(value, open, usage),
+        HirStmtKind::Return { value } => {
+            if let Some(v) = value {
+                scan_expr(v, open, usage);
+            }
+        }
+        HirStmtKind::Expression { expression } => scan_expr(expression, open, usage),
+        HirStmtKind::Block { statements } => {
+            for s in statements {
+                scan_stmt(s, open, usage);
+            }
+        }
+        HirStmtKind::While { condition, body, .. } => {
+            scan_expr(condition, open, usage);
+            scan_stmt(body, open, usage);
+        }
+        HirStmtKind::For { range, body, .. } => {
+            scan_expr(range, open, usage);
+            scan_stmt(body, open, usage);
+        }
+        HirStmtKind::Effect { body, .. } => scan_stmt(body, open, usage),
+        HirStmtKind::Function { .. } | HirStmtKind::Class { .. }
+        | HirStmtKind::Break { .. } | HirStmtKind::Continue { .. } => {}
+    }
+}
+
+/// A call of a builtin: the handle may be passed along without being handed
+/// off, and passing it to its closer (directly or through `system.defer`)
+/// closes it.
+fn scan_builtin_call(path: &str, arguments: &[HirExpression], open: &Open, usage: &mut Usage) {
+    let closer = match open.close {
+        Close::Builtin { name, .. } => Some(name),
+        Close::Method(_) => None,
+    };
+    let deferred_close = path == "system.defer"
+        && arguments.first().and_then(builtin_path).is_some_and(|f| Some(f.as_str()) == closer);
+    for a in arguments {
+        if !is_handle(a, open) {
+            scan_expr(a, open, usage);
+        } else if Some(path) == closer || deferred_close {
+            usage.closed = true;
+        } else if STORING_BUILTINS.contains(&path) {
+            usage.escaped = true;
+        }
+    }
+}
+
+fn scan_expr(expr: &HirExpression, open: &Open, usage: &mut Usage) {
+    if is_handle(expr, open) {
+        usage.escaped = true;
+        return;
+    }
+    let name = open.name.as_str();
+    let closes = |method: &str| matches!(open.close, Close::Method(methods) if methods.contains(&method));
+    if let Some((path, arguments)) = builtin_call(expr) {
+        scan_builtin_call(&path, arguments, open, usage);
+        return;
+    }
+    match &expr.kind {
+        HirExprKind::Identifier(_) => {}
+        HirExprKind::Call { function, arguments } => {
+            match &function.kind {
+                HirExprKind::MemberAccess { object, member } if is_receiver(object, name) => {
+                    if closes(member) {
+                        usage.closed = true;
+                    }
+                }
+                _ => scan_expr(function, open, usage),
+            }
+            for a in arguments {
+                scan_expr(a, open, usage);
+            }
+        }
+        HirExprKind::MethodCall { object, method, arguments } => {
+            if is_receiver(object, name) {
+                if closes(method) {
+                    usage.closed = true;
+                }
+            } else {
+                scan_expr(object, open, usage);
+            }
+            for a in arguments {
+                scan_expr(a, open, usage);
+            }
+        }
+        HirExprKind::MemberAccess { object, .. } => {
+            if !is_receiver(object, name) {
+                scan_expr(object, open, usage);
+            }
+        }
+        HirExprKind::Prefix { right, .. } => scan_expr(right, open, usage),
+        HirExprKind::Infix { left, right, .. } => {
+            scan_expr(left, open, usage);
+            scan_expr(right, open, usage);
+        }
+        HirExprKind::If { condition, consequence, alternative } => {
+            scan_expr(condition, open, usage);
+            scan_stmt(consequence, open, usage);
+            if let Some(alt) = alternative {
+                scan_stmt(alt, open, usage);
+            }
+        }
+        HirExprKind::FunctionLiteral { body, .. } => scan_stmt(body, open, usage),
+        HirExprKind::ArrayLiteral(elems) => {
+            for e in elems {
+                scan_expr(e, open, usage);
+            }
+        }
+        HirExprKind::StructLiteral(_, fields) => {
+            for (_, e) in fields {
+                scan_expr(e, open, usage);
+            }
+        }
+        HirExprKind::MapLiteral(pairs) => {
+            for (k, v) in pairs {
+                scan_expr(k, open, usage);
+                scan_expr(v, open, usage);
+            }
+        }
+        HirExprKind::Index { left, index } => {
+            scan_expr(left, open, usage);
+            scan_expr(index, open, usage);
+        }
+        HirExprKind::Assign { target, value } => {
+            scan_expr(target, open, usage);
+            scan_expr(value, open, usage);
+        }
+        HirExprKind::Range { start, end } => {
+            scan_expr(start, open, usage);
+            scan_expr(end, open, usage);
+        }
+        HirExprKind::Match { value, arms, .. } => {
+            scan_expr(value, open, usage);
+            for (_, body) in arms {
+                scan_stmt(body, open, usage);
+            }
+        }
+        HirExprKind::Integer(_) | HirExprKind::Float(_) | HirExprKind::String(_)
+        | HirExprKind::Boolean(_) | HirExprKind::Null => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bumpalo::Bump;
+    use kinetix_language::lexer::Lexer;
+    use kinetix_language::parser::Parser;
+
+    fn lower(src: &str) -> HirProgram {
+        let arena = Bump::new();
+        let mut parser = Parser::new(Lexer::new(src), &arena);
+        let program = parser.parse_program();
+        assert!(parser.errors.is_empty(), "Parser errors: {:?}", parser.errors);
+        let symbols = crate::symbol::resolve_program(&program.statements).expect("resolves");
+        let traits = crate::trait_solver::TraitEnvironment::new();
+        crate::hir::lower_to_hir(&program.statements, &symbols, &traits)
+    }
+
+    fn lint(src: &str) -> Vec<String> {
+        check(&lower(src)).iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn unclosed_connection_warns() {
+        let warnings = lint("fn load() {\n    let conn = db.connect(\"a.db\")\n    conn.query(\"SELECT 1\")\n}\n");
+        assert_eq!(warnings.len(), 1, "{:?}", warnings);
+        assert!(warnings[0].starts_with("Line 2:5: resource 'conn'"), "{:?}", warnings);
+    }
+
+    #[test]
+    fn closed_deferred_or_returned_connections_are_fine() {
+        assert!(lint("let conn = db.connect(\"a.db\")\nconn.close()\n").is_empty());
+        assert!(lint("let conn = db.connect(\"a.db\")\nsystem.defer(fn() { conn.close() })\n").is_empty());
+        assert!(lint("fn open() { let conn = db.connect(\"a.db\")\n return conn }\n").is_empty());
+    }
+
+    #[test]
+    fn other_native_handles_are_checked() {
+        let warnings = lint("let r = csv.open(\"a.csv\")\nprint(r.next())\nlet h = crypto.hasher.new(\"sha256\")\nh.update(\"x\")\n");
+        assert_eq!(warnings.len(), 2, "{:?}", warnings);
+        assert!(warnings[1].contains("call `h.finalize()`"), "{:?}", warnings);
+        assert!(lint("let h = crypto.hasher.new(\"sha256\")\nh.update(\"x\")\nprint(h.finalize())\n").is_empty());
+    }
+
+    #[test]
+    fn sockets_are_closed_by_their_builtin() {
+        let warnings = lint("fn ping() {\n    let r = net.tcp.connect(\"h\", 1)\n    net.tcp.send(r.ok, \"x\")\n}\n");
+        assert_eq!(warnings.len(), 1, "{:?}", warnings);
+        assert!(warnings[0].contains("until the function returns; call `net.tcp.close(r.ok)`"), "{:?}", warnings);
+        assert!(lint("let s = net.tcp.connect(\"h\", 1).ok\nnet.tcp.send(s, \"x\")\nnet.tcp.close(s)\n").is_empty());
+        assert!(lint("let c = ipc.connect(\"p\")\nsystem.defer(ipc.close, c)\n").is_empty());
+        assert!(lint("let c = ipc.connect(\"p\")\nsystem.thread.spawn(fn(x: int) { print(x) }, c)\n").is_empty());
+        assert_eq!(lint("let c = ipc.connect(\"p\")\nsystem.defer(net.udp.close, c)\n").len(), 1);
+    }
+
+    #[test]
+    fn only_unclosed_function_locals_close_on_return() {
+        let program = lower("let top = ipc.connect(\"p\")\nfn ping() {\n    let r = net.tcp.connect(\"h\", 1)\n    let w = ipc.spawn(\"w.kix\")\n    let db = db.connect(\"a.db\")\n    let kept = ipc.connect(\"p\")\n    return kept\n}\n");
+        assert_eq!(closes_on_return(&program), vec![ScopeClose { line: 3, name: "r".to_string(), closer: "net.tcp.close", ok: true }]);
+    }
+}
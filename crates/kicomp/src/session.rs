@@ -0,0 +1,218 @@
+//! Per-session caches for the trait environment and the reactive graph.
+
+use crate::hir::HirProgram;
+use crate::ir_hash::DeterministicHasher;
+use crate::reactive::{self, ReactiveGraph};
+use crate::trait_solver::{ImplDef, TraitDef, TraitEnvironment};
+use kinetix_language::ast::Statement;
+use kinetix_language::diagnostic::Diagnostic;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::time::Instant;
+
+/// What one cached step did during the latest check.
+#[derive(Debug, Clone)]
+pub struct CachedPhase {
+    pub name: &'static str,
+    pub duration_ms: f64,
+    /// Items taken from the previous check.
+    pub reused: usize,
+    /// Items built from scratch.
+    pub rebuilt: usize,
+}
+
+#[derive(Clone)]
+enum TraitItem {
+    Trait(TraitDef),
+    Impl(ImplDef),
+}
+
+/// Kept by a watch loop, the REPL or the LSP, which check the same program
+/// again after every edit: most edits leave its traits, impls and
+/// `state`/`computed` declarations alone, so only the items whose inputs
+/// changed are rebuilt.
+pub struct Session {
+    /// Parsed trait and impl blocks, by the fingerprint of what they declare.
+    trait_items: HashMap<u64, TraitItem>,
+    /// Fingerprints, in registration order, of the items `traits` holds;
+    /// `None` until a build succeeds.
+    trait_keys: Option<Vec<u64>>,
+    traits: TraitEnvironment,
+    reactive: Option<ReactiveGraph>,
+    /// The latest run of each step: what it did and how long it took, for
+    /// `--time-passes`.
+    pub phases: Vec<CachedPhase>,
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Self {
+            trait_items: HashMap::new(),
+            trait_keys: None,
+            traits: TraitEnvironment::new(),
+            reactive: None,
+            phases: Vec::new(),
+        }
+    }
+
+    /// The trait environment for `statements`: every `trait` registered, then
+    /// every `impl`, then the traits checked for cycles. Items whose
+    /// declaration is unchanged are not parsed again, and when no item
+    /// changed the previous environment (method cache included) is returned.
+    pub fn trait_environment(&mut self, statements: &[Statement]) -> Result<&TraitEnvironment, String> {
+        let start = Instant::now();
+        let traits = statements.iter().filter(|s| matches!(s, Statement::Trait { .. }));
+        let impls = statements.iter().filter(|s| matches!(s, Statement::Impl { .. }));
+        let items: Vec<(u64, &Statement)> = traits.chain(impls).map(|s| (fingerprint(s), s)).collect();
+        let keys: Vec<u64> = items.iter().map(|(key, _)| *key).collect();
+        if self.trait_keys.as_ref() == Some(&keys) {
+            self.record("Trait Environment", start, keys.len(), 0);
+            return Ok(&self.traits);
+        }
+
+        self.trait_keys = None;
+        let mut previous = std::mem::take(&mut self.trait_items);
+        let (mut reused, mut rebuilt) = (0, 0);
+        let mut env = TraitEnvironment::new();
+        let mut result = Ok(());
+        for (key, stmt) in items {
+            let item = match previous.remove(&key).or_else(|| self.trait_items.get(&key).cloned()) {
+                Some(item) => {
+                    reused += 1;
+                    item
+                }
+                None => {
+                    rebuilt += 1;
+                    match TraitEnvironment::trait_def(stmt) {
+                        Some(def) => TraitItem::Trait(def),
+                        None => TraitItem::Impl(TraitEnvironment::impl_def(stmt).expect("filtered to impl statements")),
+                    }
+                }
+            };
+            self.trait_items.insert(key, item.clone());
+            match item {
+                TraitItem::Trait(def) => env.add_trait(def),
+                TraitItem::Impl(def) => {
+                    if let Err(e) = env.add_impl(def) {
+                        result = Err(e);
+                        break;
+                    }
+                }
+            }
+        }
+        let result = result.and_then(|()| env.validate_cycles());
+        self.record("Trait Environment", start, reused, rebuilt);
+        result?;
+
+        self.traits = env;
+        self.trait_keys = Some(keys);
+        Ok(&self.traits)
+    }
+
+    /// The reactive graph for `program`. Its nodes and dependencies are
+    /// collected again, but the graph is only re-sorted when they changed.
+    pub fn reactive_graph(&mut self, program: &HirProgram) -> Result<&ReactiveGraph, Vec<Diagnostic>> {
+        let start = Instant::now();
+        let (nodes, dependencies) = reactive::reactive_dependencies(program)?;
+        let unchanged = self.reactive.as_ref()
+            .is_some_and(|graph| graph.nodes == nodes && graph.dependencies == dependencies);
+        let count = nodes.len();
+        if !unchanged {
+            self.reactive = None;
+            self.reactive = Some(reactive::assemble_graph(nodes, dependencies)?);
+        }
+        let (reused, rebuilt) = if unchanged { (count, 0) } else { (0, count) };
+        self.record("Reactive Graph", start, reused, rebuilt);
+        Ok(self.reactive.as_ref().expect("built above"))
+    }
+
+    fn record(&mut self, name: &'static str, start: Instant, reused: usize, rebuilt: usize) {
+        let phase = CachedPhase { name, duration_ms: start.elapsed().as_secs_f64() * 1000.0, reused, rebuilt };
+        match self.phases.iter_mut().find(|p| p.name == name) {
+            Some(existing) => *existing = phase,
+            None => self.phases.push(phase),
+        }
+    }
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Hash of exactly what `trait_def`/`impl_def` read from a statement, so
+/// moving an item to another line does not invalidate it.
+fn fingerprint(stmt: &Statement) -> u64 {
+    let mut hasher = DeterministicHasher::new();
+    match stmt {
+        Statement::Trait { name, generics, methods, .. } => {
+            ("trait", name, generics, methods).hash(&mut hasher);
+        }
+        Statement::Impl { trait_name, target_name, generics, methods, .. } => {
+            ("impl", trait_name, target_name, generics).hash(&mut hasher);
+            for method in methods {
+                if let Statement::Function { name, parameters, return_type, .. } = method {
+                    (name, parameters, return_type).hash(&mut hasher);
+                }
+            }
+        }
+        _ => {}
+    }
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bumpalo::Bump;
+    use kinetix_language::lexer::Lexer;
+    use kinetix_language::parser::Parser;
+
+    const SHAPES: &str = "trait Shape {\n  fn area(self) -> float\n}\nstruct Square { side: float }\nstruct Circle { r: float }\nimpl Shape for Square {\n  fn area(self) -> float {\n    return self.side * self.side\n  }\n}\nimpl Shape for Circle {\n  fn area(self) -> float {\n    return 3.14 * self.r * self.r\n  }\n}\n";
+
+    fn check_traits(session: &mut Session, src: &str) -> Result<(usize, usize), String> {
+        let arena = Bump::new();
+        let mut parser = Parser::new(Lexer::new(src), &arena);
+        let program = parser.parse_program();
+        assert!(parser.errors.is_empty(), "{:?}", parser.errors);
+        session.trait_environment(&program.statements)?;
+        let phase = &session.phases[0];
+        Ok((phase.reused, phase.rebuilt))
+    }
+
+    #[test]
+    fn trait_items_are_rebuilt_only_when_changed() {
+        let mut session = Session::new();
+        assert_eq!(check_traits(&mut session, SHAPES), Ok((0, 3)));
+        assert_eq!(check_traits(&mut session, SHAPES), Ok((3, 0)));
+        assert!(session.traits.resolve_method("Square", "area").is_some());
+
+        let edited = SHAPES.replace("fn area(self) -> float {\n    return 3.14", "fn area(self) -> int {\n    return 3");
+        assert_eq!(check_traits(&mut session, &format!("\n\n{}", edited)), Ok((2, 1)));
+
+        let overlapping = format!("{}impl Shape for Circle {{\n  fn area(self) -> float {{\n    return 1.0\n  }}\n}}\n", SHAPES);
+        let err = check_traits(&mut session, &overlapping).unwrap_err();
+        assert!(err.contains("Overlapping implementations of trait 'Shape' for type 'Circle'"), "{}", err);
+        assert_eq!(check_traits(&mut session, SHAPES), Ok((3, 0)));
+    }
+
+    #[test]
+    fn reactive_graph_is_reused_while_its_inputs_match() {
+        let mut session = Session::new();
+        let graph_for = |session: &mut Session, src: &str| -> (Vec<String>, usize) {
+            let arena = Bump::new();
+            let mut parser = Parser::new(Lexer::new(src), &arena);
+            let program = parser.parse_program();
+            let symbols = crate::symbol::resolve_program(&program.statements).unwrap();
+            let hir = crate::hir::lower_to_hir(&program.statements, &symbols, &TraitEnvironment::new());
+            let order = session.reactive_graph(&hir).unwrap().update_order.clone();
+            (order, session.phases[0].reused)
+        };
+        let src = "state count = 1\ncomputed double = count * 2\n";
+        assert_eq!(graph_for(&mut session, src), (vec!["count".to_string(), "double".to_string()], 0));
+        assert_eq!(graph_for(&mut session, &format!("{}println(double)\n", src)).1, 2);
+        let edited = graph_for(&mut session, "state count = 1\nstate step = 2\ncomputed double = count * step\n");
+        assert_eq!(edited, (vec!["count".to_string(), "step".to_string(), "double".to_string()], 0));
+    }
+}
@@ -13,6 +13,7 @@
 
 use crate::mir::{MirProgram, MirFunction, BasicBlockData, StatementKind, Operand, RValue, TerminatorKind};
 use std::collections::HashSet;
+use rayon::prelude::*;
 
 fn successors(block: &BasicBlockData) -> Vec<usize> {
     match block.terminator.as_ref().map(|t| &t.kind) {
@@ -22,12 +23,14 @@ fn successors(block: &BasicBlockData) -> Vec<usize> {
     }
 }
 
+/// Functions are validated in parallel; the error reported is the first one
+/// in program order (main block, then functions).
 pub fn validate(program: &MirProgram) -> Result<(), String> {
-    validate_function(&program.main_block)?;
-    for func in &program.functions {
-        validate_function(func)?;
-    }
-    Ok(())
+    let results: Vec<Result<(), String>> = rayon::iter::once(&program.main_block)
+        .chain(program.functions.par_iter())
+        .map(validate_function)
+        .collect();
+    results.into_iter().collect()
 }
 
 fn validate_function(func: &MirFunction) -> Result<(), String> {
@@ -222,6 +225,11 @@ mod tests {
     use super::*;
     use crate::mir::{BasicBlock, BasicBlockData, LocalDecl, LocalId, Mutability, MirStatement, Place, Terminator, Constant};
     use crate::types::Type;
+    use kinetix_language::span::Span;
+
+    fn at(line: usize) -> Span {
+        Span { line, ..Span::default() }
+    }
 
     // Build 38 Phase B1: real definite-assignment dataflow. `local0` is assigned
     // only on the `then` branch, not on `else`; reading it unconditionally after
@@ -237,26 +245,26 @@ mod tests {
                     then_block: BasicBlock(1),
                     else_block: BasicBlock(2),
                 },
-                line: 0,
+                span: at(0),
             }),
         };
         let then_block = BasicBlockData {
             statements: vec![MirStatement {
                 kind: StatementKind::Assign(Place { local: LocalId(0) }, RValue::Use(Operand::Constant(Constant::Int(1)))),
-                line: 1,
+                span: at(1),
             }],
-            terminator: Some(Terminator { kind: TerminatorKind::Goto(BasicBlock(3)), line: 1 }),
+            terminator: Some(Terminator { kind: TerminatorKind::Goto(BasicBlock(3)), span: at(1) }),
         };
         let else_block = BasicBlockData {
             statements: vec![],
-            terminator: Some(Terminator { kind: TerminatorKind::Goto(BasicBlock(3)), line: 2 }),
+            terminator: Some(Terminator { kind: TerminatorKind::Goto(BasicBlock(3)), span: at(2) }),
         };
         let merge_block = BasicBlockData {
             statements: vec![MirStatement {
                 kind: StatementKind::Expression(RValue::Use(Operand::Copy(Place { local: LocalId(0) }))),
-                line: 3,
+                span: at(3),
             }],
-            terminator: Some(Terminator { kind: TerminatorKind::Return(None), line: 3 }),
+            terminator: Some(Terminator { kind: TerminatorKind::Return(None), span: at(3) }),
         };
 
         let f = MirFunction {
@@ -282,29 +290,29 @@ mod tests {
                     then_block: BasicBlock(1),
                     else_block: BasicBlock(2),
                 },
-                line: 0,
+                span: at(0),
             }),
         };
         let then_block = BasicBlockData {
             statements: vec![MirStatement {
                 kind: StatementKind::Assign(Place { local: LocalId(0) }, RValue::Use(Operand::Constant(Constant::Int(1)))),
-                line: 1,
+                span: at(1),
             }],
-            terminator: Some(Terminator { kind: TerminatorKind::Goto(BasicBlock(3)), line: 1 }),
+            terminator: Some(Terminator { kind: TerminatorKind::Goto(BasicBlock(3)), span: at(1) }),
         };
         let else_block = BasicBlockData {
             statements: vec![MirStatement {
                 kind: StatementKind::Assign(Place { local: LocalId(0) }, RValue::Use(Operand::Constant(Constant::Int(2)))),
-                line: 2,
+                span: at(2),
             }],
-            terminator: Some(Terminator { kind: TerminatorKind::Goto(BasicBlock(3)), line: 2 }),
+            terminator: Some(Terminator { kind: TerminatorKind::Goto(BasicBlock(3)), span: at(2) }),
         };
         let merge_block = BasicBlockData {
             statements: vec![MirStatement {
                 kind: StatementKind::Expression(RValue::Use(Operand::Copy(Place { local: LocalId(0) }))),
-                line: 3,
+                span: at(3),
             }],
-            terminator: Some(Terminator { kind: TerminatorKind::Return(None), line: 3 }),
+            terminator: Some(Terminator { kind: TerminatorKind::Return(None), span: at(3) }),
         };
 
         let f = MirFunction {
@@ -0,0 +1,217 @@
+//! Strict mode (`--strict`, `#strict`): reports parameters and values left
+//! without a type annotation, which would otherwise be typed `Any`.
+
+use kinetix_language::ast::{Expression, ExpressionKind, Program, Statement};
+use kinetix_language::diagnostic::{codes, Diagnostic};
+use kinetix_language::span::Span;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Turns strict mode on for every program, as `--strict` does.
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether `program` is checked: `--strict`, or `#strict` in the file.
+pub fn enabled(program: &Program<'_>) -> bool {
+    program.strict || ENABLED.load(Ordering::Relaxed)
+}
+
+/// Returns `Ok(warnings)`, or `Err(errors)` when a public signature is
+/// missing a type.
+pub fn check(statements: &[Statement<'_>]) -> Result<Vec<Diagnostic>, Vec<Diagnostic>> {
+    let mut strict = Strict { errors: Vec::new(), warnings: Vec::new() };
+    statements.iter().for_each(|s| strict.visit_stmt(s, &Scope::default()));
+    if strict.errors.is_empty() { Ok(strict.warnings) } else { Err(strict.errors) }
+}
+
+struct Strict {
+    errors: Vec<Diagnostic>,
+    warnings: Vec<Diagnostic>,
+}
+
+/// The function being walked.
+#[derive(Default)]
+struct Scope {
+    name: String,
+    /// Its parameters typed `Any`.
+    untyped: HashSet<String>,
+    /// Whether it declares a return type.
+    returns: bool,
+}
+
+impl Strict {
+    fn error(&mut self, span: Span, message: String) {
+        self.errors.push(Diagnostic::error(codes::STRICT, message).at(span));
+    }
+
+    fn warning(&mut self, span: Span, message: String) {
+        self.warnings.push(Diagnostic::warning(codes::LINT, message).at(span));
+    }
+
+    fn visit_function(&mut self, name: &str, parameters: &[(String, String)], return_type: &str, public: bool, body: &Statement<'_>, span: Span) {
+        let untyped: HashSet<String> = parameters.iter()
+            .filter(|(p, ty)| ty == "Any" && p != "self")
+            .map(|(p, _)| p.clone())
+            .collect();
+        for (param, _) in parameters.iter().filter(|(p, _)| untyped.contains(p)) {
+            if public {
+                self.error(span, format!("parameter '{}' of public function '{}' has no type annotation", param, name));
+            } else {
+                self.warning(span, format!("parameter '{}' of '{}' has no type annotation, so it is typed Any", param, name));
+            }
+        }
+        let returns = return_type != "void";
+        if public && !returns && returns_value(body) {
+            self.error(span, format!("public function '{}' returns a value but declares no return type", name));
+        }
+        self.visit_stmt(body, &Scope { name: name.to_string(), untyped, returns });
+    }
+
+    fn visit_stmt(&mut self, stmt: &Statement<'_>, scope: &Scope) {
+        match stmt {
+            Statement::Function { name, parameters, body, return_type, public, span, .. } => {
+                self.visit_function(name, parameters, return_type, *public, body, *span);
+            }
+            Statement::Class { methods, constants, .. } => methods.iter().chain(constants).for_each(|s| self.visit_stmt(s, scope)),
+            Statement::Impl { methods, .. } => methods.iter().for_each(|s| self.visit_stmt(s, scope)),
+            Statement::Let { name, type_hint, value, span, .. } => {
+                if let (None, Expression { kind: ExpressionKind::Identifier(from), .. }) = (type_hint, value)
+                    && scope.untyped.contains(*from)
+                {
+                    self.warning(*span, format!("'{}' is typed Any, copied from parameter '{}' of '{}'", name, from, scope.name));
+                }
+                self.visit_expr(value, scope);
+            }
+            Statement::Return { value, span, .. } => {
+                if let Some(Expression { kind: ExpressionKind::Identifier(from), .. }) = value
+                    && scope.untyped.contains(*from)
+                    && !scope.returns
+                {
+                    self.warning(*span, format!("'{}' returns parameter '{}', which is typed Any", scope.name, from));
+                }
+                if let Some(value) = value {
+                    self.visit_expr(value, scope);
+                }
+            }
+            Statement::State { value, .. } | Statement::Computed { value, .. } | Statement::Expression { expression: value, .. } => {
+                self.visit_expr(value, scope);
+            }
+            Statement::Effect { body, .. } => self.visit_stmt(body, scope),
+            Statement::Block { statements, .. } => statements.iter().for_each(|s| self.visit_stmt(s, scope)),
+            Statement::While { condition: value, body, .. } | Statement::For { range: value, body, .. } => {
+                self.visit_expr(value, scope);
+                self.visit_stmt(body, scope);
+            }
+            Statement::Struct { .. }
+            | Statement::Include { .. }
+            | Statement::Version { .. }
+            | Statement::ImportBundle { .. }
+            | Statement::Import { .. }
+            | Statement::Enum { .. }
+            | Statement::Trait { .. }
+            | Statement::Break { .. }
+            | Statement::Continue { .. } => {}
+        }
+    }
+
+    /// Only lambdas matter here: they are the functions inside expressions.
+    fn visit_expr(&mut self, expr: &Expression<'_>, scope: &Scope) {
+        match &expr.kind {
+            ExpressionKind::FunctionLiteral { parameters, body, return_type } => {
+                self.visit_function("<lambda>", parameters, return_type, false, body, body.span());
+            }
+            ExpressionKind::Prefix { right, .. } | ExpressionKind::Try { value: right } => self.visit_expr(right, scope),
+            ExpressionKind::MemberAccess { object, .. } => self.visit_expr(object, scope),
+            ExpressionKind::Assign { target: left, value: right }
+            | ExpressionKind::Infix { left, right, .. }
+            | ExpressionKind::Index { left, index: right }
+            | ExpressionKind::Range { start: left, end: right } => {
+                self.visit_expr(left, scope);
+                self.visit_expr(right, scope);
+            }
+            ExpressionKind::If { condition, consequence, alternative } => {
+                self.visit_expr(condition, scope);
+                self.visit_stmt(consequence, scope);
+                if let Some(alt) = alternative {
+                    self.visit_stmt(alt, scope);
+                }
+            }
+            ExpressionKind::Call { function, arguments } => {
+                self.visit_expr(function, scope);
+                arguments.iter().for_each(|a| self.visit_expr(a, scope));
+            }
+            ExpressionKind::StructLiteral { fields, .. } => fields.iter().for_each(|(_, v)| self.visit_expr(v, scope)),
+            ExpressionKind::ArrayLiteral(items) => items.iter().for_each(|i| self.visit_expr(i, scope)),
+            ExpressionKind::MapLiteral(entries) => entries.iter().for_each(|(k, v)| {
+                self.visit_expr(k, scope);
+                self.visit_expr(v, scope);
+            }),
+            ExpressionKind::Match { value, arms, .. } => {
+                self.visit_expr(value, scope);
+                arms.iter().for_each(|(_, body)| self.visit_stmt(body, scope));
+            }
+            ExpressionKind::Identifier(_)
+            | ExpressionKind::Integer(_)
+            | ExpressionKind::Float(_)
+            | ExpressionKind::String(_)
+            | ExpressionKind::Boolean(_)
+            | ExpressionKind::Null => {}
+        }
+    }
+}
+
+/// Whether a function body has a `return` with a value, outside the
+/// functions nested in it.
+fn returns_value(stmt: &Statement<'_>) -> bool {
+    match stmt {
+        Statement::Return { value, .. } => value.is_some(),
+        Statement::Block { statements, .. } => statements.iter().any(returns_value),
+        Statement::While { body, .. } | Statement::For { body, .. } => returns_value(body),
+        Statement::Expression { expression: Expression { kind: ExpressionKind::If { consequence, alternative, .. }, .. }, .. } => {
+            returns_value(consequence) || alternative.is_some_and(returns_value)
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bumpalo::Bump;
+    use kinetix_language::lexer::Lexer;
+    use kinetix_language::parser::Parser;
+
+    fn strict(src: &str) -> (bool, Result<Vec<String>, Vec<String>>) {
+        let arena = Bump::new();
+        let mut parser = Parser::new(Lexer::new(src), &arena);
+        let program = parser.parse_program();
+        assert!(parser.errors.is_empty(), "Parser errors: {:?}", parser.errors);
+        let text = |diagnostics: Vec<Diagnostic>| diagnostics.iter().map(ToString::to_string).collect();
+        (program.strict, check(&program.statements).map(text).map_err(text))
+    }
+
+    #[test]
+    fn public_signatures_need_types_and_any_is_traced() {
+        let (directive, result) = strict("#strict\npub fn area(w, h: int) {\n  return w * h\n}\n");
+        assert!(directive);
+        assert_eq!(result, Err(vec![
+            "Line 2:1: parameter 'w' of public function 'area' has no type annotation".to_string(),
+            "Line 2:1: public function 'area' returns a value but declares no return type".to_string(),
+        ]));
+
+        let (directive, result) = strict("fn echo(x) {\n  let y = x\n  return y\n}\nfn id(v) {\n  return v\n}\n");
+        assert!(!directive);
+        assert_eq!(result, Ok(vec![
+            "Line 1:1: parameter 'x' of 'echo' has no type annotation, so it is typed Any".to_string(),
+            "Line 2:3: 'y' is typed Any, copied from parameter 'x' of 'echo'".to_string(),
+            "Line 5:1: parameter 'v' of 'id' has no type annotation, so it is typed Any".to_string(),
+            "Line 6:3: 'id' returns parameter 'v', which is typed Any".to_string(),
+        ]));
+
+        let (_, result) = strict("pub fn add(a: int, b: int) -> int {\n  return a + b\n}\nlet f = fn(n: int) -> int { return n }\n");
+        assert_eq!(result, Ok(vec![]));
+    }
+}
@@ -1,13 +1,11 @@
-/// Symbol Resolution Pass — builds a scope-aware symbol table from the AST.
-///
-/// This pass walks the untyped AST and:
-/// 1. Registers all definitions (let, fn, class, struct) in a scoped symbol table.
-/// 2. Reports undeclared variable errors.
-/// 3. Produces a SymbolTable that the HIR lowering pass can consume.
-
-use std::collections::HashMap;
-use kinetix_language::ast::{Statement, Expression};
-use crate::types::Type;
+//! Symbol Resolution Pass — builds a scope-aware symbol table from the AST.
+
+use std::collections::{HashMap, HashSet};
+use kinetix_language::ast::{Statement, Expression, ExpressionKind};
+use kinetix_language::diagnostic::{codes, Diagnostic, Subject};
+use kinetix_language::span::Span;
+use crate::hir::{HirExprKind, HirExpression, HirProgram, HirStatement, HirStmtKind};
+use crate::types::{Substitution, Type};
 use crate::types::parse_type_hint;
 
 /// A single symbol entry in the table.
@@ -17,6 +15,9 @@ pub struct Symbol {
     pub ty: Type,
     pub mutable: bool,
     pub scope_depth: usize,
+    /// The `let`, `mut` or `computed` statement that declared it; `None` for
+    /// parameters, loop variables and builtins, which may be reassigned.
+    pub declared_at: Option<Span>,
 }
 
 /// A registry for struct and class definitions.
@@ -26,12 +27,29 @@ pub struct StructDef {
     pub parent: Option<String>,
     pub fields: std::collections::HashMap<String, Type>,
     pub methods: std::collections::HashMap<String, Type>,
+    /// Class fields declared without `pub`, with their lines, so visibility
+    /// errors can point at them. Struct fields are always public.
+    pub private_fields: HashMap<String, usize>,
+    /// Declaration line.
+    pub line: usize,
+    /// Methods without a leading `self` parameter, called as `ClassName.method()`.
+    pub static_methods: HashSet<String>,
+    /// Class-level constants (`let MAX = 10` in a class body), read as
+    /// `ClassName.MAX`; any other `ClassName.NAME` that isn't a method is an
+    /// error.
+    pub constants: HashMap<String, Type>,
+}
+
+/// True for a method declared without a leading `self` parameter -- an
+/// associated function called on the class itself (`Config.default()`).
+pub fn is_static_method(parameters: &[(String, String)]) -> bool {
+    parameters.first().is_none_or(|(name, _)| name != "self")
 }
 
 /// A registry for enum definitions: the ordered variant list (name + optional
 /// payload type), used by `exhaustiveness.rs` to check variant coverage and by
 /// `hir.rs` to tell a nullary-variant pattern (`None`) apart from a catch-all
-/// binding pattern (`x`) -- both parse as a bare `Expression::Identifier`.
+/// binding pattern (`x`) -- both parse as a bare `ExpressionKind::Identifier`.
 #[derive(Debug, Clone)]
 pub struct EnumDef {
     pub name: String,
@@ -46,6 +64,11 @@ pub struct SymbolTable {
     next_var: u32,
     pub custom_types: HashMap<String, StructDef>,
     pub enums: HashMap<String, EnumDef>,
+    /// Class whose methods are currently being resolved; private fields of
+    /// that class are accessible here and nowhere else.
+    class_context: Option<String>,
+    /// `import` aliases and the modules they name.
+    pub modules: HashMap<String, String>,
 }
 
 impl SymbolTable {
@@ -55,9 +78,17 @@ impl SymbolTable {
             next_var: 1,
             custom_types: HashMap::new(),
             enums: HashMap::new(),
+            class_context: None,
+            modules: HashMap::new(),
         }
     }
 
+    /// The module `alias` names, unless a local shadows the `import` alias.
+    pub fn module_of(&self, alias: &str) -> Option<&str> {
+        let module = self.modules.get(alias)?;
+        matches!(&self.resolve(alias)?.ty, Type::Custom { name, .. } if name == module).then_some(module.as_str())
+    }
+
     /// True if `name` is a known variant with no payload (e.g. `None`, or a
     /// user-declared nullary variant like `Red` in `enum Color { Red, ... }`).
     /// Used to classify a match arm's bare identifier as a variant pattern
@@ -89,12 +120,23 @@ impl SymbolTable {
 
     /// Define a symbol in the current scope.
     pub fn define(&mut self, name: &str, ty: Type, mutable: bool) {
+        self.define_binding(name, ty, mutable, None);
+    }
+
+    /// Define a declared variable; only these are held to `mutable` when
+    /// assigned.
+    pub fn define_at(&mut self, name: &str, ty: Type, mutable: bool, span: Span) {
+        self.define_binding(name, ty, mutable, Some(span));
+    }
+
+    fn define_binding(&mut self, name: &str, ty: Type, mutable: bool, declared_at: Option<Span>) {
         let depth = self.depth();
         let sym = Symbol {
             name: name.to_string(),
             ty,
             mutable,
             scope_depth: depth,
+            declared_at,
         };
         if let Some(scope) = self.scopes.last_mut() {
             scope.insert(name.to_string(), sym);
@@ -110,35 +152,85 @@ impl SymbolTable {
         }
         None
     }
+
+    /// The class or struct a bare `name` refers to, unless a variable or an
+    /// enum of that name is what it means.
+    fn type_named(&self, name: &str) -> Option<&StructDef> {
+        let global = self.resolve(name).is_some_and(|s| s.scope_depth == 0 && s.declared_at.is_none());
+        if !global || self.enums.contains_key(name) {
+            return None;
+        }
+        self.custom_types.get(name)
+    }
+
+    /// Whether `Class.member` names a constant or method of `def` or of a
+    /// class it extends.
+    fn has_type_member(&self, def: &StructDef, member: &str) -> bool {
+        std::iter::successors(Some(def), |d| d.parent.as_deref().and_then(|p| self.custom_types.get(p)))
+            .take(self.custom_types.len())
+            .any(|d| d.constants.contains_key(member) || d.methods.contains_key(member))
+    }
+
+    /// Returns an error if `field` is a private field of `class` and we are
+    /// not inside one of that class's methods.
+    fn check_field_access(&self, class: &str, field: &str, from: Option<&str>) -> Result<(), String> {
+        let Some(def) = self.custom_types.get(class) else { return Ok(()) };
+        let Some(line) = def.private_fields.get(field) else { return Ok(()) };
+        if from == Some(class) {
+            return Ok(());
+        }
+        Err(format!(
+            "Field '{}' of class '{}' is private (declared without `pub` at line {})",
+            field, class, line
+        ))
+    }
 }
 
+/// Builtin module namespaces (`db.connect(..)`, `crypto.hash(..)`), resolved
+/// as globals here and dispatched as module calls by the compiler.
+pub const BUILTIN_MODULES: &[&str] = &["math", "system", "data", "graph", "net", "crypto", "audio", "db", "io", "json", "sb", "random", "stats", "csv", "html", "template", "i18n", "decimal", "fs", "archive", "env", "ipc"];
+
 /// Walk the AST and populate a SymbolTable, returning errors for undeclared variables.
-pub fn resolve_program<'a>(statements: &[Statement<'a>]) -> Result<SymbolTable, Vec<String>> {
+///
+/// Registers every `let`, `fn`, `class` and `struct` definition in its scope,
+/// and enforces class field visibility: a field declared without `pub` may
+/// only be read, assigned, destructured or set in a literal from the class's
+/// own methods. `check_typed_field_access` repeats the check once types are known.
+pub fn resolve_program<'a>(statements: &[Statement<'a>]) -> Result<SymbolTable, Vec<Diagnostic>> {
+    resolve_program_with_externs(statements, &[])
+}
+
+/// Like `resolve_program`, with extra top-level functions supplied by linked
+/// bundles (`import bundle`) as `(name, arity)`. Bytecode carries no types, so
+/// every parameter and the result of an extern get a fresh type variable.
+pub fn resolve_program_with_externs<'a>(statements: &[Statement<'a>], externs: &[(String, usize)]) -> Result<SymbolTable, Vec<Diagnostic>> {
     let mut table = SymbolTable::new();
     let mut errors = Vec::new();
 
     // Register built-in modules in the global scope
-    let builtins = ["math", "system", "data", "graph", "net", "crypto", "audio"];
-    for b in builtins {
+    for b in BUILTIN_MODULES {
         table.define(b, Type::Custom { name: b.to_string(), args: vec![] }, false);
     }
     table.define("println", Type::Fn(vec![Type::Var(0)], Box::new(Type::Void)), false);
     table.define("print", Type::Fn(vec![Type::Var(0)], Box::new(Type::Void)), false);
 
-    // Global builtins from kivm::builtins::BUILTIN_NAMES (bare, non-dotted names only --
+    // Global builtins from kivm::builtins::BUILTINS (bare, non-dotted names only --
     // dotted names like "Math.abs"/"system.os.name" are dispatched via MemberAccess and
     // never resolved as identifiers, so they don't need a symbol table entry).
     // Signatures are intentionally permissive (Type::Var for anything dynamically-typed)
     // and match each builtin's primary call arity; a handful of builtins accept an
-    // optional trailing argument (input, assert, stop/exit, pad_left/pad_right's pad
+    // optional trailing argument (input, stop/exit, pad_left/pad_right's pad
     // char, min/max's 2-arg numeric form) which is not modeled here and will still fail
     // symbol/type resolution if used -- known boundary, see Gestione/roadmap.md.
     for (name, ty) in [
         ("input", Type::Fn(vec![Type::Str], Box::new(Type::Str))),
         ("len", Type::Fn(vec![Type::Var(0)], Box::new(Type::Int))),
         ("typeof", Type::Fn(vec![Type::Var(0)], Box::new(Type::Str))),
-        ("assert", Type::Fn(vec![Type::Bool], Box::new(Type::Void))),
+        // assert(cond, message?): a bare variable like inspect, so the message
+        // type-checks and an `assert` arm fits any `match`
+        ("assert", Type::Var(0)),
         ("str", Type::Fn(vec![Type::Var(0)], Box::new(Type::Str))),
+        ("format", Type::Fn(vec![Type::Var(0), Type::Int], Box::new(Type::Str))),
         ("int", Type::Fn(vec![Type::Var(0)], Box::new(Type::Int))),
         ("float", Type::Fn(vec![Type::Var(0)], Box::new(Type::Float))),
         ("bool", Type::Fn(vec![Type::Var(0)], Box::new(Type::Bool))),
@@ -147,6 +239,12 @@ pub fn resolve_program<'a>(statements: &[Statement<'a>]) -> Result<SymbolTable,
         ("stop", Type::Fn(vec![], Box::new(Type::Void))),
         ("exit", Type::Fn(vec![], Box::new(Type::Void))),
         ("copy", Type::Fn(vec![Type::Var(0)], Box::new(Type::Var(0)))),
+        ("is", Type::Fn(vec![Type::Var(0), Type::Var(1)], Box::new(Type::Bool))),
+        ("help", Type::Fn(vec![Type::Str], Box::new(Type::Void))),
+        ("to_string", Type::Fn(vec![Type::Var(0)], Box::new(Type::Str))),
+        ("fields", Type::Fn(vec![Type::Var(0)], Box::new(Type::Array(Box::new(Type::Str))))),
+        // inspect(value, depth?, width?): a bare variable, so every arity type-checks
+        ("inspect", Type::Var(0)),
 
         ("to_upper", Type::Fn(vec![Type::Str], Box::new(Type::Str))),
         ("to_lower", Type::Fn(vec![Type::Str], Box::new(Type::Str))),
@@ -205,51 +303,94 @@ pub fn resolve_program<'a>(statements: &[Statement<'a>]) -> Result<SymbolTable,
         variants: vec![("Ok".to_string(), Some(t.clone())), ("Err".to_string(), Some(e.clone()))],
     });
 
+    for (name, arity) in externs {
+        let params = (0..*arity).map(|_| table.fresh_var()).collect();
+        let ret = table.fresh_var();
+        table.define(name, Type::Fn(params, Box::new(ret)), false);
+    }
+
     // First pass: register all top-level function and type definitions
     for stmt in statements {
         match stmt {
-            Statement::Function { name, parameters, return_type, .. } => {
+            Statement::Import { module, alias, line, .. } => {
+                let alias = crate::module_graph::alias(module, alias.as_deref());
+                if table.resolve(alias).is_some() {
+                    errors.push(Diagnostic::error(codes::SYMBOL, format!("'{}' is already defined; import {} under another name with `as`", alias, module)).at_line(*line));
+                }
+                table.modules.insert(alias.to_string(), module.clone());
+                table.define(alias, Type::Custom { name: module.clone(), args: vec![] }, false);
+            }
+            Statement::Function { name, parameters, return_type, line, .. } => {
+                if externs.iter().any(|(e, _)| e == name) {
+                    errors.push(Diagnostic::error(codes::SYMBOL, format!("Function '{}' is already defined by an imported bundle", name)).at_line(*line));
+                }
                 let param_types: Vec<Type> = parameters.iter()
                     .map(|(_, ty)| parse_type_hint(ty))
                     .collect();
                 let ret = parse_type_hint(return_type);
                 table.define(name, Type::Fn(param_types, Box::new(ret)), false);
             }
-            Statement::Class { name, parent, fields, methods, .. } => {
+            Statement::Class { name, parent, fields, methods, constants, line, .. } => {
                 let mut field_map = std::collections::HashMap::new();
-                for (_, f_name, f_type) in fields {
+                let mut private_fields = HashMap::new();
+                for (is_public, f_name, f_type, f_line) in fields {
                     field_map.insert(f_name.clone(), parse_type_hint(f_type));
+                    if !is_public {
+                        private_fields.insert(f_name.clone(), *f_line);
+                    }
                 }
                 let mut method_map = std::collections::HashMap::new();
+                let mut static_methods = HashSet::new();
                 for m in methods {
                     if let Statement::Function { name: m_name, parameters, return_type, .. } = m {
                         let param_types: Vec<Type> = parameters.iter()
                             .map(|(_, ty)| parse_type_hint(ty))
                             .collect();
                         let ret = parse_type_hint(return_type);
-                        method_map.insert(m_name.clone(), Type::Fn(param_types, Box::new(ret)));
+                        method_map.insert(m_name.to_string(), Type::Fn(param_types, Box::new(ret)));
+                        if is_static_method(parameters) {
+                            static_methods.insert(m_name.to_string());
+                        }
+                    }
+                }
+                let mut constant_map = HashMap::new();
+                for c in constants {
+                    if let Statement::Let { name: c_name, type_hint, .. } = c {
+                        let ty = match type_hint {
+                            Some(hint) => parse_type_hint(hint),
+                            None => table.fresh_var(),
+                        };
+                        constant_map.insert(c_name.to_string(), ty);
                     }
                 }
-                table.custom_types.insert(name.clone(), StructDef {
-                    name: name.clone(),
-                    parent: parent.clone(),
+                table.custom_types.insert(name.to_string(), StructDef {
+                    name: name.to_string(),
+                    parent: parent.map(str::to_string),
                     fields: field_map,
                     methods: method_map,
+                    private_fields,
+                    line: *line,
+                    static_methods,
+                    constants: constant_map,
                 });
-                table.define(name, Type::Custom { name: name.clone(), args: vec![] }, false);
+                table.define(name, Type::Custom { name: name.to_string(), args: vec![] }, false);
             }
-            Statement::Struct { name, fields, .. } => {
+            Statement::Struct { name, fields, line, .. } => {
                 let mut field_map = std::collections::HashMap::new();
                 for (f_name, f_type) in fields {
                     field_map.insert(f_name.clone(), parse_type_hint(f_type));
                 }
-                table.custom_types.insert(name.clone(), StructDef {
-                    name: name.clone(),
+                table.custom_types.insert(name.to_string(), StructDef {
+                    name: name.to_string(),
                     parent: None,
                     fields: field_map,
                     methods: std::collections::HashMap::new(),
+                    private_fields: HashMap::new(),
+                    line: *line,
+                    static_methods: HashSet::new(),
+                    constants: HashMap::new(),
                 });
-                table.define(name, Type::Custom { name: name.clone(), args: vec![] }, false);
+                table.define(name, Type::Custom { name: name.to_string(), args: vec![] }, false);
             }
             Statement::Enum { name, generics, variants, .. } => {
                 // Ordered (name, fresh Type::Var) pairs -- a Vec, not a HashMap,
@@ -258,7 +399,7 @@ pub fn resolve_program<'a>(statements: &[Statement<'a>]) -> Result<SymbolTable,
                     .map(|g| (g.clone(), table.fresh_var()))
                     .collect();
                 let enum_ty = Type::Custom {
-                    name: name.clone(),
+                    name: name.to_string(),
                     args: generic_vars.iter().map(|(_, t)| t.clone()).collect(),
                 };
 
@@ -275,7 +416,7 @@ pub fn resolve_program<'a>(statements: &[Statement<'a>]) -> Result<SymbolTable,
                     }
                     variant_defs.push((vname.clone(), payload_ty));
                 }
-                table.enums.insert(name.clone(), EnumDef { name: name.clone(), variants: variant_defs });
+                table.enums.insert(name.to_string(), EnumDef { name: name.to_string(), variants: variant_defs });
                 table.define(name, enum_ty, false);
             }
             _ => {}
@@ -290,25 +431,36 @@ pub fn resolve_program<'a>(statements: &[Statement<'a>]) -> Result<SymbolTable,
     // an `impl` targeting an enum (which never gets a `custom_types` entry of
     // its own) defensively creates one so method-call resolution still finds it.
     for stmt in statements {
-        if let Statement::Impl { target_name, methods, .. } = stmt {
+        if let Statement::Impl { target_name, methods, line, .. } = stmt {
             let mut method_map = std::collections::HashMap::new();
+            let mut static_methods = HashSet::new();
             for m in methods {
                 if let Statement::Function { name: m_name, parameters, return_type, .. } = m {
                     let param_types: Vec<Type> = parameters.iter()
                         .map(|(_, ty)| parse_type_hint(ty))
                         .collect();
                     let ret = parse_type_hint(return_type);
-                    method_map.insert(m_name.clone(), Type::Fn(param_types, Box::new(ret)));
+                    method_map.insert(m_name.to_string(), Type::Fn(param_types, Box::new(ret)));
+                    if is_static_method(parameters) {
+                        static_methods.insert(m_name.to_string());
+                    }
                 }
             }
-            match table.custom_types.get_mut(target_name) {
-                Some(def) => def.methods.extend(method_map),
+            match table.custom_types.get_mut(*target_name) {
+                Some(def) => {
+                    def.methods.extend(method_map);
+                    def.static_methods.extend(static_methods);
+                }
                 None => {
-                    table.custom_types.insert(target_name.clone(), StructDef {
-                        name: target_name.clone(),
+                    table.custom_types.insert(target_name.to_string(), StructDef {
+                        name: target_name.to_string(),
                         parent: None,
                         fields: std::collections::HashMap::new(),
                         methods: method_map,
+                        private_fields: HashMap::new(),
+                        line: *line,
+                        static_methods,
+                        constants: HashMap::new(),
                     });
                 }
             }
@@ -327,7 +479,7 @@ pub fn resolve_program<'a>(statements: &[Statement<'a>]) -> Result<SymbolTable,
     }
 }
 
-fn resolve_statement<'a>(stmt: &Statement<'a>, table: &mut SymbolTable, errors: &mut Vec<String>) {
+fn resolve_statement<'a>(stmt: &Statement<'a>, table: &mut SymbolTable, errors: &mut Vec<Diagnostic>) {
     let line = match stmt {
         Statement::Let { line, .. } => *line,
         Statement::Return { line, .. } => *line,
@@ -343,21 +495,28 @@ fn resolve_statement<'a>(stmt: &Statement<'a>, table: &mut SymbolTable, errors:
         Statement::Impl { line, .. } => *line,
         Statement::Include { line, .. } => *line,
         Statement::Version { line, .. } => *line,
-        Statement::Break { line } => *line,
-        Statement::Continue { line } => *line,
+        Statement::ImportBundle { line, .. } => *line,
+        Statement::Import { line, .. } => *line,
+        Statement::Break { line, .. } => *line,
+        Statement::Continue { line, .. } => *line,
         Statement::State { line, .. } => *line,
         Statement::Computed { line, .. } => *line,
         Statement::Effect { line, .. } => *line,
     };
 
     match stmt {
-        Statement::Let { name, value, mutable, type_hint, .. } => {
+        Statement::Let { name, value, mutable, type_hint, span, .. } => {
             resolve_expression(value, table, errors, line);
-            let ty = match type_hint {
-                Some(hint) => parse_type_hint(hint),
-                None => table.fresh_var(), // unique inference variable
+            let ty = match (type_hint, value) {
+                (Some(hint), _) => parse_type_hint(hint),
+                // `let a = Account { .. }` -- remember the class so later
+                // `a.field` accesses can be visibility-checked.
+                (None, Expression { kind: ExpressionKind::StructLiteral { name: class, .. }, .. }) if table.custom_types.contains_key(*class) => {
+                    Type::Custom { name: class.to_string(), args: vec![] }
+                }
+                (None, _) => table.fresh_var(), // unique inference variable
             };
-            table.define(name, ty, *mutable);
+            table.define_at(name, ty, *mutable, Span { line, ..*span });
         }
         Statement::Effect { body, .. } => {
             resolve_statement(body, table, errors);
@@ -397,10 +556,17 @@ fn resolve_statement<'a>(stmt: &Statement<'a>, table: &mut SymbolTable, errors:
             resolve_statement(body, table, errors);
             table.exit_scope();
         }
-        Statement::Class { methods, .. } => {
+        Statement::Class { name, methods, constants, .. } => {
+            for c in constants {
+                if let Statement::Let { value, .. } = c {
+                    resolve_expression(value, table, errors, line);
+                }
+            }
+            let outer = table.class_context.replace(name.to_string());
             for m in methods {
                 resolve_statement(m, table, errors);
             }
+            table.class_context = outer;
         }
         Statement::State { name, value, type_hint, .. } => {
             resolve_expression(value, table, errors, line);
@@ -410,54 +576,58 @@ fn resolve_statement<'a>(stmt: &Statement<'a>, table: &mut SymbolTable, errors:
             };
             table.define(name, ty, true); // state vars are implicitly mutable
         }
-        Statement::Computed { name, value, type_hint, .. } => {
+        Statement::Computed { name, value, type_hint, span, .. } => {
             resolve_expression(value, table, errors, line);
             let ty = match type_hint {
                 Some(hint) => parse_type_hint(hint),
                 None => table.fresh_var(),
             };
-            table.define(name, ty, false); // computed vars are immutable
+            table.define_at(name, ty, false, Span { line, ..*span }); // computed vars are immutable
         }
         _ => {} // Include, Version, Break, Continue, Struct — no refs to resolve
     }
 }
 
-fn resolve_expression<'a>(expr: &Expression<'a>, table: &mut SymbolTable, errors: &mut Vec<String>, line: usize) {
-    match expr {
-        Expression::Identifier(name) => {
+fn resolve_expression<'a>(expr: &Expression<'a>, table: &mut SymbolTable, errors: &mut Vec<Diagnostic>, line: usize) {
+    match &expr.kind {
+        ExpressionKind::Identifier(name) => {
             if table.resolve(name).is_none() {
-                errors.push(format!("Line {}: Undeclared variable: '{}'", line, name));
+                let error = error_at(expr, line, format!("Undeclared variable: '{}'", name));
+                errors.push(error.about(Subject::Undeclared(name.to_string())));
             }
         }
-        Expression::Prefix { right, .. } => {
+        ExpressionKind::Prefix { right, .. } => {
             resolve_expression(right, table, errors, line);
         }
-        Expression::Try { value } => {
+        ExpressionKind::Try { value } => {
             resolve_expression(value, table, errors, line);
         }
-        Expression::Infix { left, right, .. } => {
+        ExpressionKind::Infix { left, right, .. } => {
             resolve_expression(left, table, errors, line);
             resolve_expression(right, table, errors, line);
         }
-        Expression::If { condition, consequence, alternative } => {
+        ExpressionKind::If { condition, consequence, alternative } => {
             resolve_expression(condition, table, errors, line);
             resolve_statement(consequence, table, errors);
             if let Some(alt) = alternative {
                 resolve_statement(alt, table, errors);
             }
         }
-        Expression::Call { function, arguments } => {
+        ExpressionKind::Call { function, arguments } => {
             resolve_expression(function, table, errors, line);
             for arg in arguments {
                 resolve_expression(arg, table, errors, line);
             }
         }
-        Expression::StructLiteral { fields, .. } => {
-            for (_, field_expr) in fields {
+        ExpressionKind::StructLiteral { name, fields } => {
+            for (field, field_expr) in fields {
                 resolve_expression(field_expr, table, errors, line);
+                if let Err(e) = table.check_field_access(name, field, table.class_context.as_deref()) {
+                    errors.push(error_at(expr, line, e));
+                }
             }
         }
-        Expression::FunctionLiteral { parameters, body, .. } => {
+        ExpressionKind::FunctionLiteral { parameters, body, .. } => {
             table.enter_scope();
             for (pname, ptype) in parameters {
                 table.define(pname, parse_type_hint(ptype), false);
@@ -465,60 +635,210 @@ fn resolve_expression<'a>(expr: &Expression<'a>, table: &mut SymbolTable, errors
             resolve_statement(body, table, errors);
             table.exit_scope();
         }
-        Expression::ArrayLiteral(elems) => {
+        ExpressionKind::ArrayLiteral(elems) => {
             for e in elems { resolve_expression(e, table, errors, line); }
         }
-        Expression::MapLiteral(pairs) => {
+        ExpressionKind::MapLiteral(pairs) => {
             for (k, v) in pairs {
                 resolve_expression(k, table, errors, line);
                 resolve_expression(v, table, errors, line);
             }
         }
-        Expression::Index { left, index } => {
+        ExpressionKind::Index { left, index } => {
             resolve_expression(left, table, errors, line);
             resolve_expression(index, table, errors, line);
         }
-        Expression::MemberAccess { object, .. } => {
+        ExpressionKind::MemberAccess { object, member } => {
             resolve_expression(object, table, errors, line);
+            if let ExpressionKind::Identifier(alias) = &object.kind {
+                let missing = table.module_of(alias)
+                    .filter(|module| table.resolve(&crate::link::qualify(module, member)).is_none());
+                if let Some(module) = missing {
+                    let error = error_at(expr, line, format!("Module '{}' has no public function '{}'", module, member));
+                    errors.push(error.about(Subject::ModuleMember { module: module.to_string(), member: member.to_string() }));
+                }
+            }
+            if let ExpressionKind::Identifier(name) = &object.kind
+                && let Some(def) = table.type_named(name)
+                && !table.has_type_member(def, member)
+            {
+                errors.push(error_at(expr, line, format!("'{}' has no constant or method '{}'", name, member)));
+            }
+            let access = known_class(object, table).map(|class| table.check_field_access(&class, member, table.class_context.as_deref()));
+            if let Some(Err(e)) = access {
+                errors.push(error_at(expr, line, e));
+            }
         }
-        Expression::Assign { target, value } => {
+        ExpressionKind::Assign { target, value } => {
             resolve_expression(target, table, errors, line);
             resolve_expression(value, table, errors, line);
+            let immutable = match &target.kind {
+                ExpressionKind::Identifier(name) => table.resolve(name).filter(|sym| !sym.mutable),
+                _ => None,
+            };
+            if let Some(Symbol { name, declared_at: Some(declared), .. }) = immutable {
+                let at = if declared.column > 0 { format!("{}:{}", declared.line, declared.column) } else { declared.line.to_string() };
+                errors.push(error_at(target, line, format!(
+                    "Cannot assign twice to immutable variable '{}' (declared on line {}; use `mut {}` to allow reassignment)",
+                    name, declared.line, name
+                )).at(*declared).with_note(format!("'{}' is declared at line {}", name, at)));
+            }
         }
-        Expression::Match { value, arms } => {
+        ExpressionKind::Match { value, arms, by_type, .. } => {
             resolve_expression(value, table, errors, line);
             for (pattern, body) in arms {
                 // A binding pattern (`x`) or a variant payload binding
                 // (`Circle(r)`) introduces a new name scoped to this arm's
                 // body -- define it before resolving the body, or it would
-                // (incorrectly) fail as an undeclared variable. Wildcards and
-                // literal patterns introduce nothing.
+                // (incorrectly) fail as an undeclared variable. Nested
+                // payloads and struct fields can bind several names at once;
+                // wildcards, literals and ranges introduce nothing.
                 table.enter_scope();
-                match crate::pattern::classify_pattern(pattern, |n| table.is_nullary_variant(n)) {
-                    crate::pattern::ArmPattern::Binding(name) => {
-                        let fv = table.fresh_var();
-                        table.define(&name, fv, false);
-                    }
-                    crate::pattern::ArmPattern::Variant { binding: Some(bname), .. } => {
-                        let fv = table.fresh_var();
-                        table.define(&bname, fv, false);
-                    }
-                    crate::pattern::ArmPattern::Literal(lit) => {
-                        resolve_expression(lit, table, errors, line);
-                    }
-                    _ => {}
+                let classified = crate::pattern::classify_arm(pattern, *by_type, |n| table.is_nullary_variant(n));
+                resolve_pattern(&classified, table, errors, line);
+                for bname in classified.bindings() {
+                    let fv = table.fresh_var();
+                    table.define(&bname, fv, false);
                 }
                 resolve_statement(body, table, errors);
                 table.exit_scope();
             }
         }
-        Expression::Range { start, end } => {
+        ExpressionKind::Range { start, end } => {
             resolve_expression(start, table, errors, line);
             resolve_expression(end, table, errors, line);
         }
         // Literals: no resolution needed
-        Expression::Integer(_) | Expression::Float(_) | Expression::String(_)
-        | Expression::Boolean(_) | Expression::Null => {}
+        ExpressionKind::Integer(_) | ExpressionKind::Float(_) | ExpressionKind::String(_)
+        | ExpressionKind::Boolean(_) | ExpressionKind::Null => {}
+    }
+}
+
+/// Resolves the value expressions embedded in a match pattern (literals and
+/// range bounds). Bindings are defined by the caller, once per arm.
+fn resolve_pattern(pattern: &crate::pattern::ArmPattern<'_>, table: &mut SymbolTable, errors: &mut Vec<Diagnostic>, line: usize) {
+    use crate::pattern::ArmPattern;
+    match pattern {
+        ArmPattern::Literal(lit) => resolve_expression(lit, table, errors, line),
+        ArmPattern::Range { start, end } => {
+            resolve_expression(start, table, errors, line);
+            resolve_expression(end, table, errors, line);
+        }
+        ArmPattern::Variant { payload: Some(p), .. } => resolve_pattern(p, table, errors, line),
+        ArmPattern::Struct { name, fields } => {
+            for (field, p) in fields {
+                if let Err(e) = table.check_field_access(name, field, table.class_context.as_deref()) {
+                    errors.push(Diagnostic::error(codes::SYMBOL, e).at_line(line));
+                }
+                resolve_pattern(p, table, errors, line);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// An error about `expr`, at its span; at `line` for a node the parser
+/// didn't make.
+fn error_at(expr: &Expression<'_>, line: usize, message: String) -> Diagnostic {
+    let error = Diagnostic::error(codes::SYMBOL, message);
+    if expr.span.line > 0 { error.at(expr.span) } else { error.at_line(line) }
+}
+
+/// The class an expression statically evaluates to, when that is known
+/// before type inference: a class literal, or a name declared (or
+/// annotated) with a class type.
+fn known_class(expr: &Expression<'_>, table: &SymbolTable) -> Option<String> {
+    match &expr.kind {
+        ExpressionKind::StructLiteral { name, .. } => Some(name.to_string()),
+        ExpressionKind::Identifier(name) => match table.resolve(name).map(|s| &s.ty) {
+            Some(Type::Custom { name: class, .. }) if table.custom_types.contains_key(class) => Some(class.clone()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Field visibility again, once the type checker has typed every receiver:
+/// catches the reads symbol resolution couldn't place in a class, like
+/// `open().balance` or `a.balance` on an unannotated `let a = open()`.
+pub fn check_typed_field_access(hir: &HirProgram, symbols: &SymbolTable, sub: &Substitution) -> Result<(), Vec<Diagnostic>> {
+    let mut checker = TypedAccess { symbols, sub, class: None, errors: Vec::new() };
+    for stmt in &hir.statements {
+        checker.check_statement(stmt);
+    }
+    if checker.errors.is_empty() { Ok(()) } else { Err(checker.errors) }
+}
+
+struct TypedAccess<'a> {
+    symbols: &'a SymbolTable,
+    sub: &'a Substitution,
+    /// The class whose methods are being checked.
+    class: Option<&'a str>,
+    errors: Vec<Diagnostic>,
+}
+
+impl<'a> TypedAccess<'a> {
+    fn check_statement(&mut self, stmt: &'a HirStatement) {
+        let line = stmt.line;
+        match &stmt.kind {
+            HirStmtKind::Let { value, .. } | HirStmtKind::Return { value: Some(value) } | HirStmtKind::State { value, .. }
+            | HirStmtKind::Computed { value, .. } | HirStmtKind::Expression { expression: value } => self.check_expression(value, line),
+            HirStmtKind::Effect { body, .. } | HirStmtKind::Function { body, .. } => self.check_statement(body),
+            HirStmtKind::Block { statements } => statements.iter().for_each(|s| self.check_statement(s)),
+            HirStmtKind::Class { name, methods } => {
+                let outer = self.class.replace(name);
+                methods.iter().for_each(|m| self.check_statement(m));
+                self.class = outer;
+            }
+            HirStmtKind::While { condition: value, body, .. } | HirStmtKind::For { range: value, body, .. } => {
+                self.check_expression(value, line);
+                self.check_statement(body);
+            }
+            HirStmtKind::Return { value: None } | HirStmtKind::Break { .. } | HirStmtKind::Continue { .. } => {}
+        }
+    }
+
+    fn check_expression(&mut self, expr: &'a HirExpression, line: usize) {
+        match &expr.kind {
+            HirExprKind::MemberAccess { object, member } => {
+                self.check_expression(object, line);
+                if let Type::Custom { name, .. } = self.sub.apply(&object.ty)
+                    && let Err(e) = self.symbols.check_field_access(&name, member, self.class)
+                {
+                    self.errors.push(Diagnostic::error(codes::SYMBOL, e).at_line(line));
+                }
+            }
+            HirExprKind::If { condition, consequence, alternative } => {
+                self.check_expression(condition, line);
+                self.check_statement(consequence);
+                if let Some(alt) = alternative {
+                    self.check_statement(alt);
+                }
+            }
+            HirExprKind::Match { value, arms, .. } => {
+                self.check_expression(value, line);
+                arms.iter().for_each(|(_, body)| self.check_statement(body));
+            }
+            HirExprKind::Prefix { right, .. } => self.check_expression(right, line),
+            HirExprKind::Infix { left, right, .. } | HirExprKind::Index { left, index: right }
+            | HirExprKind::Assign { target: left, value: right } | HirExprKind::Range { start: left, end: right } => {
+                self.check_expression(left, line);
+                self.check_expression(right, line);
+            }
+            HirExprKind::Call { function: object, arguments } | HirExprKind::MethodCall { object, arguments, .. } => {
+                self.check_expression(object, line);
+                arguments.iter().for_each(|arg| self.check_expression(arg, line));
+            }
+            HirExprKind::FunctionLiteral { body, .. } => self.check_statement(body),
+            HirExprKind::ArrayLiteral(elems) => elems.iter().for_each(|e| self.check_expression(e, line)),
+            HirExprKind::StructLiteral(_, fields) => fields.iter().for_each(|(_, e)| self.check_expression(e, line)),
+            HirExprKind::MapLiteral(pairs) => pairs.iter().for_each(|(k, v)| {
+                self.check_expression(k, line);
+                self.check_expression(v, line);
+            }),
+            HirExprKind::Identifier(_) | HirExprKind::Integer(_) | HirExprKind::Float(_) | HirExprKind::String(_)
+            | HirExprKind::Boolean(_) | HirExprKind::Null => {}
+        }
     }
 }
 
@@ -529,7 +849,7 @@ mod tests {
     use kinetix_language::lexer::Lexer;
     use kinetix_language::parser::Parser;
 
-    fn parse_and_resolve(src: &str) -> Result<SymbolTable, Vec<String>> {
+    fn parse_and_resolve(src: &str) -> Result<SymbolTable, Vec<Diagnostic>> {
         let arena = Bump::new();
         let lexer = Lexer::new(src);
         let mut parser = Parser::new(lexer, &arena);
@@ -549,7 +869,10 @@ mod tests {
         let result = parse_and_resolve("let x = y + 1");
         assert!(result.is_err());
         let errors = result.unwrap_err();
-        assert!(errors[0].contains("Undeclared variable: 'y'"));
+        assert!(errors[0].message.contains("Undeclared variable: 'y'"));
+        assert_eq!(errors[0].code, codes::SYMBOL);
+        assert_eq!(errors[0].span().map(|span| (span.line, span.column, span.end - span.start)), Some((1, 9, 1)));
+        assert_eq!(errors[0].subject, Some(Subject::Undeclared("y".to_string())));
     }
 
     #[test]
@@ -559,4 +882,67 @@ mod tests {
         );
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_assignment_to_immutable_let() {
+        let errors = parse_and_resolve("let total = 0\nfor i in 0..3 {\n  total = total + i\n}").unwrap_err();
+        let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+        assert_eq!(errors, vec!["Line 3:3: Cannot assign twice to immutable variable 'total' (declared on line 1; use `mut total` to allow reassignment)".to_string()]);
+        let errors = parse_and_resolve("\n  let total = 0\ntotal = 1").unwrap_err();
+        assert_eq!(errors[0].spans.iter().map(|s| (s.line, s.column)).collect::<Vec<_>>(), [(3, 1), (2, 3)]);
+        assert_eq!(errors[0].notes, ["'total' is declared at line 2:3"]);
+        assert!(parse_and_resolve("mut total = 0\ntotal = total + 1").is_ok());
+        assert!(parse_and_resolve("fn f(n: int) -> int {\n  n = n - 1\n  return n\n}").is_ok(), "parameters stay assignable");
+        assert!(parse_and_resolve("let x = 1\nlet x = 2").is_ok(), "shadowing is not assignment");
+    }
+
+    const ACCOUNT: &str = "class Account {\n  pub owner: str\n  balance: int\n  fn show(self) { print(self.balance) }\n  fn open(owner: str) -> Account { return Account { owner: owner, balance: 0 } }\n}\n";
+
+    #[test]
+    fn test_private_field_access() {
+        assert!(parse_and_resolve(&format!("{}let a = Account.open(\"x\")\nprint(a.owner)", ACCOUNT)).is_ok());
+
+        let errors = parse_and_resolve(&format!("{}let a: Account = Account.open(\"x\")\nprint(a.balance)", ACCOUNT)).unwrap_err();
+        assert!(errors[0].message.contains("Field 'balance' of class 'Account' is private"), "{:?}", errors);
+        assert!(errors[0].message.contains("at line 3"), "diagnostic should point at the field: {:?}", errors);
+
+        let errors = parse_and_resolve(&format!("{}fn peek(a: Account) -> int {{ return a.balance }}", ACCOUNT)).unwrap_err();
+        assert!(errors[0].message.contains("private"), "{:?}", errors);
+
+        let errors = parse_and_resolve(&format!("{}let a = Account {{ owner: \"x\", balance: 1 }}", ACCOUNT)).unwrap_err();
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert!(errors[0].message.contains("Field 'balance' of class 'Account' is private"), "{:?}", errors);
+    }
+
+    #[test]
+    fn test_class_constants() {
+        let class = "class Config {\n  let MAX: int = 10\n  fn default() -> int { return Config.MAX }\n}\n";
+        assert!(parse_and_resolve(&format!("{}print(Config.MAX + Config.default())", class)).is_ok());
+
+        let errors = parse_and_resolve(&format!("{}print(Config.MAXX)", class)).unwrap_err();
+        let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+        assert_eq!(errors, ["Line 5:7: 'Config' has no constant or method 'MAXX'"]);
+        assert!(parse_and_resolve(&format!("{}fn f(Config: int) -> int {{ return Config.MAXX }}", class)).is_ok(), "a parameter shadows the class");
+    }
+
+    #[test]
+    fn test_private_field_access_on_typed_receivers() {
+        let check = |src: &str| {
+            let arena = Bump::new();
+            let mut parser = Parser::new(Lexer::new(src), &arena);
+            let program = parser.parse_program();
+            assert!(parser.errors.is_empty(), "Parser errors: {:?}", parser.errors);
+            let symbols = resolve_program(&program.statements).expect("resolves before types are known");
+            let hir = crate::hir::lower_to_hir(&program.statements, &symbols, &crate::trait_solver::TraitEnvironment::new());
+            let mut ctx = crate::typeck::TypeContext::new();
+            let constraints = ctx.collect_constraints(&hir);
+            ctx.solve(&constraints).expect("type checks");
+            check_typed_field_access(&hir, &symbols, &ctx.substitution)
+        };
+        assert!(check(&format!("{}let a = Account.open(\"x\")\nprint(a.owner)", ACCOUNT)).is_ok());
+
+        let errors = check(&format!("{}let a = Account.open(\"x\")\nprint(a.balance)", ACCOUNT)).unwrap_err();
+        let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+        assert_eq!(errors, ["Line 8: Field 'balance' of class 'Account' is private (declared without `pub` at line 3)"]);
+    }
 }
@@ -44,6 +44,21 @@ impl TraitEnvironment {
     }
 
     pub fn register_trait(&mut self, stmt: &Statement) -> Result<(), String> {
+        if let Some(def) = Self::trait_def(stmt) {
+            self.add_trait(def);
+        }
+        Ok(())
+    }
+
+    pub fn register_impl(&mut self, stmt: &Statement) -> Result<(), String> {
+        match Self::impl_def(stmt) {
+            Some(def) => self.add_impl(def),
+            None => Ok(()),
+        }
+    }
+
+    /// The definition a `trait` statement declares, without registering it.
+    pub fn trait_def(stmt: &Statement) -> Option<TraitDef> {
         if let Statement::Trait { name, generics, methods, .. } = stmt {
             let mut trait_methods = Vec::new();
             for (m_name, m_params, m_ret) in methods {
@@ -55,58 +70,69 @@ impl TraitEnvironment {
                     return_ty: ret,
                 });
             }
-            let def = TraitDef {
-                name: name.clone(),
+            return Some(TraitDef {
+                name: name.to_string(),
                 generics: generics.clone(),
                 methods: trait_methods,
-            };
-            self.traits.insert(name.clone(), def);
+            });
         }
-        Ok(())
+        None
     }
 
-    pub fn register_impl(&mut self, stmt: &Statement) -> Result<(), String> {
+    pub fn add_trait(&mut self, def: TraitDef) {
+        self.traits.insert(def.name.clone(), def);
+    }
+
+    /// The block an `impl` statement declares, without registering it.
+    pub fn impl_def(stmt: &Statement) -> Option<ImplDef> {
         if let Statement::Impl { target_name, generics, trait_name, methods, .. } = stmt {
             let mut impl_methods = HashMap::new();
             for m in methods {
                 if let Statement::Function { name: m_name, parameters, return_type, .. } = m {
                     let params: Vec<Type> = parameters.iter().map(|(_, t)| parse_type_hint(t)).collect();
                     let ret = parse_type_hint(return_type);
-                    impl_methods.insert(m_name.clone(), TraitMethod {
-                        name: m_name.clone(),
+                    impl_methods.insert(m_name.to_string(), TraitMethod {
+                        name: m_name.to_string(),
                         params,
                         return_ty: ret,
                     });
                 }
             }
 
-            let def = ImplDef {
-                target_name: target_name.clone(),
+            return Some(ImplDef {
+                target_name: target_name.to_string(),
                 generics: generics.clone(),
-                trait_name: trait_name.clone(),
+                trait_name: trait_name.map(str::to_string),
                 methods: impl_methods,
-            };
-
-            // Orphan rules / Coherence check
-            if let Some(existing_impls) = self.impls.get(target_name) {
-                for existing in existing_impls {
-                    if existing.trait_name == *trait_name {
-                        if let Some(t_name) = trait_name {
-                            return Err(format!("Overlapping implementations of trait '{}' for type '{}'", t_name, target_name));
-                        } else {
-                            // Multiple inherent impls are allowed, but method names cannot overlap
-                            for new_method in def.methods.keys() {
-                                if existing.methods.contains_key(new_method) {
-                                    return Err(format!("Duplicate definition of inherent method '{}' for type '{}'", new_method, target_name));
-                                }
+            });
+        }
+        None
+    }
+
+    /// Registers an impl block after checking it against the ones already
+    /// registered for the same type.
+    pub fn add_impl(&mut self, def: ImplDef) -> Result<(), String> {
+        let target_name = &def.target_name;
+        let trait_name = &def.trait_name;
+        // Orphan rules / Coherence check
+        if let Some(existing_impls) = self.impls.get(target_name) {
+            for existing in existing_impls {
+                if existing.trait_name == *trait_name {
+                    if let Some(t_name) = trait_name {
+                        return Err(format!("Overlapping implementations of trait '{}' for type '{}'", t_name, target_name));
+                    } else {
+                        // Multiple inherent impls are allowed, but method names cannot overlap
+                        for new_method in def.methods.keys() {
+                            if existing.methods.contains_key(new_method) {
+                                return Err(format!("Duplicate definition of inherent method '{}' for type '{}'", new_method, target_name));
                             }
                         }
                     }
                 }
             }
-
-            self.impls.entry(target_name.clone()).or_insert_with(Vec::new).push(def);
         }
+
+        self.impls.entry(target_name.clone()).or_default().push(def);
         Ok(())
     }
 
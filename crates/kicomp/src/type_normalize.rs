@@ -8,6 +8,7 @@
 use crate::hir::{HirProgram, HirStatement, HirStmtKind, HirExpression, HirExprKind, HirPattern};
 use crate::types::Type;
 use crate::symbol::SymbolTable;
+use kinetix_language::span::Span;
 
 /// Normalizes the entire HIR program.
 pub fn normalize(program: &mut HirProgram, symbols: &SymbolTable) -> Result<(), String> {
@@ -38,7 +39,7 @@ fn normalize_stmt(stmt: &mut HirStatement, symbols: &SymbolTable) -> Result<(),
                 normalize_stmt(s, symbols)?;
             }
         }
-        HirStmtKind::While { condition, body } => {
+        HirStmtKind::While { condition, body, .. } => {
             normalize_expr(condition, symbols)?;
             normalize_stmt(body, symbols)?;
         }
@@ -53,7 +54,7 @@ fn normalize_stmt(stmt: &mut HirStatement, symbols: &SymbolTable) -> Result<(),
             *return_type = normalize_type(return_type)?;
             normalize_stmt(body, symbols)?;
         }
-        HirStmtKind::Break | HirStmtKind::Continue => {}
+        HirStmtKind::Break { .. } | HirStmtKind::Continue { .. } => {}
         HirStmtKind::State { value, .. } | HirStmtKind::Computed { value, .. } => {
             normalize_expr(value, symbols)?;
         }
@@ -119,7 +120,7 @@ fn normalize_expr(expr: &mut HirExpression, symbols: &SymbolTable) -> Result<(),
                 normalize_stmt(else_b, symbols)?;
             }
         }
-        HirExprKind::Match { value, arms } => {
+        HirExprKind::Match { value, arms, .. } => {
             normalize_expr(value, symbols)?;
             for (pat, body) in arms {
                 normalize_pattern(pat, symbols)?;
@@ -188,7 +189,19 @@ fn normalize_pattern(pat: &mut HirPattern, symbols: &SymbolTable) -> Result<(),
             normalize_expr(expr, symbols)?;
             Ok(())
         }
-        HirPattern::Wildcard | HirPattern::Binding(_) | HirPattern::Variant { .. } => Ok(()),
+        HirPattern::Range { start, end } => {
+            normalize_expr(start, symbols)?;
+            normalize_expr(end, symbols)?;
+            Ok(())
+        }
+        HirPattern::Variant { payload: Some(p), .. } => normalize_pattern(p, symbols),
+        HirPattern::Struct { fields, .. } => {
+            for (_, p) in fields {
+                normalize_pattern(p, symbols)?;
+            }
+            Ok(())
+        }
+        HirPattern::Wildcard | HirPattern::Binding(_) | HirPattern::Type { .. } | HirPattern::Variant { payload: None, .. } => Ok(()),
     }
 }
 
@@ -259,7 +272,11 @@ fn resolve_stmt(stmt: &mut HirStatement, symbols: &SymbolTable, sub: &Substituti
             for m in methods { resolve_stmt(m, symbols, sub)?; }
         }
         HirStmtKind::Effect { body, .. } => resolve_stmt(body, symbols, sub)?,
-        _ => {} // While, For, If, Break, Continue handled via expressions
+        HirStmtKind::While { condition: value, body, .. } | HirStmtKind::For { range: value, body, .. } => {
+            resolve_expr(value, symbols, sub)?;
+            resolve_stmt(body, symbols, sub)?;
+        }
+        _ => {} // If, Break, Continue handled via expressions
     }
     Ok(())
 }
@@ -285,6 +302,15 @@ fn resolve_expr(expr: &mut HirExpression, symbols: &SymbolTable, sub: &Substitut
             };
 
             if let Some(class_name) = class_name {
+                if let HirExprKind::MethodCall { method, .. } = &expr.kind {
+                    let user_defined = symbols.custom_types.get(&class_name)
+                        .is_some_and(|def| def.methods.contains_key(method));
+                    if !user_defined && is_derived_helper(&class_name, method, symbols) {
+                        rewrite_derived_helper(expr);
+                        return Ok(());
+                    }
+                }
+
                 if let Some(struct_def) = symbols.custom_types.get(&class_name) {
                     // Extract method name before swapping
                     let method_name = if let HirExprKind::MethodCall { method, .. } = &expr.kind {
@@ -317,6 +343,7 @@ fn resolve_expr(expr: &mut HirExpression, symbols: &SymbolTable, sub: &Substitut
                                         right: Box::new(*extracted_obj),
                                     },
                                     ty: self_type,
+                                    span: Span::default(),
                                 });
                             } else {
                                 // Consuming method: passes `self` by value (Move)
@@ -329,6 +356,7 @@ fn resolve_expr(expr: &mut HirExpression, symbols: &SymbolTable, sub: &Substitut
                                 function: Box::new(crate::hir::HirExpression {
                                     kind: HirExprKind::Identifier(fully_qualified_name),
                                     ty: Type::Var(0),
+                                    span: Span::default(),
                                 }),
                                 arguments: new_args,
                             };
@@ -372,6 +400,7 @@ fn resolve_expr(expr: &mut HirExpression, symbols: &SymbolTable, sub: &Substitut
                                 function: Box::new(crate::hir::HirExpression {
                                     kind: HirExprKind::Identifier(fully_qualified_name),
                                     ty: expr.ty.clone(),
+                                    span: Span::default(),
                                 }),
                                 arguments: ext_args,
                             };
@@ -420,7 +449,7 @@ fn resolve_expr(expr: &mut HirExpression, symbols: &SymbolTable, sub: &Substitut
             resolve_expr(value, symbols, sub)?;
         }
         HirExprKind::FunctionLiteral { body, .. } => resolve_stmt(body, symbols, sub)?,
-        HirExprKind::Match { value, arms } => {
+        HirExprKind::Match { value, arms, .. } => {
             resolve_expr(value, symbols, sub)?;
             for (_, body) in arms { resolve_stmt(body, symbols, sub)?; }
         }
@@ -432,3 +461,40 @@ fn resolve_expr(expr: &mut HirExpression, symbols: &SymbolTable, sub: &Substitut
     }
     Ok(())
 }
+
+/// Derived instance helpers every user type gets for free unless it declares
+/// a method of the same name (reflection-lite): `value.to_string()` on enums,
+/// structs and classes, `value.fields()` on structs and classes. The static
+/// `EnumName.from_str(s)` is lowered directly in `hir.rs`.
+fn is_derived_helper(class_name: &str, method: &str, symbols: &SymbolTable) -> bool {
+    match method {
+        "to_string" => symbols.enums.contains_key(class_name) || symbols.custom_types.contains_key(class_name),
+        "fields" => symbols.custom_types.contains_key(class_name),
+        _ => false,
+    }
+}
+
+/// Rewrites a derived-helper `MethodCall` into a plain `Call` of the builtin
+/// of the same name, with the receiver borrowed rather than moved.
+fn rewrite_derived_helper(expr: &mut HirExpression) {
+    let mut temp_kind = HirExprKind::Null;
+    std::mem::swap(&mut expr.kind, &mut temp_kind);
+    let HirExprKind::MethodCall { object, method, .. } = temp_kind else {
+        unreachable!("rewrite_derived_helper called on a non-MethodCall expression");
+    };
+
+    let receiver_ty = Type::Ref(Box::new(object.ty.clone()));
+    let receiver = crate::hir::HirExpression {
+        kind: HirExprKind::Prefix { operator: "&".to_string(), right: object },
+        ty: receiver_ty,
+        span: Span::default(),
+    };
+    expr.kind = HirExprKind::Call {
+        function: Box::new(crate::hir::HirExpression {
+            kind: HirExprKind::Identifier(method),
+            ty: Type::Var(0),
+            span: Span::default(),
+        }),
+        arguments: vec![receiver],
+    };
+}
@@ -7,34 +7,57 @@
 
 use crate::types::{Type, TypeVarId, Substitution};
 use crate::hir::*;
+use kinetix_language::diagnostic::{codes, Diagnostic};
+use kinetix_language::span::Span;
+use rayon::prelude::*;
 
 /// A type constraint: two types that must unify.
 #[derive(Debug, Clone)]
 pub struct Constraint {
     pub left: Type,
     pub right: Type,
-    pub line: usize,
+    /// The expression it comes from.
+    pub span: Span,
+    /// Set for `left[index]` whose type is `right`: what the index must be
+    /// depends on what `left` turns out to be (see `solve_index`).
+    pub index: Option<Type>,
 }
 
 impl Constraint {
-    pub fn new(left: Type, right: Type, line: usize) -> Self {
-        Self { left, right, line }
+    pub fn new(left: Type, right: Type, span: Span) -> Self {
+        Self { left, right, span, index: None }
     }
-}
 
-/// A type error produced during unification.
-#[derive(Debug, Clone)]
-pub struct TypeError {
-    pub message: String,
-    pub line: usize,
+    /// `container[index]` has type `result`.
+    pub fn index(container: Type, index: Type, result: Type, span: Span) -> Self {
+        Self { left: container, right: result, span, index: Some(index) }
+    }
 }
 
-impl std::fmt::Display for TypeError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Line {}: {}", self.line, self.message)
+/// Adds `offset` to every variable in `fresh`, the ones a statement's walk
+/// made with `fresh_var`.
+fn shift_vars(ty: &mut Type, fresh: &std::ops::Range<TypeVarId>, offset: TypeVarId) {
+    match ty {
+        Type::Var(id) if fresh.contains(id) => *id += offset,
+        Type::Fn(params, ret) => {
+            params.iter_mut().for_each(|p| shift_vars(p, fresh, offset));
+            shift_vars(ret, fresh, offset);
+        }
+        Type::Array(inner) | Type::Ref(inner) | Type::MutRef(inner) => shift_vars(inner, fresh, offset),
+        Type::Map(k, v) => {
+            shift_vars(k, fresh, offset);
+            shift_vars(v, fresh, offset);
+        }
+        Type::Custom { args, .. } => args.iter_mut().for_each(|a| shift_vars(a, fresh, offset)),
+        _ => {}
     }
 }
 
+/// A type error at `span`; `Span::default()` for one no line is to blame for.
+fn type_error(message: String, span: Span) -> Diagnostic {
+    Diagnostic::error(codes::TYPE, message).at(span)
+}
+
 /// The Type Context holds the global state for type checking.
 pub struct TypeContext {
     next_var: TypeVarId,
@@ -57,10 +80,35 @@ impl TypeContext {
     }
 
     /// Collect constraints from the entire HIR program.
+    ///
+    /// Top-level statements are walked in parallel, each numbering its fresh
+    /// variables from the same start; they are then shifted past those of the
+    /// statements before it, so the result is exactly the sequential one.
     pub fn collect_constraints(&mut self, program: &HirProgram) -> Vec<Constraint> {
+        let start = self.next_var;
+        let per_stmt: Vec<(Vec<Constraint>, TypeVarId)> = program.statements
+            .par_iter()
+            .map(|stmt| {
+                let mut ctx = TypeContext { next_var: start, substitution: Substitution::new() };
+                let mut constraints = Vec::new();
+                ctx.collect_stmt(stmt, &mut constraints);
+                (constraints, ctx.next_var - start)
+            })
+            .collect();
+
         let mut constraints = Vec::new();
-        for stmt in &program.statements {
-            self.collect_stmt(stmt, &mut constraints);
+        for (mut stmt_constraints, fresh) in per_stmt {
+            let offset = self.next_var - start;
+            if fresh > 0 && offset > 0 {
+                let range = start..start + fresh;
+                for c in &mut stmt_constraints {
+                    for ty in [&mut c.left, &mut c.right].into_iter().chain(c.index.as_mut()) {
+                        shift_vars(ty, &range, offset);
+                    }
+                }
+            }
+            self.next_var += fresh;
+            constraints.extend(stmt_constraints);
         }
         constraints
     }
@@ -68,24 +116,24 @@ impl TypeContext {
     fn collect_stmt(&mut self, stmt: &HirStatement, constraints: &mut Vec<Constraint>) {
         match &stmt.kind {
             HirStmtKind::Let { name: _, mutable: _, value } => {
-                self.collect_expr(value, stmt.line, constraints);
+                self.collect_expr(value, stmt.location(), constraints);
                 // The Let's type must match the value's type
-                constraints.push(Constraint::new(stmt.ty.clone(), value.ty.clone(), stmt.line));
+                constraints.push(Constraint::new(stmt.ty.clone(), value.ty.clone(), value.location(stmt.location())));
             }
             HirStmtKind::State { value, .. } | HirStmtKind::Computed { value, .. } => {
-                self.collect_expr(value, stmt.line, constraints);
-                constraints.push(Constraint::new(stmt.ty.clone(), value.ty.clone(), stmt.line));
+                self.collect_expr(value, stmt.location(), constraints);
+                constraints.push(Constraint::new(stmt.ty.clone(), value.ty.clone(), value.location(stmt.location())));
             }
             HirStmtKind::Effect { body, .. } => {
                 self.collect_stmt(body, constraints);
             }
             HirStmtKind::Return { value } => {
                 if let Some(v) = value {
-                    self.collect_expr(v, stmt.line, constraints);
+                    self.collect_expr(v, stmt.location(), constraints);
                 }
             }
             HirStmtKind::Expression { expression } => {
-                self.collect_expr(expression, stmt.line, constraints);
+                self.collect_expr(expression, stmt.location(), constraints);
             }
             HirStmtKind::Block { statements } => {
                 for s in statements {
@@ -98,7 +146,7 @@ impl TypeContext {
                 if let HirStmtKind::Block { statements } = &body.kind {
                     for s in statements {
                         if let HirStmtKind::Return { value: Some(v) } = &s.kind {
-                            constraints.push(Constraint::new(return_type.clone(), v.ty.clone(), s.line));
+                            constraints.push(Constraint::new(return_type.clone(), v.ty.clone(), v.location(s.location())));
                         }
                     }
                 }
@@ -108,70 +156,73 @@ impl TypeContext {
                     self.collect_stmt(m, constraints);
                 }
             }
-            HirStmtKind::While { condition, body } => {
-                self.collect_expr(condition, stmt.line, constraints);
+            HirStmtKind::While { condition, body, .. } => {
+                self.collect_expr(condition, stmt.location(), constraints);
                 // Condition must be bool
-                constraints.push(Constraint::new(Type::Bool, condition.ty.clone(), stmt.line));
+                constraints.push(Constraint::new(Type::Bool, condition.ty.clone(), condition.location(stmt.location())));
                 self.collect_stmt(body, constraints);
             }
             HirStmtKind::For { range, body, .. } => {
-                self.collect_expr(range, stmt.line, constraints);
+                self.collect_expr(range, stmt.location(), constraints);
                 self.collect_stmt(body, constraints);
             }
-            HirStmtKind::Break | HirStmtKind::Continue => {}
+            HirStmtKind::Break { .. } | HirStmtKind::Continue { .. } => {}
         }
     }
 
-    fn collect_expr(&mut self, expr: &HirExpression, line: usize, constraints: &mut Vec<Constraint>) {
+    /// `span` is where the nearest enclosing node with a span is, for
+    /// desugared expressions that have none of their own.
+    fn collect_expr(&mut self, expr: &HirExpression, span: Span, constraints: &mut Vec<Constraint>) {
+        let span = expr.location(span);
         match &expr.kind {
             HirExprKind::Infix { left, operator, right } => {
-                self.collect_expr(left, line, constraints);
-                self.collect_expr(right, line, constraints);
+                self.collect_expr(left, span, constraints);
+                self.collect_expr(right, span, constraints);
                 // Both operands must have the same type
-                constraints.push(Constraint::new(left.ty.clone(), right.ty.clone(), line));
+                constraints.push(Constraint::new(left.ty.clone(), right.ty.clone(), span));
                 // For comparison operators, result is bool
                 match operator.as_str() {
                     "==" | "!=" | "<" | ">" | "<=" | ">=" => {
-                        constraints.push(Constraint::new(expr.ty.clone(), Type::Bool, line));
+                        constraints.push(Constraint::new(expr.ty.clone(), Type::Bool, span));
                     }
                     // For arithmetic, result type matches operand type
                     "+" | "-" | "*" | "/" | "%" => {
-                        constraints.push(Constraint::new(expr.ty.clone(), left.ty.clone(), line));
+                        constraints.push(Constraint::new(expr.ty.clone(), left.ty.clone(), span));
                     }
                     // Logical operators
                     "&&" | "||" => {
-                        constraints.push(Constraint::new(left.ty.clone(), Type::Bool, line));
-                        constraints.push(Constraint::new(right.ty.clone(), Type::Bool, line));
-                        constraints.push(Constraint::new(expr.ty.clone(), Type::Bool, line));
+                        constraints.push(Constraint::new(left.ty.clone(), Type::Bool, span));
+                        constraints.push(Constraint::new(right.ty.clone(), Type::Bool, span));
+                        constraints.push(Constraint::new(expr.ty.clone(), Type::Bool, span));
                     }
                     _ => {}
                 }
             }
             HirExprKind::Prefix { right, operator } => {
-                self.collect_expr(right, line, constraints);
+                self.collect_expr(right, span, constraints);
                 match operator.as_str() {
-                    "!" => constraints.push(Constraint::new(expr.ty.clone(), Type::Bool, line)),
-                    "-" => constraints.push(Constraint::new(expr.ty.clone(), right.ty.clone(), line)),
-                    "&" => constraints.push(Constraint::new(expr.ty.clone(), Type::Ref(Box::new(right.ty.clone())), line)),
-                    "&mut" => constraints.push(Constraint::new(expr.ty.clone(), Type::MutRef(Box::new(right.ty.clone())), line)),
+                    "!" => constraints.push(Constraint::new(expr.ty.clone(), Type::Bool, span)),
+                    "-" => constraints.push(Constraint::new(expr.ty.clone(), right.ty.clone(), span)),
+                    "&" => constraints.push(Constraint::new(expr.ty.clone(), Type::Ref(Box::new(right.ty.clone())), span)),
+                    "&mut" => constraints.push(Constraint::new(expr.ty.clone(), Type::MutRef(Box::new(right.ty.clone())), span)),
                     _ => {}
                 }
             }
             HirExprKind::Call { function, arguments } => {
-                self.collect_expr(function, line, constraints);
+                self.collect_expr(function, span, constraints);
                 for arg in arguments {
-                    self.collect_expr(arg, line, constraints);
+                    self.collect_expr(arg, span, constraints);
                 }
                 // Constrain: function type must be Fn([arg types...], return_type)
                 // and expr.ty must be the return type
                 let arg_types: Vec<Type> = arguments.iter().map(|a| a.ty.clone()).collect();
                 let expected_fn = Type::Fn(arg_types, Box::new(expr.ty.clone()));
-                constraints.push(Constraint::new(function.ty.clone(), expected_fn, line));
+                constraints.push(Constraint::new(function.ty.clone(), expected_fn, span));
             }
             HirExprKind::MethodCall { object, arguments, .. } => {
-                self.collect_expr(object, line, constraints);
+                self.collect_expr(object, span, constraints);
                 for arg in arguments {
-                    self.collect_expr(arg, line, constraints);
+                    self.collect_expr(arg, span, constraints);
                 }
 
                 // --- M2.6 GLOBAL ENFORCEMENT: Fallible Builtins return Result<T,E> ---
@@ -190,83 +241,128 @@ impl TypeContext {
                                 name: "Result".into(),
                                 args: vec![ok_ty, Type::Custom { name: "String".into(), args: vec![] }],
                             };
-                            constraints.push(Constraint::new(expr.ty.clone(), result_ty, line));
+                            constraints.push(Constraint::new(expr.ty.clone(), result_ty, span));
                         }
                     }
                 }
             }
             HirExprKind::If { condition, consequence, alternative } => {
-                self.collect_expr(condition, line, constraints);
-                constraints.push(Constraint::new(Type::Bool, condition.ty.clone(), line));
+                self.collect_expr(condition, span, constraints);
+                constraints.push(Constraint::new(Type::Bool, condition.ty.clone(), span));
                 self.collect_stmt(consequence, constraints);
                 if let Some(alt) = alternative {
                     self.collect_stmt(alt, constraints);
                     // Both branches must have the same type
-                    constraints.push(Constraint::new(consequence.ty.clone(), alt.ty.clone(), line));
+                    constraints.push(Constraint::new(consequence.ty.clone(), alt.ty.clone(), span));
                 }
             }
             HirExprKind::Assign { target, value } => {
-                self.collect_expr(target, line, constraints);
-                self.collect_expr(value, line, constraints);
-                constraints.push(Constraint::new(target.ty.clone(), value.ty.clone(), line));
+                self.collect_expr(target, span, constraints);
+                self.collect_expr(value, span, constraints);
+                constraints.push(Constraint::new(target.ty.clone(), value.ty.clone(), span));
             }
             HirExprKind::Index { left, index } => {
-                self.collect_expr(left, line, constraints);
-                self.collect_expr(index, line, constraints);
-                // Index must be int
-                constraints.push(Constraint::new(index.ty.clone(), Type::Int, line));
-                // left must be Array<T> where T = expr.ty
-                constraints.push(Constraint::new(left.ty.clone(), Type::Array(Box::new(expr.ty.clone())), line));
+                self.collect_expr(left, span, constraints);
+                self.collect_expr(index, span, constraints);
+                // Array<T> and str by int, Map<K, V> by K
+                constraints.push(Constraint::index(left.ty.clone(), index.ty.clone(), expr.ty.clone(), span));
             }
             HirExprKind::ArrayLiteral(elems) => {
-                for e in elems { self.collect_expr(e, line, constraints); }
+                for e in elems { self.collect_expr(e, span, constraints); }
                 // All elements must have the same type
                 if elems.len() >= 2 {
                     let first = &elems[0].ty;
                     for e in &elems[1..] {
-                        constraints.push(Constraint::new(first.clone(), e.ty.clone(), line));
+                        constraints.push(Constraint::new(first.clone(), e.ty.clone(), span));
                     }
                 }
             }
             HirExprKind::MapLiteral(pairs) => {
                 for (k, v) in pairs {
-                    self.collect_expr(k, line, constraints);
-                    self.collect_expr(v, line, constraints);
+                    self.collect_expr(k, span, constraints);
+                    self.collect_expr(v, span, constraints);
                 }
             }
             HirExprKind::MemberAccess { object, .. } => {
-                self.collect_expr(object, line, constraints);
+                self.collect_expr(object, span, constraints);
             }
             HirExprKind::Range { start, end } => {
-                self.collect_expr(start, line, constraints);
-                self.collect_expr(end, line, constraints);
-                constraints.push(Constraint::new(start.ty.clone(), Type::Int, line));
-                constraints.push(Constraint::new(end.ty.clone(), Type::Int, line));
+                self.collect_expr(start, span, constraints);
+                self.collect_expr(end, span, constraints);
+                constraints.push(Constraint::new(start.ty.clone(), Type::Int, span));
+                constraints.push(Constraint::new(end.ty.clone(), Type::Int, span));
             }
             HirExprKind::FunctionLiteral { body, .. } => {
                 self.collect_stmt(body, constraints);
             }
+            HirExprKind::Match { value, arms, .. } => {
+                // Arm bodies are checked against the bindings their patterns
+                // introduced -- for a type switch (`match x as { int(n) => .. }`)
+                // that's where each arm's narrowed binding type is enforced.
+                self.collect_expr(value, span, constraints);
+                for (_, body) in arms {
+                    self.collect_stmt(body, constraints);
+                }
+            }
             // Literals and identifiers — no constraints to add
             _ => {}
         }
     }
 
     /// Solve all collected constraints via Robinson unification.
-    pub fn solve(&mut self, constraints: &[Constraint]) -> Result<(), Vec<TypeError>> {
+    pub fn solve(&mut self, constraints: &[Constraint]) -> Result<(), Vec<Diagnostic>> {
         let mut errors = Vec::new();
+        // An index into a container not yet known waits for the rest, then
+        // defaults to an array.
+        let mut deferred = Vec::new();
         for c in constraints {
-            if let Err(msg) = self.unify(&c.left, &c.right) {
-                errors.push(TypeError { message: msg, line: c.line });
+            let result = match &c.index {
+                Some(index) => match self.solve_index(&c.left, index, &c.right, false) {
+                    Some(result) => result,
+                    None => {
+                        deferred.push(c);
+                        continue;
+                    }
+                },
+                None => self.unify(&c.left, &c.right),
+            };
+            if let Err(msg) = result {
+                errors.push(type_error(msg, c.span));
+            }
+        }
+        for c in deferred {
+            let index = c.index.as_ref().expect("only index constraints are deferred");
+            if let Some(Err(msg)) = self.solve_index(&c.left, index, &c.right, true) {
+                errors.push(type_error(msg, c.span));
             }
         }
         // M2.5 Generic Instantiation Depth Limit (DOS protection)
         if let Err(msg) = self.substitution.check_depth_limit(32) {
-            errors.push(TypeError { message: msg, line: 0 }); // Global error
+            errors.push(type_error(msg, Span::default())); // Global error
         }
 
         if errors.is_empty() { Ok(()) } else { Err(errors) }
     }
 
+    /// `container[index]: result`. A string is indexed by int and yields a
+    /// string, a map by its key type and yields its value type, an array by
+    /// int. `None` while the container is still unknown, unless `settle`,
+    /// which takes it to be a map when the index is a string and an array
+    /// otherwise.
+    fn solve_index(&mut self, container: &Type, index: &Type, result: &Type, settle: bool) -> Option<Result<(), String>> {
+        Some(match self.substitution.apply(container) {
+            Type::Str => self.unify(index, &Type::Int).and_then(|_| self.unify(result, &Type::Str)),
+            Type::Map(key, value) => self.unify(index, &key).and_then(|_| self.unify(result, &value)),
+            Type::Var(_) if !settle => return None,
+            container @ Type::Var(_) if self.substitution.apply(index) == Type::Str => {
+                self.unify(&container, &Type::Map(Box::new(Type::Str), Box::new(result.clone())))
+            }
+            container => self
+                .unify(index, &Type::Int)
+                .and_then(|_| self.unify(&container, &Type::Array(Box::new(result.clone())))),
+        })
+    }
+
     /// Robinson unification: make two types equal under the current substitution.
     fn unify(&mut self, a: &Type, b: &Type) -> Result<(), String> {
         let a = self.substitution.apply(a);
@@ -279,14 +375,14 @@ impl TypeContext {
             // Var binds to anything (occurs check)
             (Type::Var(id), _) => {
                 if self.occurs(*id, &b) {
-                    return Err(format!("Infinite type: ?T{} occurs in {}", id, b));
+                    return Err(format!("Infinite type: a value of type {} would have to contain itself", b));
                 }
                 self.substitution.bind(*id, b);
                 Ok(())
             }
             (_, Type::Var(id)) => {
                 if self.occurs(*id, &a) {
-                    return Err(format!("Infinite type: ?T{} occurs in {}", id, a));
+                    return Err(format!("Infinite type: a value of type {} would have to contain itself", a));
                 }
                 self.substitution.bind(*id, a);
                 Ok(())
@@ -357,7 +453,7 @@ mod tests {
     use crate::symbol::resolve_program;
     use crate::hir::lower_to_hir;
 
-    fn check(src: &str) -> Result<Substitution, Vec<TypeError>> {
+    fn check(src: &str) -> Result<Substitution, Vec<Diagnostic>> {
         let arena = Bump::new();
         let lexer = Lexer::new(src);
         let mut parser = Parser::new(lexer, &arena);
@@ -372,6 +468,24 @@ mod tests {
         Ok(ctx.substitution)
     }
 
+    #[test]
+    fn test_fresh_vars_numbered_in_statement_order() {
+        let arena = Bump::new();
+        let mut parser = Parser::new(Lexer::new("let a = net.ping(\"a\")\nlet b = 1\nlet c = net.ping(\"b\")\n"), &arena);
+        let program = parser.parse_program();
+        let symbols = resolve_program(&program.statements).unwrap();
+        let hir = lower_to_hir(&program.statements, &symbols, &crate::trait_solver::TraitEnvironment::new());
+        let mut ctx = TypeContext::new();
+        let fresh: Vec<Type> = ctx.collect_constraints(&hir).iter()
+            .filter_map(|c| match &c.right {
+                Type::Custom { name, args } if name == "Result" => Some(args[0].clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(fresh, vec![Type::Var(10000), Type::Var(10001)]);
+        assert_eq!(ctx.fresh_var(), Type::Var(10002));
+    }
+
     #[test]
     fn test_simple_let_int() {
         let sub = check("let x: int = 42").unwrap();
@@ -414,4 +528,37 @@ mod tests {
         let result = ctx.unify(&v, &Type::Array(Box::new(v.clone())));
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_index_depends_on_the_container() {
+        check("let a = [1, 2]\nlet x: int = a[0]").unwrap();
+        check("let s = \"abc\"\nlet c: str = s[1]").unwrap();
+        assert!(check("let s = \"abc\"\nlet c: int = s[1]").is_err());
+        assert!(check("let a = [1, 2]\nlet x = a[\"k\"]").is_err());
+
+        // A container still unknown at the end is a map when indexed by a string
+        let mut ctx = TypeContext::new();
+        let (container, result) = (ctx.fresh_var(), ctx.fresh_var());
+        ctx.solve(&[Constraint::index(container.clone(), Type::Str, result.clone(), Span::default())]).unwrap();
+        assert_eq!(ctx.substitution.apply(&container), Type::Map(Box::new(Type::Str), Box::new(result)));
+    }
+
+    #[test]
+    fn test_class_constant_read_before_the_class() {
+        let class = "class Config {\n  let MAX: int = 10\n}\n";
+        check(&format!("fn limit() -> int {{ return Config.MAX }}\n{}", class)).unwrap();
+        let errors = check(&format!("fn limit() -> str {{ return Config.MAX }}\n{}", class)).unwrap_err();
+        assert_eq!(errors[0].to_string(), "Line 1:28: Type mismatch: string vs int");
+    }
+
+    #[test]
+    fn test_errors_point_at_the_offending_expression() {
+        let src = "let a = 1\nlet s = \"x\"\nlet bad = a + s\nprintln(1 + 2.5)";
+        let errors = check(src).unwrap_err();
+        let at: Vec<(usize, usize, &str)> = errors.iter()
+            .map(|e| e.span().map(|s| (s.line, s.column, s.text(src))).expect("placed"))
+            .collect();
+        assert_eq!(at[0], (3, 11, "a + s"));
+        assert!(at.contains(&(4, 9, "1 + 2.5")), "{:?}", at);
+    }
 }
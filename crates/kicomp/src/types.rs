@@ -19,7 +19,7 @@ pub enum Type {
     /// Function type: (param_types) -> return_type
     Fn(Vec<Type>, Box<Type>),
 
-    /// Homogeneous array: Array<T>
+    /// Homogeneous array: [T]
     Array(Box<Type>),
 
     /// Map: Map<K, V>
@@ -58,13 +58,17 @@ impl Type {
     }
 }
 
+/// The names users see, in diagnostics and hovers: the spelling `typeof`
+/// uses for strings (`string`), array literal syntax for arrays (`[int]`),
+/// `Map<string, int>` for maps and declared names for user types. A type not
+/// inferred yet shows as `_`.
 impl fmt::Display for Type {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Type::Int => write!(f, "int"),
             Type::Float => write!(f, "float"),
             Type::Bool => write!(f, "bool"),
-            Type::Str => write!(f, "str"),
+            Type::Str => write!(f, "string"),
             Type::Void => write!(f, "void"),
             Type::Fn(params, ret) => {
                 write!(f, "fn(")?;
@@ -74,11 +78,11 @@ impl fmt::Display for Type {
                 }
                 write!(f, ") -> {}", ret)
             }
-            Type::Array(inner) => write!(f, "Array<{}>", inner),
+            Type::Array(inner) => write!(f, "[{}]", inner),
             Type::Map(k, v) => write!(f, "Map<{}, {}>", k, v),
             Type::Ref(inner) => write!(f, "&{}", inner),
             Type::MutRef(inner) => write!(f, "&mut {}", inner),
-            Type::Var(id) => write!(f, "?T{}", id),
+            Type::Var(_) => write!(f, "_"),
             Type::Custom { name, args } => {
                 write!(f, "{}", name)?;
                 if !args.is_empty() {
@@ -203,8 +207,11 @@ mod tests {
     fn test_type_display() {
         assert_eq!(Type::Int.to_string(), "int");
         assert_eq!(Type::Fn(vec![Type::Int, Type::Float], Box::new(Type::Bool)).to_string(), "fn(int, float) -> bool");
-        assert_eq!(Type::Array(Box::new(Type::Str)).to_string(), "Array<str>");
-        assert_eq!(Type::Var(42).to_string(), "?T42");
+        assert_eq!(Type::Array(Box::new(Type::Str)).to_string(), "[string]");
+        assert_eq!(Type::Map(Box::new(Type::Str), Box::new(Type::Int)).to_string(), "Map<string, int>");
+        let point = Type::Custom { name: "Point".to_string(), args: vec![] };
+        assert_eq!(Type::Custom { name: "Option".to_string(), args: vec![point] }.to_string(), "Option<Point>");
+        assert_eq!(Type::Var(42).to_string(), "_");
     }
 
     #[test]
@@ -0,0 +1,288 @@
+//! `#version` gate: builtins newer than the declared build are errors, and
+//! deprecated or removed names warn or fail with their replacement.
+
+use crate::compiler::CURRENT_BUILD;
+use kinetix_language::ast::{Expression, ExpressionKind, Statement};
+use kinetix_language::diagnostic::{codes, Diagnostic};
+use kinetix_language::span::Span;
+
+/// A builtin, or a family of builtins when `name` ends with `.`, and the
+/// build that introduced it.
+pub struct Feature {
+    pub name: &'static str,
+    pub since: i64,
+}
+
+pub const FEATURES: &[Feature] = &[
+    Feature { name: "system.", since: 24 },
+    Feature { name: "system.thread.", since: 26 },
+    Feature { name: "system.defer", since: 26 },
+    Feature { name: "net.tcp.", since: 28 },
+    Feature { name: "net.udp.", since: 28 },
+    Feature { name: "net.http.", since: 28 },
+    Feature { name: "net.resolve", since: 28 },
+    Feature { name: "net.ping", since: 30 },
+    Feature { name: "net.getInterfaces", since: 30 },
+    Feature { name: "net.tls.", since: 30 },
+    Feature { name: "data.file.", since: 31 },
+    Feature { name: "data.dir.", since: 32 },
+    Feature { name: "data.path.", since: 32 },
+    Feature { name: "data.watch", since: 32 },
+];
+
+/// An old builtin name, what replaces it, the build that deprecated it and
+/// the build (if any) that removed it from the VM.
+pub struct Deprecation {
+    pub name: &'static str,
+    pub replacement: &'static str,
+    pub since: i64,
+    pub removed: Option<i64>,
+}
+
+pub const DEPRECATIONS: &[Deprecation] = &[
+    Deprecation { name: "system.os_name", replacement: "system.os.name", since: 24, removed: None },
+    Deprecation { name: "system.isWindows", replacement: "system.os.isWindows", since: 24, removed: None },
+    Deprecation { name: "system.isLinux", replacement: "system.os.isLinux", since: 24, removed: None },
+    Deprecation { name: "system.isMac", replacement: "system.os.isMac", since: 24, removed: None },
+    Deprecation { name: "net.get", replacement: "net.http.get", since: 28, removed: None },
+    Deprecation { name: "net.post", replacement: "net.http.post", since: 28, removed: None },
+    Deprecation { name: "net.download", replacement: "net.http.download", since: 28, removed: None },
+    Deprecation { name: "data.read_text", replacement: "data.file.read", since: 31, removed: Some(31) },
+    Deprecation { name: "data.write_text", replacement: "data.file.write", since: 31, removed: Some(31) },
+    Deprecation { name: "data.exists", replacement: "data.file.exists", since: 31, removed: Some(31) },
+    Deprecation { name: "data.copy", replacement: "data.file.copy", since: 31, removed: Some(31) },
+];
+
+/// The build a feature or builtin path first appeared in, if it is gated.
+/// The most specific entry wins (`system.thread.spawn` is 26, not 24).
+pub fn introduced_in(path: &str) -> Option<i64> {
+    FEATURES.iter()
+        .filter(|f| path == f.name || (f.name.ends_with('.') && path.starts_with(f.name)))
+        .max_by_key(|f| f.name.len())
+        .map(|f| f.since)
+}
+
+/// Checks the program parsed from `source` against its `#version`. `Ok`
+/// carries the deprecation warnings, `Err` the errors.
+pub fn diagnose(statements: &[Statement<'_>], source: &str) -> Result<Vec<Diagnostic>, Vec<Diagnostic>> {
+    let findings = findings(statements);
+    let errors = findings.iter().any(|f| f.error);
+    let diagnostics = findings.iter()
+        .filter(|f| f.error == errors)
+        .map(|f| {
+            let diagnostic = match f.error {
+                true => Diagnostic::error(codes::VERSION, &f.message),
+                false => Diagnostic::warning(codes::LINT, &f.message),
+            };
+            match &f.deprecated {
+                // Only a span covering the name as written can be replaced
+                Some((written, d)) if f.span.text(source) == written => diagnostic.at(f.span)
+                    .with_replacement(format!("use {}", d.replacement), f.span, written.chars().count(), d.replacement),
+                _ => diagnostic.at(f.span),
+            }
+        })
+        .collect();
+    if errors { Err(diagnostics) } else { Ok(diagnostics) }
+}
+
+/// One problem with a program's `#version`.
+struct Finding {
+    span: Span,
+    message: String,
+    error: bool,
+    /// For a deprecated name: the name as written, and its entry.
+    deprecated: Option<(String, &'static Deprecation)>,
+}
+
+fn findings(statements: &[Statement<'_>]) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let mut declared: Option<(i64, usize)> = None;
+    for stmt in statements {
+        if let Statement::Version { build, line, span, .. } = stmt {
+            match declared {
+                Some((first, first_line)) if first != *build => findings.push(Finding {
+                    span: *span,
+                    message: format!("#version {} conflicts with #version {} on line {}", build, first, first_line),
+                    error: true,
+                    deprecated: None,
+                }),
+                Some(_) => {}
+                None => declared = Some((*build, *line)),
+            }
+        }
+    }
+    let target = declared.map_or(CURRENT_BUILD, |(build, _)| build);
+
+    let mut uses = Vec::new();
+    for stmt in statements {
+        collect_stmt(stmt, &mut uses);
+    }
+
+    for (written, span) in uses {
+        let path = match written.split_once('.') {
+            Some((module, rest)) => format!("{}.{}", module.to_ascii_lowercase(), rest),
+            None => written.to_ascii_lowercase(),
+        };
+        if let Some(since) = introduced_in(&path).filter(|since| *since > target) {
+            findings.push(Finding {
+                span,
+                message: format!("{} was introduced in build {}, but this script declares #version {}", path, since, target),
+                error: true,
+                deprecated: None,
+            });
+        } else if let Some(d) = DEPRECATIONS.iter().find(|d| d.name == path) {
+            let (message, error) = match d.removed {
+                Some(removed) if target >= removed => (format!("{} was removed in build {}; use {} instead", path, removed, d.replacement), true),
+                _ if target >= d.since => (format!("{} is deprecated since build {}; use {} instead", path, d.since, d.replacement), false),
+                _ => continue,
+            };
+            findings.push(Finding { span, message, error, deprecated: Some((written, d)) });
+        }
+    }
+    findings
+}
+
+/// `net.tcp.connect` for a member-access chain rooted at an identifier.
+fn flatten(expr: &Expression<'_>) -> Option<String> {
+    match &expr.kind {
+        ExpressionKind::Identifier(name) => Some(name.to_string()),
+        ExpressionKind::MemberAccess { object, member } => Some(format!("{}.{}", flatten(object)?, member)),
+        _ => None,
+    }
+}
+
+fn collect_stmt(stmt: &Statement<'_>, uses: &mut Vec<(String, Span)>) {
+    match stmt {
+        Statement::Let { value, span, .. }
+        | Statement::State { value, span, .. }
+        | Statement::Computed { value, span, .. }
+        | Statement::Expression { expression: value, span, .. } => collect_expr(value, *span, uses),
+        Statement::Return { value, span, .. } => {
+            if let Some(value) = value {
+                collect_expr(value, *span, uses);
+            }
+        }
+        Statement::Effect { body, .. } | Statement::Function { body, .. } => collect_stmt(body, uses),
+        Statement::Block { statements, .. } => statements.iter().for_each(|s| collect_stmt(s, uses)),
+        Statement::While { condition: value, body, span, .. } | Statement::For { range: value, body, span, .. } => {
+            collect_expr(value, *span, uses);
+            collect_stmt(body, uses);
+        }
+        Statement::Class { methods, constants, .. } => {
+            methods.iter().chain(constants).for_each(|s| collect_stmt(s, uses));
+        }
+        Statement::Impl { methods, .. } => methods.iter().for_each(|s| collect_stmt(s, uses)),
+        Statement::Struct { .. }
+        | Statement::Include { .. }
+        | Statement::Version { .. }
+        | Statement::ImportBundle { .. }
+        | Statement::Import { .. }
+        | Statement::Enum { .. }
+        | Statement::Trait { .. }
+        | Statement::Break { .. }
+        | Statement::Continue { .. } => {}
+    }
+}
+
+fn collect_expr(expr: &Expression<'_>, span: Span, uses: &mut Vec<(String, Span)>) {
+    match &expr.kind {
+        ExpressionKind::MemberAccess { object, .. } => match flatten(expr) {
+            Some(path) => uses.push((path, if expr.span.line > 0 { expr.span } else { span })),
+            None => collect_expr(object, span, uses),
+        },
+        ExpressionKind::Prefix { right, .. } => collect_expr(right, span, uses),
+        ExpressionKind::Infix { left, right, .. }
+        | ExpressionKind::Assign { target: left, value: right }
+        | ExpressionKind::Index { left, index: right }
+        | ExpressionKind::Range { start: left, end: right } => {
+            collect_expr(left, span, uses);
+            collect_expr(right, span, uses);
+        }
+        ExpressionKind::If { condition, consequence, alternative } => {
+            collect_expr(condition, span, uses);
+            collect_stmt(consequence, uses);
+            if let Some(alt) = alternative {
+                collect_stmt(alt, uses);
+            }
+        }
+        ExpressionKind::Call { function, arguments } => {
+            collect_expr(function, span, uses);
+            arguments.iter().for_each(|a| collect_expr(a, span, uses));
+        }
+        ExpressionKind::StructLiteral { fields, .. } => fields.iter().for_each(|(_, v)| collect_expr(v, span, uses)),
+        ExpressionKind::FunctionLiteral { body, .. } => collect_stmt(body, uses),
+        ExpressionKind::ArrayLiteral(items) => items.iter().for_each(|i| collect_expr(i, span, uses)),
+        ExpressionKind::MapLiteral(entries) => entries.iter().for_each(|(k, v)| {
+            collect_expr(k, span, uses);
+            collect_expr(v, span, uses);
+        }),
+        ExpressionKind::Match { value, arms, .. } => {
+            collect_expr(value, span, uses);
+            arms.iter().for_each(|(_, body)| collect_stmt(body, uses));
+        }
+        ExpressionKind::Try { value } => collect_expr(value, span, uses),
+        ExpressionKind::Identifier(_)
+        | ExpressionKind::Integer(_)
+        | ExpressionKind::Float(_)
+        | ExpressionKind::String(_)
+        | ExpressionKind::Boolean(_)
+        | ExpressionKind::Null => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bumpalo::Bump;
+    use kinetix_language::lexer::Lexer;
+    use kinetix_language::parser::Parser;
+
+    fn gate(src: &str) -> Result<Vec<String>, Vec<String>> {
+        let arena = Bump::new();
+        let mut parser = Parser::new(Lexer::new(src), &arena);
+        let program = parser.parse_program();
+        assert!(parser.errors.is_empty(), "Parser errors: {:?}", parser.errors);
+        let text = |diagnostics: Vec<Diagnostic>| diagnostics.iter().map(ToString::to_string).collect();
+        diagnose(&program.statements, src).map(text).map_err(text)
+    }
+
+    #[test]
+    fn features_newer_than_the_declared_build_are_refused() {
+        let errs = gate("#version 29\nlet c = net.tcp.connect(\"h\", 1)\nfn f() { return net.tls.connect(\"h\", 443) }\n").unwrap_err();
+        assert_eq!(errs, vec!["Line 3:17: net.tls.connect was introduced in build 30, but this script declares #version 29".to_string()]);
+        assert!(gate("#version 30\nlet c = net.tls.connect(\"h\", 443)\n").unwrap().is_empty());
+        assert_eq!(introduced_in("system.thread.spawn"), Some(26));
+        assert_eq!(introduced_in("system.exec"), Some(24));
+        assert_eq!(introduced_in("println"), None);
+    }
+
+    #[test]
+    fn deprecated_names_warn_or_fail_depending_on_the_declared_build() {
+        let warnings = gate("let page = net.get(\"http://x\")\n").unwrap();
+        assert_eq!(warnings, vec!["Line 1:12: net.get is deprecated since build 28; use net.http.get instead".to_string()]);
+        assert!(gate("#version 27\nlet page = net.get(\"http://x\")\n").unwrap().is_empty());
+
+        assert!(gate("#version 30\nlet t = data.read_text(\"a.txt\")\n").unwrap().is_empty());
+        let errs = gate("#version 31\nlet t = data.read_text(\"a.txt\")\n").unwrap_err();
+        assert_eq!(errs, vec!["Line 2:9: data.read_text was removed in build 31; use data.file.read instead".to_string()]);
+
+        let errs = gate("#version 30\n#version 31\n").unwrap_err();
+        assert_eq!(errs, vec!["Line 2:1: #version 31 conflicts with #version 30 on line 1".to_string()]);
+    }
+
+    #[test]
+    fn deprecated_names_come_with_a_rename() {
+        let src = "let a = NET.get(\"x\") + net.get(\"y\")\nprint(System.os_name)\nlet b = `net.get` + net.get(\"z\")\n";
+        let arena = Bump::new();
+        let mut parser = Parser::new(Lexer::new(src), &arena);
+        let program = parser.parse_program();
+        let warnings = diagnose(&program.statements, src).unwrap();
+        let fixes: Vec<_> = warnings.iter().flat_map(|w| &w.fixes).collect();
+        assert_eq!(fixes.iter().map(|f| (f.at.line, f.at.column, f.replace)).collect::<Vec<_>>(), [(1, 9, 7), (1, 24, 7), (2, 7, 14), (3, 21, 7)]);
+        assert_eq!(fixes[0].message, "use net.http.get");
+        assert_eq!(
+            kinetix_language::diagnostic::Fix::apply(src, &fixes),
+            "let a = net.http.get(\"x\") + net.http.get(\"y\")\nprint(system.os.name)\nlet b = `net.get` + net.http.get(\"z\")\n"
+        );
+    }
+}
@@ -17,11 +17,17 @@ pub type VTableMap = HashMap<String, ClassVTable>;
 
 /// Build the static VTable from a compiled program.
 ///
-/// Scans all `Constant::Class` entries in the program's constant pools
-/// and maps each method to its function index for O(1) dispatch.
+/// Maps each method of the program's class metadata, and of any
+/// `Constant::Class` entries in its constant pools, to its function index
+/// for O(1) dispatch.
 pub fn build_vtable(program: &CompiledProgram) -> VTableMap {
     let mut vtable_map: VTableMap = HashMap::new();
 
+    for class in &program.classes {
+        let class_vtable = vtable_map.entry(class.name.clone()).or_default();
+        class_vtable.extend(class.methods.iter().map(|(name, &idx)| (name.clone(), idx)));
+    }
+
     // Scan main function constants for class definitions
     scan_constants(&program.main.constants, &program.functions, &mut vtable_map);
 
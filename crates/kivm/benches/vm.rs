@@ -0,0 +1,20 @@
+//! `cargo bench -p kinetix-kivm`: times `VM::run` on each program of
+//! `bench_suite` (compiling and creating the VM aren't timed).
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use kinetix_kivm::bench_suite as suite;
+use kinetix_kivm::vm::VM;
+
+fn vm(c: &mut Criterion) {
+    let mut group = c.benchmark_group("vm");
+    for case in suite::VM_SUITE {
+        let program = suite::compile(case.source).unwrap_or_else(|e| panic!("{}: {}", case.name, e));
+        group.bench_function(case.name, |b| {
+            b.iter_batched(|| VM::new(program.clone()), |mut vm| vm.run().unwrap(), BatchSize::SmallInput)
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, vm);
+criterion_main!(benches);
@@ -0,0 +1,118 @@
+//! Log of privileged builtin calls, for `kivm run --audit FILE`, so what a
+//! third-party `.exki` actually did can be reviewed afterwards.
+
+use std::io::{LineWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use kinetix_kicomp::capability::Capability;
+
+use crate::builtins::modules::i18n::civil_from_days;
+use crate::vm::Value;
+
+/// Longest argument kept in the log, in characters; longer ones (file
+/// contents, request bodies) are cut and end in `…`.
+const MAX_ARG_CHARS: usize = 512;
+
+pub struct AuditLog {
+    out: Mutex<Box<dyn Write + Send>>,
+}
+
+impl AuditLog {
+    /// A log written to `path`, replacing any previous one.
+    pub fn create(path: &Path) -> Result<Self, String> {
+        let file = std::fs::File::create(path).map_err(|e| format!("Cannot create audit log {}: {}", path.display(), e))?;
+        Ok(Self::to_writer(LineWriter::new(file)))
+    }
+
+    pub fn to_writer(out: impl Write + Send + 'static) -> Self {
+        AuditLog { out: Mutex::new(Box::new(out)) }
+    }
+
+    /// Appends a call of `builtin` as one JSON line, e.g.
+    /// `{"args":["https://example.com"],"builtin":"net.http.get","capability":"NetAccess","time":"2026-10-16T09:30:12.041Z"}`.
+    /// It is called before the builtin runs, for every builtin that needs a
+    /// capability, from every thread of the program. A log that can't be
+    /// written is an error, so nothing privileged runs unrecorded.
+    pub fn record(&self, builtin: &str, capability: &Capability, args: &[Value]) -> Result<(), String> {
+        let entry = serde_json::json!({
+            "time": timestamp(SystemTime::now()),
+            "builtin": builtin,
+            "capability": capability.to_string(),
+            "args": args.iter().map(argument).collect::<Vec<_>>(),
+        });
+        let mut out = self.out.lock().unwrap_or_else(|e| e.into_inner());
+        writeln!(out, "{}", entry).map_err(|e| format!("Cannot write audit log: {}", e))
+    }
+}
+
+fn argument(value: &Value) -> serde_json::Value {
+    match value {
+        Value::Int(n) => (*n).into(),
+        Value::Bool(b) => (*b).into(),
+        Value::Null => serde_json::Value::Null,
+        Value::Str(s) => truncate(s).into(),
+        other => truncate(&other.to_string()).into(),
+    }
+}
+
+fn truncate(text: &str) -> String {
+    match text.char_indices().nth(MAX_ARG_CHARS) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+/// `time` as RFC 3339 UTC with milliseconds.
+fn timestamp(time: SystemTime) -> String {
+    let since = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since.as_secs() as i64;
+    let (year, month, day) = civil_from_days(secs.div_euclid(86_400));
+    let of_day = secs.rem_euclid(86_400);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year, month, day, of_day / 3600, of_day % 3600 / 60, of_day % 60, since.subsec_millis()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn privileged_calls_are_logged_as_json_lines() {
+        let buffer = Shared::default();
+        let mut vm = crate::vm::VM::new(kinetix_kicomp::ir::CompiledProgram::new());
+        vm.audit = Some(Arc::new(AuditLog::to_writer(buffer.clone())));
+
+        let pattern = std::env::temp_dir().join("kinetix-audit-*.none").display().to_string();
+        crate::builtins::call_builtin("data.file.exists", &[Value::Str("x".repeat(600))], &mut vm).unwrap();
+        crate::builtins::call_builtin("str.upper", &[Value::Str("not logged".into())], &mut vm).unwrap();
+        crate::builtins::call_builtin("fs.glob", &[Value::Str(pattern.clone())], &mut vm).unwrap();
+
+        let text = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let entries: Vec<serde_json::Value> = text.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(entries.len(), 2, "{}", text);
+        assert_eq!(entries[0]["builtin"], "data.file.exists");
+        assert_eq!(entries[0]["capability"], "FsRead");
+        assert_eq!(entries[0]["args"][0].as_str().unwrap().chars().count(), MAX_ARG_CHARS + 1);
+        assert_eq!(entries[1]["builtin"], "fs.glob");
+        assert_eq!(entries[1]["args"][0], pattern);
+
+        assert_eq!(timestamp(UNIX_EPOCH + std::time::Duration::from_millis(1_709_640_000_007)), "2024-03-05T12:00:00.007Z");
+    }
+}
@@ -0,0 +1,55 @@
+//! The VM benchmark programs: fib, loops, string building by `+` and by
+//! `sb`, record churn and method dispatch. Shared by `cargo bench -p
+//! kinetix-kivm` and `kivm bench`.
+
+use bumpalo::Bump;
+use kinetix_kicomp::compiler::Compiler;
+use kinetix_kicomp::ir::CompiledProgram;
+use kinetix_language::lexer::Lexer;
+use kinetix_language::parser::Parser;
+
+/// One benchmark program. Sources must not print, so timings aren't
+/// dominated by terminal I/O.
+pub struct Case {
+    pub name: &'static str,
+    pub source: &'static str,
+}
+
+pub const VM_SUITE: &[Case] = &[
+    Case {
+        name: "fib",
+        source: "fn fib(n: int) -> int {\n    if n < 2 { return n }\n    return fib(n - 1) + fib(n - 2)\n}\nlet r = fib(20)\n",
+    },
+    Case {
+        name: "loops",
+        source: "mut total = 0\nmut i = 0\nwhile i < 100000 {\n    total = total + i\n    i = i + 1\n}\n",
+    },
+    Case {
+        name: "string_building",
+        source: "mut s = \"\"\nfor i in 0..5000 {\n    s = s + str(i)\n}\n",
+    },
+    Case {
+        name: "string_builder",
+        source: "let b = sb.new()\nfor i in 0..5000 {\n    b.append(str(i))\n}\nlet s = b.to_str()\n",
+    },
+    Case {
+        name: "record_churn",
+        source: "struct Entry { key: str, value: int }\nmut total = 0\nfor i in 0..5000 {\n    let e = Entry { key: str(i), value: i }\n    total = total + e.value\n}\n",
+    },
+    Case {
+        name: "method_dispatch",
+        source: "class Counter {\n    pub n: int\n    fn bump(self, by: int) -> int { return self.n + by }\n}\nlet c = Counter { n: 1 }\nmut acc = 0\nfor i in 0..20000 {\n    acc = c.bump(acc)\n}\n",
+    },
+];
+
+/// Parses and compiles a case's source.
+pub fn compile(src: &str) -> Result<CompiledProgram, String> {
+    let arena = Bump::new();
+    let mut parser = Parser::new(Lexer::new(src), &arena);
+    let program = parser.parse_program();
+    if !parser.errors.is_empty() {
+        return Err(parser.errors.iter().map(|e| e.to_string()).collect::<Vec<_>>().join("; "));
+    }
+    Compiler::new().compile(&program.statements, None).cloned()
+        .map_err(|errs| errs.iter().map(|e| e.to_string()).collect::<Vec<_>>().join("; "))
+}
@@ -1,798 +1,817 @@
-/// Built-in functions for KixVM.
+//! Built-in functions for KixVM: every builtin is a `Builtin` entry (name,
+//! signature, capability, doc, handler) in a table.
 
 use crate::vm::Value;
+use kinetix_kicomp::capability::Capability;
+use std::collections::HashMap;
 
 #[path = "modules/mod.rs"]
 pub mod modules;
 
-use std::sync::{Arc, Mutex};
-use std::thread::JoinHandle;
+pub type Handler = fn(&[Value], &mut VM) -> Result<Value, String>;
+
+/// One builtin. Signatures use Kinetix type names; `any` is any value, `fn`
+/// a function value, a trailing `?` marks an optional parameter, `...` a
+/// variadic one, and `result<T>` is the `{ok: T}` / `{err: str}` map the
+/// system and net builtins return instead of raising.
+pub struct Builtin {
+    pub name: &'static str,
+    /// Parameters and return type, e.g. `(s: str, sep: str) -> [str]`.
+    pub signature: &'static str,
+    /// Sandbox capability a program needs to call it.
+    pub capability: Option<Capability>,
+    pub doc: &'static str,
+    pub handler: Handler,
+}
 
-lazy_static::lazy_static! {
-    static ref THREAD_REGISTRY: Arc<Mutex<std::collections::HashMap<i64, JoinHandle<Result<Value, String>>>>> = Arc::new(Mutex::new(std::collections::HashMap::new()));
-    static ref NEXT_THREAD_ID: Arc<Mutex<i64>> = Arc::new(Mutex::new(1));
+pub const fn builtin(name: &'static str, signature: &'static str, doc: &'static str, handler: Handler) -> Builtin {
+    Builtin { name, signature, capability: None, doc, handler }
 }
 
-pub const BUILTIN_NAMES: &[&str] = &[
-    // Core
-    "print", "println", "input", "len", "typeof", "assert",
-    "str", "int", "float", "bool", "byte", "char", "stop", "exit", "copy",
-    
-    // String Globals
-    "to_upper", "to_lower", "trim", "split", "replace", "contains", 
-    "starts_with", "ends_with", "pad_left", "pad_right", "join",
+impl Builtin {
+    pub const fn needs(self, cap: Capability) -> Self {
+        Builtin { capability: Some(cap), ..self }
+    }
 
-    // List Globals
-    "push", "pop", "remove_at", "insert", "reverse", "sort", 
-    "min", "max", "any", "all",
-
-    // Iteration
-    "range", "enumerate", "zip", "map", "filter", "reduce",
-    
-    // Math Globals (wrapper/alias if needed, usually accessed via Math.)
-    "Math.abs", "Math.ceil", "Math.floor", "Math.round", "Math.pow", "Math.sqrt",
-    "Math.sin", "Math.cos", "Math.tan", "Math.asin", "Math.acos", "Math.atan2",
-    "Math.deg", "Math.rad", "Math.cbrt", "Math.exp", "Math.log", "Math.log10",
-    "Math.clamp", "Math.lerp", "Math.min", "Math.max", "Math.random", "Math.random_range", 
-    "math.distance_sq", "math.dot", "math.cross", "math.normalize",
-    "System.time", "time.now", "time.ticks", "time.sleep",
-    "system.os.isWindows", "system.os.isLinux", "system.os.isMac",
-    "system.os.name", "system.os.arch", "system.exec",
-    "system.thread.spawn", "system.thread.join", "system.thread.sleep", "system.defer",
-    "env.get", "env.set", "env.args",
-    // Net TCP/UDP (Build 28)
-    "net.tcp.connect", "net.tcp.listen", "net.tcp.accept", "net.tcp.send", "net.tcp.recv",
-    "net.tcp.recvLine", "net.tcp.setTimeout", "net.tcp.setNoDelay", "net.tcp.shutdown", "net.tcp.close",
-    "net.tcp.localAddr", "net.tcp.peerAddr",
-    "net.udp.bind", "net.udp.send", "net.udp.recv", "net.udp.setTimeout", "net.udp.close",
-    "net.http.get", "net.http.post", "net.http.download", "net.resolve",
-    // Net Utils (Build 30)
-    "net.ping", "net.getInterfaces", "net.tls.connect",
-];
+    /// `(required, max)` parameter counts read off the signature; `max` is
+    /// `None` for variadic builtins.
+    pub fn arity(&self) -> (usize, Option<usize>) {
+        let params = params_of(self.signature);
+        let required = params.iter().filter(|p| !p.ends_with('?') && !p.ends_with("...")).count();
+        let max = if params.iter().any(|p| p.ends_with("...")) { None } else { Some(params.len()) };
+        (required, max)
+    }
+}
 
-use crate::vm::VM;
+impl std::fmt::Debug for Builtin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", self.name, self.signature)
+    }
+}
 
-pub fn call_builtin(name: &str, args: &[Value], vm: &mut VM) -> Result<Value, String> {
-    match name {
-        // --- Core ---
-        "print" | "println" => {
-            let text: Vec<String> = args.iter().map(|a| format!("{}", a)).collect();
-            let line = text.join(" ");
-            println!("{}", line);
-            vm.output.push(line);
-            Ok(Value::Null)
+/// Top-level parameters of a signature, e.g. `["s: str", "pad: str?"]`.
+fn params_of(signature: &str) -> Vec<&str> {
+    let mut depth = 0;
+    let mut params = Vec::new();
+    let mut start = 1;
+    for (i, c) in signature.char_indices() {
+        match c {
+            '(' | '[' | '{' | '<' => depth += 1,
+            ']' | '}' | '>' if depth > 1 => depth -= 1,
+            ',' if depth == 1 => {
+                params.push(signature[start..i].trim());
+                start = i + 1;
+            }
+            ')' if depth == 1 => {
+                params.push(signature[start..i].trim());
+                break;
+            }
+            ')' => depth -= 1,
+            _ => {}
         }
-        "input" => {
-            if let Some(Value::Str(prompt)) = args.first() { print!("{}", prompt); }
-            let mut buf = String::new();
-            std::io::stdin().read_line(&mut buf).map_err(|e| e.to_string())?;
-            Ok(Value::Str(buf.trim().to_string()))
+    }
+    params.retain(|p| !p.is_empty());
+    params
+}
+
+/// Every builtin the VM exposes as a global, in registration order.
+pub const BUILTINS: &[Builtin] = &[
+    // Core
+    builtin("print", "(values: any...)", "Prints the values separated by spaces, followed by a newline. Large or deeply nested values are abbreviated (see inspect).", |args, vm| {
+        let limits = crate::pretty::print_limits();
+        let text: Vec<String> = args.iter().map(|a| crate::pretty::render(a, limits)).collect();
+        let line = text.join(" ");
+        modules::io::write_line(&line);
+        vm.output.push(line);
+        Ok(Value::Null)
+    }),
+    builtin("println", "(values: any...)", "Prints the values separated by spaces, followed by a newline.", |args, vm| call_builtin("print", args, vm)),
+    builtin("io.echo", "(value: any)", "Prints `value` on its own line unless it is null; what `kivm eval` wraps its last expression in.", |args, vm| match args.first() {
+        Some(Value::Null) | None => Ok(Value::Null),
+        Some(_) => call_builtin("print", args, vm),
+    }),
+    builtin("input", "(prompt: str?) -> str", "Prints `prompt`, reads one line from stdin and returns it trimmed.", |args, _| {
+        if let Some(Value::Str(prompt)) = args.first() { modules::io::write(prompt); }
+        modules::io::flush();
+        let mut buf = String::new();
+        std::io::stdin().read_line(&mut buf).map_err(|e| e.to_string())?;
+        Ok(Value::Str(buf.trim().to_string()))
+    }),
+    builtin("len", "(value: any) -> int", "Length of a string (in bytes), array or map; 0 for anything else.", |args, _| match args.first() {
+        Some(Value::Str(s)) => Ok(Value::Int(s.len() as i64)),
+        Some(Value::Array(a)) => Ok(Value::Int(a.len() as i64)),
+        Some(Value::Map(m)) => Ok(Value::Int(m.len() as i64)),
+        _ => Ok(Value::Int(0)),
+    }),
+    builtin("typeof", "(value: any) -> str", "Type name: int, float, string, bool, null, array, function, map, or the declared struct/class/enum name.", |args, _| {
+        Ok(Value::Str(args.first().map_or("void", type_name).to_string()))
+    }),
+    builtin("assert", "(cond: any, message: any?)", "Raises `Assertion failed: message` when `cond` is falsy.", |args, _| {
+        let cond = args.first().map(|v| v.is_truthy()).unwrap_or(false);
+        if !cond {
+            let msg = args.get(1).map(|v| format!("{}", v)).unwrap_or_else(|| "Assertion failed".into());
+            return Err(format!("Assertion failed: {}", msg));
         }
-        "len" => match args.first() { // Global len
-            Some(Value::Str(s)) => Ok(Value::Int(s.len() as i64)),
-            Some(Value::Array(a)) => Ok(Value::Int(a.len() as i64)),
-            Some(Value::Map(m)) => Ok(Value::Int(m.len() as i64)),
-            _ => Ok(Value::Int(0)),
-        },
-        "typeof" => {
-            let t = match args.first() {
-                Some(Value::Int(_)) => "int",
-                Some(Value::Float(_)) => "float",
-                Some(Value::Str(_)) => "string",
-                Some(Value::Bool(_)) => "bool",
-                Some(Value::Null) => "null",
-                Some(Value::Array(_)) => "array",
-                Some(Value::Function(_)) => "function",
-                Some(Value::NativeFn(_)) => "native_function",
-                Some(Value::NativeModule(_)) => "module",
-                Some(Value::BoundMethod(_, _)) => "bound_method",
-                Some(Value::Map(_)) => "map",
-                None => "void",
-            };
-            Ok(Value::Str(t.to_string()))
+        Ok(Value::Null)
+    }),
+    builtin("str", "(value: any) -> str", "The value as printed by println.", |args, _| {
+        Ok(Value::Str(format!("{}", args.first().cloned().unwrap_or(Value::Null))))
+    }),
+    builtin("int", "(value: any) -> int", "Converts a float (truncating), numeric string or bool to int; raises on a non-numeric string.", |args, _| match args.first() {
+        Some(Value::Int(n)) => Ok(Value::Int(*n)),
+        Some(Value::Float(f)) => Ok(Value::Int(*f as i64)),
+        Some(Value::Str(s)) => s.parse::<i64>().map(Value::Int).map_err(|_| format!("Cannot convert '{}' to int", s)),
+        Some(Value::Bool(b)) => Ok(Value::Int(if *b { 1 } else { 0 })),
+        _ => Ok(Value::Int(0)),
+    }),
+    builtin("float", "(value: any) -> float", "Converts an int or numeric string to float; raises on a non-numeric string.", |args, _| match args.first() {
+        Some(Value::Float(f)) => Ok(Value::Float(*f)),
+        Some(Value::Int(n)) => Ok(Value::Float(*n as f64)),
+        Some(Value::Str(s)) => s.parse::<f64>().map(Value::Float).map_err(|_| format!("Cannot convert '{}' to float", s)),
+        _ => Ok(Value::Float(0.0)),
+    }),
+    builtin("bool", "(value: any) -> bool", "Truthiness of the value.", |args, _| {
+        Ok(Value::Bool(args.first().map(|v| v.is_truthy()).unwrap_or(false)))
+    }),
+    builtin("byte", "(n: int) -> int", "The low 8 bits of `n`.", |args, _| {
+        let n = args.first().and_then(|v| v.as_int().ok()).unwrap_or(0);
+        Ok(Value::Int(n & 0xFF))
+    }),
+    builtin("char", "(code: int) -> str", "The one-character string for a Unicode code point; raises on an invalid code.", |args, _| {
+        let n = args.first().and_then(|v| v.as_int().ok()).unwrap_or(0);
+        if let Some(c) = std::char::from_u32(n as u32) {
+            Ok(Value::Str(c.to_string()))
+        } else {
+            Err(format!("Invalid char code: {}", n))
         }
-        "assert" => {
-            let cond = args.first().map(|v| v.is_truthy()).unwrap_or(false);
-            if !cond {
-                let msg = args.get(1).map(|v| format!("{}", v)).unwrap_or_else(|| "Assertion failed".into());
-                return Err(format!("Assertion failed: {}", msg));
+    }),
+    builtin("stop", "(code: int?)", "Exits the process with `code` (default 0).", |args, _| {
+        let code = args.first().and_then(|v| match v {
+            Value::Int(n) => Some(*n as i32),
+            _ => None,
+        }).unwrap_or(0);
+        modules::io::flush();
+        std::process::exit(code);
+    }),
+    builtin("exit", "(code: int?)", "Exits the process with `code` (default 0).", |args, vm| call_builtin("stop", args, vm)),
+    builtin("copy", "(value: T) -> T", "A copy of the value, leaving the original usable after a move.", |args, _| {
+        Ok(args.first().cloned().unwrap_or(Value::Null))
+    }),
+    // format(x, digits): fixed number of decimals for numbers; str(x) otherwise.
+    builtin("format", "(value: any, digits: int?) -> str", "Numbers with exactly `digits` decimals (0-20); other values as str(value).", |args, _| match (args.first(), args.get(1)) {
+        (Some(n @ (Value::Int(_) | Value::Float(_))), Some(Value::Int(digits))) => {
+            if !(0..=20).contains(digits) {
+                return Err(format!("format: digits must be between 0 and 20, got {}", digits));
             }
-            Ok(Value::Null)
+            Ok(Value::Str(format!("{:.*}", *digits as usize, n.as_float()?)))
+        }
+        (Some(v), _) => Ok(Value::Str(format!("{}", v))),
+        (None, _) => Ok(Value::Str("null".to_string())),
+    }),
+    builtin("is", "(a: any, b: any) -> bool", "Identity: same kind and same value, bit-for-bit for floats (NaN is NaN, 1 is not 1.0).", |args, _| match (args.first(), args.get(1)) {
+        (Some(a), Some(b)) => Ok(Value::Bool(a.is_same(b))),
+        _ => Err("is expects two arguments".into()),
+    }),
+    builtin("help", "(name: str)", "Prints the signature, required capability and description of a builtin, e.g. help(\"str.split\").", |args, vm| {
+        let text = match args.first() {
+            Some(Value::Str(name)) => help_text(name),
+            _ => format!("help(name) describes a builtin. Builtins: {}", builtin_names().collect::<Vec<_>>().join(", ")),
+        };
+        modules::io::write_line(&text);
+        vm.output.push(text);
+        Ok(Value::Null)
+    }),
+
+    // Reflection-lite (also reachable as `value.to_string()` / `value.fields()`)
+    builtin("to_string", "(value: any) -> str", "Like str, but renders enum values as `Variant(payload)` and instances as `Point { x: 1, y: 2 }`.", |args, _| {
+        Ok(Value::Str(derived_to_string(args.first().unwrap_or(&Value::Null))))
+    }),
+    builtin("inspect", "(value: any, depth: int?, width: int?) -> str", "The value rendered with strings quoted, at most `depth` levels deep (default 5) and `width` items per array or map (default 100).", |args, _| {
+        let mut limits = crate::pretty::Limits::INSPECT;
+        for (limit, arg) in [&mut limits.depth, &mut limits.width].into_iter().zip(args.iter().skip(1)) {
+            *limit = usize::try_from(arg.as_int()?).map_err(|_| format!("inspect limits must not be negative, got {}", arg))?;
         }
-        "stop" | "exit" | "System.exit" => {
-            let code = args.first().and_then(|v| match v {
-                Value::Int(n) => Some(*n as i32),
-                _ => None,
-            }).unwrap_or(0);
-            std::process::exit(code);
+        Ok(Value::Str(crate::pretty::inspect(args.first().unwrap_or(&Value::Null), limits)))
+    }),
+    builtin("fields", "(instance: any) -> [str]", "Field names of a struct or class instance, sorted; raises for other values.", |args, _| match args.first() {
+        Some(Value::Map(map)) => {
+            let mut keys: Vec<&String> = map.keys().filter(|k| !is_hidden_key(k)).collect();
+            keys.sort();
+            Ok(Value::Array(keys.into_iter().map(|k| Value::Str(k.clone())).collect()))
         }
+        Some(other) => Err(format!("fields expects a struct or class instance, got {}", derived_to_string(other))),
+        None => Err("fields expects a struct or class instance".into()),
+    }),
 
-        "copy" => Ok(args.first().cloned().unwrap_or(Value::Null)),
-        
-        // --- Global String Wrappers ---
-        "to_upper" => call_builtin("str.upper", args, vm),
-        "to_lower" => call_builtin("str.lower", args, vm),
-        "trim" => call_builtin("str.trim", args, vm),
-        "split" => call_builtin("str.split", args, vm),
-        "replace" => call_builtin("str.replace", args, vm),
-        "contains" => {
-            match args.first() {
-                Some(Value::Str(_)) => call_builtin("str.contains", args, vm),
-                Some(Value::Array(_)) => call_builtin("array.contains", args, vm),
-                _ => Ok(Value::Bool(false)),
+    // String Globals
+    builtin("to_upper", "(s: str) -> str", "Uppercase copy of `s`.", |args, vm| call_builtin("str.upper", args, vm)),
+    builtin("to_lower", "(s: str) -> str", "Lowercase copy of `s`.", |args, vm| call_builtin("str.lower", args, vm)),
+    builtin("trim", "(s: str) -> str", "`s` without leading and trailing whitespace.", |args, vm| call_builtin("str.trim", args, vm)),
+    builtin("split", "(s: str, sep: str) -> [str]", "The parts of `s` between occurrences of `sep`.", |args, vm| call_builtin("str.split", args, vm)),
+    builtin("replace", "(s: str, old: str, new: str) -> str", "`s` with every `old` replaced by `new`.", |args, vm| call_builtin("str.replace", args, vm)),
+    builtin("contains", "(haystack: str | [T], needle: any) -> bool", "Whether a string contains a substring, or an array an equal element.", |args, vm| match args.first() {
+        Some(Value::Str(_)) => call_builtin("str.contains", args, vm),
+        Some(Value::Array(_)) => call_builtin("array.contains", args, vm),
+        _ => Ok(Value::Bool(false)),
+    }),
+    builtin("starts_with", "(s: str, prefix: str) -> bool", "Whether `s` begins with `prefix`.", |args, _| {
+        if let (Some(Value::Str(s)), Some(Value::Str(prefix))) = (args.first(), args.get(1)) {
+            Ok(Value::Bool(s.starts_with(prefix)))
+        } else { Ok(Value::Bool(false)) }
+    }),
+    builtin("ends_with", "(s: str, suffix: str) -> bool", "Whether `s` ends with `suffix`.", |args, _| {
+        if let (Some(Value::Str(s)), Some(Value::Str(suffix))) = (args.first(), args.get(1)) {
+            Ok(Value::Bool(s.ends_with(suffix)))
+        } else { Ok(Value::Bool(false)) }
+    }),
+    builtin("pad_left", "(s: str, width: int, pad: str?) -> str", "`s` right-aligned to `width` using the first char of `pad` (default space).", |args, _| {
+        if let (Some(Value::Str(s)), Some(Value::Int(len))) = (args.first(), args.get(1)) {
+            let pad_char = args.get(2).and_then(|v| if let Value::Str(c) = v { c.chars().next() } else { None }).unwrap_or(' ');
+            let width = *len as usize;
+            if s.len() >= width { Ok(Value::Str(s.clone())) } else {
+                let pad = std::iter::repeat_n(pad_char, width - s.len()).collect::<String>();
+                Ok(Value::Str(format!("{}{}", pad, s)))
             }
-        },
-
-        // --- Global List Wrappers ---
-        "push" => call_builtin("array.push", args, vm),
-        "pop" => call_builtin("array.pop", args, vm),
-        "remove_at" => {
-             if let (Some(Value::Array(arr)), Some(Value::Int(idx))) = (args.get(0), args.get(1)) {
-                 let mut new_arr = arr.clone();
-                 if *idx >= 0 && (*idx as usize) < new_arr.len() {
-                     new_arr.remove(*idx as usize);
-                 }
-                 Ok(Value::Array(new_arr))
-             } else { Ok(Value::Null) }
-        },
-        "insert" => {
-             if let (Some(Value::Array(arr)), Some(Value::Int(idx)), Some(val)) = (args.get(0), args.get(1), args.get(2)) {
-                 let mut new_arr = arr.clone();
-                 let idx = (*idx as usize).min(new_arr.len());
-                 new_arr.insert(idx, val.clone());
-                 Ok(Value::Array(new_arr))
-             } else { Ok(Value::Null) }
-        },
-        "reverse" => call_builtin("array.reverse", args, vm),
-        "sort" => call_builtin("array.sort", args, vm),
-        
-        "any" => {
-            if let (Some(Value::Array(arr)), Some(callback)) = (args.get(0), args.get(1)) {
-                let (arr, callback) = (arr.clone(), callback.clone());
-                for item in arr {
-                    if vm.call_function_now(callback.clone(), vec![item])?.is_truthy() {
-                        return Ok(Value::Bool(true));
-                    }
-                }
-                Ok(Value::Bool(false))
-            } else { Err("Invalid args for any".into()) }
-        },
-        "all" => {
-            if let (Some(Value::Array(arr)), Some(callback)) = (args.get(0), args.get(1)) {
-                let (arr, callback) = (arr.clone(), callback.clone());
-                for item in arr {
-                    if !vm.call_function_now(callback.clone(), vec![item])?.is_truthy() {
-                        return Ok(Value::Bool(false));
-                    }
+        } else { Ok(Value::Null) }
+    }),
+    builtin("pad_right", "(s: str, width: int, pad: str?) -> str", "`s` left-aligned to `width` using the first char of `pad` (default space).", |args, _| {
+        if let (Some(Value::Str(s)), Some(Value::Int(len))) = (args.first(), args.get(1)) {
+            let pad_char = args.get(2).and_then(|v| if let Value::Str(c) = v { c.chars().next() } else { None }).unwrap_or(' ');
+            let width = *len as usize;
+            if s.len() >= width { Ok(Value::Str(s.clone())) } else {
+                let pad = std::iter::repeat_n(pad_char, width - s.len()).collect::<String>();
+                Ok(Value::Str(format!("{}{}", s, pad)))
+            }
+        } else { Ok(Value::Null) }
+    }),
+    builtin("join", "(items: [T], sep: str) -> str", "The items as strings, separated by `sep`.", |args, _| {
+        if let (Some(Value::Array(list)), Some(Value::Str(sep))) = (args.first(), args.get(1)) {
+            let joined = list.iter().map(|v| format!("{}", v)).collect::<Vec<_>>().join(sep);
+            Ok(Value::Str(joined))
+        } else { Ok(Value::Null) }
+    }),
+
+    // List Globals
+    builtin("push", "(items: [T], value: T) -> [T]", "A new array with `value` appended.", |args, vm| call_builtin("array.push", args, vm)),
+    builtin("pop", "(items: [T]) -> [T]", "A new array without the last element.", |args, vm| call_builtin("array.pop", args, vm)),
+    builtin("remove_at", "(items: [T], index: int) -> [T]", "A new array without the element at `index` (unchanged if out of range).", |args, _| {
+        if let (Some(Value::Array(arr)), Some(Value::Int(idx))) = (args.first(), args.get(1)) {
+            let mut new_arr = arr.clone();
+            if *idx >= 0 && (*idx as usize) < new_arr.len() {
+                new_arr.remove(*idx as usize);
+            }
+            Ok(Value::Array(new_arr))
+        } else { Ok(Value::Null) }
+    }),
+    builtin("insert", "(items: [T], index: int, value: T) -> [T]", "A new array with `value` inserted before `index` (clamped to the end).", |args, _| {
+        if let (Some(Value::Array(arr)), Some(Value::Int(idx)), Some(val)) = (args.first(), args.get(1), args.get(2)) {
+            let mut new_arr = arr.clone();
+            let idx = (*idx as usize).min(new_arr.len());
+            new_arr.insert(idx, val.clone());
+            Ok(Value::Array(new_arr))
+        } else { Ok(Value::Null) }
+    }),
+    builtin("reverse", "(items: [T]) -> [T]", "A new array in reverse order.", |args, vm| call_builtin("array.reverse", args, vm)),
+    builtin("sort", "(items: [T]) -> [T]", "A new array in ascending order (the total order used by min/max: null < bool < number < string < array < map).", |args, vm| call_builtin("array.sort", args, vm)),
+    builtin("min", "(items: [T] | T, other: T?) -> T", "Smallest element, or null for an empty array; with two numbers, the smaller one.", |args, vm| {
+        if args.len() == 1 {
+            if let Some(Value::Array(arr)) = args.first() {
+                Ok(arr.iter().min_by(|a, b| a.sort_cmp(b)).cloned().unwrap_or(Value::Null))
+            } else { Ok(args[0].clone()) }
+        } else {
+            call_builtin("Math.min", args, vm)
+        }
+    }),
+    builtin("max", "(items: [T] | T, other: T?) -> T", "Largest element, or null for an empty array; with two numbers, the larger one.", |args, vm| {
+        if args.len() == 1 {
+            if let Some(Value::Array(arr)) = args.first() {
+                Ok(arr.iter().max_by(|a, b| a.sort_cmp(b)).cloned().unwrap_or(Value::Null))
+            } else { Ok(args[0].clone()) }
+        } else {
+            call_builtin("Math.max", args, vm)
+        }
+    }),
+    builtin("any", "(items: [T], pred: fn) -> bool", "Whether `pred` is truthy for at least one element.", |args, vm| {
+        if let (Some(Value::Array(arr)), Some(callback)) = (args.first(), args.get(1)) {
+            let (arr, callback) = (arr.clone(), callback.clone());
+            for item in arr {
+                if vm.call_function_now(callback.clone(), vec![item])?.is_truthy() {
+                    return Ok(Value::Bool(true));
                 }
-                Ok(Value::Bool(true))
-            } else { Err("Invalid args for all".into()) }
-        },
-
-        "min" => {
-            if args.len() == 1 {
-                if let Some(Value::Array(arr)) = args.first() {
-                    // Find min in array
-                    let min_val = arr.iter().min_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
-                    Ok(min_val.cloned().unwrap_or(Value::Null))
-                } else { Ok(args[0].clone()) }
-            } else {
-                call_builtin("Math.min", args, vm)
             }
-        },
-        "max" => {
-            if args.len() == 1 {
-                 if let Some(Value::Array(arr)) = args.first() {
-                    // Find max in array
-                    let max_val = arr.iter().max_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
-                    Ok(max_val.cloned().unwrap_or(Value::Null))
-                } else { Ok(args[0].clone()) }
-            } else {
-                call_builtin("Math.max", args, vm)
+            Ok(Value::Bool(false))
+        } else { Err("Invalid args for any".into()) }
+    }),
+    builtin("all", "(items: [T], pred: fn) -> bool", "Whether `pred` is truthy for every element.", |args, vm| {
+        if let (Some(Value::Array(arr)), Some(callback)) = (args.first(), args.get(1)) {
+            let (arr, callback) = (arr.clone(), callback.clone());
+            for item in arr {
+                if !vm.call_function_now(callback.clone(), vec![item])?.is_truthy() {
+                    return Ok(Value::Bool(false));
+                }
             }
-        },
-
-        "starts_with" => {
-             if let (Some(Value::Str(s)), Some(Value::Str(prefix))) = (args.get(0), args.get(1)) {
-                 Ok(Value::Bool(s.starts_with(prefix)))
-             } else { Ok(Value::Bool(false)) }
-        },
-        "ends_with" => {
-             if let (Some(Value::Str(s)), Some(Value::Str(suffix))) = (args.get(0), args.get(1)) {
-                 Ok(Value::Bool(s.ends_with(suffix)))
-             } else { Ok(Value::Bool(false)) }
-        },
-        "pad_left" => {
-             if let (Some(Value::Str(s)), Some(Value::Int(len))) = (args.get(0), args.get(1)) {
-                 let pad_char = args.get(2).and_then(|v| if let Value::Str(c) = v { c.chars().next() } else { None }).unwrap_or(' ');
-                 let width = *len as usize;
-                 if s.len() >= width { Ok(Value::Str(s.clone())) } else {
-                     let pad = std::iter::repeat(pad_char).take(width - s.len()).collect::<String>();
-                     Ok(Value::Str(format!("{}{}", pad, s)))
-                 }
-             } else { Ok(Value::Null) }
-        },
-        "pad_right" => {
-             if let (Some(Value::Str(s)), Some(Value::Int(len))) = (args.get(0), args.get(1)) {
-                 let pad_char = args.get(2).and_then(|v| if let Value::Str(c) = v { c.chars().next() } else { None }).unwrap_or(' ');
-                 let width = *len as usize;
-                 if s.len() >= width { Ok(Value::Str(s.clone())) } else {
-                     let pad = std::iter::repeat(pad_char).take(width - s.len()).collect::<String>();
-                     Ok(Value::Str(format!("{}{}", s, pad)))
-                 }
-             } else { Ok(Value::Null) }
-        },
-        "join" => {
-             if let (Some(Value::Array(list)), Some(Value::Str(sep))) = (args.get(0), args.get(1)) {
-                 let joined = list.iter().map(|v| format!("{}", v)).collect::<Vec<_>>().join(sep);
-                 Ok(Value::Str(joined))
-             } else { Ok(Value::Null) }
-        },
-
-        // --- Math Module ---
-        "Math.sin" => Ok(Value::Float(args.first().and_then(|v| v.as_float().ok()).unwrap_or(0.0).sin())),
-
-        // --- Math Extras ---
-        "Math.asin" => Ok(Value::Float(args.first().and_then(|v| v.as_float().ok()).unwrap_or(0.0).asin())),
-        "Math.acos" => Ok(Value::Float(args.first().and_then(|v| v.as_float().ok()).unwrap_or(0.0).acos())),
-        "Math.atan2" => {
-            let y = args.get(0).and_then(|v| v.as_float().ok()).unwrap_or(0.0);
-            let x = args.get(1).and_then(|v| v.as_float().ok()).unwrap_or(0.0);
-            Ok(Value::Float(y.atan2(x)))
-        },
-        "Math.deg" => Ok(Value::Float(args.first().and_then(|v| v.as_float().ok()).unwrap_or(0.0).to_degrees())),
-        "Math.rad" => Ok(Value::Float(args.first().and_then(|v| v.as_float().ok()).unwrap_or(0.0).to_radians())),
-        "Math.cbrt" => Ok(Value::Float(args.first().and_then(|v| v.as_float().ok()).unwrap_or(0.0).cbrt())),
-        "Math.exp" => Ok(Value::Float(args.first().and_then(|v| v.as_float().ok()).unwrap_or(0.0).exp())),
-        "Math.log" => Ok(Value::Float(args.first().and_then(|v| v.as_float().ok()).unwrap_or(0.0).ln())),
-        "Math.log10" => Ok(Value::Float(args.first().and_then(|v| v.as_float().ok()).unwrap_or(0.0).log10())),
-        "Math.clamp" => {
-            let val = args.get(0);
-            let min = args.get(1);
-            let max = args.get(2);
-            match (val, min, max) {
-                (Some(Value::Int(v)), Some(Value::Int(mn)), Some(Value::Int(mx))) => {
-                    Ok(Value::Int(*v.max(mn).min(mx)))
-                },
-                (Some(v), Some(mn), Some(mx)) => {
-                     let vf = v.as_float().unwrap_or(0.0);
-                     let mnf = mn.as_float().unwrap_or(0.0);
-                     let mxf = mx.as_float().unwrap_or(1.0);
-                     Ok(Value::Float(vf.max(mnf).min(mxf)))
-                },
-                _ => Ok(Value::Null)
+            Ok(Value::Bool(true))
+        } else { Err("Invalid args for all".into()) }
+    }),
+
+    // Iteration (returning lists currently)
+    builtin("range", "(start: int, end: int, step: int?) -> [int]", "The ints from `start` up to (or, with a negative step, down to) `end`, exclusive.", |args, _| {
+        let start = args.first().and_then(|v| v.as_int().ok()).unwrap_or(0);
+        let end = args.get(1).and_then(|v| v.as_int().ok()).unwrap_or(0);
+        let step = args.get(2).and_then(|v| v.as_int().ok()).unwrap_or(1);
+        let mut res = Vec::new();
+        let mut i = start;
+        if step > 0 {
+            while i < end { res.push(Value::Int(i)); i += step; }
+        } else if step < 0 {
+            while i > end { res.push(Value::Int(i)); i += step; }
+        }
+        Ok(Value::Array(res))
+    }),
+    builtin("enumerate", "(items: [T]) -> [[int, T]]", "`[index, element]` pairs.", |args, _| {
+        if let Some(Value::Array(arr)) = args.first() {
+            let res = arr.iter().enumerate().map(|(i, v)| {
+                Value::Array(vec![Value::Int(i as i64), v.clone()])
+            }).collect();
+            Ok(Value::Array(res))
+        } else { Ok(Value::Null) }
+    }),
+    builtin("zip", "(a: [A], b: [B]) -> [[A, B]]", "Pairs of elements at the same index, as long as the shorter array.", |args, _| {
+        if let (Some(Value::Array(a)), Some(Value::Array(b))) = (args.first(), args.get(1)) {
+            let res = a.iter().zip(b).map(|(x, y)| Value::Array(vec![x.clone(), y.clone()])).collect();
+            Ok(Value::Array(res))
+        } else { Ok(Value::Null) }
+    }),
+    builtin("map", "(items: [T], f: fn) -> [U]", "`f(element)` for every element.", |args, vm| {
+        if let (Some(Value::Array(arr)), Some(callback)) = (args.first(), args.get(1)) {
+            let (arr, callback) = (arr.clone(), callback.clone());
+            let mut res = Vec::with_capacity(arr.len());
+            for item in arr {
+                res.push(vm.call_function_now(callback.clone(), vec![item])?);
             }
-        },
-        "Math.lerp" => {
-            let a = args.get(0).and_then(|v| v.as_float().ok()).unwrap_or(0.0);
-            let b = args.get(1).and_then(|v| v.as_float().ok()).unwrap_or(0.0);
-            let t = args.get(2).and_then(|v| v.as_float().ok()).unwrap_or(0.0);
-            Ok(Value::Float(a + (b - a) * t))
-        },
-        
-        // --- Iterators (Returning Lists currently) ---
-        "range" => {
-            let start = args.get(0).and_then(|v| v.as_int().ok()).unwrap_or(0);
-            let end = args.get(1).and_then(|v| v.as_int().ok()).unwrap_or(0);
-            let step = args.get(2).and_then(|v| v.as_int().ok()).unwrap_or(1);
+            Ok(Value::Array(res))
+        } else { Err("map expects (array, function)".into()) }
+    }),
+    builtin("filter", "(items: [T], pred: fn) -> [T]", "The elements for which `pred` is truthy.", |args, vm| {
+        if let (Some(Value::Array(arr)), Some(callback)) = (args.first(), args.get(1)) {
+            let (arr, callback) = (arr.clone(), callback.clone());
             let mut res = Vec::new();
-            let mut i = start;
-            if step > 0 {
-                while i < end { res.push(Value::Int(i)); i += step; }
-            } else if step < 0 {
-                while i > end { res.push(Value::Int(i)); i += step; }
+            for item in arr {
+                if vm.call_function_now(callback.clone(), vec![item.clone()])?.is_truthy() {
+                    res.push(item);
+                }
             }
             Ok(Value::Array(res))
-        },
-        "enumerate" => {
-             if let Some(Value::Array(arr)) = args.first() {
-                 let res = arr.iter().enumerate().map(|(i, v)| {
-                     Value::Array(vec![Value::Int(i as i64), v.clone()])
-                 }).collect();
-                 Ok(Value::Array(res))
-             } else { Ok(Value::Null) }
-        },
-        "zip" => {
-             if let (Some(Value::Array(a)), Some(Value::Array(b))) = (args.get(0), args.get(1)) {
-                 let len = a.len().min(b.len());
-                 let mut res = Vec::with_capacity(len);
-                 for i in 0..len {
-                     res.push(Value::Array(vec![a[i].clone(), b[i].clone()]));
-                 }
-                 Ok(Value::Array(res))
-             } else { Ok(Value::Null) }
-        },
-        "map" => {
-            if let (Some(Value::Array(arr)), Some(callback)) = (args.get(0), args.get(1)) {
-                let (arr, callback) = (arr.clone(), callback.clone());
-                let mut res = Vec::with_capacity(arr.len());
-                for item in arr {
-                    res.push(vm.call_function_now(callback.clone(), vec![item])?);
-                }
-                Ok(Value::Array(res))
-            } else { Err("map expects (array, function)".into()) }
-        },
-        "filter" => {
-            if let (Some(Value::Array(arr)), Some(callback)) = (args.get(0), args.get(1)) {
-                let (arr, callback) = (arr.clone(), callback.clone());
-                let mut res = Vec::new();
-                for item in arr {
-                    if vm.call_function_now(callback.clone(), vec![item.clone()])?.is_truthy() {
-                        res.push(item);
-                    }
-                }
-                Ok(Value::Array(res))
-            } else { Err("filter expects (array, function)".into()) }
-        },
-        "reduce" => {
-            if let (Some(Value::Array(arr)), Some(callback), Some(init)) = (args.get(0), args.get(1), args.get(2)) {
-                let (arr, callback) = (arr.clone(), callback.clone());
-                let mut acc = init.clone();
-                for item in arr {
-                    acc = vm.call_function_now(callback.clone(), vec![acc, item])?;
-                }
-                Ok(acc)
-            } else { Err("reduce expects (array, function, initial_value)".into()) }
-        },
-
-        // --- Conversions ---
-        "byte" => {
-             let n = args.first().and_then(|v| v.as_int().ok()).unwrap_or(0);
-             Ok(Value::Int((n & 0xFF) as i64))
-        },
-        "char" => {
-             let n = args.first().and_then(|v| v.as_int().ok()).unwrap_or(0);
-             if let Some(c) = std::char::from_u32(n as u32) {
-                 Ok(Value::Str(c.to_string()))
-             } else {
-                 Err(format!("Invalid char code: {}", n))
-             }
-        },
-
-        "Math.cos" => Ok(Value::Float(args.first().and_then(|v| v.as_float().ok()).unwrap_or(0.0).cos())),
-        "Math.tan" => Ok(Value::Float(args.first().and_then(|v| v.as_float().ok()).unwrap_or(0.0).tan())),
-        "Math.sqrt" => Ok(Value::Float(args.first().and_then(|v| v.as_float().ok()).unwrap_or(0.0).sqrt())),
-        "Math.abs" => match args.first() {
-             Some(Value::Int(i)) => Ok(Value::Int(i.abs())),
-             Some(Value::Float(f)) => Ok(Value::Float(f.abs())),
-             _ => Ok(Value::Int(0)),
-        },
-        "Math.floor" => Ok(Value::Float(args.first().and_then(|v| v.as_float().ok()).unwrap_or(0.0).floor())),
-        "Math.ceil" => Ok(Value::Float(args.first().and_then(|v| v.as_float().ok()).unwrap_or(0.0).ceil())),
-        "Math.round" => Ok(Value::Float(args.first().and_then(|v| v.as_float().ok()).unwrap_or(0.0).round())),
-        "Math.pow" => {
-            let base = args.get(0).and_then(|v| v.as_float().ok()).unwrap_or(0.0);
-            let exp = args.get(1).and_then(|v| v.as_float().ok()).unwrap_or(1.0);
-            Ok(Value::Float(base.powf(exp)))
-        }
-        "Math.min" => {
-             match (args.get(0), args.get(1)) {
-                 (Some(Value::Int(a)), Some(Value::Int(b))) => Ok(Value::Int(*a.min(b))),
-                 (Some(val_a), Some(val_b)) => {
-                     let a = val_a.as_float().unwrap_or(0.0);
-                     let b = val_b.as_float().unwrap_or(0.0);
-                     Ok(Value::Float(a.min(b)))
-                 }
-                 _ => Ok(Value::Null)
-             }
-        },
-        "Math.max" => {
-             match (args.get(0), args.get(1)) {
-                 (Some(Value::Int(a)), Some(Value::Int(b))) => Ok(Value::Int(*a.max(b))),
-                 (Some(val_a), Some(val_b)) => {
-                     let a = val_a.as_float().unwrap_or(0.0);
-                     let b = val_b.as_float().unwrap_or(0.0);
-                     Ok(Value::Float(a.max(b)))
-                 }
-                 _ => Ok(Value::Null)
-             }
-        }
-        "Math.random" => Ok(Value::Float(rand::random())), 
-        "Math.random_range" => {
-            let min = args.get(0).and_then(|v| v.as_float().ok()).unwrap_or(0.0);
-            let max = args.get(1).and_then(|v| v.as_float().ok()).unwrap_or(1.0);
-            // Basic implementation using standard rand if available or pseudo logic
-            // Assuming rand crate is available as per line 91
-            let r = rand::random::<f64>(); 
-            Ok(Value::Float(min + r * (max - min)))
-        }
-        
-        // --- System Module ---
-        "System.time" | "time.now" => {
-            modules::system::call("time", args) // Fixed path and semicolon
-        },
-        s if s.starts_with("System.") => {
-            let func = s.strip_prefix("System.").unwrap();
-            modules::system::call(func, args)
-        },
-        s if s.starts_with("sys.") => {
-             let func = s.strip_prefix("sys.").unwrap();
-             modules::system::call(func, args)
-        },
-
-        // --- Net Module ---
-        s if s.starts_with("net.") => {
-            let func = s.strip_prefix("net.").unwrap();
-            modules::net::call(func, args)
-        },
-        s if s.starts_with("Net.") => {
-            let func = s.strip_prefix("Net.").unwrap();
-            modules::net::call(func, args)
-        },
-
-        // --- Crypto Module ---
-        s if s.starts_with("Crypto.") => {
-             let func = s.strip_prefix("Crypto.").unwrap();
-             modules::crypto::call(func, args)
-        },
-        "crypto.hash" => modules::crypto::call("hash", args),
-        "crypto.hmac" => modules::crypto::call("hmac", args),
-        "crypto.uuid" => modules::crypto::call("uuid", args),
-        "crypto.random_bytes" => modules::crypto::call("random_bytes", args),
-
-        // --- Audio Module ---
-        s if s.starts_with("Audio.") => {
-             let func = s.strip_prefix("Audio.").unwrap();
-             modules::audio::call(func, args)
-        },
-        "audio.play_oneshot" => modules::audio::call("play_oneshot", args),
-        "audio.play_stream" => modules::audio::call("play_stream", args),
-
-        // --- Data Module ---
-        s if s.starts_with("data.") => {
-             let func = s.strip_prefix("data.").unwrap();
-             modules::data::call(func, args)
-        },
-        // JSON & CSV
-        "json.parse" => modules::data::call("json.parse", args),
-        // "json.stringify" handled by data module if matching exact name, or:
-        s if s.starts_with("json.") => modules::data::call(s, args),
-        s if s.starts_with("csv.") => modules::data::call(s, args),
-
-        // --- DB Module ---
-        s if s.starts_with("db.") => {
-             let func = s.strip_prefix("db.").unwrap();
-             modules::db::call(func, args)
-        },
-        // DB Connection Methods (db_conn:ID.method)
-        s if s.starts_with("db_conn:") => {
-             modules::db::call(s, args)
-        },
-
-        // --- Graph Module ---
-        s if s.starts_with("graph.") => {
-             let func = s.strip_prefix("graph.").unwrap();
-             modules::graph::call(func, args, vm)
-        },
-
-        // --- LLM Module ---
-        s if s.starts_with("llm.") => {
-             let func = s.strip_prefix("llm.").unwrap();
-             modules::llm::call(func, args, vm)
-        },
-
-        // --- Term Module ---
-        s if s.starts_with("term.") => {
-             let func = s.strip_prefix("term.").unwrap();
-             modules::term::call(func, args)
-        },
-
-        // --- Env Module Override/Expansion ---
-        "env.cwd" => {
-            std::env::current_dir()
-                .map(|p| Value::Str(p.to_string_lossy().to_string()))
-                .map_err(|e| e.to_string())
-        },
-        "env.set_cwd" => {
-            if let Some(Value::Str(path)) = args.first() {
-                std::env::set_current_dir(path)
-                    .map(|_| Value::Null)
-                    .map_err(|e| e.to_string())
-            } else { Err("Expected path string".into()) }
-        },
-        "env.user" => modules::system::call("user_name", args),
-        "env.hostname" => modules::system::call("hostname", args),
-
-        
-        // --- Env Module ---
-        "env.get" => {
-             if let Some(Value::Str(key)) = args.first() {
-                 match std::env::var(key) {
-                     Ok(val) => Ok(Value::Str(val)),
-                     Err(_) => Ok(Value::Null),
-                 }
-             } else { Ok(Value::Null) }
-        },
-        "env.set" => {
-             if let (Some(Value::Str(key)), Some(Value::Str(val))) = (args.get(0), args.get(1)) {
-                 unsafe { std::env::set_var(key, val); }
-                 Ok(Value::Null)
-             } else { Ok(Value::Null) }
-        },
-        "env.args" => {
-             let args: Vec<Value> = std::env::args().map(Value::Str).collect();
-             Ok(Value::Array(args))
-        },
-
-        // --- Vector Math (Arrays) ---
-        "math.vector2" => {
-             let x = args.get(0).unwrap_or(&Value::Float(0.0)).clone();
-             let y = args.get(1).unwrap_or(&Value::Float(0.0)).clone();
-             Ok(Value::Array(vec![x, y]))
-        },
-        "math.vector3" => {
-             let x = args.get(0).unwrap_or(&Value::Float(0.0)).clone();
-             let y = args.get(1).unwrap_or(&Value::Float(0.0)).clone();
-             let z = args.get(2).unwrap_or(&Value::Float(0.0)).clone();
-             Ok(Value::Array(vec![x, y, z]))
-        },
-        "math.dot" => {
-             if let (Some(Value::Array(a)), Some(Value::Array(b))) = (args.get(0), args.get(1)) {
-                 let mut sum = 0.0;
-                 for (v1, v2) in a.iter().zip(b.iter()) {
-                     sum += v1.as_float().unwrap_or(0.0) * v2.as_float().unwrap_or(0.0);
-                 }
-                 Ok(Value::Float(sum))
-             } else { Ok(Value::Float(0.0)) }
-        },
-        "math.cross" => {
-             if let (Some(Value::Array(a)), Some(Value::Array(b))) = (args.get(0), args.get(1)) {
-                 if a.len() >= 3 && b.len() >= 3 {
-                     let ax = a[0].as_float().unwrap_or(0.0); let ay = a[1].as_float().unwrap_or(0.0); let az = a[2].as_float().unwrap_or(0.0);
-                     let bx = b[0].as_float().unwrap_or(0.0); let by = b[1].as_float().unwrap_or(0.0); let bz = b[2].as_float().unwrap_or(0.0);
-                     Ok(Value::Array(vec![
-                         Value::Float(ay * bz - az * by),
-                         Value::Float(az * bx - ax * bz),
-                         Value::Float(ax * by - ay * bx)
-                     ]))
-                 } else { Ok(Value::Null) }
-             } else { Ok(Value::Null) }
-        },
-        "math.length_sq" => {
-             if let Some(Value::Array(a)) = args.first() {
-                 let sum: f64 = a.iter().map(|v| { let f = v.as_float().unwrap_or(0.0); f*f }).sum();
-                 Ok(Value::Float(sum))
-             } else { Ok(Value::Float(0.0)) }
-        },
-        "math.length" | "math.distance" => { // Distance handled if 2 args? No, distance takes 2 args. Split logic.
-             // This branch only for 1 arg calls to length? No, regex logic for builtin names is exact match.
-             // I need separate cases.
-             if name == "math.distance" {
-                 if let (Some(Value::Array(a)), Some(Value::Array(b))) = (args.get(0), args.get(1)) {
-                     let mut sum = 0.0;
-                     for (v1, v2) in a.iter().zip(b.iter()) {
-                         let diff = v1.as_float().unwrap_or(0.0) - v2.as_float().unwrap_or(0.0);
-                         sum += diff * diff;
-                     }
-                     Ok(Value::Float(sum.sqrt()))
-                 } else { Ok(Value::Float(0.0)) }
-             } else {
-                 // math.length
-                 if let Some(Value::Array(a)) = args.first() {
-                     let sum: f64 = a.iter().map(|v| { let f = v.as_float().unwrap_or(0.0); f*f }).sum();
-                     Ok(Value::Float(sum.sqrt()))
-                 } else { Ok(Value::Float(0.0)) }
-             }
-        },
-        "math.distance_sq" => {
-             if let (Some(Value::Array(a)), Some(Value::Array(b))) = (args.get(0), args.get(1)) {
-                 let mut sum = 0.0;
-                 for (v1, v2) in a.iter().zip(b.iter()) {
-                     let diff = v1.as_float().unwrap_or(0.0) - v2.as_float().unwrap_or(0.0);
-                     sum += diff * diff;
-                 }
-                 Ok(Value::Float(sum))
-             } else { Ok(Value::Float(0.0)) }
-        },
-        "math.normalize" => {
-             if let Some(Value::Array(a)) = args.first() {
-                 let sum: f64 = a.iter().map(|v| { let f = v.as_float().unwrap_or(0.0); f*f }).sum();
-                 let len = sum.sqrt();
-                 if len == 0.0 { Ok(Value::Array(a.clone())) } else {
-                     let res = a.iter().map(|v| Value::Float(v.as_float().unwrap_or(0.0) / len)).collect();
-                     Ok(Value::Array(res))
-                 }
-             } else { Ok(Value::Null) }
-        },
-
-        // --- String Methods ---
-        "str.len" => Ok(Value::Int(args.first().and_then(|v| if let Value::Str(s) = v { Some(s.len() as i64) } else { None }).unwrap_or(0))),
-        "str.upper" => {
-             if let Some(Value::Str(s)) = args.first() {
-                 Ok(Value::Str(s.to_uppercase()))
-             } else { Ok(Value::Null) }
-        },
-        "str.lower" => {
-             if let Some(Value::Str(s)) = args.first() {
-                 Ok(Value::Str(s.to_lowercase()))
-             } else { Ok(Value::Null) }
-        },
-        "str.trim" => {
-             if let Some(Value::Str(s)) = args.first() {
-                 Ok(Value::Str(s.trim().to_string()))
-             } else { Ok(Value::Null) }
-        },
-        "str.contains" => {
-             if let (Some(Value::Str(s)), Some(Value::Str(sub))) = (args.get(0), args.get(1)) {
-                 Ok(Value::Bool(s.contains(sub)))
-             } else { Ok(Value::Bool(false)) }
-        },
-        "str.replace" => {
-             if let (Some(Value::Str(s)), Some(Value::Str(old)), Some(Value::Str(new))) = (args.get(0), args.get(1), args.get(2)) {
-                 Ok(Value::Str(s.replace(old, new)))
-             } else { Ok(Value::Null) }
-        },
-        "str.split" => {
-             if let (Some(Value::Str(s)), Some(Value::Str(delim))) = (args.get(0), args.get(1)) {
-                 let parts = s.split(delim).map(|p| Value::Str(p.to_string())).collect();
-                 Ok(Value::Array(parts))
-             } else { Ok(Value::Null) }
-        },
-        
-        // --- Array Methods ---
-        "array.len" => Ok(Value::Int(args.first().and_then(|v| if let Value::Array(a) = v { Some(a.len() as i64) } else { None }).unwrap_or(0))),
-        "array.push" => {
-            // Note: This requires mutable access to VM memory/registers which call_builtin doesn't strictly have access to via 'args' slice references alone if we want to modify the original array in place. 
-            // However, KiVM passes arrays by reference (sort of, via Clone currently in VM loop... wait).
-            // VM::step implementation of Call passes `args` as CLONED values currently.
-            // "let func_val = frame.reg(instr.a).clone();"
-            // "for i in 0..arg_count { args.push(frame.reg(instr.a + 1 + i as u8).clone()); }"
-            // This means `array.push` won't work in-place with current VM architecture unless we change how arrays are passed/stored (Heap/Rc).
-            // For now, we returns a NEW array with the item pushed (functional style) or we accept that it's limited.
-            // Requirement says "Expanding Library". Let's assume functional for now or just log warning.
-            // Actually, let's implement it returning the new array.
-            if let Some(Value::Array(arr)) = args.first() {
-                 let mut new_arr = arr.clone();
-                 if let Some(val) = args.get(1) {
-                     new_arr.push(val.clone());
-                 }
-                 Ok(Value::Array(new_arr))
-            } else {
-                 Ok(Value::Null)
+        } else { Err("filter expects (array, function)".into()) }
+    }),
+    builtin("reduce", "(items: [T], f: fn, init: U) -> U", "Folds the array left to right with `f(acc, element)`, starting from `init`.", |args, vm| {
+        if let (Some(Value::Array(arr)), Some(callback), Some(init)) = (args.first(), args.get(1), args.get(2)) {
+            let (arr, callback) = (arr.clone(), callback.clone());
+            let mut acc = init.clone();
+            for item in arr {
+                acc = vm.call_function_now(callback.clone(), vec![acc, item])?;
             }
-        },
-        "array.pop" => {
-            if let Some(Value::Array(arr)) = args.first() {
-                 let mut new_arr = arr.clone();
-                 new_arr.pop();
-                 Ok(Value::Array(new_arr))
-            } else {
-                 Ok(Value::Null)
+            Ok(acc)
+        } else { Err("reduce expects (array, function, initial_value)".into()) }
+    }),
+];
+
+fn float_arg(args: &[Value], i: usize, default: f64) -> f64 {
+    args.get(i).and_then(|v| v.as_float().ok()).unwrap_or(default)
+}
+
+/// `Math.*` scalar functions and the `math.*` vector helpers (vectors are
+/// plain arrays of numbers).
+pub const MATH: &[Builtin] = &[
+    builtin("Math.abs", "(x: int | float) -> int | float", "Absolute value, keeping the argument's type.", |args, _| match args.first() {
+        Some(Value::Int(i)) => Ok(Value::Int(i.abs())),
+        Some(Value::Float(f)) => Ok(Value::Float(f.abs())),
+        _ => Ok(Value::Int(0)),
+    }),
+    builtin("Math.ceil", "(x: float) -> float", "Smallest integral value >= x.", |args, _| Ok(Value::Float(float_arg(args, 0, 0.0).ceil()))),
+    builtin("Math.floor", "(x: float) -> float", "Largest integral value <= x.", |args, _| Ok(Value::Float(float_arg(args, 0, 0.0).floor()))),
+    builtin("Math.round", "(x: float) -> float", "Nearest integral value, halves away from zero.", |args, _| Ok(Value::Float(float_arg(args, 0, 0.0).round()))),
+    builtin("Math.pow", "(base: float, exp: float) -> float", "`base` raised to `exp`.", |args, _| Ok(Value::Float(float_arg(args, 0, 0.0).powf(float_arg(args, 1, 1.0))))),
+    builtin("Math.sqrt", "(x: float) -> float", "Square root (NaN for negatives).", |args, _| Ok(Value::Float(float_arg(args, 0, 0.0).sqrt()))),
+    builtin("Math.sin", "(radians: float) -> float", "Sine.", |args, _| Ok(Value::Float(float_arg(args, 0, 0.0).sin()))),
+    builtin("Math.cos", "(radians: float) -> float", "Cosine.", |args, _| Ok(Value::Float(float_arg(args, 0, 0.0).cos()))),
+    builtin("Math.tan", "(radians: float) -> float", "Tangent.", |args, _| Ok(Value::Float(float_arg(args, 0, 0.0).tan()))),
+    builtin("Math.asin", "(x: float) -> float", "Arcsine, in radians.", |args, _| Ok(Value::Float(float_arg(args, 0, 0.0).asin()))),
+    builtin("Math.acos", "(x: float) -> float", "Arccosine, in radians.", |args, _| Ok(Value::Float(float_arg(args, 0, 0.0).acos()))),
+    builtin("Math.atan2", "(y: float, x: float) -> float", "Angle of the point (x, y), in radians.", |args, _| Ok(Value::Float(float_arg(args, 0, 0.0).atan2(float_arg(args, 1, 0.0))))),
+    builtin("Math.deg", "(radians: float) -> float", "Radians to degrees.", |args, _| Ok(Value::Float(float_arg(args, 0, 0.0).to_degrees()))),
+    builtin("Math.rad", "(degrees: float) -> float", "Degrees to radians.", |args, _| Ok(Value::Float(float_arg(args, 0, 0.0).to_radians()))),
+    builtin("Math.cbrt", "(x: float) -> float", "Cube root.", |args, _| Ok(Value::Float(float_arg(args, 0, 0.0).cbrt()))),
+    builtin("Math.exp", "(x: float) -> float", "e raised to `x`.", |args, _| Ok(Value::Float(float_arg(args, 0, 0.0).exp()))),
+    builtin("Math.log", "(x: float) -> float", "Natural logarithm.", |args, _| Ok(Value::Float(float_arg(args, 0, 0.0).ln()))),
+    builtin("Math.log10", "(x: float) -> float", "Base-10 logarithm.", |args, _| Ok(Value::Float(float_arg(args, 0, 0.0).log10()))),
+    builtin("Math.clamp", "(x: T, lo: T, hi: T) -> T", "`x` limited to [lo, hi]; ints stay ints, anything else becomes float.", |args, _| {
+        match (args.first(), args.get(1), args.get(2)) {
+            (Some(Value::Int(v)), Some(Value::Int(mn)), Some(Value::Int(mx))) => Ok(Value::Int(*v.max(mn).min(mx))),
+            (Some(v), Some(mn), Some(mx)) => {
+                let vf = v.as_float().unwrap_or(0.0);
+                let mnf = mn.as_float().unwrap_or(0.0);
+                let mxf = mx.as_float().unwrap_or(1.0);
+                Ok(Value::Float(vf.max(mnf).min(mxf)))
             }
-        },
-        "array.contains" => {
-             if let Some(Value::Array(arr)) = args.first() {
-                 let target = args.get(1).unwrap_or(&Value::Null);
-                 // Need generic logic to compare Values. Assuming simple equality for now.
-                 // Value likely derives PartialEq
-                 let found = arr.iter().any(|v| v == target);
-                 Ok(Value::Bool(found))
-             } else {
-                 Ok(Value::Bool(false))
-             }
-        },
-        "array.reverse" => {
-             if let Some(Value::Array(arr)) = args.first() {
-                 let mut new_arr = arr.clone();
-                 new_arr.reverse();
-                 Ok(Value::Array(new_arr))
-             } else { Ok(Value::Null) }
-        },
-        "array.sort" => {
-             if let Some(Value::Array(arr)) = args.first() {
-                 let mut new_arr = arr.clone();
-                 // Naive sort: convert to string/int comparison or try partial_cmp.
-                 // Assuming partial_cmp exists for Value or we implement a lambda.
-                 // For now, let's sort assuming homogenous types if possible, or string fallback.
-                 // This is tricky without `Ord`.
-                 // Let's assume we can't easily sort mixed types and just do nothing or error?
-                 // Or basic string sort.
-                 // Let's try to unwrap partial_cmp for floats/ints.
-                 new_arr.sort_by(|a, b| {
-                     a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal)
-                 });
-                 Ok(Value::Array(new_arr))
-             } else { Ok(Value::Null) }
-        },
-
-        // --- Type Conversions ---
-        "str" => Ok(Value::Str(format!("{}", args.first().cloned().unwrap_or(Value::Null)))),
-        "int" => match args.first() {
-            Some(Value::Int(n)) => Ok(Value::Int(*n)),
-            Some(Value::Float(f)) => Ok(Value::Int(*f as i64)),
-            Some(Value::Str(s)) => s.parse::<i64>().map(Value::Int).map_err(|_| format!("Cannot convert '{}' to int", s)),
-            Some(Value::Bool(b)) => Ok(Value::Int(if *b { 1 } else { 0 })),
-            _ => Ok(Value::Int(0)),
-        },
-        "float" => match args.first() {
-            Some(Value::Float(f)) => Ok(Value::Float(*f)),
-            Some(Value::Int(n)) => Ok(Value::Float(*n as f64)),
-            Some(Value::Str(s)) => s.parse::<f64>().map(Value::Float).map_err(|_| format!("Cannot convert '{}' to float", s)),
-            _ => Ok(Value::Float(0.0)),
-        },
-        "bool" => Ok(Value::Bool(args.first().map(|v| v.is_truthy()).unwrap_or(false))),
-        
-        // --- OS Detection & System Layer ---
-        "system.thread.spawn" => {
-            if args.is_empty() {
-                return Ok(modules::system::call("thread.spawn", args)?); // Error path fallback
+            _ => Ok(Value::Null),
+        }
+    }),
+    builtin("Math.lerp", "(a: float, b: float, t: float) -> float", "Linear interpolation `a + (b - a) * t`.", |args, _| {
+        let (a, b, t) = (float_arg(args, 0, 0.0), float_arg(args, 1, 0.0), float_arg(args, 2, 0.0));
+        Ok(Value::Float(a + (b - a) * t))
+    }),
+    builtin("Math.min", "(a: T, b: T) -> T", "The smaller number; int if both are ints, float otherwise.", |args, _| match (args.first(), args.get(1)) {
+        (Some(Value::Int(a)), Some(Value::Int(b))) => Ok(Value::Int(*a.min(b))),
+        (Some(a), Some(b)) => Ok(Value::Float(a.as_float().unwrap_or(0.0).min(b.as_float().unwrap_or(0.0)))),
+        _ => Ok(Value::Null),
+    }),
+    builtin("Math.max", "(a: T, b: T) -> T", "The larger number; int if both are ints, float otherwise.", |args, _| match (args.first(), args.get(1)) {
+        (Some(Value::Int(a)), Some(Value::Int(b))) => Ok(Value::Int(*a.max(b))),
+        (Some(a), Some(b)) => Ok(Value::Float(a.as_float().unwrap_or(0.0).max(b.as_float().unwrap_or(0.0)))),
+        _ => Ok(Value::Null),
+    }),
+    builtin("Math.random", "() -> float", "Uniform random float in [0, 1).", |_, _| Ok(Value::Float(rand::random()))),
+    builtin("Math.random_range", "(lo: float, hi: float) -> float", "Uniform random float in [lo, hi).", |args, _| {
+        let (min, max) = (float_arg(args, 0, 0.0), float_arg(args, 1, 1.0));
+        Ok(Value::Float(min + rand::random::<f64>() * (max - min)))
+    }),
+    builtin("math.vector2", "(x: float?, y: float?) -> [float]", "The vector `[x, y]` (missing components are 0.0).", |args, _| {
+        let x = args.first().cloned().unwrap_or(Value::Float(0.0));
+        let y = args.get(1).cloned().unwrap_or(Value::Float(0.0));
+        Ok(Value::Array(vec![x, y]))
+    }),
+    builtin("math.vector3", "(x: float?, y: float?, z: float?) -> [float]", "The vector `[x, y, z]` (missing components are 0.0).", |args, _| {
+        let x = args.first().cloned().unwrap_or(Value::Float(0.0));
+        let y = args.get(1).cloned().unwrap_or(Value::Float(0.0));
+        let z = args.get(2).cloned().unwrap_or(Value::Float(0.0));
+        Ok(Value::Array(vec![x, y, z]))
+    }),
+    builtin("math.dot", "(a: [float], b: [float]) -> float", "Dot product of two vectors.", |args, _| {
+        if let (Some(Value::Array(a)), Some(Value::Array(b))) = (args.first(), args.get(1)) {
+            let sum = a.iter().zip(b).map(|(x, y)| x.as_float().unwrap_or(0.0) * y.as_float().unwrap_or(0.0)).sum();
+            Ok(Value::Float(sum))
+        } else { Ok(Value::Float(0.0)) }
+    }),
+    builtin("math.cross", "(a: [float], b: [float]) -> [float]", "Cross product of two 3D vectors; null if either is shorter.", |args, _| {
+        if let (Some(Value::Array(a)), Some(Value::Array(b))) = (args.first(), args.get(1)) {
+            if a.len() >= 3 && b.len() >= 3 {
+                let ax = a[0].as_float().unwrap_or(0.0); let ay = a[1].as_float().unwrap_or(0.0); let az = a[2].as_float().unwrap_or(0.0);
+                let bx = b[0].as_float().unwrap_or(0.0); let by = b[1].as_float().unwrap_or(0.0); let bz = b[2].as_float().unwrap_or(0.0);
+                Ok(Value::Array(vec![
+                    Value::Float(ay * bz - az * by),
+                    Value::Float(az * bx - ax * bz),
+                    Value::Float(ax * by - ay * bx),
+                ]))
+            } else { Ok(Value::Null) }
+        } else { Ok(Value::Null) }
+    }),
+    builtin("math.length_sq", "(v: [float]) -> float", "Squared length of a vector.", |args, _| match args.first() {
+        Some(Value::Array(a)) => Ok(Value::Float(length_sq(a))),
+        _ => Ok(Value::Float(0.0)),
+    }),
+    builtin("math.length", "(v: [float]) -> float", "Length of a vector.", |args, _| match args.first() {
+        Some(Value::Array(a)) => Ok(Value::Float(length_sq(a).sqrt())),
+        _ => Ok(Value::Float(0.0)),
+    }),
+    builtin("math.distance", "(a: [float], b: [float]) -> float", "Euclidean distance between two vectors.", |args, _| match (args.first(), args.get(1)) {
+        (Some(Value::Array(a)), Some(Value::Array(b))) => Ok(Value::Float(distance_sq(a, b).sqrt())),
+        _ => Ok(Value::Float(0.0)),
+    }),
+    builtin("math.distance_sq", "(a: [float], b: [float]) -> float", "Squared Euclidean distance between two vectors.", |args, _| match (args.first(), args.get(1)) {
+        (Some(Value::Array(a)), Some(Value::Array(b))) => Ok(Value::Float(distance_sq(a, b))),
+        _ => Ok(Value::Float(0.0)),
+    }),
+    builtin("math.normalize", "(v: [float]) -> [float]", "`v` scaled to length 1 (unchanged if it has length 0).", |args, _| {
+        if let Some(Value::Array(a)) = args.first() {
+            let len = length_sq(a).sqrt();
+            if len == 0.0 { Ok(Value::Array(a.clone())) } else {
+                Ok(Value::Array(a.iter().map(|v| Value::Float(v.as_float().unwrap_or(0.0) / len)).collect()))
             }
+        } else { Ok(Value::Null) }
+    }),
+];
 
-            let func_val = args[0].clone();
-            let thread_args: Vec<Value> = args.iter().skip(1).cloned().collect();
-            let cloned_program = vm.clone_program();
-
-            let mut id_lock = NEXT_THREAD_ID.lock().map_err(|_| "Failed to lock Thread ID generator")?;
-            let thread_id = *id_lock;
-            *id_lock += 1;
-
-            let handle = std::thread::spawn(move || {
-                let mut child_vm = VM::new(cloned_program);
-                // Trigger execution loop for this function natively inside the child vm instance.
-                child_vm.run_function(func_val, thread_args)
-            });
-
-            THREAD_REGISTRY.lock().map_err(|_| "Thread mapping lock failed")?.insert(thread_id, handle);
-            
-            // Return simulation of Kinetix Result<T, E> mapping to Ok(ID)
-            let mut res = std::collections::HashMap::new();
-            res.insert("ok".to_string(), Value::Int(thread_id));
-            Ok(Value::Map(res))
-        },
-        "system.thread.join" => {
-            if let Some(Value::Int(id)) = args.first() {
-                let handle_opt = THREAD_REGISTRY.lock().map_err(|_| "Thread mapping lock failed")?.remove(id);
-                if let Some(handle) = handle_opt {
-                    match handle.join() {
-                        Ok(thread_res) => {
-                            match thread_res {
-                                Ok(val) => {
-                                    let mut res = std::collections::HashMap::new();
-                                    res.insert("ok".to_string(), val);
-                                    Ok(Value::Map(res))
-                                },
-                                Err(e) => {
-                                    let mut res = std::collections::HashMap::new();
-                                    res.insert("err".to_string(), Value::Str(e));
-                                    Ok(Value::Map(res))
-                                }
-                            }
-                        },
-                        Err(_) => {
-                            let mut res = std::collections::HashMap::new();
-                            res.insert("err".to_string(), Value::Str("Native thread panicked".to_string()));
-                            Ok(Value::Map(res))
-                        }
-                    }
-                } else {
-                    let mut res = std::collections::HashMap::new();
-                    res.insert("err".to_string(), Value::Str(format!("Thread ID {} not found or already joined", id)));
-                    Ok(Value::Map(res))
-                }
-            } else {
-                return Ok(modules::system::call("thread.join", args)?); // Error path fallback
+fn length_sq(v: &[Value]) -> f64 {
+    v.iter().map(|x| { let f = x.as_float().unwrap_or(0.0); f * f }).sum()
+}
+
+fn distance_sq(a: &[Value], b: &[Value]) -> f64 {
+    a.iter().zip(b).map(|(x, y)| { let d = x.as_float().unwrap_or(0.0) - y.as_float().unwrap_or(0.0); d * d }).sum()
+}
+
+/// String and array methods (`"a,b".split(",")`, `items.sort()`): the
+/// receiver comes first in the signature. Not registered as globals.
+pub const METHODS: &[Builtin] = &[
+    builtin("str.len", "(s: str) -> int", "Length in bytes.", |args, _| match args.first() {
+        Some(Value::Str(s)) => Ok(Value::Int(s.len() as i64)),
+        _ => Ok(Value::Int(0)),
+    }),
+    builtin("str.upper", "(s: str) -> str", "Uppercase copy.", |args, _| match args.first() {
+        Some(Value::Str(s)) => Ok(Value::Str(s.to_uppercase())),
+        _ => Ok(Value::Null),
+    }),
+    builtin("str.lower", "(s: str) -> str", "Lowercase copy.", |args, _| match args.first() {
+        Some(Value::Str(s)) => Ok(Value::Str(s.to_lowercase())),
+        _ => Ok(Value::Null),
+    }),
+    builtin("str.trim", "(s: str) -> str", "Copy without leading and trailing whitespace.", |args, _| match args.first() {
+        Some(Value::Str(s)) => Ok(Value::Str(s.trim().to_string())),
+        _ => Ok(Value::Null),
+    }),
+    builtin("str.contains", "(s: str, sub: str) -> bool", "Whether `sub` occurs in `s`.", |args, _| match (args.first(), args.get(1)) {
+        (Some(Value::Str(s)), Some(Value::Str(sub))) => Ok(Value::Bool(s.contains(sub.as_str()))),
+        _ => Ok(Value::Bool(false)),
+    }),
+    builtin("str.replace", "(s: str, old: str, new: str) -> str", "Copy with every `old` replaced by `new`.", |args, _| match (args.first(), args.get(1), args.get(2)) {
+        (Some(Value::Str(s)), Some(Value::Str(old)), Some(Value::Str(new))) => Ok(Value::Str(s.replace(old.as_str(), new))),
+        _ => Ok(Value::Null),
+    }),
+    builtin("str.split", "(s: str, sep: str) -> [str]", "The parts of `s` between occurrences of `sep`.", |args, _| match (args.first(), args.get(1)) {
+        (Some(Value::Str(s)), Some(Value::Str(delim))) => Ok(Value::Array(s.split(delim.as_str()).map(|p| Value::Str(p.to_string())).collect())),
+        _ => Ok(Value::Null),
+    }),
+    builtin("array.len", "(items: [T]) -> int", "Number of elements.", |args, _| match args.first() {
+        Some(Value::Array(a)) => Ok(Value::Int(a.len() as i64)),
+        _ => Ok(Value::Int(0)),
+    }),
+    // Arrays are passed by value, so the array "mutators" return a new array.
+    builtin("array.push", "(items: [T], value: T) -> [T]", "A new array with `value` appended.", |args, _| match args.first() {
+        Some(Value::Array(arr)) => {
+            let mut new_arr = arr.clone();
+            if let Some(val) = args.get(1) {
+                new_arr.push(val.clone());
             }
-        },
-        "system.defer" => {
-            // Build 26: Register a deferred closure on the current call frame
-            if let Some(closure) = args.first() {
-                match closure {
-                    Value::Function(_) | Value::NativeFn(_) => {
-                        vm.push_defer(closure.clone());
-                        Ok(Value::Null)
-                    },
-                    _ => Err("system.defer requires a function argument".to_string()),
-                }
-            } else {
-                Err("system.defer requires a function argument".to_string())
+            Ok(Value::Array(new_arr))
+        }
+        _ => Ok(Value::Null),
+    }),
+    builtin("array.pop", "(items: [T]) -> [T]", "A new array without the last element.", |args, _| match args.first() {
+        Some(Value::Array(arr)) => {
+            let mut new_arr = arr.clone();
+            new_arr.pop();
+            Ok(Value::Array(new_arr))
+        }
+        _ => Ok(Value::Null),
+    }),
+    builtin("array.contains", "(items: [T], value: T) -> bool", "Whether an element equals `value`.", |args, _| match args.first() {
+        Some(Value::Array(arr)) => {
+            let target = args.get(1).unwrap_or(&Value::Null);
+            Ok(Value::Bool(arr.iter().any(|v| v == target)))
+        }
+        _ => Ok(Value::Bool(false)),
+    }),
+    builtin("array.reverse", "(items: [T]) -> [T]", "A new array in reverse order.", |args, _| match args.first() {
+        Some(Value::Array(arr)) => {
+            let mut new_arr = arr.clone();
+            new_arr.reverse();
+            Ok(Value::Array(new_arr))
+        }
+        _ => Ok(Value::Null),
+    }),
+    builtin("array.sort", "(items: [T]) -> [T]", "A new array in ascending order.", |args, _| match args.first() {
+        Some(Value::Array(arr)) => {
+            let mut new_arr = arr.clone();
+            new_arr.sort_by(Value::sort_cmp);
+            Ok(Value::Array(new_arr))
+        }
+        _ => Ok(Value::Null),
+    }),
+];
+
+/// Tables whose entries the VM registers as globals: the core and math
+/// tables and each module's `BUILTINS`. Globals, `help` and dispatch all read
+/// the one map built from them, so they can't drift apart.
+const GLOBAL_TABLES: &[&[Builtin]] = &[BUILTINS, MATH, modules::system::BUILTINS, modules::net::BUILTINS, modules::crypto::BUILTINS, modules::audio::BUILTINS, modules::data::BUILTINS, modules::io::BUILTINS, modules::db::BUILTINS, modules::graph::BUILTINS, modules::llm::BUILTINS, modules::term::BUILTINS, modules::stats::BUILTINS, modules::matrix::BUILTINS, modules::template::BUILTINS, modules::i18n::BUILTINS, modules::decimal::BUILTINS, modules::fs::BUILTINS, modules::archive::BUILTINS, modules::ipc::BUILTINS, modules::random::BUILTINS, modules::sb::BUILTINS, modules::markup::BUILTINS, crate::log::BUILTINS];
+
+/// Every builtin, globals first. The first entry for a name wins.
+fn all_builtins() -> impl Iterator<Item = &'static Builtin> {
+    GLOBAL_TABLES.iter().flat_map(|t| t.iter()).chain(METHODS)
+}
+
+lazy_static::lazy_static! {
+    static ref REGISTRY: HashMap<String, &'static Builtin> = {
+        let mut map = HashMap::new();
+        for b in all_builtins() {
+            map.entry(registry_key(b.name)).or_insert(b);
+        }
+        map
+    };
+}
+
+/// Lookup key: the module segment is case-insensitive (`Math.sqrt`,
+/// `math.sqrt`) and `sys.` is `system.`; the compiler capitalizes module
+/// names when it flattens `math.sqrt(x)`.
+pub(crate) fn registry_key(name: &str) -> String {
+    match name.split_once('.') {
+        Some((module, rest)) => {
+            let module = module.to_ascii_lowercase();
+            let module = if module == "sys" { "system".to_string() } else { module };
+            format!("{}.{}", module, rest)
+        }
+        None => name.to_string(),
+    }
+}
+
+/// Names of the global builtins, as registered by the VM (methods excluded).
+pub fn builtin_names() -> impl Iterator<Item = &'static str> {
+    GLOBAL_TABLES.iter().flat_map(|t| t.iter()).map(|b| b.name)
+}
+
+/// The registered builtin for `name`, if any; also accepts `string.`/`list.`
+/// for `str.`/`array.`.
+pub fn lookup(name: &str) -> Option<&'static Builtin> {
+    let key = registry_key(name);
+    if let Some(b) = REGISTRY.get(&key) {
+        return Some(b);
+    }
+    let alias = if let Some(rest) = key.strip_prefix("string.") {
+        format!("str.{}", rest)
+    } else if let Some(rest) = key.strip_prefix("list.") {
+        format!("array.{}", rest)
+    } else {
+        return None;
+    };
+    REGISTRY.get(&alias).copied()
+}
+
+/// What `help(name)` prints: signature, capability and description, or
+/// the closest names when `name` isn't a registered builtin.
+pub fn help_text(name: &str) -> String {
+    if let Some(info) = lookup(name) {
+        let caps = info.capability.as_ref().map_or("none".to_string(), |c| c.to_string());
+        return format!("{}{}\n  requires: {}\n  {}", info.name, info.signature, caps, info.doc);
+    }
+    let needle = name.to_ascii_lowercase();
+    let last = needle.rsplit('.').next().unwrap_or(&needle);
+    let similar: Vec<&str> = all_builtins()
+        .map(|b| b.name)
+        .filter(|n| !last.is_empty() && n.to_ascii_lowercase().contains(last))
+        .collect();
+    if similar.is_empty() {
+        format!("No builtin named '{}'", name)
+    } else {
+        format!("No builtin named '{}'. Similar: {}", name, similar.join(", "))
+    }
+}
+
+use crate::vm::VM;
+
+/// Builtins that `LoadMethod` falls back to for any receiver that doesn't
+/// define a method of the same name (`color.to_string()`, `point.fields()`).
+pub const DERIVED_METHODS: &[&str] = &["to_string", "fields"];
+
+/// Declared type name of a struct/class instance or enum value (the
+/// `__class__`/`__enum__` tag the compiler stamps on every such map).
+pub fn declared_type_name(map: &std::collections::HashMap<String, Value>) -> Option<&str> {
+    match map.get("__class__").or_else(|| map.get("__enum__")) {
+        Some(Value::Str(name)) => Some(name.as_str()),
+        _ => None,
+    }
+}
+
+/// Whether a map key is a compiler-internal tag (`__class__`, `__enum__`, ...)
+/// rather than a user-visible field.
+pub(crate) fn is_hidden_key(key: &str) -> bool {
+    key.starts_with("__") && key.ends_with("__")
+}
+
+/// `to_string` formatting: `Red`, `Circle(2.5)` for enum values,
+/// `Point { x: 1, y: 2 }` (fields in alphabetical order) for struct/class
+/// instances, plain `Display` for everything else.
+fn derived_to_string(value: &Value) -> String {
+    match value {
+        Value::Map(map) if map.contains_key("__enum__") => {
+            let variant = match map.get("__variant__") {
+                Some(Value::Str(v)) => v.clone(),
+                _ => "?".to_string(),
+            };
+            match map.get("__payload__") {
+                Some(Value::Null) | None => variant,
+                Some(payload) => format!("{}({})", variant, derived_to_string(payload)),
+            }
+        }
+        Value::Map(map) if map.contains_key("__class__") => {
+            let name = declared_type_name(map).unwrap_or("?");
+            let mut keys: Vec<&String> = map.keys().filter(|k| !is_hidden_key(k)).collect();
+            if keys.is_empty() {
+                return format!("{} {{}}", name);
             }
-        },
-        name if name.starts_with("system.") => modules::system::call(&name[7..], args),
+            keys.sort();
+            let body = keys.iter()
+                .map(|k| format!("{}: {}", k, derived_to_string(&map[*k])))
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!("{} {{ {} }}", name, body)
+        }
+        other => format!("{}", other),
+    }
+}
+
+static STRICT_DEFAULT: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
 
-        _ => Err(format!("Unknown built-in: {}", name)),
+/// Makes every VM created afterwards start with `strict_builtins` set; the
+/// CLI calls this once for `--strict-builtins`.
+pub fn set_strict_default(strict: bool) {
+    STRICT_DEFAULT.store(strict, std::sync::atomic::Ordering::Relaxed);
+}
+
+pub(crate) fn strict_default() -> bool {
+    STRICT_DEFAULT.load(std::sync::atomic::Ordering::Relaxed)
+}
+
+/// Type name as reported by `typeof`.
+pub fn type_name(value: &Value) -> &str {
+    match value {
+        Value::Int(_) => "int",
+        Value::Float(_) => "float",
+        Value::Str(_) => "string",
+        Value::Bool(_) => "bool",
+        Value::Null => "null",
+        Value::Array(_) => "array",
+        Value::Function(_) => "function",
+        Value::NativeFn(_) => "native_function",
+        Value::NativeModule(_) => "module",
+        Value::BoundMethod(_, _) => "bound_method",
+        Value::Map(map) => declared_type_name(map).unwrap_or("map"),
+        Value::Native(obj) => obj.type_name(),
     }
 }
 
+/// Whether `value` fits a signature type: `str`, `int`, `float` (ints
+/// too), `bool`, `fn`, `[..]` arrays, `{..}` maps and `a | b` unions.
+/// Type variables, `any` and anything else accept every value.
+fn matches_type(ty: &str, value: &Value) -> bool {
+    ty.split(" | ").any(|alt| match alt.trim() {
+        "str" => matches!(value, Value::Str(_)),
+        "int" => matches!(value, Value::Int(_)),
+        "float" => matches!(value, Value::Int(_) | Value::Float(_)),
+        "bool" => matches!(value, Value::Bool(_)),
+        "fn" => matches!(value, Value::Function(_) | Value::NativeFn(_) | Value::BoundMethod(_, _)),
+        t if t.starts_with('[') => matches!(value, Value::Array(_)),
+        t if t.starts_with('{') => matches!(value, Value::Map(_)),
+        _ => true,
+    })
+}
+
+/// Checks `args` against the signature of `b`: the count, then each
+/// argument's type. Optional parameters also accept null.
+fn check_args(b: &Builtin, args: &[Value]) -> Result<(), String> {
+    let (required, max) = b.arity();
+    if args.len() < required || max.is_some_and(|max| args.len() > max) {
+        let expected = match max {
+            Some(max) if max == required => max.to_string(),
+            Some(max) => format!("{} to {}", required, max),
+            None => format!("at least {}", required),
+        };
+        return Err(format!("{}{}: expected {} argument(s), got {}", b.name, b.signature, expected, args.len()));
+    }
+    let params = params_of(b.signature);
+    for (i, arg) in args.iter().enumerate() {
+        let param = params[i.min(params.len() - 1)];
+        let (pname, ty) = param.split_once(':').map_or(("", param), |(n, t)| (n.trim(), t.trim()));
+        let optional = ty.ends_with('?');
+        let ty = ty.trim_end_matches("...").trim_end_matches('?');
+        if !(matches_type(ty, arg) || optional && matches!(arg, Value::Null)) {
+            return Err(format!("{}{}: argument {} ({}) must be {}, got {}", b.name, b.signature, i + 1, pname, ty, type_name(arg)));
+        }
+    }
+    Ok(())
+}
+
+/// Calls the registered builtin `name`.
+///
+/// Arguments that don't fit a registered builtin's signature never reach
+/// its handler: the call evaluates to `{err: "<name><signature>: ..."}`,
+/// the same shape the system and net builtins report failures with, or
+/// raises that message as a runtime error when `vm.strict_builtins` is set.
+pub fn call_builtin(name: &str, args: &[Value], vm: &mut VM) -> Result<Value, String> {
+    if let Some(audit) = &vm.audit
+        && let Some((builtin, capability)) = gated(name)
+    {
+        audit.record(&builtin, &capability, args)?;
+    }
+    if let Some(b) = lookup(name) {
+        if let Err(msg) = check_args(b, args) {
+            if vm.strict_builtins {
+                return Err(msg);
+            }
+            let mut res = HashMap::new();
+            res.insert("err".to_string(), Value::Str(msg));
+            return Ok(Value::Map(res));
+        }
+        return (b.handler)(args, vm);
+    }
+    Err(format!("Unknown built-in: {}", name))
+}
+
+/// The canonical name and capability of `name` when it is a registered
+/// builtin that needs one.
+fn gated(name: &str) -> Option<(String, Capability)> {
+    let b = lookup(name)?;
+    b.capability.clone().map(|cap| (b.name.to_string(), cap))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -809,6 +828,47 @@ mod tests {
         assert!(matches!(r, Value::Str(s) if s == "int"));
     }
 
+    #[test]
+    fn test_typeof_reports_declared_class_and_enum_names() {
+        let mut vm = dummy_vm();
+        let mut point = std::collections::HashMap::new();
+        point.insert("__class__".to_string(), Value::Str("Point".into()));
+        point.insert("x".to_string(), Value::Int(1));
+        let r = call_builtin("typeof", &[Value::Map(point)], &mut vm).unwrap();
+        assert!(matches!(r, Value::Str(s) if s == "Point"));
+
+        let mut color = std::collections::HashMap::new();
+        color.insert("__enum__".to_string(), Value::Str("Color".into()));
+        color.insert("__variant__".to_string(), Value::Str("Red".into()));
+        let r = call_builtin("typeof", &[Value::Map(color)], &mut vm).unwrap();
+        assert!(matches!(r, Value::Str(s) if s == "Color"));
+
+        let r = call_builtin("typeof", &[Value::Map(std::collections::HashMap::new())], &mut vm).unwrap();
+        assert!(matches!(r, Value::Str(s) if s == "map"));
+    }
+
+    #[test]
+    fn test_to_string_and_fields_skip_hidden_keys() {
+        let mut vm = dummy_vm();
+        let mut point = std::collections::HashMap::new();
+        point.insert("__class__".to_string(), Value::Str("Point".into()));
+        point.insert("y".to_string(), Value::Int(2));
+        point.insert("x".to_string(), Value::Int(1));
+        let point = Value::Map(point);
+
+        let r = call_builtin("to_string", std::slice::from_ref(&point), &mut vm).unwrap();
+        assert_eq!(r, Value::Str("Point { x: 1, y: 2 }".into()));
+        let r = call_builtin("fields", &[point], &mut vm).unwrap();
+        assert_eq!(r, Value::Array(vec![Value::Str("x".into()), Value::Str("y".into())]));
+
+        let mut shape = std::collections::HashMap::new();
+        shape.insert("__enum__".to_string(), Value::Str("Shape".into()));
+        shape.insert("__variant__".to_string(), Value::Str("Circle".into()));
+        shape.insert("__payload__".to_string(), Value::Float(2.5));
+        let r = call_builtin("to_string", &[Value::Map(shape)], &mut vm).unwrap();
+        assert_eq!(r, Value::Str("Circle(2.5)".into()));
+    }
+
     #[test]
     fn test_len() {
         let mut vm = dummy_vm();
@@ -826,4 +886,98 @@ mod tests {
         let mut vm = dummy_vm();
         assert!(call_builtin("assert", &[Value::Bool(false)], &mut vm).is_err());
     }
+
+    #[test]
+    fn test_float_formatting_is_round_trip_and_format_fixes_digits() {
+        let mut vm = dummy_vm();
+        let str_of = |v: Value, vm: &mut VM| match call_builtin("str", &[v], vm).unwrap() {
+            Value::Str(s) => s,
+            other => panic!("str returned {}", other),
+        };
+        assert_eq!(str_of(Value::Float(2.0), &mut vm), "2.0");
+        assert_eq!(str_of(Value::Float(0.1 + 0.2), &mut vm), "0.30000000000000004");
+        assert_eq!(str_of(Value::Float(1e21), &mut vm), "1e21");
+        assert_eq!(str_of(Value::Float(-0.5), &mut vm), "-0.5");
+
+        let r = call_builtin("format", &[Value::Float(0.1 + 0.2), Value::Int(2)], &mut vm).unwrap();
+        assert!(matches!(r, Value::Str(s) if s == "0.30"));
+        let r = call_builtin("format", &[Value::Int(3), Value::Int(1)], &mut vm).unwrap();
+        assert!(matches!(r, Value::Str(s) if s == "3.0"));
+        assert!(call_builtin("format", &[Value::Float(1.0), Value::Int(-1)], &mut vm).is_err());
+    }
+
+    #[test]
+    fn test_help_metadata_matches_capability_map() {
+        let mut names = std::collections::HashSet::new();
+        for info in all_builtins() {
+            assert!(names.insert(registry_key(info.name)), "duplicate builtin {}", info.name);
+            assert!(info.signature.starts_with('('), "{} signature", info.name);
+            assert_eq!(lookup(info.name).map(|b| b.signature), Some(info.signature), "{} not registered", info.name);
+        }
+        let syscalls = kinetix_kicomp::capability::static_syscall_map();
+        for info in all_builtins() {
+            let mapped = syscalls.iter().find(|(n, _)| *n == info.name).map(|(_, c)| c);
+            assert_eq!(info.capability.as_ref(), mapped, "capability of {}", info.name);
+        }
+
+        let text = help_text("str.split");
+        assert!(text.starts_with("str.split(s: str, sep: str) -> [str]"), "{}", text);
+        assert!(text.contains("requires: none"));
+        assert!(help_text("math.sqrt").starts_with("Math.sqrt("));
+        assert!(help_text("string.upper").starts_with("str.upper("));
+        assert!(help_text("system.exec").contains("requires: OsExecute"));
+        assert!(help_text("net.tcp.nope").contains("No builtin named 'net.tcp.nope'"));
+        assert!(help_text("random.int").starts_with("random.int(min: int, max: int) -> int\n  requires: none"));
+
+        assert_eq!(lookup("print").unwrap().arity(), (0, None));
+        assert_eq!(lookup("pad_left").unwrap().arity(), (2, Some(3)));
+        assert_eq!(lookup("system.thread.spawn").unwrap().arity(), (1, None));
+        assert_eq!(lookup("math.vector3").unwrap().arity(), (0, Some(3)));
+        assert_eq!(lookup("Math.random").unwrap().arity(), (0, Some(0)));
+        assert_eq!(lookup("sys.exec").unwrap().name, "system.exec");
+
+        let mut vm = dummy_vm();
+        let v = Value::Array(vec![Value::Float(3.0), Value::Float(4.0)]);
+        assert_eq!(call_builtin("Math.normalize", std::slice::from_ref(&v), &mut vm).unwrap(),
+            Value::Array(vec![Value::Float(0.6), Value::Float(0.8)]));
+        assert_eq!(call_builtin("math.sqrt", &[Value::Float(9.0)], &mut vm).unwrap(), Value::Float(3.0));
+        assert_eq!(call_builtin("Json.stringify", &[Value::Int(1)], &mut vm).unwrap(), call_builtin("json.stringify", &[Value::Int(1)], &mut vm).unwrap());
+        assert!(call_builtin("nope", &[], &mut vm).unwrap_err().contains("Unknown built-in: nope"));
+
+        call_builtin("help", &[Value::Str("trim".into())], &mut vm).unwrap();
+        assert!(vm.output[0].starts_with("trim(s: str) -> str"));
+        call_builtin("io.echo", &[Value::Null], &mut vm).unwrap();
+        call_builtin("io.echo", &[Value::Int(3)], &mut vm).unwrap();
+        assert_eq!(vm.output[1..], ["3"]);
+    }
+
+    #[test]
+    fn test_bad_arguments_return_err_values_or_raise_when_strict() {
+        let mut vm = dummy_vm();
+        let err_of = |v: Value| match v {
+            Value::Map(m) => match m.get("err") {
+                Some(Value::Str(msg)) => msg.clone(),
+                other => panic!("expected err, got {:?}", other),
+            },
+            other => panic!("expected err map, got {:?}", other),
+        };
+        let msg = err_of(call_builtin("pad_left", &[Value::Int(5), Value::Int(3)], &mut vm).unwrap());
+        assert_eq!(msg, "pad_left(s: str, width: int, pad: str?) -> str: argument 1 (s) must be str, got int");
+        let msg = err_of(call_builtin("split", &[Value::Str("a,b".into())], &mut vm).unwrap());
+        assert!(msg.ends_with("expected 2 argument(s), got 1"), "{}", msg);
+        let msg = err_of(call_builtin("system.thread.spawn", &[], &mut vm).unwrap());
+        assert!(msg.ends_with("expected at least 1 argument(s), got 0"), "{}", msg);
+        let msg = err_of(call_builtin("random.int", &[Value::Int(1)], &mut vm).unwrap());
+        assert_eq!(msg, "random.int(min: int, max: int) -> int: expected 2 argument(s), got 1");
+
+        // Optional parameters, variadics, int-for-float and unions still pass.
+        assert_eq!(call_builtin("pad_left", &[Value::Str("a".into()), Value::Int(3), Value::Null], &mut vm).unwrap(), Value::Str("  a".into()));
+        assert_eq!(call_builtin("Math.sqrt", &[Value::Int(4)], &mut vm).unwrap(), Value::Float(2.0));
+        assert_eq!(call_builtin("min", &[Value::Int(4), Value::Int(2)], &mut vm).unwrap(), Value::Int(2));
+        call_builtin("print", &[Value::Int(1), Value::Str("a".into())], &mut vm).unwrap();
+
+        vm.strict_builtins = true;
+        let err = call_builtin("pad_left", &[Value::Int(5), Value::Int(3)], &mut vm).unwrap_err();
+        assert!(err.starts_with("pad_left(s: str, width: int, pad: str?) -> str: argument 1"), "{}", err);
+    }
 }
@@ -2,4 +2,15 @@
 /// Register-based bytecode interpreter.
 
 pub mod vm;
+pub mod audit;
+pub mod log;
+#[cfg(feature = "otlp")]
+pub mod otlp;
+pub mod quota;
+pub mod watchdog;
+#[cfg(feature = "trace")]
+pub mod trace;
 pub mod builtins;
+pub mod native;
+pub mod pretty;
+pub mod bench_suite;
@@ -0,0 +1,465 @@
+//! `archive.zip.*` and `archive.tar.*`: create, list and extract archives.
+
+use crate::builtins::{builtin, Builtin};
+use crate::quota::{Metered, Resource};
+use crate::vm::Value;
+use kinetix_kicomp::capability::Capability;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+pub const BUILTINS: &[Builtin] = &[
+    builtin("archive.zip.create", "(out: str, paths: [str], base: str?) -> int", "Zips `paths` (relative to `base`, default `.`) into `out`; returns how many files it stored.", |args, _| {
+        let (out, inputs) = create_args("zip", args)?;
+        zip_create(out, &inputs).map_err(|e| format!("archive.zip.create('{}'): {}", out, e))
+    }).needs(Capability::FsWrite),
+    builtin("archive.zip.list", "(archive: str) -> [str]", "Entry names in the zip, directories ending in `/`.", |args, _| {
+        let path = str_arg(&args[0]);
+        zip_list(path).map(names).map_err(|e| format!("archive.zip.list('{}'): {}", path, e))
+    }).needs(Capability::FsRead),
+    builtin("archive.zip.extract", "(archive: str, dest: str, only: [str]?) -> [str]", "Extracts the zip (or the `only` entries) under `dest`; returns the files written.", |args, _| {
+        let (path, dest, only) = extract_args(args)?;
+        zip_extract(path, dest, &only).map(names).map_err(|e| format!("archive.zip.extract('{}'): {}", path, e))
+    }).needs(Capability::FsWrite),
+    builtin("archive.tar.create", "(out: str, paths: [str], base: str?) -> int", "Tars `paths` (relative to `base`, default `.`) into `out`, gzipped for `.gz`/`.tgz`; returns how many files it stored.", |args, _| {
+        let (out, inputs) = create_args("tar", args)?;
+        tar_create(out, &inputs).map_err(|e| format!("archive.tar.create('{}'): {}", out, e))
+    }).needs(Capability::FsWrite),
+    builtin("archive.tar.list", "(archive: str) -> [str]", "Entry names in the tarball, directories ending in `/`.", |args, _| {
+        let path = str_arg(&args[0]);
+        tar_list(path).map(names).map_err(|e| format!("archive.tar.list('{}'): {}", path, e))
+    }).needs(Capability::FsRead),
+    builtin("archive.tar.extract", "(archive: str, dest: str, only: [str]?) -> [str]", "Extracts the tarball (or the `only` entries) under `dest`; returns the files written.", |args, _| {
+        let (path, dest, only) = extract_args(args)?;
+        tar_extract(path, dest, &only).map(names).map_err(|e| format!("archive.tar.extract('{}'): {}", path, e))
+    }).needs(Capability::FsWrite),
+];
+
+fn str_arg(value: &Value) -> &str {
+    let Value::Str(s) = value else { unreachable!("checked by the signature") };
+    s
+}
+
+fn names(names: Vec<String>) -> Value {
+    Value::Array(names.into_iter().map(Value::Str).collect())
+}
+
+/// A file or directory to store: its name in the archive and where it is.
+struct Input {
+    name: String,
+    source: PathBuf,
+    is_dir: bool,
+}
+
+/// Each path (a file, or a directory with everything under it) is stored by
+/// its name relative to `base` (default `.`), so
+/// `archive.zip.create("site.zip", ["public"])` holds `public/index.html`.
+fn create_args<'a>(kind: &str, args: &'a [Value]) -> Result<(&'a str, Vec<Input>), String> {
+    let out = str_arg(&args[0]);
+    let Value::Array(paths) = &args[1] else { unreachable!("checked by the signature") };
+    let base = Path::new(args.get(2).map_or(".", |b| if let Value::Str(b) = b { b.as_str() } else { "." }));
+    let mut inputs = Vec::new();
+    for path in paths {
+        let Value::Str(path) = path else { return Err(format!("archive.{}.create() expects paths as strings, got {}", kind, path)) };
+        let name = safe_name(path).map_err(|e| format!("archive.{}.create(): {}", kind, e))?;
+        collect(base.join(&name), &name, &mut inputs).map_err(|e| format!("archive.{}.create(): '{}': {}", kind, path, e))?;
+    }
+    Ok((out, inputs))
+}
+
+/// Adds `source` and, for a directory, everything under it in name order.
+/// Symlinks are skipped rather than followed.
+fn collect(source: PathBuf, name: &Path, inputs: &mut Vec<Input>) -> io::Result<()> {
+    let meta = source.symlink_metadata()?;
+    let entry_name = name.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/");
+    if meta.is_dir() {
+        if !entry_name.is_empty() {
+            inputs.push(Input { name: entry_name, source: source.clone(), is_dir: true });
+        }
+        let mut children: Vec<_> = std::fs::read_dir(&source)?.flatten().map(|e| e.file_name()).collect();
+        children.sort();
+        for child in children {
+            collect(source.join(&child), &name.join(&child), inputs)?;
+        }
+    } else if meta.is_file() {
+        inputs.push(Input { name: entry_name, source, is_dir: false });
+    }
+    Ok(())
+}
+
+fn extract_args(args: &[Value]) -> Result<(&str, &str, Vec<String>), String> {
+    let only = match args.get(2) {
+        Some(Value::Array(items)) => items.iter().map(|v| match v {
+            Value::Str(s) => Ok(s.trim_end_matches('/').to_string()),
+            other => Err(format!("archive extract expects `only` as strings, got {}", other)),
+        }).collect::<Result<_, _>>()?,
+        _ => Vec::new(),
+    };
+    Ok((str_arg(&args[0]), str_arg(&args[1]), only))
+}
+
+/// `name` as a relative path that stays inside the directory it is joined
+/// to: no root, drive prefix or `..`. Extraction vets every entry name with
+/// this before writing anything, so a hostile archive can't write outside
+/// `dest` or leave a partial extraction behind; links and device entries are
+/// never extracted either.
+fn safe_name(name: &str) -> Result<PathBuf, String> {
+    let path = Path::new(name);
+    let mut clean = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => clean.push(part),
+            Component::CurDir => {}
+            _ => return Err(format!("unsafe path '{}' (absolute or outside the destination)", name)),
+        }
+    }
+    Ok(clean)
+}
+
+/// Whether an `only` list (empty: everything) selects the entry `name`, by
+/// exact name, by directory (`"docs"` takes everything under `docs/`) or by
+/// glob pattern (`"*.kix"`).
+fn selected(only: &[String], name: &str) -> bool {
+    let name = name.trim_end_matches('/');
+    only.is_empty() || only.iter().any(|want| {
+        name == want
+            || name.strip_prefix(want.as_str()).is_some_and(|rest| rest.starts_with('/'))
+            || glob::Pattern::new(want).is_ok_and(|p| p.matches(name))
+    })
+}
+
+/// The archive file `out`, its size charged to the `fs-write-bytes` quota.
+fn created(out: &str) -> Result<Metered<File>, String> {
+    Ok(Metered::new(File::create(out).map_err(|e| e.to_string())?, Resource::FsWriteBytes))
+}
+
+fn zip_create(out: &str, inputs: &[Input]) -> Result<Value, String> {
+    let mut zip = zip::ZipWriter::new(BufWriter::new(created(out)?));
+    let mut stored = 0;
+    for input in inputs {
+        let mut options = zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            if let Ok(meta) = input.source.metadata() {
+                options = options.unix_permissions(meta.permissions().mode() & 0o777);
+            }
+        }
+        if input.is_dir {
+            zip.add_directory(input.name.as_str(), options).map_err(|e| e.to_string())?;
+            continue;
+        }
+        zip.start_file(input.name.as_str(), options).map_err(|e| e.to_string())?;
+        let mut file = File::open(&input.source).map_err(|e| format!("{}: {}", input.source.display(), e))?;
+        io::copy(&mut file, &mut zip).map_err(|e| e.to_string())?;
+        stored += 1;
+    }
+    zip.finish().map_err(|e| e.to_string())?;
+    Ok(Value::Int(stored))
+}
+
+fn zip_open(path: &str) -> Result<zip::ZipArchive<BufReader<File>>, String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    zip::ZipArchive::new(BufReader::new(file)).map_err(|e| e.to_string())
+}
+
+fn zip_list(path: &str) -> Result<Vec<String>, String> {
+    let mut zip = zip_open(path)?;
+    (0..zip.len()).map(|i| zip.by_index(i).map(|f| f.name().to_string()).map_err(|e| e.to_string())).collect()
+}
+
+fn zip_extract(path: &str, dest: &str, only: &[String]) -> Result<Vec<String>, String> {
+    let mut zip = zip_open(path)?;
+    for i in 0..zip.len() {
+        let entry = zip.by_index(i).map_err(|e| e.to_string())?;
+        safe_name(entry.name())?;
+    }
+    let mut written = Vec::new();
+    for i in 0..zip.len() {
+        let mut entry = zip.by_index(i).map_err(|e| e.to_string())?;
+        let name = entry.name().to_string();
+        let is_link = entry.unix_mode().is_some_and(|mode| mode & 0o170000 == 0o120000);
+        if !selected(only, &name) || is_link {
+            continue;
+        }
+        let target = Path::new(dest).join(safe_name(&name)?);
+        if entry.is_dir() {
+            std::fs::create_dir_all(&target).map_err(|e| format!("{}: {}", target.display(), e))?;
+            continue;
+        }
+        let mode = entry_mode(entry.unix_mode());
+        write_file(&target, &mut entry, mode)?;
+        written.push(name);
+    }
+    Ok(written)
+}
+
+#[cfg_attr(not(unix), allow(unused_variables))]
+fn write_file(target: &Path, data: &mut impl Read, mode: Option<u32>) -> Result<(), String> {
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("{}: {}", parent.display(), e))?;
+    }
+    let mut file = Metered::new(File::create(target).map_err(|e| format!("{}: {}", target.display(), e))?, Resource::FsWriteBytes);
+    io::copy(data, &mut file).map_err(|e| format!("{}: {}", target.display(), e))?;
+    #[cfg(unix)]
+    if let Some(mode) = mode {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(target, std::fs::Permissions::from_mode(mode)).map_err(|e| format!("{}: {}", target.display(), e))?;
+    }
+    Ok(())
+}
+
+/// Permission bits worth restoring: rwx only, never setuid/setgid/sticky.
+fn entry_mode(mode: Option<u32>) -> Option<u32> {
+    mode.map(|m| m & 0o777).filter(|m| *m != 0)
+}
+
+const BLOCK: usize = 512;
+
+/// Longest GNU long-name or pax record `tar_read` buffers in memory.
+const MAX_META: u64 = 1 << 20;
+
+fn tar_create(out: &str, inputs: &[Input]) -> Result<Value, String> {
+    let file = BufWriter::new(created(out)?);
+    let mut sink: Box<dyn Write> = if out.ends_with(".gz") || out.ends_with(".tgz") {
+        Box::new(flate2::write::GzEncoder::new(file, flate2::Compression::default()))
+    } else {
+        Box::new(file)
+    };
+    let mut stored = 0;
+    for input in inputs {
+        let meta = input.source.metadata().map_err(|e| format!("{}: {}", input.source.display(), e))?;
+        let size = if input.is_dir { 0 } else { meta.len() };
+        let mtime = meta.modified().ok()
+            .and_then(|t| t.duration_since(std::time::SystemTime::UNIX_EPOCH).ok())
+            .map_or(0, |d| d.as_secs());
+        #[cfg(unix)]
+        let mode = {
+            use std::os::unix::fs::PermissionsExt;
+            meta.permissions().mode() & 0o777
+        };
+        #[cfg(not(unix))]
+        let mode = if input.is_dir { 0o755 } else { 0o644 };
+        let header = tar_header(&input.name, input.is_dir, size, mode, mtime)?;
+        sink.write_all(&header).map_err(|e| e.to_string())?;
+        if input.is_dir {
+            continue;
+        }
+        let mut file = File::open(&input.source).map_err(|e| format!("{}: {}", input.source.display(), e))?;
+        let copied = io::copy(&mut (&mut file).take(size), &mut sink).map_err(|e| e.to_string())?;
+        if copied != size {
+            return Err(format!("{} changed size while being archived", input.source.display()));
+        }
+        sink.write_all(&[0; BLOCK][..padding(size)]).map_err(|e| e.to_string())?;
+        stored += 1;
+    }
+    sink.write_all(&[0; BLOCK * 2]).map_err(|e| e.to_string())?;
+    sink.flush().map_err(|e| e.to_string())?;
+    Ok(Value::Int(stored))
+}
+
+fn padding(size: u64) -> usize {
+    (BLOCK - (size as usize % BLOCK)) % BLOCK
+}
+
+/// A ustar header. Names over 100 bytes are split into the 155-byte prefix
+/// field at a `/`.
+fn tar_header(name: &str, is_dir: bool, size: u64, mode: u32, mtime: u64) -> Result<[u8; BLOCK], String> {
+    let name = if is_dir { format!("{}/", name) } else { name.to_string() };
+    let (prefix, short) = if name.len() <= 100 {
+        ("", name.as_str())
+    } else {
+        let split = name[..name.len().min(156)].rfind('/')
+            .filter(|i| name.len() - i - 1 <= 100 && *i > 0)
+            .ok_or_else(|| format!("'{}' is too long for a tar entry", name))?;
+        (&name[..split], &name[split + 1..])
+    };
+    let mut header = [0u8; BLOCK];
+    let mut put = |at: usize, bytes: &[u8]| header[at..at + bytes.len()].copy_from_slice(bytes);
+    put(0, short.as_bytes());
+    put(100, format!("{:07o}\0", mode).as_bytes());
+    put(108, b"0000000\0");
+    put(116, b"0000000\0");
+    if size > 0o77777777777 {
+        return Err(format!("'{}' is too large for a tar entry", name));
+    }
+    put(124, format!("{:011o}\0", size).as_bytes());
+    put(136, format!("{:011o}\0", mtime.min(0o77777777777)).as_bytes());
+    put(148, b"        ");
+    put(156, if is_dir { b"5" } else { b"0" });
+    put(257, b"ustar\x0000");
+    put(345, prefix.as_bytes());
+    let sum: u32 = header.iter().map(|b| u32::from(*b)).sum();
+    header[148..156].copy_from_slice(format!("{:06o}\0 ", sum).as_bytes());
+    Ok(header)
+}
+
+fn octal(field: &[u8]) -> Result<u64, String> {
+    if field.first().is_some_and(|b| b & 0x80 != 0) {
+        return Err("base-256 tar numbers are not supported".to_string());
+    }
+    let text = String::from_utf8_lossy(field);
+    let text = text.trim_matches(|c: char| c == '\0' || c == ' ');
+    if text.is_empty() {
+        return Ok(0);
+    }
+    u64::from_str_radix(text, 8).map_err(|_| format!("bad number '{}' in tar header", text))
+}
+
+fn cstr(field: &[u8]) -> String {
+    let end = field.iter().position(|b| *b == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).into_owned()
+}
+
+fn tar_list(path: &str) -> Result<Vec<String>, String> {
+    tar_read(path, None)
+}
+
+fn tar_extract(path: &str, dest: &str, only: &[String]) -> Result<Vec<String>, String> {
+    // A first pass vets every name, so a bad entry stops the extraction
+    // before anything is written.
+    tar_read(path, None)?;
+    tar_read(path, Some((Path::new(dest), only)))
+}
+
+/// Reads every entry of the tarball at `path`, gunzipping it when it starts
+/// with gzip's magic bytes, and checks each name. Without `extract` it only
+/// lists; with `(dest, only)` it writes the selected files and directories
+/// and returns the files.
+fn tar_read(path: &str, extract: Option<(&Path, &[String])>) -> Result<Vec<String>, String> {
+    let mut file = BufReader::new(File::open(path).map_err(|e| e.to_string())?);
+    let mut magic = [0u8; 2];
+    let gzipped = file.read(&mut magic).map_err(|e| e.to_string())? == 2 && magic == [0x1f, 0x8b];
+    let file = BufReader::new(File::open(path).map_err(|e| e.to_string())?);
+    let mut input: Box<dyn Read> = if gzipped { Box::new(flate2::read::GzDecoder::new(file)) } else { Box::new(file) };
+
+    let mut names = Vec::new();
+    let mut long_name: Option<String> = None;
+    let mut header = [0u8; BLOCK];
+    loop {
+        input.read_exact(&mut header).map_err(|e| format!("truncated tar: {}", e))?;
+        if header.iter().all(|b| *b == 0) {
+            break;
+        }
+        let stored: u32 = octal(&header[148..156])? as u32;
+        let actual: u32 = header.iter().enumerate().map(|(i, b)| if (148..156).contains(&i) { 32 } else { u32::from(*b) }).sum();
+        if stored != actual {
+            return Err("tar header checksum mismatch".to_string());
+        }
+        let size = octal(&header[124..136])?;
+        let kind = header[156];
+        let mut data = (&mut input).take(size);
+        if matches!(kind, b'L' | b'x') {
+            if size > MAX_META {
+                return Err("tar metadata entry is too large".to_string());
+            }
+            let mut meta = Vec::new();
+            data.read_to_end(&mut meta).map_err(|e| e.to_string())?;
+            long_name = if kind == b'L' { Some(cstr(&meta)) } else { pax_path(&meta).or(long_name) };
+            skip(&mut input, padding(size) as u64)?;
+            continue;
+        }
+        let name = long_name.take().unwrap_or_else(|| {
+            let (short, prefix) = (cstr(&header[..100]), cstr(&header[345..500]));
+            if &header[257..262] == b"ustar" && !prefix.is_empty() { format!("{}/{}", prefix, short) } else { short }
+        });
+        let target = safe_name(&name)?;
+        match (kind, extract) {
+            (b'0' | 0 | b'7', Some((dest, only))) if selected(only, &name) => {
+                write_file(&dest.join(&target), &mut data, entry_mode(Some(octal(&header[100..108])? as u32)))?;
+                names.push(name);
+            }
+            (b'5', Some((dest, only))) if selected(only, &name) => {
+                let dir = dest.join(&target);
+                std::fs::create_dir_all(&dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+            }
+            (_, None) if kind != b'g' => names.push(name),
+            _ => {}
+        }
+        skip(&mut data, u64::MAX)?;
+        skip(&mut input, padding(size) as u64)?;
+    }
+    Ok(names)
+}
+
+/// The `path` record of a pax extended header, if any.
+fn pax_path(records: &[u8]) -> Option<String> {
+    String::from_utf8_lossy(records).lines().find_map(|line| {
+        let (_, record) = line.split_once(' ')?;
+        record.strip_prefix("path=").map(str::to_string)
+    })
+}
+
+fn skip(input: &mut impl Read, n: u64) -> Result<(), String> {
+    io::copy(&mut input.take(n), &mut io::sink()).map(|_| ()).map_err(|e| format!("truncated tar: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("kinetix_archive_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("src/sub")).unwrap();
+        std::fs::write(dir.join("src/main.kix"), "println(1)").unwrap();
+        std::fs::write(dir.join("src/sub/util.kix"), "x".repeat(700)).unwrap();
+        std::fs::write(dir.join("src/notes.txt"), "notes").unwrap();
+        dir
+    }
+
+    fn inputs(dir: &Path) -> Vec<Input> {
+        let mut inputs = Vec::new();
+        collect(dir.join("src"), Path::new("src"), &mut inputs).unwrap();
+        inputs
+    }
+
+    #[test]
+    fn zip_and_tar_round_trip_with_selection() {
+        let dir = scratch("roundtrip");
+        type Create = fn(&str, &[Input]) -> Result<Value, String>;
+        type List = fn(&str) -> Result<Vec<String>, String>;
+        type Extract = fn(&str, &str, &[String]) -> Result<Vec<String>, String>;
+        let formats: [(Create, List, Extract, &str); 3] = [
+            (zip_create, zip_list, zip_extract, "out.zip"),
+            (tar_create, tar_list, tar_extract, "out.tar.gz"),
+            (tar_create, tar_list, tar_extract, "out.tar"),
+        ];
+        for (create, list, extract, file) in formats {
+            let out = dir.join(file);
+            let out = out.to_str().unwrap();
+            assert_eq!(create(out, &inputs(&dir)), Ok(Value::Int(3)));
+            assert_eq!(list(out).unwrap(), ["src/", "src/main.kix", "src/notes.txt", "src/sub/", "src/sub/util.kix"]);
+
+            let dest = dir.join(format!("x_{}", file));
+            let dest = dest.to_str().unwrap();
+            assert_eq!(extract(out, dest, &["*.kix".to_string()]).unwrap(), ["src/main.kix", "src/sub/util.kix"]);
+            assert_eq!(std::fs::read_to_string(Path::new(dest).join("src/sub/util.kix")).unwrap(), "x".repeat(700));
+            assert!(!Path::new(dest).join("src/notes.txt").exists());
+            assert_eq!(extract(out, dest, &["src/sub".to_string()]).unwrap(), ["src/sub/util.kix"]);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unsafe_entry_names_are_rejected() {
+        assert!(safe_name("../evil").is_err());
+        assert!(safe_name("a/../../evil").is_err());
+        assert!(safe_name("/etc/passwd").is_err());
+        assert_eq!(safe_name("./a/b").unwrap(), PathBuf::from("a/b"));
+
+        let dir = scratch("unsafe");
+        let tar = dir.join("evil.tar");
+        let mut bytes = tar_header("../evil.txt", false, 4, 0o644, 0).unwrap().to_vec();
+        bytes.extend_from_slice(b"evil");
+        bytes.extend_from_slice(&[0; BLOCK - 4 + BLOCK * 2]);
+        std::fs::write(&tar, bytes).unwrap();
+        let err = tar_read(tar.to_str().unwrap(), None).unwrap_err();
+        assert!(err.contains("unsafe path '../evil.txt'"), "{}", err);
+
+        let long = format!("{}/{}", "d".repeat(120), "f".repeat(90));
+        let header = tar_header(&long, false, 0, 0o644, 0).unwrap();
+        assert_eq!(format!("{}/{}", cstr(&header[345..500]), cstr(&header[..100])), long);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,3 +1,4 @@
+use crate::builtins::{builtin, Builtin};
 use crate::vm::Value;
 use std::io::BufReader;
 use std::fs::File;
@@ -14,6 +15,12 @@ lazy_static::lazy_static! {
     // For "play_oneshot", we can block or detach?
 }
 
+pub const BUILTINS: &[Builtin] = &[
+    builtin("audio.play_oneshot", "(path: str)", "Plays the sound file at `path` on a background thread.", |a, _| call("play_oneshot", a)),
+    builtin("audio.play_stream", "(path: str)", "Same as audio.play_oneshot.", |a, _| call("play_stream", a)),
+    builtin("audio.set_volume", "(volume: float?)", "Reserved; does nothing yet.", |a, _| call("set_volume", a)),
+];
+
 pub fn call(func_name: &str, args: &[Value]) -> Result<Value, String> {
     match func_name {
         "play_oneshot" | "play_stream" => {
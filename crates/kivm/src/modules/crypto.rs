@@ -1,18 +1,45 @@
+use crate::builtins::{builtin, Builtin};
+use crate::native::{self, NativeMethod, NativeType};
 use crate::vm::Value;
-use sha2::{Sha256, Digest};
+use sha2::{Sha224, Sha256, Sha384, Sha512, Digest};
 use hmac::{Hmac, Mac};
+use kinetix_kicomp::capability::Capability;
+use std::any::Any;
+use std::collections::HashMap;
+use std::io::Read;
+use std::sync::Once;
 use uuid::Uuid;
 
 pub fn call(func_name: &str, args: &[Value]) -> Result<Value, String> {
     match func_name {
         "hash" => {
             if let Some(Value::Str(data)) = args.first() {
-                let mut hasher = Sha256::new();
+                let mut hasher = Hasher::new(algorithm("hash", args.get(1))?)?;
                 hasher.update(data.as_bytes());
-                let result = hasher.finalize();
-                Ok(Value::Str(hex::encode(result)))
+                Ok(Value::Str(hasher.finish()))
             } else { Err("Expected string data".into()) }
         },
+        // Streams the file through the digest in fixed-size chunks, so
+        // memory stays flat however large the file is
+        "hash_file" => {
+            let Some(Value::Str(path)) = args.first() else { return Err("crypto.hash_file() expects a path string".into()) };
+            let mut hasher = Hasher::new(algorithm("hash_file", args.get(1))?)?;
+            let mut file = std::fs::File::open(path).map_err(|e| format!("crypto.hash_file('{}'): {}", path, e))?;
+            let mut chunk = vec![0u8; CHUNK];
+            loop {
+                let n = file.read(&mut chunk).map_err(|e| format!("crypto.hash_file('{}'): {}", path, e))?;
+                if n == 0 {
+                    break;
+                }
+                hasher.update(&chunk[..n]);
+            }
+            Ok(Value::Str(hasher.finish()))
+        },
+        "hasher.new" => {
+            let hasher = Hasher::new(algorithm("hasher.new", args.first())?)?;
+            register_hasher_type();
+            native::wrap(HASHER_TYPE, Some(hasher))
+        },
         "hmac" => {
             if let (Some(Value::Str(key)), Some(Value::Str(data))) = (args.get(0), args.get(1)) {
                  type HmacSha256 = Hmac<Sha256>;
@@ -44,6 +71,153 @@ pub fn call(func_name: &str, args: &[Value]) -> Result<Value, String> {
                  // Ok(Value::Array(arr))
              } else { Err("Expected size int".into()) }
         },
+        // Uniform in [min, max] from the OS generator, for tokens and keys
+        // (`random.int` is reproducible, so guessable)
+        "random_int" => {
+            if let (Some(Value::Int(min)), Some(Value::Int(max))) = (args.first(), args.get(1)) {
+                if min > max {
+                    return Err(format!("crypto.random_int(): min {} is greater than max {}", min, max));
+                }
+                use rand::Rng;
+                Ok(Value::Int(rand::rngs::OsRng.gen_range(*min..=*max)))
+            } else { Err("Expected min and max ints".into()) }
+        },
         _ => Err(format!("Unknown Crypto function: {}", func_name))
     }
 }
+
+pub const BUILTINS: &[Builtin] = &[
+    builtin("crypto.hash", "(data: str, algorithm: str?) -> str", "Hex digest of `data` with sha1, sha224, sha256 (the default), sha384, sha512 or crc32.", |a, _| call("hash", a)),
+    builtin("crypto.hash_file", "(path: str, algorithm: str?) -> str", "Hex digest of the file at `path`, read in chunks so memory stays flat; algorithms as for crypto.hash.", |a, _| call("hash_file", a)).needs(Capability::FsRead),
+    builtin("crypto.hasher.new", "(algorithm: str?) -> Hasher", "An incremental hasher: `update(data)` as pieces arrive, then `finalize()` for the hex digest.", |a, _| call("hasher.new", a)),
+    builtin("crypto.hmac", "(key: str, data: str) -> str", "Hex HMAC-SHA256 of `data` under `key`.", |a, _| call("hmac", a)),
+    builtin("crypto.uuid", "() -> str", "A random (version 4) UUID.", |a, _| call("uuid", a)),
+    builtin("crypto.random_bytes", "(size: int) -> str", "`size` bytes from a secure generator, hex-encoded.", |a, _| call("random_bytes", a)),
+    builtin("crypto.random_int", "(min: int, max: int) -> int", "Uniform in `min..=max` from the OS generator, for tokens and keys.", |a, _| call("random_int", a)),
+];
+
+/// Read size for `crypto.hash_file`.
+const CHUNK: usize = 64 * 1024;
+
+/// Script-visible type name of an incremental hasher handle.
+pub const HASHER_TYPE: &str = "Hasher";
+
+static REGISTER: Once = Once::new();
+
+/// Registers the `Hasher` native type: `update` and `finalize`.
+fn register_hasher_type() {
+    REGISTER.call_once(|| {
+        let mut methods: HashMap<&'static str, NativeMethod> = HashMap::new();
+        methods.insert("update", hasher_update);
+        methods.insert("finalize", hasher_finalize);
+        native::register_type(NativeType { name: HASHER_TYPE, methods, destructor: None });
+    });
+}
+
+/// The digests `crypto.hash`, `crypto.hash_file` and `crypto.hasher.new`
+/// accept, by name.
+enum Hasher {
+    Sha1(sha1::Sha1),
+    Sha224(Sha224),
+    Sha256(Sha256),
+    Sha384(Sha384),
+    Sha512(Sha512),
+    Crc32(crc32fast::Hasher),
+}
+
+fn algorithm<'a>(func: &str, arg: Option<&'a Value>) -> Result<&'a str, String> {
+    match arg {
+        None | Some(Value::Null) => Ok("sha256"),
+        Some(Value::Str(name)) => Ok(name),
+        Some(other) => Err(format!("crypto.{}() expects the algorithm as a str, got {}", func, other)),
+    }
+}
+
+impl Hasher {
+    fn new(algorithm: &str) -> Result<Hasher, String> {
+        Ok(match algorithm.to_ascii_lowercase().replace('-', "").as_str() {
+            "sha1" => Hasher::Sha1(sha1::Sha1::new()),
+            "sha224" => Hasher::Sha224(Sha224::new()),
+            "sha256" => Hasher::Sha256(Sha256::new()),
+            "sha384" => Hasher::Sha384(Sha384::new()),
+            "sha512" => Hasher::Sha512(Sha512::new()),
+            "crc32" => Hasher::Crc32(crc32fast::Hasher::new()),
+            _ => return Err(format!("Unknown hash algorithm '{}' (expected sha256, sha512, sha384, sha224, sha1 or crc32)", algorithm)),
+        })
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Sha1(h) => h.update(data),
+            Hasher::Sha224(h) => h.update(data),
+            Hasher::Sha256(h) => h.update(data),
+            Hasher::Sha384(h) => h.update(data),
+            Hasher::Sha512(h) => h.update(data),
+            Hasher::Crc32(h) => h.update(data),
+        }
+    }
+
+    /// The digest as lowercase hex (crc32: 8 digits, big-endian).
+    fn finish(self) -> String {
+        match self {
+            Hasher::Sha1(h) => hex::encode(h.finalize()),
+            Hasher::Sha224(h) => hex::encode(h.finalize()),
+            Hasher::Sha256(h) => hex::encode(h.finalize()),
+            Hasher::Sha384(h) => hex::encode(h.finalize()),
+            Hasher::Sha512(h) => hex::encode(h.finalize()),
+            Hasher::Crc32(h) => format!("{:08x}", h.finalize()),
+        }
+    }
+}
+
+/// The handle holds `None` once finalized.
+fn hasher(obj: &mut (dyn Any + Send)) -> Result<&mut Option<Hasher>, String> {
+    obj.downcast_mut::<Option<Hasher>>().ok_or_else(|| "Expected a Hasher handle".to_string())
+}
+
+/// Feeds every str argument into the digest.
+fn hasher_update(obj: &mut (dyn Any + Send), args: &[Value]) -> Result<Value, String> {
+    let h = hasher(obj)?.as_mut().ok_or("Hasher.update(): the hasher is already finalized")?;
+    for arg in args {
+        match arg {
+            Value::Str(s) => h.update(s.as_bytes()),
+            other => return Err(format!("Hasher.update() expects str data, got {}", other)),
+        }
+    }
+    Ok(Value::Null)
+}
+
+/// Returns the hex digest; the hasher cannot be updated afterwards.
+fn hasher_finalize(obj: &mut (dyn Any + Send), _args: &[Value]) -> Result<Value, String> {
+    let h = hasher(obj)?.take().ok_or("Hasher.finalize(): the hasher is already finalized")?;
+    Ok(Value::Str(h.finish()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_and_incremental_hashes_match_the_one_shot_digest() {
+        let path = std::env::temp_dir().join(format!("kinetix_hash_file_{}", std::process::id()));
+        let data = "abc".repeat(50_000);
+        std::fs::write(&path, &data).unwrap();
+        let path_arg = Value::Str(path.to_string_lossy().into_owned());
+        for algo in ["sha256", "SHA-512", "sha1", "crc32"] {
+            let one_shot = call("hash", &[Value::Str(data.clone()), Value::Str(algo.into())]).unwrap();
+            assert_eq!(call("hash_file", &[path_arg.clone(), Value::Str(algo.into())]).unwrap(), one_shot);
+
+            let handle = call("hasher.new", &[Value::Str(algo.into())]).unwrap();
+            let Value::Native(h) = &handle else { panic!("expected a handle") };
+            for piece in data.as_bytes().chunks(7001) {
+                h.call_method("update", &[Value::Str(String::from_utf8(piece.to_vec()).unwrap())]).unwrap();
+            }
+            assert_eq!(h.call_method("finalize", &[]).unwrap(), one_shot);
+            assert!(h.call_method("update", &[Value::Str("x".into())]).is_err());
+        }
+        assert_eq!(call("hash", &[Value::Str("abc".into())]).unwrap(), Value::Str("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad".into()));
+        assert_eq!(call("hash", &[Value::Str("abc".into()), Value::Str("crc32".into())]).unwrap(), Value::Str("352441c2".into()));
+        assert!(call("hash", &[Value::Str("abc".into()), Value::Str("md4".into())]).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
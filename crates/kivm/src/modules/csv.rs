@@ -0,0 +1,298 @@
+//! `csv.open` / `csv.create`: `CsvReader` and `CsvWriter` handles that stream
+//! a CSV file a record at a time, where `csv.parse` builds the whole table.
+
+use crate::native::{self, NativeMethod, NativeType};
+use crate::quota::{Metered, Resource};
+use crate::vm::Value;
+use std::any::Any;
+use std::collections::HashMap;
+use std::fs::File;
+use std::sync::Once;
+
+/// Script-visible type names of the handles (`typeof(r)`).
+pub const READER_TYPE: &str = "CsvReader";
+pub const WRITER_TYPE: &str = "CsvWriter";
+
+static REGISTER: Once = Once::new();
+
+/// Registers `CsvReader` (`next`, `done`, `headers`) and `CsvWriter` (`write`,
+/// `flush`); both close through the generic `close`.
+fn register_types() {
+    REGISTER.call_once(|| {
+        let mut methods: HashMap<&'static str, NativeMethod> = HashMap::new();
+        methods.insert("next", next);
+        methods.insert("done", done);
+        methods.insert("headers", headers);
+        native::register_type(NativeType { name: READER_TYPE, methods, destructor: None });
+
+        let mut methods: HashMap<&'static str, NativeMethod> = HashMap::new();
+        methods.insert("write", write);
+        methods.insert("flush", flush);
+        native::register_type(NativeType { name: WRITER_TYPE, methods, destructor: None });
+    });
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Str,
+    Int,
+    Float,
+    Bool,
+}
+
+impl Kind {
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Str => "str",
+            Kind::Int => "int",
+            Kind::Float => "float",
+            Kind::Bool => "bool",
+        }
+    }
+}
+
+struct Reader {
+    inner: ::csv::Reader<File>,
+    columns: Vec<(String, Kind)>,
+    record: ::csv::StringRecord,
+    /// Whether `record` holds a row not yet returned; `None` until `done`
+    /// or `next` reads ahead.
+    ready: Option<bool>,
+}
+
+impl Reader {
+    fn fill(&mut self) -> Result<bool, String> {
+        if self.ready.is_none() {
+            self.ready = Some(self.inner.read_record(&mut self.record).map_err(|e| format!("csv: {}", e))?);
+        }
+        Ok(self.ready == Some(true))
+    }
+}
+
+struct Writer {
+    inner: ::csv::Writer<Metered<File>>,
+    headers: Vec<String>,
+}
+
+/// `csv.open(path, typed?)`: records come back as maps keyed by the header
+/// row. With `typed` set, a header such as `age:int` converts that column
+/// and drops the suffix from the key.
+pub fn open(args: &[Value]) -> Result<Value, String> {
+    let path = match args.first() {
+        Some(Value::Str(s)) => s,
+        other => return Err(format!("csv.open() expects a path, got {}", describe(other))),
+    };
+    let typed = args.get(1).is_some_and(Value::is_truthy);
+    let mut inner = ::csv::Reader::from_path(path).map_err(|e| format!("csv.open('{}'): {}", path, e))?;
+    let columns = inner.headers()
+        .map_err(|e| format!("csv.open('{}'): {}", path, e))?
+        .iter()
+        .map(|h| if typed { column(h) } else { Ok((h.to_string(), Kind::Str)) })
+        .collect::<Result<_, _>>()?;
+    register_types();
+    native::wrap(READER_TYPE, Reader { inner, columns, record: ::csv::StringRecord::new(), ready: None })
+}
+
+/// `csv.create(path, headers?, quoting?)`: `quoting` is `"necessary"` (the
+/// default), `"always"`, `"never"` or `"non_numeric"`. Closing the writer
+/// flushes it.
+pub fn create(args: &[Value]) -> Result<Value, String> {
+    let path = match args.first() {
+        Some(Value::Str(s)) => s,
+        other => return Err(format!("csv.create() expects a path, got {}", describe(other))),
+    };
+    let headers = match args.get(1) {
+        None | Some(Value::Null) => Vec::new(),
+        Some(Value::Array(items)) => items.iter().map(cell).collect(),
+        Some(other) => return Err(format!("csv.create() expects an array of headers, got {}", other)),
+    };
+    let quoting = match args.get(2) {
+        None | Some(Value::Null) => ::csv::QuoteStyle::Necessary,
+        Some(Value::Str(s)) => match s.as_str() {
+            "necessary" => ::csv::QuoteStyle::Necessary,
+            "always" => ::csv::QuoteStyle::Always,
+            "never" => ::csv::QuoteStyle::Never,
+            "non_numeric" => ::csv::QuoteStyle::NonNumeric,
+            other => return Err(format!("csv.create(): unknown quoting '{}' (expected necessary, always, never or non_numeric)", other)),
+        },
+        Some(other) => return Err(format!("csv.create() expects quoting as a str, got {}", other)),
+    };
+    let mut inner = ::csv::WriterBuilder::new()
+        .quote_style(quoting)
+        .flexible(headers.is_empty())
+        .from_writer(Metered::new(File::create(path).map_err(|e| format!("csv.create('{}'): {}", path, e))?, Resource::FsWriteBytes));
+    if !headers.is_empty() {
+        inner.write_record(&headers).map_err(|e| e.to_string())?;
+    }
+    register_types();
+    native::wrap(WRITER_TYPE, Writer { inner, headers })
+}
+
+/// Splits a typed header `name:kind`; a header without a suffix stays a str.
+fn column(header: &str) -> Result<(String, Kind), String> {
+    let Some((name, kind)) = header.rsplit_once(':') else {
+        return Ok((header.to_string(), Kind::Str));
+    };
+    let kind = match kind.trim() {
+        "str" => Kind::Str,
+        "int" => Kind::Int,
+        "float" => Kind::Float,
+        "bool" => Kind::Bool,
+        other => return Err(format!("csv.open(): unknown column type '{}' in header '{}'", other, header)),
+    };
+    Ok((name.to_string(), kind))
+}
+
+/// An empty cell in a typed column reads as null.
+fn convert(value: &str, kind: Kind, name: &str, line: u64) -> Result<Value, String> {
+    if kind != Kind::Str && value.trim().is_empty() {
+        return Ok(Value::Null);
+    }
+    let bad = || format!("csv: line {}: column '{}' expects {}, got '{}'", line, name, kind.name(), value);
+    match kind {
+        Kind::Str => Ok(Value::Str(value.to_string())),
+        Kind::Int => value.trim().parse().map(Value::Int).map_err(|_| bad()),
+        Kind::Float => value.trim().parse().map(Value::Float).map_err(|_| bad()),
+        Kind::Bool => match value.trim() {
+            "true" | "1" => Ok(Value::Bool(true)),
+            "false" | "0" => Ok(Value::Bool(false)),
+            _ => Err(bad()),
+        },
+    }
+}
+
+fn reader(obj: &mut (dyn Any + Send)) -> Result<&mut Reader, String> {
+    obj.downcast_mut::<Reader>().ok_or_else(|| "Expected a CsvReader handle".to_string())
+}
+
+fn writer(obj: &mut (dyn Any + Send)) -> Result<&mut Writer, String> {
+    obj.downcast_mut::<Writer>().ok_or_else(|| "Expected a CsvWriter handle".to_string())
+}
+
+/// The next record as a map, or null at the end of the file. The record
+/// buffer is reused, so a read allocates only the returned map.
+fn next(obj: &mut (dyn Any + Send), _args: &[Value]) -> Result<Value, String> {
+    let r = reader(obj)?;
+    if !r.fill()? {
+        return Ok(Value::Null);
+    }
+    r.ready = None;
+    let line = r.record.position().map_or(0, |p| p.line());
+    let mut row = HashMap::with_capacity(r.columns.len());
+    for (i, (name, kind)) in r.columns.iter().enumerate() {
+        let value = match r.record.get(i) {
+            Some(field) => convert(field, *kind, name, line)?,
+            None => Value::Null,
+        };
+        row.insert(name.clone(), value);
+    }
+    Ok(Value::Map(row))
+}
+
+/// Whether every record has been read.
+fn done(obj: &mut (dyn Any + Send), _args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Bool(!reader(obj)?.fill()?))
+}
+
+/// Column names, with any type suffixes removed.
+fn headers(obj: &mut (dyn Any + Send), _args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Array(reader(obj)?.columns.iter().map(|(name, _)| Value::Str(name.clone())).collect()))
+}
+
+/// Writes an array in order, or a map by the header names given to
+/// `csv.create`.
+fn write(obj: &mut (dyn Any + Send), args: &[Value]) -> Result<Value, String> {
+    let w = writer(obj)?;
+    let record: Vec<String> = match args.first() {
+        Some(Value::Array(cols)) => cols.iter().map(cell).collect(),
+        Some(Value::Map(map)) if w.headers.is_empty() => {
+            return Err(format!("CsvWriter.write(): writing a map needs headers from csv.create, got {}", Value::Map(map.clone())));
+        }
+        Some(Value::Map(map)) => w.headers.iter().map(|h| map.get(h).map_or_else(String::new, cell)).collect(),
+        other => return Err(format!("CsvWriter.write() expects an array or a map, got {}", describe(other))),
+    };
+    w.inner.write_record(&record).map_err(|e| format!("csv: {}", e))?;
+    Ok(Value::Null)
+}
+
+/// Flushes early, reporting write errors closing would swallow.
+fn flush(obj: &mut (dyn Any + Send), _args: &[Value]) -> Result<Value, String> {
+    writer(obj)?.inner.flush().map_err(|e| format!("csv: {}", e))?;
+    Ok(Value::Null)
+}
+
+/// A field as written: strings unquoted, null as an empty field.
+fn cell(value: &Value) -> String {
+    match value {
+        Value::Str(s) => s.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+fn describe(value: Option<&Value>) -> String {
+    value.map_or_else(|| "nothing".to_string(), |v| v.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn typed_rows_stream_back_through_a_writer() {
+        let dir = std::env::temp_dir().join(format!("kinetix_csv_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (input, output) = (dir.join("in.csv"), dir.join("out.csv"));
+        std::fs::write(&input, "name,age:int,score:float,ok:bool\nAda,36,9.5,true\n\"Lee, J\",,1,0\n").unwrap();
+
+        let r = open(&[Value::Str(input.to_string_lossy().into()), Value::Bool(true)]).unwrap();
+        let Value::Native(r) = &r else { panic!("expected a native handle") };
+        assert_eq!(r.type_name(), READER_TYPE);
+        let names = ["name", "age", "score", "ok"].map(|s| Value::Str(s.into()));
+        assert_eq!(r.call_method("headers", &[]).unwrap(), Value::Array(names.to_vec()));
+
+        let w = create(&[
+            Value::Str(output.to_string_lossy().into()),
+            Value::Array(vec![Value::Str("name".into()), Value::Str("age".into())]),
+        ]).unwrap();
+        let Value::Native(w) = &w else { panic!("expected a native handle") };
+
+        assert_eq!(r.call_method("done", &[]).unwrap(), Value::Bool(false));
+        let Value::Map(first) = r.call_method("next", &[]).unwrap() else { panic!("expected a row") };
+        assert_eq!(first["age"], Value::Int(36));
+        assert_eq!(first["score"], Value::Float(9.5));
+        assert_eq!(first["ok"], Value::Bool(true));
+        w.call_method("write", &[Value::Map(first)]).unwrap();
+
+        let Value::Map(second) = r.call_method("next", &[]).unwrap() else { panic!("expected a row") };
+        assert_eq!(second["name"], Value::Str("Lee, J".into()));
+        assert_eq!(second["age"], Value::Null);
+        w.call_method("write", &[Value::Map(second)]).unwrap();
+        assert_eq!(r.call_method("done", &[]).unwrap(), Value::Bool(true));
+        assert_eq!(r.call_method("next", &[]).unwrap(), Value::Null);
+
+        w.close();
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), "name,age\nAda,36\n\"Lee, J\",\n");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn quoting_and_bad_input() {
+        let dir = std::env::temp_dir().join(format!("kinetix_csv_q_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("q.csv");
+
+        let w = create(&[Value::Str(path.to_string_lossy().into()), Value::Null, Value::Str("always".into())]).unwrap();
+        let Value::Native(w) = &w else { panic!("expected a native handle") };
+        w.call_method("write", &[Value::Array(vec![Value::Str("a".into()), Value::Int(1)])]).unwrap();
+        assert!(w.call_method("write", &[Value::Map(HashMap::new())]).is_err());
+        w.close();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "\"a\",\"1\"\n");
+
+        std::fs::write(&path, "n:int\nx\n").unwrap();
+        let Value::Native(r) = open(&[Value::Str(path.to_string_lossy().into()), Value::Bool(true)]).unwrap() else { panic!() };
+        assert!(r.call_method("next", &[]).unwrap_err().contains("line 2"));
+        assert!(create(&[Value::Str(path.to_string_lossy().into()), Value::Null, Value::Str("sometimes".into())]).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
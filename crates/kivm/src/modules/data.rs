@@ -1,7 +1,35 @@
+use crate::builtins::{builtin, Builtin};
+use crate::quota::{self, Metered, Resource};
+use kinetix_kicomp::capability::Capability;
 use crate::vm::Value;
 use std::fs;
 use std::path::Path;
 
+/// `data.*`, plus the `json.*` and `csv.*` builtins this module implements.
+pub const BUILTINS: &[Builtin] = &[
+    builtin("data.file.read", "(path: str) -> result<str>", "Contents of the file at `path`, which must stay inside the working directory.", |a, _| call("file.read", a)).needs(Capability::FsRead),
+    builtin("data.file.write", "(path: str, content: str) -> result<null>", "Writes `content` to `path`, replacing the file; paths as for data.file.read.", |a, _| call("file.write", a)).needs(Capability::FsWrite),
+    builtin("data.file.exists", "(path: str) -> bool", "Whether `path` exists; false for a path outside the working directory.", |a, _| call("file.exists", a)).needs(Capability::FsRead),
+    builtin("data.file.delete", "(path: str) -> result<null>", "Deletes the file at `path`.", |a, _| call("file.delete", a)).needs(Capability::FsWrite),
+    builtin("data.file.copy", "(from: str, to: str) -> result<null>", "Copies the file `from` to `to`.", |a, _| call("file.copy", a)).needs(Capability::FsRead),
+    builtin("data.file.move", "(from: str, to: str) -> result<null>", "Moves the file `from` to `to`.", |a, _| call("file.move", a)).needs(Capability::FsWrite),
+    builtin("data.dir.list", "(path: str) -> result<[str]>", "Paths of the entries in the directory `path`, relative to the working directory.", |a, _| call("dir.list", a)).needs(Capability::FsRead),
+    builtin("data.dir.create", "(path: str) -> result<null>", "Creates the directory `path` and any missing parents.", |a, _| call("dir.create", a)).needs(Capability::FsWrite),
+    builtin("data.dir.delete", "(path: str) -> result<null>", "Deletes the directory `path` and everything in it.", |a, _| call("dir.delete", a)).needs(Capability::FsWrite),
+    builtin("data.path.normalize", "(path: str) -> str", "`path` with forward slashes and no trailing slash.", |a, _| call("path.normalize", a)).needs(Capability::FsRead),
+    builtin("data.path.isSafe", "(path: str) -> bool", "Whether `path` is relative and stays inside the working directory.", |a, _| call("path.isSafe", a)).needs(Capability::FsRead),
+    builtin("data.watch", "(path: str) -> result<null>", "Reserved; always an err until the VM has an event loop (see fs.watch).", |a, _| call("watch", a)).needs(Capability::FsRead),
+    builtin("data.read_bytes", "(path: str) -> [int]", "The bytes of the file at `path`.", |a, _| call("read_bytes", a)).needs(Capability::FsRead),
+    builtin("data.alloc", "(size: int) -> [int]", "An array of `size` zeros.", |a, _| call("alloc", a)),
+    builtin("data.xml.parse", "(text: str) -> XmlNode", "Parses an XML document and returns its root element.", |a, _| call("xml.parse", a)),
+    builtin("json.parse", "(text: str) -> any", "Parses JSON text into maps, arrays, strings, numbers, bools and null.", |a, _| call("json.parse", a)),
+    builtin("json.stringify", "(value: any) -> str", "`value` as compact JSON.", |a, _| call("json.stringify", a)),
+    builtin("csv.open", "(path: str, typed: bool?) -> CsvReader", "Opens a CSV file for reading a record at a time, as maps keyed by the header row; with `typed`, a header such as `age:int` converts its column.", |a, _| call("csv.open", a)).needs(Capability::FsRead),
+    builtin("csv.create", "(path: str, headers: [any]?) -> CsvWriter", "Creates a CSV file for writing a record at a time, starting with `headers`.", |a, _| call("csv.create", a)).needs(Capability::FsWrite),
+    builtin("csv.parse", "(text: str) -> [[str]]", "The records of CSV text, after its header row.", |a, _| call("csv.parse", a)),
+    builtin("csv.write", "(path: str, rows: [[any]])", "Writes `rows` to `path` as CSV in one go.", |a, _| call("csv.write", a)).needs(Capability::FsWrite),
+];
+
 pub fn call(name: &str, args: &[Value]) -> Result<Value, String> {
     match name {
         // --- File IO (data.file.*) & Path Traversal Security ---
@@ -54,7 +82,7 @@ pub fn call(name: &str, args: &[Value]) -> Result<Value, String> {
                     let path_str = args.first().and_then(|v| match v { Value::Str(s) => Some(s), _ => None }).ok_or("Expected path string")?;
                     let content = args.get(1).and_then(|v| match v { Value::Str(s) => Some(s), _ => None }).ok_or("Expected content string")?;
                     let safe_path = sanitize_path(path_str)?;
-                    match fs::write(&safe_path, content) {
+                    match quota::charge(Resource::FsWriteBytes, content.len() as u64).and_then(|_| fs::write(&safe_path, content).map_err(|e| e.to_string())) {
                         Ok(_) => {
                             let mut res = std::collections::HashMap::new();
                             res.insert("ok".to_string(), Value::Null);
@@ -96,7 +124,8 @@ pub fn call(name: &str, args: &[Value]) -> Result<Value, String> {
                     let dst_str = args.get(1).and_then(|v| match v { Value::Str(s) => Some(s), _ => None }).ok_or("Expected dst path string")?;
                     let safe_src = sanitize_path(src_str)?;
                     let safe_dst = sanitize_path(dst_str)?;
-                    match fs::copy(&safe_src, &safe_dst) {
+                    let size = fs::metadata(&safe_src).map_or(0, |meta| meta.len());
+                    match quota::charge(Resource::FsWriteBytes, size).and_then(|_| fs::copy(&safe_src, &safe_dst).map_err(|e| e.to_string())) {
                         Ok(_) => {
                             let mut res = std::collections::HashMap::new();
                             res.insert("ok".to_string(), Value::Null);
@@ -293,7 +322,12 @@ pub fn call(name: &str, args: &[Value]) -> Result<Value, String> {
              Ok(Value::Str(json_val.to_string()))
         },
 
+        // --- XML ---
+        "xml.parse" => super::markup::parse_xml(args),
+
         // --- CSV ---
+        "csv.open" => super::csv::open(args),
+        "csv.create" => super::csv::create(args),
         "csv.parse" => {
              let csv_str = args.first().and_then(|v| match v { Value::Str(s) => Some(s), _ => None }).ok_or("Expected CSV string")?;
              // Simple naive parse or use crate
@@ -310,7 +344,8 @@ pub fn call(name: &str, args: &[Value]) -> Result<Value, String> {
              let path = args.first().and_then(|v| match v { Value::Str(s) => Some(s), _ => None }).ok_or("Expected path")?;
              let rows = args.get(1).and_then(|v| match v { Value::Array(a) => Some(a), _ => None }).ok_or("Expected array of rows")?;
              
-             let mut wtr = csv::Writer::from_path(path).map_err(|e| e.to_string())?;
+             let file = fs::File::create(path).map_err(|e| e.to_string())?;
+             let mut wtr = csv::Writer::from_writer(Metered::new(file, Resource::FsWriteBytes));
              for row_val in rows {
                  if let Value::Array(cols) = row_val {
                      let record: Vec<String> = cols.iter().map(|v| format!("{}", v)).collect();
@@ -326,7 +361,7 @@ pub fn call(name: &str, args: &[Value]) -> Result<Value, String> {
     }
 }
 
-fn convert_json_to_value(v: serde_json::Value) -> Result<Value, String> {
+pub(crate) fn convert_json_to_value(v: serde_json::Value) -> Result<Value, String> {
     match v {
         serde_json::Value::Null => Ok(Value::Null),
         serde_json::Value::Bool(b) => Ok(Value::Bool(b)),
@@ -354,7 +389,7 @@ fn convert_json_to_value(v: serde_json::Value) -> Result<Value, String> {
     }
 }
 
-fn convert_value_to_json(v: &Value) -> serde_json::Value {
+pub(crate) fn convert_value_to_json(v: &Value) -> serde_json::Value {
     match v {
         Value::Null => serde_json::Value::Null,
         Value::Bool(b) => serde_json::Value::Bool(*b),
@@ -1,39 +1,37 @@
+use crate::builtins::{builtin, Builtin};
+use crate::native::{self, NativeMethod, NativeType};
 use crate::vm::Value;
-use std::sync::Mutex;
+use std::any::Any;
 use std::collections::HashMap;
-use lazy_static::lazy_static;
+use std::sync::Once;
+use kinetix_kicomp::capability::Capability;
 use rusqlite::Connection;
-use std::sync::atomic::{AtomicUsize, Ordering};
 
-lazy_static! {
-    static ref CONNECTIONS: Mutex<HashMap<usize, Connection>> = Mutex::new(HashMap::new());
-    static ref NEXT_ID: AtomicUsize = AtomicUsize::new(1);
+/// Script-visible type name of a connection handle (`typeof(conn)`).
+pub const CONNECTION_TYPE: &str = "DbConnection";
+
+static REGISTER: Once = Once::new();
+
+/// Registers the `DbConnection` native type: `conn.query`, `conn.execute`,
+/// and the generic `conn.close()` (dropping the `Connection` closes it).
+fn register_connection_type() {
+    REGISTER.call_once(|| {
+        let mut methods: HashMap<&'static str, NativeMethod> = HashMap::new();
+        methods.insert("query", query);
+        methods.insert("execute", execute);
+        native::register_type(NativeType { name: CONNECTION_TYPE, methods, destructor: None });
+    });
 }
 
+pub const BUILTINS: &[Builtin] = &[
+    builtin("db.connect", "(uri: str) -> DbConnection", "Opens the SQLite database at `uri` (a path, optionally prefixed `sqlite://`).", |a, _| call("connect", a)).needs(Capability::FsRead),
+];
+
 pub fn call(name: &str, args: &[Value]) -> Result<Value, String> {
     if name == "connect" {
         return connect(args);
     }
 
-    // Handle object methods: "db_conn:ID.method"
-    if name.starts_with("db_conn:") {
-        let parts: Vec<&str> = name.split('.').collect();
-        if parts.len() != 2 { return Err("Invalid DB method call".into()); }
-        
-        let handle_part = parts[0]; // "db_conn:1"
-        let method = parts[1];      // "query"
-        
-        let id_str = handle_part.strip_prefix("db_conn:").ok_or("Invalid ID format")?;
-        let id = id_str.parse::<usize>().map_err(|_| "Invalid ID")?;
-
-        match method {
-            "query" => return query(id, args),
-            "execute" => return execute(id, args),
-            "close" => return close(id),
-            _ => return Err(format!("Unknown DB method: {}", method)),
-        }
-    }
-
     Err(format!("Unknown DB function: {}", name))
 }
 
@@ -48,20 +46,20 @@ fn connect(args: &[Value]) -> Result<Value, String> {
     };
 
     let conn = Connection::open(path).map_err(|e| e.to_string())?;
-    
-    let id = NEXT_ID.fetch_add(1, Ordering::SeqCst);
-    CONNECTIONS.lock().unwrap().insert(id, conn);
-    
-    // Return a NativeModule acting as the connection object
-    Ok(Value::NativeModule(format!("db_conn:{}", id)))
+
+    register_connection_type();
+    native::wrap(CONNECTION_TYPE, conn)
+}
+
+fn connection(obj: &mut (dyn Any + Send)) -> Result<&mut Connection, String> {
+    obj.downcast_mut::<Connection>().ok_or_else(|| "Expected a DbConnection handle".to_string())
 }
 
-fn query(id: usize, args: &[Value]) -> Result<Value, String> {
+fn query(obj: &mut (dyn Any + Send), args: &[Value]) -> Result<Value, String> {
     let sql = args.first().and_then(|v| match v { Value::Str(s) => Some(s), _ => None }).ok_or("Expected SQL string")?;
     let params_val = args.get(1); // Optional params array
 
-    let connections = CONNECTIONS.lock().unwrap();
-    let conn = connections.get(&id).ok_or("Connection closed or invalid")?;
+    let conn = connection(obj)?;
 
     let mut stmt = conn.prepare(sql).map_err(|e| e.to_string())?;
 
@@ -96,12 +94,11 @@ fn query(id: usize, args: &[Value]) -> Result<Value, String> {
     Ok(Value::Array(rows_list))
 }
 
-fn execute(id: usize, args: &[Value]) -> Result<Value, String> {
+fn execute(obj: &mut (dyn Any + Send), args: &[Value]) -> Result<Value, String> {
     let sql = args.first().and_then(|v| match v { Value::Str(s) => Some(s), _ => None }).ok_or("Expected SQL string")?;
     let params_val = args.get(1);
 
-    let connections = CONNECTIONS.lock().unwrap();
-    let conn = connections.get(&id).ok_or("Connection closed or invalid")?;
+    let conn = connection(obj)?;
 
     let mut sql_params = Vec::new();
     if let Some(Value::Array(arr)) = params_val {
@@ -115,11 +112,6 @@ fn execute(id: usize, args: &[Value]) -> Result<Value, String> {
     Ok(Value::Int(affected as i64))
 }
 
-fn close(id: usize) -> Result<Value, String> {
-    CONNECTIONS.lock().unwrap().remove(&id);
-    Ok(Value::Null)
-}
-
 // Helpers
 fn value_to_sql(v: &Value) -> Box<dyn rusqlite::types::ToSql> {
     match v {
@@ -145,4 +137,27 @@ fn row_get_value(row: &rusqlite::Row, idx: usize) -> Result<Value, String> {
     }
 }
 
-
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn connection_is_a_native_handle() {
+        let conn = connect(&[Value::Str(":memory:".to_string())]).expect("in-memory sqlite opens");
+        let Value::Native(handle) = &conn else { panic!("expected a native handle, got {:?}", conn) };
+        assert_eq!(handle.type_name(), CONNECTION_TYPE);
+
+        handle.call_method("execute", &[Value::Str("CREATE TABLE t (n INTEGER)".to_string())]).unwrap();
+        let inserted = handle.call_method("execute", &[
+            Value::Str("INSERT INTO t VALUES (?)".to_string()),
+            Value::Array(vec![Value::Int(7)]),
+        ]).unwrap();
+        assert_eq!(inserted, Value::Int(1));
+        let rows = handle.call_method("query", &[Value::Str("SELECT n FROM t".to_string())]).unwrap();
+        let Value::Array(rows) = rows else { panic!("query returns rows") };
+        assert_eq!(rows.len(), 1);
+
+        handle.call_method("close", &[]).unwrap();
+        assert!(handle.call_method("query", &[Value::Str("SELECT 1".to_string())]).is_err());
+    }
+}
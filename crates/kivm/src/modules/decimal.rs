@@ -0,0 +1,402 @@
+//! `decimal`: exact base-10 arithmetic on decimal strings, with explicit rounding
+//! modes and digit-for-digit JSON.
+
+use crate::builtins::{builtin, Builtin};
+use crate::vm::Value;
+use std::cmp::Ordering;
+use std::fmt;
+
+pub const BUILTINS: &[Builtin] = &[
+    builtin("decimal.add", "(a: str | int, b: str | int) -> str", "`a + b`, exactly.", |args, _| {
+        let (a, b) = (arg("add", &args[0])?, arg("add", &args[1])?);
+        a.add(b).map(to_value).ok_or_else(|| overflow("add"))
+    }),
+    builtin("decimal.sub", "(a: str | int, b: str | int) -> str", "`a - b`, exactly.", |args, _| {
+        let (a, b) = (arg("sub", &args[0])?, arg("sub", &args[1])?);
+        a.add(b.neg()).map(to_value).ok_or_else(|| overflow("sub"))
+    }),
+    builtin("decimal.mul", "(a: str | int, b: str | int) -> str", "`a * b`, exactly.", |args, _| {
+        let (a, b) = (arg("mul", &args[0])?, arg("mul", &args[1])?);
+        a.mul(b).map(to_value).ok_or_else(|| overflow("mul"))
+    }),
+    builtin("decimal.div", "(a: str | int, b: str | int, places: int?, mode: str?) -> str", "`a / b` rounded to `places` (default 16, trailing zeros dropped) by `mode` (default `half_even`).", |args, _| {
+        let (a, b) = (arg("div", &args[0])?, arg("div", &args[1])?);
+        if b.mantissa == 0 {
+            return Err("decimal.div(): division by zero".to_string());
+        }
+        let mode = mode_arg("div", args.get(3))?;
+        let result = match args.get(2) {
+            Some(Value::Int(places)) => a.div(b, places_arg("div", *places)?, mode),
+            _ => a.div(b, DEFAULT_DIV_PLACES, mode).map(Decimal::trim),
+        };
+        result.map(to_value).ok_or_else(|| overflow("div"))
+    }),
+    builtin("decimal.round", "(a: str | int, places: int, mode: str?) -> str", "`a` rounded (or zero-padded) to exactly `places` decimal places by `mode` (default `half_even`).", |args, _| {
+        let a = arg("round", &args[0])?;
+        let places = places_arg("round", args[1].as_int()?)?;
+        a.round(places, mode_arg("round", args.get(2))?).map(to_value).ok_or_else(|| overflow("round"))
+    }),
+    builtin("decimal.cmp", "(a: str | int, b: str | int) -> int", "-1, 0 or 1 as `a` is less than, equal to or greater than `b` (`\"1.0\"` equals `\"1\"`).", |args, _| {
+        let (a, b) = (arg("cmp", &args[0])?, arg("cmp", &args[1])?);
+        let ordering = a.cmp(b).ok_or_else(|| overflow("cmp"))?;
+        Ok(Value::Int(ordering as i64))
+    }),
+    builtin("decimal.from", "(x: float | str) -> str", "A decimal string for `x`; a float converts through its shortest exact representation (`0.1` is `\"0.1\"`).", |args, _| match &args[0] {
+        Value::Float(f) if !f.is_finite() => Err(format!("decimal.from(): {} is not a number", f)),
+        Value::Float(f) => Decimal::parse(&format!("{:?}", f)).map(to_value).map_err(|e| format!("decimal.from(): {}", e)),
+        other => arg("from", other).map(to_value),
+    }),
+    builtin("decimal.parse_json", "(text: str) -> any", "`json.parse`, but fractional and out-of-range numbers become exact decimal strings.", |args, _| {
+        let Value::Str(text) = &args[0] else { unreachable!("checked by the signature") };
+        let quoted = quote_decimals(text).map_err(|e| format!("decimal.parse_json(): {}", e))?;
+        let json: serde_json::Value = serde_json::from_str(&quoted).map_err(|e| format!("decimal.parse_json(): {}", e))?;
+        super::data::convert_json_to_value(json)
+    }),
+    builtin("decimal.to_json", "(value: any) -> str", "`json.stringify`, but strings holding a fractional decimal (`\"12.30\"`) are written as JSON numbers, unchanged.", |args, _| {
+        let mut out = String::new();
+        write_json(&args[0], &mut out)?;
+        Ok(Value::Str(out))
+    }),
+];
+
+/// Places `decimal.div` rounds to when none are given.
+const DEFAULT_DIV_PLACES: u32 = 16;
+
+/// Most digits an `i128` mantissa always holds.
+const MAX_DIGITS: u32 = 38;
+
+/// `mantissa / 10^scale`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Decimal {
+    mantissa: i128,
+    scale: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Mode {
+    HalfEven,
+    HalfUp,
+    HalfDown,
+    Up,
+    Down,
+    Ceiling,
+    Floor,
+}
+
+fn pow10(exp: u32) -> Option<i128> {
+    10i128.checked_pow(exp)
+}
+
+impl Decimal {
+    /// `None` when `mantissa` has more than `MAX_DIGITS` digits.
+    fn new(mantissa: i128, scale: u32) -> Option<Decimal> {
+        (mantissa.unsigned_abs() < 10u128.pow(MAX_DIGITS)).then_some(Decimal { mantissa, scale })
+    }
+
+    /// Parses `-12.340`, `7`, `+.5` or `1.5e-3`.
+    fn parse(s: &str) -> Result<Decimal, String> {
+        let bad = || format!("'{}' is not a decimal number", s);
+        let (body, exp) = match s.find(['e', 'E']) {
+            Some(i) => (&s[..i], s[i + 1..].parse::<i32>().map_err(|_| bad())?),
+            None => (s, 0),
+        };
+        let (negative, digits) = match body.as_bytes().first() {
+            Some(b'-') => (true, &body[1..]),
+            Some(b'+') => (false, &body[1..]),
+            _ => (false, body),
+        };
+        let (int, frac) = digits.split_once('.').unwrap_or((digits, ""));
+        if int.is_empty() && frac.is_empty() || !int.bytes().chain(frac.bytes()).all(|b| b.is_ascii_digit()) {
+            return Err(bad());
+        }
+        let significant = int.trim_start_matches('0').len() + frac.len();
+        if significant > MAX_DIGITS as usize {
+            return Err(format!("'{}' has more than {} digits", s, MAX_DIGITS));
+        }
+        let mut mantissa: i128 = 0;
+        for b in int.bytes().chain(frac.bytes()) {
+            mantissa = mantissa * 10 + i128::from(b - b'0');
+        }
+        if negative {
+            mantissa = -mantissa;
+        }
+        let scale = frac.len() as i64 - i64::from(exp);
+        let too_big = || format!("'{}' is out of range", s);
+        if scale < 0 {
+            let factor = u32::try_from(-scale).ok().and_then(pow10).ok_or_else(too_big)?;
+            return mantissa.checked_mul(factor).and_then(|m| Decimal::new(m, 0)).ok_or_else(too_big);
+        }
+        let scale = u32::try_from(scale).ok().filter(|s| *s <= MAX_DIGITS).ok_or_else(too_big)?;
+        Ok(Decimal { mantissa, scale })
+    }
+
+    fn neg(self) -> Decimal {
+        Decimal { mantissa: -self.mantissa, ..self }
+    }
+
+    /// `self` with `scale` at least `scale`, adding trailing zeros.
+    fn rescale(self, scale: u32) -> Option<Decimal> {
+        if scale <= self.scale {
+            return Some(self);
+        }
+        Decimal::new(self.mantissa.checked_mul(pow10(scale - self.scale)?)?, scale)
+    }
+
+    fn align(self, other: Decimal) -> Option<(i128, i128, u32)> {
+        let scale = self.scale.max(other.scale);
+        Some((self.rescale(scale)?.mantissa, other.rescale(scale)?.mantissa, scale))
+    }
+
+    fn add(self, other: Decimal) -> Option<Decimal> {
+        let (a, b, scale) = self.align(other)?;
+        Decimal::new(a.checked_add(b)?, scale)
+    }
+
+    fn mul(self, other: Decimal) -> Option<Decimal> {
+        let scale = self.scale + other.scale;
+        let mantissa = self.mantissa.checked_mul(other.mantissa)?;
+        if scale <= MAX_DIGITS {
+            return Decimal::new(mantissa, scale);
+        }
+        Decimal { mantissa, scale }.round_unchecked(MAX_DIGITS, Mode::HalfEven)
+    }
+
+    /// `self / other` to `places` places. `other` must not be zero.
+    fn div(self, other: Decimal, places: u32, mode: Mode) -> Option<Decimal> {
+        // self / other = (m1 * 10^(places + s2)) / (m2 * 10^s1) / 10^places
+        let num = self.mantissa.checked_mul(pow10(places + other.scale)?)?;
+        let den = other.mantissa.checked_mul(pow10(self.scale)?)?;
+        Decimal::new(round_div(num, den, mode)?, places)
+    }
+
+    fn round(self, places: u32, mode: Mode) -> Option<Decimal> {
+        if places >= self.scale {
+            return self.rescale(places);
+        }
+        self.round_unchecked(places, mode)
+    }
+
+    fn round_unchecked(self, places: u32, mode: Mode) -> Option<Decimal> {
+        // Dividing by more than 10^38 always leaves 0 or ±1 by the mode.
+        let mantissa = match pow10(self.scale - places) {
+            Some(den) => round_div(self.mantissa, den, mode)?,
+            None => round_div(self.mantissa.signum(), i128::MAX, mode)?,
+        };
+        Decimal::new(mantissa, places)
+    }
+
+    /// Drops trailing zeros after the point.
+    fn trim(mut self) -> Decimal {
+        while self.scale > 0 && self.mantissa % 10 == 0 {
+            self.mantissa /= 10;
+            self.scale -= 1;
+        }
+        self
+    }
+
+    fn cmp(self, other: Decimal) -> Option<Ordering> {
+        let (a, b, _) = self.align(other)?;
+        Some(a.cmp(&b))
+    }
+}
+
+/// `num / den` rounded to an integer by `mode`.
+fn round_div(num: i128, den: i128, mode: Mode) -> Option<i128> {
+    let (q, r) = (num.checked_div(den)?, num % den);
+    if r == 0 {
+        return Some(q);
+    }
+    let sign = if (num < 0) == (den < 0) { 1 } else { -1 };
+    let (r, d) = (r.unsigned_abs(), den.unsigned_abs());
+    let half = r.cmp(&(d - r));
+    let away = match mode {
+        Mode::Down => false,
+        Mode::Up => true,
+        Mode::Ceiling => sign > 0,
+        Mode::Floor => sign < 0,
+        Mode::HalfUp => half != Ordering::Less,
+        Mode::HalfDown => half == Ordering::Greater,
+        Mode::HalfEven => half == Ordering::Greater || half == Ordering::Equal && q % 2 != 0,
+    };
+    if away { q.checked_add(sign) } else { Some(q) }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let digits = self.mantissa.unsigned_abs().to_string();
+        let sign = if self.mantissa < 0 { "-" } else { "" };
+        let scale = self.scale as usize;
+        if scale == 0 {
+            return write!(f, "{}{}", sign, digits);
+        }
+        let padded = format!("{:0>width$}", digits, width = scale + 1);
+        let (int, frac) = padded.split_at(padded.len() - scale);
+        write!(f, "{}{}.{}", sign, int, frac)
+    }
+}
+
+fn to_value(d: Decimal) -> Value {
+    Value::Str(d.to_string())
+}
+
+fn overflow(name: &str) -> String {
+    format!("decimal.{}(): the result needs more than {} digits", name, MAX_DIGITS)
+}
+
+fn arg(name: &str, value: &Value) -> Result<Decimal, String> {
+    match value {
+        Value::Int(n) => Ok(Decimal { mantissa: i128::from(*n), scale: 0 }),
+        Value::Str(s) => Decimal::parse(s.trim()).map_err(|e| format!("decimal.{}(): {}", name, e)),
+        other => Err(format!("decimal.{}() expects a decimal string or an int, got {}", name, other)),
+    }
+}
+
+fn places_arg(name: &str, places: i64) -> Result<u32, String> {
+    u32::try_from(places).ok().filter(|p| *p <= MAX_DIGITS)
+        .ok_or_else(|| format!("decimal.{}(): places must be between 0 and {}, got {}", name, MAX_DIGITS, places))
+}
+
+fn mode_arg(name: &str, value: Option<&Value>) -> Result<Mode, String> {
+    Ok(match value {
+        None | Some(Value::Null) => Mode::HalfEven,
+        Some(Value::Str(s)) => match s.as_str() {
+            "half_even" => Mode::HalfEven,
+            "half_up" => Mode::HalfUp,
+            "half_down" => Mode::HalfDown,
+            "up" => Mode::Up,
+            "down" => Mode::Down,
+            "ceiling" => Mode::Ceiling,
+            "floor" => Mode::Floor,
+            other => return Err(format!(
+                "decimal.{}(): unknown rounding mode '{}' (expected half_even, half_up, half_down, up, down, ceiling or floor)",
+                name, other
+            )),
+        },
+        Some(other) => return Err(format!("decimal.{}() expects the rounding mode as a str, got {}", name, other)),
+    })
+}
+
+/// Rewrites the JSON number tokens that would lose precision as a float
+/// (fractions, exponents, integers beyond i64) into quoted canonical
+/// decimal strings, leaving string contents alone.
+fn quote_decimals(text: &str) -> Result<String, String> {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(i) = rest.find(|c: char| c == '"' || c == '-' || c.is_ascii_digit()) {
+        out.push_str(&rest[..i]);
+        rest = &rest[i..];
+        if rest.starts_with('"') {
+            let mut end = 1;
+            let bytes = rest.as_bytes();
+            while end < bytes.len() && bytes[end] != b'"' {
+                end += if bytes[end] == b'\\' { 2 } else { 1 };
+            }
+            let end = (end + 1).min(rest.len());
+            out.push_str(&rest[..end]);
+            rest = &rest[end..];
+            continue;
+        }
+        let len = rest.find(|c: char| !(c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E'))).unwrap_or(rest.len());
+        let token = &rest[..len];
+        if token.contains(['.', 'e', 'E']) || token.parse::<i64>().is_err() {
+            out.push('"');
+            out.push_str(&Decimal::parse(token)?.to_string());
+            out.push('"');
+        } else {
+            out.push_str(token);
+        }
+        rest = &rest[len..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// `-?(0|[1-9][0-9]*)\.[0-9]+`: a decimal `to_json` writes as a number.
+fn is_plain_decimal(s: &str) -> bool {
+    let s = s.strip_prefix('-').unwrap_or(s);
+    let Some((int, frac)) = s.split_once('.') else { return false };
+    let int_ok = int == "0" || !int.starts_with('0') && !int.is_empty() && int.bytes().all(|b| b.is_ascii_digit());
+    int_ok && !frac.is_empty() && frac.bytes().all(|b| b.is_ascii_digit())
+}
+
+fn write_json(value: &Value, out: &mut String) -> Result<(), String> {
+    match value {
+        Value::Null => out.push_str("null"),
+        Value::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+        Value::Int(n) => out.push_str(&n.to_string()),
+        Value::Float(f) if f.is_finite() => out.push_str(&format!("{:?}", f)),
+        Value::Float(_) => out.push_str("null"),
+        Value::Str(s) if is_plain_decimal(s) => out.push_str(s),
+        Value::Str(s) => out.push_str(&serde_json::Value::String(s.clone()).to_string()),
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_json(item, out)?;
+            }
+            out.push(']');
+        }
+        Value::Map(map) => {
+            let mut keys: Vec<&String> = map.keys().collect();
+            keys.sort();
+            out.push('{');
+            for (i, key) in keys.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&serde_json::Value::String(key.clone()).to_string());
+                out.push(':');
+                write_json(&map[key], out)?;
+            }
+            out.push('}');
+        }
+        other => return Err(format!("decimal.to_json(): cannot encode {}", other)),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn d(s: &str) -> Decimal {
+        Decimal::parse(s).unwrap()
+    }
+
+    #[test]
+    fn exact_arithmetic_and_rounding_modes() {
+        assert_eq!(d("0.1").add(d("0.2")).unwrap().to_string(), "0.3");
+        assert_eq!(d("1.10").add(d("2.205")).unwrap().to_string(), "3.305");
+        assert_eq!(d("19.99").mul(d("3")).unwrap().to_string(), "59.97");
+        assert_eq!(d("-0.05").to_string(), "-0.05");
+        assert_eq!(d("1.5e-3").to_string(), "0.0015");
+        assert_eq!(d("12e2").to_string(), "1200");
+        assert_eq!(d("1").div(d("3"), 4, Mode::HalfEven).unwrap().to_string(), "0.3333");
+        assert_eq!(d("10").div(d("4"), 16, Mode::HalfEven).unwrap().trim().to_string(), "2.5");
+        assert_eq!(d("1.0").cmp(d("1")), Some(Ordering::Equal));
+        assert!(Decimal::parse("1.2.3").is_err() && Decimal::parse("").is_err() && Decimal::parse("1e999").is_err());
+        assert!(Decimal::parse(&"9".repeat(39)).is_err());
+        assert!(d(&"9".repeat(38)).add(d("1")).is_none());
+
+        let round = |s: &str, mode| d(s).round(0, mode).unwrap().to_string();
+        let modes = [Mode::HalfEven, Mode::HalfUp, Mode::HalfDown, Mode::Up, Mode::Down, Mode::Ceiling, Mode::Floor];
+        assert_eq!(modes.map(|m| round("2.5", m)), ["2", "3", "2", "3", "2", "3", "2"]);
+        assert_eq!(modes.map(|m| round("-2.5", m)), ["-2", "-3", "-2", "-3", "-2", "-2", "-3"]);
+        assert_eq!(modes.map(|m| round("3.5", m))[0], "4");
+        assert_eq!(modes.map(|m| round("2.51", m))[2], "3");
+        assert_eq!(d("2.5").round(2, Mode::HalfEven).unwrap().to_string(), "2.50");
+    }
+
+    #[test]
+    fn json_keeps_every_digit() {
+        let text = r#"{"price": 19.990, "qty": 3, "big": 123456789012345678901234567890, "note": "1.5 and -2", "e": 1.5e2}"#;
+        let json: serde_json::Value = serde_json::from_str(&quote_decimals(text).unwrap()).unwrap();
+        let value = super::super::data::convert_json_to_value(json).unwrap();
+        let mut out = String::new();
+        write_json(&value, &mut out).unwrap();
+        assert_eq!(out, r#"{"big":"123456789012345678901234567890","e":"150","note":"1.5 and -2","price":19.990,"qty":3}"#);
+        assert!(!is_plain_decimal("1.2.3") && !is_plain_decimal("01.5") && !is_plain_decimal("1.") && is_plain_decimal("-0.5"));
+    }
+}
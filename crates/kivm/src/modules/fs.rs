@@ -0,0 +1,318 @@
+//! `fs`: filesystem helpers for scripts (globbing, walking, watching and
+//! atomic writes).
+
+use crate::builtins::{builtin, Builtin};
+use crate::quota::{self, Resource};
+use crate::vm::Value;
+use kinetix_kicomp::capability::Capability;
+use kinetix_language::atomic::write_atomic;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+pub const BUILTINS: &[Builtin] = &[
+    builtin("fs.glob", "(pattern: str) -> [str]", "Paths matching `pattern` (`*`, `?`, `[a-z]`, and `**` for any number of directories), sorted. `*` and `?` don't match a leading dot.", |args, _| {
+        let Value::Str(pattern) = &args[0] else { unreachable!("checked by the signature") };
+        let options = glob::MatchOptions { require_literal_leading_dot: true, ..Default::default() };
+        let paths = glob::glob_with(pattern, options).map_err(|e| format!("fs.glob('{}'): {}", pattern, e))?;
+        let mut found: Vec<String> = paths.flatten().map(|p| p.to_string_lossy().into_owned()).collect();
+        found.sort();
+        Ok(Value::Array(found.into_iter().map(Value::Str).collect()))
+    }).needs(Capability::FsRead),
+    builtin("fs.walk", "(dir: str, on_entry: fn, ext: str?, min_size: int?, max_size: int?, modified_after: int?) -> int", "Calls `on_entry(path, size, mtime)` for each file under `dir` that passes the filters (`ext` may list several, as \"kix,rs\"; `modified_after` is a Unix timestamp), each directory's files in name order before its subdirectories, until it returns false; returns how many files it visited.", |args, vm| {
+        let Value::Str(dir) = &args[0] else { unreachable!("checked by the signature") };
+        let filter = Filter::from_args(&args[2..]);
+        let mut visited = 0;
+        let mut stack = vec![PathBuf::from(dir)];
+        if !stack[0].is_dir() {
+            return Err(format!("fs.walk('{}'): not a directory", dir));
+        }
+        while let Some(dir) = stack.pop() {
+            let Ok(read) = std::fs::read_dir(&dir) else { continue };
+            let mut entries: Vec<_> = read.flatten().map(|e| e.path()).collect();
+            entries.sort();
+            let mut subdirs = Vec::new();
+            for path in entries {
+                let Ok(meta) = path.symlink_metadata() else { continue };
+                if meta.is_dir() {
+                    subdirs.push(path);
+                    continue;
+                }
+                let mtime = meta.modified().ok()
+                    .and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok())
+                    .map_or(0, |d| d.as_secs() as i64);
+                if !filter.matches(&path, meta.len(), mtime) {
+                    continue;
+                }
+                visited += 1;
+                let entry = vec![Value::Str(path.to_string_lossy().into_owned()), Value::Int(meta.len() as i64), Value::Int(mtime)];
+                if matches!(vm.call_function_now(args[1].clone(), entry)?, Value::Bool(false)) {
+                    return Ok(Value::Int(visited));
+                }
+            }
+            // A directory's files come first, then its subdirectories a..z.
+            stack.extend(subdirs.into_iter().rev());
+        }
+        Ok(Value::Int(visited))
+    }).needs(Capability::FsRead),
+    builtin("fs.watch", "(path: str, on_event: fn, recursive: bool?, debounce_ms: int?) -> int", "Polls `path` and calls `on_event(kind, path)` once per file created, modified or removed, after `debounce_ms` (default 200) pass without another change, until it returns false; returns how many events were delivered.", |args, vm| {
+        let Value::Str(root) = &args[0] else { unreachable!("checked by the signature") };
+        let root = PathBuf::from(root);
+        if !root.exists() {
+            return Err(format!("fs.watch('{}'): no such file or directory", root.display()));
+        }
+        let recursive = !matches!(args.get(2), Some(Value::Bool(false)));
+        let debounce = match args.get(3) {
+            Some(Value::Int(ms)) if *ms < 0 => return Err(format!("fs.watch(): debounce_ms must not be negative, got {}", ms)),
+            Some(Value::Int(ms)) => Duration::from_millis(*ms as u64),
+            _ => DEFAULT_DEBOUNCE,
+        };
+        let poll = POLL_INTERVAL.min(debounce.max(Duration::from_millis(10)));
+
+        let mut current = snapshot(&root, recursive);
+        let mut pending: BTreeMap<PathBuf, Change> = BTreeMap::new();
+        let mut last_change = Instant::now();
+        let mut delivered = 0;
+        loop {
+            std::thread::sleep(poll);
+            let next = snapshot(&root, recursive);
+            for (path, change) in diff(&current, &next) {
+                coalesce(&mut pending, path, change);
+                last_change = Instant::now();
+            }
+            current = next;
+            if pending.is_empty() || last_change.elapsed() < debounce {
+                continue;
+            }
+            for (path, change) in std::mem::take(&mut pending) {
+                let event = vec![Value::Str(change.name().to_string()), Value::Str(path.to_string_lossy().into_owned())];
+                delivered += 1;
+                if matches!(vm.call_function_now(args[1].clone(), event)?, Value::Bool(false)) {
+                    return Ok(Value::Int(delivered));
+                }
+            }
+        }
+    }).needs(Capability::FsRead),
+    builtin("fs.write_atomic", "(path: str, content: str | [int])", "Replaces the file at `path` with `content` (a string or byte values) through a temporary file renamed over it, so it is never left half-written.", |args, _| {
+        let Value::Str(path) = &args[0] else { unreachable!("checked by the signature") };
+        let bytes = match &args[1] {
+            Value::Str(text) => text.as_bytes().to_vec(),
+            Value::Array(values) => values.iter().map(|v| match v {
+                Value::Int(b) => u8::try_from(*b).map_err(|_| format!("fs.write_atomic(): byte values must be 0 to 255, got {}", b)),
+                other => Err(format!("fs.write_atomic(): content must hold byte values, got {}", crate::builtins::type_name(other))),
+            }).collect::<Result<_, _>>()?,
+            _ => unreachable!("checked by the signature"),
+        };
+        quota::charge(Resource::FsWriteBytes, bytes.len() as u64)?;
+        write_atomic(Path::new(path), &bytes).map_err(|e| format!("fs.write_atomic('{}'): {}", path, e))?;
+        Ok(Value::Null)
+    }).needs(Capability::FsWrite),
+];
+
+const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// How often `fs.watch` rescans, at most.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The optional `fs.walk` filters; `None` lets everything through.
+#[derive(Default)]
+struct Filter {
+    exts: Option<Vec<String>>,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    modified_after: Option<i64>,
+}
+
+impl Filter {
+    fn from_args(args: &[Value]) -> Filter {
+        let size = |v: Option<&Value>| match v {
+            Some(Value::Int(n)) => Some((*n).max(0) as u64),
+            _ => None,
+        };
+        Filter {
+            exts: match args.first() {
+                Some(Value::Str(exts)) => Some(exts.split(',').map(|e| e.trim().trim_start_matches('.').to_lowercase()).filter(|e| !e.is_empty()).collect()),
+                _ => None,
+            },
+            min_size: size(args.get(1)),
+            max_size: size(args.get(2)),
+            modified_after: match args.get(3) {
+                Some(Value::Int(t)) => Some(*t),
+                _ => None,
+            },
+        }
+    }
+
+    fn matches(&self, path: &Path, size: u64, mtime: i64) -> bool {
+        let ext = path.extension().map(|e| e.to_string_lossy().to_lowercase());
+        self.exts.as_ref().is_none_or(|exts| ext.is_some_and(|ext| exts.contains(&ext)))
+            && self.min_size.is_none_or(|min| size >= min)
+            && self.max_size.is_none_or(|max| size <= max)
+            && self.modified_after.is_none_or(|after| mtime > after)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Change {
+    Created,
+    Modified,
+    Removed,
+}
+
+impl Change {
+    fn name(self) -> &'static str {
+        match self {
+            Change::Created => "created",
+            Change::Modified => "modified",
+            Change::Removed => "removed",
+        }
+    }
+}
+
+/// What a rescan compares: a file changed when its size or mtime did.
+#[derive(Clone, Copy, PartialEq)]
+struct Stamp {
+    len: u64,
+    modified: Option<SystemTime>,
+    is_dir: bool,
+}
+
+/// Every entry under `root` (or `root` alone when it is a file). Symlinks
+/// are recorded but not followed, so a link cycle cannot loop.
+fn snapshot(root: &Path, recursive: bool) -> HashMap<PathBuf, Stamp> {
+    let mut entries = HashMap::new();
+    let mut dirs = vec![root.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let Ok(read) = std::fs::read_dir(&dir) else { continue };
+        for entry in read.flatten() {
+            let Ok(meta) = entry.path().symlink_metadata() else { continue };
+            if meta.is_dir() && recursive {
+                dirs.push(entry.path());
+            }
+            entries.insert(entry.path(), Stamp { len: meta.len(), modified: meta.modified().ok(), is_dir: meta.is_dir() });
+        }
+    }
+    if !root.is_dir()
+        && let Ok(meta) = root.symlink_metadata()
+    {
+        entries.insert(root.to_path_buf(), Stamp { len: meta.len(), modified: meta.modified().ok(), is_dir: false });
+    }
+    entries
+}
+
+/// Changes from `old` to `new`. A directory's own mtime moves whenever its
+/// contents do, so directories only report being created or removed.
+fn diff(old: &HashMap<PathBuf, Stamp>, new: &HashMap<PathBuf, Stamp>) -> Vec<(PathBuf, Change)> {
+    let mut changes: Vec<(PathBuf, Change)> = new.iter().filter_map(|(path, stamp)| match old.get(path) {
+        None => Some((path.clone(), Change::Created)),
+        Some(prev) if prev != stamp && !(prev.is_dir && stamp.is_dir) => Some((path.clone(), Change::Modified)),
+        Some(_) => None,
+    }).collect();
+    changes.extend(old.keys().filter(|path| !new.contains_key(*path)).map(|path| (path.clone(), Change::Removed)));
+    changes.sort_by(|a, b| a.0.cmp(&b.0));
+    changes
+}
+
+/// Folds `change` into what is already pending for `path` in this burst; a
+/// file created and removed within one burst drops out.
+fn coalesce(pending: &mut BTreeMap<PathBuf, Change>, path: PathBuf, change: Change) {
+    let merged = match (pending.get(&path), change) {
+        (None, change) => Some(change),
+        (Some(Change::Created), Change::Removed) => None,
+        (Some(Change::Created), _) => Some(Change::Created),
+        (Some(Change::Removed), Change::Created) => Some(Change::Modified),
+        (Some(_), change) => Some(change),
+    };
+    match merged {
+        Some(change) => pending.insert(path, change),
+        None => pending.remove(&path),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rescans_report_created_modified_and_removed_files() {
+        let dir = std::env::temp_dir().join(format!("kinetix_fs_watch_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("a.txt"), "a").unwrap();
+        std::fs::write(dir.join("sub/b.txt"), "b").unwrap();
+
+        let before = snapshot(&dir, true);
+        assert_eq!(before.len(), 3);
+        assert_eq!(snapshot(&dir, false).len(), 2);
+        assert_eq!(snapshot(&dir.join("a.txt"), true).len(), 1);
+
+        std::fs::write(dir.join("a.txt"), "longer").unwrap();
+        std::fs::remove_file(dir.join("sub/b.txt")).unwrap();
+        std::fs::write(dir.join("sub/c.txt"), "c").unwrap();
+        let changes = diff(&before, &snapshot(&dir, true));
+        assert_eq!(changes, vec![
+            (dir.join("a.txt"), Change::Modified),
+            (dir.join("sub/b.txt"), Change::Removed),
+            (dir.join("sub/c.txt"), Change::Created),
+        ]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn write_atomic_replaces_the_file_and_leaves_no_temporary() {
+        let dir = std::env::temp_dir().join(format!("kinetix_fs_atomic_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.json");
+        std::fs::write(&path, "old contents, longer than the new").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o640)).unwrap();
+        }
+
+        let mut vm = crate::vm::VM::new(kinetix_kicomp::ir::CompiledProgram::new());
+        let call = |args: Vec<Value>, vm: &mut crate::vm::VM| crate::builtins::call_builtin("fs.write_atomic", &args, vm);
+        call(vec![Value::Str(path.to_string_lossy().into_owned()), Value::Str("{}".into())], &mut vm).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "{}");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o640);
+        }
+        call(vec![Value::Str(path.to_string_lossy().into_owned()), Value::Array(vec![Value::Int(104), Value::Int(105)])], &mut vm).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"hi");
+
+        let err = call(vec![Value::Str(path.to_string_lossy().into_owned()), Value::Array(vec![Value::Int(300)])], &mut vm).unwrap_err();
+        assert_eq!(err, "fs.write_atomic(): byte values must be 0 to 255, got 300");
+        let missing = dir.join("no/such/dir.txt");
+        assert!(call(vec![Value::Str(missing.to_string_lossy().into_owned()), Value::Str("x".into())], &mut vm).is_err());
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1, "temporary files left behind");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn walk_filters_by_extension_size_and_mtime() {
+        let filter = Filter::from_args(&[Value::Str("kix, .RS".into()), Value::Int(10), Value::Null, Value::Int(100)]);
+        assert!(filter.matches(Path::new("src/main.kix"), 10, 101));
+        assert!(filter.matches(Path::new("lib.rs"), 50, 200));
+        assert!(!filter.matches(Path::new("notes.txt"), 50, 200));
+        assert!(!filter.matches(Path::new("Makefile"), 50, 200));
+        assert!(!filter.matches(Path::new("small.kix"), 9, 200));
+        assert!(!filter.matches(Path::new("old.kix"), 50, 100));
+        assert!(Filter::from_args(&[]).matches(Path::new("anything"), 0, 0));
+    }
+
+    #[test]
+    fn a_burst_reports_each_path_once() {
+        let mut pending = BTreeMap::new();
+        let (a, b, c) = (PathBuf::from("a"), PathBuf::from("b"), PathBuf::from("c"));
+        coalesce(&mut pending, a.clone(), Change::Created);
+        coalesce(&mut pending, a.clone(), Change::Modified);
+        coalesce(&mut pending, b.clone(), Change::Created);
+        coalesce(&mut pending, b.clone(), Change::Removed);
+        coalesce(&mut pending, c.clone(), Change::Removed);
+        coalesce(&mut pending, c.clone(), Change::Created);
+        assert_eq!(pending.into_iter().collect::<Vec<_>>(), vec![(a, Change::Created), (c, Change::Modified)]);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::builtins::{builtin, Builtin};
 use crate::vm::{Value, VM};
 use minifb::{Window, WindowOptions, Key, MouseMode, MouseButton};
 use std::sync::Mutex;
@@ -163,6 +164,18 @@ fn map_key_to_char(k: Key) -> Option<char> {
     }
 }
 
+/// `graph.*`: an immediate-mode window; coordinates are truncated to pixels
+/// and colors are `0xRRGGBB` ints.
+pub const BUILTINS: &[Builtin] = &[
+    builtin("graph.window", "(title: str?, width: int?, height: int?, frame: fn?)", "Opens a window and calls `frame` once per frame until it is closed or Escape is pressed.", |a, vm| call("window", a, vm)),
+    builtin("graph.clear", "(color: int?)", "Fills the frame with `color` (default black).", |a, vm| call("clear", a, vm)),
+    builtin("graph.label", "(x: float, y: float, text: str)", "Draws `text` with its top-left corner at (x, y).", |a, vm| call("label", a, vm)),
+    builtin("graph.button", "(x: float?, y: float?, w: float?, h: float?, text: str?) -> bool", "Draws a button; true while the mouse is pressed over it.", |a, vm| call("button", a, vm)),
+    builtin("graph.plot_lines", "(x: float, y: float, w: float, h: float, values: [float])", "Plots `values` as a line chart in the given box.", |a, vm| call("plot_lines", a, vm)),
+    builtin("graph.draw_line", "(x1: float, y1: float, x2: float, y2: float, color: int?)", "Draws a line (default white).", |a, vm| call("draw_line", a, vm)),
+    builtin("graph.draw_circle", "(x: float, y: float, r: float, color: int?)", "Draws a circle outline centered on (x, y) (default white).", |a, vm| call("draw_circle", a, vm)),
+];
+
 pub fn call(name: &str, args: &[Value], vm: &mut VM) -> Result<Value, String> {
     match name {
         "window" => {
@@ -0,0 +1,384 @@
+//! `i18n`: message catalogs, plural rules and locale-aware formatting.
+
+use crate::builtins::{builtin, Builtin};
+use crate::vm::Value;
+use kinetix_kicomp::capability::Capability;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+pub const BUILTINS: &[Builtin] = &[
+    builtin("i18n.load", "(locale: str, path: str) -> int", "Adds the JSON catalog at `path` to `locale`; returns how many messages it holds.", |args, _| {
+        let (locale, path) = (str_arg(&args[0]), str_arg(&args[1]));
+        let src = std::fs::read_to_string(path).map_err(|e| format!("i18n.load('{}'): {}", path, e))?;
+        add(locale, &src).map_err(|e| format!("i18n.load('{}'): {}", path, e))
+    }).needs(Capability::FsRead),
+    builtin("i18n.add", "(locale: str, catalog: str) -> int", "Adds a catalog given as a JSON string to `locale`; returns how many messages it holds.", |args, _| {
+        add(str_arg(&args[0]), str_arg(&args[1])).map_err(|e| format!("i18n.add(): {}", e))
+    }),
+    builtin("i18n.set_locale", "(locale: str)", "Makes `locale` (`fr`, `pt-BR`, `de_DE`) current for translation and formatting.", |args, _| {
+        state().locale = normalize(str_arg(&args[0]));
+        Ok(Value::Null)
+    }),
+    builtin("i18n.locale", "() -> str", "The current locale.", |_, _| Ok(Value::Str(state().locale.clone()))),
+    builtin("i18n.t", "(key: str, params: {str: any}?) -> str", "The message for `key` in the current locale with `{name}` placeholders filled, or `key` when no catalog has it.", |args, _| {
+        let key = str_arg(&args[0]);
+        let state = state();
+        let text = match state.find(key) {
+            Some(Message::Text(text)) => text.clone(),
+            Some(Message::Plural(forms)) => forms["other"].clone(),
+            None => key.to_string(),
+        };
+        Ok(Value::Str(interpolate(&text, args.get(1), None)))
+    }),
+    builtin("i18n.plural", "(key: str, n: int, params: {str: any}?) -> str", "The plural form of `key` the current locale uses for `n`, with `{n}` and other placeholders filled.", |args, _| {
+        let (key, n) = (str_arg(&args[0]), args[1].as_int()?);
+        let state = state();
+        let text = match state.find(key) {
+            Some(Message::Plural(forms)) => {
+                let category = plural_category(language(&state.locale), n);
+                forms.get(category).unwrap_or(&forms["other"]).clone()
+            }
+            Some(Message::Text(text)) => text.clone(),
+            None => key.to_string(),
+        };
+        Ok(Value::Str(interpolate(&text, args.get(2), Some(n))))
+    }),
+    builtin("i18n.number", "(x: float, decimals: int?, locale: str?) -> str", "`x` with the locale's digit grouping and decimal separator, rounded to `decimals` places when given.", |args, _| {
+        let decimals = match args.get(1) {
+            None | Some(Value::Null) => None,
+            Some(v) => Some(usize::try_from(v.as_int()?).map_err(|_| "i18n.number(): decimals must not be negative".to_string())?),
+        };
+        let locale = locale_arg(args.get(2));
+        Ok(Value::Str(format_number(&args[0], decimals, &locale)))
+    }),
+    builtin("i18n.date", "(date: int | str, style: str?, locale: str?) -> str", "A Unix timestamp (UTC) or `YYYY-MM-DD` date in the locale's `short` (default), `long` or `iso` style.", |args, _| {
+        let (y, m, d) = match &args[0] {
+            Value::Int(ts) => civil_from_days(ts.div_euclid(86_400)),
+            Value::Str(s) => parse_iso_date(s).ok_or_else(|| format!("i18n.date(): expected YYYY-MM-DD, got '{}'", s))?,
+            _ => unreachable!("checked by the signature"),
+        };
+        let style = match args.get(1) {
+            Some(Value::Str(s)) => s.as_str(),
+            _ => "short",
+        };
+        format_date(y, m, d, style, &locale_arg(args.get(2))).map(Value::Str)
+    }),
+];
+
+enum Message {
+    Text(String),
+    /// Keyed by plural category; always has `other`.
+    Plural(HashMap<String, String>),
+}
+
+struct State {
+    locale: String,
+    catalogs: HashMap<String, HashMap<String, Message>>,
+}
+
+impl State {
+    /// `key` in the current locale, then in its bare language.
+    fn find(&self, key: &str) -> Option<&Message> {
+        let lang = language(&self.locale);
+        [self.locale.as_str(), lang].iter()
+            .find_map(|locale| self.catalogs.get(*locale)?.get(key))
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref STATE: Mutex<State> = Mutex::new(State {
+        locale: std::env::var("LANG").ok().filter(|l| !l.is_empty() && l != "C" && l != "POSIX").map_or_else(|| "en".to_string(), |l| normalize(&l)),
+        catalogs: HashMap::new(),
+    });
+}
+
+fn state() -> std::sync::MutexGuard<'static, State> {
+    STATE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn str_arg(value: &Value) -> &str {
+    match value {
+        Value::Str(s) => s,
+        _ => unreachable!("checked by the signature"),
+    }
+}
+
+fn locale_arg(value: Option<&Value>) -> String {
+    match value {
+        Some(Value::Str(s)) => normalize(s),
+        _ => state().locale.clone(),
+    }
+}
+
+/// `de_DE.UTF-8` → `de-DE`: language lowercase, region uppercase, no
+/// encoding or modifier.
+fn normalize(locale: &str) -> String {
+    let base = locale.split(['.', '@']).next().unwrap_or("");
+    let mut parts = base.split(['-', '_']);
+    let mut out = parts.next().unwrap_or("").to_ascii_lowercase();
+    if let Some(region) = parts.next().filter(|r| !r.is_empty()) {
+        out.push('-');
+        out.push_str(&region.to_ascii_uppercase());
+    }
+    out
+}
+
+fn language(locale: &str) -> &str {
+    locale.split('-').next().unwrap_or(locale)
+}
+
+fn region(locale: &str) -> Option<&str> {
+    locale.split('-').nth(1)
+}
+
+const CATEGORIES: &[&str] = &["zero", "one", "two", "few", "many", "other"];
+
+/// Adds the messages of a JSON catalog to `locale`, returning their count.
+fn add(locale: &str, src: &str) -> Result<Value, String> {
+    let json: serde_json::Value = serde_json::from_str(src).map_err(|e| e.to_string())?;
+    let serde_json::Value::Object(root) = json else {
+        return Err("a catalog must be a JSON object".to_string());
+    };
+    let mut messages = HashMap::new();
+    flatten("", &root, &mut messages)?;
+    let count = messages.len() as i64;
+    state().catalogs.entry(normalize(locale)).or_default().extend(messages);
+    Ok(Value::Int(count))
+}
+
+fn flatten(prefix: &str, object: &serde_json::Map<String, serde_json::Value>, out: &mut HashMap<String, Message>) -> Result<(), String> {
+    for (key, value) in object {
+        let key = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+        match value {
+            serde_json::Value::String(text) => {
+                out.insert(key, Message::Text(text.clone()));
+            }
+            serde_json::Value::Object(forms) if forms.contains_key("other") && forms.keys().all(|k| CATEGORIES.contains(&k.as_str())) => {
+                let forms = forms.iter()
+                    .map(|(k, v)| match v {
+                        serde_json::Value::String(text) => Ok((k.clone(), text.clone())),
+                        _ => Err(format!("plural form '{}.{}' must be a string", key, k)),
+                    })
+                    .collect::<Result<_, _>>()?;
+                out.insert(key, Message::Plural(forms));
+            }
+            serde_json::Value::Object(nested) => flatten(&key, nested, out)?,
+            _ => return Err(format!("message '{}' must be a string or an object", key)),
+        }
+    }
+    Ok(())
+}
+
+/// Replaces `{name}` with the matching param (and `{n}` with `n`); unknown
+/// placeholders stay as written.
+fn interpolate(text: &str, params: Option<&Value>, n: Option<i64>) -> String {
+    let params = match params {
+        Some(Value::Map(map)) => Some(map),
+        _ => None,
+    };
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(open) = rest.find('{') {
+        out.push_str(&rest[..open]);
+        rest = &rest[open..];
+        let Some(close) = rest.find('}') else { break };
+        let name = &rest[1..close];
+        match params.and_then(|p| p.get(name)) {
+            Some(Value::Str(s)) => out.push_str(s),
+            Some(other) => out.push_str(&other.to_string()),
+            None if name == "n" && n.is_some() => out.push_str(&n.unwrap_or_default().to_string()),
+            None => out.push_str(&rest[..=close]),
+        }
+        rest = &rest[close + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// The CLDR plural category of the integer `n` in `lang`.
+fn plural_category(lang: &str, n: i64) -> &'static str {
+    let n = n.unsigned_abs();
+    let (n10, n100) = (n % 10, n % 100);
+    let slavic_few = (2..=4).contains(&n10) && !(12..=14).contains(&n100);
+    match lang {
+        "ja" | "zh" | "ko" | "vi" | "th" | "id" | "ms" => "other",
+        "fr" | "pt" | "hi" => if n <= 1 { "one" } else { "other" },
+        "ru" | "uk" | "be" => {
+            if n10 == 1 && n100 != 11 { "one" } else if slavic_few { "few" } else { "many" }
+        }
+        "pl" => if n == 1 { "one" } else if slavic_few { "few" } else { "many" },
+        "cs" | "sk" => match n {
+            1 => "one",
+            2..=4 => "few",
+            _ => "other",
+        },
+        "ar" => match (n, n100) {
+            (0, _) => "zero",
+            (1, _) => "one",
+            (2, _) => "two",
+            (_, 3..=10) => "few",
+            (_, 11..=99) => "many",
+            _ => "other",
+        },
+        _ => if n == 1 { "one" } else { "other" },
+    }
+}
+
+/// Digit-group and decimal separators.
+fn separators(locale: &str) -> (&'static str, &'static str) {
+    match (language(locale), region(locale)) {
+        ("de" | "it" | "fr", Some("CH")) => ("’", "."),
+        ("fr", _) => ("\u{202f}", ","),
+        ("de" | "it" | "es" | "nl" | "pt" | "id" | "tr" | "da" | "el" | "ro" | "hr" | "sl" | "sr", _) => (".", ","),
+        ("ru" | "uk" | "be" | "pl" | "cs" | "sk" | "sv" | "fi" | "nb" | "no" | "hu" | "bg" | "lt" | "lv" | "et", _) => ("\u{a0}", ","),
+        _ => (",", "."),
+    }
+}
+
+fn format_number(x: &Value, decimals: Option<usize>, locale: &str) -> String {
+    let plain = match (x, decimals) {
+        (Value::Int(n), None) => n.to_string(),
+        (Value::Int(n), Some(d)) => format!("{:.*}", d, *n as f64),
+        (Value::Float(f), None) => f.to_string(),
+        (Value::Float(f), Some(d)) => format!("{:.*}", d, f),
+        _ => unreachable!("checked by the signature"),
+    };
+    let (digits, sign) = match plain.strip_prefix('-') {
+        Some(rest) => (rest, "-"),
+        None => (plain.as_str(), ""),
+    };
+    if !digits.starts_with(|c: char| c.is_ascii_digit()) {
+        return plain; // NaN, inf
+    }
+    let (int, frac) = digits.split_once('.').map_or((digits, None), |(i, f)| (i, Some(f)));
+    let (group, point) = separators(locale);
+    let mut out = sign.to_string();
+    for (i, c) in int.chars().enumerate() {
+        if i > 0 && (int.len() - i) % 3 == 0 {
+            out.push_str(group);
+        }
+        out.push(c);
+    }
+    if let Some(frac) = frac {
+        out.push_str(point);
+        out.push_str(frac);
+    }
+    out
+}
+
+/// Days since 1970-01-01 to a proleptic Gregorian (year, month, day)
+/// (Howard Hinnant's `civil_from_days`).
+pub(crate) fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    (yoe + era * 400 + i64::from(m <= 2), m, d)
+}
+
+fn parse_iso_date(s: &str) -> Option<(i64, u32, u32)> {
+    let mut parts = s.splitn(3, '-');
+    let (y, m, d) = (parts.next()?.parse().ok()?, parts.next()?.parse().ok()?, parts.next()?.parse().ok()?);
+    let leap = (y % 4 == 0 && y % 100 != 0) || y % 400 == 0;
+    let days_in_month = [31, if leap { 29 } else { 28 }, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31];
+    let valid = (1..=12).contains(&m) && (1..=days_in_month[m as usize - 1]).contains(&d);
+    valid.then_some((y, m, d))
+}
+
+fn month_names(lang: &str) -> Option<[&'static str; 12]> {
+    Some(match lang {
+        "en" => ["January", "February", "March", "April", "May", "June", "July", "August", "September", "October", "November", "December"],
+        "de" => ["Januar", "Februar", "März", "April", "Mai", "Juni", "Juli", "August", "September", "Oktober", "November", "Dezember"],
+        "fr" => ["janvier", "février", "mars", "avril", "mai", "juin", "juillet", "août", "septembre", "octobre", "novembre", "décembre"],
+        "it" => ["gennaio", "febbraio", "marzo", "aprile", "maggio", "giugno", "luglio", "agosto", "settembre", "ottobre", "novembre", "dicembre"],
+        "es" => ["enero", "febrero", "marzo", "abril", "mayo", "junio", "julio", "agosto", "septiembre", "octubre", "noviembre", "diciembre"],
+        "pt" => ["janeiro", "fevereiro", "março", "abril", "maio", "junho", "julho", "agosto", "setembro", "outubro", "novembro", "dezembro"],
+        "nl" => ["januari", "februari", "maart", "april", "mei", "juni", "juli", "augustus", "september", "oktober", "november", "december"],
+        _ => return None,
+    })
+}
+
+fn format_date(y: i64, m: u32, d: u32, style: &str, locale: &str) -> Result<String, String> {
+    let iso = format!("{:04}-{:02}-{:02}", y, m, d);
+    let lang = language(locale);
+    Ok(match style {
+        "iso" => iso,
+        "short" => match (lang, region(locale)) {
+            ("en", None | Some("US")) => format!("{}/{}/{}", m, d, y),
+            ("en" | "fr" | "it" | "es" | "pt" | "el" | "vi", _) => format!("{:02}/{:02}/{}", d, m, y),
+            ("de" | "ru" | "uk" | "pl" | "cs" | "sk" | "fi" | "nb" | "no" | "tr" | "da", _) => format!("{:02}.{:02}.{}", d, m, y),
+            ("nl", _) => format!("{:02}-{:02}-{}", d, m, y),
+            ("ja" | "zh", _) => format!("{}/{:02}/{:02}", y, m, d),
+            _ => iso,
+        },
+        "long" => match (lang, month_names(lang)) {
+            ("ja" | "zh", _) => format!("{}年{}月{}日", y, m, d),
+            (_, None) => iso,
+            (lang, Some(months)) => {
+                let month = months[m as usize - 1];
+                match (lang, region(locale)) {
+                    ("en", None | Some("US")) => format!("{} {}, {}", month, d, y),
+                    ("de", _) => format!("{}. {} {}", d, month, y),
+                    ("es" | "pt", _) => format!("{} de {} de {}", d, month, y),
+                    _ => format!("{} {} {}", d, month, y),
+                }
+            }
+        },
+        other => return Err(format!("i18n.date(): unknown style '{}' (expected short, long or iso)", other)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plural_rules_and_catalog_lookup() {
+        assert_eq!([0, 1, 2, 5].map(|n| plural_category("en", n)), ["other", "one", "other", "other"]);
+        assert_eq!([0, 1, 2].map(|n| plural_category("fr", n)), ["one", "one", "other"]);
+        assert_eq!([1, 3, 5, 11, 21, 22, 112].map(|n| plural_category("ru", n)), ["one", "few", "many", "many", "one", "few", "many"]);
+        assert_eq!([1, 22, 25].map(|n| plural_category("pl", n)), ["one", "few", "many"]);
+        assert_eq!([0, 1, 2, 3, 11, 100].map(|n| plural_category("ar", n)), ["zero", "one", "two", "few", "many", "other"]);
+
+        assert_eq!(normalize("it_IT.UTF-8"), "it-IT");
+        add("xx", r#"{"hi": "Ciao, {name}!", "menu": {"open": "Apri"}, "files": {"one": "{n} file", "other": "{n} file"}}"#).unwrap();
+        let name = Value::Map([("name".to_string(), Value::Str("Ada".into()))].into_iter().collect());
+        let mut state = state();
+        state.locale = "xx-YY".into();
+        assert!(matches!(state.find("menu.open"), Some(Message::Text(t)) if t == "Apri"));
+        let Some(Message::Text(hi)) = state.find("hi") else { panic!("missing 'hi'") };
+        assert_eq!(interpolate(hi, Some(&name), None), "Ciao, Ada!");
+        assert_eq!(interpolate("{n} of {total}", None, Some(3)), "3 of {total}");
+        assert!(state.find("nope").is_none());
+        drop(state);
+        assert!(add("xx", r#"{"bad": 1}"#).is_err());
+    }
+
+    #[test]
+    fn numbers_and_dates_follow_the_locale() {
+        assert_eq!(format_number(&Value::Float(1234567.891), Some(2), "it"), "1.234.567,89");
+        assert_eq!(format_number(&Value::Int(-1234567), None, "en-US"), "-1,234,567");
+        assert_eq!(format_number(&Value::Int(1234), None, "fr"), "1\u{202f}234");
+        assert_eq!(format_number(&Value::Float(0.5), None, "de"), "0,5");
+
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(19_787), (2024, 3, 5));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        assert_eq!(parse_iso_date("2024-02-29"), Some((2024, 2, 29)));
+        assert_eq!(parse_iso_date("2023-02-29"), None);
+
+        let date = |style, locale| format_date(2024, 3, 5, style, locale).unwrap();
+        assert_eq!(date("short", "en"), "3/5/2024");
+        assert_eq!(date("short", "en-GB"), "05/03/2024");
+        assert_eq!(date("short", "de-DE"), "05.03.2024");
+        assert_eq!(date("long", "en-US"), "March 5, 2024");
+        assert_eq!(date("long", "es"), "5 de marzo de 2024");
+        assert_eq!(date("long", "ja"), "2024年3月5日");
+        assert_eq!(date("iso", "fr"), "2024-03-05");
+        assert!(format_date(2024, 3, 5, "medium", "en").is_err());
+    }
+}
@@ -0,0 +1,169 @@
+//! `io` module: process streams for pipeline use
+//! (`cat data.json | kivm eval 'len(json.parse(io.stdin_all()))'`).
+
+use crate::builtins::{builtin, Builtin};
+use crate::vm::Value;
+use lazy_static::lazy_static;
+use std::io::{IsTerminal, Read, Write};
+use std::sync::Mutex;
+
+lazy_static! {
+    /// Stdin can only be drained once; the first read is kept so later
+    /// `io.stdin_all()` calls (and `--stdin-var`) all see the same text.
+    static ref STDIN: Mutex<Option<String>> = Mutex::new(None);
+    static ref STDOUT: Mutex<Stdout> = Mutex::new(Stdout { buffering: None, pending: String::new(), captured: None });
+}
+
+/// How program output (`print`, `term.*`) reaches stdout. Full buffering is
+/// much faster for print-heavy programs; by default a terminal gets line
+/// buffering and a pipe or file full buffering.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Buffering {
+    /// Write each line as soon as it is complete.
+    Line,
+    /// Write when `FULL_CAPACITY` bytes are pending or on a flush.
+    Full,
+}
+
+const FULL_CAPACITY: usize = 64 * 1024;
+
+struct Stdout {
+    /// `None` until set or first used; then decided by whether stdout is a
+    /// terminal.
+    buffering: Option<Buffering>,
+    pending: String,
+    /// Set while `capture` runs: output is collected here instead.
+    captured: Option<String>,
+}
+
+impl Stdout {
+    fn flush(&mut self) {
+        if !self.pending.is_empty() {
+            // print! rather than a raw stdout handle, so test harnesses that
+            // capture output still see it.
+            print!("{}", self.pending);
+            self.pending.clear();
+        }
+        std::io::stdout().flush().ok();
+    }
+}
+
+/// Sets how output is buffered; `None` restores the terminal-dependent
+/// default. The CLI calls this once for `--buffer`.
+pub fn set_buffering(buffering: Option<Buffering>) {
+    let mut out = STDOUT.lock().unwrap_or_else(|e| e.into_inner());
+    out.flush();
+    out.buffering = buffering;
+}
+
+/// Writes program output, flushing when the buffering mode says so.
+pub fn write(text: &str) {
+    let mut out = STDOUT.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(captured) = out.captured.as_mut() {
+        captured.push_str(text);
+        return;
+    }
+    out.pending.push_str(text);
+    let buffering = *out.buffering.get_or_insert_with(|| {
+        if std::io::stdout().is_terminal() { Buffering::Line } else { Buffering::Full }
+    });
+    let due = match buffering {
+        Buffering::Line => out.pending.contains('\n'),
+        Buffering::Full => out.pending.len() >= FULL_CAPACITY,
+    };
+    if due {
+        out.flush();
+    }
+}
+
+pub fn write_line(text: &str) {
+    write(&format!("{}\n", text));
+}
+
+/// Writes out everything pending. Also called by `io.flush()`, before
+/// `input()` reads, and when a VM run ends or the process exits, so runtime
+/// errors on stderr come after the output that preceded them.
+pub fn flush() {
+    STDOUT.lock().unwrap_or_else(|e| e.into_inner()).flush();
+}
+
+/// Runs `f` with program output collected instead of written, and returns
+/// it with `f`'s result. `kivm test --format` uses it to report each test's
+/// output without mixing it into the report.
+pub fn capture<R>(f: impl FnOnce() -> R) -> (R, String) {
+    {
+        let mut out = STDOUT.lock().unwrap_or_else(|e| e.into_inner());
+        out.flush();
+        out.captured = Some(String::new());
+    }
+    let result = f();
+    let captured = STDOUT.lock().unwrap_or_else(|e| e.into_inner()).captured.take().unwrap_or_default();
+    (result, captured)
+}
+
+/// Flushes when dropped, so every way out of a VM run flushes.
+pub(crate) struct FlushOnDrop;
+
+impl Drop for FlushOnDrop {
+    fn drop(&mut self) {
+        flush();
+    }
+}
+
+pub const BUILTINS: &[Builtin] = &[
+    builtin("io.stdin_all", "() -> str", "All of stdin; read on the first call and returned again by later ones.", |a, _| call("stdin_all", a)),
+    builtin("io.flush", "()", "Writes out buffered program output.", |a, _| call("flush", a)),
+];
+
+pub fn call(func_name: &str, args: &[Value]) -> Result<Value, String> {
+    match func_name {
+        "stdin_all" => {
+            if !args.is_empty() {
+                return Err("io.stdin_all() takes no arguments".into());
+            }
+            read_once(&STDIN, std::io::stdin().lock()).map(Value::Str)
+        }
+        "flush" => {
+            if !args.is_empty() {
+                return Err("io.flush() takes no arguments".into());
+            }
+            flush();
+            Ok(Value::Null)
+        }
+        _ => Err(format!("Unknown io function: {}", func_name)),
+    }
+}
+
+fn read_once(cache: &Mutex<Option<String>>, mut reader: impl Read) -> Result<String, String> {
+    let mut cached = cache.lock().unwrap();
+    if let Some(text) = cached.as_ref() {
+        return Ok(text.clone());
+    }
+    let mut text = String::new();
+    reader.read_to_string(&mut text).map_err(|e| format!("io.stdin_all: {}", e))?;
+    *cached = Some(text.clone());
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stdin_is_read_once_and_cached() {
+        let cache = Mutex::new(None);
+        assert_eq!(read_once(&cache, "[1, 2, 3]\n".as_bytes()).unwrap(), "[1, 2, 3]\n");
+        assert_eq!(read_once(&cache, "ignored".as_bytes()).unwrap(), "[1, 2, 3]\n");
+    }
+
+    #[test]
+    fn captured_output_is_returned_instead_of_written() {
+        let (value, output) = capture(|| {
+            write_line("captured line");
+            7
+        });
+        assert_eq!(value, 7);
+        // Other tests may print concurrently into the same capture.
+        assert!(output.contains("captured line\n"), "{:?}", output);
+    }
+}
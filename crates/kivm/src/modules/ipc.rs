@@ -0,0 +1,242 @@
+//! `ipc`: JSON messages between Kinetix processes, over a worker's
+//! stdin/stdout or a TCP connection.
+
+use crate::builtins::{builtin, Builtin};
+use crate::quota::{self, Resource};
+use crate::vm::Value;
+use kinetix_kicomp::capability::Capability;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Starts a JSON text sequence record (RFC 7464). Optional on stdin and
+/// sockets, so plain JSON lines can be piped in; a worker's stdout lines
+/// without it are its own output, which the parent prints.
+const RECORD_SEPARATOR: char = '\u{1e}';
+
+/// The channel to the process that spawned this one.
+const PARENT: i64 = 0;
+
+/// A received line: a message, or why it isn't one.
+type Incoming = Result<serde_json::Value, String>;
+
+struct Channel {
+    /// `None` once closed.
+    out: Option<Box<dyn Write + Send>>,
+    incoming: Arc<Mutex<Receiver<Incoming>>>,
+    child: Option<Child>,
+    /// Shut down on close, so the peer sees the end even though the reader
+    /// thread still holds a handle.
+    socket: Option<TcpStream>,
+}
+
+lazy_static::lazy_static! {
+    static ref CHANNELS: Mutex<HashMap<i64, Channel>> = Mutex::new(HashMap::new());
+    static ref NEXT_ID: Mutex<i64> = Mutex::new(1);
+}
+
+pub const BUILTINS: &[Builtin] = &[
+    builtin("ipc.spawn", "(script: str, args: [str]?) -> int", "Starts a kivm running `script` (with `args`; an .exki file is run, anything else executed from source) and returns a channel to it.", |args, _| {
+        let Value::Str(script) = &args[0] else { unreachable!("checked by the signature") };
+        let extra: Vec<String> = match args.get(1) {
+            Some(Value::Array(items)) => items.iter().map(|v| v.to_string()).collect(),
+            _ => Vec::new(),
+        };
+        quota::charge(Resource::SpawnedProcs, 1).map_err(|e| format!("ipc.spawn('{}'): {}", script, e))?;
+        let kivm = std::env::current_exe().map_err(|e| format!("ipc.spawn('{}'): {}", script, e))?;
+        let mut command = Command::new(kivm);
+        command.arg(if script.ends_with(".exki") { "run" } else { "exec" }).arg(script).args(extra);
+        spawn(command).map(Value::Int).map_err(|e| format!("ipc.spawn('{}'): {}", script, e))
+    }).needs(Capability::OsExecute),
+    builtin("ipc.connect", "(host: str, port: int) -> int", "Connects to a Kinetix process listening with `ipc.accept`; returns the channel.", |args, _| {
+        let (Value::Str(host), Value::Int(port)) = (&args[0], &args[1]) else { unreachable!("checked by the signature") };
+        let stream = TcpStream::connect((host.as_str(), *port as u16)).map_err(|e| format!("ipc.connect('{}', {}): {}", host, port, e))?;
+        socket(stream).map(Value::Int)
+    }).needs(Capability::NetAccess),
+    builtin("ipc.accept", "(port: int) -> int", "Listens on 127.0.0.1:`port` for one `ipc.connect` and returns its channel.", |args, _| {
+        let Value::Int(port) = &args[0] else { unreachable!("checked by the signature") };
+        let listener = TcpListener::bind(("127.0.0.1", *port as u16)).map_err(|e| format!("ipc.accept({}): {}", port, e))?;
+        let (stream, _) = listener.accept().map_err(|e| format!("ipc.accept({}): {}", port, e))?;
+        socket(stream).map(Value::Int)
+    }).needs(Capability::NetAccess),
+    builtin("ipc.send", "(channel: int, message: any)", "Sends `message` as JSON; channel 0 is the parent process.", |args, _| {
+        let Value::Int(id) = &args[0] else { unreachable!("checked by the signature") };
+        send(*id, &args[1]).map(|_| Value::Null).map_err(|e| format!("ipc.send({}): {}", id, e))
+    }),
+    builtin("ipc.recv", "(channel: int, timeout_ms: int?) -> any", "The next message, or null once the channel is closed or `timeout_ms` pass.", |args, _| {
+        let Value::Int(id) = &args[0] else { unreachable!("checked by the signature") };
+        let timeout = match args.get(1) {
+            Some(Value::Int(ms)) => Some(Duration::from_millis((*ms).max(0) as u64)),
+            _ => None,
+        };
+        recv(*id, timeout).map(|m| m.unwrap_or(Value::Null)).map_err(|e| format!("ipc.recv({}): {}", id, e))
+    }),
+    builtin("ipc.on_message", "(channel: int, handler: fn) -> int", "Calls `handler(message)` for each message until the channel closes or it returns false; returns how many it handled.", |args, vm| {
+        let Value::Int(id) = &args[0] else { unreachable!("checked by the signature") };
+        let mut handled = 0;
+        while let Some(message) = recv(*id, None).map_err(|e| format!("ipc.on_message({}): {}", id, e))? {
+            handled += 1;
+            if matches!(vm.call_function_now(args[1].clone(), vec![message])?, Value::Bool(false)) {
+                break;
+            }
+        }
+        Ok(Value::Int(handled))
+    }),
+    builtin("ipc.close", "(channel: int) -> int", "Closes the channel and waits for a spawned worker; returns its exit code (0 for sockets).", |args, _| {
+        let Value::Int(id) = &args[0] else { unreachable!("checked by the signature") };
+        close(*id).map(Value::Int).map_err(|e| format!("ipc.close({}): {}", id, e))
+    }),
+];
+
+fn register(channel: Channel) -> Result<i64, String> {
+    let mut next = NEXT_ID.lock().map_err(|_| "channel ID lock failed".to_string())?;
+    let id = *next;
+    *next += 1;
+    CHANNELS.lock().map_err(|_| "channel lock failed".to_string())?.insert(id, channel);
+    Ok(id)
+}
+
+/// Spawns `command` with piped stdin and stdout and registers it.
+fn spawn(mut command: Command) -> Result<i64, String> {
+    let mut child = command.stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().map_err(|e| e.to_string())?;
+    let stdin = child.stdin.take().expect("piped above");
+    let stdout = child.stdout.take().expect("piped above");
+    register(Channel { out: Some(Box::new(stdin)), incoming: pump(BufReader::new(stdout), true, false), child: Some(child), socket: None })
+}
+
+fn socket(stream: TcpStream) -> Result<i64, String> {
+    let reader = stream.try_clone().map_err(|e| e.to_string())?;
+    let handle = stream.try_clone().map_err(|e| e.to_string())?;
+    register(Channel { out: Some(Box::new(stream)), incoming: pump(BufReader::new(reader), false, true), child: None, socket: Some(handle) })
+}
+
+/// Reads `input` line by line on a thread of its own. With `forward`, lines
+/// that aren't records are the worker's output and are printed; otherwise
+/// every non-blank line is a message. With `metered` (sockets), every line
+/// counts against the `net-bytes` quota; going over it ends the channel with
+/// the quota error.
+fn pump(input: impl BufRead + Send + 'static, forward: bool, metered: bool) -> Arc<Mutex<Receiver<Incoming>>> {
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        for line in input.lines() {
+            let Ok(line) = line else { break };
+            if metered && let Err(e) = quota::charge(Resource::NetBytes, line.len() as u64 + 1) {
+                let _ = tx.send(Err(e));
+                break;
+            }
+            let record = match line.strip_prefix(RECORD_SEPARATOR) {
+                Some(record) => record,
+                None if forward => {
+                    super::io::write_line(&line);
+                    continue;
+                }
+                None if line.trim().is_empty() => continue,
+                None => &line,
+            };
+            let message = serde_json::from_str(record).map_err(|e| format!("bad message '{}': {}", record, e));
+            if tx.send(message).is_err() {
+                break;
+            }
+        }
+    });
+    Arc::new(Mutex::new(rx))
+}
+
+/// The channel `id`, opening the one to the parent on first use.
+fn with_channel<R>(id: i64, f: impl FnOnce(&mut Channel) -> R) -> Result<R, String> {
+    let mut channels = CHANNELS.lock().map_err(|_| "channel lock failed".to_string())?;
+    if id == PARENT && !channels.contains_key(&PARENT) {
+        let incoming = pump(BufReader::new(std::io::stdin()), false, false);
+        channels.insert(PARENT, Channel { out: Some(Box::new(ParentOut)), incoming, child: None, socket: None });
+    }
+    let channel = channels.get_mut(&id).ok_or_else(|| format!("no channel {}", id))?;
+    Ok(f(channel))
+}
+
+/// Messages to the parent go through the program's stdout buffer, so they
+/// stay in order with what the worker prints.
+struct ParentOut;
+
+impl Write for ParentOut {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        super::io::write(&String::from_utf8_lossy(buf));
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        super::io::flush();
+        Ok(())
+    }
+}
+
+fn send(id: i64, message: &Value) -> Result<(), String> {
+    let record = format!("{}{}\n", RECORD_SEPARATOR, super::data::convert_value_to_json(message));
+    with_channel(id, |channel| {
+        let out = channel.out.as_mut().ok_or("the channel is closed")?;
+        if channel.socket.is_some() {
+            quota::charge(Resource::NetBytes, record.len() as u64)?;
+        }
+        out.write_all(record.as_bytes()).and_then(|_| out.flush()).map_err(|e| format!("the channel is closed ({})", e))
+    })?
+}
+
+fn recv(id: i64, timeout: Option<Duration>) -> Result<Option<Value>, String> {
+    let incoming = with_channel(id, |channel| channel.incoming.clone())?;
+    let incoming = incoming.lock().map_err(|_| "channel lock failed".to_string())?;
+    let message = match timeout {
+        Some(timeout) => match incoming.recv_timeout(timeout) {
+            Ok(message) => message,
+            Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => return Ok(None),
+        },
+        None => match incoming.recv() {
+            Ok(message) => message,
+            Err(_) => return Ok(None),
+        },
+    };
+    super::data::convert_json_to_value(message?).map(Some)
+}
+
+fn close(id: i64) -> Result<i64, String> {
+    let mut channel = CHANNELS.lock().map_err(|_| "channel lock failed".to_string())?
+        .remove(&id)
+        .ok_or_else(|| format!("no channel {}", id))?;
+    drop(channel.out.take());
+    if let Some(socket) = &channel.socket {
+        let _ = socket.shutdown(std::net::Shutdown::Both);
+    }
+    match channel.child.as_mut() {
+        Some(child) => {
+            let status = child.wait().map_err(|e| e.to_string())?;
+            Ok(status.code().unwrap_or(-1) as i64)
+        }
+        None => Ok(0),
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_round_trip_through_a_worker() {
+        // A stand-in worker: prints a line, then echoes each message back
+        // inside a map (`${line#?}` drops the record separator)
+        let mut worker = Command::new("sh");
+        worker.args(["-c", "echo starting; while read -r line; do printf '\\036{\"got\": %s}\\n' \"${line#?}\"; done; exit 3"]);
+        let id = spawn(worker).unwrap();
+
+        let message = Value::Array(vec![Value::Int(1), Value::Str("two".into())]);
+        send(id, &message).unwrap();
+        let received = recv(id, Some(Duration::from_secs(10))).unwrap();
+        let Some(Value::Map(reply)) = received else { panic!("expected a map, got {:?}", received.map(|v| v.to_string())) };
+        assert_eq!(reply["got"].to_string(), message.to_string());
+
+        assert!(recv(id, Some(Duration::from_millis(20))).unwrap().is_none());
+        assert_eq!(close(id), Ok(3));
+        assert_eq!(send(id, &Value::Null).unwrap_err(), format!("no channel {}", id));
+    }
+}
@@ -1,6 +1,12 @@
+use crate::builtins::{builtin, Builtin};
 use crate::vm::{Value, VM};
 use serde_json::json;
 
+pub const BUILTINS: &[Builtin] = &[
+    builtin("llm.ask", "(prompt: str) -> str", "Sends `prompt` to the local Ollama server (llama3) and returns its answer.", |a, vm| call("ask", a, vm)),
+    builtin("llm.complete", "(prompt: str) -> str", "Same as llm.ask.", |a, vm| call("complete", a, vm)),
+];
+
 pub fn call(name: &str, args: &[Value], _vm: &mut VM) -> Result<Value, String> {
     match name {
         "ask" | "complete" => {
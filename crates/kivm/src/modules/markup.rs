@@ -0,0 +1,912 @@
+//! `data.xml.parse` / `html.parse`: markup trees of `XmlNode` / `HtmlNode`
+//! handles with CSS-selector queries.
+
+use crate::builtins::{builtin, Builtin};
+use crate::native::{self, NativeMethod, NativeType};
+use crate::vm::Value;
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::{Arc, Once};
+
+/// Script-visible type names of node handles (`typeof(n)`).
+pub const XML_NODE_TYPE: &str = "XmlNode";
+pub const HTML_NODE_TYPE: &str = "HtmlNode";
+
+static REGISTER: Once = Once::new();
+
+/// Registers `XmlNode` and `HtmlNode`, which share one method table.
+fn register_types() {
+    REGISTER.call_once(|| {
+        for name in [XML_NODE_TYPE, HTML_NODE_TYPE] {
+            let mut methods: HashMap<&'static str, NativeMethod> = HashMap::new();
+            methods.insert("tag", tag);
+            methods.insert("attr", attr);
+            methods.insert("attrs", attrs);
+            methods.insert("children", children);
+            methods.insert("text", text);
+            methods.insert("parent", parent);
+            methods.insert("select", select);
+            methods.insert("first", first);
+            native::register_type(NativeType { name, methods, destructor: None });
+        }
+    });
+}
+
+/// `data.xml.parse(src)`: the root element. Parsing is strict: mismatched
+/// or unclosed tags, unquoted attributes and unknown entities are errors
+/// naming the line.
+pub fn parse_xml(args: &[Value]) -> Result<Value, String> {
+    let Some(Value::Str(src)) = args.first() else {
+        return Err("data.xml.parse() expects a str".to_string());
+    };
+    let doc = Parser::new(src, false).run().map_err(|e| format!("data.xml.parse(): {}", e))?;
+    let root = doc.elements(0).next().expect("a parsed XML document has a root element");
+    wrap(&Arc::new(doc), root)
+}
+
+/// `html.parse(src)`: a `#document` node holding the top-level nodes,
+/// parsed the way browsers forgive it. Tag and attribute names are
+/// case-insensitive, void elements (`<br>`, `<img>`) need no end tag, `<p>`,
+/// `<li>`, `<td>` and friends close implicitly, stray end tags are ignored
+/// and `<script>`/`<style>` bodies are raw text.
+pub fn call_html(name: &str, args: &[Value]) -> Result<Value, String> {
+    match name {
+        "parse" => {
+            let Some(Value::Str(src)) = args.first() else {
+                return Err("html.parse() expects a str".to_string());
+            };
+            let doc = Parser::new(src, true).run()?;
+            wrap(&Arc::new(doc), 0)
+        }
+        _ => Err(format!("Unknown html function: {}", name)),
+    }
+}
+
+/// `html.parse`, and the node queries in function form (`html.select(n, css)`
+/// is `n.select(css)`); they take `XmlNode`s too.
+pub const BUILTINS: &[Builtin] = &[
+    builtin("html.parse", "(src: str) -> HtmlNode", "Parses an HTML document, forgiving malformed markup, and returns the document node.", |a, _| call_html("parse", a)),
+    builtin("html.select", "(node: HtmlNode, selector: str) -> [HtmlNode]", "Every descendant of `node` matching the CSS `selector`, in document order.", |a, _| node_method(a, "select")),
+    builtin("html.first", "(node: HtmlNode, selector: str) -> HtmlNode?", "The first descendant of `node` matching `selector`, or null.", |a, _| node_method(a, "first")),
+    builtin("html.text", "(node: HtmlNode) -> str", "All text inside `node`, in order.", |a, _| node_method(a, "text")),
+    builtin("html.attr", "(node: HtmlNode, name: str) -> str?", "The value of attribute `name`, or null when it is absent.", |a, _| node_method(a, "attr")),
+];
+
+fn node_method(args: &[Value], name: &str) -> Result<Value, String> {
+    match args.split_first() {
+        Some((Value::Native(handle), rest)) if [XML_NODE_TYPE, HTML_NODE_TYPE].contains(&handle.type_name()) => handle.call_method(name, rest),
+        other => Err(format!("html.{}() expects an HtmlNode, got {}", name, other.map_or_else(|| "nothing".to_string(), |(v, _)| v.to_string()))),
+    }
+}
+
+// ── Tree ──
+
+struct Document {
+    nodes: Vec<Element>,
+    html: bool,
+}
+
+struct Element {
+    tag: String,
+    attrs: Vec<(String, String)>,
+    children: Vec<Child>,
+    parent: Option<usize>,
+}
+
+enum Child {
+    Element(usize),
+    Text(String),
+}
+
+impl Document {
+    fn elements(&self, id: usize) -> impl Iterator<Item = usize> + '_ {
+        self.nodes[id].children.iter().filter_map(|c| match c {
+            Child::Element(e) => Some(*e),
+            Child::Text(_) => None,
+        })
+    }
+
+    fn attr(&self, id: usize, name: &str) -> Option<&str> {
+        self.nodes[id].attrs.iter()
+            .find(|(k, _)| if self.html { k.eq_ignore_ascii_case(name) } else { k == name })
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Descendant text in document order, walked without recursion so deep
+    /// trees cannot overflow the stack.
+    fn text(&self, id: usize) -> String {
+        let mut out = String::new();
+        let mut stack = vec![self.nodes[id].children.iter()];
+        while let Some(top) = stack.last_mut() {
+            match top.next() {
+                Some(Child::Text(t)) => out.push_str(t),
+                Some(Child::Element(e)) => stack.push(self.nodes[*e].children.iter()),
+                None => { stack.pop(); }
+            }
+        }
+        out
+    }
+
+    /// Descendants of `id` in document order.
+    fn descendants(&self, id: usize) -> Vec<usize> {
+        let mut out = Vec::new();
+        let mut stack: Vec<usize> = self.elements(id).collect();
+        stack.reverse();
+        while let Some(e) = stack.pop() {
+            out.push(e);
+            let start = stack.len();
+            stack.extend(self.elements(e));
+            stack[start..].reverse();
+        }
+        out
+    }
+}
+
+// ── Parser ──
+
+const VOID_ELEMENTS: &[&str] = &["area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source", "track", "wbr"];
+const RAW_TEXT_ELEMENTS: &[&str] = &["script", "style"];
+const BLOCKS_CLOSING_P: &[&str] = &[
+    "p", "div", "ul", "ol", "dl", "table", "pre", "blockquote", "form", "hr", "h1", "h2", "h3", "h4", "h5", "h6",
+    "section", "article", "header", "footer", "nav", "aside", "main", "figure",
+];
+
+/// Whether opening `new` implicitly closes an open `open` element (HTML).
+fn closes(open: &str, new: &str) -> bool {
+    match open {
+        "p" => BLOCKS_CLOSING_P.contains(&new),
+        "li" => new == "li",
+        "dt" | "dd" => matches!(new, "dt" | "dd"),
+        "td" | "th" => matches!(new, "td" | "th" | "tr"),
+        "tr" => new == "tr",
+        "option" => matches!(new, "option" | "optgroup"),
+        _ => false,
+    }
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+    html: bool,
+    doc: Document,
+    stack: Vec<usize>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(src: &'a str, html: bool) -> Self {
+        let root = Element { tag: "#document".to_string(), attrs: Vec::new(), children: Vec::new(), parent: None };
+        Parser { src, pos: 0, html, doc: Document { nodes: vec![root], html }, stack: Vec::new() }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.src[self.pos..]
+    }
+
+    fn error(&self, message: impl std::fmt::Display) -> String {
+        format!("line {}: {}", self.src[..self.pos].matches('\n').count() + 1, message)
+    }
+
+    fn current(&self) -> usize {
+        self.stack.last().copied().unwrap_or(0)
+    }
+
+    /// Skips past `end`; HTML forgives a missing terminator by stopping at
+    /// the end of input.
+    fn skip_past(&mut self, end: &str, what: &str) -> Result<&'a str, String> {
+        match self.rest().find(end) {
+            Some(i) => {
+                let skipped = &self.rest()[..i];
+                self.pos += i + end.len();
+                Ok(skipped)
+            }
+            None if self.html => {
+                let skipped = self.rest();
+                self.pos = self.src.len();
+                Ok(skipped)
+            }
+            None => Err(self.error(format!("unterminated {}", what))),
+        }
+    }
+
+    fn run(mut self) -> Result<Document, String> {
+        while self.pos < self.src.len() {
+            let rest = self.rest();
+            if rest.starts_with("<!--") {
+                self.pos += 4;
+                self.skip_past("-->", "comment")?;
+            } else if rest.starts_with("<![CDATA[") {
+                self.pos += 9;
+                let data = self.skip_past("]]>", "CDATA section")?;
+                self.push_text(data.to_string())?;
+            } else if rest.starts_with("<?") {
+                self.skip_past("?>", "processing instruction")?;
+            } else if rest.starts_with("<!") {
+                self.skip_past(">", "declaration")?;
+            } else if rest.starts_with("</") {
+                self.end_tag()?;
+            } else if rest.starts_with('<') && rest[1..].starts_with(|c: char| c.is_alphabetic() || c == '_') {
+                self.start_tag()?;
+            } else if rest.starts_with('<') && !self.html {
+                return Err(self.error("unescaped '<' in text"));
+            } else {
+                let first = rest.chars().next().map_or(1, char::len_utf8);
+                let len = rest[first..].find('<').map_or(rest.len(), |i| i + first);
+                let raw = &rest[..len];
+                self.pos += len;
+                let decoded = self.decode(raw)?;
+                self.push_text(decoded)?;
+            }
+        }
+        if !self.html {
+            if let Some(open) = self.stack.last() {
+                return Err(self.error(format!("unclosed <{}>", self.doc.nodes[*open].tag)));
+            }
+            if self.doc.nodes[0].children.is_empty() {
+                return Err(self.error("no root element"));
+            }
+        }
+        Ok(self.doc)
+    }
+
+    fn name(&mut self) -> &'a str {
+        let rest = self.rest();
+        let len = rest.find(|c: char| c.is_whitespace() || matches!(c, '/' | '>' | '=')).unwrap_or(rest.len());
+        self.pos += len;
+        &rest[..len]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn start_tag(&mut self) -> Result<(), String> {
+        self.pos += 1;
+        let mut tag = self.name().to_string();
+        if self.html {
+            tag.make_ascii_lowercase();
+        }
+        let mut attrs: Vec<(String, String)> = Vec::new();
+        let self_closing = loop {
+            self.skip_whitespace();
+            let rest = self.rest();
+            if rest.is_empty() {
+                if self.html {
+                    return Ok(());
+                }
+                return Err(self.error(format!("unterminated <{}> tag", tag)));
+            }
+            if rest.starts_with("/>") {
+                self.pos += 2;
+                break true;
+            }
+            if rest.starts_with('>') {
+                self.pos += 1;
+                break false;
+            }
+            if rest.starts_with('/') && self.html {
+                self.pos += 1;
+                continue;
+            }
+            let mut key = self.name().to_string();
+            if key.is_empty() {
+                return Err(self.error(format!("unexpected '{}' in <{}>", &rest[..rest.chars().next().map_or(0, char::len_utf8)], tag)));
+            }
+            if self.html {
+                key.make_ascii_lowercase();
+            }
+            self.skip_whitespace();
+            let value = if self.rest().starts_with('=') {
+                self.pos += 1;
+                self.skip_whitespace();
+                self.attr_value(&key)?
+            } else if self.html {
+                String::new()
+            } else {
+                return Err(self.error(format!("attribute '{}' has no value", key)));
+            };
+            if attrs.iter().any(|(k, _)| *k == key) {
+                if !self.html {
+                    return Err(self.error(format!("duplicate attribute '{}'", key)));
+                }
+            } else {
+                attrs.push((key, value));
+            }
+        };
+
+        if self.html {
+            while let Some(&open) = self.stack.last() {
+                if !closes(&self.doc.nodes[open].tag, &tag) {
+                    break;
+                }
+                self.stack.pop();
+            }
+        } else if self.stack.is_empty() && !self.doc.nodes[0].children.is_empty() {
+            return Err(self.error(format!("<{}> is a second root element", tag)));
+        }
+
+        let parent = self.current();
+        let id = self.doc.nodes.len();
+        self.doc.nodes.push(Element { tag: tag.clone(), attrs, children: Vec::new(), parent: Some(parent) });
+        self.doc.nodes[parent].children.push(Child::Element(id));
+
+        let void = self.html && VOID_ELEMENTS.contains(&tag.as_str());
+        if self_closing || void {
+            return Ok(());
+        }
+        self.stack.push(id);
+        if self.html && RAW_TEXT_ELEMENTS.contains(&tag.as_str()) {
+            let end = format!("</{}", tag);
+            let len = self.rest().to_ascii_lowercase().find(&end).unwrap_or(self.rest().len());
+            let body = &self.rest()[..len];
+            self.pos += len;
+            if !body.is_empty() {
+                self.doc.nodes[id].children.push(Child::Text(body.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    fn attr_value(&mut self, key: &str) -> Result<String, String> {
+        let rest = self.rest();
+        match rest.chars().next() {
+            Some(quote @ ('"' | '\'')) => match rest[1..].find(quote) {
+                Some(end) => {
+                    self.pos += end + 2;
+                    self.decode(&rest[1..end + 1])
+                }
+                None if self.html => {
+                    self.pos = self.src.len();
+                    self.decode(&rest[1..])
+                }
+                None => Err(self.error(format!("unterminated value of attribute '{}'", key))),
+            },
+            _ if self.html => {
+                let len = rest.find(|c: char| c.is_whitespace() || c == '>').unwrap_or(rest.len());
+                self.pos += len;
+                self.decode(&rest[..len])
+            }
+            _ => Err(self.error(format!("value of attribute '{}' must be quoted", key))),
+        }
+    }
+
+    fn end_tag(&mut self) -> Result<(), String> {
+        self.pos += 2;
+        let mut tag = self.name().to_string();
+        if self.html {
+            tag.make_ascii_lowercase();
+        }
+        self.skip_past(">", "end tag")?;
+        if self.html {
+            if let Some(i) = self.stack.iter().rposition(|&open| self.doc.nodes[open].tag == tag) {
+                self.stack.truncate(i);
+            }
+            return Ok(());
+        }
+        match self.stack.pop() {
+            Some(open) if self.doc.nodes[open].tag == tag => Ok(()),
+            Some(open) => Err(self.error(format!("expected </{}>, found </{}>", self.doc.nodes[open].tag, tag))),
+            None => Err(self.error(format!("</{}> has no matching start tag", tag))),
+        }
+    }
+
+    fn push_text(&mut self, text: String) -> Result<(), String> {
+        if self.stack.is_empty() && !self.html {
+            if text.trim().is_empty() {
+                return Ok(());
+            }
+            return Err(self.error("text outside the root element"));
+        }
+        let current = self.current();
+        let children = &mut self.doc.nodes[current].children;
+        match children.last_mut() {
+            Some(Child::Text(prev)) => prev.push_str(&text),
+            _ => children.push(Child::Text(text)),
+        }
+        Ok(())
+    }
+
+    /// Replaces entity and character references. Unknown entities are an
+    /// error in XML and left as written in HTML.
+    fn decode(&self, raw: &str) -> Result<String, String> {
+        let mut out = String::with_capacity(raw.len());
+        let mut rest = raw;
+        while let Some(amp) = rest.find('&') {
+            out.push_str(&rest[..amp]);
+            rest = &rest[amp..];
+            let decoded = rest.find(';').filter(|&end| end <= 32).and_then(|end| Some((entity(&rest[1..end], self.html)?, end)));
+            match decoded {
+                Some((c, end)) => {
+                    out.push(c);
+                    rest = &rest[end + 1..];
+                }
+                None if self.html => {
+                    out.push('&');
+                    rest = &rest[1..];
+                }
+                None => {
+                    let name = rest.split(|c: char| c == ';' || c.is_whitespace()).next().unwrap_or(rest);
+                    return Err(self.error(format!("unknown entity '{}'", name)));
+                }
+            }
+        }
+        out.push_str(rest);
+        Ok(out)
+    }
+}
+
+fn entity(name: &str, html: bool) -> Option<char> {
+    if let Some(num) = name.strip_prefix('#') {
+        let code = match num.strip_prefix(['x', 'X']) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => num.parse().ok()?,
+        };
+        return char::from_u32(code);
+    }
+    let c = match name {
+        "lt" => '<',
+        "gt" => '>',
+        "amp" => '&',
+        "quot" => '"',
+        "apos" => '\'',
+        _ if !html => return None,
+        "nbsp" => '\u{a0}',
+        "copy" => '©',
+        "reg" => '®',
+        "trade" => '™',
+        "hellip" => '…',
+        "mdash" => '—',
+        "ndash" => '–',
+        "lsquo" => '‘',
+        "rsquo" => '’',
+        "ldquo" => '“',
+        "rdquo" => '”',
+        "laquo" => '«',
+        "raquo" => '»',
+        "middot" => '·',
+        "times" => '×',
+        "divide" => '÷',
+        "deg" => '°',
+        "euro" => '€',
+        _ => return None,
+    };
+    Some(c)
+}
+
+// ── Selectors ──
+
+struct Complex {
+    /// Compounds left to right; `combinators[i]` sits between compound `i`
+    /// and `i + 1`.
+    compounds: Vec<Compound>,
+    combinators: Vec<Combinator>,
+}
+
+#[derive(Clone, Copy)]
+enum Combinator {
+    Descendant,
+    Child,
+}
+
+#[derive(Default)]
+struct Compound {
+    tag: Option<String>,
+    id: Option<String>,
+    classes: Vec<String>,
+    attrs: Vec<(String, Option<(AttrOp, String)>)>,
+    pseudos: Vec<Pseudo>,
+}
+
+#[derive(Clone, Copy)]
+enum AttrOp {
+    Equals,
+    Prefix,
+    Suffix,
+    Contains,
+    Word,
+}
+
+enum Pseudo {
+    First,
+    Last,
+    Nth(usize),
+}
+
+struct SelectorParser<'a> {
+    src: &'a str,
+    pos: usize,
+}
+
+/// Supports `tag`, `*`, `#id`, `.class`, `[attr]`, `[attr=v]` (also `^=`,
+/// `$=`, `*=`, `~=`), `:first-child`, `:last-child`, `:nth-child(n)`, the
+/// descendant and `>` combinators, and `,` groups.
+fn parse_selector(src: &str) -> Result<Vec<Complex>, String> {
+    let mut p = SelectorParser { src, pos: 0 };
+    let mut groups = Vec::new();
+    loop {
+        p.skip_whitespace();
+        let mut complex = Complex { compounds: vec![p.compound()?], combinators: Vec::new() };
+        loop {
+            let spaced = p.skip_whitespace();
+            match p.peek() {
+                None | Some(',') => break,
+                Some('>') => {
+                    p.pos += 1;
+                    p.skip_whitespace();
+                    complex.combinators.push(Combinator::Child);
+                }
+                _ if spaced => complex.combinators.push(Combinator::Descendant),
+                Some(c) => return Err(p.error(format!("unexpected '{}'", c))),
+            }
+            complex.compounds.push(p.compound()?);
+        }
+        groups.push(complex);
+        if p.peek().is_none() {
+            return Ok(groups);
+        }
+        p.pos += 1; // ','
+    }
+}
+
+impl SelectorParser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.src[self.pos..].chars().next()
+    }
+
+    fn error(&self, message: impl std::fmt::Display) -> String {
+        format!("selector '{}': {} at offset {}", self.src, message, self.pos)
+    }
+
+    fn skip_whitespace(&mut self) -> bool {
+        let rest = &self.src[self.pos..];
+        let skipped = rest.len() - rest.trim_start().len();
+        self.pos += skipped;
+        skipped > 0
+    }
+
+    fn eat(&mut self, s: &str) -> bool {
+        let found = self.src[self.pos..].starts_with(s);
+        if found {
+            self.pos += s.len();
+        }
+        found
+    }
+
+    fn ident(&mut self) -> Result<String, String> {
+        let rest = &self.src[self.pos..];
+        let len = rest.find(|c: char| !(c.is_alphanumeric() || matches!(c, '-' | '_'))).unwrap_or(rest.len());
+        if len == 0 {
+            return Err(self.error("expected a name"));
+        }
+        self.pos += len;
+        Ok(rest[..len].to_string())
+    }
+
+    fn compound(&mut self) -> Result<Compound, String> {
+        let mut compound = Compound::default();
+        let start = self.pos;
+        if self.eat("*") {
+            // matches any tag
+        } else if self.peek().is_some_and(|c| c.is_alphabetic() || c == '_') {
+            compound.tag = Some(self.ident()?);
+        }
+        loop {
+            match self.peek() {
+                Some('#') => {
+                    self.pos += 1;
+                    compound.id = Some(self.ident()?);
+                }
+                Some('.') => {
+                    self.pos += 1;
+                    compound.classes.push(self.ident()?);
+                }
+                Some('[') => {
+                    self.pos += 1;
+                    compound.attrs.push(self.attribute()?);
+                }
+                Some(':') => {
+                    self.pos += 1;
+                    compound.pseudos.push(self.pseudo()?);
+                }
+                _ => break,
+            }
+        }
+        if self.pos == start {
+            return Err(self.error("expected a selector"));
+        }
+        Ok(compound)
+    }
+
+    fn attribute(&mut self) -> Result<(String, Option<(AttrOp, String)>), String> {
+        self.skip_whitespace();
+        let name = self.ident()?;
+        self.skip_whitespace();
+        let op = [("=", AttrOp::Equals), ("^=", AttrOp::Prefix), ("$=", AttrOp::Suffix), ("*=", AttrOp::Contains), ("~=", AttrOp::Word)]
+            .into_iter()
+            .find(|(s, _)| self.eat(s))
+            .map(|(_, op)| op);
+        let test = match op {
+            None => None,
+            Some(op) => {
+                self.skip_whitespace();
+                let value = match self.peek() {
+                    Some(quote @ ('"' | '\'')) => {
+                        let rest = &self.src[self.pos + 1..];
+                        let end = rest.find(quote).ok_or_else(|| self.error("unterminated string"))?;
+                        self.pos += end + 2;
+                        rest[..end].to_string()
+                    }
+                    _ => self.ident()?,
+                };
+                self.skip_whitespace();
+                Some((op, value))
+            }
+        };
+        if !self.eat("]") {
+            return Err(self.error("expected ']'"));
+        }
+        Ok((name, test))
+    }
+
+    fn pseudo(&mut self) -> Result<Pseudo, String> {
+        match self.ident()?.as_str() {
+            "first-child" => Ok(Pseudo::First),
+            "last-child" => Ok(Pseudo::Last),
+            "nth-child" => {
+                let rest = &self.src[self.pos..];
+                let n = rest.strip_prefix('(')
+                    .and_then(|r| r.split_once(')'))
+                    .and_then(|(n, _)| n.trim().parse::<usize>().ok())
+                    .filter(|n| *n > 0)
+                    .ok_or_else(|| self.error("expected :nth-child(n) with n >= 1"))?;
+                self.pos += rest.find(')').map_or(0, |i| i + 1);
+                Ok(Pseudo::Nth(n))
+            }
+            other => Err(self.error(format!("unsupported pseudo-class ':{}'", other))),
+        }
+    }
+}
+
+impl Document {
+    fn matches_compound(&self, id: usize, c: &Compound) -> bool {
+        let node = &self.nodes[id];
+        if let Some(tag) = &c.tag {
+            let same = if self.html { node.tag.eq_ignore_ascii_case(tag) } else { node.tag == *tag };
+            if !same {
+                return false;
+            }
+        }
+        if c.id.as_deref().is_some_and(|want| self.attr(id, "id") != Some(want)) {
+            return false;
+        }
+        if !c.classes.is_empty() {
+            let class = self.attr(id, "class").unwrap_or("");
+            if !c.classes.iter().all(|want| class.split_whitespace().any(|have| have == want)) {
+                return false;
+            }
+        }
+        for (name, test) in &c.attrs {
+            let Some(value) = self.attr(id, name) else { return false };
+            let ok = match test {
+                None => true,
+                Some((AttrOp::Equals, want)) => value == want,
+                Some((AttrOp::Prefix, want)) => !want.is_empty() && value.starts_with(want.as_str()),
+                Some((AttrOp::Suffix, want)) => !want.is_empty() && value.ends_with(want.as_str()),
+                Some((AttrOp::Contains, want)) => !want.is_empty() && value.contains(want.as_str()),
+                Some((AttrOp::Word, want)) => value.split_whitespace().any(|w| w == want),
+            };
+            if !ok {
+                return false;
+            }
+        }
+        if !c.pseudos.is_empty() {
+            let Some(parent) = node.parent else { return false };
+            let siblings: Vec<usize> = self.elements(parent).collect();
+            let index = siblings.iter().position(|&s| s == id).unwrap_or(0);
+            for pseudo in &c.pseudos {
+                let ok = match pseudo {
+                    Pseudo::First => index == 0,
+                    Pseudo::Last => index + 1 == siblings.len(),
+                    Pseudo::Nth(n) => index + 1 == *n,
+                };
+                if !ok {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    /// Whether `id` matches `complex` up to compound `i`, checked right to
+    /// left through its ancestors.
+    fn matches(&self, id: usize, complex: &Complex, i: usize) -> bool {
+        if !self.matches_compound(id, &complex.compounds[i]) {
+            return false;
+        }
+        if i == 0 {
+            return true;
+        }
+        let mut ancestor = self.nodes[id].parent.filter(|&p| p != 0);
+        match complex.combinators[i - 1] {
+            Combinator::Child => ancestor.is_some_and(|p| self.matches(p, complex, i - 1)),
+            Combinator::Descendant => {
+                while let Some(p) = ancestor {
+                    if self.matches(p, complex, i - 1) {
+                        return true;
+                    }
+                    ancestor = self.nodes[p].parent.filter(|&p| p != 0);
+                }
+                false
+            }
+        }
+    }
+
+    fn select(&self, id: usize, selector: &[Complex]) -> Vec<usize> {
+        self.descendants(id)
+            .into_iter()
+            .filter(|&d| selector.iter().any(|c| self.matches(d, c, c.compounds.len() - 1)))
+            .collect()
+    }
+}
+
+// ── Handles ──
+
+struct Node {
+    doc: Arc<Document>,
+    id: usize,
+}
+
+fn wrap(doc: &Arc<Document>, id: usize) -> Result<Value, String> {
+    register_types();
+    let type_name = if doc.html { HTML_NODE_TYPE } else { XML_NODE_TYPE };
+    native::wrap(type_name, Node { doc: Arc::clone(doc), id })
+}
+
+fn node(obj: &mut (dyn Any + Send)) -> Result<&Node, String> {
+    obj.downcast_ref::<Node>().ok_or_else(|| "Expected an XmlNode or HtmlNode handle".to_string())
+}
+
+fn tag(obj: &mut (dyn Any + Send), _args: &[Value]) -> Result<Value, String> {
+    let n = node(obj)?;
+    Ok(Value::Str(n.doc.nodes[n.id].tag.clone()))
+}
+
+/// The attribute's value, or null when it is absent.
+fn attr(obj: &mut (dyn Any + Send), args: &[Value]) -> Result<Value, String> {
+    let n = node(obj)?;
+    let Some(Value::Str(name)) = args.first() else {
+        return Err("attr() expects an attribute name".to_string());
+    };
+    Ok(n.doc.attr(n.id, name).map_or(Value::Null, |v| Value::Str(v.to_string())))
+}
+
+fn attrs(obj: &mut (dyn Any + Send), _args: &[Value]) -> Result<Value, String> {
+    let n = node(obj)?;
+    Ok(Value::Map(n.doc.nodes[n.id].attrs.iter().map(|(k, v)| (k.clone(), Value::Str(v.clone()))).collect()))
+}
+
+/// Child elements only; text is left out.
+fn children(obj: &mut (dyn Any + Send), _args: &[Value]) -> Result<Value, String> {
+    let n = node(obj)?;
+    Ok(Value::Array(n.doc.elements(n.id).map(|e| wrap(&n.doc, e)).collect::<Result<_, _>>()?))
+}
+
+/// All descendant text, in order.
+fn text(obj: &mut (dyn Any + Send), _args: &[Value]) -> Result<Value, String> {
+    let n = node(obj)?;
+    Ok(Value::Str(n.doc.text(n.id)))
+}
+
+fn parent(obj: &mut (dyn Any + Send), _args: &[Value]) -> Result<Value, String> {
+    let n = node(obj)?;
+    match n.doc.nodes[n.id].parent {
+        // An XML root's parent is the hidden document node.
+        Some(0) if !n.doc.html => Ok(Value::Null),
+        Some(p) => wrap(&n.doc, p),
+        None => Ok(Value::Null),
+    }
+}
+
+fn selector_arg(method: &str, args: &[Value]) -> Result<Vec<Complex>, String> {
+    match args.first() {
+        Some(Value::Str(css)) => parse_selector(css),
+        _ => Err(format!("{}() expects a CSS selector string", method)),
+    }
+}
+
+/// Every matching descendant, in document order.
+fn select(obj: &mut (dyn Any + Send), args: &[Value]) -> Result<Value, String> {
+    let n = node(obj)?;
+    let selector = selector_arg("select", args)?;
+    Ok(Value::Array(n.doc.select(n.id, &selector).into_iter().map(|e| wrap(&n.doc, e)).collect::<Result<_, _>>()?))
+}
+
+/// The first matching descendant, or null.
+fn first(obj: &mut (dyn Any + Send), args: &[Value]) -> Result<Value, String> {
+    let n = node(obj)?;
+    let selector = selector_arg("first", args)?;
+    match n.doc.select(n.id, &selector).first() {
+        Some(&e) => wrap(&n.doc, e),
+        None => Ok(Value::Null),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(doc: &Document, ids: &[usize]) -> Vec<String> {
+        ids.iter().map(|&i| {
+            let n = &doc.nodes[i];
+            match doc.attr(i, "id") {
+                Some(id) => format!("{}#{}", n.tag, id),
+                None => n.tag.clone(),
+            }
+        }).collect()
+    }
+
+    fn select(doc: &Document, css: &str) -> Vec<String> {
+        tags(doc, &doc.select(0, &parse_selector(css).unwrap()))
+    }
+
+    #[test]
+    fn xml_is_strict() {
+        let doc = Parser::new("<?xml version=\"1.0\"?>\n<cfg a=\"1 &amp; 2\"><v>x&lt;y</v><![CDATA[<raw>]]><e/></cfg>", false).run().unwrap();
+        let root = doc.elements(0).next().unwrap();
+        assert_eq!(doc.nodes[root].tag, "cfg");
+        assert_eq!(doc.attr(root, "a"), Some("1 & 2"));
+        assert_eq!(doc.text(root), "x<y<raw>");
+        assert_eq!(tags(&doc, &doc.elements(root).collect::<Vec<_>>()), ["v", "e"]);
+
+        for (src, error) in [
+            ("<a><b></a>", "line 1: expected </b>, found </a>"),
+            ("<a>\n<b>", "line 2: unclosed <b>"),
+            ("<a x=1/>", "line 1: value of attribute 'x' must be quoted"),
+            ("<a>&nbsp;</a>", "line 1: unknown entity '&nbsp'"),
+            ("<a/><b/>", "line 1: <b> is a second root element"),
+            ("", "line 1: no root element"),
+        ] {
+            assert_eq!(Parser::new(src, false).run().err().as_deref(), Some(error), "{}", src);
+        }
+    }
+
+    #[test]
+    fn html_forgives_malformed_markup() {
+        let src = "<!DOCTYPE html><HTML><body><p id=a>one<p class='x y'>two<br>three</b>\
+                   <ul><li id=l1>a<li id=l2>b &copy; &bogus;</ul>\
+                   <script>if (a<b) {}</script><table><tr><td id=c1>1<td id=c2>2<tr><td id=c3>3</table>";
+        let doc = Parser::new(src, true).run().unwrap();
+        let body = doc.select(0, &parse_selector("body").unwrap())[0];
+        assert_eq!(tags(&doc, &doc.elements(body).collect::<Vec<_>>()), ["p#a", "p", "ul", "script", "table"]);
+        assert_eq!(select(&doc, "ul > li"), ["li#l1", "li#l2"]);
+        assert_eq!(doc.text(doc.select(0, &parse_selector("#l2").unwrap())[0]), "b © &bogus;");
+        assert_eq!(doc.text(doc.select(0, &parse_selector("script").unwrap())[0]), "if (a<b) {}");
+        assert_eq!(select(&doc, "tr td"), ["td#c1", "td#c2", "td#c3"]);
+        assert_eq!(select(&doc, "tr:first-child td:last-child, p.y"), ["p", "td#c2"]);
+        assert_eq!(select(&doc, "P[ID=a]"), ["p#a"]);
+        assert_eq!(select(&doc, "[class~=x]"), ["p"]);
+        assert_eq!(select(&doc, "li:nth-child(2)"), ["li#l2"]);
+        assert!(parse_selector("a >").is_err());
+        assert!(parse_selector("a:hover").is_err());
+    }
+
+    #[test]
+    fn handles_navigate_the_tree() {
+        let root = parse_xml(&[Value::Str("<r><i n=\"1\">a</i><i n=\"2\">b</i></r>".into())]).unwrap();
+        let Value::Native(root) = root else { panic!("expected a handle") };
+        assert_eq!(root.type_name(), XML_NODE_TYPE);
+        assert_eq!(root.call_method("parent", &[]).unwrap(), Value::Null);
+        let Value::Array(items) = root.call_method("select", &[Value::Str("i[n=\"2\"]".into())]).unwrap() else { panic!() };
+        let Value::Native(item) = &items[0] else { panic!() };
+        assert_eq!(item.call_method("text", &[]).unwrap(), Value::Str("b".into()));
+        assert_eq!(item.call_method("attr", &[Value::Str("missing".into())]).unwrap(), Value::Null);
+        let Value::Native(up) = item.call_method("parent", &[]).unwrap() else { panic!() };
+        assert_eq!(up.call_method("tag", &[]).unwrap(), Value::Str("r".into()));
+        assert_eq!(root.call_method("first", &[Value::Str("q".into())]).unwrap(), Value::Null);
+
+        let doc = call_html("parse", &[Value::Str("<ul><li>a<li class=x>b</ul>".into())]).unwrap();
+        let li = node_method(&[doc, Value::Str("li.x".into())], "first").unwrap();
+        assert_eq!(node_method(&[li], "text").unwrap(), Value::Str("b".into()));
+        assert!(node_method(&[Value::Int(1)], "text").is_err());
+    }
+}
@@ -0,0 +1,332 @@
+//! `math.*` matrix helpers and the homogeneous 2D (3x3) and 3D (4x4)
+//! transforms games and graphics use.
+
+use crate::builtins::{builtin, Builtin};
+use crate::vm::Value;
+
+/// `math.inverse` and `math.determinant` eliminate in O(n³); larger
+/// matrices belong in a numeric library, not a script.
+const MAX_ELIMINATION_SIZE: usize = 64;
+
+/// Largest matrix `math.matrix` and `math.identity` build, in elements, so a
+/// typo can't ask for terabytes.
+const MAX_ELEMENTS: i64 = 1 << 24;
+
+/// Transforms compose with `math.mat_mul`; the rightmost one applies first.
+pub const BUILTINS: &[Builtin] = &[
+    builtin("math.matrix", "(rows: int, cols: int, fill: float?) -> [[float]]", "A `rows` x `cols` matrix filled with `fill` (default 0.0).", |args, _| {
+        let (rows, cols) = size("matrix", args[0].as_int()?, args[1].as_int()?)?;
+        let fill = args.get(2).and_then(|v| v.as_float().ok()).unwrap_or(0.0);
+        Ok(Mat { rows, cols, data: vec![fill; rows * cols] }.into_value())
+    }),
+    builtin("math.identity", "(n: int) -> [[float]]", "The `n` x `n` identity matrix.", |args, _| {
+        let n = args[0].as_int()?;
+        Ok(Mat::identity(size("identity", n, n)?.0).into_value())
+    }),
+    builtin("math.mat_mul", "(a: [[float]], b: [[float]] | [float]) -> [[float]] | [float]", "Matrix product `a * b`; with a vector `b`, the vector `a * b`.", |args, _| {
+        let a = Mat::from_value("mat_mul", &args[0])?;
+        match &args[1] {
+            Value::Array(v) if v.iter().all(|x| !matches!(x, Value::Array(_))) => {
+                let v = floats("mat_mul", v)?;
+                if a.cols != v.len() {
+                    return Err(format!("math.mat_mul(): a {}x{} matrix can't multiply a vector of {}", a.rows, a.cols, v.len()));
+                }
+                Ok(Value::Array((0..a.rows).map(|r| Value::Float((0..a.cols).map(|c| a.at(r, c) * v[c]).sum())).collect()))
+            }
+            b => Ok(a.mul(&Mat::from_value("mat_mul", b)?)?.into_value()),
+        }
+    }),
+    builtin("math.transpose", "(m: [[float]]) -> [[float]]", "Rows and columns swapped.", |args, _| {
+        let m = Mat::from_value("transpose", &args[0])?;
+        let mut t = Mat { rows: m.cols, cols: m.rows, data: vec![0.0; m.data.len()] };
+        for r in 0..m.rows {
+            for c in 0..m.cols {
+                t.data[c * t.cols + r] = m.at(r, c);
+            }
+        }
+        Ok(t.into_value())
+    }),
+    builtin("math.determinant", "(m: [[float]]) -> float", "Determinant of a square matrix.", |args, _| {
+        let m = Mat::from_value("determinant", &args[0])?.square("determinant")?;
+        Ok(Value::Float(m.eliminate(None)))
+    }),
+    builtin("math.inverse", "(m: [[float]]) -> [[float]]", "Inverse of a square matrix (up to 64x64); an error if it is singular.", |args, _| {
+        let m = Mat::from_value("inverse", &args[0])?.square("inverse")?;
+        let mut inverse = Mat::identity(m.rows);
+        if m.eliminate(Some(&mut inverse)) == 0.0 {
+            return Err("math.inverse(): the matrix is singular".to_string());
+        }
+        Ok(inverse.into_value())
+    }),
+    builtin("math.translate2", "(x: float, y: float) -> [[float]]", "3x3 2D translation.", |args, _| {
+        let (x, y) = (args[0].as_float()?, args[1].as_float()?);
+        Ok(Mat::rows(&[[1.0, 0.0, x], [0.0, 1.0, y], [0.0, 0.0, 1.0]]).into_value())
+    }),
+    builtin("math.rotate2", "(radians: float) -> [[float]]", "3x3 2D rotation, counter-clockwise.", |args, _| {
+        let (sin, cos) = args[0].as_float()?.sin_cos();
+        Ok(Mat::rows(&[[cos, -sin, 0.0], [sin, cos, 0.0], [0.0, 0.0, 1.0]]).into_value())
+    }),
+    builtin("math.scale2", "(x: float, y: float) -> [[float]]", "3x3 2D scaling.", |args, _| {
+        let (x, y) = (args[0].as_float()?, args[1].as_float()?);
+        Ok(Mat::rows(&[[x, 0.0, 0.0], [0.0, y, 0.0], [0.0, 0.0, 1.0]]).into_value())
+    }),
+    builtin("math.translate3", "(x: float, y: float, z: float) -> [[float]]", "4x4 3D translation.", |args, _| {
+        let (x, y, z) = (args[0].as_float()?, args[1].as_float()?, args[2].as_float()?);
+        Ok(Mat::rows(&[[1.0, 0.0, 0.0, x], [0.0, 1.0, 0.0, y], [0.0, 0.0, 1.0, z], [0.0, 0.0, 0.0, 1.0]]).into_value())
+    }),
+    builtin("math.rotate3", "(axis: [float], radians: float) -> [[float]]", "4x4 3D rotation about `axis` (right-handed).", |args, _| {
+        let Value::Array(axis) = &args[0] else { unreachable!("checked by the signature") };
+        let axis = floats("rotate3", axis)?;
+        let len = axis.iter().map(|a| a * a).sum::<f64>().sqrt();
+        if axis.len() != 3 || len == 0.0 {
+            return Err("math.rotate3(): the axis must be a non-zero 3D vector".to_string());
+        }
+        let (x, y, z) = (axis[0] / len, axis[1] / len, axis[2] / len);
+        let (s, c) = args[1].as_float()?.sin_cos();
+        let t = 1.0 - c;
+        Ok(Mat::rows(&[
+            [t * x * x + c, t * x * y - s * z, t * x * z + s * y, 0.0],
+            [t * x * y + s * z, t * y * y + c, t * y * z - s * x, 0.0],
+            [t * x * z - s * y, t * y * z + s * x, t * z * z + c, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]).into_value())
+    }),
+    builtin("math.scale3", "(x: float, y: float, z: float) -> [[float]]", "4x4 3D scaling.", |args, _| {
+        let (x, y, z) = (args[0].as_float()?, args[1].as_float()?, args[2].as_float()?);
+        Ok(Mat::rows(&[[x, 0.0, 0.0, 0.0], [0.0, y, 0.0, 0.0], [0.0, 0.0, z, 0.0], [0.0, 0.0, 0.0, 1.0]]).into_value())
+    }),
+    builtin("math.transform", "(m: [[float]], point: [float]) -> [float]", "A 2D point through a 3x3 transform, or a 3D point through a 4x4 one.", |args, _| {
+        let m = Mat::from_value("transform", &args[0])?;
+        let Value::Array(point) = &args[1] else { unreachable!("checked by the signature") };
+        let mut p = floats("transform", point)?;
+        if m.rows != m.cols || m.cols != p.len() + 1 {
+            return Err(format!("math.transform(): a {}x{} matrix can't transform a {}D point", m.rows, m.cols, p.len()));
+        }
+        p.push(1.0);
+        let out: Vec<f64> = (0..m.rows).map(|r| (0..m.cols).map(|c| m.at(r, c) * p[c]).sum()).collect();
+        let w = if out[m.rows - 1] == 0.0 { 1.0 } else { out[m.rows - 1] };
+        Ok(Value::Array(out[..m.rows - 1].iter().map(|v| Value::Float(v / w)).collect()))
+    }),
+];
+
+fn size(name: &str, rows: i64, cols: i64) -> Result<(usize, usize), String> {
+    match rows.checked_mul(cols) {
+        Some(n) if rows >= 0 && cols >= 0 && n <= MAX_ELEMENTS => Ok((rows as usize, cols as usize)),
+        _ => Err(format!("math.{}(): size {}x{} is negative or over {} elements", name, rows, cols, MAX_ELEMENTS)),
+    }
+}
+
+fn floats(name: &str, items: &[Value]) -> Result<Vec<f64>, String> {
+    items.iter().map(|v| match v {
+        Value::Int(n) => Ok(*n as f64),
+        Value::Float(f) => Ok(*f),
+        other => Err(format!("math.{}() expects numbers, got {}", name, other)),
+    }).collect()
+}
+
+/// Row-major dense matrix.
+/// A matrix as scripts pass it, an array of equally long rows of numbers
+/// (`[[1.0, 0.0], [0.0, 1.0]]`); a vector is a plain array, as for
+/// `math.dot`. Helpers always return a new matrix of floats.
+#[derive(Debug, Clone, PartialEq)]
+struct Mat {
+    rows: usize,
+    cols: usize,
+    data: Vec<f64>,
+}
+
+impl Mat {
+    fn identity(n: usize) -> Self {
+        let mut m = Mat { rows: n, cols: n, data: vec![0.0; n * n] };
+        for i in 0..n {
+            m.data[i * n + i] = 1.0;
+        }
+        m
+    }
+
+    fn rows<const N: usize>(rows: &[[f64; N]]) -> Self {
+        Mat { rows: rows.len(), cols: N, data: rows.iter().flatten().copied().collect() }
+    }
+
+    fn from_value(name: &str, value: &Value) -> Result<Self, String> {
+        let Value::Array(rows) = value else {
+            return Err(format!("math.{}() expects a matrix (array of rows), got {}", name, value));
+        };
+        let mut data = Vec::new();
+        let cols = match rows.first() {
+            Some(Value::Array(first)) => first.len(),
+            Some(other) => return Err(format!("math.{}() expects a matrix (array of rows), got a row {}", name, other)),
+            None => 0,
+        };
+        for (i, row) in rows.iter().enumerate() {
+            match row {
+                Value::Array(row) if row.len() == cols => data.extend(floats(name, row)?),
+                _ => return Err(format!("math.{}(): row {} is not an array of {} numbers", name, i, cols)),
+            }
+        }
+        Ok(Mat { rows: rows.len(), cols, data })
+    }
+
+    fn into_value(self) -> Value {
+        if self.cols == 0 {
+            return Value::Array((0..self.rows).map(|_| Value::Array(Vec::new())).collect());
+        }
+        Value::Array(self.data.chunks(self.cols).map(|row| Value::Array(row.iter().map(|v| Value::Float(*v)).collect())).collect())
+    }
+
+    fn at(&self, r: usize, c: usize) -> f64 {
+        self.data[r * self.cols + c]
+    }
+
+    fn square(self, name: &str) -> Result<Self, String> {
+        if self.rows != self.cols {
+            return Err(format!("math.{}() needs a square matrix, got {}x{}", name, self.rows, self.cols));
+        }
+        if self.rows > MAX_ELIMINATION_SIZE {
+            return Err(format!("math.{}() supports matrices up to {}x{}, got {}x{}", name, MAX_ELIMINATION_SIZE, MAX_ELIMINATION_SIZE, self.rows, self.cols));
+        }
+        Ok(self)
+    }
+
+    fn mul(&self, other: &Mat) -> Result<Mat, String> {
+        if self.cols != other.rows {
+            return Err(format!("math.mat_mul(): can't multiply {}x{} by {}x{}", self.rows, self.cols, other.rows, other.cols));
+        }
+        let mut out = Mat { rows: self.rows, cols: other.cols, data: vec![0.0; self.rows * other.cols] };
+        for r in 0..self.rows {
+            for k in 0..self.cols {
+                let a = self.at(r, k);
+                for c in 0..other.cols {
+                    out.data[r * out.cols + c] += a * other.at(k, c);
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    /// Gauss-Jordan elimination with partial pivoting on a square matrix.
+    /// Returns the determinant (0.0 when singular); with `inverse` (the
+    /// identity on entry), applies the same row operations to it, leaving
+    /// the inverse there when the determinant is non-zero.
+    fn eliminate(mut self, mut inverse: Option<&mut Mat>) -> f64 {
+        let n = self.rows;
+        let mut det = 1.0;
+        for col in 0..n {
+            let pivot = (col..n).max_by(|&a, &b| self.at(a, col).abs().total_cmp(&self.at(b, col).abs())).unwrap_or(col);
+            if self.at(pivot, col).abs() < 1e-12 {
+                return 0.0;
+            }
+            if pivot != col {
+                self.swap_rows(pivot, col);
+                if let Some(inv) = inverse.as_deref_mut() {
+                    inv.swap_rows(pivot, col);
+                }
+                det = -det;
+            }
+            let p = self.at(col, col);
+            det *= p;
+            self.divide_row(col, p);
+            if let Some(inv) = inverse.as_deref_mut() {
+                inv.divide_row(col, p);
+            }
+            for r in (0..n).filter(|&r| r != col) {
+                let factor = self.at(r, col);
+                if factor != 0.0 {
+                    self.add_row(r, col, -factor);
+                    if let Some(inv) = inverse.as_deref_mut() {
+                        inv.add_row(r, col, -factor);
+                    }
+                }
+            }
+        }
+        det
+    }
+
+    fn swap_rows(&mut self, a: usize, b: usize) {
+        for c in 0..self.cols {
+            self.data.swap(a * self.cols + c, b * self.cols + c);
+        }
+    }
+
+    fn divide_row(&mut self, r: usize, divisor: f64) {
+        self.data[r * self.cols..(r + 1) * self.cols].iter_mut().for_each(|v| *v /= divisor);
+    }
+
+    /// Row `target` += `factor` * row `source`.
+    fn add_row(&mut self, target: usize, source: usize, factor: f64) {
+        for c in 0..self.cols {
+            let v = self.at(source, c);
+            self.data[target * self.cols + c] += factor * v;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtins::call_builtin;
+    use crate::vm::VM;
+    use kinetix_kicomp::ir::CompiledProgram;
+
+    fn call(name: &str, args: &[Value]) -> Result<Value, String> {
+        let mut vm = VM::new(CompiledProgram::new());
+        vm.strict_builtins = true;
+        call_builtin(name, args, &mut vm)
+    }
+
+    fn mat<const N: usize>(rows: &[[f64; N]]) -> Value {
+        Mat::rows(rows).into_value()
+    }
+
+    fn vec_of(items: &[f64]) -> Value {
+        Value::Array(items.iter().map(|v| Value::Float(*v)).collect())
+    }
+
+    fn flat(matrix: Value) -> Value {
+        let Value::Array(rows) = matrix else { panic!("expected a matrix, got {}", matrix) };
+        Value::Array(rows.into_iter().flat_map(|row| match row {
+            Value::Array(items) => items,
+            other => panic!("expected a row, got {}", other),
+        }).collect())
+    }
+
+    fn close(value: Value, expected: &[f64]) {
+        let Value::Array(items) = value else { panic!("expected an array, got {}", value) };
+        let got: Vec<f64> = items.iter().map(|v| v.as_float().unwrap()).collect();
+        assert!(got.len() == expected.len() && got.iter().zip(expected).all(|(a, b)| (a - b).abs() < 1e-9), "{:?} != {:?}", got, expected);
+    }
+
+    #[test]
+    fn multiply_transpose_and_invert() {
+        let a = mat(&[[1.0, 2.0], [3.0, 4.0]]);
+        assert_eq!(call("math.mat_mul", &[a.clone(), mat(&[[0.0, 1.0], [1.0, 0.0]])]), Ok(mat(&[[2.0, 1.0], [4.0, 3.0]])));
+        assert_eq!(call("math.mat_mul", &[a.clone(), vec_of(&[1.0, 1.0])]), Ok(vec_of(&[3.0, 7.0])));
+        assert_eq!(call("math.transpose", &[mat(&[[1.0, 2.0, 3.0]])]), Ok(mat(&[[1.0], [2.0], [3.0]])));
+        assert_eq!(call("math.determinant", std::slice::from_ref(&a)), Ok(Value::Float(-2.0)));
+
+        let inverse = call("math.inverse", std::slice::from_ref(&a)).unwrap();
+        close(flat(inverse.clone()), &[-2.0, 1.0, 1.5, -0.5]);
+        close(flat(call("math.mat_mul", &[a, inverse]).unwrap()), &[1.0, 0.0, 0.0, 1.0]);
+
+        assert!(call("math.inverse", &[mat(&[[1.0, 2.0], [2.0, 4.0]])]).is_err());
+        assert!(call("math.mat_mul", &[mat(&[[1.0, 2.0]]), mat(&[[1.0, 2.0]])]).is_err());
+        assert!(call("math.transpose", &[Value::Array(vec![vec_of(&[1.0]), vec_of(&[1.0, 2.0])])]).is_err());
+        assert_eq!(call("math.matrix", &[Value::Int(1), Value::Int(2), Value::Int(7)]), Ok(mat(&[[7.0, 7.0]])));
+        assert!(call("math.matrix", &[Value::Int(1 << 20), Value::Int(1 << 20)]).is_err());
+    }
+
+    #[test]
+    fn transforms_compose_right_to_left() {
+        let quarter = Value::Float(std::f64::consts::FRAC_PI_2);
+        let rotate = call("math.rotate2", std::slice::from_ref(&quarter)).unwrap();
+        let translate = call("math.translate2", &[Value::Float(10.0), Value::Float(0.0)]).unwrap();
+        let m = call("math.mat_mul", &[translate, rotate]).unwrap();
+        close(call("math.transform", &[m, vec_of(&[1.0, 0.0])]).unwrap(), &[10.0, 1.0]);
+
+        let spin = call("math.rotate3", &[vec_of(&[0.0, 0.0, 2.0]), quarter]).unwrap();
+        close(call("math.transform", &[spin.clone(), vec_of(&[1.0, 0.0, 0.0])]).unwrap(), &[0.0, 1.0, 0.0]);
+        let scale = call("math.scale3", &[Value::Float(2.0), Value::Float(2.0), Value::Float(2.0)]).unwrap();
+        close(call("math.transform", &[scale, vec_of(&[1.0, 2.0, 3.0])]).unwrap(), &[2.0, 4.0, 6.0]);
+        assert!(call("math.transform", &[spin, vec_of(&[1.0, 0.0])]).is_err());
+    }
+}
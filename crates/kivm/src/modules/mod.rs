@@ -3,7 +3,20 @@ pub mod net;
 pub mod crypto;
 pub mod audio;
 pub mod data;
+pub mod io;
 pub mod db;
 pub mod graph;
 pub mod llm;
 pub mod term;
+pub mod sb;
+pub mod random;
+pub mod stats;
+pub mod matrix;
+pub mod csv;
+pub mod markup;
+pub mod template;
+pub mod i18n;
+pub mod decimal;
+pub mod fs;
+pub mod archive;
+pub mod ipc;
@@ -3,7 +3,10 @@
 /// TCP/UDP use std::net (synchronous, ownership-safe).
 /// HTTP uses ureq (synchronous blocking).
 
+use crate::builtins::{builtin, Builtin};
+use crate::quota::{self, Metered, Resource};
 use crate::vm::Value;
+use kinetix_kicomp::capability::Capability;
 use std::collections::HashMap;
 use std::io::{Read, Write, BufRead, BufReader};
 use std::net::{TcpStream, TcpListener, UdpSocket, Shutdown};
@@ -41,6 +44,18 @@ fn err_result(msg: &str) -> Value {
     Value::Map(m)
 }
 
+/// Longest HTTP response body read into a string, as ureq's `into_string`.
+const MAX_RESPONSE_BYTES: u64 = 10 * 1024 * 1024;
+
+/// The body of `resp`, charged to the `net-bytes` quota.
+fn response_text(resp: ureq::Response) -> Result<String, String> {
+    let mut text = String::new();
+    Metered::new(resp.into_reader().take(MAX_RESPONSE_BYTES), Resource::NetBytes)
+        .read_to_string(&mut text)
+        .map_err(|e| e.to_string())?;
+    Ok(text)
+}
+
 pub fn call(func_name: &str, args: &[Value]) -> Result<Value, String> {
     match func_name {
         // =====================================================================
@@ -119,6 +134,9 @@ pub fn call(func_name: &str, args: &[Value]) -> Result<Value, String> {
             };
             let mut streams = TCP_STREAMS.lock().map_err(|_| "TCP lock failed".to_string())?;
             let stream = streams.get_mut(&cid).ok_or_else(|| format!("Connection {} not found", cid))?;
+            if let Err(e) = quota::charge(Resource::NetBytes, data.len() as u64) {
+                return Ok(err_result(&e));
+            }
             match stream.write_all(&data) {
                 Ok(_) => Ok(ok_result(Value::Null)),
                 Err(e) => Ok(err_result(&format!("TCP send failed: {}", e))),
@@ -138,7 +156,7 @@ pub fn call(func_name: &str, args: &[Value]) -> Result<Value, String> {
             let mut streams = TCP_STREAMS.lock().map_err(|_| "TCP lock failed".to_string())?;
             let stream = streams.get_mut(&cid).ok_or_else(|| format!("Connection {} not found", cid))?;
             let mut buf = vec![0u8; max_bytes];
-            match stream.read(&mut buf) {
+            match Metered::new(stream, Resource::NetBytes).read(&mut buf) {
                 Ok(n) => {
                     let text = String::from_utf8_lossy(&buf[..n]).to_string();
                     Ok(ok_result(Value::Str(text)))
@@ -159,7 +177,7 @@ pub fn call(func_name: &str, args: &[Value]) -> Result<Value, String> {
             drop(streams);
             let mut reader = BufReader::new(cloned);
             let mut line = String::new();
-            match reader.read_line(&mut line) {
+            match reader.read_line(&mut line).and_then(|n| quota::charge(Resource::NetBytes, n as u64).map_err(std::io::Error::other)) {
                 Ok(_) => Ok(ok_result(Value::Str(line.trim_end().to_string()))),
                 Err(e) => Ok(err_result(&format!("TCP recvLine failed: {}", e))),
             }
@@ -214,8 +232,16 @@ pub fn call(func_name: &str, args: &[Value]) -> Result<Value, String> {
             }
         }
 
-        // net.tcp.close(conn_id) — alias for shutdown
-        "tcp.close" => call("tcp.shutdown", args),
+        // net.tcp.close(id) — shutdown for a connection; a listener stops
+        // listening
+        "tcp.close" => {
+            if let Some(Value::Int(id)) = args.first()
+                && TCP_LISTENERS.lock().map_err(|_| "TCP lock failed".to_string())?.remove(id).is_some()
+            {
+                return Ok(ok_result(Value::Null));
+            }
+            call("tcp.shutdown", args)
+        }
 
         // net.tcp.localAddr(conn_id) -> Result<String, E>
         "tcp.localAddr" => {
@@ -281,6 +307,9 @@ pub fn call(func_name: &str, args: &[Value]) -> Result<Value, String> {
             let sockets = UDP_SOCKETS.lock().map_err(|_| "UDP lock failed".to_string())?;
             let sock = sockets.get(&sid).ok_or_else(|| format!("Socket {} not found", sid))?;
             let target = format!("{}:{}", addr, port);
+            if let Err(e) = quota::charge(Resource::NetBytes, data.len() as u64) {
+                return Ok(err_result(&e));
+            }
             match sock.send_to(&data, &target) {
                 Ok(n) => Ok(ok_result(Value::Int(n as i64))),
                 Err(e) => Ok(err_result(&format!("UDP send failed: {}", e))),
@@ -301,6 +330,7 @@ pub fn call(func_name: &str, args: &[Value]) -> Result<Value, String> {
             let sock = sockets.get(&sid).ok_or_else(|| format!("Socket {} not found", sid))?;
             let mut buf = vec![0u8; max_bytes];
             match sock.recv_from(&mut buf) {
+                Ok((n, _)) if let Err(e) = quota::charge(Resource::NetBytes, n as u64) => Ok(err_result(&e)),
                 Ok((n, addr)) => {
                     let mut res = HashMap::new();
                     res.insert("ok".to_string(), Value::Str(String::from_utf8_lossy(&buf[..n]).to_string()));
@@ -352,7 +382,10 @@ pub fn call(func_name: &str, args: &[Value]) -> Result<Value, String> {
                 match ureq::get(url).call() {
                     Ok(resp) => {
                         let status = resp.status();
-                        let text = resp.into_string().map_err(|e| e.to_string())?;
+                        let text = match response_text(resp) {
+                            Ok(text) => text,
+                            Err(e) => return Ok(err_result(&e)),
+                        };
                         let mut res = HashMap::new();
                         res.insert("ok".to_string(), Value::Str(text));
                         res.insert("status".to_string(), Value::Int(status as i64));
@@ -368,10 +401,16 @@ pub fn call(func_name: &str, args: &[Value]) -> Result<Value, String> {
         "post" | "http.post" => {
             if let (Some(Value::Str(url)), Some(body_val)) = (args.get(0), args.get(1)) {
                 let body_str = format!("{}", body_val);
+                if let Err(e) = quota::charge(Resource::NetBytes, body_str.len() as u64) {
+                    return Ok(err_result(&e));
+                }
                 match ureq::post(url).send_string(&body_str) {
                     Ok(resp) => {
                         let status = resp.status();
-                        let text = resp.into_string().map_err(|e| e.to_string())?;
+                        let text = match response_text(resp) {
+                            Ok(text) => text,
+                            Err(e) => return Ok(err_result(&e)),
+                        };
                         let mut res = HashMap::new();
                         res.insert("ok".to_string(), Value::Str(text));
                         res.insert("status".to_string(), Value::Int(status as i64));
@@ -388,10 +427,12 @@ pub fn call(func_name: &str, args: &[Value]) -> Result<Value, String> {
             if let (Some(Value::Str(url)), Some(Value::Str(dest))) = (args.get(0), args.get(1)) {
                 match ureq::get(url).call() {
                     Ok(resp) => {
-                        let mut reader = resp.into_reader();
-                        let mut file = std::fs::File::create(dest).map_err(|e| e.to_string())?;
-                        std::io::copy(&mut reader, &mut file).map_err(|e| e.to_string())?;
-                        Ok(ok_result(Value::Null))
+                        let mut reader = Metered::new(resp.into_reader(), Resource::NetBytes);
+                        let mut file = Metered::new(std::fs::File::create(dest).map_err(|e| e.to_string())?, Resource::FsWriteBytes);
+                        match std::io::copy(&mut reader, &mut file) {
+                            Ok(_) => Ok(ok_result(Value::Null)),
+                            Err(e) => Ok(err_result(&format!("Download failed: {}", e))),
+                        }
                     }
                     Err(e) => Ok(err_result(&format!("Download failed: {}", e))),
                 }
@@ -563,3 +604,37 @@ pub fn call(func_name: &str, args: &[Value]) -> Result<Value, String> {
         _ => Err(format!("Unknown net function: {}", func_name)),
     }
 }
+
+/// `net.*` builtins, with `net.get`/`net.post`/`net.download` kept as the
+/// legacy names of the `net.http.*` ones.
+pub const BUILTINS: &[Builtin] = &[
+    // Net TCP/UDP (Build 28)
+    builtin("net.tcp.connect", "(host: str, port: int) -> result<int>", "Opens a TCP connection; returns its id.", |a, _| call("tcp.connect", a)).needs(Capability::NetAccess),
+    builtin("net.tcp.listen", "(port: int) -> result<int>", "Listens on 0.0.0.0:`port`; returns the listener id.", |a, _| call("tcp.listen", a)).needs(Capability::NetAccess),
+    builtin("net.tcp.accept", "(listener: int) -> result<int>", "Waits for a connection; the result also carries the peer `addr`.", |a, _| call("tcp.accept", a)).needs(Capability::NetAccess),
+    builtin("net.tcp.send", "(conn: int, data: str) -> result<null>", "Writes all of `data` to the connection.", |a, _| call("tcp.send", a)).needs(Capability::NetAccess),
+    builtin("net.tcp.recv", "(conn: int, max_bytes: int?) -> result<str>", "Reads up to `max_bytes` (default 4096).", |a, _| call("tcp.recv", a)).needs(Capability::NetAccess),
+    builtin("net.tcp.recvLine", "(conn: int) -> result<str>", "Reads one line, without its line ending.", |a, _| call("tcp.recvLine", a)).needs(Capability::NetAccess),
+    builtin("net.tcp.setTimeout", "(conn: int, ms: int) -> result<null>", "Read and write timeout for the connection.", |a, _| call("tcp.setTimeout", a)).needs(Capability::NetAccess),
+    builtin("net.tcp.setNoDelay", "(conn: int, enabled: bool?) -> result<null>", "Sets TCP_NODELAY on (default) or off.", |a, _| call("tcp.setNoDelay", a)).needs(Capability::NetAccess),
+    builtin("net.tcp.shutdown", "(conn: int) -> result<null>", "Shuts down both directions and forgets the connection.", |a, _| call("tcp.shutdown", a)).needs(Capability::NetAccess),
+    builtin("net.tcp.close", "(conn: int) -> result<null>", "Same as net.tcp.shutdown; closes a listener too.", |a, _| call("tcp.close", a)).needs(Capability::NetAccess),
+    builtin("net.tcp.localAddr", "(conn: int) -> result<str>", "Local `ip:port` of the connection.", |a, _| call("tcp.localAddr", a)).needs(Capability::NetAccess),
+    builtin("net.tcp.peerAddr", "(conn: int) -> result<str>", "Remote `ip:port` of the connection.", |a, _| call("tcp.peerAddr", a)).needs(Capability::NetAccess),
+    builtin("net.udp.bind", "(port: int) -> result<int>", "Binds a UDP socket on 0.0.0.0:`port`; returns its id.", |a, _| call("udp.bind", a)).needs(Capability::NetAccess),
+    builtin("net.udp.send", "(sock: int, host: str, port: int, data: str) -> result<int>", "Sends one datagram; returns the bytes sent.", |a, _| call("udp.send", a)).needs(Capability::NetAccess),
+    builtin("net.udp.recv", "(sock: int, max_bytes: int?) -> result<str>", "Receives one datagram; the result also carries the sender `addr`.", |a, _| call("udp.recv", a)).needs(Capability::NetAccess),
+    builtin("net.udp.setTimeout", "(sock: int, ms: int) -> result<null>", "Read and write timeout for the socket.", |a, _| call("udp.setTimeout", a)).needs(Capability::NetAccess),
+    builtin("net.udp.close", "(sock: int) -> result<null>", "Closes the socket.", |a, _| call("udp.close", a)).needs(Capability::NetAccess),
+    builtin("net.http.get", "(url: str) -> result<str>", "HTTP GET; the result also carries the `status` code.", |a, _| call("http.get", a)).needs(Capability::NetAccess),
+    builtin("net.http.post", "(url: str, body: any) -> result<str>", "HTTP POST of str(body); the result also carries the `status` code.", |a, _| call("http.post", a)).needs(Capability::NetAccess),
+    builtin("net.http.download", "(url: str, dest: str) -> result<null>", "Downloads `url` into the file `dest`.", |a, _| call("http.download", a)).needs(Capability::NetAccess),
+    builtin("net.get", "(url: str) -> result<str>", "Same as net.http.get.", |a, _| call("get", a)).needs(Capability::NetAccess),
+    builtin("net.post", "(url: str, body: any) -> result<str>", "Same as net.http.post.", |a, _| call("post", a)).needs(Capability::NetAccess),
+    builtin("net.download", "(url: str, dest: str) -> result<null>", "Same as net.http.download.", |a, _| call("download", a)).needs(Capability::NetAccess),
+    builtin("net.resolve", "(host: str) -> result<str>", "First IP address `host` resolves to.", |a, _| call("resolve", a)).needs(Capability::NetAccess),
+    // Net Utils (Build 30)
+    builtin("net.ping", "(host: str, timeout_ms: int?) -> result<int>", "Round-trip time of a TCP connect to port 80, in milliseconds (timeout default 1000).", |a, _| call("ping", a)).needs(Capability::NetAccess),
+    builtin("net.getInterfaces", "() -> result<[{name: str, addr: str}]>", "The machine's network interfaces and their addresses.", |a, _| call("getInterfaces", a)).needs(Capability::NetAccess),
+    builtin("net.tls.connect", "(host: str, port: int) -> result<int>", "Opens a TLS connection; returns its id.", |a, _| call("tls.connect", a)).needs(Capability::NetAccess),
+];
@@ -0,0 +1,197 @@
+//! `random`: a seedable, reproducible pseudo-random generator (xoshiro256**); not
+//! for secrets, which `crypto.random_*` is for.
+
+use crate::builtins::{builtin, Builtin};
+use crate::vm::Value;
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::sync::Mutex;
+
+static RNG: Mutex<Option<Rng>> = Mutex::new(None);
+
+pub const BUILTINS: &[Builtin] = &[
+    builtin("random.seed", "(seed: int)", "Reseeds the generator, so the numbers that follow repeat from run to run.", |a, _| call("seed", a)),
+    builtin("random.int", "(min: int, max: int) -> int", "Uniform in `min..=max`; raises when `min` is greater than `max`.", |a, _| call("int", a)),
+    builtin("random.float", "() -> float", "Uniform in `[0, 1)`.", |a, _| call("float", a)),
+    builtin("random.choice", "(items: [T]) -> T", "A uniformly chosen element; raises on an empty array.", |a, _| call("choice", a)),
+    builtin("random.shuffle", "(items: [T]) -> [T]", "A shuffled copy of `items`.", |a, _| call("shuffle", a)),
+    builtin("random.gauss", "(mu: float, sigma: float) -> float", "Normally distributed with mean `mu` and standard deviation `sigma`.", |a, _| call("gauss", a)),
+];
+
+pub fn call(name: &str, args: &[Value]) -> Result<Value, String> {
+    let mut guard = RNG.lock().unwrap_or_else(|e| e.into_inner());
+    if name == "seed" {
+        let Some(Value::Int(seed)) = args.first() else {
+            return Err(format!("random.seed() expects an int, got {}", describe(args.first())));
+        };
+        *guard = Some(Rng::new(*seed as u64));
+        return Ok(Value::Null);
+    }
+    let rng = guard.get_or_insert_with(|| Rng::new(RandomState::new().hash_one(0u8)));
+    match name {
+        "int" => {
+            let (Some(Value::Int(min)), Some(Value::Int(max))) = (args.first(), args.get(1)) else {
+                return Err("random.int() expects (min: int, max: int)".to_string());
+            };
+            if min > max {
+                return Err(format!("random.int(): min {} is greater than max {}", min, max));
+            }
+            Ok(Value::Int(rng.int(*min, *max)))
+        }
+        "float" => Ok(Value::Float(rng.float())),
+        "choice" => match args.first() {
+            Some(Value::Array(items)) if items.is_empty() => Err("random.choice() of an empty array".to_string()),
+            Some(Value::Array(items)) => Ok(items[rng.below(items.len() as u64) as usize].clone()),
+            other => Err(format!("random.choice() expects an array, got {}", describe(other))),
+        },
+        "shuffle" => match args.first() {
+            Some(Value::Array(items)) => {
+                let mut items = items.clone();
+                rng.shuffle(&mut items);
+                Ok(Value::Array(items))
+            }
+            other => Err(format!("random.shuffle() expects an array, got {}", describe(other))),
+        },
+        "gauss" => {
+            let (Some(mu), Some(sigma)) = (args.first().and_then(number), args.get(1).and_then(number)) else {
+                return Err("random.gauss() expects (mu: float, sigma: float)".to_string());
+            };
+            Ok(Value::Float(mu + sigma * rng.standard_normal()))
+        }
+        _ => Err(format!("Unknown random function: {}", name)),
+    }
+}
+
+fn number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Int(n) => Some(*n as f64),
+        Value::Float(f) => Some(*f),
+        _ => None,
+    }
+}
+
+fn describe(value: Option<&Value>) -> String {
+    value.map_or_else(|| "nothing".to_string(), |v| v.to_string())
+}
+
+/// xoshiro256** seeded through SplitMix64.
+pub struct Rng {
+    state: [u64; 4],
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        let mut sm = seed;
+        Rng { state: [splitmix64(&mut sm), splitmix64(&mut sm), splitmix64(&mut sm), splitmix64(&mut sm)] }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let s = &mut self.state;
+        let result = s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = s[1] << 17;
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+        result
+    }
+
+    /// Uniform in `[0, 1)`, from the top 53 bits.
+    pub fn float(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+    }
+
+    /// Uniform in `[0, n)` without modulo bias (rejects the uneven tail).
+    fn below(&mut self, n: u64) -> u64 {
+        let zone = u64::MAX - u64::MAX % n;
+        loop {
+            let x = self.next_u64();
+            if x < zone {
+                return x % n;
+            }
+        }
+    }
+
+    /// Uniform in `[min, max]`, both ends included.
+    pub fn int(&mut self, min: i64, max: i64) -> i64 {
+        let span = max.wrapping_sub(min) as u64;
+        let offset = if span == u64::MAX { self.next_u64() } else { self.below(span + 1) };
+        min.wrapping_add(offset as i64)
+    }
+
+    /// Fisher-Yates.
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = self.below(i as u64 + 1) as usize;
+            items.swap(i, j);
+        }
+    }
+
+    /// Box-Muller, one value per call.
+    pub fn standard_normal(&mut self) -> f64 {
+        let u1 = 1.0 - self.float(); // (0, 1]: ln(0) is -inf
+        let u2 = self.float();
+        (-2.0 * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos()
+    }
+}
+
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeded_generator_is_reproducible_and_in_range() {
+        // SplitMix64's published first output for seed 0
+        assert_eq!(splitmix64(&mut 0), 0xE220_A839_7B1D_CDAF);
+
+        let (mut a, mut b) = (Rng::new(42), Rng::new(42));
+        let draws: Vec<u64> = (0..8).map(|_| a.next_u64()).collect();
+        assert_eq!(draws, (0..8).map(|_| b.next_u64()).collect::<Vec<_>>());
+        assert_ne!(draws, (0..8).map(|_| Rng::new(43).next_u64()).collect::<Vec<_>>());
+
+        let mut rng = Rng::new(7);
+        for _ in 0..1000 {
+            assert!((-3..=3).contains(&rng.int(-3, 3)));
+            assert!((0.0..1.0).contains(&rng.float()));
+        }
+        assert_eq!(rng.int(5, 5), 5);
+        let full = rng.int(i64::MIN, i64::MAX);
+        assert!((i64::MIN..=i64::MAX).contains(&full));
+
+        let mut items: Vec<i32> = (0..20).collect();
+        rng.shuffle(&mut items);
+        assert_ne!(items, (0..20).collect::<Vec<_>>());
+        items.sort();
+        assert_eq!(items, (0..20).collect::<Vec<_>>());
+
+        let mean = (0..10_000).map(|_| rng.standard_normal()).sum::<f64>() / 10_000.0;
+        assert!(mean.abs() < 0.05, "mean {}", mean);
+    }
+
+    #[test]
+    fn module_calls_follow_the_seed() {
+        let draw = || {
+            call("seed", &[Value::Int(1)]).unwrap();
+            let array = Value::Array((1..=5).map(Value::Int).collect());
+            [
+                call("int", &[Value::Int(1), Value::Int(6)]).unwrap(),
+                call("choice", std::slice::from_ref(&array)).unwrap(),
+                call("shuffle", &[array]).unwrap(),
+                call("gauss", &[Value::Int(0), Value::Float(1.0)]).unwrap(),
+            ]
+        };
+        assert_eq!(draw(), draw());
+        assert!(call("int", &[Value::Int(2), Value::Int(1)]).is_err());
+        assert!(call("choice", &[Value::Array(vec![])]).is_err());
+    }
+}
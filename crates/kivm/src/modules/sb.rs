@@ -0,0 +1,114 @@
+//! `sb`: string builders, so a string built in a loop grows one buffer in
+//! place instead of being copied on every `s = s + piece`.
+
+use crate::builtins::{builtin, Builtin};
+use crate::native::{self, NativeMethod, NativeType};
+use crate::vm::Value;
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::Once;
+
+/// Script-visible type name of a builder handle (`typeof(b)`).
+pub const BUILDER_TYPE: &str = "StringBuilder";
+
+static REGISTER: Once = Once::new();
+
+/// Registers the `StringBuilder` native type: `append`, `to_str`, `len` and
+/// `clear`.
+fn register_builder_type() {
+    REGISTER.call_once(|| {
+        let mut methods: HashMap<&'static str, NativeMethod> = HashMap::new();
+        methods.insert("append", append);
+        methods.insert("to_str", to_str);
+        methods.insert("len", len);
+        methods.insert("clear", clear);
+        native::register_type(NativeType { name: BUILDER_TYPE, methods, destructor: None });
+    });
+}
+
+pub const BUILTINS: &[Builtin] = &[
+    builtin("sb.new", "(initial: str?) -> StringBuilder", "A string builder holding `initial`; `b.append(...)` grows it in place and `b.to_str()` copies it out.", |a, _| call("new", a)),
+    builtin("sb.append", "(builder: StringBuilder, values: any...)", "Same as `builder.append(values...)`: appends each value as print formats it.", |a, _| method(a, "append")),
+    builtin("sb.to_str", "(builder: StringBuilder) -> str", "Same as `builder.to_str()`.", |a, _| method(a, "to_str")),
+];
+
+/// Calls `builder.<name>(rest...)` for the function form of a method.
+fn method(args: &[Value], name: &str) -> Result<Value, String> {
+    match args.split_first() {
+        Some((Value::Native(handle), rest)) if handle.type_name() == BUILDER_TYPE => handle.call_method(name, rest),
+        other => Err(format!("sb.{}() expects a StringBuilder, got {}", name, other.map_or_else(|| "nothing".to_string(), |(v, _)| v.to_string()))),
+    }
+}
+
+pub fn call(name: &str, args: &[Value]) -> Result<Value, String> {
+    match name {
+        "new" => {
+            let initial = match args.first() {
+                None => String::new(),
+                Some(Value::Str(s)) => s.clone(),
+                Some(other) => return Err(format!("sb.new() expects an optional str, got {}", other)),
+            };
+            register_builder_type();
+            native::wrap(BUILDER_TYPE, initial)
+        }
+        _ => Err(format!("Unknown sb function: {}", name)),
+    }
+}
+
+fn buffer(obj: &mut (dyn Any + Send)) -> Result<&mut String, String> {
+    obj.downcast_mut::<String>().ok_or_else(|| "Expected a StringBuilder handle".to_string())
+}
+
+/// Appends every argument, formatted as `print` would.
+fn append(obj: &mut (dyn Any + Send), args: &[Value]) -> Result<Value, String> {
+    let buf = buffer(obj)?;
+    for arg in args {
+        match arg {
+            Value::Str(s) => buf.push_str(s),
+            other => {
+                use std::fmt::Write;
+                let _ = write!(buf, "{}", other);
+            }
+        }
+    }
+    Ok(Value::Null)
+}
+
+/// Copies the buffer out, once the pieces are all appended.
+fn to_str(obj: &mut (dyn Any + Send), _args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Str(buffer(obj)?.clone()))
+}
+
+/// Length in bytes, as `len` counts a string.
+fn len(obj: &mut (dyn Any + Send), _args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Int(buffer(obj)?.len() as i64))
+}
+
+fn clear(obj: &mut (dyn Any + Send), _args: &[Value]) -> Result<Value, String> {
+    buffer(obj)?.clear();
+    Ok(Value::Null)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builder_appends_in_place_and_copies_out_once() {
+        let b = call("new", &[Value::Str("n=".to_string())]).unwrap();
+        let Value::Native(handle) = &b else { panic!("expected a native handle, got {:?}", b) };
+        assert_eq!(handle.type_name(), BUILDER_TYPE);
+
+        handle.call_method("append", &[Value::Int(4), Value::Str(", ".to_string()), Value::Bool(true)]).unwrap();
+        assert_eq!(handle.call_method("to_str", &[]).unwrap(), Value::Str("n=4, true".to_string()));
+        assert_eq!(handle.call_method("len", &[]).unwrap(), Value::Int(9));
+
+        handle.call_method("clear", &[]).unwrap();
+        assert_eq!(handle.call_method("to_str", &[]).unwrap(), Value::Str(String::new()));
+        assert!(call("new", &[Value::Int(1)]).is_err());
+
+        method(&[b.clone(), Value::Str("x".to_string())], "append").unwrap();
+        assert_eq!(method(std::slice::from_ref(&b), "to_str").unwrap(), Value::Str("x".to_string()));
+        assert!(method(&[Value::Str("x".to_string())], "to_str").is_err());
+    }
+}
@@ -0,0 +1,169 @@
+//! `stats`: aggregates over arrays, computed natively.
+
+use crate::builtins::{builtin, Builtin};
+use crate::pretty::{self, Limits};
+use crate::vm::{Value, VM};
+use std::collections::{HashMap, HashSet};
+
+/// The numeric functions take an array of ints and floats and raise an
+/// error for anything else, or for an empty array where the result would be
+/// undefined. `variance` and `stddev` are the population forms unless
+/// `sample` is true (divide by n - 1); `percentile` interpolates linearly
+/// between the two nearest ranks.
+pub const BUILTINS: &[Builtin] = &[
+    builtin("stats.sum", "(xs: [float]) -> int | float", "Sum; an int when every element is an int (0 for an empty array).", |args, _| sum(&args[0])),
+    builtin("stats.mean", "(xs: [float]) -> float", "Arithmetic mean.", |args, _| Ok(Value::Float(mean(&numbers("mean", &args[0])?)))),
+    builtin("stats.median", "(xs: [float]) -> float", "Middle value, or the mean of the two middle values.", |args, _| {
+        Ok(Value::Float(percentile(sorted(numbers("median", &args[0])?), 50.0)))
+    }),
+    builtin("stats.variance", "(xs: [float], sample: bool?) -> float", "Population variance, or the sample variance when `sample` is true.", |args, _| {
+        Ok(Value::Float(variance(&numbers("variance", &args[0])?, args.get(1).is_some_and(Value::is_truthy))?))
+    }),
+    builtin("stats.stddev", "(xs: [float], sample: bool?) -> float", "Square root of `stats.variance`.", |args, _| {
+        Ok(Value::Float(variance(&numbers("stddev", &args[0])?, args.get(1).is_some_and(Value::is_truthy))?.sqrt()))
+    }),
+    builtin("stats.percentile", "(xs: [float], p: float) -> float", "The `p`th percentile (0 to 100), interpolated between ranks.", |args, _| {
+        let p = args[1].as_float()?;
+        if !(0.0..=100.0).contains(&p) {
+            return Err(format!("stats.percentile(): p must be between 0 and 100, got {}", p));
+        }
+        Ok(Value::Float(percentile(sorted(numbers("percentile", &args[0])?), p)))
+    }),
+    builtin("stats.group_by", "(items: [T], key: fn | str) -> {str: [T]}", "Elements grouped by `key(element)`, or by the field `key` of map elements; each group keeps input order.", group_by),
+    builtin("stats.unique", "(items: [T]) -> [T]", "The elements without repeats, in order of first appearance.", |args, _| {
+        let Value::Array(items) = &args[0] else { unreachable!("checked by the signature") };
+        let mut seen = HashSet::new();
+        Ok(Value::Array(items.iter().filter(|v| seen.insert(identity(v))).cloned().collect()))
+    }),
+];
+
+/// The elements of `xs` as floats; `name` is for the error message.
+fn numbers(name: &str, xs: &Value) -> Result<Vec<f64>, String> {
+    let Value::Array(items) = xs else { unreachable!("checked by the signature") };
+    if items.is_empty() {
+        return Err(format!("stats.{}() of an empty array", name));
+    }
+    items.iter().map(|v| match v {
+        Value::Int(n) => Ok(*n as f64),
+        Value::Float(f) => Ok(*f),
+        other => Err(format!("stats.{}() expects numbers, got {}", name, pretty::inspect(other, Limits::INSPECT))),
+    }).collect()
+}
+
+fn sum(xs: &Value) -> Result<Value, String> {
+    let Value::Array(items) = xs else { unreachable!("checked by the signature") };
+    if !items.iter().all(|v| matches!(v, Value::Int(_))) {
+        return Ok(Value::Float(numbers("sum", xs)?.iter().sum()));
+    }
+    items.iter()
+        .try_fold(0i64, |total, v| match v {
+            Value::Int(n) => total.checked_add(*n),
+            _ => None,
+        })
+        .map(Value::Int)
+        .ok_or_else(|| "stats.sum(): int overflow".to_string())
+}
+
+fn mean(xs: &[f64]) -> f64 {
+    xs.iter().sum::<f64>() / xs.len() as f64
+}
+
+fn variance(xs: &[f64], sample: bool) -> Result<f64, String> {
+    let n = xs.len() as f64 - if sample { 1.0 } else { 0.0 };
+    if n <= 0.0 {
+        return Err("stats: the sample variance needs at least two values".to_string());
+    }
+    let m = mean(xs);
+    Ok(xs.iter().map(|x| (x - m) * (x - m)).sum::<f64>() / n)
+}
+
+fn sorted(mut xs: Vec<f64>) -> Vec<f64> {
+    xs.sort_by(f64::total_cmp);
+    xs
+}
+
+/// `p` in [0, 100] over sorted, non-empty `xs`.
+fn percentile(xs: Vec<f64>, p: f64) -> f64 {
+    let rank = p / 100.0 * (xs.len() - 1) as f64;
+    let (lo, hi) = (rank.floor() as usize, rank.ceil() as usize);
+    xs[lo] + (xs[hi] - xs[lo]) * (rank - lo as f64)
+}
+
+fn group_by(args: &[Value], vm: &mut VM) -> Result<Value, String> {
+    let Value::Array(items) = &args[0] else { unreachable!("checked by the signature") };
+    let mut groups: HashMap<String, Value> = HashMap::new();
+    for item in items {
+        let key = match &args[1] {
+            Value::Str(field) => match item {
+                Value::Map(map) => map.get(field).cloned().unwrap_or(Value::Null),
+                other => return Err(format!("stats.group_by(): grouping by field '{}' needs map elements, got {}", field, pretty::inspect(other, Limits::INSPECT))),
+            },
+            key => vm.call_function_now(key.clone(), vec![item.clone()])?,
+        };
+        match groups.entry(key.to_string()).or_insert_with(|| Value::Array(Vec::new())) {
+            Value::Array(group) => group.push(item.clone()),
+            _ => unreachable!("groups only hold arrays"),
+        }
+    }
+    Ok(Value::Map(groups))
+}
+
+/// A key equal for equal values: ints and floats stay apart (`1` is not
+/// `1.0`), as do strings and the numbers they spell.
+fn identity(value: &Value) -> String {
+    pretty::inspect(value, Limits { depth: usize::MAX, width: usize::MAX })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtins::call_builtin;
+    use kinetix_kicomp::ir::CompiledProgram;
+
+    fn call(name: &str, args: &[Value]) -> Result<Value, String> {
+        let mut vm = VM::new(CompiledProgram::new());
+        vm.strict_builtins = true;
+        call_builtin(name, args, &mut vm)
+    }
+
+    fn array(items: &[f64]) -> Value {
+        Value::Array(items.iter().map(|f| Value::Float(*f)).collect())
+    }
+
+    #[test]
+    fn numeric_aggregates() {
+        let ints = Value::Array([3, 1, 4, 1, 5].into_iter().map(Value::Int).collect());
+        assert_eq!(call("stats.sum", std::slice::from_ref(&ints)), Ok(Value::Int(14)));
+        assert_eq!(call("stats.sum", &[Value::Array(vec![Value::Int(1), Value::Float(0.5)])]), Ok(Value::Float(1.5)));
+        assert_eq!(call("stats.mean", std::slice::from_ref(&ints)), Ok(Value::Float(2.8)));
+        assert_eq!(call("stats.median", std::slice::from_ref(&ints)), Ok(Value::Float(3.0)));
+        assert_eq!(call("stats.median", &[array(&[1.0, 2.0, 3.0, 4.0])]), Ok(Value::Float(2.5)));
+
+        let xs = array(&[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]);
+        assert_eq!(call("stats.variance", std::slice::from_ref(&xs)), Ok(Value::Float(4.0)));
+        assert_eq!(call("stats.stddev", std::slice::from_ref(&xs)), Ok(Value::Float(2.0)));
+        assert_eq!(call("stats.variance", &[xs.clone(), Value::Bool(true)]), Ok(Value::Float(32.0 / 7.0)));
+        assert_eq!(call("stats.percentile", &[xs.clone(), Value::Int(25)]), Ok(Value::Float(4.0)));
+        assert_eq!(call("stats.percentile", &[xs.clone(), Value::Int(100)]), Ok(Value::Float(9.0)));
+
+        assert!(call("stats.mean", &[Value::Array(vec![])]).is_err());
+        assert!(call("stats.mean", &[Value::Array(vec![Value::Str("1".into())])]).is_err());
+        assert!(call("stats.percentile", &[xs, Value::Int(101)]).is_err());
+        assert!(call("stats.sum", &[Value::Array(vec![Value::Int(i64::MAX), Value::Int(1)])]).is_err());
+    }
+
+    #[test]
+    fn unique_and_group_by_field() {
+        let items = Value::Array(vec![Value::Int(1), Value::Float(1.0), Value::Str("1".into()), Value::Int(1), Value::Int(2)]);
+        assert_eq!(
+            call("stats.unique", &[items]),
+            Ok(Value::Array(vec![Value::Int(1), Value::Float(1.0), Value::Str("1".into()), Value::Int(2)]))
+        );
+
+        let row = |city: &str, n: i64| Value::Map([("city".to_string(), Value::Str(city.into())), ("n".to_string(), Value::Int(n))].into_iter().collect());
+        let rows = Value::Array(vec![row("Rome", 1), row("Oslo", 2), row("Rome", 3)]);
+        let Ok(Value::Map(groups)) = call("stats.group_by", &[rows, Value::Str("city".into())]) else { panic!("expected a map") };
+        assert_eq!(groups["Rome"], Value::Array(vec![row("Rome", 1), row("Rome", 3)]));
+        assert_eq!(groups["Oslo"], Value::Array(vec![row("Oslo", 2)]));
+    }
+}
@@ -1,5 +1,9 @@
-use crate::vm::Value;
+use crate::builtins::{builtin, Builtin};
+use crate::vm::{Value, VM};
+use kinetix_kicomp::capability::Capability;
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
 use sysinfo::System;
 
 // Lazy initialization of System info to avoid overhead on every call
@@ -8,8 +12,12 @@ use sysinfo::System;
 // valid for this architecture:
 lazy_static::lazy_static! {
     static ref SYS: Arc<Mutex<System>> = Arc::new(Mutex::new(System::new_all()));
+    static ref THREAD_REGISTRY: Arc<Mutex<HashMap<i64, ThreadHandle>>> = Arc::new(Mutex::new(HashMap::new()));
+    static ref NEXT_THREAD_ID: Arc<Mutex<i64>> = Arc::new(Mutex::new(1));
 }
 
+type ThreadHandle = JoinHandle<Result<Value, String>>;
+
 // Helper to refresh and get
 #[allow(dead_code)]
 fn with_system<F, T>(f: F) -> Result<T, String>
@@ -75,6 +83,9 @@ pub fn call(func_name: &str, args: &[Value]) -> Result<Value, String> {
         "exec" => {
             if let Some(Value::Str(cmd)) = args.first() {
                 // Security: Capabilities check should happen at compile-time in sandbox auditor
+                if let Err(e) = crate::quota::charge(crate::quota::Resource::SpawnedProcs, 1) {
+                    return Ok(err_res(&e));
+                }
                 let output = std::process::Command::new(if cfg!(windows) { "cmd.exe" } else { "sh" })
                     .arg(if cfg!(windows) { "/c" } else { "-c" })
                     .arg(cmd)
@@ -110,7 +121,7 @@ pub fn call(func_name: &str, args: &[Value]) -> Result<Value, String> {
             }
         },
         "defer" => {
-            // Handled by builtins.rs interceptor (Build 26)
+            // Handled by `BUILTINS` below, which has the VM (Build 26)
             Ok(Value::Null)
         },
         "hostname" => {
@@ -131,3 +142,231 @@ pub fn call(func_name: &str, args: &[Value]) -> Result<Value, String> {
         _ => Err(format!("Unknown System function: {}", func_name))
     }
 }
+
+fn result(key: &str, value: Value) -> Value {
+    let mut res = HashMap::new();
+    res.insert(key.to_string(), value);
+    Value::Map(res)
+}
+
+fn record(fields: Vec<(&str, Value)>) -> Value {
+    Value::Map(fields.into_iter().map(|(k, v)| (k.to_string(), v)).collect())
+}
+
+fn os_memory() -> Result<Value, String> {
+    let mut sys = SYS.lock().map_err(|_| "Global system context lock failed")?;
+    sys.refresh_memory();
+    let bytes = |n: u64| Value::Int(n as i64);
+    Ok(record(vec![
+        ("total", bytes(sys.total_memory())),
+        ("available", bytes(sys.available_memory())),
+        ("free", bytes(sys.free_memory())),
+        ("used", bytes(sys.used_memory())),
+        ("swap_total", bytes(sys.total_swap())),
+        ("swap_used", bytes(sys.used_swap())),
+    ]))
+}
+
+fn os_disks() -> Value {
+    let disks = sysinfo::Disks::new_with_refreshed_list();
+    Value::Array(disks.iter().map(|disk| {
+        let (total, free) = (disk.total_space(), disk.available_space());
+        record(vec![
+            ("mount", Value::Str(disk.mount_point().to_string_lossy().into_owned())),
+            ("name", Value::Str(disk.name().to_string_lossy().into_owned())),
+            ("fs", Value::Str(disk.file_system().to_string_lossy().into_owned())),
+            ("total", Value::Int(total as i64)),
+            ("free", Value::Int(free as i64)),
+            ("used", Value::Int(total.saturating_sub(free) as i64)),
+            ("removable", Value::Bool(disk.is_removable())),
+        ])
+    }).collect())
+}
+
+fn os_cpu() -> Result<Value, String> {
+    let mut sys = SYS.lock().map_err(|_| "Global system context lock failed")?;
+    sys.refresh_cpu();
+    let first = sys.cpus().first();
+    let load = System::load_average();
+    Ok(record(vec![
+        ("count", Value::Int(sys.cpus().len() as i64)),
+        ("cores", Value::Int(sys.physical_core_count().unwrap_or(0) as i64)),
+        ("brand", Value::Str(first.map_or(String::new(), |c| c.brand().trim().to_string()))),
+        ("mhz", Value::Int(first.map_or(0, |c| c.frequency()) as i64)),
+        ("load", Value::Array(vec![Value::Float(load.one), Value::Float(load.five), Value::Float(load.fifteen)])),
+    ]))
+}
+
+fn os_uptime() -> Value {
+    record(vec![
+        ("seconds", Value::Int(System::uptime() as i64)),
+        ("boot_time", Value::Int(System::boot_time() as i64)),
+    ])
+}
+
+/// Reads the first `type == Battery` supply under /sys/class/power_supply.
+#[cfg(target_os = "linux")]
+fn os_battery() -> Value {
+    let Ok(entries) = std::fs::read_dir("/sys/class/power_supply") else { return Value::Null };
+    let mut supplies: Vec<_> = entries.flatten().map(|e| e.path()).collect();
+    supplies.sort();
+    let read = |dir: &std::path::Path, file: &str| std::fs::read_to_string(dir.join(file)).map(|s| s.trim().to_string()).ok();
+    for dir in supplies {
+        if read(&dir, "type").as_deref() != Some("Battery") {
+            continue;
+        }
+        let Some(percent) = read(&dir, "capacity").and_then(|c| c.parse::<i64>().ok()) else { continue };
+        let status = read(&dir, "status").unwrap_or_else(|| "Unknown".to_string()).to_lowercase().replace(' ', "_");
+        return record(vec![
+            ("percent", Value::Int(percent)),
+            ("charging", Value::Bool(status == "charging")),
+            ("status", Value::Str(status)),
+        ]);
+    }
+    Value::Null
+}
+
+#[cfg(not(target_os = "linux"))]
+fn os_battery() -> Value {
+    Value::Null
+}
+
+fn thread_spawn(args: &[Value], vm: &mut VM) -> Result<Value, String> {
+    if args.is_empty() {
+        return call("thread.spawn", args); // Error path fallback
+    }
+
+    let func_val = args[0].clone();
+    let thread_args: Vec<Value> = args.iter().skip(1).cloned().collect();
+    let program = vm.program().clone();
+    let strict = vm.strict_builtins;
+    let audit = vm.audit.clone();
+    let handle = vm.watched_by();
+
+    let mut id_lock = NEXT_THREAD_ID.lock().map_err(|_| "Failed to lock Thread ID generator")?;
+    let thread_id = *id_lock;
+    *id_lock += 1;
+
+    let handle = std::thread::spawn(move || {
+        let mut child_vm = VM::with_program(program);
+        child_vm.strict_builtins = strict;
+        child_vm.audit = audit;
+        if let Some(handle) = handle {
+            child_vm.set_handle(handle);
+        }
+        // Trigger execution loop for this function natively inside the child vm instance.
+        child_vm.run_function(func_val, thread_args)
+    });
+
+    THREAD_REGISTRY.lock().map_err(|_| "Thread mapping lock failed")?.insert(thread_id, handle);
+
+    // Return simulation of Kinetix Result<T, E> mapping to Ok(ID)
+    Ok(result("ok", Value::Int(thread_id)))
+}
+
+fn thread_join(args: &[Value], _vm: &mut VM) -> Result<Value, String> {
+    let Some(Value::Int(id)) = args.first() else {
+        return call("thread.join", args); // Error path fallback
+    };
+    let handle_opt = THREAD_REGISTRY.lock().map_err(|_| "Thread mapping lock failed")?.remove(id);
+    match handle_opt.map(|handle| handle.join()) {
+        Some(Ok(Ok(val))) => Ok(result("ok", val)),
+        Some(Ok(Err(e))) => Ok(result("err", Value::Str(e))),
+        Some(Err(_)) => Ok(result("err", Value::Str("Native thread panicked".to_string()))),
+        None => Ok(result("err", Value::Str(format!("Thread ID {} not found or already joined", id)))),
+    }
+}
+
+/// `system.*`, `env.*` and `time.*` builtins (`System.x` and `sys.x` reach
+/// the same entries). Names not listed here fall through to `call`.
+pub const BUILTINS: &[Builtin] = &[
+    builtin("System.time", "() -> int", "Reserved; not implemented by the VM yet.", |a, _| call("time", a)).needs(Capability::SysInfo),
+    builtin("time.now", "() -> int", "Reserved; not implemented by the VM yet.", |a, _| call("time", a)).needs(Capability::SysInfo),
+    builtin("time.ticks", "() -> int", "Reserved; not implemented by the VM yet.", |a, _| call("time.ticks", a)).needs(Capability::SysInfo),
+    builtin("time.sleep", "(ms: int)", "Reserved; not implemented by the VM yet.", |a, _| call("time.sleep", a)).needs(Capability::SysInfo),
+    builtin("system.exit", "(code: int?)", "Exits the process with `code` (default 0).", |args, vm| crate::builtins::call_builtin("exit", args, vm)),
+    builtin("system.os.isWindows", "() -> bool", "Whether the program runs on Windows.", |a, _| call("os.isWindows", a)).needs(Capability::SysInfo),
+    builtin("system.os.isLinux", "() -> bool", "Whether the program runs on Linux.", |a, _| call("os.isLinux", a)).needs(Capability::SysInfo),
+    builtin("system.os.isMac", "() -> bool", "Whether the program runs on macOS.", |a, _| call("os.isMac", a)).needs(Capability::SysInfo),
+    builtin("system.os.name", "() -> result<str>", "Operating system name, e.g. \"Ubuntu\" or \"Windows\".", |a, _| call("os.name", a)).needs(Capability::SysInfo),
+    builtin("system.os.arch", "() -> result<str>", "CPU architecture, e.g. \"x86_64\" or \"aarch64\".", |a, _| call("os.arch", a)).needs(Capability::SysInfo),
+    builtin("system.os.memory", "() -> {total: int, available: int, free: int, used: int, swap_total: int, swap_used: int}", "Physical memory and swap, in bytes.", |_, _| os_memory()).needs(Capability::SysInfo),
+    builtin("system.os.disks", "() -> [{mount: str, name: str, fs: str, total: int, free: int, used: int, removable: bool}]", "Every mounted disk with its size and free space in bytes.", |_, _| Ok(os_disks())).needs(Capability::SysInfo),
+    builtin("system.os.cpu", "() -> {count: int, cores: int, brand: str, mhz: int, load: [float]}", "Logical CPUs, physical cores (0 if unknown), model, clock and the 1/5/15-minute load averages (zeros on Windows).", |_, _| os_cpu()).needs(Capability::SysInfo),
+    builtin("system.os.uptime", "() -> {seconds: int, boot_time: int}", "Seconds since boot and the boot time as a Unix timestamp.", |_, _| Ok(os_uptime())).needs(Capability::SysInfo),
+    builtin("system.os.battery", "() -> {percent: int, status: str, charging: bool}?", "Charge and state of the first battery, or null when there is none or the platform does not expose it (only Linux does).", |_, _| Ok(os_battery())).needs(Capability::SysInfo),
+    builtin("system.exec", "(command: str) -> result<{stdout: str, stderr: str, status: int}>", "Runs `command` through the system shell and waits for it.", |a, _| call("exec", a)).needs(Capability::OsExecute),
+    builtin("system.thread.spawn", "(f: fn, args: any...) -> result<int>", "Runs `f(args...)` on a new VM thread; returns its thread id.", thread_spawn).needs(Capability::ThreadControl),
+    builtin("system.thread.join", "(id: int) -> result<any>", "Waits for a spawned thread and returns its result.", thread_join).needs(Capability::ThreadControl),
+    builtin("system.thread.sleep", "(ms: int) -> result<null>", "Blocks the current thread for `ms` milliseconds.", |a, _| call("thread.sleep", a)).needs(Capability::ThreadControl),
+    builtin("system.defer", "(f: fn, args: any...)", "Calls `f(args...)` when the current function returns, also when an error unwinds it; the last registered runs first.", |args, vm| match args.split_first() {
+        // Build 26: Register a deferred call on the current call frame
+        Some((f @ (Value::Function(_) | Value::NativeFn(_) | Value::BoundMethod(..)), rest)) => {
+            vm.push_defer(f.clone(), rest.to_vec());
+            Ok(Value::Null)
+        }
+        _ => Err("system.defer requires a function argument".to_string()),
+    }).needs(Capability::ThreadControl),
+    builtin("system.cpu_usage", "() -> float", "Global CPU usage in percent, sampled over 100 ms.", |a, _| call("cpu_usage", a)),
+    builtin("system.memory_free", "() -> int", "Free memory, in MiB.", |a, _| call("memory_free", a)),
+    builtin("system.memory_total", "() -> int", "Total memory, in MiB.", |a, _| call("memory_total", a)),
+    builtin("system.os_version", "() -> str", "Operating system version, or \"Unknown\".", |a, _| call("os_version", a)),
+    builtin("system.hostname", "() -> str", "Host name of the machine, or \"Unknown\".", |a, _| call("hostname", a)),
+    builtin("system.user_name", "() -> str", "Name of the current user, or \"Unknown\".", |a, _| call("user_name", a)),
+    builtin("system.uptime", "() -> int", "Seconds since the machine booted.", |a, _| call("uptime", a)),
+    builtin("system.os_name", "() -> result<str>", "Same as system.os.name.", |a, _| call("os_name", a)),
+    builtin("system.isWindows", "() -> bool", "Same as system.os.isWindows.", |a, _| call("isWindows", a)),
+    builtin("system.isLinux", "() -> bool", "Same as system.os.isLinux.", |a, _| call("isLinux", a)),
+    builtin("system.isMac", "() -> bool", "Same as system.os.isMac.", |a, _| call("isMac", a)),
+    builtin("system.clipboard_set", "(text: str) -> result<null>", "Reserved; always an err until the VM has clipboard access.", |a, _| call("clipboard_set", a)),
+    builtin("system.clipboard_get", "() -> result<str>", "Reserved; always an err until the VM has clipboard access.", |a, _| call("clipboard_get", a)),
+    builtin("env.get", "(key: str) -> str?", "Value of an environment variable, or null if unset.", |args, _| match args.first() {
+        Some(Value::Str(key)) => Ok(std::env::var(key).map(Value::Str).unwrap_or(Value::Null)),
+        _ => Ok(Value::Null),
+    }).needs(Capability::SysInfo),
+    builtin("env.set", "(key: str, value: str)", "Sets an environment variable for this process.", |args, _| {
+        if let (Some(Value::Str(key)), Some(Value::Str(val))) = (args.first(), args.get(1)) {
+            unsafe { std::env::set_var(key, val); }
+        }
+        Ok(Value::Null)
+    }).needs(Capability::SysInfo),
+    builtin("env.args", "() -> [str]", "Command-line arguments of the process, program path first.", |_, _| {
+        Ok(Value::Array(std::env::args().map(Value::Str).collect()))
+    }).needs(Capability::SysInfo),
+    builtin("env.cwd", "() -> str", "Current working directory.", |_, _| {
+        std::env::current_dir()
+            .map(|p| Value::Str(p.to_string_lossy().to_string()))
+            .map_err(|e| e.to_string())
+    }),
+    builtin("env.set_cwd", "(path: str)", "Changes the current working directory.", |args, _| match args.first() {
+        Some(Value::Str(path)) => std::env::set_current_dir(path).map(|_| Value::Null).map_err(|e| e.to_string()),
+        _ => Err("Expected path string".into()),
+    }),
+    builtin("env.user", "() -> str", "Name of the current user, or \"Unknown\".", |a, _| call("user_name", a)),
+    builtin("env.hostname", "() -> str", "Host name of the machine, or \"Unknown\".", |a, _| call("hostname", a)),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field<'a>(value: &'a Value, key: &str) -> &'a Value {
+        let Value::Map(map) = value else { panic!("expected a map, got {}", value) };
+        map.get(key).unwrap_or_else(|| panic!("missing '{}'", key))
+    }
+
+    #[test]
+    fn os_info_is_structured() {
+        let memory = os_memory().unwrap();
+        assert!(matches!((field(&memory, "total"), field(&memory, "available")), (Value::Int(t), Value::Int(a)) if t > &0 && a <= t));
+        let cpu = os_cpu().unwrap();
+        assert!(matches!(field(&cpu, "count"), Value::Int(n) if *n >= 1));
+        assert!(matches!(field(&cpu, "load"), Value::Array(l) if l.len() == 3));
+        assert!(matches!(field(&os_uptime(), "boot_time"), Value::Int(t) if *t > 0));
+        let Value::Array(disks) = os_disks() else { panic!("disks is not an array") };
+        for disk in &disks {
+            assert!(matches!((field(disk, "used"), field(disk, "total")), (Value::Int(u), Value::Int(t)) if u <= t));
+        }
+        assert!(matches!(os_battery(), Value::Null | Value::Map(_)));
+    }
+}
@@ -0,0 +1,265 @@
+//! `template.render`: mustache-style templates (`{{name}}`, sections,
+//! inverted sections and comments).
+
+use crate::builtins::{builtin, Builtin};
+use crate::vm::Value;
+
+/// Deepest section nesting a template may use; rendering recurses once per
+/// level.
+const MAX_DEPTH: usize = 100;
+
+pub const BUILTINS: &[Builtin] = &[
+    builtin("template.render", "(tpl: str, data: {str: any}, escape: bool?) -> str", "Renders a mustache-style template with `{{name}}`, `{{{raw}}}`, `{{#section}}`, `{{^inverted}}` and `{{! comments}}`.", |args, _| {
+        let Value::Str(tpl) = &args[0] else { unreachable!("checked by the signature") };
+        let escape = !matches!(args.get(2), Some(Value::Bool(false)));
+        let nodes = parse(tpl).map_err(|e| format!("template.render(): {}", e))?;
+        let mut out = String::with_capacity(tpl.len());
+        render(&nodes, &mut vec![&args[1]], escape, &mut out);
+        Ok(Value::Str(out))
+    }),
+];
+
+#[derive(Debug, PartialEq)]
+enum Node {
+    Text(String),
+    Var { name: String, escape: bool },
+    Section { name: String, inverted: bool, body: Vec<Node> },
+}
+
+/// An open section while parsing: its name, whether it is inverted, the
+/// offset it opened at, and the nodes of the enclosing level.
+type Open = (String, bool, usize, Vec<Node>);
+
+/// `{{{name}}}` and `{{& name}}` are never escaped and `{{! ..}}` is
+/// dropped; a section or comment tag alone on its line removes the whole
+/// line, so templates can be indented naturally.
+fn parse(src: &str) -> Result<Vec<Node>, String> {
+    let line_of = |pos: usize| src[..pos].matches('\n').count() + 1;
+    let mut nodes: Vec<Node> = Vec::new();
+    let mut open: Vec<Open> = Vec::new();
+    let mut pos = 0;
+    while let Some(start) = src[pos..].find("{{").map(|i| pos + i) {
+        push_text(&mut nodes, &src[pos..start]);
+        let (close, inner_start) = if src[start..].starts_with("{{{") { ("}}}", start + 3) } else { ("}}", start + 2) };
+        let end = src[inner_start..].find(close).map(|i| inner_start + i)
+            .ok_or_else(|| format!("line {}: unclosed tag", line_of(start)))?;
+        let tag = &src[inner_start..end];
+        pos = end + close.len();
+
+        let (sigil, name) = match tag.chars().next() {
+            _ if close == "}}}" => ('&', tag.trim()),
+            Some(c @ ('#' | '^' | '/' | '!' | '&' | '>')) => (c, tag[1..].trim()),
+            _ => (' ', tag.trim()),
+        };
+        if name.is_empty() && sigil != '!' {
+            return Err(format!("line {}: empty tag", line_of(start)));
+        }
+        if matches!(sigil, '#' | '^' | '/' | '!') {
+            pos = strip_standalone(src, &mut nodes, start, pos);
+        }
+        match sigil {
+            '!' => {}
+            '>' => return Err(format!("line {}: partials ({{{{> {}}}}}) are not supported", line_of(start), name)),
+            '#' | '^' => {
+                if open.len() == MAX_DEPTH {
+                    return Err(format!("line {}: sections nest deeper than {}", line_of(start), MAX_DEPTH));
+                }
+                open.push((name.to_string(), sigil == '^', start, std::mem::take(&mut nodes)));
+            }
+            '/' => {
+                let Some((opened, inverted, _, outer)) = open.pop() else {
+                    return Err(format!("line {}: {{{{/{}}}}} closes no section", line_of(start), name));
+                };
+                if opened != name {
+                    return Err(format!("line {}: {{{{/{}}}}} closes {{{{{}{}}}}}", line_of(start), name, if inverted { '^' } else { '#' }, opened));
+                }
+                let body = std::mem::replace(&mut nodes, outer);
+                nodes.push(Node::Section { name: opened, inverted, body });
+            }
+            _ => nodes.push(Node::Var { name: name.to_string(), escape: sigil == ' ' }),
+        }
+    }
+    push_text(&mut nodes, &src[pos..]);
+    if let Some((name, inverted, start, _)) = open.pop() {
+        return Err(format!("line {}: {{{{{}{}}}}} is never closed", line_of(start), if inverted { '^' } else { '#' }, name));
+    }
+    Ok(nodes)
+}
+
+fn push_text(nodes: &mut Vec<Node>, text: &str) {
+    if text.is_empty() {
+        return;
+    }
+    match nodes.last_mut() {
+        Some(Node::Text(prev)) => prev.push_str(text),
+        _ => nodes.push(Node::Text(text.to_string())),
+    }
+}
+
+/// When the tag spanning `start..end` is the only thing on its line, drops
+/// the line's indentation from the text before it and returns the position
+/// past its newline; otherwise returns `end`.
+fn strip_standalone(src: &str, nodes: &mut [Node], start: usize, end: usize) -> usize {
+    let line_start = src[..start].rfind('\n').map_or(0, |i| i + 1);
+    if !src[line_start..start].trim().is_empty() {
+        return end;
+    }
+    let rest = &src[end..];
+    let line_end = match rest.find('\n') {
+        Some(i) => end + i + 1,
+        None => src.len(),
+    };
+    if !src[end..line_end].trim().is_empty() {
+        return end;
+    }
+    let indent = start - line_start;
+    if let Some(Node::Text(prev)) = nodes.last_mut() {
+        prev.truncate(prev.len() - indent);
+    }
+    line_end
+}
+
+/// The value `name` refers to: the innermost enclosing section value (or
+/// the data) that has it, then each dotted part in turn. `.` is the
+/// innermost context itself.
+fn lookup<'v>(name: &str, stack: &[&'v Value]) -> Option<&'v Value> {
+    if name == "." {
+        return stack.last().copied();
+    }
+    let mut parts = name.split('.');
+    let first = parts.next()?;
+    let mut value = stack.iter().rev().find_map(|ctx| match ctx {
+        Value::Map(map) => map.get(first),
+        _ => None,
+    })?;
+    for part in parts {
+        value = match value {
+            Value::Map(map) => map.get(part)?,
+            _ => return None,
+        };
+    }
+    Some(value)
+}
+
+/// `{{name}}` is HTML-escaped unless `escape` is false, and a missing or
+/// null value renders as nothing. A section repeats its body once per
+/// element of an array and renders it once for any other truthy value, with
+/// that value as the innermost context; an inverted section renders only
+/// when the value is falsy or an empty array.
+fn render(nodes: &[Node], stack: &mut Vec<&Value>, escape: bool, out: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => out.push_str(text),
+            Node::Var { name, escape: escaped } => match lookup(name, stack) {
+                None | Some(Value::Null) => {}
+                Some(Value::Str(s)) if *escaped && escape => escape_html(s, out),
+                Some(Value::Str(s)) => out.push_str(s),
+                Some(other) if *escaped && escape => escape_html(&other.to_string(), out),
+                Some(other) => out.push_str(&other.to_string()),
+            },
+            Node::Section { name, inverted, body } => {
+                let value = lookup(name, stack);
+                let truthy = value.is_some_and(Value::is_truthy);
+                match value {
+                    _ if *inverted && !truthy => render(body, stack, escape, out),
+                    _ if *inverted => {}
+                    Some(Value::Array(items)) => {
+                        for item in items {
+                            stack.push(item);
+                            render(body, stack, escape, out);
+                            stack.pop();
+                        }
+                    }
+                    Some(value) if truthy => {
+                        stack.push(value);
+                        render(body, stack, escape, out);
+                        stack.pop();
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+fn escape_html(s: &str, out: &mut String) {
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtins::call_builtin;
+    use crate::vm::VM;
+    use kinetix_kicomp::ir::CompiledProgram;
+    use std::collections::HashMap;
+
+    fn map(pairs: Vec<(&str, Value)>) -> Value {
+        Value::Map(pairs.into_iter().map(|(k, v)| (k.to_string(), v)).collect::<HashMap<_, _>>())
+    }
+
+    fn render_str(tpl: &str, data: Value) -> Result<Value, String> {
+        let mut vm = VM::new(CompiledProgram::new());
+        vm.strict_builtins = true;
+        call_builtin("template.render", &[Value::Str(tpl.into()), data], &mut vm)
+    }
+
+    #[test]
+    fn renders_values_sections_and_standalone_lines() {
+        let data = map(vec![
+            ("title", Value::Str("<Tom & Jerry>".into())),
+            ("user", map(vec![("name", Value::Str("Ada".into()))])),
+            ("items", Value::Array(vec![
+                map(vec![("n", Value::Int(1))]),
+                map(vec![("n", Value::Int(2))]),
+            ])),
+            ("tags", Value::Array(vec![Value::Str("a".into()), Value::Str("b".into())])),
+            ("empty", Value::Array(vec![])),
+            ("admin", Value::Bool(false)),
+        ]);
+        let tpl = "\
+<h1>{{title}}</h1>{{{title}}}
+<ul>
+  {{#items}}
+  <li>{{n}} by {{user.name}}</li>
+  {{/items}}
+</ul>
+{{#tags}}[{{.}}]{{/tags}}{{! ignored }}
+{{^empty}}none{{/empty}}{{#admin}}secret{{/admin}}{{missing}}
+";
+        assert_eq!(
+            render_str(tpl, data),
+            Ok(Value::Str("\
+<h1>&lt;Tom &amp; Jerry&gt;</h1><Tom & Jerry>
+<ul>
+  <li>1 by Ada</li>
+  <li>2 by Ada</li>
+</ul>
+[a][b]
+none
+".into()))
+        );
+    }
+
+    #[test]
+    fn malformed_templates_name_the_line() {
+        let empty = map(vec![]);
+        assert_eq!(render_str("a\n{{#x}}", empty.clone()), Err("template.render(): line 2: {{#x}} is never closed".into()));
+        assert_eq!(render_str("{{#x}}{{/y}}", empty.clone()), Err("template.render(): line 1: {{/y}} closes {{#x}}".into()));
+        assert_eq!(render_str("{{name", empty.clone()), Err("template.render(): line 1: unclosed tag".into()));
+        assert!(render_str(&"{{#a}}".repeat(MAX_DEPTH + 1), empty.clone()).is_err());
+
+        let mut vm = VM::new(CompiledProgram::new());
+        let raw = call_builtin("template.render", &[Value::Str("{{v}}".into()), map(vec![("v", Value::Str("<b>".into()))]), Value::Bool(false)], &mut vm);
+        assert_eq!(raw, Ok(Value::Str("<b>".into())));
+    }
+}
@@ -1,8 +1,8 @@
 /// Terminal module — ANSI terminal control + bash-like shell commands.
 /// Accessible via `term.X()` in Kinetix scripts and used by `kivm shell`.
 
+use crate::builtins::{builtin, Builtin};
 use crate::vm::Value;
-use std::io::Write;
 
 /// Map color names to ANSI codes.
 fn ansi_color(name: &str) -> &'static str {
@@ -28,13 +28,45 @@ fn ansi_color(name: &str) -> &'static str {
     }
 }
 
+pub const BUILTINS: &[Builtin] = &[
+    builtin("term.clear", "()", "Clears the screen and moves the cursor home.", |a, _| call("clear", a)),
+    builtin("term.set_color", "(color: str?)", "Switches the text color: black, red, green, yellow, blue, magenta, cyan, white, a `bright_` variant, or reset.", |a, _| call("set_color", a)),
+    builtin("term.reset_color", "()", "Switches back to the default text color.", |a, _| call("reset_color", a)),
+    builtin("term.bold", "(text: any) -> str", "`text` wrapped in the ANSI codes for bold.", |a, _| call("bold", a)),
+    builtin("term.underline", "(text: any) -> str", "`text` wrapped in the ANSI codes for underline.", |a, _| call("underline", a)),
+    builtin("term.italic", "(text: any) -> str", "`text` wrapped in the ANSI codes for italics.", |a, _| call("italic", a)),
+    builtin("term.strikethrough", "(text: any) -> str", "`text` wrapped in the ANSI codes for strikethrough.", |a, _| call("strikethrough", a)),
+    builtin("term.color_print", "(color: str, text: any)", "Prints `text` in `color` (as for term.set_color), then resets the color.", |a, _| call("color_print", a)),
+    builtin("term.move_cursor", "(row: int?, col: int?)", "Moves the cursor to the 1-based `row` and `col`.", |a, _| call("move_cursor", a)),
+    builtin("term.hide_cursor", "()", "Hides the cursor.", |a, _| call("hide_cursor", a)),
+    builtin("term.show_cursor", "()", "Shows the cursor again.", |a, _| call("show_cursor", a)),
+    builtin("term.size", "() -> [int]", "`[columns, rows]` from COLUMNS and LINES, else `[80, 24]`.", |a, _| call("size", a)),
+    builtin("term.pwd", "() -> str", "The working directory.", |a, _| call("pwd", a)),
+    builtin("term.cd", "(dir: str?)", "Changes the working directory; the home directory when `dir` is omitted.", |a, _| call("cd", a)),
+    builtin("term.ls", "(dir: str?) -> [str]", "Entry names in `dir` (default the working directory).", |a, _| call("ls", a)),
+    builtin("term.cat", "(path: str) -> str", "Contents of the file at `path`.", |a, _| call("cat", a)),
+    builtin("term.mkdir", "(path: str)", "Creates the directory `path` and any missing parents.", |a, _| call("mkdir", a)),
+    builtin("term.rm", "(path: str)", "Deletes the file at `path`, or the directory and everything in it.", |a, _| call("rm", a)),
+    builtin("term.cp", "(from: str, to: str)", "Copies the file `from` to `to`.", |a, _| call("cp", a)),
+    builtin("term.mv", "(from: str, to: str)", "Moves or renames `from` to `to`.", |a, _| call("mv", a)),
+    builtin("term.echo", "(values: any...) -> str", "Prints the values separated by spaces and returns the line.", |a, _| call("echo", a)),
+    builtin("term.touch", "(path: str)", "Creates an empty file at `path` unless one exists.", |a, _| call("touch", a)),
+    builtin("term.which", "(cmd: str) -> str?", "Full path of `cmd` on PATH, or null.", |a, _| call("which", a)),
+    builtin("term.whoami", "() -> str", "The current user's name.", |a, _| call("whoami", a)),
+    builtin("term.env", "() -> {str: str}", "Every environment variable.", |a, _| call("env", a)),
+    builtin("term.head", "(path: str, n: int?) -> str", "The first `n` (default 10) lines of the file at `path`.", |a, _| call("head", a)),
+    builtin("term.tail", "(path: str, n: int?) -> str", "The last `n` (default 10) lines of the file at `path`.", |a, _| call("tail", a)),
+    builtin("term.wc", "(path: str) -> {str: int}", "`{lines, words, bytes}` counts of the file at `path`.", |a, _| call("wc", a)),
+    builtin("term.grep", "(pattern: str, path: str) -> [str]", "Lines of the file at `path` that contain `pattern`.", |a, _| call("grep", a)),
+];
+
 pub fn call(func_name: &str, args: &[Value]) -> Result<Value, String> {
     match func_name {
         // ── ANSI Terminal Control ──
 
         "clear" => {
-            print!("\x1b[2J\x1b[H");
-            std::io::stdout().flush().ok();
+            super::io::write("\x1b[2J\x1b[H");
+            super::io::flush();
             Ok(Value::Null)
         }
         "set_color" => {
@@ -42,13 +74,13 @@ pub fn call(func_name: &str, args: &[Value]) -> Result<Value, String> {
                 Some(Value::Str(s)) => s.as_str(),
                 _ => "reset",
             };
-            print!("{}", ansi_color(color));
-            std::io::stdout().flush().ok();
+            super::io::write(ansi_color(color));
+            super::io::flush();
             Ok(Value::Null)
         }
         "reset_color" => {
-            print!("\x1b[0m");
-            std::io::stdout().flush().ok();
+            super::io::write("\x1b[0m");
+            super::io::flush();
             Ok(Value::Null)
         }
         "bold" => {
@@ -73,7 +105,7 @@ pub fn call(func_name: &str, args: &[Value]) -> Result<Value, String> {
                 _ => "white".to_string(),
             };
             let text = args.get(1).map(|v| format!("{}", v)).unwrap_or_default();
-            println!("{}{}\x1b[0m", ansi_color(&color), text);
+            super::io::write_line(&format!("{}{}\x1b[0m", ansi_color(&color), text));
             Ok(Value::Null)
         }
         "move_cursor" => {
@@ -85,18 +117,18 @@ pub fn call(func_name: &str, args: &[Value]) -> Result<Value, String> {
                 Some(Value::Int(n)) => *n,
                 _ => 1,
             };
-            print!("\x1b[{};{}H", row, col);
-            std::io::stdout().flush().ok();
+            super::io::write(&format!("\x1b[{};{}H", row, col));
+            super::io::flush();
             Ok(Value::Null)
         }
         "hide_cursor" => {
-            print!("\x1b[?25l");
-            std::io::stdout().flush().ok();
+            super::io::write("\x1b[?25l");
+            super::io::flush();
             Ok(Value::Null)
         }
         "show_cursor" => {
-            print!("\x1b[?25h");
-            std::io::stdout().flush().ok();
+            super::io::write("\x1b[?25h");
+            super::io::flush();
             Ok(Value::Null)
         }
         "size" => {
@@ -201,7 +233,7 @@ pub fn call(func_name: &str, args: &[Value]) -> Result<Value, String> {
         "echo" => {
             let text: Vec<String> = args.iter().map(|v| format!("{}", v)).collect();
             let line = text.join(" ");
-            println!("{}", line);
+            super::io::write_line(&line);
             Ok(Value::Str(line))
         }
         "touch" => {
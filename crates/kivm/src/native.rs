@@ -0,0 +1,185 @@
+//! Native userdata: opaque host objects handed to scripts as `Value::Native`,
+//! with a registered method table (`conn.query(sql)`) and destructor.
+
+use crate::vm::Value;
+use lazy_static::lazy_static;
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+
+/// A method callable on a native object: receives the unwrapped object and
+/// the script arguments (receiver excluded).
+pub type NativeMethod = fn(&mut (dyn Any + Send), &[Value]) -> Result<Value, String>;
+
+/// Typed destructor, run exactly once with the object being released.
+pub type NativeDestructor = fn(Box<dyn Any + Send>);
+
+/// Method table and destructor for one native type. Embedders (and builtin
+/// modules like `db`) register one with `register_type`, then wrap Rust
+/// objects with `wrap`.
+pub struct NativeType {
+    pub name: &'static str,
+    pub methods: HashMap<&'static str, NativeMethod>,
+    pub destructor: Option<NativeDestructor>,
+}
+
+lazy_static! {
+    static ref TYPES: RwLock<HashMap<&'static str, Arc<NativeType>>> = RwLock::new(HashMap::new());
+}
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Registers (or replaces) a native type. Must happen before `wrap` is
+/// called with that type name.
+pub fn register_type(ty: NativeType) {
+    TYPES.write().unwrap().insert(ty.name, Arc::new(ty));
+}
+
+/// The shared state behind a `Value::Native` handle. Cloning the `Value`
+/// clones the `Arc`, so every copy refers to the same object; handles compare
+/// by identity. The object is destroyed when the last handle goes away or on
+/// an explicit `close()`, whichever comes first.
+pub struct NativeObject {
+    ty: Arc<NativeType>,
+    id: u64,
+    inner: Mutex<Option<Box<dyn Any + Send>>>,
+}
+
+impl NativeObject {
+    pub fn type_name(&self) -> &'static str {
+        self.ty.name
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.inner.lock().unwrap().is_none()
+    }
+
+    /// Runs `f` on the wrapped object, if it is still open and of type `T`.
+    pub fn with<T: Any, R>(&self, f: impl FnOnce(&mut T) -> R) -> Result<R, String> {
+        let mut guard = self.inner.lock().unwrap();
+        let obj = guard.as_mut().ok_or_else(|| format!("{} handle #{} is closed", self.ty.name, self.id))?;
+        let obj = obj.downcast_mut::<T>().ok_or_else(|| format!("{} handle #{} has an unexpected payload type", self.ty.name, self.id))?;
+        Ok(f(obj))
+    }
+
+    /// Releases the wrapped object now. Later method calls error; a second
+    /// close is a no-op.
+    pub fn close(&self) {
+        let taken = self.inner.lock().unwrap().take();
+        if let Some(obj) = taken {
+            match self.ty.destructor {
+                Some(destroy) => destroy(obj),
+                None => drop(obj),
+            }
+        }
+    }
+
+    /// Dispatches `method` through the type's method table. `close` is
+    /// available on every native type unless the type defines its own.
+    pub fn call_method(&self, method: &str, args: &[Value]) -> Result<Value, String> {
+        let Some(func) = self.ty.methods.get(method) else {
+            if method == "close" {
+                self.close();
+                return Ok(Value::Null);
+            }
+            return Err(format!("Method '{}' not found on native type '{}'", method, self.ty.name));
+        };
+        let mut guard = self.inner.lock().unwrap();
+        let obj = guard.as_mut().ok_or_else(|| format!("{} handle #{} is closed", self.ty.name, self.id))?;
+        func(obj.as_mut(), args)
+    }
+
+    pub fn has_method(&self, method: &str) -> bool {
+        method == "close" || self.ty.methods.contains_key(method)
+    }
+}
+
+impl Drop for NativeObject {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+impl fmt::Debug for NativeObject {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Native({}#{})", self.ty.name, self.id)
+    }
+}
+
+/// Handles compare by identity: two values are equal only if they refer to
+/// the same wrapped object.
+impl PartialEq for NativeObject {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+/// Wraps `obj` as a script-visible handle of the registered type `type_name`.
+pub fn wrap<T: Any + Send>(type_name: &str, obj: T) -> Result<Value, String> {
+    let ty = TYPES.read().unwrap().get(type_name).cloned()
+        .ok_or_else(|| format!("Native type '{}' is not registered", type_name))?;
+    Ok(Value::Native(Arc::new(NativeObject {
+        ty,
+        id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+        inner: Mutex::new(Some(Box::new(obj))),
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    static DESTROYED: AtomicUsize = AtomicUsize::new(0);
+
+    struct Counter(i64);
+
+    fn bump(obj: &mut (dyn Any + Send), args: &[Value]) -> Result<Value, String> {
+        let c = obj.downcast_mut::<Counter>().ok_or("not a counter")?;
+        c.0 += args.first().map(|v| v.as_int()).transpose()?.unwrap_or(1);
+        Ok(Value::Int(c.0))
+    }
+
+    fn register_counter() {
+        let mut methods: HashMap<&'static str, NativeMethod> = HashMap::new();
+        methods.insert("bump", bump);
+        register_type(NativeType {
+            name: "TestCounter",
+            methods,
+            destructor: Some(|_| { DESTROYED.fetch_add(1, Ordering::SeqCst); }),
+        });
+    }
+
+    #[test]
+    fn methods_dispatch_and_destructor_runs_once() {
+        register_counter();
+        let before = DESTROYED.load(Ordering::SeqCst);
+        let v = wrap("TestCounter", Counter(0)).unwrap();
+        let Value::Native(h) = &v else { panic!("expected a native handle") };
+        assert_eq!(h.call_method("bump", &[Value::Int(5)]).unwrap(), Value::Int(5));
+        assert!(h.call_method("missing", &[]).is_err());
+
+        let copy = v.clone();
+        assert_eq!(copy, v, "clones refer to the same object");
+        drop(v);
+        assert_eq!(DESTROYED.load(Ordering::SeqCst), before, "a live copy keeps the object alive");
+
+        let Value::Native(h) = &copy else { unreachable!() };
+        h.call_method("close", &[]).unwrap();
+        assert_eq!(DESTROYED.load(Ordering::SeqCst), before + 1);
+        assert!(h.call_method("bump", &[]).unwrap_err().contains("closed"));
+        drop(copy);
+        assert_eq!(DESTROYED.load(Ordering::SeqCst), before + 1, "closing twice must not destroy twice");
+    }
+
+    #[test]
+    fn wrapping_unregistered_type_errors() {
+        assert!(wrap("NoSuchType", 1u8).is_err());
+    }
+}
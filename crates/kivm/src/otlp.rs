@@ -0,0 +1,355 @@
+//! OTLP exporter (`otlp` feature): ships the runtime log's spans and metrics to
+//! an OpenTelemetry collector over OTLP/HTTP JSON.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde_json::{json, Value as Json};
+
+use crate::log::{Event, Sink};
+
+/// How often batches are sent.
+pub const INTERVAL: Duration = Duration::from_secs(5);
+/// Spans that trigger a send before `INTERVAL` is up.
+const MAX_BATCH: usize = 512;
+
+struct SpanRecord {
+    trace_id: [u8; 16],
+    span_id: [u8; 8],
+    parent: Option<[u8; 8]>,
+    name: String,
+    /// Where the function starts, 0 if unknown.
+    line: u32,
+    start: u64,
+    end: u64,
+}
+
+enum Message {
+    Span(SpanRecord),
+    Builtin(String),
+    Finished(usize),
+    Stop,
+}
+
+/// Cumulative metrics, as sent.
+#[derive(Default)]
+struct Metrics {
+    /// Calls and total seconds per function.
+    functions: BTreeMap<String, (u64, f64)>,
+    builtins: BTreeMap<String, u64>,
+    heap_allocations: Option<u64>,
+    changed: bool,
+}
+
+/// A span entered and not yet left; `start` in Unix nanos.
+struct OpenSpan {
+    trace_id: [u8; 16],
+    span_id: [u8; 8],
+    line: u32,
+    start: u64,
+}
+
+thread_local! {
+    /// Open spans of this thread, innermost last.
+    static OPEN: RefCell<Vec<OpenSpan>> = const { RefCell::new(Vec::new()) };
+}
+
+pub struct Exporter {
+    sender: Sender<Message>,
+    worker: Mutex<Option<JoinHandle<()>>>,
+}
+
+/// Sends the last batch when dropped.
+pub struct ExportGuard(Arc<Exporter>);
+
+impl Drop for ExportGuard {
+    fn drop(&mut self) {
+        self.0.shutdown();
+    }
+}
+
+/// Sends the runtime log to the collector at `endpoint` (e.g.
+/// `http://localhost:4318`) under `service.name` `service`, from now until
+/// the guard is dropped: each outermost call a trace, with the calls it
+/// makes as child spans, and the cumulative metrics
+/// `kinetix.function.calls`, `kinetix.function.duration`,
+/// `kinetix.builtin.calls` and `kinetix.vm.heap_allocations`. Batches go out
+/// every `INTERVAL` from a background thread, so a program never waits on
+/// the collector; one it refuses is dropped, the first failure reported on
+/// stderr.
+pub fn export_to(endpoint: &str, service: &str) -> ExportGuard {
+    let exporter = Arc::new(Exporter::start(endpoint, service, INTERVAL));
+    crate::log::set_sink(exporter.clone());
+    ExportGuard(exporter)
+}
+
+impl Exporter {
+    pub fn start(endpoint: &str, service: &str, interval: Duration) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let endpoint = endpoint.trim_end_matches('/').to_string();
+        let resource = json!({ "attributes": [
+            attribute("service.name", service),
+            attribute("telemetry.sdk.name", "kinetix"),
+            attribute("telemetry.sdk.version", env!("CARGO_PKG_VERSION")),
+        ] });
+        let worker = std::thread::spawn(move || export(receiver, &endpoint, &resource, interval));
+        Exporter { sender, worker: Mutex::new(Some(worker)) }
+    }
+
+    /// Sends what is left and stops; later events are dropped.
+    pub fn shutdown(&self) {
+        let _ = self.sender.send(Message::Stop);
+        if let Some(worker) = self.worker.lock().unwrap_or_else(|e| e.into_inner()).take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl Sink for Exporter {
+    fn record(&self, event: &Event) {
+        let message = match *event {
+            Event::Enter { line, .. } => {
+                OPEN.with(|open| {
+                    let mut open = open.borrow_mut();
+                    let trace_id = open.last().map_or_else(rand::random, |outer| outer.trace_id);
+                    open.push(OpenSpan { trace_id, span_id: rand::random(), line, start: unix_nanos() });
+                });
+                return;
+            }
+            Event::Exit { name, duration, .. } => {
+                let Some(span) = OPEN.with(|open| {
+                    let mut open = open.borrow_mut();
+                    let OpenSpan { trace_id, span_id, line, start } = open.pop()?;
+                    let parent = open.last().map(|outer| outer.span_id);
+                    let end = start + duration.as_nanos() as u64;
+                    Some(SpanRecord { trace_id, span_id, parent, name: name.to_string(), line, start, end })
+                }) else { return };
+                Message::Span(span)
+            }
+            Event::Builtin { name } if !name.starts_with("log.") => Message::Builtin(name.to_string()),
+            Event::Builtin { .. } => return,
+            Event::Finished { heap_allocations } => Message::Finished(heap_allocations),
+        };
+        let _ = self.sender.send(message);
+    }
+}
+
+/// The background thread: collects messages and sends them in batches.
+fn export(receiver: Receiver<Message>, endpoint: &str, resource: &Json, interval: Duration) {
+    let started = unix_nanos();
+    let mut spans = Vec::new();
+    let mut metrics = Metrics::default();
+    let mut warned = false;
+    let mut deadline = Instant::now() + interval;
+    loop {
+        let stop = match receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+            Ok(Message::Stop) | Err(RecvTimeoutError::Disconnected) => true,
+            Ok(message) => {
+                metrics.add(&message);
+                if let Message::Span(span) = message {
+                    spans.push(span);
+                }
+                if spans.len() < MAX_BATCH {
+                    continue;
+                }
+                false
+            }
+            Err(RecvTimeoutError::Timeout) => false,
+        };
+        let mut sent = Ok(());
+        if !spans.is_empty() {
+            sent = post(&format!("{}/v1/traces", endpoint), &traces_body(resource, &spans));
+            spans.clear();
+        }
+        if metrics.changed {
+            sent = sent.and(post(&format!("{}/v1/metrics", endpoint), &metrics_body(resource, &metrics, started, unix_nanos())));
+            metrics.changed = false;
+        }
+        if let Err(e) = sent && !warned {
+            eprintln!("[otlp] could not export to {}: {} (further failures are not reported)", endpoint, e);
+            warned = true;
+        }
+        if stop {
+            return;
+        }
+        deadline = Instant::now() + interval;
+    }
+}
+
+fn post(url: &str, body: &Json) -> Result<(), String> {
+    ureq::post(url)
+        .timeout(Duration::from_secs(10))
+        .send_json(body)
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+impl Metrics {
+    fn add(&mut self, message: &Message) {
+        match message {
+            Message::Span(span) => {
+                let (calls, seconds) = self.functions.entry(span.name.clone()).or_default();
+                *calls += 1;
+                *seconds += (span.end - span.start) as f64 / 1e9;
+            }
+            Message::Builtin(name) => *self.builtins.entry(name.clone()).or_default() += 1,
+            Message::Finished(allocations) => self.heap_allocations = Some(*allocations as u64),
+            Message::Stop => return,
+        }
+        self.changed = true;
+    }
+}
+
+fn traces_body(resource: &Json, spans: &[SpanRecord]) -> Json {
+    let spans: Vec<Json> = spans.iter().map(|span| {
+        let mut json = json!({
+            "traceId": hex::encode(span.trace_id),
+            "spanId": hex::encode(span.span_id),
+            "name": span.name,
+            // SPAN_KIND_INTERNAL
+            "kind": 1,
+            "startTimeUnixNano": span.start.to_string(),
+            "endTimeUnixNano": span.end.to_string(),
+        });
+        if let Some(parent) = span.parent {
+            json["parentSpanId"] = json!(hex::encode(parent));
+        }
+        if span.line > 0 {
+            json["attributes"] = json!([{ "key": "code.lineno", "value": { "intValue": span.line.to_string() } }]);
+        }
+        json
+    }).collect();
+    json!({ "resourceSpans": [{ "resource": resource, "scopeSpans": [{ "scope": scope(), "spans": spans }] }] })
+}
+
+fn metrics_body(resource: &Json, metrics: &Metrics, start: u64, now: u64) -> Json {
+    let point = |key: &str, name: &str, value: Json| {
+        let mut point = json!({
+            "attributes": [attribute(key, name)],
+            "startTimeUnixNano": start.to_string(),
+            "timeUnixNano": now.to_string(),
+        });
+        let kind = if value.is_f64() { "asDouble" } else { "asInt" };
+        point[kind] = value;
+        point
+    };
+    // AGGREGATION_TEMPORALITY_CUMULATIVE
+    let sum = |name: &str, unit: &str, points: Vec<Json>| json!({
+        "name": name, "unit": unit,
+        "sum": { "aggregationTemporality": 2, "isMonotonic": true, "dataPoints": points },
+    });
+    let mut list = vec![
+        sum("kinetix.function.calls", "{call}",
+            metrics.functions.iter().map(|(name, (calls, _))| point("function", name, json!(calls.to_string()))).collect()),
+        sum("kinetix.function.duration", "s",
+            metrics.functions.iter().map(|(name, (_, seconds))| point("function", name, json!(*seconds))).collect()),
+        sum("kinetix.builtin.calls", "{call}",
+            metrics.builtins.iter().map(|(name, calls)| point("builtin", name, json!(calls.to_string()))).collect()),
+    ];
+    if let Some(allocations) = metrics.heap_allocations {
+        list.push(json!({
+            "name": "kinetix.vm.heap_allocations", "unit": "{allocation}",
+            "gauge": { "dataPoints": [{ "timeUnixNano": now.to_string(), "asInt": allocations.to_string() }] },
+        }));
+    }
+    list.retain(|metric| metric["sum"]["dataPoints"].as_array().is_none_or(|points| !points.is_empty()));
+    json!({ "resourceMetrics": [{ "resource": resource, "scopeMetrics": [{ "scope": scope(), "metrics": list }] }] })
+}
+
+fn scope() -> Json {
+    json!({ "name": "kinetix.kivm", "version": env!("CARGO_PKG_VERSION") })
+}
+
+fn attribute(key: &str, value: &str) -> Json {
+    json!({ "key": key, "value": { "stringValue": value } })
+}
+
+fn unix_nanos() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    /// Answers `expected` POSTs, returning `(path, body)` of each.
+    fn collector(listener: TcpListener, expected: usize) -> std::thread::JoinHandle<Vec<(String, Json)>> {
+        std::thread::spawn(move || {
+            let mut requests = Vec::new();
+            while requests.len() < expected {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                while requests.len() < expected {
+                    let mut request_line = String::new();
+                    if reader.read_line(&mut request_line).unwrap_or(0) == 0 {
+                        break;
+                    }
+                    let mut length = 0;
+                    loop {
+                        let mut header = String::new();
+                        reader.read_line(&mut header).unwrap();
+                        if header.trim().is_empty() {
+                            break;
+                        }
+                        if let Some((name, value)) = header.split_once(':') && name.eq_ignore_ascii_case("content-length") {
+                            length = value.trim().parse().unwrap();
+                        }
+                    }
+                    let mut body = vec![0; length];
+                    reader.read_exact(&mut body).unwrap();
+                    let path = request_line.split_whitespace().nth(1).unwrap().to_string();
+                    requests.push((path, serde_json::from_slice(&body).unwrap()));
+                    stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n").unwrap();
+                }
+            }
+            requests
+        })
+    }
+
+    #[test]
+    fn spans_and_metrics_reach_the_collector() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let served = collector(listener, 2);
+
+        let exporter = Exporter::start(&endpoint, "orders", Duration::from_secs(60));
+        exporter.record(&Event::Enter { name: "handle", depth: 0, line: 3 });
+        exporter.record(&Event::Enter { name: "load", depth: 1, line: 0 });
+        exporter.record(&Event::Builtin { name: "log.span_enter" });
+        exporter.record(&Event::Builtin { name: "db.query" });
+        exporter.record(&Event::Exit { name: "load", depth: 1, duration: Duration::from_millis(2) });
+        exporter.record(&Event::Exit { name: "handle", depth: 0, duration: Duration::from_millis(5) });
+        exporter.record(&Event::Finished { heap_allocations: 42 });
+        exporter.shutdown();
+        exporter.record(&Event::Builtin { name: "dropped" });
+
+        let requests = served.join().unwrap();
+        let paths: Vec<&str> = requests.iter().map(|(path, _)| path.as_str()).collect();
+        assert_eq!(paths, ["/v1/traces", "/v1/metrics"]);
+
+        let traces = &requests[0].1["resourceSpans"][0];
+        assert_eq!(traces["resource"]["attributes"][0], attribute("service.name", "orders"));
+        let spans = traces["scopeSpans"][0]["spans"].as_array().unwrap();
+        let (load, handle) = (&spans[0], &spans[1]);
+        assert_eq!((load["name"].as_str(), handle["name"].as_str()), (Some("load"), Some("handle")));
+        assert_eq!(load["traceId"], handle["traceId"]);
+        assert_eq!(load["parentSpanId"], handle["spanId"]);
+        assert!(handle.get("parentSpanId").is_none());
+        assert_eq!(handle["attributes"][0]["value"]["intValue"], "3");
+        assert!(load.get("attributes").is_none(), "no line, no code.lineno");
+
+        let metrics = requests[1].1["resourceMetrics"][0]["scopeMetrics"][0]["metrics"].as_array().unwrap();
+        let names: Vec<&str> = metrics.iter().map(|m| m["name"].as_str().unwrap()).collect();
+        assert_eq!(names, ["kinetix.function.calls", "kinetix.function.duration", "kinetix.builtin.calls", "kinetix.vm.heap_allocations"]);
+        let builtins = metrics[2]["sum"]["dataPoints"].as_array().unwrap();
+        assert_eq!(builtins.len(), 1);
+        assert_eq!((&builtins[0]["attributes"][0], &builtins[0]["asInt"]), (&attribute("builtin", "db.query"), &json!("1")));
+        assert_eq!(metrics[3]["gauge"]["dataPoints"][0]["asInt"], "42");
+    }
+}
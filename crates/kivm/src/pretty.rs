@@ -0,0 +1,183 @@
+//! Value pretty-printing for `print`, `inspect` and the shell, within nesting
+//! and width limits.
+
+use crate::vm::Value;
+use std::sync::RwLock;
+
+/// How much of a value to render.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limits {
+    /// Containers nested deeper than this render as `[...]` / `{...}`.
+    pub depth: usize,
+    /// Items shown per array or map before the rest is summarized.
+    pub width: usize,
+}
+
+impl Limits {
+    /// `print`'s default: generous enough that ordinary output is unchanged.
+    pub const PRINT: Limits = Limits { depth: 32, width: 1000 };
+    /// The interactive shell, where a screenful is the useful amount.
+    pub const SHELL: Limits = Limits { depth: 6, width: 100 };
+    /// `inspect` without explicit limits.
+    pub const INSPECT: Limits = Limits { depth: 5, width: 100 };
+}
+
+static PRINT_LIMITS: RwLock<Limits> = RwLock::new(Limits::PRINT);
+
+/// Sets the limits `print` uses from now on (the shell tightens them).
+pub fn set_print_limits(limits: Limits) {
+    *PRINT_LIMITS.write().unwrap_or_else(|e| e.into_inner()) = limits;
+}
+
+pub fn print_limits() -> Limits {
+    *PRINT_LIMITS.read().unwrap_or_else(|e| e.into_inner())
+}
+
+/// `value` as `print` shows it: strings bare, containers within `limits`
+/// and map keys sorted, so a huge value prints as a readable summary
+/// (`... 9000 more`) instead of megabytes of text.
+pub fn render(value: &Value, limits: Limits) -> String {
+    Printer { limits, quote: false, out: String::new(), path: Vec::new() }.finish(value)
+}
+
+/// `value` as `inspect` shows it: like `render`, with strings quoted and
+/// user types named the way `typeof` names them (`Point {x: 1, y: 2}`,
+/// `Circle(2.5)`), without the hidden `__class__`/`__enum__` tags.
+pub fn inspect(value: &Value, limits: Limits) -> String {
+    Printer { limits, quote: true, out: String::new(), path: Vec::new() }.finish(value)
+}
+
+struct Printer {
+    limits: Limits,
+    quote: bool,
+    out: String,
+    /// Containers currently being printed, by address, so one that contains
+    /// itself prints as `<cycle>`. Values are copied today, so this only
+    /// guards host structures that share storage.
+    path: Vec<*const ()>,
+}
+
+impl Printer {
+    fn finish(mut self, value: &Value) -> String {
+        self.value(value, 0);
+        self.out
+    }
+
+    fn value(&mut self, value: &Value, depth: usize) {
+        match value {
+            Value::Str(s) if self.quote => self.out.push_str(&format!("{:?}", s)),
+            Value::Array(items) => {
+                let Some(depth) = self.enter(items as *const _ as *const (), depth, "[...]") else { return };
+                self.out.push('[');
+                self.items(items.iter().map(|v| (None, v)), items.len(), depth);
+                self.out.push(']');
+                self.path.pop();
+            }
+            Value::Map(map) if self.quote && map.contains_key("__enum__") => {
+                self.out.push_str(match map.get("__variant__") {
+                    Some(Value::Str(variant)) => variant,
+                    _ => "?",
+                });
+                if let Some(payload) = map.get("__payload__").filter(|p| !matches!(p, Value::Null)) {
+                    let Some(depth) = self.enter(map as *const _ as *const (), depth, "(...)") else { return };
+                    self.out.push('(');
+                    self.value(payload, depth);
+                    self.out.push(')');
+                    self.path.pop();
+                }
+            }
+            Value::Map(map) => {
+                let Some(depth) = self.enter(map as *const _ as *const (), depth, "{...}") else { return };
+                let mut entries: Vec<_> = map.iter().collect();
+                if let Some(name) = crate::builtins::declared_type_name(map).filter(|_| self.quote) {
+                    self.out.push_str(name);
+                    self.out.push(' ');
+                    entries.retain(|(k, _)| !crate::builtins::is_hidden_key(k));
+                }
+                let len = entries.len();
+                entries.sort_by(|a, b| a.0.cmp(b.0));
+                self.out.push('{');
+                self.items(entries.into_iter().map(|(k, v)| (Some(k.as_str()), v)), len, depth);
+                self.out.push('}');
+                self.path.pop();
+            }
+            other => self.out.push_str(&other.to_string()),
+        }
+    }
+
+    /// Pushes a container onto the path, or writes its placeholder and
+    /// returns `None` when it is a cycle or too deep.
+    fn enter(&mut self, addr: *const (), depth: usize, elided: &str) -> Option<usize> {
+        if self.path.contains(&addr) {
+            self.out.push_str("<cycle>");
+            return None;
+        }
+        if depth >= self.limits.depth {
+            self.out.push_str(elided);
+            return None;
+        }
+        self.path.push(addr);
+        Some(depth + 1)
+    }
+
+    fn items<'v>(&mut self, items: impl Iterator<Item = (Option<&'v str>, &'v Value)>, len: usize, depth: usize) {
+        for (i, (key, value)) in items.take(self.limits.width).enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            if let Some(key) = key {
+                self.out.push_str(key);
+                self.out.push_str(": ");
+            }
+            self.value(value, depth);
+        }
+        if len > self.limits.width {
+            self.out.push_str(&format!("{}... {} more", if self.limits.width > 0 { ", " } else { "" }, len - self.limits.width));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ints(n: i64) -> Value {
+        Value::Array((0..n).map(Value::Int).collect())
+    }
+
+    #[test]
+    fn render_matches_display_within_limits() {
+        let v = Value::Array(vec![Value::Int(1), Value::Str("a".into()), Value::Float(2.0), ints(2)]);
+        assert_eq!(render(&v, Limits::PRINT), v.to_string());
+        assert_eq!(render(&Value::Str("x".into()), Limits::PRINT), "x");
+        assert_eq!(inspect(&v, Limits::INSPECT), "[1, \"a\", 2.0, [0, 1]]");
+    }
+
+    #[test]
+    fn depth_and_width_are_elided() {
+        let nested = Value::Array(vec![Value::Array(vec![Value::Array(vec![Value::Int(1)])])]);
+        assert_eq!(render(&nested, Limits { depth: 2, width: 10 }), "[[[...]]]");
+        assert_eq!(render(&ints(5), Limits { depth: 2, width: 3 }), "[0, 1, 2, ... 2 more]");
+        assert_eq!(render(&ints(5), Limits { depth: 2, width: 0 }), "[... 5 more]");
+
+        let map = Value::Map([("b".to_string(), Value::Int(2)), ("a".to_string(), Value::Int(1))].into_iter().collect());
+        assert_eq!(render(&map, Limits::PRINT), "{a: 1, b: 2}");
+    }
+
+    #[test]
+    fn inspect_names_user_types() {
+        let point = Value::Map([
+            ("__class__".to_string(), Value::Str("Point".into())),
+            ("x".to_string(), Value::Int(1)),
+            ("label".to_string(), Value::Str("p".into())),
+        ].into_iter().collect());
+        assert_eq!(inspect(&point, Limits::INSPECT), "Point {label: \"p\", x: 1}");
+
+        let circle = Value::Map([
+            ("__enum__".to_string(), Value::Str("Shape".into())),
+            ("__variant__".to_string(), Value::Str("Circle".into())),
+            ("__payload__".to_string(), Value::Float(2.5)),
+        ].into_iter().collect());
+        assert_eq!(inspect(&Value::Array(vec![circle]), Limits::INSPECT), "[Circle(2.5)]");
+    }
+}
@@ -0,0 +1,187 @@
+//! Resource quotas for sandboxed runs (`kivm exec --sandbox --limit ...`),
+//! shared by every thread of the process.
+
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Resource {
+    /// Bytes sent and received by `net.*` and `ipc` sockets.
+    NetBytes,
+    /// Bytes written to files by `data.*`, `csv.*`, `archive.*`,
+    /// `fs.write_atomic` and `net.http.download`.
+    FsWriteBytes,
+    /// Programs started with `system.exec` and `ipc.spawn`.
+    SpawnedProcs,
+}
+
+impl Resource {
+    pub const ALL: [Resource; 3] = [Resource::NetBytes, Resource::FsWriteBytes, Resource::SpawnedProcs];
+
+    /// The name used by `--limit`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Resource::NetBytes => "net-bytes",
+            Resource::FsWriteBytes => "fs-write-bytes",
+            Resource::SpawnedProcs => "spawned-procs",
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+const UNLIMITED: u64 = u64::MAX;
+
+/// Limits and usage per resource.
+struct Quotas {
+    limits: [AtomicU64; 3],
+    used: [AtomicU64; 3],
+}
+
+/// The quotas of this process.
+static QUOTAS: Quotas = Quotas::new();
+
+impl Quotas {
+    const fn new() -> Self {
+        Quotas {
+            limits: [AtomicU64::new(UNLIMITED), AtomicU64::new(UNLIMITED), AtomicU64::new(UNLIMITED)],
+            used: [AtomicU64::new(0), AtomicU64::new(0), AtomicU64::new(0)],
+        }
+    }
+
+    fn charge(&self, resource: Resource, amount: u64) -> Result<(), String> {
+        let limit = self.limits[resource.index()].load(Ordering::Relaxed);
+        self.used[resource.index()]
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| used.checked_add(amount).filter(|total| *total <= limit))
+            .map(|_| ())
+            .map_err(|used| exceeded(resource, limit, used))
+    }
+
+    fn refund(&self, resource: Resource, amount: u64) {
+        self.used[resource.index()].fetch_sub(amount, Ordering::Relaxed);
+    }
+}
+
+/// Parses one `--limit` entry, `NAME=AMOUNT`. Byte amounts take a `B`, `KB`,
+/// `MB` or `GB` suffix (powers of 1024); `spawned-procs` is a plain count.
+pub fn parse_limit(spec: &str) -> Result<(Resource, u64), String> {
+    let (name, amount) = spec.split_once('=').ok_or_else(|| format!("expected NAME=AMOUNT, got '{}'", spec))?;
+    let resource = Resource::ALL.into_iter().find(|r| r.name() == name.trim())
+        .ok_or_else(|| format!("unknown limit '{}' (expected net-bytes, fs-write-bytes or spawned-procs)", name.trim()))?;
+    let amount = amount.trim();
+    let digits = amount.find(|c: char| !c.is_ascii_digit()).unwrap_or(amount.len());
+    let (number, unit) = amount.split_at(digits);
+    let multiplier: u64 = match (resource, unit.trim().to_ascii_uppercase().as_str()) {
+        (_, "") => 1,
+        (Resource::SpawnedProcs, _) => return Err(format!("spawned-procs is a count, got '{}'", amount)),
+        (_, "B") => 1,
+        (_, "KB" | "K") => 1 << 10,
+        (_, "MB" | "M") => 1 << 20,
+        (_, "GB" | "G") => 1 << 30,
+        (_, other) => return Err(format!("unknown size unit '{}' in '{}'", other, spec)),
+    };
+    let number: u64 = number.parse().map_err(|_| format!("expected an amount in '{}'", spec))?;
+    let max = number.checked_mul(multiplier).ok_or_else(|| format!("'{}' is too large", amount))?;
+    Ok((resource, max))
+}
+
+/// Caps `resource` at `max` for the rest of the process; the CLI calls this
+/// once per `--limit` entry.
+pub fn set_limit(resource: Resource, max: u64) {
+    QUOTAS.limits[resource.index()].store(max, Ordering::Relaxed);
+}
+
+/// Counts `amount` against the quota of `resource`, or fails without
+/// counting it if that would exceed the limit. Callers fail the builtin the
+/// way any other I/O error does and transfer nothing.
+pub fn charge(resource: Resource, amount: u64) -> Result<(), String> {
+    QUOTAS.charge(resource, amount)
+}
+
+fn exceeded(resource: Resource, limit: u64, used: u64) -> String {
+    match resource {
+        Resource::SpawnedProcs => format!("quota exceeded: spawned-procs is limited to {}", limit),
+        _ => format!("quota exceeded: {} is limited to {} bytes ({} used)", resource.name(), limit, used),
+    }
+}
+
+/// A reader or writer whose traffic is charged to a byte quota; going over
+/// it is an I/O error carrying the quota message.
+pub struct Metered<T> {
+    inner: T,
+    resource: Resource,
+    quotas: &'static Quotas,
+}
+
+impl<T> Metered<T> {
+    pub fn new(inner: T, resource: Resource) -> Self {
+        Metered { inner, resource, quotas: &QUOTAS }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+fn quota_error(message: String) -> io::Error {
+    io::Error::other(message)
+}
+
+impl<W: Write> Write for Metered<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.quotas.charge(self.resource, buf.len() as u64).map_err(quota_error)?;
+        let written = self.inner.write(buf);
+        // Give back what the writer didn't take; it is charged again when retried
+        self.quotas.refund(self.resource, (buf.len() - *written.as_ref().unwrap_or(&0)) as u64);
+        written
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Seeking is free; bytes written again after seeking back are charged again.
+impl<T: Seek> Seek for Metered<T> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+impl<R: Read> Read for Metered<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.quotas.charge(self.resource, read as u64).map_err(quota_error)?;
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits_parse_and_charges_stop_at_the_limit() {
+        assert_eq!(parse_limit("net-bytes=10MB"), Ok((Resource::NetBytes, 10 << 20)));
+        assert_eq!(parse_limit("fs-write-bytes = 1kb"), Ok((Resource::FsWriteBytes, 1024)));
+        assert_eq!(parse_limit("spawned-procs=0"), Ok((Resource::SpawnedProcs, 0)));
+        assert!(parse_limit("spawned-procs=2MB").unwrap_err().contains("is a count"));
+        assert!(parse_limit("cpu=1").unwrap_err().starts_with("unknown limit 'cpu'"));
+        assert!(parse_limit("net-bytes=10XB").unwrap_err().starts_with("unknown size unit 'XB'"));
+        assert!(parse_limit("net-bytes").is_err());
+
+        // A quota of its own, so the process-wide one stays unlimited for other tests
+        static QUOTAS: Quotas = Quotas::new();
+        QUOTAS.limits[Resource::FsWriteBytes.index()].store(10, Ordering::Relaxed);
+        let mut sink = Metered { inner: Vec::new(), resource: Resource::FsWriteBytes, quotas: &QUOTAS };
+        sink.write_all(b"12345678").unwrap();
+        let error = sink.write_all(b"abc").unwrap_err();
+        assert_eq!(error.to_string(), "quota exceeded: fs-write-bytes is limited to 10 bytes (8 used)");
+        sink.write_all(b"90").unwrap();
+        assert_eq!(sink.into_inner(), b"1234567890");
+        assert!(QUOTAS.charge(Resource::FsWriteBytes, 1).is_err());
+        assert!(QUOTAS.charge(Resource::NetBytes, u64::MAX).is_ok());
+    }
+}
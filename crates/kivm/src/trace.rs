@@ -0,0 +1,115 @@
+//! Instruction tracing (`trace` feature): `vm.set_tracer(callback)` reports each
+//! executed instruction with the registers it changed.
+
+use crate::vm::Value;
+use kinetix_kicomp::ir::Instruction;
+
+/// One executed instruction.
+#[derive(Debug, Clone)]
+pub struct TraceEvent {
+    /// Frames on the call stack while it ran, 1 for the outermost.
+    pub depth: usize,
+    pub function: String,
+    pub ip: usize,
+    /// Source line, 0 when neither the program nor its `.kisym` (see
+    /// `VM::set_symbols_path`) has a line map.
+    pub line: u32,
+    pub instruction: Instruction,
+    /// Registers of the executing frame it wrote. A call's result lands in
+    /// the caller's register when the callee returns, so it shows up on the
+    /// callee's `Return`, not on the `Call`.
+    pub changes: Vec<RegisterChange>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RegisterChange {
+    pub register: u16,
+    /// The local declared in the register, from the program or its `.kisym`.
+    pub name: Option<String>,
+    pub before: Value,
+    pub after: Value,
+}
+
+pub(crate) type Tracer = Box<dyn FnMut(&TraceEvent) + Send>;
+
+/// The registers that differ between `before` and `after`; a register one
+/// side doesn't have reads as null.
+pub(crate) fn diff(before: &[Value], after: &[Value]) -> Vec<RegisterChange> {
+    let null = Value::Null;
+    (0..before.len().max(after.len()))
+        .filter_map(|i| {
+            let (old, new) = (before.get(i).unwrap_or(&null), after.get(i).unwrap_or(&null));
+            (!same(old, new)).then(|| RegisterChange { register: i as u16, name: None, before: old.clone(), after: new.clone() })
+        })
+        .collect()
+}
+
+/// Identity rather than `==`: `1` becoming `1.0` is a change, a NaN staying
+/// NaN is not.
+fn same(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Float(x), Value::Float(y)) => x.to_bits() == y.to_bits(),
+        _ => std::mem::discriminant(a) == std::mem::discriminant(b) && a == b,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TraceEvent;
+    use crate::vm::{VM, Value};
+    use bumpalo::Bump;
+    use kinetix_kicomp::compiler::Compiler;
+    use kinetix_kicomp::ir::{CompiledProgram, Opcode};
+    use kinetix_kicomp::kisym::DebugSymbols;
+    use std::path::Path;
+    use kinetix_language::lexer::Lexer;
+    use kinetix_language::parser::Parser;
+    use std::sync::{Arc, Mutex};
+
+    /// Runs `program` traced, with its `.kisym` at `symbols` if given.
+    fn trace(program: CompiledProgram, symbols: Option<&Path>) -> Vec<TraceEvent> {
+        let mut vm = VM::new(program);
+        if let Some(path) = symbols {
+            vm.set_symbols_path(path.to_path_buf());
+        }
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        vm.set_tracer(move |event| sink.lock().unwrap().push(event.clone()));
+        vm.run().unwrap();
+        let events = events.lock().unwrap().clone();
+        events
+    }
+
+    fn compile(source: &str) -> CompiledProgram {
+        let arena = Bump::new();
+        let program = Parser::new(Lexer::new(source), &arena).parse_program();
+        Compiler::new().compile(&program.statements, None).unwrap().clone()
+    }
+
+    const SOURCE: &str = "fn twice_plus_one(a: int) -> int {\n    let b = a * 2\n    return b + 1\n}\nprintln(twice_plus_one(20))\n";
+
+    #[test]
+    fn every_instruction_is_reported_with_the_registers_it_wrote() {
+        let events = trace(compile(SOURCE), None);
+        let mul = events.iter().find(|e| e.instruction.opcode == Opcode::Mul).expect("the multiplication is traced");
+        assert_eq!((mul.function.as_str(), mul.depth, mul.line), ("twice_plus_one", 2, 2));
+        assert!(mul.changes.iter().any(|c| matches!(c.after, Value::Int(40))), "{:?}", mul.changes);
+        assert!(events.iter().any(|e| e.function == "<main>" && e.depth == 1), "main runs at depth 1");
+        assert!(events.iter().all(|e| e.instruction.opcode != Opcode::Return || e.depth == 2));
+        assert!(events.iter().filter(|e| e.instruction.opcode == Opcode::Nop).all(|e| e.changes.is_empty()));
+    }
+
+    #[test]
+    fn a_stripped_program_is_traced_with_its_kisym() {
+        let mut program = compile(SOURCE);
+        let path = std::env::temp_dir().join(format!("kinetix_trace_{}.kisym", std::process::id()));
+        DebugSymbols::split(&mut program, Path::new("twice.kix")).write(&path).unwrap();
+        let events = trace(program, Some(&path));
+        std::fs::remove_file(&path).unwrap();
+
+        let mul = events.iter().find(|e| e.instruction.opcode == Opcode::Mul).expect("the multiplication is traced");
+        assert_eq!(mul.line, 2);
+        let b: Vec<&Value> = events.iter().flat_map(|e| &e.changes).filter(|c| c.name.as_deref() == Some("b")).map(|c| &c.after).collect();
+        assert!(matches!(b.last(), Some(Value::Int(40))), "{:?}", b);
+    }
+}
@@ -1,12 +1,15 @@
 /// KiVM Virtual Machine: register-based bytecode interpreter.
 
 use kinetix_kicomp::ir::*;
+use kinetix_kicomp::kisym::{DebugSymbols, FunctionSymbols};
 use crate::builtins;
+use crate::watchdog::{Heartbeat, VmHandle, Watchdog};
 use std::collections::HashMap;
 use std::fmt;
+use std::sync::Arc;
 
 /// Runtime value in the VM.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub enum Value {
     Int(i64),
     Float(f64),
@@ -19,8 +22,43 @@ pub enum Value {
     NativeModule(String),
     BoundMethod(Box<Value>, Box<Value>),
     Map(HashMap<String, Value>),
+    /// Opaque host object (DB connection, socket, ...); see `native.rs`.
+    Native(std::sync::Arc<crate::native::NativeObject>),
 }
 
+/// `==`/`!=` semantics:
+/// - numbers compare by value, across `int` and `float` (`1 == 1.0`);
+///   NaN is unequal to everything, itself included (IEEE 754);
+/// - strings, bools and `null` compare by value;
+/// - arrays and maps compare deeply, element by element / key by key;
+/// - functions are equal when they are the same function, bound methods when
+///   both receiver and method are; native handles when they wrap the same
+///   object (see `NativeObject`);
+/// - values of different kinds are never equal.
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Int(a), Value::Int(b)) => a == b,
+            (Value::Float(a), Value::Float(b)) => a == b,
+            (Value::Int(a), Value::Float(b)) | (Value::Float(b), Value::Int(a)) => (*a as f64) == *b,
+            (Value::Str(a), Value::Str(b)) => a == b,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Null, Value::Null) => true,
+            (Value::Array(a), Value::Array(b)) => a == b,
+            (Value::Map(a), Value::Map(b)) => a == b,
+            (Value::Function(a), Value::Function(b)) => a == b,
+            (Value::NativeFn(a), Value::NativeFn(b)) => a == b,
+            (Value::NativeModule(a), Value::NativeModule(b)) => a == b,
+            (Value::BoundMethod(ra, ma), Value::BoundMethod(rb, mb)) => ra == rb && ma == mb,
+            (Value::Native(a), Value::Native(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+/// `<`, `>`, `<=`, `>=`: numbers (mixing `int` and `float`), strings
+/// (byte-wise), bools and arrays (lexicographic) are ordered; anything else,
+/// and any comparison involving NaN, is unordered and yields `false`.
 impl PartialOrd for Value {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
         match (self, other) {
@@ -39,6 +77,68 @@ impl PartialOrd for Value {
 }
 
 impl Value {
+    /// Total order used by `sort`, `min` and `max`, so a NaN or a mixed
+    /// array still sorts deterministically. Kinds order as null < bool <
+    /// number < string < array < map < everything else; numbers by value
+    /// with NaN after every other number; arrays lexicographically; values
+    /// with no natural order (maps, functions, handles) compare equal within
+    /// their kind, so the stable sort keeps their original order.
+    pub fn sort_cmp(&self, other: &Self) -> std::cmp::Ordering {
+        use std::cmp::Ordering;
+        fn rank(v: &Value) -> u8 {
+            match v {
+                Value::Null => 0,
+                Value::Bool(_) => 1,
+                Value::Int(_) | Value::Float(_) => 2,
+                Value::Str(_) => 3,
+                Value::Array(_) => 4,
+                Value::Map(_) => 5,
+                _ => 6,
+            }
+        }
+        match (self, other) {
+            (Value::Int(a), Value::Int(b)) => a.cmp(b),
+            (Value::Int(a), Value::Float(b)) => int_float_cmp(*a, *b),
+            (Value::Float(a), Value::Int(b)) => int_float_cmp(*b, *a).reverse(),
+            (Value::Float(a), Value::Float(b)) => match (a.is_nan(), b.is_nan()) {
+                (true, true) => Ordering::Equal,
+                (true, false) => Ordering::Greater,
+                (false, true) => Ordering::Less,
+                (false, false) => a.partial_cmp(b).unwrap_or(Ordering::Equal),
+            },
+            (Value::Str(a), Value::Str(b)) => a.cmp(b),
+            (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+            (Value::Array(a), Value::Array(b)) => {
+                for (x, y) in a.iter().zip(b) {
+                    match x.sort_cmp(y) {
+                        Ordering::Equal => {}
+                        unequal => return unequal,
+                    }
+                }
+                a.len().cmp(&b.len())
+            }
+            _ => rank(self).cmp(&rank(other)),
+        }
+    }
+
+    /// Identity, as tested by `is(a, b)`. Native handles are identical when
+    /// they wrap the same object. Everything else is a plain value in KiVM
+    /// (assignment copies arrays and maps), so identity is "same value"
+    /// without `==`'s leniencies: no `int`/`float` mixing, NaN is NaN, and
+    /// `0.0` is not `-0.0`.
+    pub fn is_same(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Float(a), Value::Float(b)) => a.to_bits() == b.to_bits() || (a.is_nan() && b.is_nan()),
+            (Value::Int(_), Value::Float(_)) | (Value::Float(_), Value::Int(_)) => false,
+            (Value::Array(a), Value::Array(b)) => a.len() == b.len() && a.iter().zip(b).all(|(x, y)| x.is_same(y)),
+            (Value::Map(a), Value::Map(b)) => {
+                a.len() == b.len() && a.iter().all(|(k, x)| b.get(k).is_some_and(|y| x.is_same(y)))
+            }
+            (Value::BoundMethod(ra, ma), Value::BoundMethod(rb, mb)) => ra.is_same(rb) && ma.is_same(mb),
+            _ => self == other,
+        }
+    }
+
     pub fn is_truthy(&self) -> bool {
         match self {
             Value::Bool(b) => *b,
@@ -69,11 +169,37 @@ impl Value {
     }
 }
 
+/// `int` against `float` exactly, without rounding the int to a float (which
+/// would make `2^53` and `2^53 + 1` both equal `2^53 as f64`): NaN is after
+/// every int, then the float's integer part decides, then its fraction.
+fn int_float_cmp(int: i64, float: f64) -> std::cmp::Ordering {
+    use std::cmp::Ordering;
+    if float.is_nan() {
+        return Ordering::Less;
+    }
+    let whole = float.trunc();
+    // Saturates past the i128 range, which is still past every i64
+    match (int as i128).cmp(&(whole as i128)) {
+        Ordering::Equal if float > whole => Ordering::Less,
+        Ordering::Equal if float < whole => Ordering::Greater,
+        unequal => unequal,
+    }
+}
+
+/// How every float is printed (`print`, `str`, string concatenation):
+/// the shortest decimal that parses back to the same `f64`, always with a
+/// fractional part or exponent so it reads as a float. `1.0`, `0.1 + 0.2`
+/// -> `0.30000000000000004`, `1e21`, `1.5e-7`; non-finite values print as
+/// `NaN`, `inf` and `-inf`. Independent of the host locale.
+pub fn format_float(v: f64) -> String {
+    format!("{:?}", v)
+}
+
 impl fmt::Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Value::Int(n) => write!(f, "{}", n),
-            Value::Float(v) => write!(f, "{}", v),
+            Value::Float(v) => write!(f, "{}", format_float(*v)),
             Value::Str(s) => write!(f, "{}", s),
             Value::Bool(b) => write!(f, "{}", b),
             Value::Null => write!(f, "null"),
@@ -97,34 +223,45 @@ impl fmt::Display for Value {
             Value::NativeFn(name) => write!(f, "<native:{}>", name),
             Value::NativeModule(name) => write!(f, "<module:{}>", name),
             Value::BoundMethod(_, method) => write!(f, "<bound:{}>", method),
+            Value::Native(obj) => write!(f, "<{}#{}>", obj.type_name(), obj.id()),
         }
     }
 }
 
+/// Read by `CallFrame::reg` for a register the frame never wrote.
+static NULL: Value = Value::Null;
+
+/// One activation. The function's code and constants are shared with every
+/// other frame of the same function; only the registers are per call.
 #[derive(Debug)]
 struct CallFrame {
-    function: CompiledFunction,
+    function: Arc<CompiledFunction>,
+    /// Index of `function` in the program's functions; `None` for `main` and
+    /// the root frame. Its `.kisym` entry is found by it.
+    index: Option<usize>,
+    /// Row of `VM::global_caches` belonging to `function`.
+    cache: usize,
     ip: usize,
     registers: Vec<Value>,
     return_to_reg: Option<u16>,
-    /// Deferred closures to execute in LIFO order when this frame is popped.
-    deferred: Vec<Value>,
+    /// Deferred calls (`system.defer`) to make in LIFO order when this frame
+    /// is popped.
+    deferred: Vec<(Value, Vec<Value>)>,
 }
 
 impl CallFrame {
-    fn new(function: CompiledFunction, args: Vec<Value>, return_to_reg: Option<u16>) -> Self {
+    /// Sizes the register file to the function's `locals` (at least its
+    /// arity); registers past that read as null and grow on first write.
+    fn new(function: Arc<CompiledFunction>, index: Option<usize>, cache: usize, mut args: Vec<Value>, return_to_reg: Option<u16>) -> Self {
         let num_regs = std::cmp::max(function.locals as usize, function.arity as usize);
-        let safe_num_regs = if num_regs == 0 { 256 } else { num_regs };
-        let mut registers = vec![Value::Null; safe_num_regs];
-        
-        for (i, arg) in args.into_iter().enumerate() {
-            if i < registers.len() {
-                registers[i] = arg;
-            }
-        }
-        
+        args.truncate(num_regs);
+        let mut registers = args;
+        registers.resize(num_regs, Value::Null);
+
         Self {
             function,
+            index,
+            cache,
             ip: 0,
             registers,
             return_to_reg,
@@ -132,28 +269,24 @@ impl CallFrame {
         }
     }
 
-    /// Register a closure to be executed when this frame exits (LIFO order).
-    fn push_defer(&mut self, closure: Value) {
-        self.deferred.push(closure);
+    /// Register a call to be made when this frame exits (LIFO order).
+    fn push_defer(&mut self, function: Value, args: Vec<Value>) {
+        self.deferred.push((function, args));
     }
 
     fn reg(&self, idx: u16) -> &Value {
-        &self.registers[idx as usize]
+        self.registers.get(idx as usize).unwrap_or(&NULL)
     }
 
     fn reg_mut(&mut self, idx: u16) -> &mut Value {
+        if idx as usize >= self.registers.len() {
+            self.registers.resize(idx as usize + 1, Value::Null);
+        }
         &mut self.registers[idx as usize]
     }
 
     fn set_reg(&mut self, idx: u16, val: Value) {
-        if (idx as usize) < self.registers.len() {
-            self.registers[idx as usize] = val;
-        } else {
-             while self.registers.len() <= idx as usize {
-                 self.registers.push(Value::Null);
-             }
-             self.registers[idx as usize] = val;
-        }
+        *self.reg_mut(idx) = val;
     }
 
     fn get_constant(&self, idx: u16) -> &Constant {
@@ -161,16 +294,143 @@ impl CallFrame {
     }
 }
 
+/// Marks an inline-cache entry whose global hasn't been resolved yet.
+const UNRESOLVED: u32 = u32::MAX;
+
+/// Global variables, stored by slot. A name gets its slot the first time it
+/// is read or written and keeps it for the VM's lifetime (a slot is never
+/// freed or reused), so a slot cached by an instruction can't go stale:
+/// later `SetGlobal`s write through the same slot. `None` is "not defined
+/// yet".
+///
+/// The first slots belong to the builtins and are numbered once per process
+/// by `BUILTIN_GLOBALS`, so a new VM only allocates their (empty) slots; a
+/// builtin's `NativeFn` is filled in the first time its name is resolved.
+struct Globals {
+    slots: Vec<Option<Value>>,
+    /// Names defined by the program, slotted after the builtins.
+    index: HashMap<String, u32>,
+}
+
+/// Builtin slot numbering shared by every VM: the slot of each builtin name
+/// (and of its `registry_key` alias), and the builtin each slot holds.
+struct BuiltinGlobals {
+    index: HashMap<String, u32>,
+    names: Vec<&'static str>,
+}
+
+lazy_static::lazy_static! {
+    static ref BUILTIN_GLOBALS: BuiltinGlobals = {
+        let mut table = BuiltinGlobals { index: HashMap::new(), names: Vec::new() };
+        for name in crate::builtins::builtin_names() {
+            table.insert(name.to_string(), name);
+            // `math.sqrt` and `Math.sqrt` are the same builtin.
+            let key = crate::builtins::registry_key(name);
+            if key != name {
+                table.insert(key, name);
+            }
+        }
+        table
+    };
+}
+
+impl BuiltinGlobals {
+    /// A later builtin registered under an existing name replaces it in place.
+    fn insert(&mut self, key: String, name: &'static str) {
+        match self.index.get(&key) {
+            Some(&slot) => self.names[slot as usize] = name,
+            None => {
+                self.index.insert(key, self.names.len() as u32);
+                self.names.push(name);
+            }
+        }
+    }
+}
+
+impl Default for Globals {
+    fn default() -> Self {
+        Self { slots: vec![None; BUILTIN_GLOBALS.names.len()], index: HashMap::new() }
+    }
+}
+
+impl Globals {
+    fn slot(&mut self, name: &str) -> u32 {
+        if let Some(&slot) = BUILTIN_GLOBALS.index.get(name) {
+            let entry = &mut self.slots[slot as usize];
+            // A builtin slot is never undefined: `None` only means nothing
+            // has resolved it in this VM yet.
+            if entry.is_none() {
+                *entry = Some(Value::NativeFn(BUILTIN_GLOBALS.names[slot as usize].to_string()));
+            }
+            return slot;
+        }
+        if let Some(&slot) = self.index.get(name) {
+            return slot;
+        }
+        let slot = self.slots.len() as u32;
+        self.slots.push(None);
+        self.index.insert(name.to_string(), slot);
+        slot
+    }
+}
+
 /// Statistics for Memory Allocations (Global Allocation Audit)
 #[derive(Debug, Clone, Default)]
 pub struct MemoryStats {
     pub total_heap_allocations: usize,
 }
 
+/// A program loaded for execution: immutable once built and `Send + Sync`,
+/// so one instance can back any number of VMs (one per request, worker or
+/// `system.thread.spawn`) through `VM::with_program`. Everything a run
+/// mutates -- globals, call stack, reactive state, inline caches -- lives in
+/// the `VM`.
+pub struct SharedProgram {
+    /// Everything but the function bodies, which live in `main`/`functions`.
+    compiled: CompiledProgram,
+    main: Arc<CompiledFunction>,
+    functions: Vec<Arc<CompiledFunction>>,
+    /// Halt-only frame that catches a re-entrant call's result in reg 0.
+    root: Arc<CompiledFunction>,
+}
+
+impl SharedProgram {
+    pub fn new(mut compiled: CompiledProgram) -> Self {
+        let main = Arc::new(std::mem::replace(&mut compiled.main, CompiledFunction::new(String::new(), 0)));
+        let functions = std::mem::take(&mut compiled.functions).into_iter().map(Arc::new).collect();
+        let mut root = CompiledFunction::new("<root>".to_string(), 0);
+        root.locals = 1;
+        root.instructions.push(Instruction { opcode: Opcode::Halt, a: 0, b: 0, c: 0 });
+        Self { compiled, main, functions, root: Arc::new(root) }
+    }
+
+    /// Reassembles the `CompiledProgram` this was built from.
+    pub fn to_compiled(&self) -> CompiledProgram {
+        let mut program = self.compiled.clone();
+        program.main = (*self.main).clone();
+        program.functions = self.functions.iter().map(|f| (**f).clone()).collect();
+        program
+    }
+
+    /// `functions` followed by `main`: the rows of a VM's inline caches.
+    fn cached_functions(&self) -> impl Iterator<Item = &Arc<CompiledFunction>> {
+        self.functions.iter().chain(std::iter::once(&self.main))
+    }
+}
+
 pub struct VM {
-    program: CompiledProgram,
+    program: Arc<SharedProgram>,
     call_stack: Vec<CallFrame>,
-    globals: HashMap<String, Value>,
+    globals: Globals,
+    /// Inline caches for `GetGlobal`/`SetGlobal`: one row per function (then
+    /// `main`, then `root`), indexed by the instruction's name constant, holding
+    /// the resolved global slot or `UNRESOLVED`. Keyed by constant rather than
+    /// by ip because `add_constant` dedupes names: every access to the same
+    /// global from one function shares an entry.
+    global_caches: Vec<Vec<u32>>,
+    /// `LoadMethod` caches, same layout keyed by the method-name constant:
+    /// the last receiver class seen and its `Class::method` global slot.
+    method_caches: Vec<Vec<Option<(String, u32)>>>,
     pub output: Vec<String>,
     
     // Reactive Core Data
@@ -179,56 +439,149 @@ pub struct VM {
     
     // Memory Tracking
     pub mem_stats: MemoryStats,
+
+    /// `.kisym` side file of a program compiled with `--kisym` (its line
+    /// maps live there), read the first time an error needs a line.
+    symbols_path: Option<std::path::PathBuf>,
+    symbols: std::cell::OnceCell<Option<DebugSymbols>>,
+
+    /// Builtins called with the wrong number or types of arguments raise
+    /// instead of returning an `{err: ...}` value (`--strict-builtins`).
+    pub strict_builtins: bool,
+
+    /// Where calls of capability-gated builtins are logged (`--audit FILE`).
+    pub audit: Option<Arc<crate::audit::AuditLog>>,
+
+    /// Heartbeat and pause/cancel handle, once an embedder asks for them
+    /// (see `crate::watchdog`).
+    watchdog: Option<Box<Watchdog>>,
+
+    /// Called after every instruction once set (see `crate::trace`).
+    #[cfg(feature = "trace")]
+    tracer: Option<crate::trace::Tracer>,
 }
 
 impl VM {
     pub fn new(program: CompiledProgram) -> Self {
-        let mut globals = HashMap::new();
-        for name in crate::builtins::BUILTIN_NAMES {
-            globals.insert(name.to_string(), Value::NativeFn(name.to_string()));
-        }
+        Self::with_program(Arc::new(SharedProgram::new(program)))
+    }
+
+    /// A fresh VM over a program other VMs may be running concurrently.
+    pub fn with_program(program: Arc<SharedProgram>) -> Self {
+        let global_caches = program.cached_functions()
+            .map(|f| vec![UNRESOLVED; f.constants.len()])
+            .chain(std::iter::once(vec![]))
+            .collect();
+        let method_caches = program.cached_functions()
+            .map(|f| vec![None; f.constants.len()])
+            .chain(std::iter::once(vec![]))
+            .collect();
 
         Self {
             program,
             call_stack: Vec::new(),
-            globals,
+            globals: Globals::default(),
+            global_caches,
+            method_caches,
             output: Vec::new(),
             state_values: HashMap::new(),
             dirty_states: std::collections::HashSet::new(),
             mem_stats: MemoryStats::default(),
+            symbols_path: None,
+            symbols: std::cell::OnceCell::new(),
+            strict_builtins: crate::builtins::strict_default(),
+            audit: None,
+            watchdog: None,
+            #[cfg(feature = "trace")]
+            tracer: None,
         }
     }
 
+    /// Replaces the program this VM runs, keeping its globals and reactive
+    /// state. `program` must extend the previous one, as a `Compiler` does
+    /// after `next_chunk`: function values already in globals index into it.
+    pub fn load(&mut self, program: CompiledProgram) {
+        let fresh = Self::new(program);
+        self.program = fresh.program;
+        self.global_caches = fresh.global_caches;
+        self.method_caches = fresh.method_caches;
+        self.call_stack.clear();
+    }
+
+    /// Names the program has defined as globals so far, in no particular order.
+    pub fn global_names(&self) -> impl Iterator<Item = &str> {
+        self.globals.index.iter()
+            .filter(|&(_, &slot)| self.globals.slots[slot as usize].is_some())
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// The handle that pauses, resumes or cancels this VM from another thread.
+    pub fn handle(&mut self) -> VmHandle {
+        self.watchdog.get_or_insert_default().handle.clone()
+    }
+
+    /// Puts this VM under `handle`, so one pause or cancel covers several VMs.
+    pub fn set_handle(&mut self, handle: VmHandle) {
+        match &mut self.watchdog {
+            Some(watchdog) => watchdog.handle = handle,
+            None => self.watchdog = Some(Box::new(Watchdog::with_handle(handle))),
+        }
+    }
+
+    /// The handle this VM is under, if any; threads the program spawns share it.
+    pub fn watched_by(&self) -> Option<VmHandle> {
+        self.watchdog.as_ref().map(|watchdog| watchdog.handle.clone())
+    }
+
+    /// Calls `callback` on this thread every `every` executed instructions.
+    pub fn set_heartbeat(&mut self, every: u64, callback: impl FnMut(&Heartbeat) + Send + 'static) {
+        self.watchdog.get_or_insert_default().set_heartbeat(every, Box::new(callback));
+    }
+
+    /// Calls `tracer` after every instruction this VM executes; threads the
+    /// program spawns run untraced.
+    #[cfg(feature = "trace")]
+    pub fn set_tracer(&mut self, tracer: impl FnMut(&crate::trace::TraceEvent) + Send + 'static) {
+        self.tracer = Some(Box::new(tracer));
+    }
+
     pub fn call_stack_len(&self) -> usize {
         self.call_stack.len()
     }
 
     pub fn clone_program(&self) -> CompiledProgram {
-        self.program.clone()
+        self.program.to_compiled()
     }
 
-    /// Push a deferred closure onto the current call frame's defer stack.
-    /// The closure will be executed in LIFO order when the frame returns.
-    pub fn push_defer(&mut self, closure: Value) {
+    pub fn program(&self) -> &Arc<SharedProgram> {
+        &self.program
+    }
+
+    /// Push a deferred call onto the current call frame's defer stack.
+    /// It is made in LIFO order when the frame returns.
+    pub fn push_defer(&mut self, function: Value, args: Vec<Value>) {
         if let Some(frame) = self.call_stack.last_mut() {
-            frame.push_defer(closure);
+            frame.push_defer(function, args);
+        }
+    }
+
+    /// Pops the current frame, then makes its deferred calls. They can't
+    /// fail the function that registered them, so their errors are dropped.
+    fn pop_frame(&mut self) -> CallFrame {
+        let mut frame = self.call_stack.pop().expect("Stack underflow");
+        for (function, args) in std::mem::take(&mut frame.deferred).into_iter().rev() {
+            let _ = self.call_function_now(function, args);
         }
+        frame
     }
 
     pub fn run_function(&mut self, func: Value, args: Vec<Value>) -> Result<Value, String> {
+        let _flush = crate::builtins::modules::io::FlushOnDrop;
         self.call_stack.clear();
         self.dirty_states.clear();
 
         // Push a dummy root frame to catch the return value in reg 0
-        let dummy_frame = CallFrame::new(CompiledFunction {
-            instructions: vec![Instruction { opcode: Opcode::Halt, a: 0, b: 0, c: 0 }],
-            constants: vec![],
-            arity: 0,
-            locals: 1, // Only need reg 0 for return value
-            param_names: vec![],
-            line_map: vec![],
-            name: "thread_root".to_string(),
-        }, vec![], None);
+        let dummy_frame = CallFrame::new(self.program.root.clone(), None, self.program.functions.len() + 1, vec![], None);
         self.call_stack.push(dummy_frame);
 
         // Initiate the call
@@ -242,13 +595,14 @@ impl VM {
             
             let result = match self.step() {
                 Ok(r) => r,
+                Err(e) if self.unwind(&e, 0) => continue,
                 Err(e) => return Err(self.runtime_error(&e)),
             };
             match result {
                 StepResult::Continue => {},
                 StepResult::Halt => break,
                 StepResult::Return(val) => {
-                    let popped = self.call_stack.pop().expect("Stack underflow");
+                    let popped = self.pop_frame();
                     if let Some(reg) = popped.return_to_reg {
                         if let Some(parent) = self.call_stack.last_mut() {
                             parent.set_reg(reg, val);
@@ -258,12 +612,14 @@ impl VM {
                     }
                 },
                 StepResult::Call(f, a, dest_reg) => {
-                     self.call_value(f, a, Some(dest_reg))?;
+                     let called = self.call_value(f, a, Some(dest_reg));
+                     self.catch(called, 0)?;
                 },
                 StepResult::TailCall(f, a) => {
-                    let popped = self.call_stack.pop().expect("Stack underflow");
+                    let popped = self.pop_frame();
                     let ret_reg = popped.return_to_reg;
-                    self.call_value(f, a, ret_reg)?;
+                    let called = self.call_value(f, a, ret_reg);
+                    self.catch(called, 0)?;
                 }
             }
         }
@@ -280,17 +636,17 @@ impl VM {
     /// invoke a Kinetix closure/function synchronously (map/filter/reduce/any/all).
     pub fn call_function_now(&mut self, func: Value, args: Vec<Value>) -> Result<Value, String> {
         let base_len = self.call_stack.len();
+        let result = self.run_nested(func, args, base_len);
+        // A call that failed leaves its frames behind
+        while self.call_stack.len() > base_len {
+            self.pop_frame();
+        }
+        result
+    }
 
+    fn run_nested(&mut self, func: Value, args: Vec<Value>, base_len: usize) -> Result<Value, String> {
         // Dummy frame to catch the return value in reg 0, mirroring run_function().
-        let dummy_frame = CallFrame::new(CompiledFunction {
-            instructions: vec![Instruction { opcode: Opcode::Halt, a: 0, b: 0, c: 0 }],
-            constants: vec![],
-            arity: 0,
-            locals: 1,
-            param_names: vec![],
-            line_map: vec![],
-            name: "callback_root".to_string(),
-        }, vec![], None);
+        let dummy_frame = CallFrame::new(self.program.root.clone(), None, self.program.functions.len() + 1, vec![], None);
         self.call_stack.push(dummy_frame);
 
         self.call_value(func, args, Some(0))?;
@@ -302,13 +658,14 @@ impl VM {
 
             let result = match self.step() {
                 Ok(r) => r,
+                Err(e) if self.unwind(&e, base_len) => continue,
                 Err(e) => return Err(self.runtime_error(&e)),
             };
             match result {
                 StepResult::Continue => {}
                 StepResult::Halt => break, // Our dummy frame's Halt: callback finished.
                 StepResult::Return(val) => {
-                    let popped = self.call_stack.pop().expect("Stack underflow");
+                    let popped = self.pop_frame();
                     if let Some(reg) = popped.return_to_reg {
                         if let Some(parent) = self.call_stack.last_mut() {
                             parent.set_reg(reg, val);
@@ -318,31 +675,32 @@ impl VM {
                     }
                 }
                 StepResult::Call(f, a, dest_reg) => {
-                    self.call_value(f, a, Some(dest_reg))?;
+                    let called = self.call_value(f, a, Some(dest_reg));
+                    self.catch(called, base_len)?;
                 }
                 StepResult::TailCall(f, a) => {
-                    let popped = self.call_stack.pop().expect("Stack underflow");
+                    let popped = self.pop_frame();
                     let ret_reg = popped.return_to_reg;
-                    self.call_value(f, a, ret_reg)?;
+                    let called = self.call_value(f, a, ret_reg);
+                    self.catch(called, base_len)?;
                 }
             }
         }
 
-        let result = self.call_stack.get(base_len)
+        Ok(self.call_stack.get(base_len)
             .map(|f| f.reg(0).clone())
-            .unwrap_or(Value::Null);
-        self.call_stack.truncate(base_len);
-        Ok(result)
+            .unwrap_or(Value::Null))
     }
 
     pub fn run(&mut self) -> Result<(), String> {
+        let _flush = crate::builtins::modules::io::FlushOnDrop;
         let mut ticks = 0;
         const MAX_TICKS: usize = 1000; // Prevent infinite reactive loops
 
         // Tick loop (Frame Scheduler)
         loop {
             let main_args = vec![];
-            let main_frame = CallFrame::new(self.program.main.clone(), main_args, None);
+            let main_frame = CallFrame::new(self.program.main.clone(), None, self.program.functions.len(), main_args, None);
             self.call_stack.push(main_frame);
             
             // Clear dirty tracking for this frame
@@ -356,38 +714,14 @@ impl VM {
                 
                 let result = match self.step() {
                     Ok(r) => r,
+                    Err(e) if self.unwind(&e, 0) => continue,
                     Err(e) => return Err(self.runtime_error(&e)),
                 };
                 match result {
                     StepResult::Continue => {},
                     StepResult::Halt => break,
                     StepResult::Return(val) => {
-                        let popped = self.call_stack.pop().expect("Stack underflow");
-                        // Execute deferred closures in LIFO order (Build 26)
-                        let deferred_closures: Vec<Value> = popped.deferred.into_iter().rev().collect();
-                        for closure in deferred_closures {
-                            // Fire-and-forget: deferred closures cannot fail the caller
-                            let _ = self.call_value(closure.clone(), vec![], None);
-                            // Run the deferred closure to completion within the current execution loop
-                            loop {
-                                if self.call_stack.is_empty() { break; }
-                                match self.step() {
-                                    Ok(StepResult::Continue) => {},
-                                    Ok(StepResult::Halt) => break,
-                                    Ok(StepResult::Return(_)) => {
-                                        self.call_stack.pop();
-                                        break;
-                                    },
-                                    Ok(StepResult::Call(f, a, d)) => { let _ = self.call_value(f, a, Some(d)); },
-                                    Ok(StepResult::TailCall(f, a)) => {
-                                        let p = self.call_stack.pop().expect("Stack underflow");
-                                        let r = p.return_to_reg;
-                                        let _ = self.call_value(f, a, r);
-                                    },
-                                    Err(_) => break, // Swallow deferred errors silently
-                                }
-                            }
-                        }
+                        let popped = self.pop_frame();
                         if let Some(reg) = popped.return_to_reg {
                             if let Some(parent) = self.call_stack.last_mut() {
                                 parent.set_reg(reg, val);
@@ -398,12 +732,14 @@ impl VM {
                         }
                     },
                     StepResult::Call(func, args, dest_reg) => {
-                         self.call_value(func, args, Some(dest_reg))?;
+                         let called = self.call_value(func, args, Some(dest_reg));
+                         self.catch(called, 0)?;
                     },
                     StepResult::TailCall(func, args) => {
-                        let popped = self.call_stack.pop().expect("Stack underflow");
+                        let popped = self.pop_frame();
                         let ret_reg = popped.return_to_reg;
-                        self.call_value(func, args, ret_reg)?;
+                        let called = self.call_value(func, args, ret_reg);
+                        self.catch(called, 0)?;
                     }
                 }
             } // end inner execution loop
@@ -425,16 +761,93 @@ impl VM {
             self.call_stack.clear(); // Ensure clean state before re-running
         }
 
+        if crate::log::metrics_enabled() {
+            crate::log::record(&crate::log::Event::Finished { heap_allocations: self.mem_stats.total_heap_allocations });
+        }
         Ok(())
     }
 
+    /// Looks for a handler of `error` from the innermost frame down to frame
+    /// `floor`. The first frame whose exception table covers where it is
+    /// (the faulting instruction, or the call in progress) resumes at the
+    /// handler with the message in the handler's register; the frames above
+    /// it are dropped. Returns whether one was found.
+    fn unwind(&mut self, error: &str, floor: usize) -> bool {
+        // A cancel from the embedder stops the program, handlers or not
+        if self.watchdog.as_ref().is_some_and(|w| w.handle.is_cancelled()) {
+            return false;
+        }
+        for depth in (floor..self.call_stack.len()).rev() {
+            let frame = &self.call_stack[depth];
+            let Some(handler) = frame.function.handler_for(frame.ip.saturating_sub(1)).copied() else { continue };
+            while self.call_stack.len() > depth + 1 {
+                self.pop_frame();
+            }
+            let frame = &mut self.call_stack[depth];
+            frame.ip = handler.handler as usize;
+            frame.set_reg(handler.register, Value::Str(error.to_string()));
+            return true;
+        }
+        false
+    }
+
+    /// `result`, unless its error was caught by a handler at frame `floor`
+    /// or above.
+    fn catch(&mut self, result: Result<(), String>, floor: usize) -> Result<(), String> {
+        match result {
+            Err(e) if !self.unwind(&e, floor) => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// Points the VM at the program's `.kisym` file (see `kinetix_kicomp::kisym`).
+    pub fn set_symbols_path(&mut self, path: std::path::PathBuf) {
+        self.symbols_path = Some(path);
+    }
+
+    fn debug_symbols(&self) -> Option<&DebugSymbols> {
+        let path = self.symbols_path.as_ref()?;
+        self.symbols.get_or_init(|| DebugSymbols::read(path).ok()).as_ref()
+    }
+
+    /// The `.kisym` entry of `function` (at `index`, `None` for `main`).
+    fn function_symbols(&self, function: &CompiledFunction, index: Option<usize>) -> Option<&FunctionSymbols> {
+        self.debug_symbols()?.function(index, &function.name)
+    }
+
+    /// Source line of instruction `ip` of `function`, 0 if unknown: from its
+    /// line map or, when that went to a `.kisym`, from the side file.
+    fn line_of(&self, function: &CompiledFunction, index: Option<usize>, ip: usize) -> u32 {
+        match function.line_map.get(ip) {
+            Some(&line) if line > 0 => line,
+            _ => self.function_symbols(function, index).and_then(|f| f.line_at(ip)).unwrap_or(0),
+        }
+    }
+
+    /// Name of the local `function` keeps in `register`, if it declares one
+    /// there (the last, when scopes reuse it).
+    #[cfg(feature = "trace")]
+    fn local_name(&self, function: &CompiledFunction, index: Option<usize>, register: u16) -> Option<String> {
+        let locals = if function.local_names.is_empty() {
+            &self.function_symbols(function, index)?.locals
+        } else {
+            &function.local_names
+        };
+        locals.iter().rev().find(|(_, r)| *r == register).map(|(name, _)| name.clone())
+    }
+
+    /// Source line of the instruction the current frame last executed (a
+    /// builtin's call site), 0 if unknown.
+    pub(crate) fn current_line(&self) -> u32 {
+        self.call_stack.last().map_or(0, |frame| self.line_of(&frame.function, frame.index, frame.ip.saturating_sub(1)))
+    }
+
     /// Build a detailed runtime error string with function name, line number, and message.
     fn runtime_error(&self, msg: &str) -> String {
         if let Some(frame) = self.call_stack.last() {
             let fn_name = &frame.function.name;
             // ip has already been incremented by step(), so the faulting instruction is ip - 1
-            let ip = if frame.ip > 0 { frame.ip - 1 } else { 0 };
-            let line = frame.function.line_map.get(ip).copied().unwrap_or(0);
+            let line = self.current_line();
             if line > 0 {
                 format!("[line {}] in {}: {}", line, fn_name, msg)
             } else {
@@ -446,6 +859,46 @@ impl VM {
     }
 
     pub fn step(&mut self) -> Result<StepResult, String> {
+        #[cfg(feature = "trace")]
+        if self.tracer.is_some() {
+            return self.step_traced();
+        }
+        self.execute()
+    }
+
+    /// `step`, reporting the instruction and the registers it wrote.
+    #[cfg(feature = "trace")]
+    fn step_traced(&mut self) -> Result<StepResult, String> {
+        let depth = self.call_stack.len();
+        let frame = &self.call_stack[depth - 1];
+        let (function, index, ip, before) = (frame.function.clone(), frame.index, frame.ip, frame.registers.clone());
+        let result = self.execute();
+        // Past the end there is no instruction: the frame returns null
+        let Some(&instruction) = function.instructions.get(ip) else { return result };
+        let after = self.call_stack.get(depth - 1).map_or(&[][..], |frame| &frame.registers[..]);
+        let mut changes = crate::trace::diff(&before, after);
+        for change in &mut changes {
+            change.name = self.local_name(&function, index, change.register);
+        }
+        let event = crate::trace::TraceEvent {
+            depth,
+            function: function.name.clone(),
+            ip,
+            line: self.line_of(&function, index, ip),
+            instruction,
+            changes,
+        };
+        if let Some(tracer) = &mut self.tracer {
+            tracer(&event);
+        }
+        result
+    }
+
+    #[inline(always)]
+    fn execute(&mut self) -> Result<StepResult, String> {
+        if let Some(watchdog) = &mut self.watchdog {
+            watchdog.tick(self.call_stack.len())?;
+        }
         let frame_idx = self.call_stack.len() - 1;
         let frame = &mut self.call_stack[frame_idx];
 
@@ -458,21 +911,20 @@ impl VM {
 
         match instr.opcode {
             Opcode::LoadConst => {
-                let c = frame.get_constant(instr.b).clone();
-                let val = match c {
-                    Constant::Integer(i) => Value::Int(i),
-                    Constant::Float(f) => Value::Float(f),
+                let val = match frame.get_constant(instr.b) {
+                    Constant::Integer(i) => Value::Int(*i),
+                    Constant::Float(f) => Value::Float(*f),
                     Constant::String(s) => {
                         self.mem_stats.total_heap_allocations += 1;
-                        Value::Str(s)
+                        Value::Str(s.clone())
                     },
-                    Constant::Boolean(b) => Value::Bool(b),
+                    Constant::Boolean(b) => Value::Bool(*b),
                     Constant::Null => Value::Null,
-                    Constant::Function(idx) => Value::Function(idx),
+                    Constant::Function(idx) => Value::Function(*idx),
                     Constant::Class { name, .. } => {
                          self.mem_stats.total_heap_allocations += 1;
                          let mut map = HashMap::new();
-                         map.insert("__class_name__".to_string(), Value::Str(name));
+                         map.insert("__class_name__".to_string(), Value::Str(name.clone()));
                          self.mem_stats.total_heap_allocations += 1;
                          Value::Map(map)
                     }
@@ -576,8 +1028,8 @@ impl VM {
 
             Opcode::Print => {
                 let val = frame.reg(instr.a);
-                let out = format!("{}", val);
-                println!("{}", out);
+                let out = crate::pretty::render(val, crate::pretty::print_limits());
+                crate::builtins::modules::io::write_line(&out);
                 self.output.push(out);
             }
 
@@ -590,23 +1042,40 @@ impl VM {
                 frame.set_reg(instr.a, val);
             }
             Opcode::GetGlobal => {
-                let name = match frame.get_constant(instr.b) {
-                    Constant::String(s) => s.clone(),
-                    _ => return Err("GetGlobal: expected string constant".into()),
+                let slot = match self.global_caches[frame.cache][instr.b as usize] {
+                    UNRESOLVED => {
+                        let Constant::String(name) = frame.get_constant(instr.b) else {
+                            return Err("GetGlobal: expected string constant".into());
+                        };
+                        let slot = self.globals.slot(name);
+                        self.global_caches[frame.cache][instr.b as usize] = slot;
+                        slot
+                    }
+                    slot => slot,
                 };
-                if let Some(val) = self.globals.get(&name) {
+                if let Some(val) = &self.globals.slots[slot as usize] {
                     frame.set_reg(instr.a, val.clone());
                 } else {
+                    let Constant::String(name) = frame.get_constant(instr.b) else { unreachable!() };
+                    if let Some((module, plain)) = name.split_once("::").filter(|(m, _)| self.program.compiled.modules.iter().any(|l| l == m)) {
+                        return Err(format!("Undefined global: {} (in linked bundle '{}')", plain, module));
+                    }
                     return Err(format!("Undefined global: {}", name));
                 }
             }
             Opcode::SetGlobal => {
-                let name = match frame.get_constant(instr.a) {
-                     Constant::String(s) => s.clone(),
-                    _ => return Err("SetGlobal: expected string constant".into()),
+                let slot = match self.global_caches[frame.cache][instr.a as usize] {
+                    UNRESOLVED => {
+                        let Constant::String(name) = frame.get_constant(instr.a) else {
+                            return Err("SetGlobal: expected string constant".into());
+                        };
+                        let slot = self.globals.slot(name);
+                        self.global_caches[frame.cache][instr.a as usize] = slot;
+                        slot
+                    }
+                    slot => slot,
                 };
-                let val = frame.reg(instr.b).clone();
-                self.globals.insert(name, val);
+                self.globals.slots[slot as usize] = Some(frame.reg(instr.b).clone());
             }
             
             Opcode::SetState => {
@@ -665,47 +1134,26 @@ impl VM {
                 }
             }
             Opcode::GetMember => {
-                 let member_name = match frame.get_constant(instr.c) {
-                    Constant::String(s) => s.clone(),
-                     _ => return Err("GetMember: name must be string".into()),
+                // Records are plain maps with no fixed layout, so there is no
+                // slot to cache here; just look the name up in place instead
+                // of copying it first.
+                let Constant::String(member_name) = frame.get_constant(instr.c) else {
+                    return Err("GetMember: name must be string".into());
                 };
-                let obj = frame.reg(instr.b);
-                match obj {
-                    Value::Map(map) => {
-                        if let Some(val) = map.get(&member_name) {
-                            frame.set_reg(instr.a, val.clone());
-                        } else {
-                            frame.set_reg(instr.a, Value::Null);
-                        }
-                    },
+                let val = match frame.reg(instr.b) {
+                    Value::Map(map) => map.get(member_name).cloned().unwrap_or(Value::Null),
                     _ => return Err("GetMember: target not a map".into()),
-                }
+                };
+                frame.set_reg(instr.a, val);
             }
             Opcode::GetIndex => {
-                let idx = frame.reg(instr.c).as_int()?;
-                match frame.reg(instr.b) {
-                    Value::Array(arr) => {
-                        match usize::try_from(idx).ok().filter(|&i| i < arr.len()) {
-                            Some(i) => frame.set_reg(instr.a, arr[i].clone()),
-                            None => return Err(format!("Index {} out of bounds for array of length {}", idx, arr.len())),
-                        }
-                    }
-                    other => return Err(format!("GetIndex: expected array, got {:?}", other)),
-                }
+                let val = index_value(frame.reg(instr.b), frame.reg(instr.c))?;
+                frame.set_reg(instr.a, val);
             }
             Opcode::SetIndex => {
-                let idx = frame.reg(instr.b).as_int()?;
+                let idx = frame.reg(instr.b).clone();
                 let val = frame.reg(instr.c).clone();
-                match frame.reg_mut(instr.a) {
-                    Value::Array(arr) => {
-                        let len = arr.len();
-                        match usize::try_from(idx).ok().filter(|&i| i < len) {
-                            Some(i) => arr[i] = val,
-                            None => return Err(format!("Index {} out of bounds for array of length {}", idx, len)),
-                        }
-                    }
-                    other => return Err(format!("SetIndex: expected array, got {:?}", other)),
-                }
+                set_index_value(frame.reg_mut(instr.a), &idx, val)?;
             }
             Opcode::MakeArray => {
                 self.mem_stats.total_heap_allocations += 1;
@@ -764,6 +1212,17 @@ impl VM {
                     frame.ip = instr.a as usize;
                 }
             }
+            Opcode::JumpTable => {
+                let Constant::Integer(low) = *frame.get_constant(instr.b) else {
+                    return Err("JumpTable: expected an integer constant".into());
+                };
+                let count = instr.c as usize;
+                let entry = match frame.reg(instr.a) {
+                    Value::Int(n) => n.checked_sub(low).and_then(|d| usize::try_from(d).ok()).filter(|&d| d < count),
+                    _ => None,
+                };
+                frame.ip += entry.unwrap_or(count);
+            }
 
             Opcode::Call => {
                 let func_val = frame.reg(instr.a).clone();
@@ -802,30 +1261,56 @@ impl VM {
                     return Ok(StepResult::Continue);
                 }
 
+                // Native handles dispatch through their type's method table.
+                if let Value::Native(native) = &obj {
+                    if !native.has_method(&method_name) {
+                        return Err(format!("Method '{}' not found on native type '{}'", method_name, native.type_name()));
+                    }
+                    frame.set_reg(instr.a, Value::BoundMethod(Box::new(obj), Box::new(Value::NativeFn(method_name))));
+                    return Ok(StepResult::Continue);
+                }
+
+                let is_derived = builtins::DERIVED_METHODS.contains(&method_name.as_str());
                 let class_name = match &obj {
                     Value::Map(map) => {
-                        match map.get("__class__").or_else(|| map.get("__enum__")) {
-                            Some(Value::Str(c)) => c.clone(),
-                            _ => return Err("LoadMethod: object is a map but has no __class__ or __enum__ field".into()),
+                        match builtins::declared_type_name(map) {
+                            Some(c) => c.to_string(),
+                            None if is_derived => String::new(),
+                            None => return Err("LoadMethod: object is a map but has no __class__ or __enum__ field".into()),
                         }
                     }
+                    _ if is_derived => String::new(),
                     _ => return Err("LoadMethod: object is not a class instance".into()),
                 };
 
-                if let Some(vtable) = self.program.vtable.get(&class_name) {
-                    if let Some(&func_idx) = vtable.get(&method_name) {
-                        self.mem_stats.total_heap_allocations += 1;
-                        let func_val = Value::Function(func_idx);
-                        frame.set_reg(instr.a, Value::BoundMethod(Box::new(obj.clone()), Box::new(func_val)));
-                        return Ok(StepResult::Continue);
-                    }
+                if let Some(vtable) = self.program.compiled.vtable.get(&class_name)
+                    && let Some(&func_idx) = vtable.get(&method_name)
+                {
+                    self.mem_stats.total_heap_allocations += 1;
+                    let func_val = Value::Function(func_idx);
+                    frame.set_reg(instr.a, Value::BoundMethod(Box::new(obj), Box::new(func_val)));
+                    return Ok(StepResult::Continue);
                 }
 
-                // Fallback to legacy string-based dynamic lookup
-                let flat_name = format!("{}::{}", class_name, method_name);
-                if let Some(func_val) = self.globals.get(&flat_name) {
+                // Programs compiled before class metadata existed: fall back to
+                // the flattened `Class::method` global, through a monomorphic
+                // cache (one receiver class per call site).
+                let cache = &mut self.method_caches[frame.cache][instr.c as usize];
+                let slot = match cache {
+                    Some((class, slot)) if *class == class_name => *slot,
+                    _ => {
+                        let slot = self.globals.slot(&format!("{}::{}", class_name, method_name));
+                        *cache = Some((class_name.clone(), slot));
+                        slot
+                    }
+                };
+                if let Some(func_val) = &self.globals.slots[slot as usize] {
                     self.mem_stats.total_heap_allocations += 1;
-                    frame.set_reg(instr.a, Value::BoundMethod(Box::new(obj.clone()), Box::new(func_val.clone())));
+                    frame.set_reg(instr.a, Value::BoundMethod(Box::new(obj), Box::new(func_val.clone())));
+                } else if is_derived {
+                    // No user-defined override: fall back to the derived
+                    // reflection-lite builtin of the same name.
+                    frame.set_reg(instr.a, Value::BoundMethod(Box::new(obj), Box::new(Value::NativeFn(method_name))));
                 } else {
                     return Err(format!("Method '{}' not found on class '{}' (VTable resolution failed)", method_name, class_name));
                 }
@@ -856,15 +1341,25 @@ impl VM {
     pub fn call_value(&mut self, func: Value, mut args: Vec<Value>, return_reg: Option<u16>) -> Result<(), String> {
         match func {
             Value::BoundMethod(receiver, method) => {
+                if let (Value::Native(native), Value::NativeFn(name)) = (&*receiver, &*method) {
+                    let result = native.call_method(name, &args)?;
+                    if let (Some(reg), Some(frame)) = (return_reg, self.call_stack.last_mut()) {
+                        frame.set_reg(reg, result);
+                    }
+                    return Ok(());
+                }
                 args.insert(0, *receiver);
                 self.call_value(*method, args, return_reg)
             }
             Value::Function(func_idx) => {
                 let func = self.program.functions[func_idx].clone();
-                self.call_stack.push(CallFrame::new(func, args, return_reg));
+                self.call_stack.push(CallFrame::new(func, Some(func_idx), func_idx, args, return_reg));
                 Ok(())
             }
             Value::NativeFn(name) => {
+                if crate::log::metrics_enabled() {
+                    crate::log::record(&crate::log::Event::Builtin { name: &name });
+                }
                 let result = builtins::call_builtin(&name, &args, self)?;
                 if let Some(reg) = return_reg {
                     if let Some(frame) = self.call_stack.last_mut() {
@@ -874,8 +1369,14 @@ impl VM {
                 Ok(())
             }
             Value::Str(name) => {
-                let result = builtins::call_builtin(&name, &args, self)
-                    .map_err(|_| format!("Cannot call Str('{}') (not expecting a native function)", name))?;
+                // A builtin that fails reports its own error; only an unknown name isn't callable
+                let result = builtins::call_builtin(&name, &args, self).map_err(|e| {
+                    if e.starts_with("Unknown built-in") {
+                        format!("Cannot call Str('{}') (not expecting a native function)", name)
+                    } else {
+                        e
+                    }
+                })?;
                 if let Some(reg) = return_reg {
                     if let Some(frame) = self.call_stack.last_mut() {
                         frame.set_reg(reg, result);
@@ -897,6 +1398,66 @@ pub enum StepResult {
     TailCall(Value, Vec<Value>),
 }
 
+/// An array or string position: an int in `0..len`. Negative indices are
+/// an error rather than counting from the end, so an off-by-one below zero
+/// can't silently read the last element.
+fn checked_index(index: &Value, len: usize, what: &str) -> Result<usize, String> {
+    let Value::Int(n) = index else {
+        return Err(format!("{} index must be an int, got {}", what, crate::builtins::type_name(index)));
+    };
+    if *n < 0 {
+        return Err(format!(
+            "Index {} out of bounds for {} of length {} (negative indices are not supported; use len(x) - {})",
+            n, what.to_lowercase(), len, n.unsigned_abs()
+        ));
+    }
+    usize::try_from(*n).ok().filter(|&i| i < len)
+        .ok_or_else(|| format!("Index {} out of bounds for {} of length {}", n, what.to_lowercase(), len))
+}
+
+/// `container[index]`: an array element; the character starting at byte
+/// `index` of a string (a byte offset, like `len`); or the value of a map's
+/// string key, which must be present.
+fn index_value(container: &Value, index: &Value) -> Result<Value, String> {
+    match container {
+        Value::Array(arr) => Ok(arr[checked_index(index, arr.len(), "Array")?].clone()),
+        Value::Str(s) => {
+            let i = checked_index(index, s.len(), "String")?;
+            s.get(i..).and_then(|rest| rest.chars().next())
+                .map(|c| Value::Str(c.to_string()))
+                .ok_or_else(|| format!("Index {} is inside a multi-byte character of a string of length {}", i, s.len()))
+        }
+        Value::Map(map) => {
+            let Value::Str(key) = index else {
+                return Err(format!("Map key must be a string, got {}", crate::builtins::type_name(index)));
+            };
+            map.get(key).cloned().ok_or_else(|| format!("Key \"{}\" not found in map of {} entries", key, map.len()))
+        }
+        other => Err(format!("Cannot index {}", crate::builtins::type_name(other))),
+    }
+}
+
+/// `container[index] = val`: replaces an array element in `0..len` or sets
+/// a map's string key (adding it when missing). Strings are immutable.
+fn set_index_value(container: &mut Value, index: &Value, val: Value) -> Result<(), String> {
+    match container {
+        Value::Array(arr) => {
+            let i = checked_index(index, arr.len(), "Array")?;
+            arr[i] = val;
+            Ok(())
+        }
+        Value::Map(map) => {
+            let Value::Str(key) = index else {
+                return Err(format!("Map key must be a string, got {}", crate::builtins::type_name(index)));
+            };
+            map.insert(key.clone(), val);
+            Ok(())
+        }
+        Value::Str(_) => Err("Cannot assign to a string index: strings are immutable".to_string()),
+        other => Err(format!("Cannot index {}", crate::builtins::type_name(other))),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -928,6 +1489,71 @@ mod tests {
         assert_eq!(vm.output, vec!["20".to_string()]);
     }
 
+    #[test]
+    fn errors_unwind_to_the_innermost_covering_handler() {
+        let mut divide = CompiledFunction::new("divide".to_string(), 1);
+        divide.locals = 3;
+        divide.constants = vec![Constant::Integer(0)];
+        divide.instructions = vec![
+            Instruction::ab(Opcode::LoadConst, 1, 0),  // r1 = 0
+            Instruction::new(Opcode::Div, 2, 0, 1),    // r2 = r0 / 0: raises
+            Instruction::a_only(Opcode::Return, 2),
+        ];
+        let mut program = CompiledProgram::new();
+        program.functions.push(divide);
+        program.main.locals = 4;
+        program.main.constants = vec![Constant::Function(0), Constant::Integer(10)];
+        program.main.instructions = vec![
+            Instruction::ab(Opcode::LoadConst, 0, 0),  // r0 = divide
+            Instruction::ab(Opcode::LoadConst, 1, 1),  // r1 = 10
+            Instruction::ab(Opcode::Call, 0, 1),       // r0 = divide(10), caught below
+            Instruction::a_only(Opcode::Print, 0),
+            Instruction::a_only(Opcode::Jump, 7),
+            Instruction::a_only(Opcode::Print, 3),     // handler: print the error
+            Instruction::ab(Opcode::Call, 2, 0),       // r2 = null(): caught by the outer entry
+            Instruction::a_only(Opcode::Halt, 0),
+            Instruction::a_only(Opcode::Print, 3),     // outer handler
+            Instruction::a_only(Opcode::Halt, 0),
+        ];
+        program.main.exception_table = vec![
+            ExceptionHandler { start: 2, end: 3, handler: 5, register: 3 },
+            ExceptionHandler { start: 0, end: 8, handler: 8, register: 3 },
+        ];
+        assert_eq!(program.main.check_exception_table(), Ok(()));
+        let mut vm = VM::new(program.clone());
+        vm.run().expect("both errors are handled");
+        assert_eq!(vm.output.len(), 2, "{:?}", vm.output);
+        assert!(vm.output[0].contains("Division by zero"), "{:?}", vm.output);
+
+        // Without a table the same error stops the program
+        program.main.exception_table.clear();
+        let error = VM::new(program).run().unwrap_err();
+        assert!(error.contains("in divide: Division by zero"), "{}", error);
+    }
+
+    #[test]
+    fn test_builtin_globals_are_shared_and_filled_on_first_use() {
+        let mut globals = Globals::default();
+        assert!(globals.slots.iter().all(Option::is_none));
+        assert!(globals.index.is_empty());
+
+        for alias in ["Math.sqrt", "math.sqrt"] {
+            let slot = globals.slot(alias);
+            assert!(matches!(&globals.slots[slot as usize], Some(Value::NativeFn(name)) if name == "Math.sqrt"));
+        }
+
+        let user = globals.slot("answer");
+        assert_eq!(user as usize, BUILTIN_GLOBALS.names.len());
+        assert!(globals.slots[user as usize].is_none());
+
+        // A program may shadow a builtin; the slot keeps what it wrote.
+        let len = globals.slot("len");
+        globals.slots[len as usize] = Some(Value::Int(7));
+        assert_eq!(globals.slot("len"), len);
+        assert!(matches!(globals.slots[len as usize], Some(Value::Int(7))));
+        assert!(Globals::default().slots[len as usize].is_none());
+    }
+
     #[test]
     fn test_get_index_out_of_bounds_errors() {
         let consts = vec![Constant::Integer(10), Constant::Integer(5)];
@@ -941,6 +1567,36 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_index_semantics_for_arrays_strings_and_maps() {
+        let arr = Value::Array(vec![Value::Int(10), Value::Int(20), Value::Int(30)]);
+        assert_eq!(index_value(&arr, &Value::Int(2)), Ok(Value::Int(30)));
+        assert_eq!(index_value(&arr, &Value::Int(3)), Err("Index 3 out of bounds for array of length 3".to_string()));
+        assert_eq!(
+            index_value(&arr, &Value::Int(-1)),
+            Err("Index -1 out of bounds for array of length 3 (negative indices are not supported; use len(x) - 1)".to_string())
+        );
+        assert_eq!(index_value(&arr, &Value::Str("0".into())), Err("Array index must be an int, got string".to_string()));
+
+        let s = Value::Str("héllo".into());
+        assert_eq!(index_value(&s, &Value::Int(0)), Ok(Value::Str("h".into())));
+        assert_eq!(index_value(&s, &Value::Int(1)), Ok(Value::Str("é".into())));
+        assert_eq!(index_value(&s, &Value::Int(2)), Err("Index 2 is inside a multi-byte character of a string of length 6".to_string()));
+        assert_eq!(index_value(&s, &Value::Int(6)), Err("Index 6 out of bounds for string of length 6".to_string()));
+        let mut s = s;
+        assert!(set_index_value(&mut s, &Value::Int(0), Value::Str("j".into())).is_err());
+
+        let mut map = Value::Map(HashMap::from([("a".to_string(), Value::Int(1))]));
+        assert_eq!(index_value(&map, &Value::Str("a".into())), Ok(Value::Int(1)));
+        assert_eq!(index_value(&map, &Value::Str("b".into())), Err("Key \"b\" not found in map of 1 entries".to_string()));
+        set_index_value(&mut map, &Value::Str("b".into()), Value::Int(2)).unwrap();
+        assert_eq!(index_value(&map, &Value::Str("b".into())), Ok(Value::Int(2)));
+        assert!(index_value(&map, &Value::Int(0)).is_err());
+
+        let mut arr = arr;
+        assert!(set_index_value(&mut arr, &Value::Int(-1), Value::Null).is_err());
+    }
+
     #[test]
     fn test_set_index_writes_array_element() {
         let consts = vec![Constant::Integer(1), Constant::Integer(2), Constant::Integer(99), Constant::Integer(0)];
@@ -983,4 +1639,140 @@ mod tests {
         ], vec![]).expect("should run without error");
         assert_eq!(vm.output, vec!["false".to_string()]);
     }
+
+    #[test]
+    fn test_jump_table_lands_on_the_entry_or_skips_the_table() {
+        // match r0 { 10 => print 1, 11 => print 2, _ => print 3 }, for r0 in 11, 12, "x"
+        let program = || vec![
+            Instruction::ab(Opcode::LoadConst, 0, 0),
+            Instruction::new(Opcode::JumpTable, 0, 1, 2),
+            Instruction::a_only(Opcode::Jump, 4),
+            Instruction::a_only(Opcode::Jump, 6),
+            Instruction::ab(Opcode::LoadConst, 1, 4), // default
+            Instruction::a_only(Opcode::Jump, 7),
+            Instruction::ab(Opcode::LoadConst, 1, 3), // 11
+            Instruction::a_only(Opcode::Print, 1),
+            Instruction::a_only(Opcode::Halt, 0),
+        ];
+        let consts = |value| vec![value, Constant::Integer(10), Constant::Integer(1), Constant::Integer(2), Constant::Integer(3)];
+        let output = |value: Constant| run_main(program(), consts(value)).expect("should run without error").output;
+        assert_eq!(output(Constant::Integer(11)), vec!["2".to_string()]);
+        assert_eq!(output(Constant::Integer(12)), vec!["3".to_string()]);
+        assert_eq!(output(Constant::String("x".into())), vec!["3".to_string()]);
+    }
+
+    #[test]
+    fn test_equality_ordering_and_identity_semantics() {
+        let nan = Value::Float(f64::NAN);
+        assert_eq!(Value::Int(1), Value::Float(1.0));
+        assert_ne!(nan, nan.clone());
+        assert_eq!(Value::Array(vec![Value::Int(1), Value::Str("a".into())]), Value::Array(vec![Value::Float(1.0), Value::Str("a".into())]));
+        assert_ne!(Value::Int(0), Value::Null);
+        assert_eq!(nan.partial_cmp(&Value::Float(1.0)), None);
+
+        let mut mixed = [nan.clone(), Value::Str("b".into()), Value::Int(3), Value::Null, Value::Float(-1.5), Value::Bool(true)];
+        mixed.sort_by(Value::sort_cmp);
+        assert_eq!(mixed.iter().map(|v| v.to_string()).collect::<Vec<_>>(), ["null", "true", "-1.5", "3", "NaN", "b"]);
+
+        // Ints past 2^53 against floats compare exactly, so the order stays total
+        let big = 1i64 << 53;
+        let cmp = |a: Value, b: Value| a.sort_cmp(&b);
+        assert_eq!(cmp(Value::Int(big), Value::Float(big as f64)), std::cmp::Ordering::Equal);
+        assert_eq!(cmp(Value::Int(big + 1), Value::Float(big as f64)), std::cmp::Ordering::Greater);
+        assert_eq!(cmp(Value::Float(big as f64), Value::Int(big + 1)), std::cmp::Ordering::Less);
+        assert_eq!(cmp(Value::Int(2), Value::Float(2.5)), std::cmp::Ordering::Less);
+        assert_eq!(cmp(Value::Int(-2), Value::Float(-2.5)), std::cmp::Ordering::Greater);
+        assert_eq!(cmp(Value::Int(i64::MAX), Value::Float(f64::INFINITY)), std::cmp::Ordering::Less);
+        assert_eq!(cmp(Value::Int(i64::MIN), Value::Float(f64::NEG_INFINITY)), std::cmp::Ordering::Greater);
+        let mut large = [Value::Int(big + 1), Value::Float(big as f64), Value::Int(big), Value::Float(big as f64 + 2.0), Value::Int(big - 1)];
+        large.sort_by(Value::sort_cmp);
+        assert_eq!(large.iter().map(|v| v.to_string()).collect::<Vec<_>>(), [
+            (big - 1).to_string(), Value::Float(big as f64).to_string(), big.to_string(), (big + 1).to_string(), Value::Float(big as f64 + 2.0).to_string(),
+        ]);
+
+        assert!(nan.is_same(&Value::Float(f64::NAN)));
+        assert!(!Value::Int(1).is_same(&Value::Float(1.0)));
+        assert!(!Value::Float(0.0).is_same(&Value::Float(-0.0)));
+        assert!(Value::Array(vec![Value::Int(1)]).is_same(&Value::Array(vec![Value::Int(1)])));
+    }
+
+    #[test]
+    fn test_frames_share_code_and_size_registers_to_locals() {
+        let mut func = CompiledFunction::new("f".to_string(), 2);
+        func.locals = 3;
+        let func = Arc::new(func);
+        let mut frame = CallFrame::new(func.clone(), Some(0), 0, vec![Value::Int(1), Value::Int(2)], None);
+        assert_eq!(Arc::strong_count(&func), 2);
+        assert_eq!(frame.registers.len(), 3);
+        assert_eq!(frame.reg(1), &Value::Int(2));
+        assert_eq!(frame.reg(7), &Value::Null);
+        frame.set_reg(7, Value::Int(9));
+        assert_eq!(frame.reg(7), &Value::Int(9));
+    }
+
+    #[test]
+    fn test_global_inline_cache_sees_later_writes() {
+        let consts = vec![Constant::String("g".to_string()), Constant::Integer(1), Constant::Integer(2)];
+        let vm = run_main(vec![
+            Instruction::ab(Opcode::LoadConst, 0, 1),   // r0 = 1
+            Instruction::ab(Opcode::SetGlobal, 0, 0),   // g = r0
+            Instruction::ab(Opcode::GetGlobal, 1, 0),   // r1 = g (resolves the slot)
+            Instruction::a_only(Opcode::Print, 1),
+            Instruction::ab(Opcode::LoadConst, 0, 2),   // r0 = 2
+            Instruction::ab(Opcode::SetGlobal, 0, 0),   // g = r0
+            Instruction::ab(Opcode::GetGlobal, 1, 0),   // r1 = g (cached slot)
+            Instruction::a_only(Opcode::Print, 1),
+            Instruction::a_only(Opcode::Halt, 0),
+        ], consts).expect("should run without error");
+        assert_eq!(vm.output, vec!["1".to_string(), "2".to_string()]);
+
+        let err = run_main(vec![
+            Instruction::ab(Opcode::GetGlobal, 0, 0),
+            Instruction::a_only(Opcode::Halt, 0),
+        ], vec![Constant::String("missing".to_string())]).err().expect("undefined global");
+        assert!(err.contains("Undefined global: missing"), "{}", err);
+    }
+
+    #[test]
+    fn test_many_vms_share_one_program() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<SharedProgram>();
+
+        let mut program = CompiledProgram::new();
+        program.main.constants = vec![Constant::String("g".to_string()), Constant::Integer(21)];
+        program.main.instructions = vec![
+            Instruction::ab(Opcode::LoadConst, 0, 1),  // r0 = 21
+            Instruction::new(Opcode::Add, 0, 0, 0),    // r0 = r0 + r0
+            Instruction::ab(Opcode::SetGlobal, 0, 0),  // g = r0
+            Instruction::ab(Opcode::GetGlobal, 1, 0),  // r1 = g
+            Instruction::a_only(Opcode::Print, 1),
+            Instruction::a_only(Opcode::Halt, 0),
+        ];
+        let shared = Arc::new(SharedProgram::new(program));
+        let workers: Vec<_> = (0..8)
+            .map(|_| {
+                let shared = shared.clone();
+                std::thread::spawn(move || {
+                    let mut vm = VM::with_program(shared);
+                    vm.run().map(|_| vm.output)
+                })
+            })
+            .collect();
+        for worker in workers {
+            assert_eq!(worker.join().unwrap(), Ok(vec!["42".to_string()]));
+        }
+        assert_eq!(Arc::strong_count(&shared), 1);
+    }
+
+    #[test]
+    fn test_deferred_calls_run_last_first_with_their_arguments() {
+        let arena = bumpalo::Bump::new();
+        let source = "fn log(tag: str, n: int) -> int {\n    println(tag + str(n))\n    return n\n}\n\
+            fn work() -> int {\n    system.defer(log, \"first \", 1)\n    system.defer(fn(n: int) { log(\"second \", n) }, 2)\n    println(\"body\")\n    return log(\"ret \", 0)\n}\n\
+            println(work())\nprintln(\"after\")\n";
+        let program = kinetix_language::parser::Parser::new(kinetix_language::lexer::Lexer::new(source), &arena).parse_program();
+        let mut vm = VM::new(kinetix_kicomp::compiler::Compiler::new().compile(&program.statements, None).unwrap().clone());
+        vm.run().unwrap();
+        assert_eq!(vm.output, ["body", "ret 0", "second 2", "first 1", "0", "after"]);
+    }
 }
@@ -0,0 +1,179 @@
+//! Watchdog API for embedders: a heartbeat every N instructions, and a
+//! `VmHandle` that pauses, resumes or cancels a VM from another thread.
+
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+
+const RUNNING: u8 = 0;
+const PAUSED: u8 = 1;
+const CANCELLED: u8 = 2;
+
+/// What a heartbeat callback is told.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Heartbeat {
+    /// Instructions executed so far by this VM.
+    pub instructions: u64,
+    /// Frames on the call stack.
+    pub call_depth: usize,
+}
+
+/// Pauses, resumes or cancels the VMs it was handed to, from any thread.
+/// Threads a program starts with `thread.spawn` share their VM's handle.
+/// It is checked between instructions, so a builtin that blocks
+/// (`time.sleep`, `ipc.recv`, a network read) finishes first.
+#[derive(Clone, Default)]
+pub struct VmHandle {
+    shared: Arc<Shared>,
+}
+
+#[derive(Default)]
+struct Shared {
+    state: AtomicU8,
+    /// Held while changing `state`, so a VM going to sleep can't miss a wake-up.
+    lock: Mutex<()>,
+    changed: Condvar,
+}
+
+impl VmHandle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes the VMs stop before their next instruction until `resume`.
+    pub fn pause(&self) {
+        self.set(|state| if state == RUNNING { PAUSED } else { state });
+    }
+
+    pub fn resume(&self) {
+        self.set(|state| if state == PAUSED { RUNNING } else { state });
+    }
+
+    /// Makes the VMs fail with `Execution cancelled`. Cannot be undone.
+    pub fn cancel(&self) {
+        self.set(|_| CANCELLED);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.shared.state.load(Ordering::Acquire) == PAUSED
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.shared.state.load(Ordering::Acquire) == CANCELLED
+    }
+
+    fn set(&self, next: impl Fn(u8) -> u8) {
+        let _guard = self.shared.lock.lock().unwrap_or_else(|e| e.into_inner());
+        let state = self.shared.state.load(Ordering::Acquire);
+        self.shared.state.store(next(state), Ordering::Release);
+        self.shared.changed.notify_all();
+    }
+
+    /// Called by the VM before each instruction: waits out a pause and
+    /// reports a cancel.
+    pub(crate) fn check(&self) -> Result<(), String> {
+        match self.shared.state.load(Ordering::Acquire) {
+            RUNNING => return Ok(()),
+            CANCELLED => return Err(cancelled()),
+            _ => {}
+        }
+        let mut guard = self.shared.lock.lock().unwrap_or_else(|e| e.into_inner());
+        loop {
+            match self.shared.state.load(Ordering::Acquire) {
+                RUNNING => return Ok(()),
+                CANCELLED => return Err(cancelled()),
+                _ => guard = self.shared.changed.wait(guard).unwrap_or_else(|e| e.into_inner()),
+            }
+        }
+    }
+}
+
+fn cancelled() -> String {
+    "Execution cancelled".to_string()
+}
+
+type Callback = Box<dyn FnMut(&Heartbeat) + Send>;
+
+/// A VM's handle and heartbeat; the VM has none until one is asked for, so
+/// unwatched VMs pay a single branch per instruction.
+#[derive(Default)]
+pub(crate) struct Watchdog {
+    pub(crate) handle: VmHandle,
+    heartbeat: Option<(u64, Callback)>,
+    instructions: u64,
+    /// Instructions left until the next heartbeat.
+    until_beat: u64,
+}
+
+impl Watchdog {
+    pub(crate) fn with_handle(handle: VmHandle) -> Self {
+        Watchdog { handle, ..Default::default() }
+    }
+
+    pub(crate) fn set_heartbeat(&mut self, every: u64, callback: Callback) {
+        let every = every.max(1);
+        self.heartbeat = Some((every, callback));
+        self.until_beat = every;
+    }
+
+    pub(crate) fn tick(&mut self, call_depth: usize) -> Result<(), String> {
+        self.instructions += 1;
+        if let Some((every, callback)) = &mut self.heartbeat {
+            self.until_beat -= 1;
+            if self.until_beat == 0 {
+                self.until_beat = *every;
+                callback(&Heartbeat { instructions: self.instructions, call_depth });
+            }
+        }
+        self.handle.check()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vm::VM;
+    use bumpalo::Bump;
+    use kinetix_kicomp::compiler::Compiler;
+    use kinetix_language::lexer::Lexer;
+    use kinetix_language::parser::Parser;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    fn endless_vm() -> VM {
+        let arena = Bump::new();
+        let mut parser = Parser::new(Lexer::new("mut i = 0\nwhile true {\n    i = i + 1\n}\n"), &arena);
+        let program = parser.parse_program();
+        VM::new(Compiler::new().compile(&program.statements, None).unwrap().clone())
+    }
+
+    #[test]
+    fn heartbeats_pause_and_cancel_reach_a_running_vm() {
+        let mut vm = endless_vm();
+        let handle = vm.handle();
+        let (beats, received) = mpsc::channel();
+        vm.set_heartbeat(1000, move |beat| {
+            let _ = beats.send(*beat);
+        });
+        let running = std::thread::spawn(move || vm.run());
+
+        let first = received.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!((first.instructions, first.call_depth), (1000, 1));
+        assert_eq!(received.recv_timeout(Duration::from_secs(10)).unwrap().instructions, 2000);
+
+        handle.pause();
+        assert!(handle.is_paused());
+        // At most the beat already under way arrives, then the VM is stopped
+        std::thread::sleep(Duration::from_millis(50));
+        let _ = received.try_iter().count();
+        assert!(received.recv_timeout(Duration::from_millis(100)).is_err());
+
+        handle.resume();
+        assert!(received.recv_timeout(Duration::from_secs(10)).is_ok());
+
+        handle.pause();
+        handle.cancel();
+        handle.resume();
+        assert!(handle.is_cancelled());
+        let error = running.join().unwrap().unwrap_err();
+        assert!(error.ends_with("Execution cancelled"), "{}", error);
+    }
+}
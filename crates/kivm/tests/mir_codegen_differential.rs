@@ -148,3 +148,10 @@ fn nested_break_continue() {
 fn array_index_and_len() {
     assert_same_output("let arr = [1, 2, 3, 4]\nprintln(arr[2])\nprintln(len(arr))");
 }
+
+#[test]
+fn nested_array_literals_and_call_elements() {
+    let src = "fn sq(x: int) -> int {\n    return x * x\n}\nprintln([[1, 2], [3, 4]])\nprintln([sq(3), sq(4)])";
+    assert_same_output(src);
+    assert_eq!(run_and_capture_output(compile_via_ast(src)), ["[[1, 2], [3, 4]]", "[9, 16]"]);
+}
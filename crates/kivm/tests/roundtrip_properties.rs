@@ -0,0 +1,307 @@
+/// Round-trip property tests over randomly generated programs.
+///
+/// Each case builds a small, well-typed program (integer `let`/`mut`,
+/// arithmetic, `if`/`else`, `for` over a range, calls to generated
+/// functions) from a seeded RNG and checks:
+///
+/// - **parse -> format -> parse**: the program rendered with a different
+///   layout (extra parentheses, spacing, trailing comments) parses to the
+///   same AST as its canonical rendering. The re-layout stands in for the
+///   source formatter until one exists; once it does, its output goes
+///   through the same `assert_same_ast`.
+/// - **compile -> serialize -> deserialize**: the compiled program survives
+///   a `.exki` write/read unchanged and runs to the same output. A textual
+///   disassembly round-trip belongs next to it once disasm lands.
+/// - **optimize**: the program runs to the same output after the `-O2`
+///   passes (the generated functions are all inlining candidates).
+///
+/// `KINETIX_ROUNDTRIP_CASES` (default 128) sets the number of cases and
+/// `KINETIX_ROUNDTRIP_SEED` replays a single failing case; every failure
+/// message names its seed and source.
+use bumpalo::Bump;
+use kinetix_kicomp::compiler::Compiler;
+use kinetix_kicomp::exn;
+use kinetix_kicomp::ir::CompiledProgram;
+use kinetix_language::lexer::Lexer;
+use kinetix_language::parser::Parser;
+use kinetix_kivm::vm::VM;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// Values are kept small (`% 1000` on every stored value, literal-only
+/// multipliers) so no generated program can overflow an `int`.
+enum Expr {
+    Lit(i64),
+    Var(String),
+    Bin(Box<Expr>, &'static str, Box<Expr>),
+    Call(String, Box<Expr>, Box<Expr>),
+}
+
+enum Stmt {
+    Let { name: String, mutable: bool, value: Expr },
+    Print(Expr),
+    If { cond: (Expr, &'static str, Expr), then: Expr, otherwise: Expr },
+    For { acc: String, end: i64, body: Expr },
+}
+
+struct Program {
+    functions: Vec<(String, Expr)>,
+    statements: Vec<Stmt>,
+}
+
+struct Gen {
+    rng: StdRng,
+    vars: Vec<String>,
+    functions: Vec<String>,
+}
+
+impl Gen {
+    fn expr(&mut self, depth: u32) -> Expr {
+        let leaf = depth == 0 || self.rng.gen_bool(0.3);
+        if leaf {
+            if !self.vars.is_empty() && self.rng.gen_bool(0.6) {
+                let i = self.rng.gen_range(0..self.vars.len());
+                return Expr::Var(self.vars[i].clone());
+            }
+            return Expr::Lit(self.rng.gen_range(0..50));
+        }
+        match self.rng.gen_range(0..5) {
+            0 if !self.functions.is_empty() => {
+                let f = self.functions[self.rng.gen_range(0..self.functions.len())].clone();
+                Expr::Call(f, Box::new(self.expr(depth - 1)), Box::new(self.expr(depth - 1)))
+            }
+            1 => Expr::Bin(Box::new(self.expr(depth - 1)), "*", Box::new(Expr::Lit(self.rng.gen_range(0..10)))),
+            2 => Expr::Bin(Box::new(self.expr(depth - 1)), "%", Box::new(Expr::Lit(self.rng.gen_range(1..20)))),
+            3 => Expr::Bin(Box::new(self.expr(depth - 1)), "-", Box::new(self.expr(depth - 1))),
+            _ => Expr::Bin(Box::new(self.expr(depth - 1)), "+", Box::new(self.expr(depth - 1))),
+        }
+    }
+
+    fn bounded(&mut self, depth: u32) -> Expr {
+        Expr::Bin(Box::new(self.expr(depth)), "%", Box::new(Expr::Lit(1000)))
+    }
+
+    fn program(seed: u64) -> Program {
+        let mut g = Gen { rng: StdRng::seed_from_u64(seed), vars: Vec::new(), functions: Vec::new() };
+        let mut functions = Vec::new();
+        for i in 0..g.rng.gen_range(0..3) {
+            g.vars = vec!["a".into(), "b".into()];
+            let body = g.bounded(2);
+            let name = format!("f{}", i);
+            g.functions.push(name.clone());
+            functions.push((name, body));
+        }
+        g.vars.clear();
+
+        let mut statements = Vec::new();
+        for i in 0..g.rng.gen_range(1..12) {
+            let stmt = match g.rng.gen_range(0..5) {
+                0 | 1 => {
+                    let value = g.bounded(3);
+                    let name = format!("v{}", i);
+                    g.vars.push(name.clone());
+                    Stmt::Let { name, mutable: g.rng.gen_bool(0.3), value }
+                }
+                2 => Stmt::Print(g.expr(3)),
+                3 => {
+                    let ops = ["<", ">", "==", "!=", "<=", ">="];
+                    let cond = (g.expr(2), ops[g.rng.gen_range(0..ops.len())], g.expr(2));
+                    Stmt::If { cond, then: g.expr(2), otherwise: g.expr(2) }
+                }
+                _ => {
+                    let acc = format!("acc{}", i);
+                    let end = g.rng.gen_range(0..8);
+                    g.vars.push("i".into());
+                    let body = g.bounded(2);
+                    g.vars.pop();
+                    g.vars.push(acc.clone());
+                    Stmt::For { acc, end, body }
+                }
+            };
+            statements.push(stmt);
+        }
+        Program { functions, statements }
+    }
+}
+
+/// How a program is laid out. `Canonical` is the plain, fully parenthesized
+/// rendering; `Noisy` varies everything the parser should ignore.
+enum Layout<'r> {
+    Canonical,
+    Noisy(&'r mut StdRng),
+}
+
+impl Layout<'_> {
+    fn space(&mut self) -> &'static str {
+        match self {
+            Layout::Canonical => " ",
+            Layout::Noisy(rng) => ["", " ", "  "][rng.gen_range(0..3)],
+        }
+    }
+
+    fn chance(&mut self, p: f64) -> bool {
+        match self {
+            Layout::Canonical => false,
+            Layout::Noisy(rng) => rng.gen_bool(p),
+        }
+    }
+
+    fn comment(&mut self) -> &'static str {
+        if self.chance(0.3) { " // noise" } else { "" }
+    }
+
+    fn expr(&mut self, e: &Expr) -> String {
+        let text = match e {
+            Expr::Lit(n) => n.to_string(),
+            Expr::Var(v) => v.clone(),
+            Expr::Bin(l, op, r) => {
+                let (l, r) = (self.expr(l), self.expr(r));
+                let (s1, s2) = (self.space(), self.space());
+                format!("({}{}{}{}{})", l, s1, op, s2, r)
+            }
+            Expr::Call(f, a, b) => {
+                let (a, b) = (self.expr(a), self.expr(b));
+                format!("{}({},{}{})", f, a, self.space(), b)
+            }
+        };
+        if self.chance(0.2) { format!("({})", text) } else { text }
+    }
+
+    fn render(&mut self, p: &Program) -> String {
+        let mut out = String::new();
+        for (name, body) in &p.functions {
+            let body = self.expr(body);
+            let c = self.comment();
+            out.push_str(&format!("fn {}(a: int, b: int) -> int {{{}\n    return {}\n}}\n", name, c, body));
+        }
+        for stmt in &p.statements {
+            match stmt {
+                Stmt::Let { name, mutable, value } => {
+                    let kw = if *mutable { "mut" } else { "let" };
+                    let value = self.expr(value);
+                    let c = self.comment();
+                    out.push_str(&format!("{} {} ={}{}{}\n", kw, name, self.space(), value, c));
+                }
+                Stmt::Print(e) => {
+                    let e = self.expr(e);
+                    let c = self.comment();
+                    out.push_str(&format!("println({}){}\n", e, c));
+                }
+                Stmt::If { cond: (l, op, r), then, otherwise } => {
+                    let (l, r) = (self.expr(l), self.expr(r));
+                    let (then, otherwise) = (self.expr(then), self.expr(otherwise));
+                    let s = self.space();
+                    out.push_str(&format!(
+                        "if {} {}{}{} {{\n    println({})\n}} else {{\n    println({})\n}}\n",
+                        l, op, s, r, then, otherwise
+                    ));
+                }
+                Stmt::For { acc, end, body } => {
+                    let body = self.expr(body);
+                    let c = self.comment();
+                    out.push_str(&format!(
+                        "mut {} = 0\nfor i in 0..{} {{{}\n    {} = ({} + {}) % 1000\n}}\nprintln({})\n",
+                        acc, end, c, acc, acc, body, acc
+                    ));
+                }
+            }
+        }
+        out
+    }
+}
+
+fn cases() -> Vec<u64> {
+    if let Ok(seed) = std::env::var("KINETIX_ROUNDTRIP_SEED") {
+        return vec![seed.parse().expect("KINETIX_ROUNDTRIP_SEED must be an integer")];
+    }
+    let n = std::env::var("KINETIX_ROUNDTRIP_CASES").ok().and_then(|n| n.parse().ok()).unwrap_or(128);
+    (0..n).collect()
+}
+
+fn parse_debug(src: &str, seed: u64) -> String {
+    let arena = Bump::new();
+    let mut parser = Parser::new(Lexer::new(src), &arena);
+    let program = parser.parse_program();
+    assert!(parser.errors.is_empty(), "seed {}: parse errors {:?} in:\n{}", seed, parser.errors, src);
+    without_spans(&format!("{:?}", program.statements))
+}
+
+/// Statement and expression spans record the layout the relayout is free to change.
+fn without_spans(debug: &str) -> String {
+    let mut out = String::with_capacity(debug.len());
+    let mut rest = debug;
+    while let Some(start) = rest.find(", span: Span {") {
+        out.push_str(&rest[..start]);
+        let end = rest[start..].find('}').expect("a span closes");
+        rest = &rest[start + end + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+fn assert_same_ast(original: &str, reformatted: &str, seed: u64) {
+    assert_eq!(
+        parse_debug(original, seed),
+        parse_debug(reformatted, seed),
+        "seed {}: reformatting changed the AST\n--- original\n{}--- reformatted\n{}",
+        seed, original, reformatted
+    );
+}
+
+fn compile(src: &str, seed: u64) -> CompiledProgram {
+    let arena = Bump::new();
+    let mut parser = Parser::new(Lexer::new(src), &arena);
+    let program = parser.parse_program();
+    Compiler::new()
+        .compile(&program.statements, None)
+        .cloned()
+        .unwrap_or_else(|e| panic!("seed {}: compile error {:?} in:\n{}", seed, e, src))
+}
+
+fn run(program: CompiledProgram, src: &str, seed: u64) -> Vec<String> {
+    let mut vm = VM::new(program);
+    vm.run().unwrap_or_else(|e| panic!("seed {}: runtime error {} in:\n{}", seed, e, src));
+    vm.output
+}
+
+#[test]
+fn relayout_parses_to_the_same_ast() {
+    for seed in cases() {
+        let program = Gen::program(seed);
+        let canonical = Layout::Canonical.render(&program);
+        let mut rng = StdRng::seed_from_u64(seed ^ 0x5eed);
+        let noisy = Layout::Noisy(&mut rng).render(&program);
+        assert_same_ast(&canonical, &noisy, seed);
+    }
+}
+
+#[test]
+fn bytecode_survives_exki_roundtrip() {
+    for seed in cases() {
+        let src = Layout::Canonical.render(&Gen::program(seed));
+        let compiled = compile(&src, seed);
+
+        let mut bytes = Vec::new();
+        exn::write_exn(&mut bytes, &compiled).expect("write .exki");
+        let reloaded = exn::read_exn(&mut bytes.as_slice()).expect("read .exki");
+        assert_eq!(
+            serde_json::to_value(&compiled).unwrap(),
+            serde_json::to_value(&reloaded).unwrap(),
+            "seed {}: .exki round-trip changed the program for:\n{}",
+            seed, src
+        );
+        assert_eq!(run(compiled, &src, seed), run(reloaded, &src, seed), "seed {}: output differs for:\n{}", seed, src);
+    }
+}
+
+#[test]
+fn optimized_bytecode_runs_to_the_same_output() {
+    for seed in cases() {
+        let src = Layout::Canonical.render(&Gen::program(seed));
+        let compiled = compile(&src, seed);
+        let mut optimized = compiled.clone();
+        kinetix_kicomp::opt::optimize(&mut optimized);
+        kinetix_kicomp::opt::inline_small_functions(&mut optimized);
+        assert_eq!(run(compiled, &src, seed), run(optimized, &src, seed), "seed {}: -O2 changed the output of:\n{}", seed, src);
+    }
+}
@@ -1,120 +1,181 @@
 #![allow(dead_code)]
 
+use crate::span::Span;
+
+/// Every statement carries the `line` it starts on and its `span`, from its
+/// first token to its last (`Span::default()` for ones the parser or a later
+/// pass synthesized).
 #[derive(Debug)]
 pub enum Statement<'a> {
     Let {
-        name: String,
+        name: &'a str,
         mutable: bool,
         type_hint: Option<String>,
         value: Expression<'a>,
+        /// Declared `pub mut` at the top level: shared with functions, which
+        /// may assign it without a warning (see `kicomp::global_lint`).
+        public: bool,
         line: usize,
+        span: Span,
     },
     State {
-        name: String,
+        name: &'a str,
         type_hint: Option<String>,
         value: Expression<'a>,
         line: usize,
+        span: Span,
     },
     Computed {
-        name: String,
+        name: &'a str,
         type_hint: Option<String>,
         value: Expression<'a>,
         line: usize,
+        span: Span,
     },
     Effect {
         dependencies: Vec<String>,
         body: &'a Statement<'a>,
         line: usize,
+        span: Span,
     },
     Return {
         value: Option<Expression<'a>>,
         line: usize,
+        span: Span,
     },
     Expression {
         expression: Expression<'a>,
         line: usize,
+        span: Span,
     },
     Block {
         statements: Vec<Statement<'a>>,
         line: usize,
+        span: Span,
     },
     Function {
-        name: String,
+        name: &'a str,
         parameters: Vec<(String, String)>, // (name, type)
         body: &'a Statement<'a>, // Block
         return_type: String,
+        /// Declared `pub fn`: exported when the program is linked as a bundle.
+        public: bool,
         line: usize,
+        span: Span,
     },
     While {
         condition: Expression<'a>,
         body: &'a Statement<'a>,
+        /// `'outer: while ...` -- target name for a labeled `break`/`continue`.
+        label: Option<&'a str>,
         line: usize,
+        span: Span,
     },
     For {
-        iterator: String,
+        iterator: &'a str,
         range: Expression<'a>,
         body: &'a Statement<'a>,
+        label: Option<&'a str>,
         line: usize,
+        span: Span,
     },
     Class {
-        name: String,
-        parent: Option<String>,
+        name: &'a str,
+        parent: Option<&'a str>,
         methods: Vec<Statement<'a>>, // Function statements
-        fields: Vec<(bool, String, String)>, // (is_public, name, type)
+        fields: Vec<(bool, String, String, usize)>, // (is_public, name, type, line)
+        /// Class-level constants (`let MAX = 10`), read as `ClassName.MAX`.
+        constants: Vec<Statement<'a>>, // Let statements
         line: usize,
+        span: Span,
     },
     Struct {
-        name: String,
+        name: &'a str,
         fields: Vec<(String, String)>,
         line: usize,
+        span: Span,
     },
     Include {
         path: String,
         alias: Option<String>,
         line: usize,
+        span: Span,
     },
     Version {
         build: i64,
         line: usize,
+        span: Span,
+    },
+    /// `import bundle "lib.exki"`: links a precompiled bytecode library.
+    ImportBundle {
+        path: String,
+        line: usize,
+        span: Span,
+    },
+    /// `import util.math as m`: the source module `util/math.kix`, compiled
+    /// as its own unit; its `pub fn`s are called as `m.name(...)`. Without
+    /// `as`, the alias is the last segment of the path.
+    Import {
+        module: String,
+        alias: Option<String>,
+        line: usize,
+        span: Span,
     },
     Enum {
-        name: String,
+        name: &'a str,
         generics: Vec<String>,
         variants: Vec<(String, Option<String>)>, // VariantName(OptionalPayloadType)
         line: usize,
+        span: Span,
     },
     Trait {
-        name: String,
+        name: &'a str,
         generics: Vec<String>,
         methods: Vec<(String, Vec<(String, String)>, String)>, // MethodName, Params, ReturnType
         line: usize,
+        span: Span,
     },
     Impl {
-        trait_name: Option<String>,
-        target_name: String,
+        trait_name: Option<&'a str>,
+        target_name: &'a str,
         generics: Vec<String>,
         methods: Vec<Statement<'a>>, // Functions
         line: usize,
+        span: Span,
     },
-    Break { line: usize },
-    Continue { line: usize },
+    /// `break` / `break 'outer`
+    Break { label: Option<&'a str>, line: usize, span: Span },
+    /// `continue` / `continue 'outer`
+    Continue { label: Option<&'a str>, line: usize, span: Span },
+}
+
+/// An expression and its `span`, from its first token to its last
+/// (`Span::default()` for ones the parser or a later pass synthesized).
+#[derive(Debug)]
+pub struct Expression<'a> {
+    pub kind: ExpressionKind<'a>,
+    pub span: Span,
 }
 
+/// Expressions are the bulk of any program, so their names, operators and
+/// string literals are `&'a str` slices living in the parser's arena rather
+/// than one heap `String` per node. Declared names in `Statement` are too;
+/// each distinct one is stored once (see `Parser::intern`).
 #[derive(Debug)]
-pub enum Expression<'a> {
-    Identifier(String),
+pub enum ExpressionKind<'a> {
+    Identifier(&'a str),
     Integer(i64),
     Float(f64),
-    String(String),
+    String(&'a str),
     Boolean(bool),
     Null,
     Prefix {
-        operator: String,
+        operator: &'a str,
         right: &'a Expression<'a>,
     },
     Infix {
         left: &'a Expression<'a>,
-        operator: String,
+        operator: &'a str,
         right: &'a Expression<'a>,
     },
     If {
@@ -127,7 +188,7 @@ pub enum Expression<'a> {
         arguments: Vec<Expression<'a>>,
     },
     StructLiteral {
-        name: String,
+        name: &'a str,
         fields: Vec<(String, Expression<'a>)>,
     },
     FunctionLiteral {
@@ -143,7 +204,7 @@ pub enum Expression<'a> {
     },
     MemberAccess {
         object: &'a Expression<'a>,
-        member: String,
+        member: &'a str,
     },
     Assign {
         target: &'a Expression<'a>,
@@ -152,6 +213,14 @@ pub enum Expression<'a> {
     Match {
         value: &'a Expression<'a>,
         arms: Vec<(Expression<'a>, &'a Statement<'a>)>, 
+        /// `match x as { int(n) => ..., Point(p) => ..., _ => ... }`: each arm
+        /// names a type (optionally binding the narrowed value) instead of a
+        /// value pattern, and is selected by the value's runtime type.
+        by_type: bool,
+        /// The closing `}`, where a fix-it adds missing arms;
+        /// `Span::default()` for the matches `if let`, `while let` and `?`
+        /// desugar to, which always end in `_`.
+        end: Span,
     },
     Range {
         start: &'a Expression<'a>,
@@ -165,10 +234,62 @@ pub enum Expression<'a> {
 #[derive(Debug)]
 pub struct Program<'a> {
     pub statements: Vec<Statement<'a>>,
+    /// Top-level `test "name" { ... }` blocks. They are kept out of
+    /// `statements`, so running or compiling the file ignores them; only
+    /// `kivm test` runs them.
+    pub tests: Vec<TestBlock<'a>>,
+    /// The file says `#strict`: missing type annotations are reported (see
+    /// `kicomp::strict`).
+    pub strict: bool,
+}
+
+impl<'a> Statement<'a> {
+    pub fn span(&self) -> Span {
+        match self {
+            Statement::Let { span, .. } | Statement::State { span, .. } | Statement::Computed { span, .. }
+            | Statement::Effect { span, .. } | Statement::Return { span, .. } | Statement::Expression { span, .. }
+            | Statement::Block { span, .. } | Statement::Function { span, .. } | Statement::While { span, .. }
+            | Statement::For { span, .. } | Statement::Class { span, .. } | Statement::Struct { span, .. }
+            | Statement::Include { span, .. } | Statement::Version { span, .. } | Statement::ImportBundle { span, .. }
+            | Statement::Import { span, .. } | Statement::Enum { span, .. } | Statement::Trait { span, .. }
+            | Statement::Impl { span, .. } | Statement::Break { span, .. } | Statement::Continue { span, .. } => *span,
+        }
+    }
+
+    pub fn span_mut(&mut self) -> &mut Span {
+        match self {
+            Statement::Let { span, .. } | Statement::State { span, .. } | Statement::Computed { span, .. }
+            | Statement::Effect { span, .. } | Statement::Return { span, .. } | Statement::Expression { span, .. }
+            | Statement::Block { span, .. } | Statement::Function { span, .. } | Statement::While { span, .. }
+            | Statement::For { span, .. } | Statement::Class { span, .. } | Statement::Struct { span, .. }
+            | Statement::Include { span, .. } | Statement::Version { span, .. } | Statement::ImportBundle { span, .. }
+            | Statement::Import { span, .. } | Statement::Enum { span, .. } | Statement::Trait { span, .. }
+            | Statement::Impl { span, .. } | Statement::Break { span, .. } | Statement::Continue { span, .. } => span,
+        }
+    }
+}
+
+impl<'a> Expression<'a> {
+    pub fn new(kind: ExpressionKind<'a>, span: Span) -> Self {
+        Expression { kind, span }
+    }
+
+    /// A node with no source text of its own.
+    pub fn synthetic(kind: ExpressionKind<'a>) -> Self {
+        Expression { kind, span: Span::default() }
+    }
 }
 
 impl<'a> Program<'a> {
     pub fn new() -> Self {
-        Program { statements: vec![] }
+        Program { statements: vec![], tests: vec![], strict: false }
     }
 }
+
+/// A `test "name" { ... }` block.
+#[derive(Debug)]
+pub struct TestBlock<'a> {
+    pub name: String,
+    pub body: &'a Statement<'a>, // Block
+    pub line: usize,
+}
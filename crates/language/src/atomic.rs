@@ -0,0 +1,75 @@
+//! Crash-safe file replacement, shared by the compiler cache, the `fs`
+//! builtins and the installer.
+
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+/// Replaces `path` with `bytes` all at once: written to a temporary file
+/// next to it, flushed to disk, then renamed over it, so a crash or power
+/// loss leaves the old file or the new one, never a truncated mix. The
+/// temporary file takes the permissions of the file it replaces. On Windows
+/// a running executable can't be replaced but can be renamed, so it is
+/// moved aside to `<stem>.old` first (removed by the next replace).
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    let name = path.file_name().ok_or_else(|| std::io::Error::other(format!("{:?} is not a file path", path)))?;
+    let tmp = path.with_file_name(format!(".{}.tmp{}", name.to_string_lossy(), std::process::id()));
+    let written = (|| {
+        let mut file = fs::File::create(&tmp)?;
+        file.write_all(bytes)?;
+        if let Ok(meta) = fs::metadata(path) {
+            file.set_permissions(meta.permissions())?;
+        }
+        file.sync_all()?;
+        replace_file(&tmp, path)
+    })();
+    if written.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    written?;
+    // The rename itself is only durable once the directory is flushed
+    #[cfg(unix)]
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+#[cfg(not(windows))]
+fn replace_file(tmp: &Path, path: &Path) -> std::io::Result<()> {
+    fs::rename(tmp, path)
+}
+
+#[cfg(windows)]
+fn replace_file(tmp: &Path, path: &Path) -> std::io::Result<()> {
+    let old = path.with_extension("old");
+    let _ = fs::remove_file(&old);
+    match fs::rename(tmp, path) {
+        Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied && path.exists() => {
+            fs::rename(path, &old)?;
+            fs::rename(tmp, path)
+        }
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaces_the_file_and_leaves_no_temporary() {
+        let dir = std::env::temp_dir().join(format!("kinetix_atomic_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("cache.bin");
+
+        write_atomic(&path, b"first").unwrap();
+        write_atomic(&path, b"2nd").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"2nd");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        assert!(write_atomic(&dir.join("missing").join("x"), b"x").is_err());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
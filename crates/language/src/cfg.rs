@@ -0,0 +1,120 @@
+//! Compile-time configuration: what `#if os(windows)` and
+//! `#if feature("debug")` test, and the `--define` constants.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::RwLock;
+
+/// The parser resolves `#if` / `#elif` / `#else` / `#endif` against it while
+/// parsing, so a branch that is not taken never reaches the AST (its
+/// `#include`s are never opened), and substitutes `#define` constants at
+/// each use, so diagnostics keep the use's own line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cfg {
+    pub os: String,
+    pub arch: String,
+    pub features: BTreeSet<String>,
+    /// `--define` constants; they override a `#define` of the same name.
+    pub defines: BTreeMap<String, DefineValue>,
+}
+
+/// The value of a `#define` or `--define`. Only constants, so a substitution
+/// can never capture or be shadowed by a name at its use site.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DefineValue {
+    Int(i64),
+    Float(f64),
+    Str(String),
+    Bool(bool),
+    Null,
+}
+
+impl DefineValue {
+    /// Reads a `--define` value: a number, `true`/`false`/`null`, or else a
+    /// string (surrounding double quotes optional).
+    pub fn from_flag(raw: &str) -> Self {
+        let raw = raw.trim();
+        if let Ok(n) = raw.parse() {
+            return DefineValue::Int(n);
+        }
+        if let Ok(f) = raw.parse() {
+            return DefineValue::Float(f);
+        }
+        match raw {
+            "true" => DefineValue::Bool(true),
+            "false" => DefineValue::Bool(false),
+            "null" => DefineValue::Null,
+            _ => DefineValue::Str(raw.strip_prefix('"').and_then(|r| r.strip_suffix('"')).unwrap_or(raw).to_string()),
+        }
+    }
+}
+
+impl Cfg {
+    /// The host's `os`/`arch` and no features.
+    pub fn host() -> Self {
+        Cfg {
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            features: BTreeSet::new(),
+            defines: BTreeMap::new(),
+        }
+    }
+
+    /// Adds a `--define NAME=VALUE` constant.
+    pub fn define(&mut self, flag: &str) -> Result<(), String> {
+        let (name, value) = flag.split_once('=').ok_or_else(|| format!("--define {}: expected NAME=VALUE", flag))?;
+        let name = name.trim();
+        let valid = name.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid {
+            return Err(format!("--define {}: '{}' is not a valid name", flag, name));
+        }
+        self.defines.insert(name.to_string(), DefineValue::from_flag(value));
+        Ok(())
+    }
+
+    /// The host configuration with `--cfg` flags applied: `os=NAME` and
+    /// `arch=NAME` override the target, `feature=NAME` or a bare `NAME`
+    /// enables a feature.
+    pub fn from_flags(flags: &[String]) -> Result<Self, String> {
+        let mut cfg = Cfg::host();
+        for flag in flags {
+            let (key, value) = flag.split_once('=').unwrap_or(("feature", flag.as_str()));
+            let value = value.trim().trim_matches('"');
+            if value.is_empty() {
+                return Err(format!("--cfg {}: missing a name", flag));
+            }
+            match key.trim() {
+                "os" => cfg.os = value.to_string(),
+                "arch" => cfg.arch = value.to_string(),
+                "feature" => {
+                    cfg.features.insert(value.to_string());
+                }
+                other => return Err(format!("--cfg {}: unknown key '{}' (expected os, arch or feature)", flag, other)),
+            }
+        }
+        Ok(cfg)
+    }
+
+    /// Whether the predicate `name(value)` holds.
+    pub fn test(&self, name: &str, value: &str) -> Result<bool, String> {
+        match name {
+            "os" => Ok(self.os == value),
+            "arch" => Ok(self.arch == value),
+            "feature" => Ok(self.features.contains(value)),
+            other => Err(format!("Unknown #if predicate '{}' (expected os, arch, feature, not, any or all)", other)),
+        }
+    }
+}
+
+static DEFAULT: RwLock<Option<Cfg>> = RwLock::new(None);
+
+/// Sets the configuration every parser created afterwards starts with; the
+/// CLI calls it from `--cfg` and `--define`.
+pub fn set_default(cfg: Cfg) {
+    *DEFAULT.write().unwrap_or_else(|e| e.into_inner()) = Some(cfg);
+}
+
+/// The configuration set by `set_default`, or the host's.
+pub fn current() -> Cfg {
+    DEFAULT.read().unwrap_or_else(|e| e.into_inner()).clone().unwrap_or_else(Cfg::host)
+}
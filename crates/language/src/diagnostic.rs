@@ -0,0 +1,236 @@
+//! Diagnostics: what the compiler passes report, rendered by the CLI with a
+//! source snippet, published by the language server and serialized as JSON.
+
+use crate::span::Span;
+use serde::Serialize;
+use std::fmt;
+
+/// Error and warning codes, one per kind of check.
+pub mod codes {
+    /// Syntax: the parser, `#if`/`#define`.
+    pub const PARSE: &str = "E0001";
+    /// `#version` requirements.
+    pub const VERSION: &str = "E0002";
+    /// `import bundle` and source modules.
+    pub const LINK: &str = "E0003";
+    /// Undeclared, duplicate or misused names.
+    pub const SYMBOL: &str = "E0004";
+    /// Traits and their implementations.
+    pub const TRAIT: &str = "E0005";
+    /// Type mismatches, including ones found while normalizing types.
+    pub const TYPE: &str = "E0006";
+    /// Method calls that don't resolve.
+    pub const METHOD: &str = "E0007";
+    /// `match` arms that don't cover every case.
+    pub const EXHAUSTIVENESS: &str = "E0008";
+    /// Builtins the sandbox doesn't grant.
+    pub const CAPABILITY: &str = "E0009";
+    /// Moves, borrows and uninitialized reads.
+    pub const BORROW: &str = "E0010";
+    /// A consistency check inside the compiler failed: a compiler bug, not
+    /// a problem with the program.
+    pub const INTERNAL: &str = "E0011";
+    /// Type annotations strict mode requires (`--strict`, `#strict`).
+    pub const STRICT: &str = "E0012";
+    /// Programs the bytecode compiler can't emit: too many registers or
+    /// constants, `break` outside a loop.
+    pub const CODEGEN: &str = "E0013";
+    /// Lints: suspicious but valid code.
+    pub const LINT: &str = "W0001";
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Diagnostic {
+    /// Stable across passes and wordings: `E0006` is a type error wherever
+    /// it comes from.
+    pub code: &'static str,
+    pub severity: Severity,
+    pub message: String,
+    /// The file the spans point into, when it isn't the one being compiled
+    /// (an `#include`d file, a module).
+    pub file: Option<String>,
+    /// Where the problem is, primary first. A span that only knows its line
+    /// has column 0.
+    pub spans: Vec<Span>,
+    pub notes: Vec<String>,
+    pub help: Option<String>,
+    /// Edits that resolve it, best first; `kivm fix` applies the first.
+    pub fixes: Vec<Fix>,
+    /// What it is about, for a later pass that adds to it.
+    #[serde(skip)]
+    pub subject: Option<Subject>,
+}
+
+/// The name a symbol error is about.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Subject {
+    /// A name that is not declared.
+    Undeclared(String),
+    /// `member`, which module `module` doesn't export.
+    ModuleMember { module: String, member: String },
+}
+
+/// An edit that resolves a diagnostic: `insert` goes in at `at`'s line and
+/// column, in place of the `replace` characters there.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Fix {
+    /// What it does, e.g. "add the missing arms".
+    pub message: String,
+    pub at: Span,
+    /// How many characters, from `at` on the same line, it replaces; 0 to
+    /// only insert.
+    pub replace: usize,
+    pub insert: String,
+}
+
+impl Fix {
+    /// `source` with `fixes` applied. Positions are those of the original
+    /// source; a fix whose line doesn't exist is skipped.
+    pub fn apply(source: &str, fixes: &[&Fix]) -> String {
+        let mut edits: Vec<(usize, usize, &str)> = fixes.iter()
+            .filter_map(|fix| {
+                let line_start = match fix.at.line {
+                    0 => return None,
+                    1 => 0,
+                    n => source.match_indices('\n').nth(n - 2)?.0 + 1,
+                };
+                let line = source[line_start..].split('\n').next().unwrap_or("");
+                let offset = |column: usize| line.char_indices().nth(column).map_or(line.len(), |(i, _)| i);
+                let column = fix.at.column.saturating_sub(1);
+                Some((line_start + offset(column), line_start + offset(column + fix.replace), fix.insert.as_str()))
+            })
+            .collect();
+        // Last first, so the offsets before each edit stay valid; of two
+        // fixes at one place, the later one first, so they end up in order.
+        edits.reverse();
+        edits.sort_by_key(|&(start, _, _)| std::cmp::Reverse(start));
+        let mut fixed = source.to_string();
+        for (start, end, insert) in edits {
+            fixed.replace_range(start..end, insert);
+        }
+        fixed
+    }
+}
+
+impl Diagnostic {
+    pub fn error(code: &'static str, message: impl Into<String>) -> Self {
+        Self::new(code, Severity::Error, message.into())
+    }
+
+    pub fn warning(code: &'static str, message: impl Into<String>) -> Self {
+        Self::new(code, Severity::Warning, message.into())
+    }
+
+    fn new(code: &'static str, severity: Severity, message: String) -> Self {
+        Diagnostic { code, severity, message, file: None, spans: Vec::new(), notes: Vec::new(), help: None, fixes: Vec::new(), subject: None }
+    }
+
+    /// Adds a span; the first one added is the primary one. A span on line
+    /// 0 is no location and is left out.
+    pub fn at(mut self, span: Span) -> Self {
+        if span.line > 0 {
+            self.spans.push(span);
+        }
+        self
+    }
+
+    pub fn in_file(mut self, file: impl Into<String>) -> Self {
+        self.file = Some(file.into());
+        self
+    }
+
+    /// Points at line `line`, column unknown.
+    pub fn at_line(self, line: usize) -> Self {
+        self.at(Span { line, ..Span::default() })
+    }
+
+    pub fn about(mut self, subject: Subject) -> Self {
+        self.subject = Some(subject);
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    /// Adds a fix inserting `insert` at `at`.
+    pub fn with_fix(self, message: impl Into<String>, at: Span, insert: impl Into<String>) -> Self {
+        self.with_replacement(message, at, 0, insert)
+    }
+
+    /// Adds a fix replacing the `replace` characters at `at` with `insert`.
+    pub fn with_replacement(mut self, message: impl Into<String>, at: Span, replace: usize, insert: impl Into<String>) -> Self {
+        self.fixes.push(Fix { message: message.into(), at, replace, insert: insert.into() });
+        self
+    }
+
+    /// The primary span.
+    pub fn span(&self) -> Option<Span> {
+        self.spans.first().copied()
+    }
+}
+
+/// The `Line N:C: message` form the passes used to print.
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.span() {
+            Some(span) if span.column > 0 => write!(f, "Line {}: {}", span, self.message),
+            Some(span) => write!(f, "Line {}: {}", span.line, self.message),
+            None => f.write_str(&self.message),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diagnostics_print_their_location() {
+        assert_eq!(Diagnostic::error(codes::SYMBOL, "Undeclared variable: 'x'").at_line(7).to_string(), "Line 7: Undeclared variable: 'x'");
+        assert_eq!(Diagnostic::error(codes::TYPE, "Infinite type").to_string(), "Infinite type");
+        // Line 0 is how desugared code says "no particular line"
+        assert_eq!(Diagnostic::error(codes::TYPE, "too deep").at_line(0).span(), None);
+
+        let diagnostic = Diagnostic::error(codes::PARSE, "Expected ')'")
+            .at(Span { start: 4, end: 5, line: 1, column: 5 })
+            .with_help("close the call");
+        assert_eq!(diagnostic.to_string(), "Line 1:5: Expected ')'");
+        assert_eq!(diagnostic.help.as_deref(), Some("close the call"));
+    }
+
+    #[test]
+    fn fixes_insert_at_their_line_and_column() {
+        let at = |line, column| Span { line, column, ..Span::default() };
+        let fix = |line, column, insert: &str| Fix { message: String::new(), at: at(line, column), replace: 0, insert: insert.to_string() };
+        let (a, b, c) = (fix(2, 1, "  B => 2,\n"), fix(2, 1, "  C => 3,\n"), fix(1, 3, "é"));
+        assert_eq!(Fix::apply("mé {\n}\n", &[&a, &b, &c]), "méé {\n  B => 2,\n  C => 3,\n}\n");
+        // Past the end of its line: at the end of that line, not the next
+        assert_eq!(Fix::apply("ab\ncd", &[&fix(1, 9, "!")]), "ab!\ncd");
+        assert_eq!(Fix::apply("ab", &[&fix(3, 1, "!")]), "ab");
+        let rename = Fix { replace: 7, ..fix(1, 9, "net.http.get") };
+        assert_eq!(Fix::apply("let p = net.get(u)", &[&rename]), "let p = net.http.get(u)");
+    }
+}
@@ -1,6 +1,10 @@
 
-#[derive(Debug, PartialEq, Clone)]
-pub enum Token {
+use crate::span::{Span, Spanned};
+
+/// Lexer token. Identifier, label and string payloads borrow straight from
+/// the source text, so tokens are `Copy` and lexing allocates nothing.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Token<'a> {
     // Keywords
     Let,
     Mut,
@@ -31,11 +35,13 @@ pub enum Token {
     Effect,
     
     // Literals
-    Identifier(String),
+    Identifier(&'a str),
     Integer(i64),
     Float(f64),
-    String(String),
-    BacktickString(String),
+    String(&'a str),
+    BacktickString(&'a str),
+    /// Loop label: `'outer` (name stored without the quote)
+    Label(&'a str),
     
     // Operators
     Plus,
@@ -76,12 +82,17 @@ pub enum Token {
     Illegal,
 }
 
+#[derive(Clone)]
 pub struct Lexer<'a> {
     input: &'a str,
     position: usize,
     read_position: usize,
     ch: Option<char>,
     pub line: usize,
+    /// Column of `ch` (1-based, in characters), kept as characters are read.
+    column: usize,
+    /// Where the token being scanned starts: byte offset, line, column.
+    token_start: (usize, usize, usize),
     last_was_space: bool, // Renamed from space_before_current
 }
 
@@ -93,6 +104,8 @@ impl<'a> Lexer<'a> {
             read_position: 0,
             ch: None,
             line: 1,
+            column: 0,
+            token_start: (0, 1, 1),
             last_was_space: false, // Initialized the new field
         };
         lexer.read_char();
@@ -107,6 +120,7 @@ impl<'a> Lexer<'a> {
     fn read_char(&mut self) {
         if self.ch == Some('\n') {
             self.line += 1;
+            self.column = 0;
         }
         if self.read_position >= self.input.len() {
             self.ch = None;
@@ -114,6 +128,9 @@ impl<'a> Lexer<'a> {
             self.ch = self.input[self.read_position..].chars().next();
         }
         self.position = self.read_position;
+        if self.position <= self.input.len() {
+            self.column += 1;
+        }
         if let Some(ch) = self.ch {
             self.read_position += ch.len_utf8();
         } else {
@@ -155,9 +172,23 @@ impl<'a> Lexer<'a> {
         }
     }
 
-    pub fn next_token(&mut self) -> Token {
+    pub fn next_token(&mut self) -> Token<'a> {
+        self.next_spanned().node
+    }
+
+    /// The next token and where it is in the source.
+    pub fn next_spanned(&mut self) -> Spanned<Token<'a>> {
+        let node = self.scan();
+        let (start, line, column) = self.token_start;
+        let end = self.position.min(self.input.len());
+        Spanned { node, span: Span { start, end: end.max(start), line, column } }
+    }
+
+    fn scan(&mut self) -> Token<'a> {
         self.skip_whitespace();
-        
+        let start = self.position.min(self.input.len());
+        self.token_start = (start, self.line, self.column);
+
         let token = match self.ch {
             Some('#') => Token::Hash,
             Some('=') => {
@@ -184,7 +215,7 @@ impl<'a> Lexer<'a> {
             Some('/') => {
                 if self.peek_char() == Some('/') {
                     self.skip_comment();
-                    return self.next_token(); // Recurse after comment
+                    return self.scan(); // Recurse after comment
                 } else {
                     Token::Slash
                 }
@@ -250,10 +281,17 @@ impl<'a> Lexer<'a> {
             },
             Some('"') => return self.read_string(),
             Some('`') => return self.read_backtick_string(),
+            Some('\'') => {
+                if self.peek_char().is_some_and(is_letter) {
+                    self.read_char(); // skip the quote
+                    return Token::Label(self.read_identifier());
+                }
+                Token::Illegal
+            },
             Some(ch) => {
                 if is_letter(ch) {
                     let ident = self.read_identifier();
-                    return match ident.as_str() {
+                    return match ident {
                         "let" => Token::Let,
                         "mut" => Token::Mut,
                         "fn" => Token::Fn,
@@ -296,7 +334,7 @@ impl<'a> Lexer<'a> {
         token
     }
 
-    fn read_identifier(&mut self) -> String {
+    fn read_identifier(&mut self) -> &'a str {
         let position = self.position;
         while let Some(ch) = self.ch {
              if is_letter(ch) || ch.is_digit(10) {
@@ -305,10 +343,10 @@ impl<'a> Lexer<'a> {
                 break;
             }
         }
-        self.input[position..self.position].to_string()
+        &self.input[position..self.position]
     }
 
-    fn read_number(&mut self) -> Token {
+    fn read_number(&mut self) -> Token<'a> {
         let position = self.position;
         while let Some(ch) = self.ch {
             if ch.is_digit(10) {
@@ -340,7 +378,7 @@ impl<'a> Lexer<'a> {
         Token::Integer(num_str.parse().unwrap_or(0))
     }
 
-    fn read_string(&mut self) -> Token {
+    fn read_string(&mut self) -> Token<'a> {
         let position = self.position + 1;
         self.read_char(); // Consume opening "
         
@@ -359,10 +397,10 @@ impl<'a> Lexer<'a> {
         let str_val = &self.input[position..self.position];
         // Consume closing " so next call to next_token starts fresh
         self.read_char();
-        Token::String(str_val.to_string())
+        Token::String(str_val)
     }
 
-    fn read_backtick_string(&mut self) -> Token {
+    fn read_backtick_string(&mut self) -> Token<'a> {
         let position = self.position + 1;
         self.read_char(); // Consume opening `
         
@@ -381,7 +419,7 @@ impl<'a> Lexer<'a> {
         let str_val = &self.input[position..self.position];
         // Consume closing ` so next call to next_token starts fresh
         self.read_char();
-        Token::BacktickString(str_val.to_string())
+        Token::BacktickString(str_val)
     }
 }
 
@@ -484,6 +522,27 @@ let result = add(five, ten);
         assert_eq!(l.next_token(), Token::EOF);
     }
     
+    #[test]
+    fn test_token_spans() {
+        let input = "let é = \"hi\" // note\n  x.y";
+        let mut l = Lexer::new(input);
+        let spans: Vec<(Token, usize, usize, &str)> = std::iter::from_fn(|| {
+            let t = l.next_spanned();
+            (t.node != Token::EOF).then(|| (t.node, t.span.line, t.span.column, t.span.text(input)))
+        }).collect();
+        assert_eq!(spans, vec![
+            (Token::Let, 1, 1, "let"),
+            (Token::Identifier("é"), 1, 5, "é"),
+            (Token::Equal, 1, 7, "="),
+            (Token::String("hi"), 1, 9, "\"hi\""),
+            (Token::Identifier("x"), 2, 3, "x"),
+            (Token::Dot, 2, 4, "."),
+            (Token::Identifier("y"), 2, 5, "y"),
+        ]);
+        let eof = l.next_spanned().span;
+        assert_eq!((eof.start, eof.end, eof.line), (input.len(), input.len(), 2));
+    }
+
     #[test]
     fn test_range_vs_float() {
         let input = "0..10 3.14";
@@ -495,4 +554,15 @@ let result = add(five, ten);
         match l.next_token() { Token::Float(f) => assert!((f - 3.14).abs() < 0.001), _ => panic!("expected 3.14") }
         assert_eq!(l.next_token(), Token::EOF);
     }
+
+    #[test]
+    fn test_loop_label() {
+        let mut l = Lexer::new("'outer: while break 'outer '");
+        assert_eq!(l.next_token(), Token::Label("outer"));
+        assert_eq!(l.next_token(), Token::Colon);
+        assert_eq!(l.next_token(), Token::While);
+        assert_eq!(l.next_token(), Token::Break);
+        assert_eq!(l.next_token(), Token::Label("outer"));
+        assert_eq!(l.next_token(), Token::Illegal, "a lone quote is not a label");
+    }
 }
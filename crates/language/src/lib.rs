@@ -1,3 +1,7 @@
+pub mod span;
+pub mod diagnostic;
 pub mod lexer;
 pub mod ast;
 pub mod parser;
+pub mod cfg;
+pub mod atomic;
@@ -1,6 +1,11 @@
 mod lexer;
 mod ast;
 mod parser;
+mod cfg;
+#[allow(dead_code)]
+mod span;
+#[allow(dead_code)]
+mod diagnostic;
 
 use clap::Parser;
 use std::fs;
@@ -13,10 +18,25 @@ use parser::Parser as KinetixParser; // Avoid name collision
 struct Args {
     #[arg(short, long)]
     input: Option<String>,
+    /// Flags for `#if` blocks (see `kivm --cfg`)
+    #[arg(long = "cfg")]
+    cfg: Vec<String>,
+    /// Compile-time constants, NAME=VALUE (see `kivm --define`)
+    #[arg(long = "define")]
+    define: Vec<String>,
 }
 
 fn main() {
     let args = Args::parse();
+    let flags = cfg::Cfg::from_flags(&args.cfg)
+        .and_then(|mut flags| args.define.iter().try_for_each(|d| flags.define(d)).map(|_| flags));
+    match flags {
+        Ok(flags) => cfg::set_default(flags),
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    }
 
     if let Some(path) = args.input {
         match fs::read_to_string(&path) {
@@ -1,7 +1,11 @@
 
 use crate::lexer::{Lexer, Token};
-use crate::ast::{Program, Statement, Expression};
+use crate::ast::{Program, Statement, Expression, ExpressionKind, TestBlock};
+use crate::cfg::{Cfg, DefineValue};
+use crate::diagnostic::{codes, Diagnostic};
+use crate::span::Span;
 use bumpalo::Bump;
+use std::collections::{HashMap, HashSet};
 
 #[derive(PartialEq, PartialOrd)]
 enum Precedence {
@@ -19,17 +23,39 @@ enum Precedence {
 pub struct Parser<'src, 'arena> {
     lexer: Lexer<'src>,
     pub arena: &'arena Bump,
-    cur_token: Token,
-    peek_token: Token,
+    cur_token: Token<'src>,
+    peek_token: Token<'src>,
     // Line numbers tracked in lockstep with cur_token/peek_token. `self.lexer.line`
     // always reflects the scanner's position just after producing peek_token, NOT
     // cur_token -- code that needs "the line the current token is on" (e.g. the
     // Holy-Shell same-line check below) must use `cur_line`, not `self.lexer.line`.
     cur_line: usize,
     peek_line: usize,
-    pub errors: Vec<String>,
+    cur_span: Span,
+    peek_span: Span,
+    pub errors: Vec<Diagnostic>,
     allow_struct_literal: bool,
     peek_space_before: bool,
+    /// Flags `#if` conditions are tested against.
+    pub cfg: Cfg,
+    /// Open `#if`s, innermost last.
+    cfg_stack: Vec<CfgFrame>,
+    /// `#define`d constants and the line of their `#define`.
+    defines: HashMap<String, (DefineValue, usize)>,
+    /// `test` blocks parsed so far, moved into `Program::tests`.
+    tests: Vec<TestBlock<'arena>>,
+    /// Seen `#strict`.
+    strict: bool,
+    /// Every name and literal copied into the arena so far, so each distinct
+    /// one is stored once however often it appears.
+    interned: HashSet<&'arena str>,
+}
+
+struct CfgFrame {
+    /// Whether a branch of this `#if` has been taken, so later `#elif`/`#else`
+    /// branches are skipped.
+    taken: bool,
+    line: usize,
 }
 
 impl<'src, 'arena> Parser<'src, 'arena> {
@@ -41,36 +67,95 @@ impl<'src, 'arena> Parser<'src, 'arena> {
             peek_token: Token::EOF,
             cur_line: 1,
             peek_line: 1,
+            cur_span: Span::default(),
+            peek_span: Span::default(),
             errors: vec![],
             allow_struct_literal: true,
             peek_space_before: false,
+            cfg: crate::cfg::current(),
+            cfg_stack: Vec::new(),
+            defines: HashMap::new(),
+            tests: Vec::new(),
+            strict: false,
+            interned: HashSet::new(),
         };
         p.next_token();
         p.next_token();
         p
     }
 
+    /// The arena copy of `s` AST nodes borrow, made the first time `s` is
+    /// seen and shared by every later occurrence.
+    fn intern(&mut self, s: &str) -> &'arena str {
+        if let Some(&interned) = self.interned.get(s) {
+            return interned;
+        }
+        let interned = &*self.arena.alloc_str(s);
+        self.interned.insert(interned);
+        interned
+    }
+
     pub fn next_token(&mut self) {
-        self.cur_token = self.peek_token.clone();
+        self.cur_token = self.peek_token;
         self.cur_line = self.peek_line;
-        self.peek_token = self.lexer.next_token();
+        self.cur_span = self.peek_span;
+        let peek = self.lexer.next_spanned();
+        self.peek_token = peek.node;
+        self.peek_span = peek.span;
         self.peek_line = self.lexer.line;
         self.peek_space_before = self.lexer.space_before_current();
     }
 
     pub fn parse_program(&mut self) -> Program<'arena> {
         let mut program = Program::new();
+        program.statements.extend(self.by_ref());
+        program.tests = std::mem::take(&mut self.tests);
+        program.strict = self.strict;
+        program
+    }
 
+    /// Parses the next top-level statement, or returns `None` at end of
+    /// input. Statements that fail to parse are skipped (their errors land
+    /// in `errors`), so callers can stop after the first few declarations or
+    /// handle each statement as soon as it's parsed; the rest of the input
+    /// is never parsed. The result borrows only the arena, not the parser,
+    /// so it outlives the parser.
+    pub fn next_statement(&mut self) -> Option<Statement<'arena>> {
         while self.cur_token != Token::EOF {
-            if let Some(stmt) = self.parse_statement() {
-                program.statements.push(stmt);
+            if self.at_test_block() {
+                if let Some(test) = self.parse_test_block() {
+                    self.tests.push(test);
+                }
+                self.next_token();
+                continue;
             }
+            let stmt = self.parse_statement();
             self.next_token();
+            if stmt.is_some() {
+                return stmt;
+            }
         }
-        program
+        if let Some(frame) = self.cfg_stack.pop() {
+            self.error_on_line(frame.line, "#if without a matching #endif".to_string());
+            self.cfg_stack.clear();
+        }
+        None
     }
 
+    /// Parses the statement starting at the current token; it spans from
+    /// there to the last token it consumed.
     fn parse_statement(&mut self) -> Option<Statement<'arena>> {
+        let start = self.cur_span;
+        let stmt = self.parse_statement_kind();
+        stmt.map(|stmt| self.spanned(stmt, start))
+    }
+
+    fn spanned(&self, mut stmt: Statement<'arena>, start: Span) -> Statement<'arena> {
+        *stmt.span_mut() = start.to(self.cur_span);
+        stmt
+    }
+
+    fn parse_statement_kind(&mut self) -> Option<Statement<'arena>> {
         match self.cur_token {
             Token::Let => self.parse_let_statement(false),
             Token::Mut => self.parse_let_statement(true),
@@ -87,24 +172,111 @@ impl<'src, 'arena> Parser<'src, 'arena> {
             Token::Trait => self.parse_trait_statement(),
             Token::Impl => self.parse_impl_statement(),
             Token::Hash => self.parse_hash_directive(),
-            Token::Break => Some(Statement::Break { line: self.lexer.line }),
-            Token::Continue => Some(Statement::Continue { line: self.lexer.line }),
+            Token::Import => self.parse_import_statement(),
+            Token::Pub => self.parse_pub_statement(),
+            Token::Label(_) => self.parse_labeled_loop(),
+            Token::Break => {
+                let line = self.lexer.line;
+                Some(Statement::Break { label: self.parse_jump_label(), line, span: Span::default() })
+            }
+            Token::Continue => {
+                let line = self.lexer.line;
+                Some(Statement::Continue { label: self.parse_jump_label(), line, span: Span::default() })
+            }
+            // Top-level ones are taken by `next_statement` before this
+            _ if self.at_test_block() => {
+                self.push_error("test blocks are only allowed at the top level of a file".to_string());
+                self.parse_test_block();
+                None
+            }
             _ => self.parse_expression_statement(),
         }
     }
 
-    // --- Hash Directives (#include, #version) ---
+    /// `test "name" {`: `test` is only a keyword in front of a string and a
+    /// brace, so it stays usable as a name (`test "x"` is still a call).
+    fn at_test_block(&self) -> bool {
+        self.cur_token == Token::Identifier("test")
+            && matches!(self.peek_token, Token::String(_))
+            && self.lexer.clone().next_token() == Token::LBrace
+    }
+
+    /// Parses `test "name" { ... }`, ending on its `}`.
+    fn parse_test_block(&mut self) -> Option<TestBlock<'arena>> {
+        let line = self.cur_line;
+        self.next_token(); // consume "test", now at the name
+        let Token::String(name) = self.cur_token else { return None };
+        let name = name.to_string();
+        if !self.expect_peek(Token::LBrace) {
+            return None;
+        }
+        let body = self.parse_block_statement()?;
+        Some(TestBlock { name, body: self.arena.alloc(body), line })
+    }
+
+    // --- Hash Directives (#include, #version, #if, #strict) ---
     fn parse_hash_directive(&mut self) -> Option<Statement<'arena>> {
         // Peek at the next token to determine which directive
         match &self.peek_token {
             Token::Include => self.parse_include_statement(),
-            Token::Identifier(name) if name == "version" => {
+            Token::If => {
+                let line = self.lexer.line;
+                self.next_token(); // consume #, now at "if"
+                let taken = self.parse_cfg_condition().unwrap_or_else(|e| {
+                    self.push_error(e);
+                    false
+                });
+                self.cfg_stack.push(CfgFrame { taken, line });
+                if !taken {
+                    self.skip_cfg_branch();
+                }
+                None
+            }
+            // Reached by parsing the branch that was taken: skip the rest
+            Token::Else | Token::Identifier("elif") => {
+                self.next_token(); // consume #
+                if self.cfg_stack.is_empty() {
+                    self.push_error(format!("#{} without #if", if self.cur_token == Token::Else { "else" } else { "elif" }));
+                    return None;
+                }
+                self.skip_cfg_branch();
+                None
+            }
+            Token::Identifier("define") => {
+                self.parse_define();
+                None
+            }
+            Token::Identifier("undef") => {
+                self.next_token(); // consume #
+                self.next_token(); // consume "undef", now at the name
+                match self.cur_token {
+                    Token::Identifier(name) => {
+                        self.defines.remove(name);
+                    }
+                    other => self.push_error(format!("Expected a name after #undef, got {:?}", other)),
+                }
+                None
+            }
+            Token::Identifier("strict") => {
+                self.next_token(); // consume #, now at "strict"
+                self.strict = true;
+                None
+            }
+            Token::Identifier("endif") => {
+                self.next_token(); // consume #
+                if self.cfg_stack.pop().is_none() {
+                    self.push_error("#endif without #if".to_string());
+                }
+                None
+            }
+            Token::Identifier(name) if *name == "version" => {
+                let line = self.lexer.line;
                 self.next_token(); // consume #
                 self.next_token(); // consume "version", now at the build number
                 match &self.cur_token {
                     Token::Integer(n) => {
                         let build = *n;
-                        Some(Statement::Version { build, line: self.lexer.line })
+                        Some(Statement::Version { build, line, span: Span::default() })
                     }
                     _ => {
                         self.push_error(format!("Expected integer after #version, got {:?}", self.cur_token));
@@ -119,13 +291,222 @@ impl<'src, 'arena> Parser<'src, 'arena> {
         }
     }
 
+    /// Parses the `#if`/`#elif` condition after the current token and
+    /// evaluates it: `os(windows)`, `arch(x86_64)`, `feature("debug")`,
+    /// `not(..)`, `any(..)`, `all(..)` and `!cond`.
+    fn parse_cfg_condition(&mut self) -> Result<bool, String> {
+        self.next_token();
+        match self.cur_token {
+            Token::Bang => Ok(!self.parse_cfg_condition()?),
+            Token::Identifier(name) => {
+                if self.peek_token != Token::LParen {
+                    return Err(format!("Expected ( after #if predicate '{}', got {:?}", name, self.peek_token));
+                }
+                self.next_token();
+                let value = match name {
+                    "not" => !self.parse_cfg_condition()?,
+                    "defined" => {
+                        self.next_token();
+                        match self.cur_token {
+                            Token::Identifier(arg) => self.define_value(arg).is_some(),
+                            other => return Err(format!("Expected a name in defined(..), got {:?}", other)),
+                        }
+                    }
+                    "any" | "all" => {
+                        let mut values = Vec::new();
+                        while self.peek_token != Token::RParen {
+                            values.push(self.parse_cfg_condition()?);
+                            if self.peek_token == Token::Comma {
+                                self.next_token();
+                            } else if self.peek_token != Token::RParen {
+                                return Err(format!("Expected , or ) in {}(..), got {:?}", name, self.peek_token));
+                            }
+                        }
+                        if name == "any" { values.contains(&true) } else { !values.contains(&false) }
+                    }
+                    _ => {
+                        self.next_token();
+                        let arg = match self.cur_token {
+                            Token::Identifier(arg) | Token::String(arg) => arg,
+                            other => return Err(format!("Expected a name in {}(..), got {:?}", name, other)),
+                        };
+                        self.cfg.test(name, arg)?
+                    }
+                };
+                if self.peek_token != Token::RParen {
+                    return Err(format!("Expected ) to close {}(..), got {:?}", name, self.peek_token));
+                }
+                self.next_token();
+                Ok(value)
+            }
+            other => Err(format!("Expected a condition after #if, got {:?}", other)),
+        }
+    }
+
+    /// `#define NAME value`, with `cur_token` on `#`. A `--define` of the
+    /// same name wins over it.
+    fn parse_define(&mut self) {
+        let line = self.lexer.line;
+        self.next_token(); // consume #
+        self.next_token(); // consume "define", now at the name
+        let Token::Identifier(name) = self.cur_token else {
+            self.push_error(format!("Expected a name after #define, got {:?}", self.cur_token));
+            return;
+        };
+        self.next_token();
+        let negate = self.cur_token == Token::Minus;
+        if negate {
+            self.next_token();
+        }
+        let value = match (self.cur_token, negate) {
+            (Token::Integer(n), _) => DefineValue::Int(if negate { -n } else { n }),
+            (Token::Float(f), _) => DefineValue::Float(if negate { -f } else { f }),
+            (Token::String(s), false) => DefineValue::Str(s.to_string()),
+            (Token::True, false) => DefineValue::Bool(true),
+            (Token::False, false) => DefineValue::Bool(false),
+            (Token::Null, false) => DefineValue::Null,
+            (other, _) => {
+                self.error_on_line(line, format!(
+                    "#define {} needs a constant value (number, string, true, false or null), got {:?}",
+                    name, other
+                ));
+                return;
+            }
+        };
+        if self.cfg.defines.contains_key(name) {
+            return;
+        }
+        match self.defines.get(name) {
+            Some((previous, first_line)) if *previous != value => self.error_on_line(line, format!(
+                "#define {} conflicts with its #define on line {}",
+                name, first_line
+            )),
+            Some(_) => {}
+            None => {
+                self.defines.insert(name.to_string(), (value, line));
+            }
+        }
+    }
+
+    /// The expression a `#define`d name stands for.
+    fn define_value(&mut self, name: &str) -> Option<ExpressionKind<'arena>> {
+        let value = self.cfg.defines.get(name).or_else(|| self.defines.get(name).map(|(value, _)| value))?;
+        Some(match value.clone() {
+            DefineValue::Int(n) => ExpressionKind::Integer(n),
+            DefineValue::Float(f) => ExpressionKind::Float(f),
+            DefineValue::Str(s) => ExpressionKind::String(self.intern(&s)),
+            DefineValue::Bool(b) => ExpressionKind::Boolean(b),
+            DefineValue::Null => ExpressionKind::Null,
+        })
+    }
+
+    /// Errors when a declaration would shadow a `#define`d name, which
+    /// would otherwise silently be substituted at every later use.
+    fn check_not_defined(&mut self, name: &str) {
+        if self.define_value(name).is_some() {
+            let origin = match self.defines.get(name) {
+                Some((_, line)) if !self.cfg.defines.contains_key(name) => format!("#define on line {}", line),
+                _ => "--define".to_string(),
+            };
+            self.push_error(format!("'{}' is a constant from {} and cannot be redeclared", name, origin));
+        }
+    }
+
+    /// Skips the tokens of a branch that is not taken, nested `#if`s
+    /// included, up to the `#elif`/`#else` that starts the next branch to
+    /// take or the `#endif` closing the innermost open `#if`.
+    fn skip_cfg_branch(&mut self) {
+        let mut depth = 0;
+        loop {
+            self.next_token();
+            match (self.cur_token, self.peek_token) {
+                (Token::EOF, _) => return,
+                (Token::Hash, Token::If) => depth += 1,
+                (Token::Hash, Token::Identifier("endif")) if depth > 0 => depth -= 1,
+                (Token::Hash, Token::Identifier("endif")) => {
+                    self.next_token();
+                    self.cfg_stack.pop();
+                    return;
+                }
+                (Token::Hash, Token::Else) if depth == 0 && !self.cfg_stack.last().is_some_and(|f| f.taken) => {
+                    self.next_token();
+                    if let Some(frame) = self.cfg_stack.last_mut() {
+                        frame.taken = true;
+                    }
+                    return;
+                }
+                (Token::Hash, Token::Identifier("elif")) if depth == 0 && !self.cfg_stack.last().is_some_and(|f| f.taken) => {
+                    self.next_token(); // consume #, now at "elif"
+                    let taken = self.parse_cfg_condition().unwrap_or_else(|e| {
+                        self.push_error(e);
+                        false
+                    });
+                    if taken {
+                        if let Some(frame) = self.cfg_stack.last_mut() {
+                            frame.taken = true;
+                        }
+                        return;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // --- Imports ---
+    // import bundle "mathlib.exki"
+    // import util.math as m
+    fn parse_import_statement(&mut self) -> Option<Statement<'arena>> {
+        let line = self.lexer.line;
+        self.next_token(); // now at "bundle" or the module path
+        if matches!(&self.cur_token, Token::Identifier(kind) if *kind == "bundle") && matches!(self.peek_token, Token::String(_)) {
+            self.next_token(); // now at the path
+            let Token::String(path) = &self.cur_token else { unreachable!() };
+            return Some(Statement::ImportBundle { path: path.to_string(), line, span: Span::default() });
+        }
+        let mut module = match &self.cur_token {
+            Token::Identifier(name) => name.to_string(),
+            other => {
+                self.push_error(format!("Expected a module path or 'bundle \"path\"' after import, got {:?}", other));
+                return None;
+            }
+        };
+        while self.peek_token == Token::Dot {
+            self.next_token(); // now at .
+            self.next_token(); // now at the next segment
+            match &self.cur_token {
+                Token::Identifier(name) => {
+                    module.push('.');
+                    module.push_str(name);
+                }
+                other => {
+                    self.push_error(format!("Expected a module name after '.' in import {}, got {:?}", module, other));
+                    return None;
+                }
+            }
+        }
+        let mut alias = None;
+        if self.peek_token == Token::As {
+            self.next_token(); // now at as
+            self.next_token(); // now at the alias
+            match &self.cur_token {
+                Token::Identifier(name) => alias = Some(name.to_string()),
+                other => {
+                    self.push_error(format!("Expected an alias after 'import {} as', got {:?}", module, other));
+                    return None;
+                }
+            }
+        }
+        Some(Statement::Import { module, alias, line, span: Span::default() })
+    }
 
     // --- Variable Declaration ---
     fn parse_let_statement(&mut self, mutable: bool) -> Option<Statement<'arena>> {
         let start_line = self.lexer.line;
-        match &self.peek_token {
+        match self.peek_token {
             Token::Identifier(name) => {
-                let name = name.clone();
+                let name = self.intern(name);
+                self.check_not_defined(name);
                 self.next_token(); 
 
                 // Optional type annotation: let x: int = ...
@@ -134,7 +515,7 @@ impl<'src, 'arena> Parser<'src, 'arena> {
                     self.next_token(); // consume :
                     self.next_token(); // consume type
                     if let Token::Identifier(t) = &self.cur_token {
-                        type_hint = Some(t.clone());
+                        type_hint = Some(t.to_string());
                     }
                 }
 
@@ -149,10 +530,10 @@ impl<'src, 'arena> Parser<'src, 'arena> {
                 } else if let Some(ref t) = type_hint {
                     // Safe Default Initialization
                     let expr = match t.as_str() {
-                        "int" => Expression::Integer(0),
-                        "float" => Expression::Float(0.0),
-                        "bool" => Expression::Boolean(false),
-                        "string" | "str" => Expression::String("".to_string()),
+                        "int" => Expression::synthetic(ExpressionKind::Integer(0)),
+                        "float" => Expression::synthetic(ExpressionKind::Float(0.0)),
+                        "bool" => Expression::synthetic(ExpressionKind::Boolean(false)),
+                        "string" | "str" => Expression::synthetic(ExpressionKind::String("")),
                         _ => {
                             self.push_error(format!("Cannot default initialize type '{}'", t));
                             return None;
@@ -167,10 +548,10 @@ impl<'src, 'arena> Parser<'src, 'arena> {
                     return None;
                 };
                 
-                Some(Statement::Let { name, mutable, type_hint, value, line: start_line })
+                Some(Statement::Let { name, mutable, type_hint, value, public: false, line: start_line, span: Span::default() })
             }
             _ => {
-                self.peek_error(Token::Identifier("name".to_string()));
+                self.peek_error(Token::Identifier("name"));
                 None
             }
         }
@@ -178,9 +559,9 @@ impl<'src, 'arena> Parser<'src, 'arena> {
 
     fn parse_state_statement(&mut self) -> Option<Statement<'arena>> {
         let start_line = self.lexer.line;
-        match &self.peek_token {
+        match self.peek_token {
             Token::Identifier(name) => {
-                let name = name.clone();
+                let name = self.intern(name);
                 self.next_token(); 
 
                 // Optional type annotation: state x: int = ...
@@ -189,7 +570,7 @@ impl<'src, 'arena> Parser<'src, 'arena> {
                     self.next_token(); // consume :
                     self.next_token(); // consume type
                     if let Token::Identifier(t) = &self.cur_token {
-                        type_hint = Some(t.clone());
+                        type_hint = Some(t.to_string());
                     }
                 }
 
@@ -204,10 +585,10 @@ impl<'src, 'arena> Parser<'src, 'arena> {
                 } else if let Some(ref t) = type_hint {
                     // Safe Default Initialization
                     let expr = match t.as_str() {
-                        "int" => Expression::Integer(0),
-                        "float" => Expression::Float(0.0),
-                        "bool" => Expression::Boolean(false),
-                        "string" | "str" => Expression::String("".to_string()),
+                        "int" => Expression::synthetic(ExpressionKind::Integer(0)),
+                        "float" => Expression::synthetic(ExpressionKind::Float(0.0)),
+                        "bool" => Expression::synthetic(ExpressionKind::Boolean(false)),
+                        "string" | "str" => Expression::synthetic(ExpressionKind::String("")),
                         _ => {
                             self.push_error(format!("Cannot default initialize type '{}'", t));
                             return None;
@@ -222,10 +603,10 @@ impl<'src, 'arena> Parser<'src, 'arena> {
                     return None;
                 };
                 
-                Some(Statement::State { name, type_hint, value, line: start_line })
+                Some(Statement::State { name, type_hint, value, line: start_line, span: Span::default() })
             }
             _ => {
-                self.peek_error(Token::Identifier("name".to_string()));
+                self.peek_error(Token::Identifier("name"));
                 None
             }
         }
@@ -233,9 +614,9 @@ impl<'src, 'arena> Parser<'src, 'arena> {
 
     fn parse_computed_statement(&mut self) -> Option<Statement<'arena>> {
         let start_line = self.lexer.line;
-        match &self.peek_token {
+        match self.peek_token {
             Token::Identifier(name) => {
-                let name = name.clone();
+                let name = self.intern(name);
                 self.next_token(); 
 
                 // Optional type annotation: computed x: int = ...
@@ -244,7 +625,7 @@ impl<'src, 'arena> Parser<'src, 'arena> {
                     self.next_token(); // consume :
                     self.next_token(); // consume type
                     if let Token::Identifier(t) = &self.cur_token {
-                        type_hint = Some(t.clone());
+                        type_hint = Some(t.to_string());
                     }
                 }
 
@@ -259,10 +640,10 @@ impl<'src, 'arena> Parser<'src, 'arena> {
                 } else if let Some(ref t) = type_hint {
                     // Safe Default Initialization
                     let expr = match t.as_str() {
-                        "int" => Expression::Integer(0),
-                        "float" => Expression::Float(0.0),
-                        "bool" => Expression::Boolean(false),
-                        "string" | "str" => Expression::String("".to_string()),
+                        "int" => Expression::synthetic(ExpressionKind::Integer(0)),
+                        "float" => Expression::synthetic(ExpressionKind::Float(0.0)),
+                        "bool" => Expression::synthetic(ExpressionKind::Boolean(false)),
+                        "string" | "str" => Expression::synthetic(ExpressionKind::String("")),
                         _ => {
                             self.push_error(format!("Cannot default initialize type '{}'", t));
                             return None;
@@ -277,10 +658,10 @@ impl<'src, 'arena> Parser<'src, 'arena> {
                     return None;
                 };
                 
-                Some(Statement::Computed { name, type_hint, value, line: start_line })
+                Some(Statement::Computed { name, type_hint, value, line: start_line, span: Span::default() })
             }
             _ => {
-                self.peek_error(Token::Identifier("name".to_string()));
+                self.peek_error(Token::Identifier("name"));
                 None
             }
         }
@@ -295,7 +676,7 @@ impl<'src, 'arena> Parser<'src, 'arena> {
             self.next_token(); // move inside
             while self.cur_token != Token::RParen && self.cur_token != Token::EOF {
                 if let Token::Identifier(dep) = &self.cur_token {
-                    dependencies.push(dep.clone());
+                    dependencies.push(dep.to_string());
                 } else {
                     self.push_error(format!("Expected identifier in effect dependencies, got {:?}", self.cur_token));
                     return None;
@@ -317,17 +698,22 @@ impl<'src, 'arena> Parser<'src, 'arena> {
             self.next_token();
         }
         
-        Some(Statement::Effect { dependencies, body: self.arena.alloc(body), line: start_line })
+        Some(Statement::Effect { dependencies, body: self.arena.alloc(body), line: start_line, span: Span::default() })
     }
 
     // --- Function Declaration (statement) ---
     fn parse_fn_statement(&mut self) -> Option<Statement<'arena>> {
         let start_line = self.lexer.line;
+        let start = self.cur_span;
         // fn name(params) -> RetType { body }
         self.next_token(); // consume fn
         
-        let name = match &self.cur_token {
-            Token::Identifier(n) => n.clone(),
+        let name = match self.cur_token {
+            Token::Identifier(n) => {
+                let n = self.intern(n);
+                self.check_not_defined(n);
+                n
+            }
             Token::LParen => {
                 // This is a lambda: fn() { ... } used as expression
                 let params = self.parse_function_params()?;
@@ -336,7 +722,7 @@ impl<'src, 'arena> Parser<'src, 'arena> {
                     self.next_token(); // ->
                     self.next_token(); // type
                     if let Token::Identifier(t) = &self.cur_token {
-                        return_type = t.clone();
+                        return_type = t.to_string();
                     }
                 }
                 if !self.expect_peek(Token::LBrace) { return None; }
@@ -344,13 +730,15 @@ impl<'src, 'arena> Parser<'src, 'arena> {
                 
                 if self.peek_token == Token::Semicolon { self.next_token(); }
                 
+                let lambda = ExpressionKind::FunctionLiteral {
+                    parameters: params,
+                    body: self.arena.alloc(body),
+                    return_type,
+                };
                 return Some(Statement::Expression {
-                    expression: Expression::FunctionLiteral {
-                        parameters: params,
-                        body: self.arena.alloc(body),
-                        return_type,
-                    },
+                    expression: self.expression(lambda, start),
                     line: start_line,
+                    span: Span::default(),
                 });
             }
             _ => return None,
@@ -367,7 +755,7 @@ impl<'src, 'arena> Parser<'src, 'arena> {
             self.next_token(); // ->
             self.next_token(); // type
             if let Token::Identifier(t) = &self.cur_token {
-                return_type = t.clone();
+                return_type = t.to_string();
             }
         }
         
@@ -380,10 +768,36 @@ impl<'src, 'arena> Parser<'src, 'arena> {
             parameters: params,
             body: self.arena.alloc(body),
             return_type,
+            public: false,
             line: start_line,
+            span: Span::default(),
         })
     }
 
+    // pub fn name(...) { ... }
+    fn parse_pub_statement(&mut self) -> Option<Statement<'arena>> {
+        if self.peek_token == Token::Mut {
+            self.next_token(); // now at mut
+            return match self.parse_let_statement(true)? {
+                Statement::Let { name, mutable, type_hint, value, line, .. } => {
+                    Some(Statement::Let { name, mutable, type_hint, value, public: true, line, span: Span::default() })
+                }
+                other => Some(other),
+            };
+        }
+        if self.peek_token != Token::Fn {
+            self.push_error(format!("Expected 'fn' or 'mut' after top-level 'pub', got {:?}", self.peek_token));
+            return None;
+        }
+        self.next_token(); // now at fn
+        match self.parse_fn_statement()? {
+            Statement::Function { name, parameters, body, return_type, line, .. } => {
+                Some(Statement::Function { name, parameters, body, return_type, public: true, line, span: Span::default() })
+            }
+            other => Some(other),
+        }
+    }
+
     fn parse_return_statement(&mut self) -> Option<Statement<'arena>> {
         let start_line = self.lexer.line;
         self.next_token(); 
@@ -398,7 +812,7 @@ impl<'src, 'arena> Parser<'src, 'arena> {
             Some(expr)
         };
 
-        Some(Statement::Return { value, line: start_line })
+        Some(Statement::Return { value, line: start_line, span: Span::default() })
     }
     
     // --- While ---
@@ -406,6 +820,10 @@ impl<'src, 'arena> Parser<'src, 'arena> {
         let start_line = self.lexer.line;
         self.next_token(); // consume 'while'
         
+        if self.cur_token == Token::Let {
+            return self.parse_while_let(start_line);
+        }
+        
         let prev_allow = self.allow_struct_literal;
         self.allow_struct_literal = false;
         let condition = self.parse_expression(Precedence::Lowest, false)?;
@@ -414,7 +832,45 @@ impl<'src, 'arena> Parser<'src, 'arena> {
         if !self.expect_peek(Token::LBrace) { return None; }
         let body = self.parse_block_statement()?;
         
-        Some(Statement::While { condition, body: self.arena.alloc(body), line: start_line })
+        Some(Statement::While { condition, body: self.arena.alloc(body), label: None, line: start_line, span: Span::default() })
+    }
+
+    // --- Labeled loops ---
+    /// `'name: while ... { }` / `'name: for ... { }`, with `cur_token` on the label.
+    fn parse_labeled_loop(&mut self) -> Option<Statement<'arena>> {
+        let name = match self.cur_token {
+            Token::Label(name) => self.intern(name),
+            _ => return None,
+        };
+        if !self.expect_peek(Token::Colon) { return None; }
+        self.next_token(); // advance to the loop keyword
+
+        let mut stmt = match self.cur_token {
+            Token::While => self.parse_while_statement()?,
+            Token::For => self.parse_for_statement()?,
+            _ => {
+                self.push_error(format!("Label '{name} must be followed by a `while` or `for` loop, got {:?}", self.cur_token));
+                return None;
+            }
+        };
+        if let Statement::While { label, .. } | Statement::For { label, .. } = &mut stmt {
+            *label = Some(name);
+        }
+        Some(stmt)
+    }
+
+    /// Consumes the optional `'name` after `break`/`continue`, plus an
+    /// optional trailing `;` like any other statement.
+    fn parse_jump_label(&mut self) -> Option<&'arena str> {
+        let mut label = None;
+        if let Token::Label(name) = self.peek_token {
+            label = Some(self.intern(name));
+            self.next_token();
+        }
+        if self.peek_token == Token::Semicolon {
+            self.next_token();
+        }
+        label
     }
     
     // --- For ---
@@ -422,8 +878,8 @@ impl<'src, 'arena> Parser<'src, 'arena> {
         let start_line = self.lexer.line;
         self.next_token(); // consume 'for'
         
-        let iterator = match &self.cur_token {
-            Token::Identifier(name) => name.clone(),
+        let iterator = match self.cur_token {
+            Token::Identifier(name) => self.intern(name),
             _ => return None,
         };
         self.next_token();
@@ -443,7 +899,7 @@ impl<'src, 'arena> Parser<'src, 'arena> {
         if !self.expect_peek(Token::LBrace) { return None; }
         let body = self.parse_block_statement()?;
         
-        Some(Statement::For { iterator, range, body: self.arena.alloc(body), line: start_line })
+        Some(Statement::For { iterator, range, body: self.arena.alloc(body), label: None, line: start_line, span: Span::default() })
     }
     
     // --- Include ---
@@ -460,7 +916,7 @@ impl<'src, 'arena> Parser<'src, 'arena> {
         
         let path = match &self.cur_token {
             Token::String(s) => {
-                s.clone()
+                s.to_string()
             },
             Token::Less => {
                 // Read until >
@@ -485,11 +941,11 @@ impl<'src, 'arena> Parser<'src, 'arena> {
             self.next_token(); // move to As
             self.next_token(); // move to alias identifier
             if let Token::Identifier(a) = &self.cur_token {
-                alias = Some(a.clone());
+                alias = Some(a.to_string());
             }
         }
         
-        Some(Statement::Include { path, alias, line: start_line })
+        Some(Statement::Include { path, alias, line: start_line, span: Span::default() })
     }
     
     // --- Class ---
@@ -497,8 +953,8 @@ impl<'src, 'arena> Parser<'src, 'arena> {
         let start_line = self.lexer.line;
         self.next_token(); // consume 'class'
         
-        let name = match &self.cur_token {
-            Token::Identifier(name) => name.clone(),
+        let name = match self.cur_token {
+            Token::Identifier(name) => self.intern(name),
             _ => return None,
         };
         self.next_token();
@@ -507,8 +963,8 @@ impl<'src, 'arena> Parser<'src, 'arena> {
         let mut parent = None;
         if self.cur_token == Token::Colon {
              self.next_token();
-             if let Token::Identifier(p) = &self.cur_token {
-                 parent = Some(p.clone());
+             if let Token::Identifier(p) = self.cur_token {
+                 parent = Some(self.intern(p));
                  self.next_token();
              }
         }
@@ -517,29 +973,41 @@ impl<'src, 'arena> Parser<'src, 'arena> {
         
         let mut methods = vec![];
         let mut fields = vec![];
+        let mut constants = vec![];
         
         self.next_token(); // skip {
         
         while self.cur_token != Token::RBrace && self.cur_token != Token::EOF {
             match &self.cur_token {
                 Token::Fn => {
+                    let start = self.cur_span;
                     if let Some(stmt) = self.parse_fn_statement() {
-                        methods.push(stmt);
+                        methods.push(self.spanned(stmt, start));
                     }
                     // parse_fn_statement leaves cur_token at the method's closing }.
                     // Advance past it so the class loop continues correctly.
                     self.next_token();
                     continue;
                 },
+                Token::Let => {
+                    let start = self.cur_span;
+                    if let Some(stmt) = self.parse_let_statement(false) {
+                        constants.push(self.spanned(stmt, start));
+                    }
+                    // parse_let_statement leaves cur_token on the value's last token.
+                    self.next_token();
+                    continue;
+                },
                 Token::Pub => {
                     self.next_token();
                     if let Token::Identifier(n) = &self.cur_token {
-                        let field_name = n.clone();
+                        let field_name = n.to_string();
+                        let field_line = self.cur_span.line;
                         self.next_token();
                         if self.cur_token == Token::Colon {
                             self.next_token();
                             if let Token::Identifier(ty) = &self.cur_token {
-                                fields.push((true, field_name, ty.clone()));
+                                fields.push((true, field_name, ty.to_string(), field_line));
                                 self.next_token();
                             }
                         }
@@ -548,25 +1016,39 @@ impl<'src, 'arena> Parser<'src, 'arena> {
                 Token::Mut => {
                     self.next_token();
                     if let Token::Identifier(n) = &self.cur_token {
-                        let field_name = n.clone();
+                        let field_name = n.to_string();
+                        let field_line = self.cur_span.line;
                         self.next_token();
                         if self.cur_token == Token::Colon {
                             self.next_token();
                             if let Token::Identifier(ty) = &self.cur_token {
-                                fields.push((false, field_name, ty.clone()));
+                                fields.push((false, field_name, ty.to_string(), field_line));
                                 self.next_token();
                             }
                         }
                     }
                 },
+                Token::Identifier("const") => {
+                    // Parsed like `let` so the rest of the class still parses,
+                    // but rejected rather than silently taken as a constant.
+                    self.errors.push(
+                        Diagnostic::error(codes::PARSE, "'const' is not a keyword; class constants are declared with 'let'")
+                            .at(self.cur_span)
+                            .with_help("write `let` instead of `const`"),
+                    );
+                    self.parse_let_statement(false);
+                    self.next_token();
+                    continue;
+                },
                 Token::Identifier(_) => {
                     if let Token::Identifier(n) = &self.cur_token {
-                        let field_name = n.clone();
+                        let field_name = n.to_string();
+                        let field_line = self.cur_span.line;
                         self.next_token();
                         if self.cur_token == Token::Colon {
                             self.next_token();
                             if let Token::Identifier(ty) = &self.cur_token {
-                                fields.push((false, field_name, ty.clone()));
+                                fields.push((false, field_name, ty.to_string(), field_line));
                                 self.next_token();
                             }
                         }
@@ -577,15 +1059,15 @@ impl<'src, 'arena> Parser<'src, 'arena> {
             if self.cur_token == Token::Semicolon { self.next_token(); }
         }
         
-        Some(Statement::Class { name, parent, methods, fields, line: start_line })
+        Some(Statement::Class { name, parent, methods, fields, constants, line: start_line, span: Span::default() })
     }
     
     // --- Struct ---
     fn parse_struct_statement(&mut self) -> Option<Statement<'arena>> {
         let start_line = self.lexer.line;
         self.next_token();
-        let name = match &self.cur_token {
-            Token::Identifier(name) => name.clone(),
+        let name = match self.cur_token {
+            Token::Identifier(name) => self.intern(name),
             _ => return None,
         };
         self.next_token();
@@ -596,12 +1078,12 @@ impl<'src, 'arena> Parser<'src, 'arena> {
         let mut fields = vec![];
         while self.cur_token != Token::RBrace && self.cur_token != Token::EOF {
             if let Token::Identifier(n) = &self.cur_token {
-                let name = n.clone();
+                let name = n.to_string();
                 self.next_token();
                 if self.cur_token == Token::Colon {
                     self.next_token();
                     if let Token::Identifier(ty) = &self.cur_token {
-                        fields.push((name, ty.clone()));
+                        fields.push((name, ty.to_string()));
                         self.next_token();
                     }
                 }
@@ -611,7 +1093,7 @@ impl<'src, 'arena> Parser<'src, 'arena> {
             }
         }
         
-        Some(Statement::Struct { name, fields, line: start_line })
+        Some(Statement::Struct { name, fields, line: start_line, span: Span::default() })
     }
 
     fn parse_generics(&mut self) -> Vec<String> {
@@ -620,7 +1102,7 @@ impl<'src, 'arena> Parser<'src, 'arena> {
             self.next_token();
             while self.cur_token != Token::Greater && self.cur_token != Token::EOF {
                 if let Token::Identifier(id) = &self.cur_token {
-                    generics.push(id.clone());
+                    generics.push(id.to_string());
                     self.next_token();
                 }
                 if self.cur_token == Token::Comma {
@@ -638,8 +1120,8 @@ impl<'src, 'arena> Parser<'src, 'arena> {
     fn parse_enum_statement(&mut self) -> Option<Statement<'arena>> {
         let start_line = self.lexer.line;
         self.next_token(); // consume 'enum'
-        let name = match &self.cur_token {
-            Token::Identifier(n) => n.clone(),
+        let name = match self.cur_token {
+            Token::Identifier(n) => self.intern(n),
             _ => return None,
         };
         self.next_token();
@@ -651,13 +1133,13 @@ impl<'src, 'arena> Parser<'src, 'arena> {
         let mut variants = vec![];
         while self.cur_token != Token::RBrace && self.cur_token != Token::EOF {
             if let Token::Identifier(vname) = &self.cur_token {
-                let v = vname.clone();
+                let v = vname.to_string();
                 self.next_token();
                 let mut payload = None;
                 if self.cur_token == Token::LParen {
                     self.next_token();
                     if let Token::Identifier(ty) = &self.cur_token {
-                        payload = Some(ty.clone());
+                        payload = Some(ty.to_string());
                         self.next_token();
                     }
                     if self.cur_token == Token::RParen {
@@ -673,15 +1155,15 @@ impl<'src, 'arena> Parser<'src, 'arena> {
             }
         }
         // Do NOT consume RBrace here, parse_program handles it
-        Some(Statement::Enum { name, generics, variants, line: start_line })
+        Some(Statement::Enum { name, generics, variants, line: start_line, span: Span::default() })
     }
 
     // --- Trait ---
     fn parse_trait_statement(&mut self) -> Option<Statement<'arena>> {
         let start_line = self.lexer.line;
         self.next_token(); // consume 'trait'
-        let name = match &self.cur_token {
-            Token::Identifier(n) => n.clone(),
+        let name = match self.cur_token {
+            Token::Identifier(n) => self.intern(n),
             _ => return None,
         };
         self.next_token();
@@ -695,7 +1177,7 @@ impl<'src, 'arena> Parser<'src, 'arena> {
             if self.cur_token == Token::Fn {
                 self.next_token();
                 if let Token::Identifier(mname) = &self.cur_token {
-                    let m = mname.clone();
+                    let m = mname.to_string();
                     self.next_token();
                     
                     let mut params = vec![];
@@ -703,12 +1185,12 @@ impl<'src, 'arena> Parser<'src, 'arena> {
                         self.next_token();
                         while self.cur_token != Token::RParen && self.cur_token != Token::EOF {
                             if let Token::Identifier(pname) = &self.cur_token {
-                                let pn = pname.clone();
+                                let pn = pname.to_string();
                                 self.next_token();
                                 if self.cur_token == Token::Colon {
                                     self.next_token();
                                     if let Token::Identifier(pty) = &self.cur_token {
-                                        params.push((pn, pty.clone()));
+                                        params.push((pn, pty.to_string()));
                                         self.next_token();
                                     }
                                 }
@@ -722,7 +1204,7 @@ impl<'src, 'arena> Parser<'src, 'arena> {
                     if self.cur_token == Token::Arrow {
                         self.next_token();
                         if let Token::Identifier(rty) = &self.cur_token {
-                            ret_ty = rty.clone();
+                            ret_ty = rty.to_string();
                             self.next_token();
                         }
                     }
@@ -736,7 +1218,7 @@ impl<'src, 'arena> Parser<'src, 'arena> {
             }
         }
         // Do NOT consume RBrace here
-        Some(Statement::Trait { name, generics, methods, line: start_line })
+        Some(Statement::Trait { name, generics, methods, line: start_line, span: Span::default() })
     }
 
     // --- Impl ---
@@ -746,21 +1228,21 @@ impl<'src, 'arena> Parser<'src, 'arena> {
         
         let generics = self.parse_generics();
         
-        let first_name = match &self.cur_token {
-            Token::Identifier(n) => n.clone(),
+        let first_name = match self.cur_token {
+            Token::Identifier(n) => self.intern(n),
             _ => return None,
         };
         self.next_token();
         
         let mut trait_name = None;
-        let mut target_name = first_name.clone();
+        let mut target_name = first_name;
         
         // Check for 'for' e.g. impl Trait for Struct
         if self.cur_token == Token::For {
             self.next_token();
             trait_name = Some(first_name);
-            if let Token::Identifier(t) = &self.cur_token {
-                target_name = t.clone();
+            if let Token::Identifier(t) = self.cur_token {
+                target_name = self.intern(t);
                 self.next_token();
             } else {
                 return None;
@@ -773,8 +1255,9 @@ impl<'src, 'arena> Parser<'src, 'arena> {
         let mut methods = vec![];
         while self.cur_token != Token::RBrace && self.cur_token != Token::EOF {
             if self.cur_token == Token::Fn {
+                let start = self.cur_span;
                 if let Some(stmt) = self.parse_fn_statement() {
-                    methods.push(stmt);
+                    methods.push(self.spanned(stmt, start));
                 }
                 // `parse_fn_statement` (via `parse_block_statement`) leaves `cur_token` at the function's closing `RBrace`.
                 // We must skip it to continue parsing other methods or reach the impl's closing `RBrace`.
@@ -790,7 +1273,7 @@ impl<'src, 'arena> Parser<'src, 'arena> {
             }
         }
         // Do NOT consume RBrace here
-        Some(Statement::Impl { trait_name, target_name, generics, methods, line: start_line })
+        Some(Statement::Impl { trait_name, target_name, generics, methods, line: start_line, span: Span::default() })
     }
 
     fn parse_expression_statement(&mut self) -> Option<Statement<'arena>> {
@@ -802,7 +1285,7 @@ impl<'src, 'arena> Parser<'src, 'arena> {
             self.next_token();
         }
         
-        Some(Statement::Expression { expression: expr, line: start_line })
+        Some(Statement::Expression { expression: expr, line: start_line, span: Span::default() })
     }
 
     // --- Expression Parsing ---
@@ -836,15 +1319,16 @@ impl<'src, 'arena> Parser<'src, 'arena> {
         // Checked after the postfix loop above so `obj.field = x` and `arr[i] = x`
         // see the fully-built MemberAccess/Index target, not just the bare prefix.
         if self.peek_token == Token::Equal {
-            match &left {
-                Expression::Identifier(_) | Expression::MemberAccess { .. } | Expression::Index { .. } => {
+            match &left.kind {
+                ExpressionKind::Identifier(_) | ExpressionKind::MemberAccess { .. } | ExpressionKind::Index { .. } => {
                     self.next_token(); // consume =
                     self.next_token(); // move to value
                     let value = self.parse_expression(Precedence::Lowest, true)?;
-                    return Some(Expression::Assign {
+                    let span = left.span.to(value.span);
+                    return Some(Expression::new(ExpressionKind::Assign {
                         target: self.arena.alloc(left),
                         value: self.arena.alloc(value),
-                    });
+                    }, span));
                 },
                 _ => {}
             }
@@ -854,7 +1338,7 @@ impl<'src, 'arena> Parser<'src, 'arena> {
         // If we parsed an identifier or member access, and the NEXT token is an expression-starter
         // AND it's on the same line, parse it as a function call.
         if allow_holy && precedence <= Precedence::Lowest {
-            if let Expression::Identifier(_) | Expression::MemberAccess { .. } = &left {
+            if let ExpressionKind::Identifier(_) | ExpressionKind::MemberAccess { .. } = &left.kind {
                 // Check if the current peek_token is a token that can start a primary expression
                 // and it is on the SAME line as the identifier/member access. `cur_line` is the
                 // line of `left`'s own token (the parser always looks one token ahead, so
@@ -909,10 +1393,11 @@ impl<'src, 'arena> Parser<'src, 'arena> {
                     }
                     
                     if !args.is_empty() {
-                         left = Expression::Call {
+                         let span = left.span.to(self.cur_span);
+                         left = Expression::new(ExpressionKind::Call {
                              function: self.arena.alloc(left),
                              arguments: args,
-                         };
+                         }, span);
                     }
                 }
             }
@@ -921,81 +1406,97 @@ impl<'src, 'arena> Parser<'src, 'arena> {
         Some(left)
     }
 
+    /// `kind`, spanning from `start` to the current token.
+    fn expression(&self, kind: ExpressionKind<'arena>, start: Span) -> Expression<'arena> {
+        Expression::new(kind, start.to(self.cur_span))
+    }
+
     fn parse_prefix(&mut self) -> Option<Expression<'arena>> {
-        match &self.cur_token {
+        let start = self.cur_span;
+        let kind = match self.cur_token {
             Token::Identifier(name) => {
-                let name_clone = name.clone();
+                if let Some(value) = self.define_value(name) {
+                    return Some(Expression::new(value, start));
+                }
                 if self.allow_struct_literal && self.peek_token == Token::LBrace {
-                    return self.parse_struct_literal_expr(name_clone);
+                    let name = self.intern(name);
+                    self.parse_struct_literal_expr(name)?
+                } else {
+                    ExpressionKind::Identifier(self.intern(name))
                 }
-                Some(Expression::Identifier(name_clone))
             },
-            Token::Integer(val) => Some(Expression::Integer(*val)),
-            Token::Float(val) => Some(Expression::Float(*val)),
-            Token::String(val) => Some(Expression::String(val.clone())),
+            Token::Integer(val) => ExpressionKind::Integer(val),
+            Token::Float(val) => ExpressionKind::Float(val),
+            Token::String(val) => ExpressionKind::String(self.intern(val)),
             Token::BacktickString(val) => {
-                let system_ident = Expression::Identifier("system".to_string());
-                let member_access = Expression::MemberAccess {
+                let system_ident = Expression::new(ExpressionKind::Identifier("system"), start);
+                let member_access = Expression::new(ExpressionKind::MemberAccess {
                     object: self.arena.alloc(system_ident),
-                    member: "exec".to_string(),
-                };
-                let arg = Expression::String(val.clone());
-                Some(Expression::Call {
+                    member: "exec",
+                }, start);
+                let arg = Expression::new(ExpressionKind::String(self.intern(val)), start);
+                ExpressionKind::Call {
                     function: self.arena.alloc(member_access),
                     arguments: vec![arg],
-                })
+                }
             },
-            Token::True => Some(Expression::Boolean(true)),
-            Token::False => Some(Expression::Boolean(false)),
-            Token::Null => Some(Expression::Null),
+            Token::True => ExpressionKind::Boolean(true),
+            Token::False => ExpressionKind::Boolean(false),
+            Token::Null => ExpressionKind::Null,
             Token::Minus | Token::Bang => {
-                let op = if self.cur_token == Token::Minus { "-" } else { "!" }.to_string();
+                let op = if self.cur_token == Token::Minus { "-" } else { "!" };
                 self.next_token();
                 let right = self.parse_expression(Precedence::Prefix, false)?;
-                Some(Expression::Prefix { operator: op, right: self.arena.alloc(right) })
+                ExpressionKind::Prefix { operator: op, right: self.arena.alloc(right) }
             },
             Token::Ampersand => {
                 self.next_token();
-                let mut op = "&".to_string();
+                let mut op = "&";
                 if self.cur_token == Token::Mut {
-                    op = "&mut".to_string();
+                    op = "&mut";
                     self.next_token();
                 }
                 let right = self.parse_expression(Precedence::Prefix, false)?;
-                Some(Expression::Prefix { operator: op, right: self.arena.alloc(right) })
+                ExpressionKind::Prefix { operator: op, right: self.arena.alloc(right) }
             },
             Token::LParen => {
                 self.next_token();
                 let expr = self.parse_expression(Precedence::Lowest, true)?;
                 if !self.expect_peek(Token::RParen) { return None; }
-                Some(expr)
+                return Some(expr);
             },
-            Token::If => self.parse_if_expression(),
-            Token::Match => self.parse_match_expression(),
-            Token::Fn => self.parse_function_literal(), 
-            Token::LBracket => self.parse_array_literal(),
+            Token::If => self.parse_if_expression()?,
+            Token::Match => self.parse_match_expression()?,
+            Token::Fn => self.parse_function_literal()?,
+            Token::LBracket => self.parse_array_literal()?,
             _ => {
                 self.push_error(format!("No prefix parse function for {:?}", self.cur_token));
-                None
+                return None;
             }
-        }
+        };
+        Some(self.expression(kind, start))
     }
 
     fn parse_infix(&mut self, left: Expression<'arena>) -> Option<Expression<'arena>> {
-        match &self.cur_token {
+        let start = left.span;
+        let kind = self.parse_infix_kind(left)?;
+        Some(self.expression(kind, start))
+    }
+
+    fn parse_infix_kind(&mut self, left: Expression<'arena>) -> Option<ExpressionKind<'arena>> {
+        match self.cur_token {
             Token::LParen => return self.parse_call_expression(left),
             Token::LBracket => return self.parse_index_expression(left),
             Token::QuestionMark => {
                 self.next_token();
-                let node = Expression::Try { value: self.arena.alloc(left) };
-                return Some(node);
+                return Some(ExpressionKind::Try { value: self.arena.alloc(left) });
             },
             Token::Dot => {
                 self.next_token();
-                if let Token::Identifier(member) = &self.cur_token {
-                    return Some(Expression::MemberAccess {
+                if let Token::Identifier(member) = self.cur_token {
+                    return Some(ExpressionKind::MemberAccess {
                         object: self.arena.alloc(left),
-                        member: member.clone(),
+                        member: self.intern(member),
                     });
                 }
                 return None;
@@ -1004,7 +1505,7 @@ impl<'src, 'arena> Parser<'src, 'arena> {
                 let precedence = self.cur_precedence();
                 self.next_token();
                 let end = self.parse_expression(precedence, false)?;
-                return Some(Expression::Range {
+                return Some(ExpressionKind::Range {
                     start: self.arena.alloc(left),
                     end: self.arena.alloc(end),
                 });
@@ -1027,37 +1528,37 @@ impl<'src, 'arena> Parser<'src, 'arena> {
             Token::And => "&&",
             Token::Or => "||",
             _ => return None,
-        }.to_string();
+        };
 
         let precedence = self.cur_precedence();
         self.next_token();
         let right = self.parse_expression(precedence, false)?;
 
-        Some(Expression::Infix {
+        Some(ExpressionKind::Infix {
             left: self.arena.alloc(left),
             operator,
             right: self.arena.alloc(right),
         })
     }
     
-    fn parse_call_expression(&mut self, function: Expression<'arena>) -> Option<Expression<'arena>> {
+    fn parse_call_expression(&mut self, function: Expression<'arena>) -> Option<ExpressionKind<'arena>> {
         let arguments = self.parse_expression_list(Token::RParen)?;
-        Some(Expression::Call { function: self.arena.alloc(function), arguments })
+        Some(ExpressionKind::Call { function: self.arena.alloc(function), arguments })
     }
     
-    fn parse_index_expression(&mut self, left: Expression<'arena>) -> Option<Expression<'arena>> {
+    fn parse_index_expression(&mut self, left: Expression<'arena>) -> Option<ExpressionKind<'arena>> {
         self.next_token();
         let index = self.parse_expression(Precedence::Lowest, false)?;
         if !self.expect_peek(Token::RBracket) { return None; }
-        Some(Expression::Index { left: self.arena.alloc(left), index: self.arena.alloc(index) })
+        Some(ExpressionKind::Index { left: self.arena.alloc(left), index: self.arena.alloc(index) })
     }
     
-    fn parse_array_literal(&mut self) -> Option<Expression<'arena>> {
+    fn parse_array_literal(&mut self) -> Option<ExpressionKind<'arena>> {
         let elements = self.parse_expression_list(Token::RBracket)?;
-        Some(Expression::ArrayLiteral(elements))
+        Some(ExpressionKind::ArrayLiteral(elements))
     }
 
-    fn parse_struct_literal_expr(&mut self, name: String) -> Option<Expression<'arena>> {
+    fn parse_struct_literal_expr(&mut self, name: &'arena str) -> Option<ExpressionKind<'arena>> {
         self.next_token(); // advance to LBrace
         
         let mut fields = vec![];
@@ -1066,7 +1567,7 @@ impl<'src, 'arena> Parser<'src, 'arena> {
             self.next_token(); // advance to field identifier
             
             let field_name = match &self.cur_token {
-                Token::Identifier(n) => n.clone(),
+                Token::Identifier(n) => n.to_string(),
                 _ => {
                     self.push_error(format!("Expected field name in struct '{name}', got {:?}", self.cur_token));
                     return None;
@@ -1086,7 +1587,7 @@ impl<'src, 'arena> Parser<'src, 'arena> {
         
         if !self.expect_peek(Token::RBrace) { return None; }
         
-        Some(Expression::StructLiteral { name, fields })
+        Some(ExpressionKind::StructLiteral { name, fields })
     }
 
     fn parse_expression_list(&mut self, end_token: Token) -> Option<Vec<Expression<'arena>>> {
@@ -1110,9 +1611,13 @@ impl<'src, 'arena> Parser<'src, 'arena> {
         Some(list)
     }
 
-    fn parse_if_expression(&mut self) -> Option<Expression<'arena>> {
+    fn parse_if_expression(&mut self) -> Option<ExpressionKind<'arena>> {
         self.next_token(); // skip if
         
+        if self.cur_token == Token::Let {
+            return self.parse_if_let();
+        }
+        
         let prev_allow = self.allow_struct_literal;
         self.allow_struct_literal = false;
         let condition = self.parse_expression(Precedence::Lowest, false)?;
@@ -1128,10 +1633,13 @@ impl<'src, 'arena> Parser<'src, 'arena> {
             // else if { ... } or else { ... }
             if self.peek_token == Token::If {
                 self.next_token(); // move to if
+                let start = self.cur_span;
                 let else_if = self.parse_if_expression()?;
+                let else_if = self.expression(else_if, start);
                 let alt = Statement::Block { 
-                    statements: vec![Statement::Expression { expression: else_if, line: self.lexer.line }],
+                    statements: vec![Statement::Expression { expression: else_if, line: self.lexer.line, span: Span::default() }],
                     line: self.lexer.line,
+                    span: Span::default(),
                 };
                 alternative = Some(alt);
             } else if self.expect_peek(Token::LBrace) {
@@ -1139,14 +1647,105 @@ impl<'src, 'arena> Parser<'src, 'arena> {
             }
         }
         
-        Some(Expression::If {
+        Some(ExpressionKind::If {
             condition: self.arena.alloc(condition),
             consequence: self.arena.alloc(consequence),
             alternative: alternative.map(|a| &*self.arena.alloc(a)),
         })
     }
 
-    fn parse_match_expression(&mut self) -> Option<Expression<'arena>> {
+    /// Parses the `let PATTERN = VALUE` head shared by `if let` and `while let`,
+    /// with `cur_token` on `let`. Leaves `cur_token` on the value's last token.
+    fn parse_let_pattern_head(&mut self) -> Option<(Expression<'arena>, Expression<'arena>)> {
+        self.next_token(); // skip 'let'
+
+        // A bare binding (`if let x = ...`) would otherwise be swallowed as an
+        // assignment by `parse_expression`; every other pattern shape stops at `=`.
+        let pattern = match (self.cur_token, self.peek_token) {
+            (Token::Identifier(name), Token::Equal) => Expression::new(ExpressionKind::Identifier(self.intern(name)), self.cur_span),
+            _ => self.parse_expression(Precedence::Lowest, false)?,
+        };
+        if !self.expect_peek(Token::Equal) { return None; }
+        self.next_token(); // advance to value
+
+        let prev_allow = self.allow_struct_literal;
+        self.allow_struct_literal = false;
+        let value = self.parse_expression(Precedence::Lowest, false)?;
+        self.allow_struct_literal = prev_allow;
+
+        Some((pattern, value))
+    }
+
+    /// `if let PAT = v { a } else { b }` desugars to
+    /// `match v { PAT => { a }, _ => { b } }` (an empty block when there is no `else`).
+    fn parse_if_let(&mut self) -> Option<ExpressionKind<'arena>> {
+        let (pattern, value) = self.parse_let_pattern_head()?;
+
+        if !self.expect_peek(Token::LBrace) { return None; }
+        let consequence = self.parse_block_statement()?;
+
+        let mut alternative = Statement::Block { statements: vec![], line: self.lexer.line, span: Span::default() };
+        if self.peek_token == Token::Else {
+            self.next_token();
+            if self.peek_token == Token::If {
+                self.next_token(); // move to if
+                let start = self.cur_span;
+                let else_if = self.parse_if_expression()?;
+                let else_if = self.expression(else_if, start);
+                alternative = Statement::Block {
+                    statements: vec![Statement::Expression { expression: else_if, line: self.lexer.line, span: Span::default() }],
+                    line: self.lexer.line,
+                    span: Span::default(),
+                };
+            } else if self.expect_peek(Token::LBrace) {
+                alternative = self.parse_block_statement()?;
+            }
+        }
+
+        Some(ExpressionKind::Match {
+            value: self.arena.alloc(value),
+            arms: vec![
+                (pattern, self.arena.alloc(consequence) as &'arena Statement<'arena>),
+                (Expression::synthetic(ExpressionKind::Identifier("_")), self.arena.alloc(alternative) as &'arena Statement<'arena>),
+            ],
+            by_type: false,
+            end: Span::default(),
+        })
+    }
+
+    /// `while let PAT = v { body }` desugars to
+    /// `while true { match v { PAT => { body }, _ => { break } } }`, so `v` is
+    /// re-evaluated each iteration and the loop ends on the first mismatch.
+    fn parse_while_let(&mut self, start_line: usize) -> Option<Statement<'arena>> {
+        let (pattern, value) = self.parse_let_pattern_head()?;
+
+        if !self.expect_peek(Token::LBrace) { return None; }
+        let body = self.parse_block_statement()?;
+
+        let exit = Statement::Block {
+            statements: vec![Statement::Break { label: None, line: start_line, span: Span::default() }],
+            line: start_line,
+            span: Span::default(),
+        };
+        let dispatch = Expression::synthetic(ExpressionKind::Match {
+            value: self.arena.alloc(value),
+            arms: vec![
+                (pattern, self.arena.alloc(body) as &'arena Statement<'arena>),
+                (Expression::synthetic(ExpressionKind::Identifier("_")), self.arena.alloc(exit) as &'arena Statement<'arena>),
+            ],
+            by_type: false,
+            end: Span::default(),
+        });
+        let loop_body = Statement::Block {
+            statements: vec![Statement::Expression { expression: dispatch, line: start_line, span: Span::default() }],
+            line: start_line,
+            span: Span::default(),
+        };
+
+        Some(Statement::While { condition: Expression::synthetic(ExpressionKind::Boolean(true)), body: self.arena.alloc(loop_body), label: None, line: start_line, span: Span::default() })
+    }
+
+    fn parse_match_expression(&mut self) -> Option<ExpressionKind<'arena>> {
         self.next_token(); // Skip 'match'
         
         let prev_allow = self.allow_struct_literal;
@@ -1154,12 +1753,21 @@ impl<'src, 'arena> Parser<'src, 'arena> {
         let value = self.parse_expression(Precedence::Lowest, false)?;
         self.allow_struct_literal = prev_allow;
         
+        let by_type = self.peek_token == Token::As;
+        if by_type {
+            self.next_token(); // consume 'as'
+        }
+        
         if !self.expect_peek(Token::LBrace) { return None; }
         self.next_token(); // now inside block
         
         let mut arms = vec![];
         while self.cur_token != Token::RBrace && self.cur_token != Token::EOF {
             let pattern = self.parse_expression(Precedence::Lowest, false)?;
+            if by_type && !is_type_arm_pattern(&pattern) {
+                self.push_error("Expected a type-switch arm: `type`, `type(name)` or `_`".to_string());
+                return None;
+            }
             if !self.expect_peek(Token::FatArrow) { return None; }
             self.next_token(); // advance to start of expression/statement
             
@@ -1174,13 +1782,16 @@ impl<'src, 'arena> Parser<'src, 'arena> {
             }
         }
         
-        Some(Expression::Match {
+        Some(ExpressionKind::Match {
             value: self.arena.alloc(value),
             arms,
+            by_type,
+            end: self.cur_span,
         })
     }
     
     fn parse_block_statement(&mut self) -> Option<Statement<'arena>> {
+        let start = self.cur_span;
         let mut statements = vec![];
         self.next_token();
         
@@ -1191,10 +1802,10 @@ impl<'src, 'arena> Parser<'src, 'arena> {
             self.next_token();
         }
         
-        Some(Statement::Block { statements, line: self.lexer.line })
+        Some(Statement::Block { statements, line: self.lexer.line, span: start.to(self.cur_span) })
     }
     
-    fn parse_function_literal(&mut self) -> Option<Expression<'arena>> {
+    fn parse_function_literal(&mut self) -> Option<ExpressionKind<'arena>> {
         if !self.expect_peek(Token::LParen) { return None; }
         let params = self.parse_function_params()?;
 
@@ -1203,14 +1814,14 @@ impl<'src, 'arena> Parser<'src, 'arena> {
             self.next_token(); // ->
             self.next_token(); // type
             if let Token::Identifier(t) = &self.cur_token {
-                return_type = t.clone();
+                return_type = t.to_string();
             }
         }
         
         if !self.expect_peek(Token::LBrace) { return None; }
         let body = self.parse_block_statement()?;
         
-        Some(Expression::FunctionLiteral { 
+        Some(ExpressionKind::FunctionLiteral { 
             parameters: params, body: self.arena.alloc(body), return_type 
         })
     }
@@ -1227,13 +1838,13 @@ impl<'src, 'arena> Parser<'src, 'arena> {
         
         let parse_one = |p: &mut Parser<'src, 'arena>| -> Option<(String, String)> {
             if let Token::Identifier(name) = &p.cur_token {
-                let name = name.clone();
+                let name = name.to_string();
                 let mut ty = "Any".to_string();
                 if p.peek_token == Token::Colon {
                     p.next_token();
                     p.next_token();
                     if let Token::Identifier(t) = &p.cur_token {
-                        ty = t.clone();
+                        ty = t.to_string();
                     }
                 }
                 Some((name, ty))
@@ -1288,11 +1899,40 @@ impl<'src, 'arena> Parser<'src, 'arena> {
     }
     
     pub fn push_error(&mut self, msg: String) {
-        self.errors.push(format!("Line {}: {}", self.lexer.line, msg));
+        self.error_on_line(self.lexer.line, msg);
+    }
+
+    fn error_on_line(&mut self, line: usize, msg: String) {
+        self.errors.push(Diagnostic::error(codes::PARSE, msg).at_line(line));
     }
 
+    /// Unlike `push_error`, knows which token is at fault, so the error
+    /// carries its column: `Line N:C: ...`.
     fn peek_error(&mut self, token: Token) {
-        self.push_error(format!("Expected next token to be {:?}, got {:?} instead", token, self.peek_token));
+        let msg = format!("Expected next token to be {:?}, got {:?} instead", token, self.peek_token);
+        self.errors.push(Diagnostic::error(codes::PARSE, msg).at(self.peek_span));
+    }
+}
+
+/// A `match x as { ... }` arm is a bare type name (`int`, `null`, `_`) or a type name
+/// binding the narrowed value (`int(n)`, `Point(p)`).
+fn is_type_arm_pattern(pattern: &Expression<'_>) -> bool {
+    match &pattern.kind {
+        ExpressionKind::Identifier(_) | ExpressionKind::Null => true,
+        ExpressionKind::Call { function, arguments } => {
+            matches!(function.kind, ExpressionKind::Identifier(_))
+                && matches!(arguments.as_slice(), [Expression { kind: ExpressionKind::Identifier(_), .. }])
+        }
+        _ => false,
+    }
+}
+
+/// Top-level statements, one at a time (see `Parser::next_statement`).
+impl<'arena> Iterator for Parser<'_, 'arena> {
+    type Item = Statement<'arena>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_statement()
     }
 }
 
@@ -1302,7 +1942,7 @@ mod tests {
     use crate::lexer::Lexer;
     use crate::ast::*;
     
-    fn parse(input: &str) -> (Bump, Vec<String>) {
+    fn parse(input: &str) -> (Bump, Vec<Diagnostic>) {
         let arena = Bump::new();
         // Safety: we transmute the lifetime so the program can outlive this function.
         // In tests only — the arena is returned alongside the data.
@@ -1338,12 +1978,28 @@ mod tests {
         assert_eq!(prog.statements.len(), 1);
         match &prog.statements[0] {
             Statement::Let { name, mutable, .. } => {
-                assert_eq!(name, "x");
+                assert_eq!(*name, "x");
                 assert!(!mutable);
             },
             _ => panic!("Expected Let statement"),
         }
     }
+
+    #[test]
+    fn test_names_are_interned_once() {
+        let arena = Bump::new();
+        let mut p = Parser::new(Lexer::new("let total = 1\nfn add(n: int) { total = total + n }\nlet label = \"total\""), &arena);
+        let prog = p.parse_program();
+        assert!(p.errors.is_empty(), "Parser errors: {:?}", p.errors);
+        let Statement::Let { name: declared, .. } = &prog.statements[0] else { panic!("Expected Let statement") };
+        let Statement::Function { body: Statement::Block { statements, .. }, .. } = &prog.statements[1] else { panic!("Expected Function statement") };
+        let Statement::Expression { expression: Expression { kind: ExpressionKind::Assign { target, value }, .. }, .. } = &statements[0] else { panic!("Expected assignment") };
+        let (Expression { kind: ExpressionKind::Identifier(target), .. }, Expression { kind: ExpressionKind::Infix { left: Expression { kind: ExpressionKind::Identifier(read), .. }, .. }, .. }) = (target, value) else { panic!("Expected total = total + n") };
+        let Statement::Let { value: Expression { kind: ExpressionKind::String(literal), .. }, .. } = &prog.statements[2] else { panic!("Expected Let statement") };
+        for other in [target, read, literal] {
+            assert!(std::ptr::eq(*declared, *other), "every `total` shares one arena copy");
+        }
+    }
     
     #[test]
     fn test_mut_statement() {
@@ -1355,7 +2011,7 @@ mod tests {
         assert_eq!(prog.statements.len(), 1);
         match &prog.statements[0] {
             Statement::Let { name, mutable, .. } => {
-                assert_eq!(name, "counter");
+                assert_eq!(*name, "counter");
                 assert!(mutable);
             },
             _ => panic!("Expected Let(mut) statement"),
@@ -1372,7 +2028,7 @@ mod tests {
         assert_eq!(prog.statements.len(), 1);
         match &prog.statements[0] {
             Statement::Function { name, parameters, return_type, .. } => {
-                assert_eq!(name, "add");
+                assert_eq!(*name, "add");
                 assert_eq!(parameters.len(), 2);
                 assert_eq!(parameters[0], ("a".to_string(), "int".to_string()));
                 assert_eq!(parameters[1], ("b".to_string(), "int".to_string()));
@@ -1387,6 +2043,81 @@ mod tests {
         assert_eq!(parse_and_check("while x > 0 { x = x - 1; }"), 1);
     }
     
+    #[test]
+    fn test_if_let_desugars_to_match() {
+        let arena = Bump::new();
+        let l = Lexer::new("if let Some(x) = opt { print(x); } else { print(0); }");
+        let mut p = Parser::new(l, &arena);
+        let prog = p.parse_program();
+        assert!(p.errors.is_empty(), "Parser errors: {:?}", p.errors);
+        match &prog.statements[0] {
+            Statement::Expression { expression: Expression { kind: ExpressionKind::Match { value, arms, .. }, .. }, .. } => {
+                assert!(matches!(&value.kind, ExpressionKind::Identifier(n) if *n == "opt"));
+                assert_eq!(arms.len(), 2);
+                assert!(matches!(&arms[0].0.kind, ExpressionKind::Call { .. }));
+                assert!(matches!(&arms[1].0.kind, ExpressionKind::Identifier(n) if *n == "_"));
+            },
+            other => panic!("Expected if-let to desugar to Match, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_while_let_desugars_to_loop_with_break_arm() {
+        let arena = Bump::new();
+        let l = Lexer::new("while let Some(item) = stack.pop() { print(item); }");
+        let mut p = Parser::new(l, &arena);
+        let prog = p.parse_program();
+        assert!(p.errors.is_empty(), "Parser errors: {:?}", p.errors);
+        let Statement::While { condition, body, .. } = &prog.statements[0] else {
+            panic!("Expected While statement");
+        };
+        assert!(matches!(&condition.kind, ExpressionKind::Boolean(true)));
+        let Statement::Block { statements, .. } = body else { panic!("Expected loop body block") };
+        match &statements[0] {
+            Statement::Expression { expression: Expression { kind: ExpressionKind::Match { arms, .. }, .. }, .. } => {
+                let Statement::Block { statements: exit, .. } = arms[1].1 else { panic!("Expected fallback block") };
+                assert!(matches!(exit[0], Statement::Break { .. }), "the fallback arm should leave the loop");
+            },
+            other => panic!("Expected Match inside loop body, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_labeled_loop_and_jumps() {
+        let arena = Bump::new();
+        let l = Lexer::new("'outer: for i in items { while true { break 'outer; } continue; }");
+        let mut p = Parser::new(l, &arena);
+        let prog = p.parse_program();
+        assert!(p.errors.is_empty(), "Parser errors: {:?}", p.errors);
+        let Statement::For { label, body, .. } = &prog.statements[0] else { panic!("Expected For statement") };
+        assert_eq!(label.as_deref(), Some("outer"));
+        let Statement::Block { statements, .. } = body else { panic!("Expected loop body block") };
+        let Statement::While { label: inner, body: inner_body, .. } = &statements[0] else { panic!("Expected inner While") };
+        assert!(inner.is_none());
+        let Statement::Block { statements: inner_stmts, .. } = inner_body else { panic!("Expected inner body block") };
+        assert!(matches!(&inner_stmts[0], Statement::Break { label: Some(l), .. } if *l == "outer"));
+        assert!(matches!(&statements[1], Statement::Continue { label: None, .. }));
+    }
+
+    #[test]
+    fn test_match_as_type_switch() {
+        let arena = Bump::new();
+        let l = Lexer::new("match v as { int(n) => n, null => 0, _ => 1 }");
+        let mut p = Parser::new(l, &arena);
+        let prog = p.parse_program();
+        assert!(p.errors.is_empty(), "Parser errors: {:?}", p.errors);
+        let Statement::Expression { expression: Expression { kind: ExpressionKind::Match { arms, by_type, .. }, .. }, .. } = &prog.statements[0] else {
+            panic!("Expected Match expression");
+        };
+        assert!(*by_type);
+        assert_eq!(arms.len(), 3);
+
+        let l = Lexer::new("match v as { 1 => 0 }");
+        let mut p = Parser::new(l, &arena);
+        p.parse_program();
+        assert!(!p.errors.is_empty(), "a literal is not a type-switch arm");
+    }
+
     #[test]
     fn test_for_loop() {
         let arena = Bump::new();
@@ -1396,7 +2127,7 @@ mod tests {
         assert!(p.errors.is_empty(), "Parser errors: {:?}", p.errors);
         assert_eq!(prog.statements.len(), 1);
         match &prog.statements[0] {
-            Statement::For { iterator, .. } => assert_eq!(iterator, "i"),
+            Statement::For { iterator, .. } => assert_eq!(*iterator, "i"),
             _ => panic!("Expected For statement"),
         }
     }
@@ -1411,7 +2142,7 @@ mod tests {
         assert_eq!(prog.statements.len(), 1);
         match &prog.statements[0] {
             Statement::Struct { name, fields, .. } => {
-                assert_eq!(name, "Vector2");
+                assert_eq!(*name, "Vector2");
                 assert_eq!(fields.len(), 2);
                 assert_eq!(fields[0], ("x".to_string(), "float".to_string()));
                 assert_eq!(fields[1], ("y".to_string(), "float".to_string()));
@@ -1430,13 +2161,131 @@ mod tests {
         assert_eq!(prog.statements.len(), 1);
         match &prog.statements[0] {
             Statement::Class { name, methods, .. } => {
-                assert_eq!(name, "Player");
+                assert_eq!(*name, "Player");
                 assert_eq!(methods.len(), 1);
             },
             _ => panic!("Expected Class"),
         }
     }
-    
+
+    #[test]
+    fn test_class_constants_and_associated_fn() {
+        let arena = Bump::new();
+        let l = Lexer::new("class Config { let MAX: int = 10\n let NAME = \"cfg\"; fn default() { } pub port: int }");
+        let mut p = Parser::new(l, &arena);
+        let prog = p.parse_program();
+        assert!(p.errors.is_empty(), "Parser errors: {:?}", p.errors);
+        let Statement::Class { methods, fields, constants, .. } = &prog.statements[0] else { panic!("Expected Class") };
+        assert_eq!(constants.len(), 2);
+        assert!(matches!(&constants[0], Statement::Let { name, type_hint: Some(t), .. } if *name == "MAX" && t == "int"));
+        assert_eq!(methods.len(), 1);
+        assert_eq!(fields.len(), 1);
+    }
+
+    #[test]
+    fn test_class_const_is_rejected_at_its_keyword() {
+        let arena = Bump::new();
+        let source = "class Config {\n    const MAX = 10\n    let NAME = \"cfg\"\n}";
+        let mut p = Parser::new(Lexer::new(source), &arena);
+        let prog = p.parse_program();
+        assert_eq!(p.errors.len(), 1, "{:?}", p.errors);
+        assert_eq!(p.errors[0].code, codes::PARSE);
+        let span = p.errors[0].span().expect("placed at `const`");
+        assert_eq!((span.line, span.column, span.text(source)), (2, 5, "const"));
+        let Statement::Class { constants, .. } = &prog.statements[0] else { panic!("Expected Class") };
+        assert!(matches!(&constants[..], [Statement::Let { name, .. }] if *name == "NAME"));
+    }
+
+    #[test]
+    fn test_import_bundle() {
+        let arena = Bump::new();
+        let mut p = Parser::new(Lexer::new("import bundle \"lib/mathlib.exki\"\nprintln(square(3))"), &arena);
+        let prog = p.parse_program();
+        assert!(p.errors.is_empty(), "Parser errors: {:?}", p.errors);
+        assert_eq!(prog.statements.len(), 2);
+        assert!(matches!(&prog.statements[0], Statement::ImportBundle { path, line: 1, .. } if path == "lib/mathlib.exki"));
+
+        let mut p = Parser::new(Lexer::new("import \"mathlib.exki\""), &arena);
+        p.parse_program();
+        assert!(!p.errors.is_empty(), "import without `bundle` should be rejected");
+    }
+
+    #[test]
+    fn test_import_module() {
+        let arena = Bump::new();
+        let mut p = Parser::new(Lexer::new("import util.math as m\nimport strings\nprintln(m.square(3))"), &arena);
+        let prog = p.parse_program();
+        assert!(p.errors.is_empty(), "Parser errors: {:?}", p.errors);
+        assert_eq!(prog.statements.len(), 3);
+        assert!(matches!(&prog.statements[0], Statement::Import { module, alias: Some(a), line: 1, .. } if module == "util.math" && a == "m"));
+        assert!(matches!(&prog.statements[1], Statement::Import { module, alias: None, line: 2, .. } if module == "strings"));
+
+        let mut p = Parser::new(Lexer::new("import util. as m"), &arena);
+        p.parse_program();
+        assert!(!p.errors.is_empty(), "a dangling '.' should be rejected");
+    }
+
+    #[test]
+    fn test_statement_spans() {
+        let arena = Bump::new();
+        let source = "let x = 1\nfn f(a: int) -> int {\n    return a + x\n}\nclass P {\n    fn get() -> int { return 1 }\n}";
+        let mut p = Parser::new(Lexer::new(source), &arena);
+        let prog = p.parse_program();
+        assert!(p.errors.is_empty(), "Parser errors: {:?}", p.errors);
+        assert_eq!(prog.statements[0].span().text(source), "let x = 1");
+        let function = &prog.statements[1];
+        assert_eq!(function.span().text(source), "fn f(a: int) -> int {\n    return a + x\n}");
+        let Statement::Function { body, .. } = function else { panic!("expected a function") };
+        let Statement::Block { statements, .. } = body else { panic!("expected a block") };
+        assert_eq!(statements[0].span().text(source), "return a + x");
+        assert_eq!(statements[0].span().to_string(), "3:5");
+        let Statement::Class { methods, .. } = &prog.statements[2] else { panic!("expected a class") };
+        assert_eq!(methods[0].span().text(source), "fn get() -> int { return 1 }");
+
+        let mut p = Parser::new(Lexer::new("let a = [1, 2\nlet b = 3"), &arena);
+        p.parse_program();
+        assert!(p.errors[0].to_string().starts_with("Line 2:1: Expected next token to be RBracket"), "{:?}", p.errors);
+        assert_eq!((p.errors[0].code, p.errors[0].span().map(|s| s.text("let a = [1, 2\nlet b = 3"))), (codes::PARSE, Some("let")));
+    }
+
+    #[test]
+    fn test_expression_spans() {
+        let arena = Bump::new();
+        let source = "let total = (a.b + f(1, [2]))\ntotal = -total * 2\nprint \"x\"\n";
+        let mut p = Parser::new(Lexer::new(source), &arena);
+        let prog = p.parse_program();
+        assert!(p.errors.is_empty(), "Parser errors: {:?}", p.errors);
+        let Statement::Let { value, .. } = &prog.statements[0] else { panic!("expected a let") };
+        assert_eq!(value.span.text(source), "a.b + f(1, [2])");
+        let ExpressionKind::Infix { left, right, .. } = &value.kind else { panic!("expected an infix") };
+        assert_eq!((left.span.text(source), right.span.text(source)), ("a.b", "f(1, [2])"));
+        let ExpressionKind::Call { arguments, .. } = &right.kind else { panic!("expected a call") };
+        assert_eq!(arguments[1].span.text(source), "[2]");
+
+        let Statement::Expression { expression, .. } = &prog.statements[1] else { panic!("expected an assignment") };
+        assert_eq!(expression.span.text(source), "total = -total * 2");
+        let ExpressionKind::Assign { value, .. } = &expression.kind else { panic!("expected an assignment") };
+        let ExpressionKind::Infix { left, .. } = &value.kind else { panic!("expected an infix") };
+        assert_eq!((left.span.text(source), left.span.to_string().as_str()), ("-total", "2:9"));
+
+        let Statement::Expression { expression, .. } = &prog.statements[2] else { panic!("expected a call") };
+        assert_eq!(expression.span.text(source), "print \"x\"");
+    }
+
+    #[test]
+    fn test_pub_fn_is_exported() {
+        let arena = Bump::new();
+        let mut p = Parser::new(Lexer::new("pub fn square(x: int) -> int { return x * x }\nfn helper() { }"), &arena);
+        let prog = p.parse_program();
+        assert!(p.errors.is_empty(), "Parser errors: {:?}", p.errors);
+        assert!(matches!(&prog.statements[0], Statement::Function { name, public: true, .. } if *name == "square"));
+        assert!(matches!(&prog.statements[1], Statement::Function { public: false, .. }));
+
+        let mut p = Parser::new(Lexer::new("pub let x = 1"), &arena);
+        p.parse_program();
+        assert!(!p.errors.is_empty(), "only functions can be `pub` at top level");
+    }
+
     #[test]
     fn test_member_access() {
         let arena = Bump::new();
@@ -1447,7 +2296,7 @@ mod tests {
         assert_eq!(prog.statements.len(), 1);
         match &prog.statements[0] {
             Statement::Expression { expression, .. } => {
-                assert!(matches!(expression, Expression::MemberAccess { .. }));
+                assert!(matches!(&expression.kind, ExpressionKind::MemberAccess { .. }));
             },
             _ => panic!("Expected expression"),
         }
@@ -1463,8 +2312,8 @@ mod tests {
         assert_eq!(prog.statements.len(), 1);
         match &prog.statements[0] {
             Statement::Let { value, .. } => {
-                match value {
-                    Expression::ArrayLiteral(elems) => assert_eq!(elems.len(), 3),
+                match &value.kind {
+                    ExpressionKind::ArrayLiteral(elems) => assert_eq!(elems.len(), 3),
                     _ => panic!("Expected array literal"),
                 }         
             },
@@ -1481,7 +2330,7 @@ mod tests {
         assert!(p.errors.is_empty(), "Parser errors: {:?}", p.errors);
         assert_eq!(prog.statements.len(), 1);
         match &prog.statements[0] {
-            Statement::Let { name, .. } => assert_eq!(name, "x"),
+            Statement::Let { name, .. } => assert_eq!(*name, "x"),
             _ => panic!("Expected let"),
         }
     }
@@ -1499,29 +2348,29 @@ mod tests {
         match &prog.statements[0] {
             Statement::Let { type_hint, value, .. } => {
                 assert_eq!(type_hint.as_deref().unwrap(), "int");
-                assert!(matches!(value, Expression::Integer(0)));
+                assert!(matches!(&value.kind, ExpressionKind::Integer(0)));
             }
             _ => panic!("Expected Let"),
         }
         match &prog.statements[1] {
             Statement::Let { type_hint, value, .. } => {
                 assert_eq!(type_hint.as_deref().unwrap(), "float");
-                assert!(matches!(value, Expression::Float(f) if *f == 0.0));
+                assert!(matches!(&value.kind, ExpressionKind::Float(f) if *f == 0.0));
             }
             _ => panic!("Expected Let"),
         }
         match &prog.statements[2] {
             Statement::Let { type_hint, value, .. } => {
                 assert_eq!(type_hint.as_deref().unwrap(), "bool");
-                assert!(matches!(value, Expression::Boolean(false)));
+                assert!(matches!(&value.kind, ExpressionKind::Boolean(false)));
             }
             _ => panic!("Expected Let"),
         }
         match &prog.statements[3] {
             Statement::Let { type_hint, value, .. } => {
                 assert_eq!(type_hint.as_deref().unwrap(), "string");
-                match value {
-                    Expression::String(s) => assert_eq!(s, ""),
+                match &value.kind {
+                    ExpressionKind::String(s) => assert_eq!(*s, ""),
                     _ => panic!("Expected String"),
                 }
             }
@@ -1652,15 +2501,15 @@ mod tests {
         // 1. print "Hello" -> Call(print, ["Hello"])
         match &prog.statements[0] {
             Statement::Expression { expression, .. } => {
-                match expression {
-                    Expression::Call { function, arguments } => {
-                        match &**function {
-                            Expression::Identifier(name) => assert_eq!(name, "print"),
+                match &expression.kind {
+                    ExpressionKind::Call { function, arguments } => {
+                        match &function.kind {
+                            ExpressionKind::Identifier(name) => assert_eq!(*name, "print"),
                             _ => panic!("Expected identifier 'print'"),
                         }
                         assert_eq!(arguments.len(), 1);
-                        match &arguments[0] {
-                            Expression::String(s) => assert_eq!(s, "Hello"),
+                        match &arguments[0].kind {
+                            ExpressionKind::String(s) => assert_eq!(*s, "Hello"),
                             _ => panic!("Expected string argument"),
                         }
                     },
@@ -1673,14 +2522,14 @@ mod tests {
         // 2. let x = len [1, 2, 3] -> Let(x, Call(len, [ArrayLiteral]))
         match &prog.statements[1] {
             Statement::Let { value, .. } => {
-                match value {
-                    Expression::Call { function, arguments } => {
-                        match &**function {
-                            Expression::Identifier(name) => assert_eq!(name, "len"),
+                match &value.kind {
+                    ExpressionKind::Call { function, arguments } => {
+                        match &function.kind {
+                            ExpressionKind::Identifier(name) => assert_eq!(*name, "len"),
                             _ => panic!("Expected identifier 'len'"),
                         }
                         assert_eq!(arguments.len(), 1);
-                        assert!(matches!(&arguments[0], Expression::ArrayLiteral(_)));
+                        assert!(matches!(&arguments[0].kind, ExpressionKind::ArrayLiteral(_)));
                     },
                     _ => panic!("Expected call expression"),
                 }
@@ -1691,19 +2540,19 @@ mod tests {
         // 3. foo bar baz -> Call(foo, [bar, baz])
         match &prog.statements[2] {
             Statement::Expression { expression, .. } => {
-                match expression {
-                    Expression::Call { function, arguments } => {
-                        match &**function {
-                            Expression::Identifier(name) => assert_eq!(name, "foo"),
+                match &expression.kind {
+                    ExpressionKind::Call { function, arguments } => {
+                        match &function.kind {
+                            ExpressionKind::Identifier(name) => assert_eq!(*name, "foo"),
                             _ => panic!("Expected identifier 'foo'"),
                         }
                         assert_eq!(arguments.len(), 2);
-                        match &arguments[0] {
-                            Expression::Identifier(name) => assert_eq!(name, "bar"),
+                        match &arguments[0].kind {
+                            ExpressionKind::Identifier(name) => assert_eq!(*name, "bar"),
                             _ => panic!("Expected identifier 'bar'"),
                         }
-                        match &arguments[1] {
-                            Expression::Identifier(name) => assert_eq!(name, "baz"),
+                        match &arguments[1].kind {
+                            ExpressionKind::Identifier(name) => assert_eq!(*name, "baz"),
                             _ => panic!("Expected identifier 'baz'"),
                         }
                     },
@@ -1726,21 +2575,21 @@ mod tests {
         // 1. let files = `ls -la` -> Let(files, Call(system.exec, ["ls -la"]))
         match &prog.statements[0] {
             Statement::Let { value, .. } => {
-                match value {
-                    Expression::Call { function, arguments } => {
-                        match &**function {
-                            Expression::MemberAccess { object, member } => {
-                                match &**object {
-                                    Expression::Identifier(name) => assert_eq!(name, "system"),
+                match &value.kind {
+                    ExpressionKind::Call { function, arguments } => {
+                        match &function.kind {
+                            ExpressionKind::MemberAccess { object, member } => {
+                                match &object.kind {
+                                    ExpressionKind::Identifier(name) => assert_eq!(*name, "system"),
                                     _ => panic!("Expected identifier 'system'"),
                                 }
-                                assert_eq!(member, "exec");
+                                assert_eq!(*member, "exec");
                             },
                             _ => panic!("Expected MemberAccess"),
                         }
                         assert_eq!(arguments.len(), 1);
-                        match &arguments[0] {
-                            Expression::String(s) => assert_eq!(s, "ls -la"),
+                        match &arguments[0].kind {
+                            ExpressionKind::String(s) => assert_eq!(*s, "ls -la"),
                             _ => panic!("Expected string argument"),
                         }
                     },
@@ -1753,21 +2602,21 @@ mod tests {
         // 2. `echo hello` -> Expression(Call(system.exec, ["echo hello"]))
         match &prog.statements[1] {
             Statement::Expression { expression, .. } => {
-                match expression {
-                    Expression::Call { function, arguments } => {
-                        match &**function {
-                            Expression::MemberAccess { object, member } => {
-                                match &**object {
-                                    Expression::Identifier(name) => assert_eq!(name, "system"),
+                match &expression.kind {
+                    ExpressionKind::Call { function, arguments } => {
+                        match &function.kind {
+                            ExpressionKind::MemberAccess { object, member } => {
+                                match &object.kind {
+                                    ExpressionKind::Identifier(name) => assert_eq!(*name, "system"),
                                     _ => panic!("Expected identifier 'system'"),
                                 }
-                                assert_eq!(member, "exec");
+                                assert_eq!(*member, "exec");
                             },
                             _ => panic!("Expected MemberAccess"),
                         }
                         assert_eq!(arguments.len(), 1);
-                        match &arguments[0] {
-                            Expression::String(s) => assert_eq!(s, "echo hello"),
+                        match &arguments[0].kind {
+                            ExpressionKind::String(s) => assert_eq!(*s, "echo hello"),
                             _ => panic!("Expected string argument"),
                         }
                     },
@@ -1777,4 +2626,93 @@ mod tests {
             _ => panic!("Expected Expression statement"),
         }
     }
+
+    #[test]
+    fn test_statements_stream_one_at_a_time() {
+        let arena = Bump::new();
+        let first_two: Vec<Statement> = {
+            let mut p = Parser::new(Lexer::new("fn a() {}\nlet b = 1\nlet = = broken"), &arena);
+            let stmts = p.by_ref().take(2).collect();
+            assert!(p.errors.is_empty(), "nothing past the second statement is parsed: {:?}", p.errors);
+            stmts
+        };
+        assert!(matches!(&first_two[0], Statement::Function { name, .. } if *name == "a"));
+        assert!(matches!(&first_two[1], Statement::Let { name, .. } if *name == "b"));
+
+        let mut p = Parser::new(Lexer::new("let x = 1\nlet = 2\nlet y = 3"), &arena);
+        let names: Vec<&str> = p.by_ref()
+            .filter_map(|s| match s { Statement::Let { name, .. } => Some(name), _ => None })
+            .collect();
+        assert_eq!(names, ["x", "y"]);
+        assert!(!p.errors.is_empty());
+    }
+
+    #[test]
+    fn test_cfg_if_keeps_only_the_taken_branch() {
+        let src = "#if os(plan9)\nlet a = 1\n#elif feature(\"debug\")\nlet b = 2\n#if not(arch(x86_64))\nlet c = 3\n#endif\n#else\n#include \"missing.kix\"\n#endif\nfn f() {\n#if any(feature(debug), os(plan9))\nlet d = 4\n#else\nlet e = 5\n#endif\n}";
+        let arena = Bump::new();
+        let names = |flags: &[&str]| -> Vec<String> {
+            let mut p = Parser::new(Lexer::new(src), &arena);
+            p.cfg = Cfg::from_flags(&flags.iter().map(|f| f.to_string()).collect::<Vec<_>>()).unwrap();
+            p.cfg.arch = "x86_64".to_string();
+            let prog = p.parse_program();
+            assert!(p.errors.is_empty(), "Parser errors: {:?}", p.errors);
+            let mut names = Vec::new();
+            for stmt in &prog.statements {
+                match stmt {
+                    Statement::Let { name, .. } => names.push(name.to_string()),
+                    Statement::Include { path, .. } => names.push(path.clone()),
+                    Statement::Function { body: Statement::Block { statements, .. }, .. } => {
+                        names.extend(statements.iter().filter_map(|s| match s { Statement::Let { name, .. } => Some(name.to_string()), _ => None }));
+                    }
+                    _ => {}
+                }
+            }
+            names
+        };
+        assert_eq!(names(&["debug"]), ["b", "d"]);
+        assert_eq!(names(&[]), ["missing.kix", "e"]);
+        assert_eq!(names(&["os=plan9", "debug"]), ["a", "d"]);
+
+        let mut p = Parser::new(Lexer::new("#if os(linux)\nlet a = 1\n#endif\n#endif\n#if wat(x)\n"), &arena);
+        p.parse_program();
+        assert_eq!(p.errors.len(), 3, "{:?}", p.errors);
+    }
+
+    #[test]
+    fn test_define_substitutes_constants_at_each_use() {
+        let arena = Bump::new();
+        let src = "#define ENV \"dev\"\n#define MAX -3\nlet a = ENV\nlet b = cfg.MAX\n#undef MAX\nlet c = MAX\n";
+        let mut p = Parser::new(Lexer::new(src), &arena);
+        p.cfg.define("ENV=prod").unwrap();
+        let prog = p.parse_program();
+        assert!(p.errors.is_empty(), "Parser errors: {:?}", p.errors);
+        assert!(matches!(&prog.statements[0], Statement::Let { value: Expression { kind: ExpressionKind::String("prod"), .. }, line: 3, .. }));
+        assert!(matches!(&prog.statements[1], Statement::Let { value: Expression { kind: ExpressionKind::MemberAccess { member: "MAX", .. }, .. }, .. }));
+        assert!(matches!(&prog.statements[2], Statement::Let { value: Expression { kind: ExpressionKind::Identifier("MAX"), .. }, .. }));
+
+        let mut p = Parser::new(Lexer::new("#define N 1\nfn N() {}\n#define N 2\n#define M x\n"), &arena);
+        p.parse_program();
+        assert_eq!(p.errors.len(), 3, "{:?}", p.errors);
+        assert!(p.errors[1].to_string().starts_with("Line 3: #define N conflicts with its #define on line 1"), "{:?}", p.errors);
+    }
+
+    #[test]
+    fn test_blocks_are_collected_apart_from_statements() {
+        let arena = Bump::new();
+        let src = "fn add(a: int, b: int) -> int { return a + b }\ntest \"adds\" {\n  assert(add(1, 2) == 3)\n}\ntest \"x\"\nlet test = 1\n";
+        let mut p = Parser::new(Lexer::new(src), &arena);
+        let prog = p.parse_program();
+        assert!(p.errors.is_empty(), "Parser errors: {:?}", p.errors);
+        assert_eq!(prog.tests.len(), 1);
+        assert_eq!((prog.tests[0].name.as_str(), prog.tests[0].line), ("adds", 2));
+        assert!(matches!(prog.tests[0].body, Statement::Block { statements, .. } if statements.len() == 1));
+        // `test "x"` without a body is an ordinary call, `test` an ordinary name
+        assert_eq!(prog.statements.len(), 3);
+
+        let mut p = Parser::new(Lexer::new("fn f() {\n  test \"inner\" {}\n}\n"), &arena);
+        p.parse_program();
+        assert_eq!(p.errors.len(), 1, "{:?}", p.errors);
+        assert!(p.errors[0].message.contains("only allowed at the top level"), "{:?}", p.errors);
+    }
 }
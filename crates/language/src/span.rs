@@ -0,0 +1,58 @@
+//! Source locations.
+//!
+//! The lexer gives every token a `Span`; the parser gives every statement and
+//! expression the span from its first token to its last, and HIR lowering
+//! carries the statement spans over.
+
+use serde::Serialize;
+use std::fmt;
+
+/// A range of source text: byte offsets `start..end`, plus the line and
+/// column (1-based, in characters) where it starts. `Span::default()` marks
+/// a node with no source text, such as one the compiler synthesized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    /// From the start of `self` to the end of `other`.
+    pub fn to(self, other: Span) -> Span {
+        Span { end: other.end.max(self.start), ..self }
+    }
+
+    /// The text `self` covers in `source`.
+    pub fn text(self, source: &str) -> &str {
+        source.get(self.start..self.end).unwrap_or("")
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// A value and where it came from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Spanned<T> {
+    pub node: T,
+    pub span: Span,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spans_slice_and_join() {
+        let source = "let x = 1\nprintln(x)";
+        let span = Span { start: 10, end: 17, line: 2, column: 1 };
+        assert_eq!(span.text(source), "println");
+        assert_eq!(span.to(Span { start: 18, end: 19, line: 2, column: 9 }).text(source), "println(x");
+        assert_eq!(span.to_string(), "2:1");
+    }
+}